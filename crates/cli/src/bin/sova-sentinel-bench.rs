@@ -0,0 +1,301 @@
+//! Load generator for capacity planning. Plays synthetic blocks against a sentinel: each block
+//! locks new slots, queries the status of the slots locked the block before and unlocks the slots
+//! locked `--unlock-after` blocks before, then latency percentiles are reported per call.
+
+use anyhow::{Context, Result};
+use clap::Parser;
+use futures::stream::{self, StreamExt};
+use sova_sentinel_client::SlotLockClient;
+use sova_sentinel_proto::proto::{SlotData, SlotIdentifier};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Size of an EVM storage slot, which the sentinel pads slot indexes to
+const SLOT_INDEX_LEN: usize = 32;
+
+#[derive(Parser)]
+#[command(
+    name = "sova-sentinel-bench",
+    version,
+    about = "Generate synthetic lock, status and unlock traffic against a sentinel"
+)]
+struct Args {
+    /// Address of the sentinel's gRPC server
+    #[arg(long, env = "SOVA_SENTINEL_ADDR", default_value = "http://[::1]:50051")]
+    addr: String,
+    /// Namespace to lock slots in, the default namespace when unset
+    #[arg(long, default_value = "")]
+    namespace: String,
+    /// Bearer token sent with every call, for listeners that require one
+    #[arg(long, env = "SOVA_SENTINEL_AUTH_TOKEN", hide_env_values = true)]
+    auth_token: Option<String>,
+    /// Number of blocks to play
+    #[arg(long, default_value_t = 100)]
+    blocks: u64,
+    /// Slots locked in each block
+    #[arg(long, default_value_t = 100)]
+    slots_per_block: usize,
+    /// Most slots per batch call
+    #[arg(long, default_value_t = 50)]
+    batch_size: usize,
+    /// Batch calls of a block in flight at once
+    #[arg(long, default_value_t = 4)]
+    concurrency: usize,
+    /// Time between the starts of two blocks, 0 to play them back to back
+    #[arg(long, default_value_t = 1000)]
+    block_interval_ms: u64,
+    /// Blocks after which the slots of a block are unlocked
+    #[arg(long, default_value_t = 6)]
+    unlock_after: u64,
+    /// Sova block of the first block played
+    #[arg(long, default_value_t = 1)]
+    start_block: u64,
+    /// Bitcoin block sent with every call
+    #[arg(long, default_value_t = 100)]
+    btc_block: u64,
+    /// Contract the slots belong to, a fresh one per run when unset
+    #[arg(long)]
+    contract_address: Option<String>,
+}
+
+/// Latencies of one kind of call
+#[derive(Default)]
+struct Latencies {
+    samples: Vec<Duration>,
+    slots: usize,
+    errors: usize,
+}
+
+impl Latencies {
+    fn record(&mut self, latency: Duration, slots: usize, result: Result<(), String>) {
+        self.samples.push(latency);
+        match result {
+            Ok(()) => self.slots += slots,
+            Err(e) => {
+                if self.errors == 0 {
+                    eprintln!("First error: {}", e);
+                }
+                self.errors += 1;
+            }
+        }
+    }
+
+    /// Prints a line of the report, with `elapsed` the duration of the run
+    fn report(&mut self, call: &str, elapsed: Duration) {
+        self.samples.sort();
+        println!(
+            "call={} calls={} errors={} slots={} slots_per_sec={:.1} p50_ms={:.2} p90_ms={:.2} \
+             p99_ms={:.2} max_ms={:.2}",
+            call,
+            self.samples.len(),
+            self.errors,
+            self.slots,
+            self.slots as f64 / elapsed.as_secs_f64(),
+            millis(percentile(&self.samples, 50.0)),
+            millis(percentile(&self.samples, 90.0)),
+            millis(percentile(&self.samples, 99.0)),
+            millis(self.samples.last().copied()),
+        );
+    }
+}
+
+/// Returns the nearest-rank percentile of sorted samples, `None` if there are none
+fn percentile(sorted: &[Duration], percentile: f64) -> Option<Duration> {
+    let rank = (percentile / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted.get(rank.clamp(1, sorted.len().max(1)) - 1).copied()
+}
+
+fn millis(latency: Option<Duration>) -> f64 {
+    latency.map_or(0.0, |latency| latency.as_secs_f64() * 1000.0)
+}
+
+fn slot_index(index: u64) -> Vec<u8> {
+    let mut slot_index = vec![0; SLOT_INDEX_LEN];
+    slot_index[SLOT_INDEX_LEN - 8..].copy_from_slice(&index.to_be_bytes());
+    slot_index
+}
+
+/// Sends `batches` with at most `concurrency` in flight, recording each call's latency
+async fn run_batches<T, F>(
+    batches: Vec<Vec<T>>,
+    concurrency: usize,
+    latencies: &mut Latencies,
+    call: impl Fn(Vec<T>) -> F,
+) where
+    F: std::future::Future<Output = Result<(), String>>,
+{
+    let results = stream::iter(batches)
+        .map(|batch| {
+            let slots = batch.len();
+            let call = call(batch);
+            async move {
+                let started = Instant::now();
+                let result = call.await;
+                (started.elapsed(), slots, result)
+            }
+        })
+        .buffer_unordered(concurrency.max(1))
+        .collect::<Vec<_>>()
+        .await;
+    for (latency, slots, result) in results {
+        latencies.record(latency, slots, result);
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+    if args.slots_per_block == 0 || args.batch_size == 0 {
+        anyhow::bail!("--slots-per-block and --batch-size must be positive");
+    }
+    let client = SlotLockClient::connect(args.addr.clone())
+        .await
+        .with_context(|| format!("Failed to connect to {}", args.addr))?
+        .with_namespace(args.namespace.clone());
+    let client = match &args.auth_token {
+        Some(token) => client
+            .with_auth_token(token)
+            .context("Invalid auth token")?,
+        None => client,
+    };
+    let contract_address = args.contract_address.clone().unwrap_or_else(|| {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        format!("0x{:040x}", nanos)
+    });
+    println!(
+        "addr={} contract_address={} blocks={} slots_per_block={} batch_size={} concurrency={}",
+        args.addr,
+        contract_address,
+        args.blocks,
+        args.slots_per_block,
+        args.batch_size,
+        args.concurrency
+    );
+
+    let mut locks = Latencies::default();
+    let mut statuses = Latencies::default();
+    let mut unlocks = Latencies::default();
+    // Slots of the blocks played, newest last, until they are unlocked
+    let mut locked: VecDeque<Vec<SlotIdentifier>> = VecDeque::new();
+    let interval = Duration::from_millis(args.block_interval_ms);
+    let started = Instant::now();
+
+    for played in 0..args.blocks {
+        let block_started = Instant::now();
+        let block = args.start_block + played;
+        let first_index = played * args.slots_per_block as u64;
+        let slots: Vec<SlotData> = (0..args.slots_per_block as u64)
+            .map(|i| SlotData {
+                contract_address: contract_address.clone(),
+                slot_index: slot_index(first_index + i),
+                revert_value: vec![0],
+                current_value: vec![1],
+                // Unique per block, and never confirmed on a real node
+                btc_txid: format!("{:064x}", block),
+                ..Default::default()
+            })
+            .collect();
+        let identifiers = slots
+            .iter()
+            .map(|slot| SlotIdentifier {
+                contract_address: slot.contract_address.clone(),
+                slot_index: slot.slot_index.clone(),
+            })
+            .collect::<Vec<_>>();
+
+        let batches = slots
+            .chunks(args.batch_size)
+            .map(<[SlotData]>::to_vec)
+            .collect();
+        run_batches(batches, args.concurrency, &mut locks, |batch| {
+            let client = client.clone();
+            async move {
+                client
+                    .batch_lock_slot(block, args.btc_block, batch)
+                    .await
+                    .map(|_| ())
+                    .map_err(|e| e.to_string())
+            }
+        })
+        .await;
+
+        if let Some(previous) = locked.back() {
+            let batches = previous
+                .chunks(args.batch_size)
+                .map(<[SlotIdentifier]>::to_vec)
+                .collect();
+            run_batches(batches, args.concurrency, &mut statuses, |batch| {
+                let client = client.clone();
+                async move {
+                    client
+                        .batch_get_slot_status(block, args.btc_block, batch)
+                        .await
+                        .map(|_| ())
+                        .map_err(|e| e.to_string())
+                }
+            })
+            .await;
+        }
+
+        locked.push_back(identifiers);
+        if locked.len() as u64 > args.unlock_after {
+            let expired = locked.pop_front().unwrap_or_default();
+            let batches = expired
+                .chunks(args.batch_size)
+                .map(<[SlotIdentifier]>::to_vec)
+                .collect();
+            run_batches(batches, args.concurrency, &mut unlocks, |batch| {
+                let client = client.clone();
+                async move {
+                    client
+                        .batch_unlock_slot(block, args.btc_block, batch)
+                        .await
+                        .map(|_| ())
+                        .map_err(|e| e.to_string())
+                }
+            })
+            .await;
+        }
+
+        if let Some(rest) = interval.checked_sub(block_started.elapsed()) {
+            tokio::time::sleep(rest).await;
+        }
+    }
+
+    let elapsed = started.elapsed();
+    locks.report("BatchLockSlot", elapsed);
+    statuses.report("BatchGetSlotStatus", elapsed);
+    unlocks.report("BatchUnlockSlot", elapsed);
+    println!("elapsed_ms={}", elapsed.as_millis());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentile() {
+        let samples = (1..=100).map(Duration::from_millis).collect::<Vec<_>>();
+        assert_eq!(percentile(&samples, 50.0), Some(Duration::from_millis(50)));
+        assert_eq!(percentile(&samples, 99.0), Some(Duration::from_millis(99)));
+        assert_eq!(
+            percentile(&samples, 100.0),
+            Some(Duration::from_millis(100))
+        );
+        assert_eq!(
+            percentile(&samples[..1], 0.0),
+            Some(Duration::from_millis(1))
+        );
+        assert_eq!(percentile(&[], 50.0), None);
+    }
+
+    #[test]
+    fn test_args_are_consistent() {
+        use clap::CommandFactory;
+        Args::command().debug_assert();
+    }
+}
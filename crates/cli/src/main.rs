@@ -0,0 +1,951 @@
+//! Operator CLI for a running sentinel. Speaks gRPC to the slot lock and admin services, so
+//! locks can be inspected and managed without touching the database file.
+
+mod records;
+
+use anyhow::{Context, Result};
+use clap::{Args, Parser, Subcommand};
+use records::Format;
+use sova_sentinel_client::SlotLockClient;
+use sova_sentinel_proto::proto::{
+    admin_service_client::AdminServiceClient, get_slot_status_response, lock_event,
+    lock_slot_response, search_locks_request, ExportEventsRequest, ExportLocksRequest,
+    GetLockHistoryRequest, GetReplicationStatusRequest, GetSlotStateAtRequest,
+    GetSlotValueHistoryRequest, GetStatsRequest, ListLocksByContractRequest,
+    ListLocksByTxidRequest, LockEvent, LockMatch, LockRecord, LockStats, PromoteRequest,
+    ReplicationStatus, RestoreLocksRequest, SearchLocksRequest, SlotData, SlotIdentifier,
+};
+use sova_sentinel_proto::source;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::PathBuf;
+use tonic::metadata::{Ascii, MetadataValue};
+use tonic::service::interceptor::InterceptedService;
+use tonic::service::Interceptor;
+use tonic::transport::{Channel, Endpoint};
+use tonic::Status;
+
+/// Size of an EVM storage slot, which the sentinel pads slot indexes to
+const SLOT_INDEX_LEN: usize = 32;
+
+#[derive(Parser)]
+#[command(
+    name = "sova-sentinel-cli",
+    version,
+    about = "Inspect and manage sentinel locks"
+)]
+struct Cli {
+    /// Address of the sentinel's gRPC server
+    #[arg(long, env = "SOVA_SENTINEL_ADDR", default_value = "http://[::1]:50051")]
+    addr: String,
+    /// Namespace to operate in, the default namespace when unset
+    #[arg(long, default_value = "")]
+    namespace: String,
+    /// Address of the sentinel's admin service, when it listens apart from --addr
+    #[arg(long, env = "SOVA_SENTINEL_ADMIN_ADDR")]
+    admin_addr: Option<String>,
+    /// Bearer token sent with every call, for listeners that require one
+    #[arg(long, env = "SOVA_SENTINEL_AUTH_TOKEN", hide_env_values = true)]
+    auth_token: Option<String>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Show the sentinel's version, thresholds and Bitcoin node
+    Info,
+    /// Show the status of a slot. A settled lock is only ended by finalize, unless the sentinel
+    /// runs with implicit unlocks.
+    Status {
+        #[command(flatten)]
+        slot: SlotArgs,
+        /// Current sova block
+        #[arg(long)]
+        current_block: u64,
+        /// Current Bitcoin block
+        #[arg(long)]
+        btc_block: u64,
+        /// Query with the unsigned peek RPC, which never ends a settled lock
+        #[arg(long)]
+        peek: bool,
+        /// Let a standby at most this many sova blocks behind its primary answer
+        #[arg(long)]
+        max_staleness_blocks: Option<u64>,
+    },
+    /// End every active lock that confirmed or reverted as of a sova block
+    Finalize {
+        /// Sova block being finalized
+        #[arg(long)]
+        current_block: u64,
+        /// Current Bitcoin block
+        #[arg(long)]
+        btc_block: u64,
+    },
+    /// Lock a slot
+    Lock {
+        #[command(flatten)]
+        slot: SlotArgs,
+        /// Sova block the lock is made at
+        #[arg(long)]
+        locked_at_block: u64,
+        /// Bitcoin block the lock transaction was broadcast at
+        #[arg(long)]
+        btc_block: u64,
+        /// Bitcoin transaction the lock waits on
+        #[arg(long)]
+        btc_txid: String,
+        /// Value to revert the slot to, as hex
+        #[arg(long, default_value = "", value_parser = parse_hex)]
+        revert_value: Vec<u8>,
+        /// Value the slot holds under the lock, as hex
+        #[arg(long, default_value = "", value_parser = parse_hex)]
+        current_value: Vec<u8>,
+        /// Opaque metadata stored with the lock, as hex
+        #[arg(long, default_value = "", value_parser = parse_hex)]
+        metadata: Vec<u8>,
+        /// Label stored with the lock, as key=value; can be repeated
+        #[arg(long = "label", value_parser = parse_label)]
+        labels: Vec<(String, String)>,
+        /// Alternative Bitcoin transaction whose confirmation also unlocks the slot; can be
+        /// repeated
+        #[arg(long = "alt-btc-txid")]
+        alt_btc_txids: Vec<String>,
+        /// Number of the lock's Bitcoin transactions that must confirm to unlock the slot
+        #[arg(long, default_value_t = 1)]
+        required_confirmed_txids: u32,
+    },
+    /// Force unlock a slot without waiting for its Bitcoin transaction
+    Unlock {
+        #[command(flatten)]
+        slot: SlotArgs,
+        /// Sova block the slot is unlocked at
+        #[arg(long)]
+        current_block: u64,
+        /// Current Bitcoin block
+        #[arg(long)]
+        btc_block: u64,
+    },
+    /// Unlock every slot locked on a Bitcoin transaction
+    UnlockTx {
+        /// Bitcoin transaction the locks wait on
+        btc_txid: String,
+        /// Sova block the slots are unlocked at
+        #[arg(long)]
+        current_block: u64,
+        /// Revert the slots to their revert values instead of keeping their current values
+        #[arg(long)]
+        revert: bool,
+    },
+    /// Restore the locks of a slot archived by the retention policy
+    Restore {
+        #[command(flatten)]
+        slot: SlotArgs,
+    },
+    /// List locks by value or label, most recently locked first
+    List(ListArgs),
+    /// Show every lock a slot has had, oldest first
+    Locks {
+        #[command(flatten)]
+        slot: SlotArgs,
+    },
+    /// Show whether a slot was locked at the end of a past sova block, and by which lock
+    StateAt {
+        #[command(flatten)]
+        slot: SlotArgs,
+        /// Sova block to look back at
+        #[arg(long)]
+        block: u64,
+    },
+    /// Show the values every lock of a slot recorded and settled on, oldest first
+    ValueHistory {
+        #[command(flatten)]
+        slot: SlotArgs,
+    },
+    /// Show the slots locked on a Bitcoin transaction
+    TxLocks {
+        /// Bitcoin transaction the locks wait on
+        btc_txid: String,
+        /// Also show the locks that already ended
+        #[arg(long)]
+        all: bool,
+    },
+    /// Show every lock of a contract made in a range of sova blocks, in the order they were made
+    ContractLocks {
+        contract_address: String,
+        /// First sova block of the range
+        #[arg(long, default_value_t = 0)]
+        from_block: u64,
+        /// Last sova block of the range, inclusive, unbounded if unset
+        #[arg(long)]
+        to_block: Option<u64>,
+    },
+    /// Show the locks and unlocks of a slot in a range of sova blocks
+    History {
+        #[command(flatten)]
+        slot: SlotArgs,
+        #[command(flatten)]
+        range: BlockRange,
+    },
+    /// Export every lock and unlock in a range of sova blocks
+    Export {
+        #[command(flatten)]
+        range: BlockRange,
+    },
+
+    /// Export the full rows of every lock, active and unlocked, for import into another sentinel
+    ExportLocks {
+        /// Export the locks of every namespace instead of only --namespace
+        #[arg(long)]
+        all_namespaces: bool,
+        #[arg(long, value_enum, default_value_t = Format::Json)]
+        format: Format,
+        /// File to write the locks to, stdout when unset
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Import locks written by export-locks. Locks that are already present are skipped, so an
+    /// interrupted import can be run again.
+    ImportLocks {
+        #[arg(long, value_enum, default_value_t = Format::Json)]
+        format: Format,
+        /// File to read the locks from, stdin when unset
+        #[arg(long)]
+        input: Option<PathBuf>,
+    },
+    /// Show the active, unlocked and reverted locks of each contract, and recent reverts
+    Stats {
+        /// Only count the locks of this contract
+        #[arg(long)]
+        contract: Option<String>,
+        /// Count reverts in this many sova blocks back from the latest, 100 when unset
+        #[arg(long, default_value_t = 0)]
+        recent_blocks: u64,
+    },
+    /// Show the replication role and fencing epoch of the sentinel
+    Replication,
+    /// Promote a standby sentinel to primary, fencing its former primary
+    Promote,
+}
+
+#[derive(Args)]
+struct SlotArgs {
+    /// Contract address, 0x-prefixed
+    contract_address: String,
+    /// Slot index, as 0x-prefixed hex or a decimal number
+    #[arg(value_parser = parse_slot_index)]
+    slot_index: Vec<u8>,
+}
+
+#[derive(Args)]
+struct BlockRange {
+    /// First sova block of the range
+    #[arg(long)]
+    from_block: u64,
+    /// Last sova block of the range, inclusive
+    #[arg(long)]
+    to_block: u64,
+}
+
+#[derive(Args)]
+struct ListArgs {
+    /// Only list locks whose revert or current value contains these hex bytes
+    #[arg(long, value_parser = parse_hex, conflicts_with = "sha256")]
+    pattern: Option<Vec<u8>>,
+    /// Only list locks whose revert or current value has this SHA-256 hash, as hex
+    #[arg(long, value_parser = parse_hex)]
+    sha256: Option<Vec<u8>>,
+    /// Only list locks carrying this label, as key=value; can be repeated
+    #[arg(long = "label", value_parser = parse_label)]
+    labels: Vec<(String, String)>,
+    /// Only list locks of this contract
+    #[arg(long)]
+    contract_address: Option<String>,
+    /// Also list locks that were already unlocked
+    #[arg(long)]
+    include_unlocked: bool,
+    /// Most locks listed, the server's default when unset
+    #[arg(long, default_value_t = 0)]
+    limit: u32,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let admin_addr = cli.admin_addr.clone().unwrap_or_else(|| cli.addr.clone());
+    match cli.command {
+        Command::Info => {
+            let info = slot_lock_client(&cli.addr, cli.auth_token.as_deref(), &cli.namespace)
+                .await?
+                .get_sentinel_info()
+                .await?
+                .into_inner();
+            println!("version={}", info.version);
+            println!("bitcoin_network={}", info.bitcoin_network);
+            println!("bitcoin_tip_height={}", info.bitcoin_tip_height);
+            println!("confirmation_threshold={}", info.confirmation_threshold);
+            println!("revert_threshold={}", info.revert_threshold);
+            println!("schema_version={}", info.schema_version);
+            println!("active_locks={}", info.active_locks);
+            println!("shadow_mode={}", info.shadow_mode);
+        }
+        Command::Status {
+            slot,
+            current_block,
+            btc_block,
+            peek,
+            max_staleness_blocks,
+        } => {
+            let client =
+                slot_lock_client(&cli.addr, cli.auth_token.as_deref(), &cli.namespace).await?;
+            let client = match max_staleness_blocks {
+                Some(blocks) => client.with_max_staleness_blocks(blocks),
+                None => client,
+            };
+            let response = if peek {
+                client
+                    .peek_slot_status(
+                        current_block,
+                        btc_block,
+                        slot.contract_address,
+                        slot.slot_index,
+                    )
+                    .await?
+            } else {
+                client
+                    .get_slot_status(
+                        current_block,
+                        btc_block,
+                        slot.contract_address,
+                        slot.slot_index,
+                    )
+                    .await?
+            };
+            let role = source::role(response.metadata())
+                .unwrap_or("unknown")
+                .to_string();
+            let status = response.into_inner();
+            let name = get_slot_status_response::Status::try_from(status.status)
+                .map_or("UNKNOWN", |status| status.as_str_name());
+            let unlock_reason =
+                get_slot_status_response::UnlockReason::try_from(status.unlock_reason)
+                    .map_or("UNKNOWN", |reason| reason.as_str_name());
+            let confirmation_stage =
+                get_slot_status_response::ConfirmationStage::try_from(status.confirmation_stage)
+                    .map_or("UNKNOWN", |stage| stage.as_str_name());
+            println!(
+                "status={} contract={} slot={} btc_txid={} btc_block={} start_block={} confirmations={}/{} confirmation_stage={} blocks_until_revert={} unlock_reason={} served_by={}",
+                name,
+                status.contract_address,
+                format_hex(&status.slot_index),
+                status.btc_txid,
+                status.btc_block,
+                status.start_block,
+                status.confirmations,
+                status.required_confirmations,
+                confirmation_stage,
+                status.blocks_until_revert,
+                unlock_reason,
+                role
+            );
+        }
+        Command::Lock {
+            slot,
+            locked_at_block,
+            btc_block,
+            btc_txid,
+            revert_value,
+            current_value,
+            metadata,
+            labels,
+            alt_btc_txids,
+            required_confirmed_txids,
+        } => {
+            let response = slot_lock_client(&cli.addr, cli.auth_token.as_deref(), &cli.namespace)
+                .await?
+                .lock_slot(
+                    locked_at_block,
+                    btc_block,
+                    SlotData {
+                        contract_address: slot.contract_address,
+                        slot_index: slot.slot_index,
+                        revert_value,
+                        current_value,
+                        btc_txid,
+                        metadata,
+                        labels: labels.into_iter().collect(),
+                        alt_btc_txids,
+                        required_confirmed_txids,
+                    },
+                )
+                .await?
+                .into_inner();
+            let name = lock_slot_response::Status::try_from(response.status)
+                .map_or("UNKNOWN", |status| status.as_str_name());
+            println!(
+                "status={} contract={} slot={}",
+                name,
+                response.contract_address,
+                format_hex(&response.slot_index)
+            );
+        }
+        Command::Unlock {
+            slot,
+            current_block,
+            btc_block,
+        } => {
+            let response = slot_lock_client(&cli.addr, cli.auth_token.as_deref(), &cli.namespace)
+                .await?
+                .batch_unlock_slot(
+                    current_block,
+                    btc_block,
+                    vec![SlotIdentifier {
+                        contract_address: slot.contract_address,
+                        slot_index: slot.slot_index,
+                    }],
+                )
+                .await?;
+            for slot in response.slots {
+                println!(
+                    "unlocked contract={} slot={}",
+                    slot.contract_address,
+                    format_hex(&slot.slot_index)
+                );
+            }
+        }
+        Command::UnlockTx {
+            btc_txid,
+            current_block,
+            revert,
+        } => {
+            let response = slot_lock_client(&cli.addr, cli.auth_token.as_deref(), &cli.namespace)
+                .await?
+                .unlock_by_txid(btc_txid, current_block, revert)
+                .await?
+                .into_inner();
+            let action = if revert { "reverted" } else { "unlocked" };
+            for slot in response.slots {
+                println!(
+                    "{} contract={} slot={}",
+                    action,
+                    slot.contract_address,
+                    format_hex(&slot.slot_index)
+                );
+            }
+        }
+        Command::Finalize {
+            current_block,
+            btc_block,
+        } => {
+            let response = slot_lock_client(&cli.addr, cli.auth_token.as_deref(), &cli.namespace)
+                .await?
+                .finalize_block(current_block, btc_block)
+                .await?
+                .into_inner();
+            for slot in response.ended {
+                let name = get_slot_status_response::Status::try_from(slot.status)
+                    .map_or("UNKNOWN", |status| status.as_str_name());
+                let unlock_reason =
+                    get_slot_status_response::UnlockReason::try_from(slot.unlock_reason)
+                        .map_or("UNKNOWN", |reason| reason.as_str_name());
+                println!(
+                    "status={} contract={} slot={} btc_txid={} unlock_reason={}",
+                    name,
+                    slot.contract_address,
+                    format_hex(&slot.slot_index),
+                    slot.btc_txid,
+                    unlock_reason
+                );
+            }
+            println!("active_locks={}", response.active_locks);
+        }
+        Command::List(args) => {
+            let query = match (args.pattern, args.sha256) {
+                (Some(pattern), _) => Some(search_locks_request::Query::ValuePattern(pattern)),
+                (_, Some(hash)) => Some(search_locks_request::Query::ValueSha256(hash)),
+                (None, None) => None,
+            };
+            let response = admin_client(&admin_addr, cli.auth_token.as_deref())
+                .await?
+                .search_locks(SearchLocksRequest {
+                    query,
+                    contract_address: args.contract_address.unwrap_or_default(),
+                    include_unlocked: args.include_unlocked,
+                    limit: args.limit,
+                    namespace: Some(cli.namespace),
+                    labels: args.labels.into_iter().collect(),
+                })
+                .await?
+                .into_inner();
+            for lock in &response.locks {
+                println!("{}", format_lock(lock));
+            }
+        }
+        Command::Locks { slot } => {
+            let response = admin_client(&admin_addr, cli.auth_token.as_deref())
+                .await?
+                .get_lock_history(GetLockHistoryRequest {
+                    namespace: cli.namespace,
+                    contract_address: slot.contract_address,
+                    slot_index: slot.slot_index,
+                })
+                .await?
+                .into_inner();
+            for lock in &response.locks {
+                println!("{}", format_record(lock));
+            }
+        }
+        Command::StateAt { slot, block } => {
+            let state = admin_client(&admin_addr, cli.auth_token.as_deref())
+                .await?
+                .get_slot_state_at(GetSlotStateAtRequest {
+                    namespace: cli.namespace,
+                    contract_address: slot.contract_address,
+                    slot_index: slot.slot_index,
+                    sova_block: block,
+                })
+                .await?
+                .into_inner();
+            match &state.lock {
+                Some(lock) => println!("locked {}", format_record(lock)),
+                None => println!("free"),
+            }
+            if let Some(lock) = &state.last_ended {
+                println!("last_ended {}", format_record(lock));
+            }
+        }
+        Command::ValueHistory { slot } => {
+            let response = admin_client(&admin_addr, cli.auth_token.as_deref())
+                .await?
+                .get_slot_value_history(GetSlotValueHistoryRequest {
+                    namespace: cli.namespace,
+                    contract_address: slot.contract_address,
+                    slot_index: slot.slot_index,
+                })
+                .await?
+                .into_inner();
+            for change in &response.changes {
+                println!(
+                    "lock_id={} start_block={} end_block={} unlock_reason={} btc_txid={} revert_value={} current_value={} settled_value={} purged={}",
+                    change.lock_id,
+                    change.start_block,
+                    change.end_block.map_or_else(String::new, |block| block.to_string()),
+                    change.unlock_reason,
+                    change.btc_txid,
+                    format_hex(&change.revert_value),
+                    format_hex(&change.current_value),
+                    change.settled_value.as_deref().map_or_else(String::new, format_hex),
+                    change.purged
+                );
+            }
+        }
+        Command::TxLocks { btc_txid, all } => {
+            let response = admin_client(&admin_addr, cli.auth_token.as_deref())
+                .await?
+                .list_locks_by_txid(ListLocksByTxidRequest {
+                    namespace: cli.namespace,
+                    btc_txid,
+                    include_unlocked: all,
+                })
+                .await?
+                .into_inner();
+            for lock in &response.locks {
+                println!("{}", format_record(lock));
+            }
+        }
+        Command::ContractLocks {
+            contract_address,
+            from_block,
+            to_block,
+        } => {
+            let mut client = admin_client(&admin_addr, cli.auth_token.as_deref()).await?;
+            let mut after_id = 0;
+            loop {
+                let page = client
+                    .list_locks_by_contract(ListLocksByContractRequest {
+                        namespace: cli.namespace.clone(),
+                        contract_address: contract_address.clone(),
+                        start_block: from_block,
+                        end_block: to_block,
+                        after_id,
+                        limit: 0,
+                    })
+                    .await?
+                    .into_inner();
+                for lock in &page.locks {
+                    println!("{}", format_record(lock));
+                }
+                if page.next_after_id == 0 {
+                    break;
+                }
+                after_id = page.next_after_id;
+            }
+        }
+        Command::History { slot, range } => {
+            let slot_index = pad_slot_index(slot.slot_index);
+            let mut events = export_events(
+                &admin_addr,
+                cli.auth_token.as_deref(),
+                &cli.namespace,
+                &range,
+            )
+            .await?;
+            while let Some(event) = events.message().await? {
+                if event.contract_address == slot.contract_address && event.slot_index == slot_index
+                {
+                    println!("{}", format_event(&event));
+                }
+            }
+        }
+        Command::Export { range } => {
+            let mut events = export_events(
+                &admin_addr,
+                cli.auth_token.as_deref(),
+                &cli.namespace,
+                &range,
+            )
+            .await?;
+            while let Some(event) = events.message().await? {
+                println!("{}", format_event(&event));
+            }
+        }
+        Command::Restore { slot } => {
+            let response = admin_client(&admin_addr, cli.auth_token.as_deref())
+                .await?
+                .restore_locks(RestoreLocksRequest {
+                    namespace: cli.namespace,
+                    contract_address: slot.contract_address,
+                    slot_index: slot.slot_index,
+                })
+                .await?
+                .into_inner();
+            println!("restored={}", response.restored);
+        }
+        Command::ExportLocks {
+            all_namespaces,
+            format,
+            output,
+        } => {
+            let mut writer: Box<dyn Write> = match output {
+                Some(path) => {
+                    Box::new(BufWriter::new(File::create(&path).with_context(|| {
+                        format!("Failed to create {}", path.display())
+                    })?))
+                }
+                None => Box::new(std::io::stdout().lock()),
+            };
+            let mut records = admin_client(&admin_addr, cli.auth_token.as_deref())
+                .await?
+                .export_locks(ExportLocksRequest {
+                    namespace: (!all_namespaces).then_some(cli.namespace),
+                })
+                .await?
+                .into_inner();
+            while let Some(record) = records.message().await? {
+                records::write_record(&mut writer, format, &record)?;
+            }
+            writer.flush()?;
+        }
+        Command::ImportLocks { format, input } => {
+            let records = match input {
+                Some(path) => records::read_records(
+                    BufReader::new(
+                        File::open(&path)
+                            .with_context(|| format!("Failed to open {}", path.display()))?,
+                    ),
+                    format,
+                )?,
+                None => records::read_records(std::io::stdin().lock(), format)?,
+            };
+            let response = admin_client(&admin_addr, cli.auth_token.as_deref())
+                .await?
+                .import_locks(futures::stream::iter(records))
+                .await?
+                .into_inner();
+            println!(
+                "imported={} skipped={}",
+                response.imported, response.skipped
+            );
+        }
+        Command::Stats {
+            contract,
+            recent_blocks,
+        } => {
+            let response = admin_client(&admin_addr, cli.auth_token.as_deref())
+                .await?
+                .get_stats(GetStatsRequest {
+                    namespace: Some(cli.namespace),
+                    contract_address: contract.unwrap_or_default(),
+                    recent_blocks,
+                })
+                .await?
+                .into_inner();
+            if let Some(total) = &response.total {
+                println!(
+                    "total {} recent_reverts={} recent_blocks={}",
+                    format_stats(total),
+                    response.recent_reverts,
+                    response.recent_blocks
+                );
+            }
+            for stats in &response.contracts {
+                println!(
+                    "contract={} {}",
+                    stats.contract_address,
+                    format_stats(stats)
+                );
+            }
+        }
+        Command::Replication => {
+            let status = admin_client(&admin_addr, cli.auth_token.as_deref())
+                .await?
+                .get_replication_status(GetReplicationStatusRequest {})
+                .await?
+                .into_inner();
+            println!("{}", format_replication_status(&status));
+        }
+        Command::Promote => {
+            let response = admin_client(&admin_addr, cli.auth_token.as_deref())
+                .await?
+                .promote(PromoteRequest {})
+                .await?
+                .into_inner();
+            println!(
+                "{} primary_fenced={}",
+                format_replication_status(&response.status.unwrap_or_default()),
+                response.primary_fenced
+            );
+        }
+    }
+    Ok(())
+}
+
+async fn slot_lock_client(
+    addr: &str,
+    auth_token: Option<&str>,
+    namespace: &str,
+) -> Result<SlotLockClient> {
+    let client = SlotLockClient::connect(addr.to_string())
+        .await
+        .with_context(|| format!("Failed to connect to {}", addr))?
+        .with_namespace(namespace);
+    match auth_token {
+        Some(token) => client.with_auth_token(token).context("Invalid auth token"),
+        None => Ok(client),
+    }
+}
+
+/// Sends the bearer token, if any, with every admin call
+#[derive(Clone)]
+struct Authorization(Option<MetadataValue<Ascii>>);
+
+impl Interceptor for Authorization {
+    fn call(&mut self, mut request: tonic::Request<()>) -> Result<tonic::Request<()>, Status> {
+        if let Some(authorization) = &self.0 {
+            request
+                .metadata_mut()
+                .insert("authorization", authorization.clone());
+        }
+        Ok(request)
+    }
+}
+
+async fn admin_client(
+    addr: &str,
+    auth_token: Option<&str>,
+) -> Result<AdminServiceClient<InterceptedService<Channel, Authorization>>> {
+    let authorization = auth_token
+        .map(|token| format!("Bearer {}", token).parse())
+        .transpose()
+        .context("Invalid auth token")?;
+    let channel = Endpoint::from_shared(addr.to_string())?
+        .connect()
+        .await
+        .with_context(|| format!("Failed to connect to {}", addr))?;
+    Ok(AdminServiceClient::with_interceptor(
+        channel,
+        Authorization(authorization),
+    ))
+}
+
+async fn export_events(
+    addr: &str,
+    auth_token: Option<&str>,
+    namespace: &str,
+    range: &BlockRange,
+) -> Result<tonic::Streaming<LockEvent>> {
+    Ok(admin_client(addr, auth_token)
+        .await?
+        .export_events(ExportEventsRequest {
+            start_sova_block: range.from_block,
+            end_sova_block: range.to_block,
+            namespace: Some(namespace.to_string()),
+        })
+        .await?
+        .into_inner())
+}
+
+fn format_lock(lock: &LockMatch) -> String {
+    format!(
+        "contract={} slot={} btc_txid={} btc_block={} start_block={} end_block={} unlock_reason={} revert_value={} current_value={}{}",
+        lock.contract_address,
+        format_hex(&lock.slot_index),
+        lock.btc_txid,
+        lock.btc_block,
+        lock.start_block,
+        lock.end_block,
+        lock.unlock_reason,
+        format_hex(&lock.revert_value),
+        format_hex(&lock.current_value),
+        format_labels(&lock.labels)
+    )
+}
+
+fn format_record(record: &LockRecord) -> String {
+    format!(
+        "id={} contract={} slot={} start_block={} end_block={} unlock_reason={} btc_txid={}{}{} btc_block={} revert_value={} current_value={} created_at={}{}",
+        record.id,
+        record.contract_address,
+        format_hex(&record.slot_index),
+        record.start_block,
+        record.end_block.map_or_else(String::new, |block| block.to_string()),
+        record.unlock_reason,
+        record.btc_txid,
+        if record.alt_btc_txids.is_empty() {
+            String::new()
+        } else {
+            format!(" alt_btc_txids={}", record.alt_btc_txids.join(","))
+        },
+        if record.required_confirmed_txids > 1 {
+            format!(" required_confirmed_txids={}", record.required_confirmed_txids)
+        } else {
+            String::new()
+        },
+        record.btc_block,
+        format_hex(&record.revert_value),
+        format_hex(&record.current_value),
+        record.created_at,
+        format_labels(&record.labels)
+    )
+}
+
+fn format_stats(stats: &LockStats) -> String {
+    format!(
+        "active={} unlocked={} reverted={} average_duration_blocks={:.1}",
+        stats.active_locks,
+        stats.unlocked_locks,
+        stats.reverted_locks,
+        stats.average_lock_duration_blocks
+    )
+}
+
+fn format_replication_status(status: &ReplicationStatus) -> String {
+    format!(
+        "role={} epoch={} applied_seq={} last_seq={} latest_block={}",
+        status.role().as_str_name(),
+        status.epoch,
+        status.applied_seq,
+        status.last_seq,
+        status.latest_block
+    )
+}
+
+fn format_event(event: &LockEvent) -> String {
+    let kind = lock_event::Kind::try_from(event.kind).map_or("UNKNOWN", |kind| kind.as_str_name());
+    format!(
+        "{} sova_block={} lock_id={} contract={} slot={} btc_txid={} btc_block={} lock_start_block={} unlock_reason={} revert_value={} current_value={}{}",
+        kind,
+        event.sova_block,
+        event.lock_id,
+        event.contract_address,
+        format_hex(&event.slot_index),
+        event.btc_txid,
+        event.btc_block,
+        event.lock_start_block,
+        event.unlock_reason,
+        format_hex(&event.revert_value),
+        format_hex(&event.current_value),
+        format_labels(&event.labels)
+    )
+}
+
+/// Formats labels as ` label.key=value` pairs, sorted by key
+fn format_labels(labels: &HashMap<String, String>) -> String {
+    let mut labels: Vec<_> = labels.iter().collect();
+    labels.sort();
+    labels
+        .into_iter()
+        .map(|(key, value)| format!(" label.{}={}", key, value))
+        .collect()
+}
+
+fn format_hex(bytes: &[u8]) -> String {
+    format!("0x{}", hex::encode(bytes))
+}
+
+/// Parses hex bytes, with or without a `0x` prefix
+fn parse_hex(s: &str) -> Result<Vec<u8>> {
+    Ok(hex::decode(s.strip_prefix("0x").unwrap_or(s))?)
+}
+
+/// Parses a slot index given as `0x`-prefixed hex or as a decimal number
+fn parse_slot_index(s: &str) -> Result<Vec<u8>> {
+    let slot_index = if s.starts_with("0x") {
+        parse_hex(s)?
+    } else {
+        let number: u128 = s.parse().context("Expected 0x-prefixed hex or a number")?;
+        let bytes = number.to_be_bytes();
+        let first = bytes
+            .iter()
+            .position(|b| *b != 0)
+            .unwrap_or(bytes.len() - 1);
+        bytes[first..].to_vec()
+    };
+    if slot_index.is_empty() || slot_index.len() > SLOT_INDEX_LEN {
+        anyhow::bail!("Slot indexes are 1 to {} bytes", SLOT_INDEX_LEN);
+    }
+    Ok(slot_index)
+}
+
+/// Left-pads a slot index with zeros to the form the sentinel stores and reports
+fn pad_slot_index(slot_index: Vec<u8>) -> Vec<u8> {
+    let mut padded = vec![0; SLOT_INDEX_LEN.saturating_sub(slot_index.len())];
+    padded.extend(slot_index);
+    padded
+}
+
+fn parse_label(s: &str) -> Result<(String, String)> {
+    let (key, value) = s.split_once('=').context("Expected key=value")?;
+    Ok((key.to_string(), value.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_slot_index() {
+        assert_eq!(parse_slot_index("0x0102").unwrap(), vec![1, 2]);
+        assert_eq!(parse_slot_index("258").unwrap(), vec![1, 2]);
+        assert_eq!(parse_slot_index("0").unwrap(), vec![0]);
+        assert!(parse_slot_index("0x").is_err());
+        assert!(parse_slot_index("slot").is_err());
+        assert_eq!(pad_slot_index(vec![1, 2])[30..], [1, 2]);
+    }
+
+    #[test]
+    fn test_parse_label() {
+        assert_eq!(
+            parse_label("deposit=a=b").unwrap(),
+            ("deposit".to_string(), "a=b".to_string())
+        );
+        assert!(parse_label("deposit").is_err());
+    }
+
+    #[test]
+    fn test_cli_is_consistent() {
+        use clap::CommandFactory;
+        Cli::command().debug_assert();
+    }
+}
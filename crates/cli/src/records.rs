@@ -0,0 +1,165 @@
+//! Files of lock records written by `export-locks` and read by `import-locks`
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use prost::Message;
+use serde_json::{json, Value};
+use sova_sentinel_proto::proto::LockRecord;
+use std::io::{BufRead, Write};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// One JSON object per line, with bytes as 0x-prefixed hex
+    Json,
+    /// Length-delimited `LockRecord` protobuf messages
+    Protobuf,
+}
+
+/// Writes one record in `format`
+pub fn write_record(writer: &mut impl Write, format: Format, record: &LockRecord) -> Result<()> {
+    match format {
+        Format::Json => writeln!(writer, "{}", to_json(record))?,
+        Format::Protobuf => writer.write_all(&record.encode_length_delimited_to_vec())?,
+    }
+    Ok(())
+}
+
+/// Reads every record of a file in `format`
+pub fn read_records(mut reader: impl BufRead, format: Format) -> Result<Vec<LockRecord>> {
+    match format {
+        Format::Json => reader
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| line.as_ref().map_or(true, |line| !line.trim().is_empty()))
+            .map(|(idx, line)| {
+                from_json(&line?).with_context(|| format!("Invalid record on line {}", idx + 1))
+            })
+            .collect(),
+        Format::Protobuf => {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes)?;
+            let mut buf = bytes.as_slice();
+            let mut records = Vec::new();
+            while !buf.is_empty() {
+                records.push(
+                    LockRecord::decode_length_delimited(&mut buf)
+                        .with_context(|| format!("Invalid record {}", records.len()))?,
+                );
+            }
+            Ok(records)
+        }
+    }
+}
+
+fn to_json(record: &LockRecord) -> Value {
+    json!({
+        "id": record.id,
+        "namespace": record.namespace,
+        "contract_address": record.contract_address,
+        "slot_index": crate::format_hex(&record.slot_index),
+        "revert_value": crate::format_hex(&record.revert_value),
+        "current_value": crate::format_hex(&record.current_value),
+        "btc_txid": record.btc_txid,
+        "btc_block": record.btc_block,
+        "start_block": record.start_block,
+        "end_block": record.end_block,
+        "unlock_reason": record.unlock_reason,
+        "metadata": crate::format_hex(&record.metadata),
+        "labels": record.labels,
+        "created_at": record.created_at,
+        "alt_btc_txids": record.alt_btc_txids,
+        "required_confirmed_txids": record.required_confirmed_txids,
+        "locked_by": record.locked_by,
+    })
+}
+
+fn from_json(line: &str) -> Result<LockRecord> {
+    let value: Value = serde_json::from_str(line)?;
+    let string = |key: &str| -> Result<String> {
+        match &value[key] {
+            Value::Null => Ok(String::new()),
+            Value::String(s) => Ok(s.clone()),
+            _ => anyhow::bail!("{} must be a string", key),
+        }
+    };
+    let number = |key: &str| -> Result<Option<u64>> {
+        match &value[key] {
+            Value::Null => Ok(None),
+            number => number
+                .as_u64()
+                .map(Some)
+                .with_context(|| format!("{} must be a number", key)),
+        }
+    };
+    let bytes = |key: &str| -> Result<Vec<u8>> {
+        crate::parse_hex(&string(key)?).with_context(|| format!("{} must be hex", key))
+    };
+
+    Ok(LockRecord {
+        id: number("id")?.unwrap_or_default(),
+        namespace: string("namespace")?,
+        contract_address: string("contract_address")?,
+        slot_index: bytes("slot_index")?,
+        revert_value: bytes("revert_value")?,
+        current_value: bytes("current_value")?,
+        btc_txid: string("btc_txid")?,
+        btc_block: number("btc_block")?.unwrap_or_default(),
+        start_block: number("start_block")?.unwrap_or_default(),
+        end_block: number("end_block")?,
+        unlock_reason: string("unlock_reason")?,
+        metadata: bytes("metadata")?,
+        labels: match &value["labels"] {
+            Value::Null => Default::default(),
+            labels => serde_json::from_value(labels.clone()).context("labels must be strings")?,
+        },
+        created_at: string("created_at")?,
+        alt_btc_txids: match &value["alt_btc_txids"] {
+            Value::Null => Vec::new(),
+            txids => {
+                serde_json::from_value(txids.clone()).context("alt_btc_txids must be strings")?
+            }
+        },
+        required_confirmed_txids: number("required_confirmed_txids")?.unwrap_or_default() as u32,
+        locked_by: string("locked_by")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let records = vec![
+            LockRecord {
+                id: 1,
+                contract_address: "0x123".to_string(),
+                slot_index: vec![0, 1],
+                btc_txid: "ab".repeat(32),
+                start_block: 100,
+                end_block: Some(101),
+                unlock_reason: "confirmed".to_string(),
+                labels: [("deposit".to_string(), "7".to_string())].into(),
+                alt_btc_txids: vec!["cd".repeat(32)],
+                required_confirmed_txids: 2,
+                locked_by: "key:indexer".to_string(),
+                created_at: "2024-01-01 00:00:00".to_string(),
+                ..Default::default()
+            },
+            LockRecord {
+                id: 2,
+                namespace: "testnet".to_string(),
+                metadata: vec![0xff],
+                ..Default::default()
+            },
+        ];
+
+        for format in [Format::Json, Format::Protobuf] {
+            let mut file = Vec::new();
+            for record in &records {
+                write_record(&mut file, format, record).unwrap();
+            }
+            assert_eq!(read_records(file.as_slice(), format).unwrap(), records);
+        }
+    }
+}
@@ -3,7 +3,7 @@ use sova_sentinel_proto::proto::{SlotData, SlotIdentifier};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let mut client = SlotLockClient::connect(String::from("http://[::1]:50051")).await?;
+    let client = SlotLockClient::connect(String::from("http://[::1]:50051")).await?;
 
     // See protobuf definitions for request and response payloads
     // proto/src/proto/slot_lock.proto
@@ -41,6 +41,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         revert_value: revert_bytes.clone(),
         current_value: current_bytes.clone(),
         btc_txid: btc_txid.clone(),
+        // Tags the lock for reconciliation, searchable through the admin service
+        metadata: Vec::new(),
+        labels: [("deposit_id".to_string(), "42".to_string())].into(),
+        alt_btc_txids: Vec::new(),
+        required_confirmed_txids: 0,
     };
     let response_lock = client.lock_slot(sova_block, btc_block, slot).await?;
 
@@ -72,6 +77,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             revert_value: revert_bytes.clone(),
             current_value: current_bytes.clone(),
             btc_txid: "txid1".to_string(),
+            ..Default::default()
         },
         SlotData {
             contract_address: address_2.clone(),
@@ -79,6 +85,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             revert_value: vec![7, 8, 9],
             current_value: vec![10, 11, 12],
             btc_txid: "txid2".to_string(),
+            ..Default::default()
         },
     ];
 
@@ -131,6 +138,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             revert_value: revert_bytes.clone(),
             current_value: current_bytes.clone(),
             btc_txid: "txid3".to_string(),
+            ..Default::default()
         },
         SlotData {
             contract_address: address_2.clone(),
@@ -138,6 +146,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             revert_value: vec![7, 8, 9],
             current_value: vec![10, 11, 12],
             btc_txid: "txid4".to_string(),
+            ..Default::default()
         },
     ];
 
@@ -0,0 +1,393 @@
+// The errors are those of the async client, whose `tonic::Status` is large
+#![allow(clippy::result_large_err)]
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::runtime::Runtime;
+use tonic::metadata::errors::InvalidMetadataValue;
+
+use sova_sentinel_proto::batch_size::BatchSizeHints;
+use sova_sentinel_proto::proto::{
+    BatchGetSlotStatusResponse, BatchLockSlotResponse, BatchUnlockSlotResponse, CommitLockResponse,
+    ContractSlots, ExpectedCurrentValue, FinalizeBlockResponse, GetSentinelInfoResponse,
+    GetServerInfoResponse, GetSlotStatusResponse, LockSlotResponse, PrepareLockResponse,
+    ReplaceLockTxResponse, SlotData, SlotIdentifier, SlotLockStatus, UnlockByTxidResponse,
+};
+
+use crate::{Endpoints, SlotLockClient, SlotLockClientBuilder, SlotLockClientError};
+
+/// [`SlotLockClient`] whose calls block the calling thread, running on a single-threaded runtime
+/// of its own. For synchronous code only: calls panic if made from within an async runtime.
+/// Clones share the runtime and, like clones of [`SlotLockClient`], the connections.
+#[derive(Clone)]
+pub struct SlotLockBlockingClient {
+    runtime: Arc<Runtime>,
+    client: SlotLockClient,
+}
+
+impl SlotLockBlockingClient {
+    /// Connects to `endpoints` with the default options, see [`SlotLockClient::connect`]
+    pub fn connect(endpoints: impl Into<Endpoints>) -> Result<Self, SlotLockClientError> {
+        Self::connect_with(SlotLockClient::builder(endpoints))
+    }
+
+    /// Connects with the options of `builder`, see [`SlotLockClientBuilder::connect`]
+    pub fn connect_with(builder: SlotLockClientBuilder) -> Result<Self, SlotLockClientError> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let client = runtime.block_on(builder.connect())?;
+        Ok(Self {
+            runtime: Arc::new(runtime),
+            client,
+        })
+    }
+
+    /// See [`SlotLockClient::with_namespace`]
+    pub fn with_namespace(mut self, namespace: impl Into<String>) -> Self {
+        self.client = self.client.with_namespace(namespace);
+        self
+    }
+
+    /// See [`SlotLockClient::with_auth_token`]
+    pub fn with_auth_token(mut self, token: &str) -> Result<Self, InvalidMetadataValue> {
+        self.client = self.client.with_auth_token(token)?;
+        Ok(self)
+    }
+
+    /// Returns the async client calls are made with
+    pub fn client(&self) -> &SlotLockClient {
+        &self.client
+    }
+
+    pub fn endpoint(&self) -> &str {
+        self.client.endpoint()
+    }
+
+    pub fn last_request_id(&self) -> Option<String> {
+        self.client.last_request_id()
+    }
+
+    pub fn batch_size_hints(&self) -> BatchSizeHints {
+        self.client.batch_size_hints()
+    }
+
+    pub fn lock_slot(
+        &self,
+        locked_at_block: u64,
+        btc_block: u64,
+        slot: SlotData,
+    ) -> Result<tonic::Response<LockSlotResponse>, tonic::Status> {
+        self.runtime
+            .block_on(self.client.lock_slot(locked_at_block, btc_block, slot))
+    }
+
+    pub fn lock_slot_with_raw_tx(
+        &self,
+        locked_at_block: u64,
+        btc_block: u64,
+        slot: SlotData,
+        raw_tx_hex: String,
+    ) -> Result<tonic::Response<LockSlotResponse>, tonic::Status> {
+        self.runtime.block_on(self.client.lock_slot_with_raw_tx(
+            locked_at_block,
+            btc_block,
+            slot,
+            raw_tx_hex,
+        ))
+    }
+
+    pub fn get_slot_status(
+        &self,
+        current_block: u64,
+        btc_block: u64,
+        contract_address: String,
+        slot_index: Vec<u8>,
+    ) -> Result<tonic::Response<GetSlotStatusResponse>, tonic::Status> {
+        self.runtime.block_on(self.client.get_slot_status(
+            current_block,
+            btc_block,
+            contract_address,
+            slot_index,
+        ))
+    }
+
+    pub fn peek_slot_status(
+        &self,
+        current_block: u64,
+        btc_block: u64,
+        contract_address: String,
+        slot_index: Vec<u8>,
+    ) -> Result<tonic::Response<GetSlotStatusResponse>, tonic::Status> {
+        self.runtime.block_on(self.client.peek_slot_status(
+            current_block,
+            btc_block,
+            contract_address,
+            slot_index,
+        ))
+    }
+
+    pub fn batch_lock_slot(
+        &self,
+        locked_at_block: u64,
+        btc_block: u64,
+        slots: Vec<SlotData>,
+    ) -> Result<tonic::Response<BatchLockSlotResponse>, tonic::Status> {
+        self.runtime.block_on(
+            self.client
+                .batch_lock_slot(locked_at_block, btc_block, slots),
+        )
+    }
+
+    pub fn batch_lock_slot_atomic(
+        &self,
+        locked_at_block: u64,
+        btc_block: u64,
+        slots: Vec<SlotData>,
+    ) -> Result<tonic::Response<BatchLockSlotResponse>, tonic::Status> {
+        self.runtime.block_on(
+            self.client
+                .batch_lock_slot_atomic(locked_at_block, btc_block, slots),
+        )
+    }
+
+    pub fn batch_lock_contract_slots(
+        &self,
+        locked_at_block: u64,
+        btc_block: u64,
+        contract_slots: Vec<ContractSlots>,
+    ) -> Result<tonic::Response<BatchLockSlotResponse>, tonic::Status> {
+        self.runtime.block_on(self.client.batch_lock_contract_slots(
+            locked_at_block,
+            btc_block,
+            contract_slots,
+        ))
+    }
+
+    pub fn batch_get_slot_status(
+        &self,
+        current_block: u64,
+        btc_block: u64,
+        slots: Vec<SlotIdentifier>,
+    ) -> Result<BatchGetSlotStatusResponse, SlotLockClientError> {
+        self.runtime.block_on(
+            self.client
+                .batch_get_slot_status(current_block, btc_block, slots),
+        )
+    }
+
+    pub fn batch_peek_slot_status(
+        &self,
+        current_block: u64,
+        btc_block: u64,
+        slots: Vec<SlotIdentifier>,
+    ) -> Result<BatchGetSlotStatusResponse, tonic::Status> {
+        self.runtime.block_on(
+            self.client
+                .batch_peek_slot_status(current_block, btc_block, slots),
+        )
+    }
+
+    pub fn batch_unlock_slot(
+        &self,
+        current_block: u64,
+        btc_block: u64,
+        slots: Vec<SlotIdentifier>,
+    ) -> Result<BatchUnlockSlotResponse, SlotLockClientError> {
+        self.runtime.block_on(
+            self.client
+                .batch_unlock_slot(current_block, btc_block, slots),
+        )
+    }
+
+    pub fn batch_unlock_slot_if_current(
+        &self,
+        current_block: u64,
+        btc_block: u64,
+        slots: Vec<SlotIdentifier>,
+        expected_current_values: Vec<ExpectedCurrentValue>,
+    ) -> Result<BatchUnlockSlotResponse, SlotLockClientError> {
+        self.runtime
+            .block_on(self.client.batch_unlock_slot_if_current(
+                current_block,
+                btc_block,
+                slots,
+                expected_current_values,
+            ))
+    }
+
+    pub fn replace_lock_tx(
+        &self,
+        old_btc_txid: String,
+        new_btc_txid: String,
+    ) -> Result<tonic::Response<ReplaceLockTxResponse>, tonic::Status> {
+        self.runtime
+            .block_on(self.client.replace_lock_tx(old_btc_txid, new_btc_txid))
+    }
+
+    pub fn unlock_by_txid(
+        &self,
+        btc_txid: String,
+        current_block: u64,
+        revert: bool,
+    ) -> Result<tonic::Response<UnlockByTxidResponse>, tonic::Status> {
+        self.runtime
+            .block_on(self.client.unlock_by_txid(btc_txid, current_block, revert))
+    }
+
+    pub fn prepare_lock(
+        &self,
+        locked_at_block: u64,
+        btc_block: u64,
+        slots: Vec<SlotData>,
+    ) -> Result<tonic::Response<PrepareLockResponse>, tonic::Status> {
+        self.runtime
+            .block_on(self.client.prepare_lock(locked_at_block, btc_block, slots))
+    }
+
+    pub fn commit_lock(
+        &self,
+        reservation_id: u64,
+    ) -> Result<tonic::Response<CommitLockResponse>, tonic::Status> {
+        self.runtime
+            .block_on(self.client.commit_lock(reservation_id))
+    }
+
+    pub fn finalize_block(
+        &self,
+        current_block: u64,
+        btc_block: u64,
+    ) -> Result<tonic::Response<FinalizeBlockResponse>, tonic::Status> {
+        self.runtime
+            .block_on(self.client.finalize_block(current_block, btc_block))
+    }
+
+    pub fn get_server_info(&self) -> Result<tonic::Response<GetServerInfoResponse>, tonic::Status> {
+        self.runtime.block_on(self.client.get_server_info())
+    }
+
+    pub fn get_sentinel_info(
+        &self,
+    ) -> Result<tonic::Response<GetSentinelInfoResponse>, tonic::Status> {
+        self.runtime.block_on(self.client.get_sentinel_info())
+    }
+
+    pub fn batch_lock_slot_chunked(
+        &self,
+        locked_at_block: u64,
+        btc_block: u64,
+        slots: Vec<SlotData>,
+    ) -> Result<Vec<SlotLockStatus>, SlotLockClientError> {
+        self.runtime.block_on(self.client.batch_lock_slot_chunked(
+            locked_at_block,
+            btc_block,
+            slots,
+        ))
+    }
+
+    pub fn batch_lock_contract_slots_chunked(
+        &self,
+        locked_at_block: u64,
+        btc_block: u64,
+        contract_slots: Vec<ContractSlots>,
+    ) -> Result<Vec<SlotLockStatus>, SlotLockClientError> {
+        self.runtime
+            .block_on(self.client.batch_lock_contract_slots_chunked(
+                locked_at_block,
+                btc_block,
+                contract_slots,
+            ))
+    }
+
+    pub fn batch_get_slot_status_chunked(
+        &self,
+        current_block: u64,
+        btc_block: u64,
+        slots: Vec<SlotIdentifier>,
+    ) -> Result<Vec<GetSlotStatusResponse>, SlotLockClientError> {
+        self.runtime
+            .block_on(
+                self.client
+                    .batch_get_slot_status_chunked(current_block, btc_block, slots),
+            )
+    }
+
+    pub fn batch_peek_slot_status_chunked(
+        &self,
+        current_block: u64,
+        btc_block: u64,
+        slots: Vec<SlotIdentifier>,
+    ) -> Result<Vec<GetSlotStatusResponse>, SlotLockClientError> {
+        self.runtime
+            .block_on(
+                self.client
+                    .batch_peek_slot_status_chunked(current_block, btc_block, slots),
+            )
+    }
+
+    pub fn batch_unlock_slot_chunked(
+        &self,
+        current_block: u64,
+        btc_block: u64,
+        slots: Vec<SlotIdentifier>,
+    ) -> Result<Vec<SlotIdentifier>, SlotLockClientError> {
+        self.runtime.block_on(self.client.batch_unlock_slot_chunked(
+            current_block,
+            btc_block,
+            slots,
+        ))
+    }
+
+    pub fn wait_for_unlock(
+        &self,
+        current_block: u64,
+        btc_block: u64,
+        slot: SlotIdentifier,
+        poll_interval: Duration,
+        deadline: Instant,
+    ) -> Result<GetSlotStatusResponse, SlotLockClientError> {
+        self.runtime.block_on(self.client.wait_for_unlock(
+            current_block,
+            btc_block,
+            slot,
+            poll_interval,
+            deadline,
+        ))
+    }
+
+    pub fn wait_for_unlock_batch(
+        &self,
+        current_block: u64,
+        btc_block: u64,
+        slots: Vec<SlotIdentifier>,
+        poll_interval: Duration,
+        deadline: Instant,
+    ) -> Result<Vec<GetSlotStatusResponse>, SlotLockClientError> {
+        self.runtime.block_on(self.client.wait_for_unlock_batch(
+            current_block,
+            btc_block,
+            slots,
+            poll_interval,
+            deadline,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_connect_fails_without_a_sentinel() {
+        let error = SlotLockBlockingClient::connect("http://127.0.0.1:1")
+            .err()
+            .unwrap();
+        assert!(matches!(error, SlotLockClientError::Transport(_)));
+    }
+
+    #[tokio::test]
+    #[should_panic]
+    async fn test_panics_within_a_runtime() {
+        let _ = SlotLockBlockingClient::connect("http://127.0.0.1:1");
+    }
+}
@@ -0,0 +1,227 @@
+//! Typed slot identities for EVM integrations. Contract addresses are sent as `0x` followed by
+//! 40 lowercase hex digits, and slot numbers and values as 32-byte big-endian words, so the same
+//! slot is always sent the same way. The sentinel matches addresses as given, so callers that
+//! also send raw addresses must format them the same way.
+
+use alloy_primitives::{Address, U256};
+use std::time::{Duration, Instant};
+
+use sova_sentinel_proto::proto::{
+    GetSlotStatusResponse, LockSlotResponse, SlotData, SlotIdentifier, SlotLockStatus,
+};
+
+use crate::{SlotLockClient, SlotLockClientError};
+
+/// Storage slot `index` of the contract at `address`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EvmSlot {
+    pub address: Address,
+    pub index: U256,
+}
+
+impl EvmSlot {
+    pub fn new(address: Address, index: U256) -> Self {
+        Self { address, index }
+    }
+
+    /// Reads the slot a sentinel response refers to, or returns `None` if its address or slot
+    /// index aren't an EVM address and storage slot
+    pub fn parse(contract_address: &str, slot_index: &[u8]) -> Option<Self> {
+        Some(Self {
+            address: contract_address.parse().ok()?,
+            index: decode_word(slot_index)?,
+        })
+    }
+}
+
+impl From<EvmSlot> for SlotIdentifier {
+    fn from(slot: EvmSlot) -> Self {
+        SlotIdentifier {
+            contract_address: encode_address(&slot.address),
+            slot_index: encode_word(slot.index),
+        }
+    }
+}
+
+/// Lock of an [`EvmSlot`]: its value to restore if the lock is reverted, its value once the
+/// block applies, and the Bitcoin transaction it waits for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EvmSlotLock {
+    pub slot: EvmSlot,
+    pub revert_value: U256,
+    pub current_value: U256,
+    pub btc_txid: String,
+}
+
+impl From<EvmSlotLock> for SlotData {
+    fn from(lock: EvmSlotLock) -> Self {
+        SlotData {
+            contract_address: encode_address(&lock.slot.address),
+            slot_index: encode_word(lock.slot.index),
+            revert_value: encode_word(lock.revert_value),
+            current_value: encode_word(lock.current_value),
+            btc_txid: lock.btc_txid,
+            ..Default::default()
+        }
+    }
+}
+
+/// Formats an address the way the typed calls send it
+pub fn encode_address(address: &Address) -> String {
+    format!("{:#x}", address)
+}
+
+/// Encodes a slot number or value as a 32-byte big-endian word
+pub fn encode_word(word: U256) -> Vec<u8> {
+    word.to_be_bytes_vec()
+}
+
+/// Decodes a big-endian word of at most 32 bytes, e.g. a slot index or value in a response
+pub fn decode_word(bytes: &[u8]) -> Option<U256> {
+    U256::try_from_be_slice(bytes)
+}
+
+impl SlotLockClient {
+    /// Locks an EVM storage slot, see [`SlotLockClient::lock_slot`]
+    pub async fn lock_evm_slot(
+        &self,
+        locked_at_block: u64,
+        btc_block: u64,
+        lock: EvmSlotLock,
+    ) -> Result<tonic::Response<LockSlotResponse>, tonic::Status> {
+        self.lock_slot(locked_at_block, btc_block, lock.into())
+            .await
+    }
+
+    /// Locks EVM storage slots, see [`SlotLockClient::batch_lock_slot_chunked`]
+    pub async fn batch_lock_evm_slots(
+        &self,
+        locked_at_block: u64,
+        btc_block: u64,
+        locks: Vec<EvmSlotLock>,
+    ) -> Result<Vec<SlotLockStatus>, SlotLockClientError> {
+        let slots = locks.into_iter().map(SlotData::from).collect();
+        self.batch_lock_slot_chunked(locked_at_block, btc_block, slots)
+            .await
+    }
+
+    /// Returns the status of an EVM storage slot, see [`SlotLockClient::get_slot_status`]
+    pub async fn get_evm_slot_status(
+        &self,
+        current_block: u64,
+        btc_block: u64,
+        slot: EvmSlot,
+    ) -> Result<tonic::Response<GetSlotStatusResponse>, tonic::Status> {
+        let slot = SlotIdentifier::from(slot);
+        self.get_slot_status(
+            current_block,
+            btc_block,
+            slot.contract_address,
+            slot.slot_index,
+        )
+        .await
+    }
+
+    /// Returns the status of each of the EVM storage `slots`, in order, see
+    /// [`SlotLockClient::batch_get_slot_status_chunked`]
+    pub async fn batch_get_evm_slot_status(
+        &self,
+        current_block: u64,
+        btc_block: u64,
+        slots: Vec<EvmSlot>,
+    ) -> Result<Vec<GetSlotStatusResponse>, SlotLockClientError> {
+        let slots = slots.into_iter().map(SlotIdentifier::from).collect();
+        self.batch_get_slot_status_chunked(current_block, btc_block, slots)
+            .await
+    }
+
+    /// Unlocks EVM storage slots, returning those that were unlocked, see
+    /// [`SlotLockClient::batch_unlock_slot_chunked`]
+    pub async fn batch_unlock_evm_slots(
+        &self,
+        current_block: u64,
+        btc_block: u64,
+        slots: Vec<EvmSlot>,
+    ) -> Result<Vec<EvmSlot>, SlotLockClientError> {
+        let unlocked = self
+            .batch_unlock_slot_chunked(
+                current_block,
+                btc_block,
+                slots.iter().copied().map(SlotIdentifier::from).collect(),
+            )
+            .await?;
+        Ok(slots
+            .into_iter()
+            .filter(|slot| unlocked.contains(&SlotIdentifier::from(*slot)))
+            .collect())
+    }
+
+    /// Waits for an EVM storage slot to be unlocked, see [`SlotLockClient::wait_for_unlock`]
+    pub async fn wait_for_evm_slot_unlock(
+        &self,
+        current_block: u64,
+        btc_block: u64,
+        slot: EvmSlot,
+        poll_interval: Duration,
+        deadline: Instant,
+    ) -> Result<GetSlotStatusResponse, SlotLockClientError> {
+        self.wait_for_unlock(
+            current_block,
+            btc_block,
+            slot.into(),
+            poll_interval,
+            deadline,
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::address;
+
+    const CONTRACT: Address = address!("00000000000000000000000000000000000000Ab");
+
+    #[test]
+    fn test_encodes_slots() {
+        let slot = EvmSlot::new(CONTRACT, U256::from(0x0102));
+        let id = SlotIdentifier::from(slot);
+        assert_eq!(
+            id.contract_address,
+            "0x00000000000000000000000000000000000000ab"
+        );
+        assert_eq!(id.slot_index.len(), 32);
+        assert_eq!(id.slot_index[30..], [1, 2]);
+        assert!(id.slot_index[..30].iter().all(|&byte| byte == 0));
+
+        let data = SlotData::from(EvmSlotLock {
+            slot,
+            revert_value: U256::from(1),
+            current_value: U256::MAX,
+            btc_txid: "ab".repeat(32),
+        });
+        assert_eq!(data.contract_address, id.contract_address);
+        assert_eq!(data.slot_index, id.slot_index);
+        assert_eq!(decode_word(&data.revert_value), Some(U256::from(1)));
+        assert_eq!(data.current_value, vec![0xff; 32]);
+    }
+
+    #[test]
+    fn test_decodes_slots() {
+        let slot = EvmSlot::new(CONTRACT, U256::from(7));
+        let id = SlotIdentifier::from(slot);
+        assert_eq!(
+            EvmSlot::parse(&id.contract_address, &id.slot_index),
+            Some(slot)
+        );
+
+        // Words shorter than 32 bytes are read as big-endian numbers, longer ones are rejected
+        assert_eq!(decode_word(&[]), Some(U256::ZERO));
+        assert_eq!(decode_word(&[1, 0]), Some(U256::from(256)));
+        assert_eq!(decode_word(&[1; 33]), None);
+        assert_eq!(EvmSlot::parse(&id.contract_address, &[1; 33]), None);
+        assert_eq!(EvmSlot::parse("0x123", &id.slot_index), None);
+        assert_eq!(EvmSlot::parse("not an address", &id.slot_index), None);
+    }
+}
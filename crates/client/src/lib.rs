@@ -1,29 +1,783 @@
-use tonic::transport::Channel;
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::future::Future;
+use std::hash::BuildHasher;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tonic::metadata::errors::InvalidMetadataValue;
+use tonic::metadata::{Ascii, MetadataKey, MetadataValue};
+use tonic::transport::{Channel, Endpoint};
+use tonic::Code;
+use tracing::Instrument;
 
+#[cfg(feature = "blocking")]
+mod blocking;
+#[cfg(feature = "alloy")]
+pub mod evm;
+mod status;
+
+#[cfg(feature = "blocking")]
+pub use blocking::SlotLockBlockingClient;
+pub use status::{LockStatus, SlotLockOutcome, SlotStatus, SlotStatusReport};
+pub use tonic::codec::CompressionEncoding;
+pub use tonic::transport::{Certificate, ClientTlsConfig, Identity};
+
+use sova_sentinel_proto::batch_size::BatchSizeHints;
+use sova_sentinel_proto::error_info::{
+    error_info, BATCH_TOO_LARGE, DATABASE_UNAVAILABLE, FENCED, STALE_REPLICA, STANDBY,
+};
+use sova_sentinel_proto::fencing;
 use sova_sentinel_proto::proto::{
+    health_check_response::ServingStatus, health_client::HealthClient,
     slot_lock_service_client::SlotLockServiceClient, BatchGetSlotStatusRequest,
     BatchGetSlotStatusResponse, BatchLockSlotRequest, BatchLockSlotResponse,
-    BatchUnlockSlotRequest, BatchUnlockSlotResponse, GetSlotStatusRequest, GetSlotStatusResponse,
-    LockSlotRequest, LockSlotResponse, SlotData, SlotIdentifier,
+    BatchUnlockSlotRequest, BatchUnlockSlotResponse, CommitLockRequest, CommitLockResponse,
+    ContractSlotData, ContractSlots, ExpectedCurrentValue, FinalizeBlockRequest,
+    FinalizeBlockResponse, GetSentinelInfoRequest, GetSentinelInfoResponse, GetServerInfoRequest,
+    GetServerInfoResponse, GetSlotStatusRequest, GetSlotStatusResponse, HealthCheckRequest,
+    LockSlotRequest, LockSlotResponse, PrepareLockRequest, PrepareLockResponse,
+    ReplaceLockTxRequest, ReplaceLockTxResponse, SlotData, SlotEvent, SlotIdentifier,
+    SlotLockStatus, SubscribeSlotEventsRequest, UnlockByTxidRequest, UnlockByTxidResponse,
 };
+use sova_sentinel_proto::request_id::request_id;
+
+/// Failure of a [`SlotLockClient`] call
+#[derive(Debug, thiserror::Error)]
+pub enum SlotLockClientError {
+    /// The connection to the sentinel couldn't be established
+    #[error("Transport error: {0}")]
+    Transport(#[from] tonic::transport::Error),
+    /// The sentinel answered with an error status, e.g. `UNAVAILABLE` or `RESOURCE_EXHAUSTED`,
+    /// whose code, error details and [`retry_after`] hint tell whether to retry
+    #[error("gRPC error: {0}")]
+    Grpc(#[from] tonic::Status),
+    /// The sentinel's response doesn't match the request
+    #[error("Invalid response: {0}")]
+    InvalidResponse(String),
+    /// Slots waited on were still locked at the deadline
+    #[error("{0} slots still locked at the deadline")]
+    StillLocked(usize),
+    /// The runtime of a [`SlotLockBlockingClient`] couldn't be started
+    #[cfg(feature = "blocking")]
+    #[error("Runtime error: {0}")]
+    Runtime(#[from] std::io::Error),
+}
+
+/// Slots sent per request by the chunked batch helpers until the server advertises a batch size
+pub const DEFAULT_BATCH_SIZE: usize = 100;
+
+/// How long connecting to an endpoint may take before the next one is tried
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long an endpoint may take to answer a health check before it counts as unhealthy
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Addresses of the sentinels a client connects to, in order of preference, e.g. a primary
+/// followed by its backups
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Endpoints(Vec<String>);
+
+impl From<String> for Endpoints {
+    fn from(addr: String) -> Self {
+        Self(vec![addr])
+    }
+}
+
+impl From<&str> for Endpoints {
+    fn from(addr: &str) -> Self {
+        Self(vec![addr.to_string()])
+    }
+}
+
+impl From<Vec<String>> for Endpoints {
+    fn from(addrs: Vec<String>) -> Self {
+        Self(addrs)
+    }
+}
+
+impl From<Vec<&str>> for Endpoints {
+    fn from(addrs: Vec<&str>) -> Self {
+        Self(addrs.into_iter().map(str::to_string).collect())
+    }
+}
+
+impl<const N: usize> From<[&str; N]> for Endpoints {
+    fn from(addrs: [&str; N]) -> Self {
+        Self(addrs.into_iter().map(str::to_string).collect())
+    }
+}
+
+/// Returns how long the server asked the client to wait before retrying a failed call, taken from
+/// the status' `google.rpc.RetryInfo` detail or its `grpc-retry-pushback-ms` header. Returns
+/// `None` if the server gave no hint, in which case the caller's own backoff applies.
+pub fn retry_after(status: &tonic::Status) -> Option<Duration> {
+    sova_sentinel_proto::retry_info::retry_delay(status).or_else(|| {
+        status
+            .metadata()
+            .get("grpc-retry-pushback-ms")?
+            .to_str()
+            .ok()?
+            .parse()
+            .ok()
+            .map(Duration::from_millis)
+    })
+}
+
+/// Checks that a batch response has a status for each of the `requested` slots
+fn expect_statuses<T>(requested: usize, statuses: Vec<T>) -> Result<Vec<T>, String> {
+    if statuses.len() != requested {
+        return Err(format!(
+            "Expected {} slot statuses, got {}",
+            requested,
+            statuses.len()
+        ));
+    }
+    Ok(statuses)
+}
+
+/// Checks that the slots an unlock reports unlocked were all requested
+fn expect_requested(
+    requested: &[SlotIdentifier],
+    unlocked: Vec<SlotIdentifier>,
+) -> Result<Vec<SlotIdentifier>, String> {
+    if let Some(slot) = unlocked.iter().find(|slot| !requested.contains(slot)) {
+        return Err(format!(
+            "Unrequested slot {:02x?} of {} reported unlocked",
+            slot.slot_index, slot.contract_address
+        ));
+    }
+    Ok(unlocked)
+}
+
+/// Groups consecutive slots of the same contract
+fn group_by_contract(slots: Vec<(String, ContractSlotData)>) -> Vec<ContractSlots> {
+    let mut groups: Vec<ContractSlots> = Vec::new();
+    for (contract_address, slot) in slots {
+        match groups.last_mut() {
+            Some(group) if group.contract_address == contract_address => group.slots.push(slot),
+            _ => groups.push(ContractSlots {
+                contract_address,
+                slots: vec![slot],
+            }),
+        }
+    }
+    groups
+}
 
+/// Automatic retries of idempotent calls, such as status queries and `FinalizeBlock`, that fail
+/// with `UNAVAILABLE` on every endpoint, e.g. while the sentinel restarts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubling after every retry
+    pub base_delay: Duration,
+    /// Longest delay between two attempts
+    pub max_delay: Duration,
+    /// No retry is started once this much time has passed since the first attempt
+    pub max_elapsed: Option<Duration>,
+    /// Randomize each delay between zero and its nominal value, so clients that failed together
+    /// don't retry together
+    pub jitter: bool,
+}
+
+impl RetryPolicy {
+    /// Exponential backoff from `base_delay` up to 10 seconds, with jitter and no time limit
+    pub fn exponential(max_attempts: u32, base_delay: Duration) -> Self {
+        Self {
+            max_attempts,
+            base_delay,
+            max_delay: Duration::from_secs(10),
+            max_elapsed: None,
+            jitter: true,
+        }
+    }
+
+    pub fn with_max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    pub fn with_max_elapsed(mut self, max_elapsed: Duration) -> Self {
+        self.max_elapsed = Some(max_elapsed);
+        self
+    }
+
+    pub fn with_jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Returns how long to wait before retrying after `attempts` failed attempts, `elapsed` after
+    /// the first one, or `None` if the call shouldn't be retried. The sentinel's `retry_after`
+    /// hint, if any, is waited at least.
+    fn delay(&self, attempts: u32, elapsed: Duration, hint: Option<Duration>) -> Option<Duration> {
+        if attempts >= self.max_attempts {
+            return None;
+        }
+        let backoff = self
+            .base_delay
+            .saturating_mul(1 << (attempts - 1).min(31))
+            .min(self.max_delay);
+        let backoff = if self.jitter {
+            let random = RandomState::new().hash_one(attempts) >> 11;
+            backoff.mul_f64(random as f64 / (1u64 << 53) as f64)
+        } else {
+            backoff
+        };
+        let delay = hint.map_or(backoff, |hint| backoff.max(hint));
+        match self.max_elapsed {
+            Some(max_elapsed) if elapsed + delay >= max_elapsed => None,
+            _ => Some(delay),
+        }
+    }
+}
+
+/// Returns whether a call failed because its sentinel is unreachable or out of service, so it
+/// can be retried on another endpoint
+fn should_fail_over(status: &tonic::Status) -> bool {
+    match status.code() {
+        Code::Unavailable => {
+            error_info(status).is_none_or(|info| info.reason == DATABASE_UNAVAILABLE)
+        }
+        Code::FailedPrecondition => error_info(status).is_some_and(|info| {
+            info.reason == STANDBY || info.reason == FENCED || info.reason == STALE_REPLICA
+        }),
+        _ => false,
+    }
+}
+
+/// Returns whether the sentinel behind `channel` reports itself as serving
+async fn is_serving(channel: Channel) -> bool {
+    let mut client = HealthClient::new(channel);
+    let check = client.check(HealthCheckRequest {
+        service: String::new(),
+    });
+    matches!(
+        tokio::time::timeout(HEALTH_CHECK_TIMEOUT, check).await,
+        Ok(Ok(response)) if response.get_ref().status == ServingStatus::Serving as i32
+    )
+}
+
+/// Client of the SlotLock service. Clones are cheap and share their connections, active
+/// endpoint, fencing epoch and batch sizes, so one client can serve concurrent tasks.
+#[derive(Clone)]
 pub struct SlotLockClient {
-    client: SlotLockServiceClient<Channel>,
+    /// Address and channel of each endpoint. Channels reconnect on their own once their sentinel
+    /// is back.
+    endpoints: Arc<[(String, Channel)]>,
+    shared: Arc<SharedState>,
+    namespace: String,
+    /// Staleness bound sent with every status query, if any
+    max_staleness_blocks: Option<u64>,
+    /// `authorization` header sent along with every call, if any
+    authorization: Option<MetadataValue<Ascii>>,
+    /// Compression of requests and accepted for responses, if any
+    compression: Option<CompressionEncoding>,
+    /// Metadata sent along with every call
+    metadata: Arc<[(MetadataKey<Ascii>, MetadataValue<Ascii>)]>,
+    /// Run on every call, in order, after the metadata is set
+    interceptors: Arc<[Interceptor]>,
+    /// Told of every attempt of a call
+    call_hooks: Arc<[CallHook]>,
+    /// Retries of idempotent calls, if any
+    retry_policy: Option<RetryPolicy>,
+}
+
+/// Function a client passes every call's metadata and extensions through, see
+/// [`SlotLockClientBuilder::with_interceptor`]
+#[derive(Clone)]
+struct Interceptor(Arc<InterceptorFn>);
+
+type InterceptorFn =
+    dyn Fn(tonic::Request<()>) -> Result<tonic::Request<()>, tonic::Status> + Send + Sync;
+
+impl fmt::Debug for Interceptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Interceptor")
+    }
+}
+
+/// One attempt of a call, reported to the hooks set with
+/// [`SlotLockClientBuilder::with_call_hook`]
+#[derive(Debug, Clone, Copy)]
+pub struct CallRecord<'a> {
+    /// gRPC method, e.g. `GetSlotStatus`
+    pub method: &'static str,
+    /// Endpoint the attempt was sent to
+    pub endpoint: &'a str,
+    /// Time until the response or error status was received
+    pub latency: Duration,
+    /// `Ok`, or the code of the error status
+    pub code: Code,
+}
+
+#[derive(Clone)]
+struct CallHook(Arc<dyn Fn(&CallRecord<'_>) + Send + Sync>);
+
+impl fmt::Debug for CallHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("CallHook")
+    }
+}
+
+/// State a client shares with its clones
+#[derive(Default)]
+struct SharedState {
+    /// Index of the endpoint calls are sent to
+    active: AtomicUsize,
+    /// Highest fencing epoch seen from any sentinel, sent along with every call
+    epoch: AtomicU64,
+    batch_size_hints: Mutex<BatchSizeHints>,
+    /// Id the sentinel logged the last call under
+    last_request_id: Mutex<Option<String>>,
+}
+
+/// Options of the connections of a [`SlotLockClient`], built by [`SlotLockClient::builder`]
+#[derive(Debug, Clone)]
+pub struct SlotLockClientBuilder {
+    endpoints: Endpoints,
+    connect_timeout: Duration,
+    request_timeout: Option<Duration>,
+    tls: Option<ClientTlsConfig>,
+    keepalive: Option<(Duration, Duration)>,
+    compression: Option<CompressionEncoding>,
+    metadata: Vec<(MetadataKey<Ascii>, MetadataValue<Ascii>)>,
+    interceptors: Vec<Interceptor>,
+    call_hooks: Vec<CallHook>,
+    retry_policy: Option<RetryPolicy>,
+}
+
+impl SlotLockClientBuilder {
+    /// Sets how long connecting to an endpoint may take before the next one is tried (default:
+    /// 5 seconds)
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// Fails calls that take longer than `timeout` with `CANCELLED`. Calls aren't bounded unless
+    /// set.
+    pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = Some(timeout);
+        self
+    }
+
+    /// Connects over TLS to `https://` endpoints, e.g. with
+    /// `ClientTlsConfig::new().ca_certificate(Certificate::from_pem(ca_pem))` to trust a private
+    /// CA
+    pub fn with_tls(mut self, tls: ClientTlsConfig) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+
+    /// Sends HTTP/2 pings every `interval`, even while idle, and drops connections whose pings
+    /// aren't answered within `timeout`, so dead connections are noticed before the next call
+    pub fn with_keepalive(mut self, interval: Duration, timeout: Duration) -> Self {
+        self.keepalive = Some((interval, timeout));
+        self
+    }
+
+    /// Compresses requests with `encoding` and asks the sentinel to compress responses the same
+    /// way
+    pub fn with_compression(mut self, encoding: CompressionEncoding) -> Self {
+        self.compression = Some(encoding);
+        self
+    }
+
+    /// Retries idempotent calls that fail with `UNAVAILABLE` on every endpoint following `policy`.
+    /// They aren't retried unless set.
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(policy);
+        self
+    }
+
+    /// Sends `key: value` metadata along with every call
+    pub fn with_metadata(mut self, key: MetadataKey<Ascii>, value: MetadataValue<Ascii>) -> Self {
+        self.metadata.push((key, value));
+        self
+    }
+
+    /// Passes every call through `interceptor`, which can add metadata, e.g. refreshed auth
+    /// tokens or trace headers, or fail the call with its own status. Interceptors run in the
+    /// order they're added, on each attempt of a call.
+    pub fn with_interceptor(
+        mut self,
+        interceptor: impl Fn(tonic::Request<()>) -> Result<tonic::Request<()>, tonic::Status>
+            + Send
+            + Sync
+            + 'static,
+    ) -> Self {
+        self.interceptors.push(Interceptor(Arc::new(interceptor)));
+        self
+    }
+
+    /// Reports the method, endpoint, latency and outcome of every attempt of a call to `hook`,
+    /// e.g. to record latency and error metrics. Hooks run on the calling task, so they should
+    /// be quick.
+    pub fn with_call_hook(
+        mut self,
+        hook: impl Fn(&CallRecord<'_>) + Send + Sync + 'static,
+    ) -> Self {
+        self.call_hooks.push(CallHook(Arc::new(hook)));
+        self
+    }
+
+    fn endpoint(&self, addr: String) -> Result<Endpoint, tonic::transport::Error> {
+        let mut endpoint = Endpoint::from_shared(addr)?.connect_timeout(self.connect_timeout);
+        if let Some(timeout) = self.request_timeout {
+            endpoint = endpoint.timeout(timeout);
+        }
+        if let Some((interval, timeout)) = self.keepalive {
+            endpoint = endpoint
+                .http2_keep_alive_interval(interval)
+                .keep_alive_timeout(timeout)
+                .keep_alive_while_idle(true);
+        }
+        if let Some(tls) = &self.tls {
+            endpoint = endpoint.tls_config(tls.clone())?;
+        }
+        Ok(endpoint)
+    }
+
+    /// Connects to the first healthy sentinel of the endpoints, or the first reachable one if
+    /// none is healthy. Calls that fail because their sentinel is unreachable or out of service
+    /// are retried on the next healthy endpoint, which then serves later calls. A call whose
+    /// connection drops mid-request may thus reach two sentinels.
+    ///
+    /// Fails if no endpoint can be reached. Panics if there are no endpoints.
+    pub async fn connect(self) -> Result<SlotLockClient, tonic::transport::Error> {
+        let addrs = self.endpoints.0.clone();
+        assert!(!addrs.is_empty(), "At least one endpoint is required");
+
+        let mut endpoints = Vec::with_capacity(addrs.len());
+        let mut active = None;
+        let mut reachable = None;
+        let mut last_error = None;
+        for addr in addrs {
+            let endpoint = self.endpoint(addr.clone())?;
+            let channel = if active.is_some() {
+                endpoint.connect_lazy()
+            } else {
+                match endpoint.connect().await {
+                    Ok(channel) => {
+                        if is_serving(channel.clone()).await {
+                            active = Some(endpoints.len());
+                        } else {
+                            reachable = reachable.or(Some(endpoints.len()));
+                        }
+                        channel
+                    }
+                    Err(e) => {
+                        last_error = Some(e);
+                        endpoint.connect_lazy()
+                    }
+                }
+            };
+            endpoints.push((addr, channel));
+        }
+
+        let active = match (active.or(reachable), last_error) {
+            (Some(active), _) => active,
+            (None, Some(e)) => return Err(e),
+            (None, None) => unreachable!("every endpoint either connected or failed"),
+        };
+        Ok(SlotLockClient {
+            endpoints: endpoints.into(),
+            shared: Arc::new(SharedState {
+                active: AtomicUsize::new(active),
+                ..SharedState::default()
+            }),
+            namespace: String::new(),
+            max_staleness_blocks: None,
+            authorization: None,
+            compression: self.compression,
+            metadata: self.metadata.into(),
+            interceptors: self.interceptors.into(),
+            call_hooks: self.call_hooks.into(),
+            retry_policy: self.retry_policy,
+        })
+    }
+}
+
+fn service_client(
+    channel: Channel,
+    compression: Option<CompressionEncoding>,
+) -> SlotLockServiceClient<Channel> {
+    let client = SlotLockServiceClient::new(channel);
+    match compression {
+        Some(encoding) => client.send_compressed(encoding).accept_compressed(encoding),
+        None => client,
+    }
 }
 
 impl SlotLockClient {
-    pub async fn connect(addr: String) -> Result<Self, tonic::transport::Error> {
-        let client = SlotLockServiceClient::connect(addr).await?;
-        Ok(Self { client })
+    /// Starts configuring a client of `endpoints`, in order of preference
+    pub fn builder(endpoints: impl Into<Endpoints>) -> SlotLockClientBuilder {
+        SlotLockClientBuilder {
+            endpoints: endpoints.into(),
+            connect_timeout: CONNECT_TIMEOUT,
+            request_timeout: None,
+            tls: None,
+            keepalive: None,
+            compression: None,
+            metadata: Vec::new(),
+            interceptors: Vec::new(),
+            call_hooks: Vec::new(),
+            retry_policy: None,
+        }
+    }
+
+    /// Connects to `endpoints` with the default options, see [`SlotLockClientBuilder::connect`]
+    pub async fn connect(endpoints: impl Into<Endpoints>) -> Result<Self, tonic::transport::Error> {
+        Self::builder(endpoints).connect().await
+    }
+
+    /// Returns the address of the endpoint calls are currently sent to
+    pub fn endpoint(&self) -> &str {
+        &self.endpoints[self.shared.active.load(Ordering::Relaxed)].0
+    }
+
+    /// Returns the id the sentinel logged the last call of this client or its clones under,
+    /// successful or not, to correlate client logs with the sentinel's
+    pub fn last_request_id(&self) -> Option<String> {
+        self.shared.last_request_id.lock().unwrap().clone()
+    }
+
+    /// Switches from the `failed` endpoint to the next healthy one after it, or just the next one
+    /// if none is healthy. Nothing changes if a concurrent call already switched away from it.
+    async fn fail_over(&self, failed: usize) {
+        let count = self.endpoints.len();
+        let mut next = (failed + 1) % count;
+        for offset in 1..count {
+            let idx = (failed + offset) % count;
+            if is_serving(self.endpoints[idx].1.clone()).await {
+                next = idx;
+                break;
+            }
+        }
+        let _ =
+            self.shared
+                .active
+                .compare_exchange(failed, next, Ordering::Relaxed, Ordering::Relaxed);
+    }
+
+    /// Sends `request` through `rpc`, failing over to the other endpoints in turn while its
+    /// sentinel is unreachable, out of service or not the primary. Each call carries the highest
+    /// fencing epoch seen, so a primary superseded by a promoted standby stops granting locks.
+    async fn call<T, R, F, Fut>(
+        &self,
+        method: &'static str,
+        request: T,
+        rpc: F,
+    ) -> Result<tonic::Response<R>, tonic::Status>
+    where
+        T: Clone,
+        F: Fn(SlotLockServiceClient<Channel>, tonic::Request<T>) -> Fut,
+        Fut: Future<Output = Result<tonic::Response<R>, tonic::Status>>,
+    {
+        let mut attempts = self.endpoints.len();
+        loop {
+            let mut call = tonic::Request::new(request.clone());
+            let epoch = self.shared.epoch.load(Ordering::Relaxed);
+            if epoch > 0 {
+                fencing::insert_epoch(call.metadata_mut(), epoch);
+            }
+            if let Some(authorization) = &self.authorization {
+                call.metadata_mut()
+                    .insert("authorization", authorization.clone());
+            }
+            for (key, value) in self.metadata.iter() {
+                call.metadata_mut().append(key.clone(), value.clone());
+            }
+            for interceptor in self.interceptors.iter() {
+                let (metadata, extensions, message) = call.into_parts();
+                let (metadata, extensions, ()) =
+                    (interceptor.0)(tonic::Request::from_parts(metadata, extensions, ()))?
+                        .into_parts();
+                call = tonic::Request::from_parts(metadata, extensions, message);
+            }
+            let active = self.shared.active.load(Ordering::Relaxed);
+            let (endpoint, channel) = &self.endpoints[active];
+            let client = service_client(channel.clone(), self.compression);
+            let started = Instant::now();
+            let result = rpc(client, call)
+                .instrument(tracing::info_span!(
+                    "slot_lock_call",
+                    otel.kind = "client",
+                    rpc.method = method,
+                    endpoint = %endpoint
+                ))
+                .await;
+            let record = CallRecord {
+                method,
+                endpoint,
+                latency: started.elapsed(),
+                code: result
+                    .as_ref()
+                    .map_or_else(|status| status.code(), |_| Code::Ok),
+            };
+            match &result {
+                Ok(_) => tracing::debug!("{} on {} took {:?}", method, endpoint, record.latency),
+                Err(status) => tracing::debug!(
+                    "{} on {} failed after {:?}: {}",
+                    method,
+                    endpoint,
+                    record.latency,
+                    status
+                ),
+            }
+            for hook in self.call_hooks.iter() {
+                (hook.0)(&record);
+            }
+            let metadata = match &result {
+                Ok(response) => response.metadata(),
+                Err(status) => status.metadata(),
+            };
+            self.shared
+                .epoch
+                .fetch_max(fencing::epoch(metadata).unwrap_or(0), Ordering::Relaxed);
+            *self.shared.last_request_id.lock().unwrap() = request_id(metadata).map(str::to_string);
+            match result {
+                Err(status) if attempts > 1 && should_fail_over(&status) => {
+                    attempts -= 1;
+                    self.fail_over(active).await;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Sends `request` through `rpc` like [`SlotLockClient::call`], retrying following the retry
+    /// policy while every endpoint is unavailable. Only for calls that are safe to repeat.
+    async fn call_idempotent<T, R, F, Fut>(
+        &self,
+        method: &'static str,
+        request: T,
+        rpc: F,
+    ) -> Result<tonic::Response<R>, tonic::Status>
+    where
+        T: Clone,
+        F: Fn(SlotLockServiceClient<Channel>, tonic::Request<T>) -> Fut,
+        Fut: Future<Output = Result<tonic::Response<R>, tonic::Status>>,
+    {
+        let started = Instant::now();
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            match self.call(method, request.clone(), &rpc).await {
+                Err(status) if status.code() == Code::Unavailable => {
+                    let delay = self.retry_policy.and_then(|policy| {
+                        policy.delay(attempts, started.elapsed(), retry_after(&status))
+                    });
+                    match delay {
+                        Some(delay) => tokio::time::sleep(delay).await,
+                        None => return Err(status),
+                    }
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Sends every request in `namespace`, the Sova network the client locks slots for. Clients
+    /// use the default namespace `""` unless set.
+    pub fn with_namespace(mut self, namespace: impl Into<String>) -> Self {
+        self.namespace = namespace.into();
+        self
+    }
+
+    /// Lets status queries be answered by a standby at most `blocks` sova blocks behind its
+    /// primary. Status queries are left to the primary unless set.
+    pub fn with_max_staleness_blocks(mut self, blocks: u64) -> Self {
+        self.max_staleness_blocks = Some(blocks);
+        self
+    }
+
+    /// Sends `token` as a bearer token with every call, for listeners that require one. Fails if
+    /// the token isn't valid in a header.
+    pub fn with_auth_token(mut self, token: &str) -> Result<Self, InvalidMetadataValue> {
+        self.authorization = Some(format!("Bearer {}", token).parse()?);
+        Ok(self)
+    }
+
+    /// Sends `key: value` metadata along with every call of this clone, in addition to the
+    /// metadata set on the builder, e.g. a tenant id for the calls of one task
+    pub fn with_metadata(mut self, key: MetadataKey<Ascii>, value: MetadataValue<Ascii>) -> Self {
+        let mut metadata = self.metadata.to_vec();
+        metadata.push((key, value));
+        self.metadata = metadata.into();
+        self
+    }
+
+    /// Passes every call of this clone through `interceptor` too, after the interceptors set on
+    /// the builder, see [`SlotLockClientBuilder::with_interceptor`]
+    pub fn with_interceptor(
+        mut self,
+        interceptor: impl Fn(tonic::Request<()>) -> Result<tonic::Request<()>, tonic::Status>
+            + Send
+            + Sync
+            + 'static,
+    ) -> Self {
+        let mut interceptors = self.interceptors.to_vec();
+        interceptors.push(Interceptor(Arc::new(interceptor)));
+        self.interceptors = interceptors.into();
+        self
+    }
+
+    /// Returns the batch sizes last advertised by the server, through `GetServerInfo` or the
+    /// metadata of a batch response
+    pub fn batch_size_hints(&self) -> BatchSizeHints {
+        *self.shared.batch_size_hints.lock().unwrap()
+    }
+
+    /// Returns the number of slots the chunked batch helpers send per request
+    pub fn batch_size(&self) -> usize {
+        self.batch_size_hints()
+            .chunk_size()
+            .unwrap_or(DEFAULT_BATCH_SIZE)
+    }
+
+    fn observe_batch_size_hints<T>(&self, response: &tonic::Response<T>) {
+        if let Some(hints) = BatchSizeHints::from_metadata(response.metadata()) {
+            *self.shared.batch_size_hints.lock().unwrap() = hints;
+        }
+    }
+
+    /// Lowers the maximum batch size to the one reported by a `BATCH_TOO_LARGE` error, returning
+    /// whether a batch of `sent` slots should be retried in smaller chunks
+    fn shrink_batch_size(&self, status: &tonic::Status, sent: usize) -> bool {
+        let Some(max) = error_info(status)
+            .filter(|info| info.reason == BATCH_TOO_LARGE)
+            .and_then(|info| info.metadata.get("max_batch_size")?.parse::<u32>().ok())
+        else {
+            return false;
+        };
+        self.shared.batch_size_hints.lock().unwrap().max = max;
+        max > 0 && (max as usize) < sent
     }
 
     pub async fn lock_slot(
-        &mut self,
+        &self,
         locked_at_block: u64,
         btc_block: u64,
         slot: SlotData,
+    ) -> Result<tonic::Response<LockSlotResponse>, tonic::Status> {
+        self.lock_slot_with_raw_tx(locked_at_block, btc_block, slot, String::new())
+            .await
+    }
+
+    /// Locks a slot and has the sentinel broadcast the hex-encoded raw lock transaction
+    pub async fn lock_slot_with_raw_tx(
+        &self,
+        locked_at_block: u64,
+        btc_block: u64,
+        slot: SlotData,
+        raw_tx_hex: String,
     ) -> Result<tonic::Response<LockSlotResponse>, tonic::Status> {
         let request = LockSlotRequest {
+            namespace: self.namespace.clone(),
             locked_at_block,
             btc_block,
             contract_address: slot.contract_address,
@@ -31,76 +785,862 @@ impl SlotLockClient {
             revert_value: slot.revert_value,
             current_value: slot.current_value,
             btc_txid: slot.btc_txid,
+            raw_tx_hex,
+            metadata: slot.metadata,
+            labels: slot.labels,
+            alt_btc_txids: slot.alt_btc_txids,
+            required_confirmed_txids: slot.required_confirmed_txids,
         };
 
-        self.client.lock_slot(request).await
+        self.call("LockSlot", request, |mut client, request| async move {
+            client.lock_slot(request).await
+        })
+        .await
     }
 
     pub async fn get_slot_status(
-        &mut self,
+        &self,
         current_block: u64,
         btc_block: u64,
         contract_address: String,
         slot_index: Vec<u8>,
     ) -> Result<tonic::Response<GetSlotStatusResponse>, tonic::Status> {
         let request = GetSlotStatusRequest {
+            namespace: self.namespace.clone(),
+            max_staleness_blocks: self.max_staleness_blocks,
             current_block,
             btc_block,
             contract_address,
             slot_index,
         };
 
-        self.client.get_slot_status(request).await
+        self.call_idempotent("GetSlotStatus", request, |mut client, request| async move {
+            client.get_slot_status(request).await
+        })
+        .await
+    }
+
+    /// Reports a slot's status like [`SlotLockClient::get_slot_status`], without unlocking or
+    /// reverting its lock if it is settled
+    pub async fn peek_slot_status(
+        &self,
+        current_block: u64,
+        btc_block: u64,
+        contract_address: String,
+        slot_index: Vec<u8>,
+    ) -> Result<tonic::Response<GetSlotStatusResponse>, tonic::Status> {
+        let request = GetSlotStatusRequest {
+            namespace: self.namespace.clone(),
+            max_staleness_blocks: self.max_staleness_blocks,
+            current_block,
+            btc_block,
+            contract_address,
+            slot_index,
+        };
+
+        self.call_idempotent(
+            "PeekSlotStatus",
+            request,
+            |mut client, request| async move { client.peek_slot_status(request).await },
+        )
+        .await
     }
 
     pub async fn batch_lock_slot(
-        &mut self,
+        &self,
+        locked_at_block: u64,
+        btc_block: u64,
+        slots: Vec<SlotData>,
+    ) -> Result<tonic::Response<BatchLockSlotResponse>, tonic::Status> {
+        let request = BatchLockSlotRequest {
+            namespace: self.namespace.clone(),
+            locked_at_block,
+            btc_block,
+            slots,
+            contract_slots: Vec::new(),
+            atomic: false,
+        };
+
+        let response = self
+            .call("BatchLockSlot", request, |mut client, request| async move {
+                client.batch_lock_slot(request).await
+            })
+            .await?;
+        self.observe_batch_size_hints(&response);
+        Ok(response)
+    }
+
+    /// Locks slots as one atomic group: either every slot is locked or none is, and the locks are
+    /// unlocked or reverted together. Never split into chunks, so the batch must fit the server's
+    /// maximum batch size.
+    pub async fn batch_lock_slot_atomic(
+        &self,
         locked_at_block: u64,
         btc_block: u64,
         slots: Vec<SlotData>,
     ) -> Result<tonic::Response<BatchLockSlotResponse>, tonic::Status> {
         let request = BatchLockSlotRequest {
+            namespace: self.namespace.clone(),
             locked_at_block,
             btc_block,
             slots,
+            contract_slots: Vec::new(),
+            atomic: true,
+        };
+
+        let response = self
+            .call("BatchLockSlot", request, |mut client, request| async move {
+                client.batch_lock_slot(request).await
+            })
+            .await?;
+        self.observe_batch_size_hints(&response);
+        Ok(response)
+    }
+
+    /// Locks slots grouped by contract, which keeps requests for many slots of the same contract
+    /// small
+    pub async fn batch_lock_contract_slots(
+        &self,
+        locked_at_block: u64,
+        btc_block: u64,
+        contract_slots: Vec<ContractSlots>,
+    ) -> Result<tonic::Response<BatchLockSlotResponse>, tonic::Status> {
+        let request = BatchLockSlotRequest {
+            namespace: self.namespace.clone(),
+            locked_at_block,
+            btc_block,
+            slots: Vec::new(),
+            contract_slots,
+            atomic: false,
         };
 
-        self.client.batch_lock_slot(request).await
+        let response = self
+            .call("BatchLockSlot", request, |mut client, request| async move {
+                client.batch_lock_slot(request).await
+            })
+            .await?;
+        self.observe_batch_size_hints(&response);
+        Ok(response)
     }
 
+    /// Returns the status of each of `slots`, in order. Fails with
+    /// [`SlotLockClientError::InvalidResponse`] if the sentinel returns another number of
+    /// statuses.
     pub async fn batch_get_slot_status(
-        &mut self,
+        &self,
         current_block: u64,
         btc_block: u64,
         slots: Vec<SlotIdentifier>,
-    ) -> Result<BatchGetSlotStatusResponse, Box<dyn std::error::Error>> {
+    ) -> Result<BatchGetSlotStatusResponse, SlotLockClientError> {
+        let requested = slots.len();
         let response = self
-            .client
-            .batch_get_slot_status(BatchGetSlotStatusRequest {
-                current_block,
-                btc_block,
-                slots,
-            })
+            .call_idempotent(
+                "BatchGetSlotStatus",
+                BatchGetSlotStatusRequest {
+                    namespace: self.namespace.clone(),
+                    max_staleness_blocks: self.max_staleness_blocks,
+                    current_block,
+                    btc_block,
+                    slots,
+                },
+                |mut client, request| async move { client.batch_get_slot_status(request).await },
+            )
+            .await?;
+        self.observe_batch_size_hints(&response);
+
+        let mut response = response.into_inner();
+        response.slots = expect_statuses(requested, response.slots)
+            .map_err(SlotLockClientError::InvalidResponse)?;
+        Ok(response)
+    }
+
+    /// Reports slot statuses like [`SlotLockClient::batch_get_slot_status`], without unlocking
+    /// or reverting settled locks
+    pub async fn batch_peek_slot_status(
+        &self,
+        current_block: u64,
+        btc_block: u64,
+        slots: Vec<SlotIdentifier>,
+    ) -> Result<BatchGetSlotStatusResponse, tonic::Status> {
+        let response = self
+            .call_idempotent(
+                "BatchPeekSlotStatus",
+                BatchGetSlotStatusRequest {
+                    namespace: self.namespace.clone(),
+                    max_staleness_blocks: self.max_staleness_blocks,
+                    current_block,
+                    btc_block,
+                    slots,
+                },
+                |mut client, request| async move { client.batch_peek_slot_status(request).await },
+            )
             .await?;
+        self.observe_batch_size_hints(&response);
 
         Ok(response.into_inner())
     }
 
     pub async fn batch_unlock_slot(
-        &mut self,
+        &self,
+        current_block: u64,
+        btc_block: u64,
+        slots: Vec<SlotIdentifier>,
+    ) -> Result<BatchUnlockSlotResponse, SlotLockClientError> {
+        self.batch_unlock_slot_if_current(current_block, btc_block, slots, Vec::new())
+            .await
+    }
+
+    /// Unlocks slots only if each slot of `expected_current_values` is still locked with that
+    /// current value. Otherwise nothing is unlocked and the call fails with a
+    /// `CURRENT_VALUE_MISMATCH` error. Fails with [`SlotLockClientError::InvalidResponse`] if the
+    /// sentinel reports a slot unlocked that wasn't requested.
+    pub async fn batch_unlock_slot_if_current(
+        &self,
         current_block: u64,
         btc_block: u64,
         slots: Vec<SlotIdentifier>,
-    ) -> Result<BatchUnlockSlotResponse, Box<dyn std::error::Error>> {
+        expected_current_values: Vec<ExpectedCurrentValue>,
+    ) -> Result<BatchUnlockSlotResponse, SlotLockClientError> {
         let response = self
-            .client
-            .batch_unlock_slot(BatchUnlockSlotRequest {
+            .call(
+                "BatchUnlockSlot",
+                BatchUnlockSlotRequest {
+                    namespace: self.namespace.clone(),
+                    current_block,
+                    btc_block,
+                    slots: slots.clone(),
+                    expected_current_values,
+                },
+                |mut client, request| async move { client.batch_unlock_slot(request).await },
+            )
+            .await?;
+        self.observe_batch_size_hints(&response);
+
+        let mut response = response.into_inner();
+        response.slots = expect_requested(&slots, response.slots)
+            .map_err(SlotLockClientError::InvalidResponse)?;
+        Ok(response)
+    }
+
+    pub async fn replace_lock_tx(
+        &self,
+        old_btc_txid: String,
+        new_btc_txid: String,
+    ) -> Result<tonic::Response<ReplaceLockTxResponse>, tonic::Status> {
+        self.call(
+            "ReplaceLockTx",
+            ReplaceLockTxRequest {
+                namespace: self.namespace.clone(),
+                old_btc_txid,
+                new_btc_txid,
+            },
+            |mut client, request| async move { client.replace_lock_tx(request).await },
+        )
+        .await
+    }
+
+    /// Ends every active lock on `btc_txid` at `current_block`, reverting the slots if `revert`
+    pub async fn unlock_by_txid(
+        &self,
+        btc_txid: String,
+        current_block: u64,
+        revert: bool,
+    ) -> Result<tonic::Response<UnlockByTxidResponse>, tonic::Status> {
+        self.call(
+            "UnlockByTxid",
+            UnlockByTxidRequest {
+                namespace: self.namespace.clone(),
+                btc_txid,
+                current_block,
+                revert,
+            },
+            |mut client, request| async move { client.unlock_by_txid(request).await },
+        )
+        .await
+    }
+
+    /// Reserves slots for a block being built. The slots are locked by committing the returned
+    /// reservation with [`SlotLockClient::commit_lock`] before it times out.
+    pub async fn prepare_lock(
+        &self,
+        locked_at_block: u64,
+        btc_block: u64,
+        slots: Vec<SlotData>,
+    ) -> Result<tonic::Response<PrepareLockResponse>, tonic::Status> {
+        let response = self
+            .call(
+                "PrepareLock",
+                PrepareLockRequest {
+                    namespace: self.namespace.clone(),
+                    locked_at_block,
+                    btc_block,
+                    slots,
+                },
+                |mut client, request| async move { client.prepare_lock(request).await },
+            )
+            .await?;
+        self.observe_batch_size_hints(&response);
+        Ok(response)
+    }
+
+    /// Locks the slots of a reservation made by [`SlotLockClient::prepare_lock`]
+    pub async fn commit_lock(
+        &self,
+        reservation_id: u64,
+    ) -> Result<tonic::Response<CommitLockResponse>, tonic::Status> {
+        let response = self
+            .call(
+                "CommitLock",
+                CommitLockRequest {
+                    namespace: self.namespace.clone(),
+                    reservation_id,
+                },
+                |mut client, request| async move { client.commit_lock(request).await },
+            )
+            .await?;
+        self.observe_batch_size_hints(&response);
+        Ok(response)
+    }
+
+    /// Ends the active locks that have settled as of `current_block`, returning their final
+    /// statuses
+    pub async fn finalize_block(
+        &self,
+        current_block: u64,
+        btc_block: u64,
+    ) -> Result<tonic::Response<FinalizeBlockResponse>, tonic::Status> {
+        self.call_idempotent(
+            "FinalizeBlock",
+            FinalizeBlockRequest {
+                namespace: self.namespace.clone(),
                 current_block,
                 btc_block,
+            },
+            |mut client, request| async move { client.finalize_block(request).await },
+        )
+        .await
+    }
+
+    /// Returns the server's version and labels, and adopts the batch sizes it advertises
+    pub async fn get_server_info(
+        &self,
+    ) -> Result<tonic::Response<GetServerInfoResponse>, tonic::Status> {
+        let response = self
+            .call_idempotent(
+                "GetServerInfo",
+                GetServerInfoRequest {},
+                |mut client, request| async move { client.get_server_info(request).await },
+            )
+            .await?;
+        *self.shared.batch_size_hints.lock().unwrap() = BatchSizeHints {
+            preferred: response.get_ref().preferred_batch_size,
+            max: response.get_ref().max_batch_size,
+        };
+        Ok(response)
+    }
+
+    /// Sends `items` in requests of [`SlotLockClient::batch_size`] items built by `request`,
+    /// following the batch sizes the server advertises as they change and retrying requests
+    /// rejected with `BATCH_TOO_LARGE` in smaller chunks. Returns the results `results` takes from
+    /// each response, in the order of `items`, or fails with
+    /// [`SlotLockClientError::InvalidResponse`] if it finds them not to match the request.
+    async fn call_chunked<I, T, R, O, F, Fut>(
+        &self,
+        method: &'static str,
+        mut items: Vec<I>,
+        idempotent: bool,
+        request: impl Fn(Vec<I>) -> T,
+        rpc: F,
+        results: impl Fn(&[I], R) -> Result<Vec<O>, String>,
+    ) -> Result<Vec<O>, SlotLockClientError>
+    where
+        I: Clone,
+        T: Clone,
+        F: Fn(SlotLockServiceClient<Channel>, tonic::Request<T>) -> Fut,
+        Fut: Future<Output = Result<tonic::Response<R>, tonic::Status>>,
+    {
+        let mut merged = Vec::with_capacity(items.len());
+        while !items.is_empty() {
+            let chunk: Vec<_> = items.drain(..self.batch_size().min(items.len())).collect();
+            let response = if idempotent {
+                self.call_idempotent(method, request(chunk.clone()), &rpc)
+                    .await
+            } else {
+                self.call(method, request(chunk.clone()), &rpc).await
+            };
+            match response {
+                Ok(response) => {
+                    self.observe_batch_size_hints(&response);
+                    let results = results(&chunk, response.into_inner())
+                        .map_err(SlotLockClientError::InvalidResponse)?;
+                    merged.extend(results);
+                }
+                Err(status) if self.shrink_batch_size(&status, chunk.len()) => {
+                    items.splice(0..0, chunk);
+                }
+                Err(status) => return Err(status.into()),
+            }
+        }
+        Ok(merged)
+    }
+
+    /// Locks slots in requests of [`SlotLockClient::batch_size`] slots, following the batch
+    /// sizes the server advertises as they change. Each request is locked atomically, but the
+    /// batch as a whole isn't: if a request fails, the slots of earlier requests stay locked.
+    pub async fn batch_lock_slot_chunked(
+        &self,
+        locked_at_block: u64,
+        btc_block: u64,
+        slots: Vec<SlotData>,
+    ) -> Result<Vec<SlotLockStatus>, SlotLockClientError> {
+        self.call_chunked(
+            "BatchLockSlot",
+            slots,
+            false,
+            |slots| BatchLockSlotRequest {
+                namespace: self.namespace.clone(),
+                locked_at_block,
+                btc_block,
                 slots,
+                contract_slots: Vec::new(),
+                atomic: false,
+            },
+            |mut client, request| async move { client.batch_lock_slot(request).await },
+            |chunk, response| expect_statuses(chunk.len(), response.slots),
+        )
+        .await
+    }
+
+    /// Locks slots grouped by contract like [`SlotLockClient::batch_lock_contract_slots`], in
+    /// requests of [`SlotLockClient::batch_size`] slots like
+    /// [`SlotLockClient::batch_lock_slot_chunked`]. A contract's slots may be split across
+    /// requests. The statuses are returned in the order of the groups and their slots.
+    pub async fn batch_lock_contract_slots_chunked(
+        &self,
+        locked_at_block: u64,
+        btc_block: u64,
+        contract_slots: Vec<ContractSlots>,
+    ) -> Result<Vec<SlotLockStatus>, SlotLockClientError> {
+        let slots = contract_slots
+            .into_iter()
+            .flat_map(|group| {
+                let contract_address = group.contract_address;
+                group
+                    .slots
+                    .into_iter()
+                    .map(move |slot| (contract_address.clone(), slot))
             })
-            .await?;
+            .collect();
+        self.call_chunked(
+            "BatchLockSlot",
+            slots,
+            false,
+            |slots| BatchLockSlotRequest {
+                namespace: self.namespace.clone(),
+                locked_at_block,
+                btc_block,
+                slots: Vec::new(),
+                contract_slots: group_by_contract(slots),
+                atomic: false,
+            },
+            |mut client, request| async move { client.batch_lock_slot(request).await },
+            |chunk, response| expect_statuses(chunk.len(), response.slots),
+        )
+        .await
+    }
 
-        Ok(response.into_inner())
+    /// Queries slot statuses in requests of [`SlotLockClient::batch_size`] slots, following the
+    /// batch sizes the server advertises as they change
+    pub async fn batch_get_slot_status_chunked(
+        &self,
+        current_block: u64,
+        btc_block: u64,
+        slots: Vec<SlotIdentifier>,
+    ) -> Result<Vec<GetSlotStatusResponse>, SlotLockClientError> {
+        self.call_chunked(
+            "BatchGetSlotStatus",
+            slots,
+            true,
+            |slots| BatchGetSlotStatusRequest {
+                namespace: self.namespace.clone(),
+                max_staleness_blocks: self.max_staleness_blocks,
+                current_block,
+                btc_block,
+                slots,
+            },
+            |mut client, request| async move { client.batch_get_slot_status(request).await },
+            |chunk, response| expect_statuses(chunk.len(), response.slots),
+        )
+        .await
+    }
+
+    /// Reports slot statuses like [`SlotLockClient::batch_get_slot_status_chunked`], without
+    /// unlocking or reverting settled locks
+    pub async fn batch_peek_slot_status_chunked(
+        &self,
+        current_block: u64,
+        btc_block: u64,
+        slots: Vec<SlotIdentifier>,
+    ) -> Result<Vec<GetSlotStatusResponse>, SlotLockClientError> {
+        self.call_chunked(
+            "BatchPeekSlotStatus",
+            slots,
+            true,
+            |slots| BatchGetSlotStatusRequest {
+                namespace: self.namespace.clone(),
+                max_staleness_blocks: self.max_staleness_blocks,
+                current_block,
+                btc_block,
+                slots,
+            },
+            |mut client, request| async move { client.batch_peek_slot_status(request).await },
+            |chunk, response| expect_statuses(chunk.len(), response.slots),
+        )
+        .await
+    }
+
+    /// Polls a slot's status every `poll_interval` until its lock is no longer active, returning
+    /// its terminal status and values. Fails with [`SlotLockClientError::StillLocked`] if the
+    /// slot is still locked at `deadline`, or its status hasn't been received by then.
+    pub async fn wait_for_unlock(
+        &self,
+        current_block: u64,
+        btc_block: u64,
+        slot: SlotIdentifier,
+        poll_interval: Duration,
+        deadline: Instant,
+    ) -> Result<GetSlotStatusResponse, SlotLockClientError> {
+        loop {
+            let query = self.get_slot_status(
+                current_block,
+                btc_block,
+                slot.contract_address.clone(),
+                slot.slot_index.clone(),
+            );
+            let status = tokio::time::timeout_at(deadline.into(), query)
+                .await
+                .map_err(|_| SlotLockClientError::StillLocked(1))??
+                .into_inner();
+            if !SlotStatus::from(status.status()).is_locked() {
+                return Ok(status);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(SlotLockClientError::StillLocked(1));
+            }
+            tokio::time::sleep(poll_interval.min(deadline - now)).await;
+        }
+    }
+
+    /// Waits for each of `slots` like [`SlotLockClient::wait_for_unlock`], polling the slots
+    /// still locked in chunked batches, and returns their terminal statuses in order
+    pub async fn wait_for_unlock_batch(
+        &self,
+        current_block: u64,
+        btc_block: u64,
+        slots: Vec<SlotIdentifier>,
+        poll_interval: Duration,
+        deadline: Instant,
+    ) -> Result<Vec<GetSlotStatusResponse>, SlotLockClientError> {
+        let mut statuses = vec![None; slots.len()];
+        let mut pending: Vec<usize> = (0..slots.len()).collect();
+        while !pending.is_empty() {
+            let polled = pending.iter().map(|&i| slots[i].clone()).collect();
+            let query = self.batch_get_slot_status_chunked(current_block, btc_block, polled);
+            let polled = tokio::time::timeout_at(deadline.into(), query)
+                .await
+                .map_err(|_| SlotLockClientError::StillLocked(pending.len()))??;
+            let mut locked = Vec::new();
+            for (i, status) in pending.into_iter().zip(polled) {
+                if SlotStatus::from(status.status()).is_locked() {
+                    locked.push(i);
+                } else {
+                    statuses[i] = Some(status);
+                }
+            }
+            pending = locked;
+            if pending.is_empty() {
+                break;
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(SlotLockClientError::StillLocked(pending.len()));
+            }
+            tokio::time::sleep(poll_interval.min(deadline - now)).await;
+        }
+        Ok(statuses.into_iter().flatten().collect())
+    }
+
+    /// Unlocks slots in requests of [`SlotLockClient::batch_size`] slots, following the batch
+    /// sizes the server advertises as they change. If a request fails, the slots of earlier
+    /// requests stay unlocked.
+    pub async fn batch_unlock_slot_chunked(
+        &self,
+        current_block: u64,
+        btc_block: u64,
+        slots: Vec<SlotIdentifier>,
+    ) -> Result<Vec<SlotIdentifier>, SlotLockClientError> {
+        self.call_chunked(
+            "BatchUnlockSlot",
+            slots,
+            false,
+            |slots| BatchUnlockSlotRequest {
+                namespace: self.namespace.clone(),
+                current_block,
+                btc_block,
+                slots,
+                expected_current_values: Vec::new(),
+            },
+            |mut client, request| async move { client.batch_unlock_slot(request).await },
+            |chunk, response| expect_requested(chunk, response.slots),
+        )
+        .await
+    }
+
+    /// Returns the sentinel's version, thresholds, Bitcoin network and state, to check it is
+    /// compatible and correctly configured
+    pub async fn get_sentinel_info(
+        &self,
+    ) -> Result<tonic::Response<GetSentinelInfoResponse>, tonic::Status> {
+        self.call_idempotent(
+            "GetSentinelInfo",
+            GetSentinelInfoRequest {
+                namespace: self.namespace.clone(),
+            },
+            |mut client, request| async move { client.get_sentinel_info(request).await },
+        )
+        .await
+    }
+
+    /// Streams the locks, unlocks and reverts of the client's namespace from now on, only those
+    /// of `contract_address` unless it is empty
+    pub async fn subscribe_slot_events(
+        &self,
+        contract_address: String,
+    ) -> Result<tonic::Response<tonic::Streaming<SlotEvent>>, tonic::Status> {
+        self.call(
+            "SubscribeSlotEvents",
+            SubscribeSlotEventsRequest {
+                namespace: Some(self.namespace.clone()),
+                contract_address,
+            },
+            |mut client, request| async move { client.subscribe_slot_events(request).await },
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Client of an endpoint that is never connected to, for calls whose RPC is faked
+    fn client(retry_policy: Option<RetryPolicy>) -> SlotLockClient {
+        client_of("http://127.0.0.1:1", retry_policy)
+    }
+
+    /// Client connecting to `addr` on its first call
+    fn client_of(addr: &str, retry_policy: Option<RetryPolicy>) -> SlotLockClient {
+        let channel = Endpoint::from_shared(addr.to_string())
+            .unwrap()
+            .connect_lazy();
+        SlotLockClient {
+            endpoints: vec![(addr.to_string(), channel)].into(),
+            shared: Arc::default(),
+            namespace: String::new(),
+            max_staleness_blocks: None,
+            authorization: None,
+            compression: None,
+            metadata: Arc::new([]),
+            interceptors: Arc::new([]),
+            call_hooks: Arc::new([]),
+            retry_policy,
+        }
+    }
+
+    #[test]
+    fn test_retry_policy_delays() {
+        let ms = Duration::from_millis;
+        let policy = RetryPolicy::exponential(5, ms(100))
+            .with_max_delay(ms(350))
+            .with_jitter(false);
+        // Doubling from the base delay up to the maximum, until the attempts run out
+        assert_eq!(policy.delay(1, ms(0), None), Some(ms(100)));
+        assert_eq!(policy.delay(2, ms(0), None), Some(ms(200)));
+        assert_eq!(policy.delay(3, ms(0), None), Some(ms(350)));
+        assert_eq!(policy.delay(4, ms(0), None), Some(ms(350)));
+        assert_eq!(policy.delay(5, ms(0), None), None);
+        // The server's hint is waited at least
+        assert_eq!(policy.delay(1, ms(0), Some(ms(1000))), Some(ms(1000)));
+        assert_eq!(policy.delay(2, ms(0), Some(ms(50))), Some(ms(200)));
+
+        let limited = policy.with_max_elapsed(ms(250));
+        assert_eq!(limited.delay(1, ms(100), None), Some(ms(100)));
+        assert_eq!(limited.delay(2, ms(0), None), Some(ms(200)));
+        assert_eq!(limited.delay(2, ms(100), None), None);
+
+        let jittered = policy.with_jitter(true);
+        for attempts in 1..5 {
+            let nominal = policy.delay(attempts, ms(0), None).unwrap();
+            assert!(jittered.delay(attempts, ms(0), None).unwrap() <= nominal);
+        }
+
+        // Late attempts don't overflow the backoff
+        let unbounded =
+            RetryPolicy::exponential(u32::MAX, Duration::from_secs(1)).with_jitter(false);
+        assert_eq!(
+            unbounded.delay(1000, ms(0), None),
+            Some(Duration::from_secs(10))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_retries_only_idempotent_calls_while_unavailable() {
+        let policy = RetryPolicy::exponential(3, Duration::from_millis(1)).with_jitter(false);
+        let attempts = AtomicUsize::new(0);
+        let failing = |code: Code| {
+            let attempts = &attempts;
+            move |_, _| {
+                attempts.fetch_add(1, Ordering::Relaxed);
+                async move { Err::<tonic::Response<()>, _>(tonic::Status::new(code, "failed")) }
+            }
+        };
+        let attempted = || attempts.swap(0, Ordering::Relaxed);
+
+        let retrying = client(Some(policy));
+        let status = retrying
+            .call_idempotent("GetSlotStatus", (), failing(Code::Unavailable))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::Unavailable);
+        assert_eq!(attempted(), 3);
+
+        // Other errors aren't retried
+        for code in [
+            Code::ResourceExhausted,
+            Code::Internal,
+            Code::DeadlineExceeded,
+        ] {
+            let status = retrying
+                .call_idempotent("GetSlotStatus", (), failing(code))
+                .await
+                .unwrap_err();
+            assert_eq!(status.code(), code);
+            assert_eq!(attempted(), 1);
+        }
+
+        // Calls that aren't safe to repeat, like locks, are sent once
+        retrying
+            .call("LockSlot", (), failing(Code::Unavailable))
+            .await
+            .unwrap_err();
+        assert_eq!(attempted(), 1);
+
+        // Nor is anything retried without a policy
+        client(None)
+            .call_idempotent("GetSlotStatus", (), failing(Code::Unavailable))
+            .await
+            .unwrap_err();
+        assert_eq!(attempted(), 1);
+
+        // A call that recovers returns its response
+        let recovering = |_, _| {
+            let attempt = attempts.fetch_add(1, Ordering::Relaxed);
+            async move {
+                if attempt < 2 {
+                    Err(tonic::Status::unavailable("restarting"))
+                } else {
+                    Ok(tonic::Response::new(attempt))
+                }
+            }
+        };
+        let response = retrying
+            .call_idempotent("GetSlotStatus", (), recovering)
+            .await
+            .unwrap();
+        assert_eq!(response.into_inner(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_waits_for_unlock_until_the_deadline() {
+        // The listener never accepts, so calls are never answered
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = client_of(&format!("http://{}", listener.local_addr().unwrap()), None);
+        let slot = |idx: u8| SlotIdentifier {
+            contract_address: "0x123".to_string(),
+            slot_index: vec![idx],
+        };
+        let poll_interval = Duration::from_millis(10);
+        let started = Instant::now();
+
+        let deadline = Instant::now() + Duration::from_millis(50);
+        let error = client
+            .wait_for_unlock(101, 100, slot(1), poll_interval, deadline)
+            .await
+            .unwrap_err();
+        assert!(matches!(error, SlotLockClientError::StillLocked(1)));
+        assert!(Instant::now() >= deadline);
+
+        let deadline = Instant::now() + Duration::from_millis(50);
+        let error = client
+            .wait_for_unlock_batch(101, 100, vec![slot(1), slot(2)], poll_interval, deadline)
+            .await
+            .unwrap_err();
+        assert!(matches!(error, SlotLockClientError::StillLocked(2)));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn test_splits_batches_into_chunks() {
+        use sova_sentinel_proto::error_info::with_error_info;
+
+        let client = client(None);
+        let sent = Mutex::new(Vec::new());
+        let chunked = |items: Vec<u32>, max: Option<usize>| {
+            let sent = &sent;
+            client.call_chunked(
+                "BatchLockSlot",
+                items,
+                false,
+                |chunk| chunk,
+                move |_, request: tonic::Request<Vec<u32>>| {
+                    let chunk = request.into_inner();
+                    sent.lock().unwrap().push(chunk.len());
+                    async move {
+                        match max {
+                            Some(max) if chunk.len() > max => Err(with_error_info(
+                                Code::InvalidArgument,
+                                "too many slots",
+                                BATCH_TOO_LARGE,
+                                std::collections::HashMap::from([(
+                                    "max_batch_size".to_string(),
+                                    max.to_string(),
+                                )]),
+                            )),
+                            _ => Ok(tonic::Response::new(chunk)),
+                        }
+                    }
+                },
+                |_, response| Ok(response),
+            )
+        };
+        let sent_sizes = || std::mem::take(&mut *sent.lock().unwrap());
+
+        // Batches are sent in chunks of the default size, and their results merged in order
+        let items: Vec<u32> = (0..250).collect();
+        assert_eq!(chunked(items.clone(), None).await.unwrap(), items);
+        assert_eq!(sent_sizes(), vec![100, 100, 50]);
+
+        // A chunk rejected as too large is split at the server's maximum, which later chunks use
+        assert_eq!(chunked(items.clone(), Some(40)).await.unwrap(), items);
+        assert_eq!(sent_sizes(), vec![100, 40, 40, 40, 40, 40, 40, 10]);
+        assert_eq!(client.batch_size(), 40);
+
+        // Other rejections end the batch
+        let error = chunked(items, Some(0)).await.unwrap_err();
+        assert!(
+            matches!(error, SlotLockClientError::Grpc(status) if status.code() == Code::InvalidArgument)
+        );
+        assert_eq!(sent_sizes(), vec![40]);
     }
 }
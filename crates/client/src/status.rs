@@ -0,0 +1,216 @@
+use sova_sentinel_proto::proto::{
+    get_slot_status_response, lock_slot_response, slot_lock_status, GetSlotStatusResponse,
+    LockSlotResponse, SlotLockStatus,
+};
+
+/// Status of a slot reported by the status calls
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SlotStatus {
+    /// Not a status this client knows, e.g. one added by a newer sentinel
+    Unknown,
+    Locked,
+    /// Locked, but an input of the lock's Bitcoin transaction was spent by a conflicting mempool
+    /// transaction, or the transaction was evicted from the mempool
+    AtRisk,
+    Unlocked,
+    Reverted,
+    /// Reverted because an input of the lock's Bitcoin transaction was spent by a conflicting
+    /// mined transaction
+    DoubleSpent,
+}
+
+impl SlotStatus {
+    /// Returns whether the lock is still active
+    pub fn is_locked(self) -> bool {
+        matches!(self, Self::Locked | Self::AtRisk)
+    }
+
+    /// Returns whether the slot must be restored to its revert value
+    pub fn is_reverted(self) -> bool {
+        matches!(self, Self::Reverted | Self::DoubleSpent)
+    }
+}
+
+impl From<get_slot_status_response::Status> for SlotStatus {
+    fn from(status: get_slot_status_response::Status) -> Self {
+        use get_slot_status_response::Status;
+        match status {
+            Status::Unknown => Self::Unknown,
+            Status::Locked => Self::Locked,
+            Status::AtRisk => Self::AtRisk,
+            Status::Unlocked => Self::Unlocked,
+            Status::Reverted => Self::Reverted,
+            Status::DoubleSpent => Self::DoubleSpent,
+        }
+    }
+}
+
+/// Outcome of locking a slot
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LockStatus {
+    /// Not a status this client knows, e.g. one added by a newer sentinel
+    Unknown,
+    Locked,
+    AlreadyLocked,
+    /// Not locked because another slot of its atomic batch was already locked
+    NotLocked,
+    /// Held by a `PrepareLock` reservation until it is committed or expires
+    Reserved,
+}
+
+impl From<lock_slot_response::Status> for LockStatus {
+    fn from(status: lock_slot_response::Status) -> Self {
+        use lock_slot_response::Status;
+        match status {
+            Status::Unknown => Self::Unknown,
+            Status::Locked => Self::Locked,
+            Status::AlreadyLocked => Self::AlreadyLocked,
+            Status::NotLocked => Self::NotLocked,
+        }
+    }
+}
+
+impl From<slot_lock_status::Status> for LockStatus {
+    fn from(status: slot_lock_status::Status) -> Self {
+        use slot_lock_status::Status;
+        match status {
+            Status::Unknown => Self::Unknown,
+            Status::Locked => Self::Locked,
+            Status::AlreadyLocked => Self::AlreadyLocked,
+            Status::NotLocked => Self::NotLocked,
+            Status::Reserved => Self::Reserved,
+        }
+    }
+}
+
+/// Status response of a slot with its status decoded. The other fields are read from `response`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlotStatusReport {
+    pub status: SlotStatus,
+    pub response: GetSlotStatusResponse,
+}
+
+impl From<GetSlotStatusResponse> for SlotStatusReport {
+    fn from(response: GetSlotStatusResponse) -> Self {
+        Self {
+            status: response.status().into(),
+            response,
+        }
+    }
+}
+
+/// Lock outcome of a slot with its status decoded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SlotLockOutcome {
+    pub status: LockStatus,
+    pub contract_address: String,
+    pub slot_index: Vec<u8>,
+}
+
+impl From<LockSlotResponse> for SlotLockOutcome {
+    fn from(response: LockSlotResponse) -> Self {
+        Self {
+            status: response.status().into(),
+            contract_address: response.contract_address,
+            slot_index: response.slot_index,
+        }
+    }
+}
+
+impl From<SlotLockStatus> for SlotLockOutcome {
+    fn from(status: SlotLockStatus) -> Self {
+        Self {
+            status: status.status().into(),
+            contract_address: status.contract_address,
+            slot_index: status.slot_index,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decodes_slot_statuses() {
+        use get_slot_status_response::Status;
+
+        let decoded = [
+            Status::Unknown,
+            Status::Locked,
+            Status::AtRisk,
+            Status::Unlocked,
+            Status::Reverted,
+            Status::DoubleSpent,
+        ]
+        .map(SlotStatus::from);
+        assert_eq!(
+            decoded,
+            [
+                SlotStatus::Unknown,
+                SlotStatus::Locked,
+                SlotStatus::AtRisk,
+                SlotStatus::Unlocked,
+                SlotStatus::Reverted,
+                SlotStatus::DoubleSpent,
+            ]
+        );
+        assert_eq!(
+            decoded.map(SlotStatus::is_locked),
+            [false, true, true, false, false, false]
+        );
+        assert_eq!(
+            decoded.map(SlotStatus::is_reverted),
+            [false, false, false, false, true, true]
+        );
+
+        // Statuses added by a newer sentinel are unknown rather than an error
+        let report = SlotStatusReport::from(GetSlotStatusResponse {
+            status: 99,
+            contract_address: "0x123".to_string(),
+            ..Default::default()
+        });
+        assert_eq!(report.status, SlotStatus::Unknown);
+        assert_eq!(report.response.contract_address, "0x123");
+        let report = SlotStatusReport::from(GetSlotStatusResponse {
+            status: Status::DoubleSpent.into(),
+            ..Default::default()
+        });
+        assert!(report.status.is_reverted());
+    }
+
+    #[test]
+    fn test_decodes_lock_outcomes() {
+        let outcome = SlotLockOutcome::from(LockSlotResponse {
+            status: lock_slot_response::Status::AlreadyLocked.into(),
+            contract_address: "0x123".to_string(),
+            slot_index: vec![1],
+        });
+        assert_eq!(
+            outcome,
+            SlotLockOutcome {
+                status: LockStatus::AlreadyLocked,
+                contract_address: "0x123".to_string(),
+                slot_index: vec![1],
+            }
+        );
+
+        // Only batch statuses report reservations
+        let outcome = SlotLockOutcome::from(SlotLockStatus {
+            status: slot_lock_status::Status::Reserved.into(),
+            contract_address: "0x123".to_string(),
+            slot_index: vec![2],
+        });
+        assert_eq!(outcome.status, LockStatus::Reserved);
+        assert_eq!(outcome.slot_index, vec![2]);
+        let outcome = SlotLockOutcome::from(SlotLockStatus {
+            status: 99,
+            ..Default::default()
+        });
+        assert_eq!(outcome.status, LockStatus::Unknown);
+        assert_eq!(
+            LockStatus::from(lock_slot_response::Status::NotLocked),
+            LockStatus::NotLocked
+        );
+    }
+}
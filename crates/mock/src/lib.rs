@@ -0,0 +1,224 @@
+//! In-process sentinel for integration tests of code that calls one. It serves the real slot
+//! lock service over gRPC on a local port, backed by the server's Bitcoin node and slot store
+//! doubles instead of bitcoind and a database file, and changes their state as scripted by a
+//! [`Scenario`].
+
+mod scenario;
+mod service;
+
+pub use scenario::{Action, RunFn, Scenario, Trigger};
+pub use sova_sentinel_server::test_util::{
+    MockBitcoinService, MockFailure, MockSlotStore, DEFAULT_CONFIRMATION_THRESHOLD,
+    DEFAULT_REVERT_THRESHOLD,
+};
+
+use anyhow::Result;
+use scenario::Script;
+use service::ScriptedSlotLockService;
+use sova_sentinel_proto::proto::slot_lock_service_server::SlotLockServiceServer;
+use sova_sentinel_server::service::SlotLockServiceImpl;
+use std::net::SocketAddr;
+use tokio::net::TcpListener;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tonic::transport::server::TcpIncoming;
+use tonic::transport::Server;
+
+/// Options of a [`MockSentinel`], built by [`MockSentinel::builder`]
+pub struct MockSentinelBuilder {
+    confirmation_threshold: u32,
+    revert_threshold: u32,
+    scenario: Scenario,
+}
+
+impl MockSentinelBuilder {
+    pub fn with_confirmation_threshold(mut self, confirmation_threshold: u32) -> Self {
+        self.confirmation_threshold = confirmation_threshold;
+        self
+    }
+
+    pub fn with_revert_threshold(mut self, revert_threshold: u32) -> Self {
+        self.revert_threshold = revert_threshold;
+        self
+    }
+
+    /// Sets the scenario played as calls arrive
+    pub fn with_scenario(mut self, scenario: Scenario) -> Self {
+        self.scenario = scenario;
+        self
+    }
+
+    /// Starts serving on a free port of the loopback interface
+    pub async fn start(self) -> Result<MockSentinel> {
+        let bitcoin =
+            MockBitcoinService::new().with_confirmation_threshold(self.confirmation_threshold);
+        let store = MockSlotStore::new()?;
+        let script = Script::new(self.scenario, bitcoin.clone(), store.clone());
+        let service = ScriptedSlotLockService::new(
+            SlotLockServiceImpl::new(store.database(), bitcoin.clone(), self.revert_threshold),
+            script.clone(),
+        );
+
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let incoming = TcpIncoming::from_listener(listener, true, None)
+            .map_err(|e| anyhow::anyhow!("Failed to listen on {}: {}", addr, e))?;
+        let (shutdown, stopped) = oneshot::channel::<()>();
+        let server = tokio::spawn(
+            Server::builder()
+                .add_service(SlotLockServiceServer::new(service))
+                .serve_with_incoming_shutdown(incoming, async {
+                    stopped.await.ok();
+                }),
+        );
+
+        Ok(MockSentinel {
+            addr,
+            bitcoin,
+            store,
+            script,
+            shutdown: Some(shutdown),
+            server,
+        })
+    }
+}
+
+/// Sentinel serving on a local port until dropped
+pub struct MockSentinel {
+    addr: SocketAddr,
+    bitcoin: MockBitcoinService,
+    store: MockSlotStore,
+    script: Script,
+    shutdown: Option<oneshot::Sender<()>>,
+    server: JoinHandle<Result<(), tonic::transport::Error>>,
+}
+
+impl MockSentinel {
+    pub fn builder() -> MockSentinelBuilder {
+        MockSentinelBuilder {
+            confirmation_threshold: DEFAULT_CONFIRMATION_THRESHOLD,
+            revert_threshold: DEFAULT_REVERT_THRESHOLD,
+            scenario: Scenario::new(),
+        }
+    }
+
+    /// Starts a sentinel with the default thresholds and no scenario
+    pub async fn start() -> Result<Self> {
+        Self::builder().start().await
+    }
+
+    /// Returns the URL to connect clients to, e.g. `http://127.0.0.1:41234`
+    pub fn endpoint(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Returns the Bitcoin node double, whose state can also be changed directly
+    pub fn bitcoin(&self) -> &MockBitcoinService {
+        &self.bitcoin
+    }
+
+    /// Returns the slot store double, e.g. to seed locks or inspect reverts
+    pub fn store(&self) -> &MockSlotStore {
+        &self.store
+    }
+
+    /// Returns the number of slot lock calls received so far
+    pub fn calls(&self) -> u64 {
+        self.script.calls()
+    }
+
+    /// Runs an action right away instead of on a trigger
+    pub fn run(&self, action: Action) -> Result<()> {
+        self.script.run(action)
+    }
+
+    /// Stops serving and waits for the server to shut down
+    pub async fn shutdown(mut self) -> Result<()> {
+        if let Some(shutdown) = self.shutdown.take() {
+            shutdown.send(()).ok();
+        }
+        (&mut self.server).await??;
+        Ok(())
+    }
+}
+
+impl Drop for MockSentinel {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            shutdown.send(()).ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sova_sentinel_client::{SlotLockClient, SlotStatus, SlotStatusReport};
+    use sova_sentinel_proto::proto::SlotData;
+
+    const TXID: &str = "0000000000000000000000000000000000000000000000000000000000000001";
+    const OTHER_TXID: &str = "0000000000000000000000000000000000000000000000000000000000000002";
+
+    fn slot(index: u8, btc_txid: &str) -> SlotData {
+        SlotData {
+            contract_address: "0x123".to_string(),
+            slot_index: vec![index],
+            revert_value: vec![1],
+            current_value: vec![2],
+            btc_txid: btc_txid.to_string(),
+            ..Default::default()
+        }
+    }
+
+    async fn status(client: &SlotLockClient, block: u64, btc_block: u64, index: u8) -> SlotStatus {
+        let response = client
+            .get_slot_status(block, btc_block, "0x123".to_string(), vec![index])
+            .await
+            .unwrap();
+        SlotStatusReport::from(response.into_inner()).status
+    }
+
+    #[tokio::test]
+    async fn test_scenario_confirms_and_reverts() -> Result<(), Box<dyn std::error::Error>> {
+        let sentinel = MockSentinel::builder()
+            .with_scenario(
+                Scenario::new()
+                    .at_call(4, Action::Confirm(TXID.to_string()))
+                    .at_block(
+                        120,
+                        Action::Revert {
+                            contract_address: "0x123".to_string(),
+                            slot_index: vec![2],
+                        },
+                    )
+                    .at_btc_block(
+                        105,
+                        Action::FailCalls(1, tonic::Status::unavailable("restarting")),
+                    ),
+            )
+            .start()
+            .await?;
+        let client = SlotLockClient::connect(sentinel.endpoint()).await?;
+
+        client.lock_slot(100, 100, slot(1, TXID)).await?;
+        client.lock_slot(100, 100, slot(2, OTHER_TXID)).await?;
+        assert_eq!(status(&client, 101, 100, 1).await, SlotStatus::Locked);
+        // The transaction confirms before the fourth call
+        assert_eq!(status(&client, 101, 100, 1).await, SlotStatus::Unlocked);
+
+        // Calls for a later Bitcoin block fail once, as scripted
+        let error = client
+            .get_slot_status(102, 105, "0x123".to_string(), vec![1])
+            .await
+            .unwrap_err();
+        assert_eq!(error.code(), tonic::Code::Unavailable);
+
+        // Slot 2's lock is reverted before the first call for block 120
+        assert_eq!(status(&client, 119, 105, 2).await, SlotStatus::Locked);
+        assert_eq!(status(&client, 120, 105, 2).await, SlotStatus::Reverted);
+        assert_eq!(sentinel.calls(), 7);
+
+        sentinel.shutdown().await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,196 @@
+use anyhow::Result;
+use sova_sentinel_server::db::UnlockReason;
+use sova_sentinel_server::service::DoubleSpendStatus;
+use sova_sentinel_server::test_util::{MockBitcoinService, MockFailure, MockSlotStore};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use tonic::Status;
+
+/// Length slot indexes are stored with, that of an EVM storage slot
+const SLOT_INDEX_LEN: usize = 32;
+
+/// When a step of a [`Scenario`] runs. Steps run before the call that meets their trigger is
+/// served, so that call already sees their effect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trigger {
+    /// Before the nth call to the slot lock service, counting from 1
+    Call(u64),
+    /// Before the first call for Sova block `block` or later, by its `current_block` or
+    /// `locked_at_block`
+    Block(u64),
+    /// Before the first call for Bitcoin block `btc_block` or later
+    BtcBlock(u64),
+}
+
+/// Code an [`Action::Run`] step runs
+pub type RunFn = dyn Fn(&MockBitcoinService, &MockSlotStore) -> Result<()> + Send + Sync;
+
+/// Change a step of a [`Scenario`] makes to the sentinel's world
+pub enum Action {
+    /// Gives a transaction exactly enough confirmations to count as confirmed, unlocking its
+    /// locks on their next status query
+    Confirm(String),
+    /// Sets the number of confirmations of a transaction
+    SetConfirmations(String, u32),
+    /// Spends an input of a transaction in a conflicting mempool transaction, reporting its locks
+    /// as `AT_RISK`
+    ConflictInMempool(String),
+    /// Spends an input of a transaction in a conflicting mined transaction, reverting its locks
+    /// as `DOUBLE_SPENT`
+    DoubleSpend(String),
+    /// Reverts the lock of a slot of the default namespace, as if it reached the revert
+    /// threshold at the latest Sova block called for. Slot indexes shorter than 32 bytes are left-padded
+    /// like the sentinel does.
+    Revert {
+        contract_address: String,
+        slot_index: Vec<u8>,
+    },
+    /// Fails the next calls to the slot lock service with `status`
+    FailCalls(usize, Status),
+    /// Fails the next calls to the Bitcoin node
+    FailBitcoin(usize, MockFailure),
+    /// Fails every write to the slot locks with the given message until [`Action::ClearFailures`]
+    FailWrites(String),
+    /// Lets writes succeed again
+    ClearFailures,
+    /// Runs arbitrary code against the Bitcoin node and slot store
+    Run(Box<RunFn>),
+}
+
+/// Script of changes a [`crate::MockSentinel`] makes as calls arrive, e.g. confirming a
+/// transaction at the third call and reverting a lock at block 120
+#[derive(Default)]
+pub struct Scenario {
+    steps: Vec<(Trigger, Action)>,
+}
+
+impl Scenario {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a step running `action` on `trigger`. Steps with the same trigger run in the order
+    /// they were added.
+    pub fn on(mut self, trigger: Trigger, action: Action) -> Self {
+        self.steps.push((trigger, action));
+        self
+    }
+
+    pub fn at_call(self, call: u64, action: Action) -> Self {
+        self.on(Trigger::Call(call), action)
+    }
+
+    pub fn at_block(self, block: u64, action: Action) -> Self {
+        self.on(Trigger::Block(block), action)
+    }
+
+    pub fn at_btc_block(self, btc_block: u64, action: Action) -> Self {
+        self.on(Trigger::BtcBlock(btc_block), action)
+    }
+}
+
+struct ScriptState {
+    calls: u64,
+    /// Latest Sova block called for
+    block: u64,
+    pending: Vec<(Trigger, Action)>,
+    failures: VecDeque<Status>,
+}
+
+/// Runs a scenario against the doubles behind a mock sentinel. Clones share their progress.
+#[derive(Clone)]
+pub(crate) struct Script {
+    bitcoin: MockBitcoinService,
+    store: MockSlotStore,
+    state: Arc<Mutex<ScriptState>>,
+}
+
+impl Script {
+    pub fn new(scenario: Scenario, bitcoin: MockBitcoinService, store: MockSlotStore) -> Self {
+        Self {
+            bitcoin,
+            store,
+            state: Arc::new(Mutex::new(ScriptState {
+                calls: 0,
+                block: 0,
+                pending: scenario.steps,
+                failures: VecDeque::new(),
+            })),
+        }
+    }
+
+    /// Returns the number of calls served so far
+    pub fn calls(&self) -> u64 {
+        self.state.lock().unwrap().calls
+    }
+
+    /// Runs `action` right away
+    pub fn run(&self, action: Action) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        self.apply(&mut state, action)
+    }
+
+    /// Counts a call for `block` and `btc_block`, if it carries them, and runs the steps it
+    /// triggers. Fails with the next queued failure, if any.
+    // Returns the status the service fails the call with as is
+    #[allow(clippy::result_large_err)]
+    pub fn advance(&self, block: Option<u64>, btc_block: Option<u64>) -> Result<(), Status> {
+        let mut state = self.state.lock().unwrap();
+        state.calls += 1;
+        state.block = state.block.max(block.unwrap_or_default());
+        let calls = state.calls;
+        let (due, pending) = std::mem::take(&mut state.pending)
+            .into_iter()
+            .partition::<Vec<_>, _>(|(trigger, _)| match *trigger {
+                Trigger::Call(call) => calls >= call,
+                Trigger::Block(at) => block.is_some_and(|block| block >= at),
+                Trigger::BtcBlock(at) => btc_block.is_some_and(|btc_block| btc_block >= at),
+            });
+        state.pending = pending;
+        for (trigger, action) in due {
+            self.apply(&mut state, action).map_err(|e| {
+                Status::internal(format!("Scenario step at {:?} failed: {:#}", trigger, e))
+            })?;
+        }
+        match state.failures.pop_front() {
+            Some(status) => Err(status),
+            None => Ok(()),
+        }
+    }
+
+    fn apply(&self, state: &mut ScriptState, action: Action) -> Result<()> {
+        match action {
+            Action::Confirm(txid) => self.bitcoin.add_confirmed_tx(&txid),
+            Action::SetConfirmations(txid, confirmations) => {
+                self.bitcoin.set_confirmations(&txid, confirmations)
+            }
+            Action::ConflictInMempool(txid) => self
+                .bitcoin
+                .set_double_spend(&txid, DoubleSpendStatus::AtRisk),
+            Action::DoubleSpend(txid) => self
+                .bitcoin
+                .set_double_spend(&txid, DoubleSpendStatus::Conflicted),
+            Action::Revert {
+                contract_address,
+                slot_index,
+            } => {
+                let mut padded = vec![0; SLOT_INDEX_LEN.saturating_sub(slot_index.len())];
+                padded.extend(slot_index);
+                self.store.unlock(
+                    &contract_address,
+                    &padded,
+                    state.block,
+                    UnlockReason::RevertThreshold,
+                )?
+            }
+            Action::FailCalls(count, status) => {
+                state.failures.extend(std::iter::repeat_n(status, count))
+            }
+            Action::FailBitcoin(count, failure) => self.bitcoin.fail_next_calls(count, failure),
+            Action::FailWrites(message) => self.store.fail_writes(&message)?,
+            Action::ClearFailures => self.store.clear_failures()?,
+            Action::Run(run) => run(&self.bitcoin, &self.store)?,
+        }
+        Ok(())
+    }
+}
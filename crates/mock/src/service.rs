@@ -0,0 +1,194 @@
+use crate::scenario::Script;
+use sova_sentinel_proto::proto::{
+    slot_lock_service_server::SlotLockService, BatchGetSlotStatusRequest,
+    BatchGetSlotStatusResponse, BatchLockSlotRequest, BatchLockSlotResponse,
+    BatchUnlockSlotRequest, BatchUnlockSlotResponse, CommitLockRequest, CommitLockResponse,
+    FinalizeBlockRequest, FinalizeBlockResponse, GetLockCommitmentRequest,
+    GetLockCommitmentResponse, GetLockInclusionProofRequest, GetLockInclusionProofResponse,
+    GetPublicKeyRequest, GetPublicKeyResponse, GetSentinelInfoRequest, GetSentinelInfoResponse,
+    GetServerInfoRequest, GetServerInfoResponse, GetSlotStatusRequest, GetSlotStatusResponse,
+    LockSlotRequest, LockSlotResponse, PrepareLockRequest, PrepareLockResponse,
+    ReplaceLockTxRequest, ReplaceLockTxResponse, SubscribeSlotEventsRequest, UnlockByTxidRequest,
+    UnlockByTxidResponse,
+};
+use tonic::{Request, Response, Status};
+
+/// SlotLock service that advances its scenario before serving each call
+pub(crate) struct ScriptedSlotLockService<S> {
+    inner: S,
+    script: Script,
+}
+
+impl<S: SlotLockService> ScriptedSlotLockService<S> {
+    pub fn new(inner: S, script: Script) -> Self {
+        Self { inner, script }
+    }
+}
+
+#[tonic::async_trait]
+impl<S: SlotLockService> SlotLockService for ScriptedSlotLockService<S> {
+    type SubscribeSlotEventsStream = S::SubscribeSlotEventsStream;
+
+    async fn lock_slot(
+        &self,
+        request: Request<LockSlotRequest>,
+    ) -> Result<Response<LockSlotResponse>, Status> {
+        let req = request.get_ref();
+        self.script
+            .advance(Some(req.locked_at_block), Some(req.btc_block))?;
+        self.inner.lock_slot(request).await
+    }
+
+    async fn get_slot_status(
+        &self,
+        request: Request<GetSlotStatusRequest>,
+    ) -> Result<Response<GetSlotStatusResponse>, Status> {
+        let req = request.get_ref();
+        self.script
+            .advance(Some(req.current_block), Some(req.btc_block))?;
+        self.inner.get_slot_status(request).await
+    }
+
+    async fn batch_lock_slot(
+        &self,
+        request: Request<BatchLockSlotRequest>,
+    ) -> Result<Response<BatchLockSlotResponse>, Status> {
+        let req = request.get_ref();
+        self.script
+            .advance(Some(req.locked_at_block), Some(req.btc_block))?;
+        self.inner.batch_lock_slot(request).await
+    }
+
+    async fn batch_get_slot_status(
+        &self,
+        request: Request<BatchGetSlotStatusRequest>,
+    ) -> Result<Response<BatchGetSlotStatusResponse>, Status> {
+        let req = request.get_ref();
+        self.script
+            .advance(Some(req.current_block), Some(req.btc_block))?;
+        self.inner.batch_get_slot_status(request).await
+    }
+
+    async fn batch_unlock_slot(
+        &self,
+        request: Request<BatchUnlockSlotRequest>,
+    ) -> Result<Response<BatchUnlockSlotResponse>, Status> {
+        let req = request.get_ref();
+        self.script
+            .advance(Some(req.current_block), Some(req.btc_block))?;
+        self.inner.batch_unlock_slot(request).await
+    }
+
+    async fn replace_lock_tx(
+        &self,
+        request: Request<ReplaceLockTxRequest>,
+    ) -> Result<Response<ReplaceLockTxResponse>, Status> {
+        self.script.advance(None, None)?;
+        self.inner.replace_lock_tx(request).await
+    }
+
+    async fn unlock_by_txid(
+        &self,
+        request: Request<UnlockByTxidRequest>,
+    ) -> Result<Response<UnlockByTxidResponse>, Status> {
+        self.script
+            .advance(Some(request.get_ref().current_block), None)?;
+        self.inner.unlock_by_txid(request).await
+    }
+
+    async fn prepare_lock(
+        &self,
+        request: Request<PrepareLockRequest>,
+    ) -> Result<Response<PrepareLockResponse>, Status> {
+        let req = request.get_ref();
+        self.script
+            .advance(Some(req.locked_at_block), Some(req.btc_block))?;
+        self.inner.prepare_lock(request).await
+    }
+
+    async fn commit_lock(
+        &self,
+        request: Request<CommitLockRequest>,
+    ) -> Result<Response<CommitLockResponse>, Status> {
+        self.script.advance(None, None)?;
+        self.inner.commit_lock(request).await
+    }
+
+    async fn finalize_block(
+        &self,
+        request: Request<FinalizeBlockRequest>,
+    ) -> Result<Response<FinalizeBlockResponse>, Status> {
+        let req = request.get_ref();
+        self.script
+            .advance(Some(req.current_block), Some(req.btc_block))?;
+        self.inner.finalize_block(request).await
+    }
+
+    async fn peek_slot_status(
+        &self,
+        request: Request<GetSlotStatusRequest>,
+    ) -> Result<Response<GetSlotStatusResponse>, Status> {
+        let req = request.get_ref();
+        self.script
+            .advance(Some(req.current_block), Some(req.btc_block))?;
+        self.inner.peek_slot_status(request).await
+    }
+
+    async fn batch_peek_slot_status(
+        &self,
+        request: Request<BatchGetSlotStatusRequest>,
+    ) -> Result<Response<BatchGetSlotStatusResponse>, Status> {
+        let req = request.get_ref();
+        self.script
+            .advance(Some(req.current_block), Some(req.btc_block))?;
+        self.inner.batch_peek_slot_status(request).await
+    }
+
+    async fn get_server_info(
+        &self,
+        request: Request<GetServerInfoRequest>,
+    ) -> Result<Response<GetServerInfoResponse>, Status> {
+        self.script.advance(None, None)?;
+        self.inner.get_server_info(request).await
+    }
+
+    async fn get_sentinel_info(
+        &self,
+        request: Request<GetSentinelInfoRequest>,
+    ) -> Result<Response<GetSentinelInfoResponse>, Status> {
+        self.script.advance(None, None)?;
+        self.inner.get_sentinel_info(request).await
+    }
+
+    async fn subscribe_slot_events(
+        &self,
+        request: Request<SubscribeSlotEventsRequest>,
+    ) -> Result<Response<Self::SubscribeSlotEventsStream>, Status> {
+        self.script.advance(None, None)?;
+        self.inner.subscribe_slot_events(request).await
+    }
+
+    async fn get_public_key(
+        &self,
+        request: Request<GetPublicKeyRequest>,
+    ) -> Result<Response<GetPublicKeyResponse>, Status> {
+        self.script.advance(None, None)?;
+        self.inner.get_public_key(request).await
+    }
+
+    async fn get_lock_commitment(
+        &self,
+        request: Request<GetLockCommitmentRequest>,
+    ) -> Result<Response<GetLockCommitmentResponse>, Status> {
+        self.script.advance(None, None)?;
+        self.inner.get_lock_commitment(request).await
+    }
+
+    async fn get_lock_inclusion_proof(
+        &self,
+        request: Request<GetLockInclusionProofRequest>,
+    ) -> Result<Response<GetLockInclusionProofResponse>, Status> {
+        self.script.advance(None, None)?;
+        self.inner.get_lock_inclusion_proof(request).await
+    }
+}
@@ -1,9 +1,20 @@
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("cargo:rerun-if-changed=src/proto/slot_lock.proto");
     println!("cargo:rerun-if-changed=src/proto/health.proto");
+    println!("cargo:rerun-if-changed=src/proto/admin.proto");
+    println!("cargo:rerun-if-changed=src/proto/revert_executor.proto");
+    println!("cargo:rerun-if-changed=src/proto/google/rpc/status.proto");
+    println!("cargo:rerun-if-changed=src/proto/google/rpc/error_details.proto");
 
     tonic_build::configure().compile_protos(
-        &["src/proto/slot_lock.proto", "src/proto/health.proto"],
+        &[
+            "src/proto/slot_lock.proto",
+            "src/proto/health.proto",
+            "src/proto/admin.proto",
+            "src/proto/revert_executor.proto",
+            "src/proto/google/rpc/status.proto",
+            "src/proto/google/rpc/error_details.proto",
+        ],
         &["src/proto"],
     )?;
     Ok(())
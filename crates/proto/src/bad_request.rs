@@ -0,0 +1,51 @@
+use crate::details::{detail, StatusDetails};
+use crate::error_info::INVALID_REQUEST;
+use crate::google::rpc::{bad_request::FieldViolation, BadRequest};
+use std::collections::HashMap;
+use tonic::{Code, Status};
+
+/// Builds an `INVALID_ARGUMENT` status listing the invalid fields of the request in a
+/// `google.rpc.BadRequest` detail, along with an `INVALID_REQUEST` `google.rpc.ErrorInfo`. The
+/// message joins the violations.
+pub fn with_field_violations(field_violations: Vec<FieldViolation>) -> Status {
+    let message = field_violations
+        .iter()
+        .map(|violation| format!("{}: {}", violation.field, violation.description))
+        .collect::<Vec<_>>()
+        .join("; ");
+    StatusDetails::new()
+        .bad_request(field_violations)
+        .error_info(INVALID_REQUEST, HashMap::new())
+        .into_status(Code::InvalidArgument, message)
+}
+
+/// Returns the invalid fields from the status' `google.rpc.BadRequest` detail, empty if it has
+/// none
+pub fn field_violations(status: &Status) -> Vec<FieldViolation> {
+    detail::<BadRequest>(status)
+        .map(|bad_request| bad_request.field_violations)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_field_violations_roundtrip() {
+        let status = with_field_violations(vec![FieldViolation {
+            field: "slots[1].slot_index".to_string(),
+            description: "must be 1 to 32 bytes".to_string(),
+        }]);
+        assert_eq!(status.code(), Code::InvalidArgument);
+        assert_eq!(
+            status.message(),
+            "slots[1].slot_index: must be 1 to 32 bytes"
+        );
+
+        let violations = field_violations(&status);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].field, "slots[1].slot_index");
+        assert!(field_violations(&Status::invalid_argument("no details")).is_empty());
+    }
+}
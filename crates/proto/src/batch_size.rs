@@ -0,0 +1,67 @@
+use tonic::metadata::MetadataMap;
+
+/// Response metadata key carrying the number of slots the server prefers per batch request
+pub const PREFERRED_BATCH_SIZE_KEY: &str = "x-sova-preferred-batch-size";
+/// Response metadata key carrying the largest number of slots the server accepts per batch request
+pub const MAX_BATCH_SIZE_KEY: &str = "x-sova-max-batch-size";
+
+/// Batch sizes advertised by the sentinel through `GetServerInfo` and the metadata of batch
+/// responses. A size of 0 means the server has no preference or no limit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BatchSizeHints {
+    pub preferred: u32,
+    pub max: u32,
+}
+
+impl BatchSizeHints {
+    /// Returns the number of slots to send per request: the preferred size capped at the maximum,
+    /// or `None` if the server advertised neither
+    pub fn chunk_size(&self) -> Option<usize> {
+        match (self.preferred, self.max) {
+            (0, 0) => None,
+            (0, max) => Some(max as usize),
+            (preferred, 0) => Some(preferred as usize),
+            (preferred, max) => Some(preferred.min(max) as usize),
+        }
+    }
+
+    /// Adds the hints to response metadata
+    pub fn insert_into(&self, metadata: &mut MetadataMap) {
+        metadata.insert(PREFERRED_BATCH_SIZE_KEY, self.preferred.into());
+        metadata.insert(MAX_BATCH_SIZE_KEY, self.max.into());
+    }
+
+    /// Reads the hints from response metadata, returning `None` if the server sent none
+    pub fn from_metadata(metadata: &MetadataMap) -> Option<Self> {
+        let parse = |key| metadata.get(key)?.to_str().ok()?.parse().ok();
+        let preferred = parse(PREFERRED_BATCH_SIZE_KEY);
+        let max = parse(MAX_BATCH_SIZE_KEY);
+        if preferred.is_none() && max.is_none() {
+            return None;
+        }
+        Some(Self {
+            preferred: preferred.unwrap_or(0),
+            max: max.unwrap_or(0),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batch_size_hints_metadata() {
+        let hints = BatchSizeHints {
+            preferred: 500,
+            max: 200,
+        };
+        let mut metadata = MetadataMap::new();
+        assert_eq!(BatchSizeHints::from_metadata(&metadata), None);
+
+        hints.insert_into(&mut metadata);
+        assert_eq!(BatchSizeHints::from_metadata(&metadata), Some(hints));
+        assert_eq!(hints.chunk_size(), Some(200));
+        assert_eq!(BatchSizeHints::default().chunk_size(), None);
+    }
+}
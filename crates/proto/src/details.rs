@@ -0,0 +1,107 @@
+use crate::error_info::ERROR_DOMAIN;
+use crate::google::rpc::{
+    bad_request::FieldViolation, BadRequest, ErrorInfo, RetryInfo, Status as RpcStatus,
+};
+use prost::Message;
+use std::collections::HashMap;
+use std::time::Duration;
+use tonic::{Code, Status};
+
+/// A `google.rpc` error detail message
+pub(crate) trait Detail: Message + Default {
+    const TYPE_URL: &'static str;
+}
+
+impl Detail for RetryInfo {
+    const TYPE_URL: &'static str = "type.googleapis.com/google.rpc.RetryInfo";
+}
+
+impl Detail for ErrorInfo {
+    const TYPE_URL: &'static str = "type.googleapis.com/google.rpc.ErrorInfo";
+}
+
+impl Detail for BadRequest {
+    const TYPE_URL: &'static str = "type.googleapis.com/google.rpc.BadRequest";
+}
+
+/// Typed `google.rpc` details attached to a status as a `google.rpc.Status`, so clients can tell
+/// errors apart without parsing messages
+#[derive(Debug, Default)]
+pub struct StatusDetails {
+    details: Vec<prost_types::Any>,
+}
+
+impl StatusDetails {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn with<M: Detail>(mut self, detail: M) -> Self {
+        self.details.push(prost_types::Any {
+            type_url: M::TYPE_URL.to_string(),
+            value: detail.encode_to_vec(),
+        });
+        self
+    }
+
+    /// Adds a `google.rpc.RetryInfo` telling the client how long to wait before retrying
+    pub fn retry_info(self, retry_delay: Duration) -> Self {
+        self.with(RetryInfo {
+            retry_delay: prost_types::Duration::try_from(retry_delay).ok(),
+        })
+    }
+
+    /// Adds a `google.rpc.ErrorInfo` with one of the reasons in [`crate::error_info`]
+    pub fn error_info(self, reason: &str, metadata: HashMap<String, String>) -> Self {
+        self.with(ErrorInfo {
+            reason: reason.to_string(),
+            domain: ERROR_DOMAIN.to_string(),
+            metadata,
+        })
+    }
+
+    /// Adds a `google.rpc.BadRequest` listing the invalid fields of the request
+    pub fn bad_request(self, field_violations: Vec<FieldViolation>) -> Self {
+        self.with(BadRequest { field_violations })
+    }
+
+    /// Builds a status carrying the details
+    pub fn into_status(self, code: Code, message: impl Into<String>) -> Status {
+        let message = message.into();
+        let details = RpcStatus {
+            code: code as i32,
+            message: message.clone(),
+            details: self.details,
+        };
+        Status::with_details(code, message, details.encode_to_vec().into())
+    }
+}
+
+/// Returns the first detail of type `M` in the status, if it has one
+pub(crate) fn detail<M: Detail>(status: &Status) -> Option<M> {
+    let details = RpcStatus::decode(status.details()).ok()?;
+    details
+        .details
+        .iter()
+        .filter(|detail| detail.type_url == M::TYPE_URL)
+        .find_map(|detail| M::decode(detail.value.as_slice()).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error_info::{error_info, STALE_BTC_BLOCK};
+    use crate::retry_info::retry_delay;
+
+    #[test]
+    fn test_multiple_details() {
+        let status = StatusDetails::new()
+            .retry_info(Duration::from_secs(1))
+            .error_info(STALE_BTC_BLOCK, HashMap::new())
+            .into_status(Code::Unavailable, "Unavailable");
+
+        assert_eq!(retry_delay(&status), Some(Duration::from_secs(1)));
+        assert_eq!(error_info(&status).unwrap().reason, STALE_BTC_BLOCK);
+        assert!(detail::<BadRequest>(&status).is_none());
+    }
+}
@@ -0,0 +1,132 @@
+use crate::details::{detail, StatusDetails};
+use crate::google::rpc::ErrorInfo;
+use std::collections::HashMap;
+use tonic::{Code, Status};
+
+/// Domain of the error reasons reported by the sentinel
+pub const ERROR_DOMAIN: &str = "sova-sentinel";
+
+/// The request's `btc_block` is lower than the Bitcoin block the slot was locked at
+pub const STALE_BTC_BLOCK: &str = "STALE_BTC_BLOCK";
+
+/// The batch request has more slots than the server's maximum batch size
+pub const BATCH_TOO_LARGE: &str = "BATCH_TOO_LARGE";
+
+/// The request has invalid fields, listed in a `google.rpc.BadRequest` detail
+pub const INVALID_REQUEST: &str = "INVALID_REQUEST";
+
+/// `ReplaceLockTx` or `UnlockByTxid` found no active locks on the Bitcoin transaction
+pub const LOCK_TX_NOT_FOUND: &str = "LOCK_TX_NOT_FOUND";
+
+/// A slot of `BatchUnlockSlot` doesn't hold the expected current value
+pub const CURRENT_VALUE_MISMATCH: &str = "CURRENT_VALUE_MISMATCH";
+
+/// `CommitLock` found no reservation with the id, or it expired
+pub const RESERVATION_NOT_FOUND: &str = "RESERVATION_NOT_FOUND";
+
+/// The sentinel only serves status queries
+pub const READ_ONLY: &str = "READ_ONLY";
+
+/// `GetPublicKey` was called on a sentinel without a signing key
+pub const NO_SIGNING_KEY: &str = "NO_SIGNING_KEY";
+
+/// Lock commitments are disabled on the sentinel
+pub const LOCK_COMMITMENT_DISABLED: &str = "LOCK_COMMITMENT_DISABLED";
+
+/// The slot has no active lock in the latest lock commitment
+pub const LOCK_NOT_COMMITTED: &str = "LOCK_NOT_COMMITTED";
+
+/// The sentinel is a standby and rejects writes, and status queries without a staleness bound,
+/// until it is promoted
+pub const STANDBY: &str = "STANDBY";
+
+/// A standby is further behind its primary than a status query's `max_staleness_blocks` allows
+pub const STALE_REPLICA: &str = "STALE_REPLICA";
+
+/// The sentinel was superseded by a promoted standby and rejects writes
+pub const FENCED: &str = "FENCED";
+
+/// `Promote` was called on a sentinel that isn't a standby
+pub const NOT_STANDBY: &str = "NOT_STANDBY";
+
+/// Replication RPCs were called on a sentinel without a replication role
+pub const REPLICATION_DISABLED: &str = "REPLICATION_DISABLED";
+
+/// A state-changing request couldn't be written to the journal, so it wasn't handled
+pub const JOURNAL_FAILED: &str = "JOURNAL_FAILED";
+
+/// The Bitcoin node can't be reached or the circuit breaker is open; retry after the `RetryInfo`
+/// delay
+pub const BITCOIN_NODE_UNAVAILABLE: &str = "BITCOIN_NODE_UNAVAILABLE";
+
+/// A Bitcoin RPC call ran out of its time budget
+pub const BITCOIN_RPC_TIMEOUT: &str = "BITCOIN_RPC_TIMEOUT";
+
+/// A Bitcoin RPC call failed
+pub const BITCOIN_RPC_FAILED: &str = "BITCOIN_RPC_FAILED";
+
+/// The database is busy with another writer; retry after the `RetryInfo` delay
+pub const DATABASE_BUSY: &str = "DATABASE_BUSY";
+
+/// The database failed and is being recovered; retry after the `RetryInfo` delay
+pub const DATABASE_UNAVAILABLE: &str = "DATABASE_UNAVAILABLE";
+
+/// A database query failed
+pub const DATABASE_FAILED: &str = "DATABASE_FAILED";
+
+/// A `SubscribeSlotEvents` subscriber fell too far behind and missed events
+pub const SUBSCRIBER_LAGGED: &str = "SUBSCRIBER_LAGGED";
+
+/// The caller exceeded its request quota; retry after the `RetryInfo` delay
+pub const RATE_LIMITED: &str = "RATE_LIMITED";
+
+/// The locks would take the caller past the active locks it is allowed to hold
+pub const ACTIVE_LOCK_QUOTA: &str = "ACTIVE_LOCK_QUOTA";
+
+/// Active locks are limited and the caller couldn't be identified to count them against a quota
+pub const UNIDENTIFIED_CALLER: &str = "UNIDENTIFIED_CALLER";
+
+/// The listener requires a bearer token and the request had none or a wrong one
+pub const UNAUTHENTICATED: &str = "UNAUTHENTICATED";
+
+/// Builds a status carrying a `google.rpc.ErrorInfo` detail with one of the reasons above, so
+/// clients can tell errors apart without parsing the message
+pub fn with_error_info(
+    code: Code,
+    message: impl Into<String>,
+    reason: &str,
+    metadata: HashMap<String, String>,
+) -> Status {
+    StatusDetails::new()
+        .error_info(reason, metadata)
+        .into_status(code, message)
+}
+
+/// Returns the status' `google.rpc.ErrorInfo` detail, if it has one
+pub fn error_info(status: &Status) -> Option<ErrorInfo> {
+    detail(status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_info_roundtrip() {
+        let metadata = HashMap::from([("lock_btc_block".to_string(), "200".to_string())]);
+        let status = with_error_info(
+            Code::FailedPrecondition,
+            "Stale btc_block",
+            STALE_BTC_BLOCK,
+            metadata.clone(),
+        );
+        assert_eq!(status.code(), Code::FailedPrecondition);
+
+        let info = error_info(&status).unwrap();
+        assert_eq!(info.reason, STALE_BTC_BLOCK);
+        assert_eq!(info.domain, ERROR_DOMAIN);
+        assert_eq!(info.metadata, metadata);
+
+        assert!(error_info(&Status::failed_precondition("no details")).is_none());
+    }
+}
@@ -0,0 +1,16 @@
+use tonic::metadata::MetadataMap;
+
+/// Metadata key carrying a fencing epoch: on responses the epoch of the sentinel that served
+/// them, on requests the highest epoch the client has seen. A sentinel receiving a write with a
+/// higher epoch than its own was superseded by a promoted standby and fences itself.
+pub const EPOCH_KEY: &str = "x-sova-sentinel-epoch";
+
+/// Adds a fencing epoch to request or response metadata
+pub fn insert_epoch(metadata: &mut MetadataMap, epoch: u64) {
+    metadata.insert(EPOCH_KEY, epoch.into());
+}
+
+/// Reads the fencing epoch from request or response metadata, `None` if there is none
+pub fn epoch(metadata: &MetadataMap) -> Option<u64> {
+    metadata.get(EPOCH_KEY)?.to_str().ok()?.parse().ok()
+}
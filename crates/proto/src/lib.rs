@@ -1,4 +1,27 @@
+pub mod bad_request;
+pub mod batch_size;
+pub mod details;
+pub mod error_info;
+pub mod fencing;
+pub mod request_id;
+pub mod retry_info;
+pub mod source;
+
 pub mod proto {
     tonic::include_proto!("slot_lock");
     tonic::include_proto!("health");
+    tonic::include_proto!("admin");
+    tonic::include_proto!("revert_executor");
+}
+
+/// Every package is included into [`proto`], so the `slot_lock` types other packages import
+/// resolve there
+mod slot_lock {
+    pub use crate::proto::DeploymentLabels;
+}
+
+pub mod google {
+    pub mod rpc {
+        tonic::include_proto!("google.rpc");
+    }
 }
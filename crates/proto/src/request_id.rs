@@ -0,0 +1,11 @@
+use tonic::metadata::MetadataMap;
+
+/// Metadata key carrying the id of a request. The sentinel keeps the id a caller sends, or
+/// generates one, logs it with every line about the request and echoes it in the response, so
+/// client and server logs can be correlated.
+pub const REQUEST_ID_KEY: &str = "x-request-id";
+
+/// Reads the request id from request or response metadata, `None` if there is none
+pub fn request_id(metadata: &MetadataMap) -> Option<&str> {
+    metadata.get(REQUEST_ID_KEY)?.to_str().ok()
+}
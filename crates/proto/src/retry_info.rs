@@ -0,0 +1,38 @@
+use crate::details::{detail, StatusDetails};
+use crate::google::rpc::RetryInfo;
+use std::time::Duration;
+use tonic::{Code, Status};
+
+/// Builds a status carrying a `google.rpc.RetryInfo` detail that tells the client how long to
+/// wait before retrying
+pub fn with_retry_info(code: Code, message: impl Into<String>, retry_delay: Duration) -> Status {
+    StatusDetails::new()
+        .retry_info(retry_delay)
+        .into_status(code, message)
+}
+
+/// Returns the retry delay from the status' `google.rpc.RetryInfo` detail, if it has one
+pub fn retry_delay(status: &Status) -> Option<Duration> {
+    detail::<RetryInfo>(status)?
+        .retry_delay
+        .and_then(|retry_delay| Duration::try_from(retry_delay).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_info_roundtrip() {
+        let status = with_retry_info(
+            Code::Unavailable,
+            "Database is busy",
+            Duration::from_millis(1500),
+        );
+        assert_eq!(status.code(), Code::Unavailable);
+        assert_eq!(status.message(), "Database is busy");
+        assert_eq!(retry_delay(&status), Some(Duration::from_millis(1500)));
+
+        assert_eq!(retry_delay(&Status::unavailable("no details")), None);
+    }
+}
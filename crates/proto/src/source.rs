@@ -0,0 +1,19 @@
+use tonic::metadata::MetadataMap;
+
+/// Metadata key naming the replication role of the sentinel that answered a request, `primary`,
+/// `standby` or `fenced`, so clients reading from standbys can tell which node served them
+pub const ROLE_KEY: &str = "x-sova-sentinel-role";
+
+/// Metadata key carrying the instance id of the sentinel that answered a request
+pub const INSTANCE_ID_KEY: &str = "x-sova-sentinel-instance-id";
+
+/// Reads the role of the answering sentinel from response metadata, `None` if there is none
+pub fn role(metadata: &MetadataMap) -> Option<&str> {
+    metadata.get(ROLE_KEY)?.to_str().ok()
+}
+
+/// Reads the instance id of the answering sentinel from response metadata, `None` if there is
+/// none
+pub fn instance_id(metadata: &MetadataMap) -> Option<&str> {
+    metadata.get(INSTANCE_ID_KEY)?.to_str().ok()
+}
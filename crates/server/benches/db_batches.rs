@@ -0,0 +1,167 @@
+//! Benchmarks of the storage backends' batch paths against a store that already holds locks, at
+//! the batch sizes clients send. Writes are rolled back after each iteration, so every iteration
+//! sees the same store.
+//!
+//! ```text
+//! cargo bench -p sova-sentinel-server --bench db_batches
+//! ```
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use sova_sentinel_server::db::{Database, RedbStore, SlotInsertData, SlotStore, UnlockReason};
+
+const BATCH_SIZES: [usize; 4] = [10, 100, 1_000, 10_000];
+
+/// Locks already in the store, in another contract, half of them unlocked
+const POPULATED_LOCKS: usize = 50_000;
+
+const CONTRACT_ADDRESS: &str = "0x000000000000000000000000000000000000bench";
+
+fn slot(contract_address: &str, index: usize) -> SlotInsertData {
+    let mut slot_index = vec![0; 32];
+    slot_index[24..].copy_from_slice(&(index as u64).to_be_bytes());
+    SlotInsertData {
+        namespace: String::new(),
+        contract_address: contract_address.to_string(),
+        start_block: 100,
+        btc_block: 100,
+        slot_index,
+        btc_txid: format!("{:064x}", index / 100),
+        revert_value: vec![0; 32],
+        current_value: vec![1; 32],
+        metadata: Vec::new(),
+        labels: Default::default(),
+        alt_btc_txids: Vec::new(),
+        required_confirmed_txids: 1,
+        locked_by: None,
+    }
+}
+
+fn slots(contract_address: &str, count: usize) -> Vec<SlotInsertData> {
+    (0..count).map(|i| slot(contract_address, i)).collect()
+}
+
+fn sqlite() -> Database {
+    Database::new(rusqlite::Connection::open_in_memory().unwrap()).unwrap()
+}
+
+fn redb() -> RedbStore {
+    RedbStore::in_memory().unwrap()
+}
+
+/// Returns `db` holding the populated locks, plus locks of the benchmarked slots if `locked` is
+/// set
+fn populated<S: SlotStore>(db: S, locked: bool) -> S {
+    let background = slots(
+        "0x0000000000000000000000000000000000000001",
+        POPULATED_LOCKS,
+    );
+    db.with_transaction(|transaction| db.batch_insert_slot_locks(transaction, &background))
+        .unwrap();
+    let unlocked = background
+        .iter()
+        .step_by(2)
+        .map(|slot| {
+            (
+                slot.contract_address.as_str(),
+                slot.slot_index.as_slice(),
+                150,
+            )
+        })
+        .collect::<Vec<_>>();
+    db.with_transaction(|transaction| {
+        db.batch_unlock_slots(transaction, "", &unlocked, UnlockReason::Confirmed)
+    })
+    .unwrap();
+    if locked {
+        let benchmarked = slots(CONTRACT_ADDRESS, BATCH_SIZES[BATCH_SIZES.len() - 1]);
+        db.with_transaction(|transaction| db.batch_insert_slot_locks(transaction, &benchmarked))
+            .unwrap();
+    }
+    db
+}
+
+fn bench_insert<S: SlotStore>(c: &mut Criterion, backend: &str, db: S) {
+    let db = populated(db, false);
+    let mut group = c.benchmark_group(format!("{}/batch_insert_slot_locks", backend));
+    group.sample_size(10);
+    for size in BATCH_SIZES {
+        let batch = slots(CONTRACT_ADDRESS, size);
+        group.throughput(Throughput::Elements(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &batch, |b, batch| {
+            b.iter(|| {
+                db.with_rolled_back_transaction(|transaction| {
+                    db.batch_insert_slot_locks(transaction, batch)
+                })
+                .unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_get<S: SlotStore>(c: &mut Criterion, backend: &str, db: S) {
+    let db = populated(db, true);
+    let mut group = c.benchmark_group(format!("{}/batch_get_locked_slots", backend));
+    group.sample_size(10);
+    for size in BATCH_SIZES {
+        let batch = slots(CONTRACT_ADDRESS, size);
+        let keys = batch
+            .iter()
+            .map(|slot| (slot.contract_address.as_str(), slot.slot_index.as_slice()))
+            .collect::<Vec<_>>();
+        group.throughput(Throughput::Elements(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &keys, |b, keys| {
+            b.iter(|| {
+                db.with_transaction(|transaction| {
+                    db.batch_get_locked_slots(transaction, "", keys, 120)
+                })
+                .unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_unlock<S: SlotStore>(c: &mut Criterion, backend: &str, db: S) {
+    let db = populated(db, true);
+    let mut group = c.benchmark_group(format!("{}/batch_unlock_slots", backend));
+    group.sample_size(10);
+    for size in BATCH_SIZES {
+        let batch = slots(CONTRACT_ADDRESS, size);
+        let keys = batch
+            .iter()
+            .map(|slot| {
+                (
+                    slot.contract_address.as_str(),
+                    slot.slot_index.as_slice(),
+                    120,
+                )
+            })
+            .collect::<Vec<_>>();
+        group.throughput(Throughput::Elements(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &keys, |b, keys| {
+            b.iter(|| {
+                db.with_rolled_back_transaction(|transaction| {
+                    db.batch_unlock_slots(transaction, "", keys, UnlockReason::Manual)
+                })
+                .unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_sqlite(c: &mut Criterion) {
+    bench_insert(c, "sqlite", sqlite());
+    bench_get(c, "sqlite", sqlite());
+    bench_unlock(c, "sqlite", sqlite());
+}
+
+fn bench_redb(c: &mut Criterion) {
+    bench_insert(c, "redb", redb());
+    bench_get(c, "redb", redb());
+    bench_unlock(c, "redb", redb());
+}
+
+criterion_group!(benches, bench_sqlite, bench_redb);
+criterion_main!(benches);
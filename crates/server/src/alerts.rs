@@ -0,0 +1,297 @@
+//! Alert rules watching the slot events for surges of reverted locks, an early sign of a stuck
+//! bridge or a reorg well before users notice their withdrawals hanging. Alerts are logged,
+//! counted in the metrics and optionally posted to a webhook.
+
+use crate::config::RevertAlertRule;
+use crate::deployment::DeploymentLabels;
+use crate::metrics::Metrics;
+use crate::service::{SlotEventFilter, SlotEvents};
+use futures::StreamExt;
+use reqwest::Client as HttpClient;
+use serde::Serialize;
+use sova_sentinel_proto::proto::{slot_event, SlotEvent};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Time a webhook has to accept an alert before the delivery is abandoned
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertStatus {
+    Firing,
+    Resolved,
+}
+
+/// A rule starting to fire for a namespace, or resolving
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Alert {
+    pub rule: String,
+    pub status: AlertStatus,
+    pub namespace: String,
+    /// Contract the rule is restricted to, if any
+    pub contract_address: Option<String>,
+    /// Reverts counted in the window when the alert was raised
+    pub reverts: u64,
+    pub max_reverts: u64,
+    pub window_blocks: u64,
+    /// Latest sova block of the namespace when the alert was raised
+    pub sova_block: u64,
+    /// Deployment labels of the sentinel raising the alert
+    pub labels: DeploymentLabels,
+}
+
+/// Reverts a rule has counted in one namespace
+#[derive(Debug, Default)]
+struct RevertWindow {
+    /// Reverts by sova block, within the window only
+    reverts: BTreeMap<u64, u64>,
+    latest_block: u64,
+    firing: bool,
+}
+
+/// Evaluates the alert rules against the slot events of a sentinel
+pub struct RevertAlerts {
+    rules: Vec<RevertAlertRule>,
+    events: SlotEvents,
+    windows: HashMap<(usize, String), RevertWindow>,
+    webhook_url: Option<String>,
+    client: HttpClient,
+    metrics: Arc<Metrics>,
+    labels: DeploymentLabels,
+}
+
+impl RevertAlerts {
+    pub fn new(rules: Vec<RevertAlertRule>, events: SlotEvents) -> Self {
+        Self {
+            rules,
+            events,
+            windows: HashMap::new(),
+            webhook_url: None,
+            client: HttpClient::new(),
+            metrics: Arc::new(Metrics::default()),
+            labels: DeploymentLabels::default(),
+        }
+    }
+
+    /// Posts each alert as JSON to `url`, besides logging it
+    pub fn with_webhook(mut self, url: String) -> Self {
+        self.webhook_url = Some(url);
+        self
+    }
+
+    /// Sets the deployment labels sent with each alert
+    pub fn with_labels(mut self, labels: DeploymentLabels) -> Self {
+        self.labels = labels;
+        self
+    }
+
+    /// Sets the metrics alerts are counted in
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Counts an event against the rules, returning the alerts it fires or resolves. Any event
+    /// of a namespace advances its windows, so a rule resolves once the namespace has moved on
+    /// past the surge.
+    pub fn observe(&mut self, event: &SlotEvent) -> Vec<Alert> {
+        let reverted = event.kind == slot_event::Kind::Reverted as i32;
+        let mut alerts = Vec::new();
+        for (idx, rule) in self.rules.iter().enumerate() {
+            if rule
+                .namespace
+                .as_ref()
+                .is_some_and(|namespace| *namespace != event.namespace)
+            {
+                continue;
+            }
+            let window = self
+                .windows
+                .entry((idx, event.namespace.clone()))
+                .or_default();
+            window.latest_block = window.latest_block.max(event.sova_block);
+            if reverted
+                && rule
+                    .contract_address
+                    .as_ref()
+                    .is_none_or(|contract_address| *contract_address == event.contract_address)
+            {
+                *window.reverts.entry(event.sova_block).or_default() += 1;
+            }
+            let oldest = (window.latest_block + 1).saturating_sub(rule.window_blocks);
+            window.reverts = window.reverts.split_off(&oldest);
+
+            let reverts = window.reverts.values().sum::<u64>();
+            let status = match (window.firing, reverts > rule.max_reverts) {
+                (false, true) => AlertStatus::Firing,
+                (true, false) => AlertStatus::Resolved,
+                _ => continue,
+            };
+            window.firing = status == AlertStatus::Firing;
+            alerts.push(Alert {
+                rule: rule.name.clone(),
+                status,
+                namespace: event.namespace.clone(),
+                contract_address: rule.contract_address.clone(),
+                reverts,
+                max_reverts: rule.max_reverts,
+                window_blocks: rule.window_blocks,
+                sova_block: window.latest_block,
+                labels: self.labels.clone(),
+            });
+        }
+        alerts
+    }
+
+    /// Logs an alert, records it and posts it to the webhook without waiting for the delivery
+    fn raise(&self, alert: Alert) {
+        match alert.status {
+            AlertStatus::Firing => tracing::error!(
+                "Alert {} firing for namespace \"{}\": {} reverts within {} blocks, more than {}",
+                alert.rule,
+                alert.namespace,
+                alert.reverts,
+                alert.window_blocks,
+                alert.max_reverts
+            ),
+            AlertStatus::Resolved => tracing::info!(
+                "Alert {} resolved for namespace \"{}\": {} reverts within {} blocks",
+                alert.rule,
+                alert.namespace,
+                alert.reverts,
+                alert.window_blocks
+            ),
+        }
+        self.metrics
+            .record_alert(&alert.rule, alert.status == AlertStatus::Firing);
+
+        if let Some(url) = &self.webhook_url {
+            let request = self.client.post(url).timeout(WEBHOOK_TIMEOUT).json(&alert);
+            tokio::spawn(async move {
+                let delivery = async { request.send().await?.error_for_status() };
+                if let Err(e) = delivery.await {
+                    tracing::warn!("Failed to post alert {} to the webhook: {}", alert.rule, e);
+                }
+            });
+        }
+    }
+
+    /// Evaluates the rules against the slot events, forever. Falling behind the events
+    /// resubscribes, missing the events dropped meanwhile.
+    pub async fn run(mut self) {
+        loop {
+            let mut events = Box::pin(self.events.subscribe(SlotEventFilter::default()));
+            loop {
+                match events.next().await {
+                    Some(Ok(event)) => {
+                        for alert in self.observe(&event) {
+                            self.raise(alert);
+                        }
+                    }
+                    Some(Err(status)) => {
+                        tracing::warn!("Alert rules fell behind: {}", status.message());
+                        break;
+                    }
+                    None => return,
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(name: &str, max_reverts: u64, window_blocks: u64) -> RevertAlertRule {
+        RevertAlertRule {
+            name: name.to_string(),
+            max_reverts,
+            window_blocks,
+            namespace: None,
+            contract_address: None,
+        }
+    }
+
+    fn event(kind: slot_event::Kind, namespace: &str, contract: &str, block: u64) -> SlotEvent {
+        SlotEvent {
+            kind: kind as i32,
+            sova_block: block,
+            namespace: namespace.to_string(),
+            contract_address: contract.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn revert(namespace: &str, contract: &str, block: u64) -> SlotEvent {
+        event(slot_event::Kind::Reverted, namespace, contract, block)
+    }
+
+    #[test]
+    fn test_fires_and_resolves() {
+        let metrics = Arc::new(Metrics::default());
+        let mut alerts = RevertAlerts::new(vec![rule("surge", 2, 10)], SlotEvents::default())
+            .with_metrics(metrics.clone());
+
+        assert!(alerts.observe(&revert("", "0x123", 100)).is_empty());
+        assert!(alerts.observe(&revert("", "0x123", 101)).is_empty());
+        let fired = alerts.observe(&revert("", "0x456", 105));
+        assert_eq!(
+            fired,
+            vec![Alert {
+                rule: "surge".to_string(),
+                status: AlertStatus::Firing,
+                namespace: String::new(),
+                contract_address: None,
+                reverts: 3,
+                max_reverts: 2,
+                window_blocks: 10,
+                sova_block: 105,
+                labels: DeploymentLabels::default(),
+            }]
+        );
+        assert!(alerts.observe(&revert("", "0x123", 106)).is_empty());
+
+        // Block 100 leaves the window at 110, block 101 at 111
+        let locked = event(slot_event::Kind::Locked, "", "0x123", 110);
+        assert!(alerts.observe(&locked).is_empty());
+        let locked = event(slot_event::Kind::Locked, "", "0x123", 111);
+        let resolved = alerts.observe(&locked);
+        assert_eq!(resolved[0].status, AlertStatus::Resolved);
+        assert_eq!((resolved[0].reverts, resolved[0].sova_block), (2, 111));
+
+        for alert in fired.into_iter().chain(resolved) {
+            alerts.raise(alert);
+        }
+        let output = metrics.encode().unwrap();
+        assert!(output.contains(r#"sova_sentinel_alerts_fired_total{"#));
+        assert!(output
+            .lines()
+            .any(|line| line.starts_with("sova_sentinel_alert_firing{") && line.ends_with(" 0")));
+    }
+
+    #[test]
+    fn test_rule_filters() {
+        let mut contract_rule = rule("contract", 0, 100);
+        contract_rule.contract_address = Some("0x456".to_string());
+        let mut namespace_rule = rule("namespace", 1, 100);
+        namespace_rule.namespace = Some("testnet".to_string());
+        let mut alerts = RevertAlerts::new(
+            vec![contract_rule, namespace_rule, rule("any", 1, 100)],
+            SlotEvents::default(),
+        );
+
+        assert!(alerts.observe(&revert("", "0x123", 100)).is_empty());
+        assert!(alerts.observe(&revert("testnet", "0x123", 5000)).is_empty());
+        let fired = alerts.observe(&revert("testnet", "0x456", 5001));
+        let fired: Vec<_> = fired.iter().map(|alert| alert.rule.as_str()).collect();
+        assert_eq!(fired, ["contract", "namespace", "any"]);
+
+        // Namespaces are counted apart: the default namespace has seen one revert
+        let fired = alerts.observe(&revert("", "0x123", 101));
+        let fired: Vec<_> = fired.iter().map(|alert| alert.rule.as_str()).collect();
+        assert_eq!(fired, ["any"]);
+    }
+}
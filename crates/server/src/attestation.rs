@@ -0,0 +1,209 @@
+//! Signatures over slot status responses and lock commitments, so the sova node and third parties can verify a
+//! response came from the sentinel and keep it as evidence.
+//!
+//! A response is signed with a BIP340 Schnorr signature over the tagged hash
+//! `SHA256(SHA256(tag) || SHA256(tag) || len(request) || request || response)`, where `tag`
+//! names the RPC, `len(request)` is the big-endian u64 length of the protobuf-encoded request
+//! and `response` is the protobuf-encoded response with its `signature` field empty.
+
+use crate::proto::{
+    BatchGetSlotStatusRequest, BatchGetSlotStatusResponse, GetLockCommitmentRequest,
+    GetLockCommitmentResponse, GetSlotStatusRequest, GetSlotStatusResponse,
+};
+use anyhow::{Context, Result};
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use bitcoin::secp256k1::{schnorr, Keypair, Message, Secp256k1, XOnlyPublicKey};
+use std::path::Path;
+
+const GET_SLOT_STATUS_TAG: &[u8] = b"sova-sentinel/GetSlotStatus";
+const BATCH_GET_SLOT_STATUS_TAG: &[u8] = b"sova-sentinel/BatchGetSlotStatus";
+const GET_LOCK_COMMITMENT_TAG: &[u8] = b"sova-sentinel/GetLockCommitment";
+
+/// Key the sentinel signs status responses with
+#[derive(Clone)]
+pub struct AttestationKey {
+    keypair: Keypair,
+}
+
+impl AttestationKey {
+    /// Parses a hex-encoded 32-byte secret key
+    pub fn from_hex(secret_key: &str) -> Result<Self> {
+        let keypair = Keypair::from_seckey_str(&Secp256k1::signing_only(), secret_key.trim())
+            .context("Signing key must be a hex-encoded 32-byte secp256k1 secret key")?;
+        Ok(Self { keypair })
+    }
+
+    /// Loads a hex-encoded secret key from a file
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read signing key file {}", path.display()))?;
+        Self::from_hex(&contents)
+            .with_context(|| format!("Invalid signing key file {}", path.display()))
+    }
+
+    /// Returns the x-only public key signatures verify against
+    pub fn public_key(&self) -> XOnlyPublicKey {
+        self.keypair.x_only_public_key().0
+    }
+
+    /// Signs a GetSlotStatus response to `request`, setting its `signature`
+    pub fn sign_slot_status(
+        &self,
+        request: &GetSlotStatusRequest,
+        response: &mut GetSlotStatusResponse,
+    ) {
+        response.signature.clear();
+        let message = digest(GET_SLOT_STATUS_TAG, request, response);
+        response.signature = self.sign(&message);
+    }
+
+    /// Signs a BatchGetSlotStatus response to `request`, setting its `signature`
+    pub fn sign_batch_slot_status(
+        &self,
+        request: &BatchGetSlotStatusRequest,
+        response: &mut BatchGetSlotStatusResponse,
+    ) {
+        response.signature.clear();
+        let message = digest(BATCH_GET_SLOT_STATUS_TAG, request, response);
+        response.signature = self.sign(&message);
+    }
+
+    /// Signs a lock commitment, setting its `signature`
+    pub fn sign_lock_commitment(&self, response: &mut GetLockCommitmentResponse) {
+        response.signature.clear();
+        let message = digest(
+            GET_LOCK_COMMITMENT_TAG,
+            &GetLockCommitmentRequest {},
+            response,
+        );
+        response.signature = self.sign(&message);
+    }
+
+    fn sign(&self, message: &Message) -> Vec<u8> {
+        Secp256k1::signing_only()
+            .sign_schnorr_no_aux_rand(message, &self.keypair)
+            .serialize()
+            .to_vec()
+    }
+}
+
+/// Checks that `response` to `request` carries a valid signature by `public_key`
+pub fn verify_slot_status(
+    public_key: &XOnlyPublicKey,
+    request: &GetSlotStatusRequest,
+    response: &GetSlotStatusResponse,
+) -> Result<()> {
+    let unsigned = GetSlotStatusResponse {
+        signature: Vec::new(),
+        ..response.clone()
+    };
+    verify(
+        public_key,
+        &digest(GET_SLOT_STATUS_TAG, request, &unsigned),
+        &response.signature,
+    )
+}
+
+/// Checks that `response` to `request` carries a valid signature by `public_key`
+pub fn verify_batch_slot_status(
+    public_key: &XOnlyPublicKey,
+    request: &BatchGetSlotStatusRequest,
+    response: &BatchGetSlotStatusResponse,
+) -> Result<()> {
+    let unsigned = BatchGetSlotStatusResponse {
+        signature: Vec::new(),
+        ..response.clone()
+    };
+    verify(
+        public_key,
+        &digest(BATCH_GET_SLOT_STATUS_TAG, request, &unsigned),
+        &response.signature,
+    )
+}
+
+/// Checks that a lock commitment carries a valid signature by `public_key`
+pub fn verify_lock_commitment(
+    public_key: &XOnlyPublicKey,
+    response: &GetLockCommitmentResponse,
+) -> Result<()> {
+    let unsigned = GetLockCommitmentResponse {
+        signature: Vec::new(),
+        ..response.clone()
+    };
+    verify(
+        public_key,
+        &digest(
+            GET_LOCK_COMMITMENT_TAG,
+            &GetLockCommitmentRequest {},
+            &unsigned,
+        ),
+        &response.signature,
+    )
+}
+
+fn verify(public_key: &XOnlyPublicKey, message: &Message, signature: &[u8]) -> Result<()> {
+    let signature = schnorr::Signature::from_slice(signature).context("Malformed signature")?;
+    Secp256k1::verification_only()
+        .verify_schnorr(&signature, message, public_key)
+        .context("Invalid signature")
+}
+
+fn digest(tag: &[u8], request: &impl prost::Message, response: &impl prost::Message) -> Message {
+    let tag = sha256::Hash::hash(tag);
+    let request = request.encode_to_vec();
+    let mut engine = sha256::Hash::engine();
+    engine.input(tag.as_byte_array());
+    engine.input(tag.as_byte_array());
+    engine.input(&(request.len() as u64).to_be_bytes());
+    engine.input(&request);
+    engine.input(&response.encode_to_vec());
+    Message::from_digest(sha256::Hash::from_engine(engine).to_byte_array())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_and_verify() {
+        let key = AttestationKey::from_hex(&"01".repeat(32)).unwrap();
+        let request = GetSlotStatusRequest {
+            contract_address: "0x123".to_string(),
+            current_block: 100,
+            slot_index: vec![0; 32],
+            btc_block: 10,
+            namespace: String::new(),
+            max_staleness_blocks: None,
+        };
+        let mut response = GetSlotStatusResponse {
+            contract_address: "0x123".to_string(),
+            slot_index: vec![0; 32],
+            ..Default::default()
+        };
+        key.sign_slot_status(&request, &mut response);
+        assert_eq!(response.signature.len(), 64);
+        verify_slot_status(&key.public_key(), &request, &response).unwrap();
+
+        // The signature covers the request as well as the response
+        let other_request = GetSlotStatusRequest {
+            current_block: 101,
+            ..request.clone()
+        };
+        assert!(verify_slot_status(&key.public_key(), &other_request, &response).is_err());
+        let mut tampered = response.clone();
+        tampered.confirmations = 6;
+        assert!(verify_slot_status(&key.public_key(), &request, &tampered).is_err());
+
+        // A single-slot signature does not verify as a batch signature
+        let batch_request = BatchGetSlotStatusRequest::default();
+        let batch_response = BatchGetSlotStatusResponse {
+            slots: Vec::new(),
+            signature: response.signature.clone(),
+        };
+        assert!(
+            verify_batch_slot_status(&key.public_key(), &batch_request, &batch_response).is_err()
+        );
+
+        assert!(AttestationKey::from_hex("zz").is_err());
+    }
+}
@@ -0,0 +1,230 @@
+//! Scheduled online backups of the lock database to a directory and/or an S3-compatible bucket,
+//! so the database can be restored after losing its host without stopping the sentinel
+
+mod s3;
+
+pub use s3::{S3Credentials, S3Store};
+
+use crate::db::Database;
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Backups are named `slot_locks-<UTC timestamp>.db`, so sorting names sorts them by age
+const BACKUP_PREFIX: &str = "slot_locks-";
+const BACKUP_SUFFIX: &str = ".db";
+
+/// Backs up the database every interval, keeping the newest backups at each destination
+pub struct BackupScheduler {
+    db: Database,
+    interval: Duration,
+    keep: usize,
+    directory: Option<PathBuf>,
+    s3: Option<S3Store>,
+}
+
+impl BackupScheduler {
+    pub fn new(db: Database, interval: Duration, keep: usize) -> Self {
+        Self {
+            db,
+            interval,
+            keep,
+            directory: None,
+            s3: None,
+        }
+    }
+
+    /// Writes backups to `directory`, creating it if needed
+    pub fn with_directory(mut self, directory: impl Into<PathBuf>) -> Self {
+        self.directory = Some(directory.into());
+        self
+    }
+
+    /// Uploads backups to an S3-compatible bucket
+    pub fn with_s3(mut self, store: S3Store) -> Self {
+        self.s3 = Some(store);
+        self
+    }
+
+    /// Takes a backup, copies it to every destination and deletes the backups beyond the newest
+    /// `keep`. Returns the name of the backup.
+    pub async fn backup(&self) -> Result<String> {
+        let name = backup_name(SystemTime::now());
+        // Without a directory the snapshot is only staged locally until it is uploaded
+        let directory = self.directory.clone().unwrap_or_else(std::env::temp_dir);
+        tokio::fs::create_dir_all(&directory)
+            .await
+            .with_context(|| format!("Failed to create {}", directory.display()))?;
+        let path = directory.join(&name);
+
+        let db = self.db.clone();
+        let snapshot = path.clone();
+        tokio::task::spawn_blocking(move || db.backup_to(&snapshot)).await??;
+
+        if let Some(s3) = &self.s3 {
+            let uploaded = match tokio::fs::read(&path).await {
+                Ok(body) => s3.put(&name, body).await,
+                Err(e) => Err(e.into()),
+            };
+            if self.directory.is_none() {
+                let _ = tokio::fs::remove_file(&path).await;
+            }
+            uploaded.with_context(|| format!("Failed to upload backup {}", name))?;
+
+            for name in expired(s3.list().await?, self.keep) {
+                s3.delete(&name).await?;
+            }
+        }
+        if let Some(directory) = &self.directory {
+            rotate_directory(directory, self.keep)?;
+        }
+        Ok(name)
+    }
+
+    /// Backs up the database every interval, forever
+    pub async fn run(self) {
+        let mut ticker = tokio::time::interval(self.interval);
+        // The first tick completes immediately; the first backup is taken one interval in
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            match self.backup().await {
+                Ok(name) => tracing::info!("Backed up database to {}", name),
+                Err(e) => tracing::error!("Failed to back up database: {:#}", e),
+            }
+        }
+    }
+}
+
+fn backup_name(time: SystemTime) -> String {
+    format!("{}{}{}", BACKUP_PREFIX, utc_timestamp(time), BACKUP_SUFFIX)
+}
+
+fn is_backup_name(name: &str) -> bool {
+    name.starts_with(BACKUP_PREFIX) && name.ends_with(BACKUP_SUFFIX)
+}
+
+/// Returns the backups among `names` that are older than the newest `keep`
+fn expired(mut names: Vec<String>, keep: usize) -> Vec<String> {
+    names.retain(|name| is_backup_name(name));
+    names.sort();
+    names.truncate(names.len().saturating_sub(keep));
+    names
+}
+
+/// Deletes the backups in `directory` beyond the newest `keep`
+fn rotate_directory(directory: &Path, keep: usize) -> Result<()> {
+    let mut names = Vec::new();
+    for entry in std::fs::read_dir(directory)? {
+        if let Ok(name) = entry?.file_name().into_string() {
+            names.push(name);
+        }
+    }
+    for name in expired(names, keep) {
+        std::fs::remove_file(directory.join(&name))
+            .with_context(|| format!("Failed to delete backup {}", name))?;
+    }
+    Ok(())
+}
+
+/// Formats `time` as a UTC timestamp like `20240131T235959Z`
+fn utc_timestamp(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (year, month, day, hour, minute, second) = utc_date_time(secs);
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+        year, month, day, hour, minute, second
+    )
+}
+
+/// Splits a unix time into its UTC year, month, day, hour, minute and second
+pub(crate) fn utc_date_time(secs: u64) -> (i64, i64, i64, u64, u64, u64) {
+    let (days, secs) = (secs / 86400, secs % 86400);
+
+    // Civil date from days since the epoch, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    (year, month, day, secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::SlotInsertData;
+    use std::fs;
+
+    #[test]
+    fn test_utc_timestamp() {
+        assert_eq!(utc_timestamp(UNIX_EPOCH), "19700101T000000Z");
+        assert_eq!(
+            utc_timestamp(UNIX_EPOCH + Duration::from_secs(1369353600)),
+            "20130524T000000Z"
+        );
+        assert_eq!(
+            utc_timestamp(UNIX_EPOCH + Duration::from_secs(1709251199)),
+            "20240229T235959Z"
+        );
+    }
+
+    #[test]
+    fn test_expired() {
+        let names = vec![
+            "slot_locks-20240103T000000Z.db".to_string(),
+            "slot_locks-20240101T000000Z.db".to_string(),
+            "notes.txt".to_string(),
+            "slot_locks-20240102T000000Z.db".to_string(),
+        ];
+        assert_eq!(
+            expired(names.clone(), 2),
+            vec!["slot_locks-20240101T000000Z.db"]
+        );
+        assert!(expired(names, 3).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_backs_up_to_directory() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("sova-sentinel-backup-{}", std::process::id()));
+        let db = Database::new(rusqlite::Connection::open_in_memory()?)?;
+        db.with_transaction(|tx| {
+            db.batch_insert_slot_locks(
+                tx,
+                &[SlotInsertData {
+                    namespace: String::new(),
+                    contract_address: "0x123".to_string(),
+                    start_block: 100,
+                    btc_block: 200,
+                    slot_index: vec![1],
+                    btc_txid: "txid1".to_string(),
+                    revert_value: vec![],
+                    current_value: vec![],
+                    metadata: Vec::new(),
+                    labels: Default::default(),
+                    alt_btc_txids: Vec::new(),
+                    required_confirmed_txids: 1,
+                    locked_by: None,
+                }],
+            )
+        })?;
+        fs::create_dir_all(&dir)?;
+        let old = dir.join("slot_locks-20000101T000000Z.db");
+        fs::write(&old, b"")?;
+
+        let scheduler = BackupScheduler::new(db, Duration::from_secs(60), 1).with_directory(&dir);
+        let name = scheduler.backup().await?;
+        assert!(!old.exists());
+
+        let restored = Database::new(rusqlite::Connection::open(dir.join(&name))?)?;
+        assert!(restored.is_slot_locked("", "0x123", &[1])?);
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
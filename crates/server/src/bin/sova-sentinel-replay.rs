@@ -0,0 +1,99 @@
+//! Rebuilds a lock database from a sentinel's write-ahead journal, prints the journal, or replays
+//! a recording made with SOVA_SENTINEL_RECORD_DIR.
+//!
+//! ```text
+//! sova-sentinel-replay <journal> <database> [--until-seq <seq>]
+//! sova-sentinel-replay --print <journal>
+//! sova-sentinel-replay --recording <dir>
+//! ```
+
+use anyhow::{Context, Result};
+use sova_sentinel_server::db::Database;
+use sova_sentinel_server::journal::{read_journal, replay};
+use sova_sentinel_server::proto::journal_entry;
+use sova_sentinel_server::service::{describe_request, replay_recording};
+use std::path::Path;
+
+const USAGE: &str = "Usage: sova-sentinel-replay <journal> <database> [--until-seq <seq>]\n       sova-sentinel-replay --print <journal>\n       sova-sentinel-replay --recording <dir>";
+
+fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.as_slice() {
+        [flag, journal] if flag == "--print" => print(Path::new(journal)),
+        [flag, dir] if flag == "--recording" => replay_traces(Path::new(dir)),
+        [journal, database] => rebuild(Path::new(journal), Path::new(database), None),
+        [journal, database, flag, seq] if flag == "--until-seq" => rebuild(
+            Path::new(journal),
+            Path::new(database),
+            Some(seq.parse().context("--until-seq must be a record number")?),
+        ),
+        _ => anyhow::bail!(USAGE),
+    }
+}
+
+fn rebuild(journal: &Path, database: &Path, until_seq: Option<u64>) -> Result<()> {
+    if database.exists() {
+        anyhow::bail!(
+            "{} already exists; replay into a new database",
+            database.display()
+        );
+    }
+    let entries = read_journal(journal)?;
+    let db = Database::new(rusqlite::Connection::open(database)?)?;
+    let stats = replay(&db, &entries, until_seq)?;
+    println!(
+        "requests={} changes={} incomplete={:?}",
+        stats.requests, stats.changes, stats.incomplete
+    );
+    Ok(())
+}
+
+fn print(journal: &Path) -> Result<()> {
+    for entry in read_journal(journal)? {
+        match entry.record {
+            Some(journal_entry::Record::Request(request)) => println!(
+                "seq={} recorded_at_ms={} method={} request={}",
+                entry.seq,
+                entry.recorded_at_ms,
+                request.method,
+                describe_request(&request.method, &request.payload)
+            ),
+            Some(journal_entry::Record::Outcome(outcome)) => println!(
+                "seq={} recorded_at_ms={} request_seq={} code={:?} message={:?} changes={:?}",
+                entry.seq,
+                entry.recorded_at_ms,
+                outcome.request_seq,
+                tonic::Code::from_i32(outcome.code),
+                outcome.message,
+                outcome
+                    .changes
+                    .iter()
+                    .map(|change| change.lock_id)
+                    .collect::<Vec<_>>()
+            ),
+            None => println!("seq={} empty record", entry.seq),
+        }
+    }
+    Ok(())
+}
+
+/// Replays a recording against its database snapshot, printing the requests whose outcome changed
+fn replay_traces(dir: &Path) -> Result<()> {
+    let runtime = tokio::runtime::Runtime::new()?;
+    let report = runtime.block_on(replay_recording(dir, |service| service))?;
+    for mismatch in &report.mismatches {
+        println!(
+            "seq={} method={}\n  recorded: {}\n  replayed: {}",
+            mismatch.seq, mismatch.method, mismatch.recorded, mismatch.replayed
+        );
+    }
+    println!(
+        "requests={} mismatches={}",
+        report.requests,
+        report.mismatches.len()
+    );
+    if !report.mismatches.is_empty() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
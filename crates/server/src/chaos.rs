@@ -0,0 +1,240 @@
+//! Fault injection for development. Database and Bitcoin node operations are delayed or failed at
+//! random, so integrators can check how their retries and deadlines cope with a misbehaving
+//! sentinel before meeting one in production.
+
+use crate::service::{BitcoinRpcClient, ChainInfo};
+use async_trait::async_trait;
+use bitcoin::block::Header;
+use bitcoin::{BlockHash, OutPoint, Txid};
+use bitcoincore_rpc::json::GetRawTransactionResult;
+use bitcoincore_rpc::{jsonrpc, Error};
+use std::hash::{BuildHasher, RandomState};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Fault an operation is subjected to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// Delays the operation by the given time, then runs it
+    Delay(Duration),
+    /// Fails the operation without running it
+    Fail,
+}
+
+/// Injected failure of an operation
+#[derive(Debug, thiserror::Error)]
+#[error("Injected fault")]
+pub struct InjectedFault;
+
+/// Draws the faults of operations at random: each fails with probability `failure_rate`, and each
+/// that doesn't fail is delayed with probability `delay_rate` by up to `max_delay`
+#[derive(Debug)]
+pub struct FaultInjector {
+    failure_rate: f64,
+    delay_rate: f64,
+    max_delay: Duration,
+    random: RandomState,
+    operations: AtomicU64,
+}
+
+impl FaultInjector {
+    pub fn new(failure_rate: f64, delay_rate: f64, max_delay: Duration) -> Self {
+        Self {
+            failure_rate,
+            delay_rate,
+            max_delay,
+            random: RandomState::new(),
+            operations: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns whether any fault can be drawn
+    pub fn is_enabled(&self) -> bool {
+        self.failure_rate > 0.0 || (self.delay_rate > 0.0 && !self.max_delay.is_zero())
+    }
+
+    /// Draws the fault of the next operation, `None` to run it as is
+    pub fn draw(&self) -> Option<Fault> {
+        let operation = self.operations.fetch_add(1, Ordering::Relaxed);
+        if self.uniform(operation, 0) < self.failure_rate {
+            return Some(Fault::Fail);
+        }
+        (self.uniform(operation, 1) < self.delay_rate)
+            .then(|| Fault::Delay(self.max_delay.mul_f64(self.uniform(operation, 2))))
+    }
+
+    /// Subjects a blocking operation to its fault, sleeping the thread through delays. On a
+    /// multi-threaded tokio runtime the worker's other tasks are handed to another thread for
+    /// the delay, so they aren't held up with the operation.
+    pub fn inject_blocking(&self) -> Result<(), InjectedFault> {
+        match self.draw() {
+            Some(Fault::Fail) => Err(InjectedFault),
+            Some(Fault::Delay(delay)) => {
+                let multi_threaded = tokio::runtime::Handle::try_current().is_ok_and(|handle| {
+                    handle.runtime_flavor() == tokio::runtime::RuntimeFlavor::MultiThread
+                });
+                if multi_threaded {
+                    tokio::task::block_in_place(|| std::thread::sleep(delay));
+                } else {
+                    std::thread::sleep(delay);
+                }
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Subjects an async operation to its fault
+    pub async fn inject(&self) -> Result<(), InjectedFault> {
+        match self.draw() {
+            Some(Fault::Fail) => Err(InjectedFault),
+            Some(Fault::Delay(delay)) => {
+                tokio::time::sleep(delay).await;
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Returns a number in `[0, 1]` derived from the operation and the draw
+    fn uniform(&self, operation: u64, draw: u8) -> f64 {
+        self.random.hash_one((operation, draw)) as f64 / u64::MAX as f64
+    }
+}
+
+/// Bitcoin RPC client whose calls are subjected to injected faults. Failed calls look like an
+/// unreachable node, so they are retried like real connection failures.
+pub struct ChaosRpcClient {
+    inner: Arc<dyn BitcoinRpcClient>,
+    faults: Arc<FaultInjector>,
+}
+
+impl ChaosRpcClient {
+    pub fn new(inner: Arc<dyn BitcoinRpcClient>, faults: Arc<FaultInjector>) -> Self {
+        Self { inner, faults }
+    }
+
+    async fn inject(&self) -> Result<(), Error> {
+        self.faults.inject().await.map_err(|e| {
+            Error::JsonRpc(jsonrpc::error::Error::Transport(Box::new(
+                std::io::Error::new(std::io::ErrorKind::ConnectionRefused, e),
+            )))
+        })
+    }
+}
+
+#[async_trait]
+impl BitcoinRpcClient for ChaosRpcClient {
+    async fn get_raw_transaction_info(
+        &self,
+        txid: &Txid,
+    ) -> Result<GetRawTransactionResult, Error> {
+        self.inject().await?;
+        self.inner.get_raw_transaction_info(txid).await
+    }
+
+    async fn is_output_unspent(
+        &self,
+        outpoint: &OutPoint,
+        include_mempool: bool,
+    ) -> Result<bool, Error> {
+        self.inject().await?;
+        self.inner
+            .is_output_unspent(outpoint, include_mempool)
+            .await
+    }
+
+    async fn get_tx_spending_prevout(&self, outpoint: &OutPoint) -> Result<Option<Txid>, Error> {
+        self.inject().await?;
+        self.inner.get_tx_spending_prevout(outpoint).await
+    }
+
+    async fn is_in_mempool(&self, txid: &Txid) -> Result<bool, Error> {
+        self.inject().await?;
+        self.inner.is_in_mempool(txid).await
+    }
+
+    async fn test_mempool_accept(&self, raw_tx: &[u8]) -> Result<Option<String>, Error> {
+        self.inject().await?;
+        self.inner.test_mempool_accept(raw_tx).await
+    }
+
+    async fn send_raw_transaction(&self, raw_tx: &[u8]) -> Result<Txid, Error> {
+        self.inject().await?;
+        self.inner.send_raw_transaction(raw_tx).await
+    }
+
+    async fn get_chain_info(&self) -> Result<ChainInfo, Error> {
+        self.inject().await?;
+        self.inner.get_chain_info().await
+    }
+
+    async fn get_coinbase_txid(&self, height: u64) -> Result<Txid, Error> {
+        self.inject().await?;
+        self.inner.get_coinbase_txid(height).await
+    }
+
+    async fn get_block_header(&self, height: u64) -> Result<Header, Error> {
+        self.inject().await?;
+        self.inner.get_block_header(height).await
+    }
+
+    async fn get_tx_out_proof(
+        &self,
+        txid: &Txid,
+        block_hash: &BlockHash,
+    ) -> Result<Vec<u8>, Error> {
+        self.inject().await?;
+        self.inner.get_tx_out_proof(txid, block_hash).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+    use crate::service::{MockConfirmations, MockRpcClient};
+
+    #[test]
+    fn test_draws_faults_at_their_rates() {
+        let faults = FaultInjector::new(0.0, 0.0, Duration::from_secs(1));
+        assert!(!faults.is_enabled());
+        assert!((0..100).all(|_| faults.draw().is_none()));
+
+        let faults = FaultInjector::new(1.0, 0.0, Duration::ZERO);
+        assert!((0..100).all(|_| faults.draw() == Some(Fault::Fail)));
+
+        let faults = FaultInjector::new(0.0, 1.0, Duration::from_millis(10));
+        assert!((0..100).all(|_| matches!(
+            faults.draw(),
+            Some(Fault::Delay(delay)) if delay <= Duration::from_millis(10)
+        )));
+    }
+
+    #[tokio::test]
+    async fn test_injects_faults_into_operations() -> anyhow::Result<()> {
+        let faults = Arc::new(FaultInjector::new(1.0, 0.0, Duration::ZERO));
+
+        let db = Database::new(rusqlite::Connection::open_in_memory()?)?;
+        let faulty = db.clone().with_faults(faults.clone());
+        let error = faulty.schema_version().unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<rusqlite::Error>(),
+            Some(rusqlite::Error::SqliteFailure(err, _))
+                if err.code == rusqlite::ErrorCode::DatabaseBusy
+        ));
+        db.schema_version()?;
+
+        let client = ChaosRpcClient::new(
+            Arc::new(MockRpcClient::new(6, MockConfirmations::Never)),
+            faults,
+        );
+        let error = client.get_chain_info().await.unwrap_err();
+        assert!(matches!(
+            error,
+            Error::JsonRpc(jsonrpc::error::Error::Transport(_))
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,290 @@
+//! Merkle commitments over the active locks, so auditors can check that the sentinel doesn't hide
+//! or change locks between queries.
+//!
+//! Leaves are `SHA256(0x00 || lock)` over the protobuf-encoded [`CommittedLock`] of each active
+//! lock, ordered by namespace, contract address and slot index, and inner nodes are
+//! `SHA256(0x01 || left || right)`. A level with an odd number of nodes carries its last node up
+//! unchanged. The root of no locks is `SHA256("")`.
+
+use crate::db::{Database, LockedSlot};
+use crate::proto::CommittedLock;
+use anyhow::Result;
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use prost::Message;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+
+impl From<LockedSlot> for CommittedLock {
+    fn from(lock: LockedSlot) -> Self {
+        Self {
+            namespace: lock.namespace,
+            contract_address: lock.contract_address,
+            slot_index: lock.slot_index,
+            revert_value: lock.revert_value,
+            current_value: lock.current_value,
+            btc_txid: lock.btc_txid,
+            btc_block: lock.btc_block,
+            start_block: lock.start_block,
+            alt_btc_txids: lock.alt_btc_txids,
+            required_confirmed_txids: lock.required_confirmed_txids,
+            metadata: lock.metadata,
+        }
+    }
+}
+
+/// Merkle tree over the active locks at one point in time
+#[derive(Debug)]
+pub struct LockCommitment {
+    locks: Vec<CommittedLock>,
+    /// Node hashes of each level, leaves first and the root last
+    levels: Vec<Vec<[u8; 32]>>,
+    computed_at: u64,
+}
+
+impl LockCommitment {
+    /// Builds the tree over `locks`, computed at unix time `computed_at`
+    pub fn new(mut locks: Vec<CommittedLock>, computed_at: u64) -> Self {
+        locks.sort_by(|a, b| lock_key(a).cmp(&lock_key(b)));
+        let mut levels = vec![locks.iter().map(leaf_hash).collect::<Vec<_>>()];
+        while levels.last().is_some_and(|level| level.len() > 1) {
+            let next = levels
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => node_hash(left, right),
+                    [node] => *node,
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(next);
+        }
+        Self {
+            locks,
+            levels,
+            computed_at,
+        }
+    }
+
+    pub fn root(&self) -> [u8; 32] {
+        match self.levels.last().and_then(|level| level.first()) {
+            Some(root) => *root,
+            None => sha256::Hash::hash(&[]).to_byte_array(),
+        }
+    }
+
+    pub fn lock_count(&self) -> usize {
+        self.locks.len()
+    }
+
+    /// Unix time in seconds the commitment was computed at
+    pub fn computed_at(&self) -> u64 {
+        self.computed_at
+    }
+
+    /// Returns the committed lock of a slot with its leaf index and the sibling hashes on the
+    /// path to the root, leaf level first. `None` if the slot had no active lock.
+    pub fn proof(
+        &self,
+        namespace: &str,
+        contract_address: &str,
+        slot_index: &[u8],
+    ) -> Option<(&CommittedLock, usize, Vec<[u8; 32]>)> {
+        let leaf_index = self
+            .locks
+            .binary_search_by(|lock| lock_key(lock).cmp(&(namespace, contract_address, slot_index)))
+            .ok()?;
+        let mut index = leaf_index;
+        let mut siblings = Vec::new();
+        for level in &self.levels[..self.levels.len() - 1] {
+            let sibling = index ^ 1;
+            if sibling < level.len() {
+                siblings.push(level[sibling]);
+            }
+            index /= 2;
+        }
+        Some((&self.locks[leaf_index], leaf_index, siblings))
+    }
+}
+
+/// Checks that `lock` is leaf `leaf_index` of the `lock_count` leaves of the tree with `root`
+pub fn verify_inclusion(
+    root: &[u8],
+    lock: &CommittedLock,
+    leaf_index: u64,
+    lock_count: u64,
+    siblings: &[Vec<u8>],
+) -> bool {
+    if leaf_index >= lock_count {
+        return false;
+    }
+    let mut hash = leaf_hash(lock);
+    let mut siblings = siblings.iter();
+    let (mut index, mut width) = (leaf_index, lock_count);
+    while width > 1 {
+        let sibling = index ^ 1;
+        if sibling < width {
+            let Some(sibling) = siblings
+                .next()
+                .and_then(|s| <[u8; 32]>::try_from(s.as_slice()).ok())
+            else {
+                return false;
+            };
+            hash = if index % 2 == 0 {
+                node_hash(&hash, &sibling)
+            } else {
+                node_hash(&sibling, &hash)
+            };
+        }
+        index /= 2;
+        width = width.div_ceil(2);
+    }
+    siblings.next().is_none() && hash.as_slice() == root
+}
+
+fn lock_key(lock: &CommittedLock) -> (&str, &str, &[u8]) {
+    (&lock.namespace, &lock.contract_address, &lock.slot_index)
+}
+
+fn leaf_hash(lock: &CommittedLock) -> [u8; 32] {
+    let mut engine = sha256::Hash::engine();
+    engine.input(&[LEAF_PREFIX]);
+    engine.input(&lock.encode_to_vec());
+    sha256::Hash::from_engine(engine).to_byte_array()
+}
+
+fn node_hash(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut engine = sha256::Hash::engine();
+    engine.input(&[NODE_PREFIX]);
+    engine.input(left);
+    engine.input(right);
+    sha256::Hash::from_engine(engine).to_byte_array()
+}
+
+/// Latest lock commitment, shared between the committer and the services serving it
+#[derive(Debug, Clone, Default)]
+pub struct LockCommitments(Arc<RwLock<Option<Arc<LockCommitment>>>>);
+
+impl LockCommitments {
+    /// Returns the latest commitment, `None` before the first one is computed
+    pub fn latest(&self) -> Option<Arc<LockCommitment>> {
+        self.0.read().ok()?.clone()
+    }
+
+    fn set(&self, commitment: Arc<LockCommitment>) {
+        if let Ok(mut latest) = self.0.write() {
+            *latest = Some(commitment);
+        }
+    }
+}
+
+/// Recomputes the lock commitment periodically
+pub struct LockCommitter {
+    db: Database,
+    commitments: LockCommitments,
+    interval: Duration,
+}
+
+impl LockCommitter {
+    pub fn new(db: Database, interval: Duration) -> Self {
+        Self {
+            db,
+            commitments: LockCommitments::default(),
+            interval,
+        }
+    }
+
+    /// Handle to the commitments computed by this committer
+    pub fn commitments(&self) -> LockCommitments {
+        self.commitments.clone()
+    }
+
+    /// Computes a commitment over the current active locks and makes it the latest
+    pub fn commit(&self) -> Result<Arc<LockCommitment>> {
+        let locks = self.db.active_locks()?;
+        let computed_at = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let commitment = Arc::new(LockCommitment::new(
+            locks.into_iter().map(CommittedLock::from).collect(),
+            computed_at,
+        ));
+        self.commitments.set(commitment.clone());
+        Ok(commitment)
+    }
+
+    /// Recomputes the commitment every interval, forever
+    pub async fn run(self) {
+        let mut ticker = tokio::time::interval(self.interval);
+        loop {
+            ticker.tick().await;
+            match self.commit() {
+                Ok(commitment) => tracing::debug!(
+                    "Committed to {} active locks, root {}",
+                    commitment.lock_count(),
+                    hex::encode(commitment.root())
+                ),
+                Err(e) => tracing::error!("Failed to compute lock commitment: {:#}", e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lock(namespace: &str, slot: u8) -> CommittedLock {
+        CommittedLock {
+            namespace: namespace.to_string(),
+            contract_address: "0x123".to_string(),
+            slot_index: vec![slot; 32],
+            btc_txid: "ab".repeat(32),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_inclusion_proofs() {
+        let empty = LockCommitment::new(Vec::new(), 0);
+        assert_eq!(empty.root(), sha256::Hash::hash(&[]).to_byte_array());
+        assert!(empty.proof("", "0x123", &[1; 32]).is_none());
+
+        for count in 1..=7u8 {
+            let locks: Vec<_> = (0..count)
+                .rev()
+                .map(|slot| lock(if slot % 2 == 0 { "" } else { "testnet" }, slot))
+                .collect();
+            let commitment = LockCommitment::new(locks.clone(), 0);
+            let root = commitment.root();
+            for expected in &locks {
+                let (lock, index, siblings) = commitment
+                    .proof(&expected.namespace, "0x123", &expected.slot_index)
+                    .unwrap();
+                assert_eq!(lock, expected);
+                let siblings: Vec<_> = siblings.iter().map(|s| s.to_vec()).collect();
+                let count = count as u64;
+                assert!(verify_inclusion(
+                    &root,
+                    lock,
+                    index as u64,
+                    count,
+                    &siblings
+                ));
+
+                let mut changed = lock.clone();
+                changed.current_value = vec![1];
+                assert!(!verify_inclusion(
+                    &root,
+                    &changed,
+                    index as u64,
+                    count,
+                    &siblings
+                ));
+                assert!(!verify_inclusion(&root, lock, count, count, &siblings));
+            }
+            assert!(commitment.proof("", "0x123", &[9; 32]).is_none());
+        }
+    }
+}
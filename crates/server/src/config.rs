@@ -0,0 +1,1194 @@
+use crate::maintenance::MaintenanceWindow;
+use crate::service::DEFAULT_MAX_LOGGED_SLOTS;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use tonic::transport::{Certificate, Identity, ServerTlsConfig};
+use tracing_subscriber::filter::LevelFilter;
+
+/// Predefined sets of services and background subsystems an instance runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Profile {
+    /// Everything: the SlotLock and admin services plus all background subsystems
+    Full,
+    /// Answers slot status queries but rejects lock, unlock and replacement requests
+    ReadMirror,
+    /// Only the admin service
+    AdminOnly,
+    /// Only the SlotLock service, for running next to the node that uses it
+    Embedded,
+}
+
+/// gRPC services and background subsystems mounted at startup. The health service is always
+/// mounted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Components {
+    pub slot_lock: bool,
+    /// Accept lock, unlock and replacement requests on the SlotLock service
+    pub slot_lock_writes: bool,
+    pub admin: bool,
+    pub metrics: bool,
+    /// Rebroadcast pending lock transactions
+    pub rebroadcast: bool,
+    /// Warm the confirmation cache at startup
+    pub cache_warmup: bool,
+}
+
+impl Profile {
+    pub fn components(self) -> Components {
+        match self {
+            Self::Full => Components {
+                slot_lock: true,
+                slot_lock_writes: true,
+                admin: true,
+                metrics: true,
+                rebroadcast: true,
+                cache_warmup: true,
+            },
+            Self::ReadMirror => Components {
+                slot_lock: true,
+                metrics: true,
+                cache_warmup: true,
+                ..Components::default()
+            },
+            Self::AdminOnly => Components {
+                admin: true,
+                metrics: true,
+                ..Components::default()
+            },
+            Self::Embedded => Components {
+                slot_lock: true,
+                slot_lock_writes: true,
+                ..Components::default()
+            },
+        }
+    }
+}
+
+impl Components {
+    /// Whether any mounted component talks to the Bitcoin node
+    pub fn uses_bitcoin_node(self) -> bool {
+        self.slot_lock || self.rebroadcast || self.cache_warmup
+    }
+
+    /// Combines two sets of components, mounting everything either of them mounts
+    pub fn union(self, other: Self) -> Self {
+        Self {
+            slot_lock: self.slot_lock || other.slot_lock,
+            slot_lock_writes: self.slot_lock_writes || other.slot_lock_writes,
+            admin: self.admin || other.admin,
+            metrics: self.metrics || other.metrics,
+            rebroadcast: self.rebroadcast || other.rebroadcast,
+            cache_warmup: self.cache_warmup || other.cache_warmup,
+        }
+    }
+}
+
+/// Server configuration file
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    /// Endpoint that reverted slot values are delivered to, if any
+    pub revert_executor: Option<RevertExecutorConfig>,
+    /// Thresholds overriding the sentinel-wide ones, by contract address
+    pub contract_thresholds: HashMap<String, ThresholdOverride>,
+    /// Thresholds overriding the sentinel-wide ones, by namespace. Contract overrides take
+    /// precedence over these.
+    pub namespace_thresholds: HashMap<String, ThresholdOverride>,
+    /// Scheduled backups of the lock database, if any
+    pub backup: Option<BackupConfig>,
+    /// Pruning of old unlocked locks, if any
+    pub retention: Option<RetentionConfig>,
+    /// Scheduled integrity checks, vacuuming and WAL checkpoints of the database, if any
+    pub maintenance: Option<MaintenanceConfig>,
+    /// Primary this sentinel is a standby of, if any
+    pub replication: Option<ReplicationConfig>,
+    /// Quotas of requests each peer can make, if any
+    pub rate_limit: Option<RateLimitConfig>,
+    /// How callers are identified to record who made each lock, if at all
+    pub identity: Option<IdentityConfig>,
+    /// Rules alerting operators to surges of reverted locks, if any
+    pub alerting: Option<AlertingConfig>,
+    /// Addresses the gRPC server listens on. Without any, it listens on `SOVA_SENTINEL_HOST` and
+    /// `SOVA_SENTINEL_PORT` and serves every mounted service.
+    pub listeners: Vec<ListenerConfig>,
+    pub logging: LoggingConfig,
+}
+
+/// Log verbosity and volume
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingConfig {
+    /// Level of modules without one of their own, e.g. `info`. Only errors are logged when unset.
+    /// `RUST_LOG`, when set, replaces this and `modules`.
+    pub level: Option<String>,
+    /// Levels by module path, e.g. `"sova_sentinel_server::service::bitcoin" = "debug"`
+    pub modules: BTreeMap<String, String>,
+    /// Most slots listed in the log line of a batch request or response. The others are counted.
+    pub max_logged_slots: usize,
+    /// Fraction of calls traced, between 0 and 1, by RPC method name, e.g. `LockSlot = 0.1`.
+    /// Replaces the status call sample rate for the methods listed. Warnings and errors are
+    /// logged whatever the decision.
+    pub sample_rates: HashMap<String, f64>,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            level: None,
+            modules: BTreeMap::new(),
+            max_logged_slots: DEFAULT_MAX_LOGGED_SLOTS,
+            sample_rates: HashMap::new(),
+        }
+    }
+}
+
+/// gRPC services a listener can serve. The health service is served on every listener.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ListenerService {
+    SlotLock,
+    Admin,
+}
+
+/// Address the gRPC server listens on, and what it serves there
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ListenerConfig {
+    /// Socket address, e.g. `[::]:50051` or `127.0.0.1:50052`
+    pub address: String,
+    /// Services served, every mounted one when unset
+    pub services: Option<Vec<ListenerService>>,
+    /// File holding the bearer token callers must send, none required when unset
+    pub auth_token_file: Option<String>,
+    /// PEM file of the certificate chain the listener serves TLS with, plaintext when unset
+    pub tls_cert: Option<String>,
+    /// PEM file of the private key of `tls_cert`
+    pub tls_key: Option<String>,
+    /// PEM file of the CAs client certificates must be signed by. Callers without one are
+    /// refused when set.
+    pub client_ca: Option<String>,
+}
+
+impl ListenerConfig {
+    /// Listener on `address` serving every mounted service without authentication
+    pub fn new(address: impl Into<String>) -> Self {
+        Self {
+            address: address.into(),
+            services: None,
+            auth_token_file: None,
+            tls_cert: None,
+            tls_key: None,
+            client_ca: None,
+        }
+    }
+
+    /// Reads the TLS settings of the listener, `None` if it serves plaintext
+    pub fn tls_config(&self) -> Result<Option<ServerTlsConfig>> {
+        let read = |path: &str| {
+            std::fs::read(path).with_context(|| format!("Failed to read TLS file {}", path))
+        };
+        let (Some(cert), Some(key)) = (&self.tls_cert, &self.tls_key) else {
+            return Ok(None);
+        };
+        let tls = ServerTlsConfig::new().identity(Identity::from_pem(read(cert)?, read(key)?));
+        Ok(Some(match &self.client_ca {
+            Some(client_ca) => tls.client_ca_root(Certificate::from_pem(read(client_ca)?)),
+            None => tls,
+        }))
+    }
+
+    /// Whether the listener serves `service`, if it is mounted
+    pub fn serves(&self, service: ListenerService) -> bool {
+        self.services
+            .as_ref()
+            .is_none_or(|services| services.contains(&service))
+    }
+}
+
+/// Thresholds of one contract, each falling back to the sentinel-wide one when unset
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ThresholdOverride {
+    /// Confirmations after which a lock's Bitcoin transaction counts as confirmed
+    pub confirmation_threshold: Option<u32>,
+    /// Bitcoin blocks after which an unconfirmed lock is reverted
+    pub revert_threshold: Option<u32>,
+}
+
+impl ThresholdOverride {
+    pub fn is_empty(&self) -> bool {
+        self.confirmation_threshold.is_none() && self.revert_threshold.is_none()
+    }
+
+    /// Fails on a threshold of 0, which is reserved for "not overridden" in the admin service
+    pub fn validate(&self) -> Result<()> {
+        if self.confirmation_threshold == Some(0) || self.revert_threshold == Some(0) {
+            anyhow::bail!("Threshold overrides must be at least 1");
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// Profiles whose components are mounted, combined
+    pub profiles: Vec<Profile>,
+}
+
+/// Protocol spoken by the revert executor
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RevertExecutorProtocol {
+    /// JSON `POST` of each revert
+    Http,
+    /// The `revert_executor.RevertExecutor` gRPC service
+    Grpc,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RevertExecutorConfig {
+    pub url: String,
+    #[serde(default = "default_revert_executor_protocol")]
+    pub protocol: RevertExecutorProtocol,
+    /// Attempts after which a delivery is given up and marked failed
+    #[serde(default = "default_revert_max_attempts")]
+    pub max_attempts: u32,
+    /// Seconds between retries of failed deliveries
+    #[serde(default = "default_revert_retry_interval_secs")]
+    pub retry_interval_secs: u64,
+}
+
+/// Where and how often the lock database is backed up. At least one of `path` and `s3` is
+/// required.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BackupConfig {
+    /// Seconds between backups
+    #[serde(default = "default_backup_interval_secs")]
+    pub interval_secs: u64,
+    /// Backups kept at each destination, older ones are deleted
+    #[serde(default = "default_backup_keep")]
+    pub keep: usize,
+    /// Directory backups are written to
+    pub path: Option<String>,
+    /// S3-compatible bucket backups are uploaded to. Credentials are read from
+    /// `AWS_ACCESS_KEY_ID` and `AWS_SECRET_ACCESS_KEY`.
+    pub s3: Option<S3BackupConfig>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct S3BackupConfig {
+    /// Endpoint URL, e.g. `https://s3.us-east-1.amazonaws.com`. Buckets are addressed path-style.
+    pub endpoint: String,
+    pub bucket: String,
+    #[serde(default = "default_s3_region")]
+    pub region: String,
+    /// Prepended to the name of each backup, e.g. `mainnet/`
+    #[serde(default)]
+    pub prefix: String,
+}
+
+/// How long unlocked locks are kept. A lock is archived once it exceeds either age limit; at
+/// least one of the limits is required.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RetentionConfig {
+    /// Sova blocks an unlocked lock is kept for, counted back from the latest block of its
+    /// namespace
+    pub max_age_blocks: Option<u64>,
+    /// Days an unlocked lock is kept for
+    pub max_age_days: Option<u64>,
+    /// Days an archived lock can be restored for before it is purged, forever when unset
+    pub purge_after_days: Option<u64>,
+    /// Seconds between prunes
+    #[serde(default = "default_retention_interval_secs")]
+    pub interval_secs: u64,
+    /// Database that purged locks are moved to instead of being dropped
+    pub purge_path: Option<String>,
+}
+
+/// When the database is checked for corruption, vacuumed and checkpointed
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MaintenanceConfig {
+    /// Seconds between maintenance runs
+    #[serde(default = "default_maintenance_interval_secs")]
+    pub interval_secs: u64,
+    /// Daily UTC windows maintenance runs in, e.g. `02:00-04:00`. Without any, it runs at any
+    /// time.
+    #[serde(default)]
+    pub windows: Vec<String>,
+    /// Free pages returned to the filesystem per run, 0 for all of them
+    #[serde(default = "default_maintenance_vacuum_pages")]
+    pub vacuum_pages: u64,
+}
+
+impl MaintenanceConfig {
+    pub fn windows(&self) -> Result<Vec<MaintenanceWindow>> {
+        self.windows.iter().map(|window| window.parse()).collect()
+    }
+}
+
+/// Rules watching for surges of reverted locks, and where their alerts are sent
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AlertingConfig {
+    /// URL alerts are posted to as JSON when a rule fires and when it resolves
+    pub webhook_url: Option<String>,
+    pub rules: Vec<RevertAlertRule>,
+}
+
+/// Fires when more than `max_reverts` locks are reverted within `window_blocks` sova blocks,
+/// counted back from the latest block of each namespace
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RevertAlertRule {
+    pub name: String,
+    pub max_reverts: u64,
+    pub window_blocks: u64,
+    /// Only count the reverts of this namespace when set
+    pub namespace: Option<String>,
+    /// Only count the reverts of this contract when set
+    pub contract_address: Option<String>,
+}
+
+/// Primary a standby sentinel tails until it is promoted
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ReplicationConfig {
+    /// gRPC endpoint of the primary's admin service, e.g. `http://primary:50051`
+    pub primary_url: String,
+    /// Milliseconds between polls of the primary's lock changes
+    #[serde(default = "default_replication_poll_interval_ms")]
+    pub poll_interval_ms: u64,
+}
+
+/// Request quotas, by peer. Peers are told apart by the API key they send, when it is one of
+/// `api_keys`, or else by IP address. Limits left unset don't apply.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimitConfig {
+    /// Requests per second each peer can make
+    pub requests_per_sec: Option<u32>,
+    /// Requests a peer can make at once after being idle, `requests_per_sec` when unset
+    pub burst: Option<u32>,
+    /// Requests each peer can have in flight
+    pub max_concurrent: Option<u32>,
+    /// Header carrying the caller's API key
+    #[serde(default = "default_api_key_header")]
+    pub api_key_header: String,
+    /// Quotas replacing the ones above for the peers sending these API keys
+    #[serde(default)]
+    pub api_keys: HashMap<String, RateLimitQuota>,
+}
+
+/// Sources of the caller identity recorded with each lock. A client certificate common name,
+/// verified by a listener with `client_ca` or forwarded by a trusted proxy, is recorded as
+/// `cn:<name>`, an API key as `key:<identity>`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct IdentityConfig {
+    /// Header carrying the caller's API key
+    #[serde(default = "default_api_key_header")]
+    pub api_key_header: String,
+    /// Identities of the callers sending these API keys
+    #[serde(default)]
+    pub api_keys: HashMap<String, String>,
+    /// Header a TLS-terminating proxy forwards the verified client certificate's common name
+    /// in. It is only read from requests of `trusted_proxies`.
+    pub client_cn_header: Option<String>,
+    /// Addresses of the proxies trusted to set `client_cn_header`, which must overwrite it
+    #[serde(default)]
+    pub trusted_proxies: Vec<IpAddr>,
+    /// Active locks each identified caller can hold, counting its reserved slots. Unlimited
+    /// when unset.
+    pub max_active_locks: Option<u64>,
+    /// Limits replacing `max_active_locks` for these identities, e.g. `"key:sequencer"`
+    #[serde(default)]
+    pub active_lock_limits: HashMap<String, u64>,
+}
+
+impl IdentityConfig {
+    /// Returns whether any caller's active locks are limited
+    pub fn limits_active_locks(&self) -> bool {
+        self.max_active_locks.is_some() || !self.active_lock_limits.is_empty()
+    }
+
+    /// Returns how many active locks `caller` can hold, `None` if it is unlimited
+    pub fn active_lock_limit(&self, caller: &str) -> Option<u64> {
+        self.active_lock_limits
+            .get(caller)
+            .copied()
+            .or(self.max_active_locks)
+    }
+}
+
+/// Request quota of one API key. Limits left unset don't apply.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimitQuota {
+    pub requests_per_sec: Option<u32>,
+    pub burst: Option<u32>,
+    pub max_concurrent: Option<u32>,
+}
+
+impl RateLimitQuota {
+    /// Fails on a limit of 0, and on a burst without a rate
+    pub fn validate(&self) -> Result<()> {
+        if [self.requests_per_sec, self.burst, self.max_concurrent].contains(&Some(0)) {
+            anyhow::bail!("Rate limits must be at least 1");
+        }
+        if self.burst.is_some() && self.requests_per_sec.is_none() {
+            anyhow::bail!("burst needs requests_per_sec");
+        }
+        Ok(())
+    }
+}
+
+impl RateLimitConfig {
+    /// Returns the quota of peers without an API key of their own
+    pub fn default_quota(&self) -> RateLimitQuota {
+        RateLimitQuota {
+            requests_per_sec: self.requests_per_sec,
+            burst: self.burst,
+            max_concurrent: self.max_concurrent,
+        }
+    }
+}
+
+fn default_api_key_header() -> String {
+    "x-api-key".to_string()
+}
+
+fn default_replication_poll_interval_ms() -> u64 {
+    1000
+}
+
+fn default_retention_interval_secs() -> u64 {
+    3600
+}
+
+fn default_maintenance_interval_secs() -> u64 {
+    3600
+}
+
+fn default_maintenance_vacuum_pages() -> u64 {
+    1000
+}
+
+fn default_backup_interval_secs() -> u64 {
+    3600
+}
+
+fn default_backup_keep() -> usize {
+    24
+}
+
+fn default_s3_region() -> String {
+    "us-east-1".to_string()
+}
+
+fn default_revert_executor_protocol() -> RevertExecutorProtocol {
+    RevertExecutorProtocol::Http
+}
+
+fn default_revert_max_attempts() -> u32 {
+    10
+}
+
+fn default_revert_retry_interval_secs() -> u64 {
+    30
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            profiles: vec![Profile::Full],
+        }
+    }
+}
+
+impl Config {
+    /// Loads the configuration file at `SOVA_SENTINEL_CONFIG`, or the defaults when unset
+    pub fn from_env() -> Result<Self> {
+        match std::env::var("SOVA_SENTINEL_CONFIG") {
+            Ok(path) => Self::load(Path::new(&path)),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    /// Loads a TOML configuration file
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        Self::parse(&contents).with_context(|| format!("Invalid config file {}", path.display()))
+    }
+
+    pub fn parse(contents: &str) -> Result<Self> {
+        let config: Self = toml::from_str(contents)?;
+        if config.server.profiles.is_empty() {
+            anyhow::bail!("server.profiles must list at least one profile");
+        }
+        if let Some(revert_executor) = &config.revert_executor {
+            if revert_executor.max_attempts == 0 {
+                anyhow::bail!("revert_executor.max_attempts must be at least 1");
+            }
+        }
+        if let Some(backup) = &config.backup {
+            if backup.path.is_none() && backup.s3.is_none() {
+                anyhow::bail!("backup needs a path or an s3 bucket");
+            }
+            if backup.interval_secs == 0 {
+                anyhow::bail!("backup.interval_secs must be at least 1");
+            }
+            if backup.keep == 0 {
+                anyhow::bail!("backup.keep must be at least 1");
+            }
+        }
+        if let Some(retention) = &config.retention {
+            if retention.max_age_blocks.is_none() && retention.max_age_days.is_none() {
+                anyhow::bail!("retention needs max_age_blocks or max_age_days");
+            }
+            if retention.interval_secs == 0 {
+                anyhow::bail!("retention.interval_secs must be at least 1");
+            }
+        }
+        if let Some(maintenance) = &config.maintenance {
+            if maintenance.interval_secs == 0 {
+                anyhow::bail!("maintenance.interval_secs must be at least 1");
+            }
+            maintenance
+                .windows()
+                .context("Invalid maintenance.windows")?;
+        }
+        if let Some(replication) = &config.replication {
+            if replication.poll_interval_ms == 0 {
+                anyhow::bail!("replication.poll_interval_ms must be at least 1");
+            }
+        }
+        if let Some(alerting) = &config.alerting {
+            if alerting.rules.is_empty() {
+                anyhow::bail!("alerting needs at least one rule");
+            }
+            let mut names = HashSet::new();
+            for rule in &alerting.rules {
+                if !names.insert(rule.name.as_str()) {
+                    anyhow::bail!("alerting rule \"{}\" is listed twice", rule.name);
+                }
+                if rule.window_blocks == 0 {
+                    anyhow::bail!(
+                        "alerting rule \"{}\" window_blocks must be at least 1",
+                        rule.name
+                    );
+                }
+            }
+        }
+        if let Some(rate_limit) = &config.rate_limit {
+            rate_limit
+                .default_quota()
+                .validate()
+                .context("Invalid rate_limit")?;
+            if hyper::header::HeaderName::from_bytes(rate_limit.api_key_header.as_bytes()).is_err()
+            {
+                anyhow::bail!("rate_limit.api_key_header must be a valid header name");
+            }
+            for (api_key, quota) in &rate_limit.api_keys {
+                quota
+                    .validate()
+                    .with_context(|| format!("Invalid rate_limit.api_keys.\"{}\"", api_key))?;
+            }
+        }
+        if let Some(identity) = &config.identity {
+            let verifies_clients = config
+                .listeners
+                .iter()
+                .any(|listener| listener.client_ca.is_some());
+            if identity.api_keys.is_empty()
+                && identity.client_cn_header.is_none()
+                && !verifies_clients
+            {
+                anyhow::bail!(
+                    "identity needs api_keys, client_cn_header or a listener with client_ca"
+                );
+            }
+            if identity.client_cn_header.is_some() == identity.trusted_proxies.is_empty() {
+                anyhow::bail!("identity.client_cn_header and trusted_proxies need each other");
+            }
+            let headers = std::iter::once(&identity.api_key_header)
+                .chain(&identity.client_cn_header)
+                .map(|header| {
+                    (
+                        header,
+                        hyper::header::HeaderName::from_bytes(header.as_bytes()),
+                    )
+                });
+            for (header, name) in headers {
+                if name.is_err() {
+                    anyhow::bail!("identity header {} is not a valid header name", header);
+                }
+            }
+            if identity.api_keys.values().any(String::is_empty) {
+                anyhow::bail!("identity.api_keys identities must not be empty");
+            }
+            let limits = identity.max_active_locks.iter();
+            if limits
+                .chain(identity.active_lock_limits.values())
+                .any(|&limit| limit == 0)
+            {
+                anyhow::bail!("identity active lock limits must be at least 1");
+            }
+            // Quotas only bind identities the sentinel verifies itself
+            for caller in identity.active_lock_limits.keys() {
+                let known = match caller.split_once(':') {
+                    Some(("cn", name)) => !name.is_empty(),
+                    Some(("key", name)) => identity.api_keys.values().any(|key| key == name),
+                    _ => false,
+                };
+                if !known {
+                    anyhow::bail!(
+                        "identity.active_lock_limits.\"{}\" is neither cn:<name> nor \
+                         key:<identity> of one of the api_keys",
+                        caller
+                    );
+                }
+            }
+        }
+        let mut addresses = HashSet::new();
+        for listener in &config.listeners {
+            let address = listener
+                .address
+                .parse::<SocketAddr>()
+                .with_context(|| format!("Invalid listener address {}", listener.address))?;
+            if !addresses.insert(address) {
+                anyhow::bail!("Listener address {} is listed twice", listener.address);
+            }
+            if listener.services.as_ref().is_some_and(Vec::is_empty) {
+                anyhow::bail!("Listener {} serves no services", listener.address);
+            }
+            if listener.tls_cert.is_some() != listener.tls_key.is_some() {
+                anyhow::bail!(
+                    "Listener {} needs both tls_cert and tls_key",
+                    listener.address
+                );
+            }
+            if listener.client_ca.is_some() && listener.tls_cert.is_none() {
+                anyhow::bail!("Listener {} needs tls_cert for client_ca", listener.address);
+            }
+        }
+        let levels = config
+            .logging
+            .level
+            .iter()
+            .chain(config.logging.modules.values());
+        for level in levels {
+            if level.parse::<LevelFilter>().is_err() {
+                anyhow::bail!("Invalid log level {}", level);
+            }
+        }
+        for module in config.logging.modules.keys() {
+            if module.is_empty() || module.contains([',', '=', '[', ']', '{', '}']) {
+                anyhow::bail!("Invalid logging.modules key \"{}\"", module);
+            }
+        }
+        for (method, rate) in &config.logging.sample_rates {
+            if !(0.0..=1.0).contains(rate) {
+                anyhow::bail!("logging.sample_rates.{} must be between 0 and 1", method);
+            }
+        }
+        for (contract_address, thresholds) in &config.contract_thresholds {
+            thresholds
+                .validate()
+                .with_context(|| format!("Invalid contract_thresholds.\"{}\"", contract_address))?;
+        }
+        for (namespace, thresholds) in &config.namespace_thresholds {
+            thresholds
+                .validate()
+                .with_context(|| format!("Invalid namespace_thresholds.\"{}\"", namespace))?;
+        }
+        Ok(config)
+    }
+
+    /// Returns the listeners the gRPC server binds: the configured ones, or else one on `address`
+    /// serving every mounted service. With an `admin_address`, the admin service is served there
+    /// instead, apart from the data-plane API.
+    pub fn listeners(
+        &self,
+        address: &str,
+        admin_address: Option<&str>,
+    ) -> Result<Vec<ListenerConfig>> {
+        match admin_address {
+            _ if !self.listeners.is_empty() => {
+                if admin_address.is_some() {
+                    anyhow::bail!("An admin address can't be combined with configured listeners");
+                }
+                Ok(self.listeners.clone())
+            }
+            None => Ok(vec![ListenerConfig::new(address)]),
+            Some(admin_address) => {
+                let components = self.components();
+                Ok(vec![
+                    ListenerConfig {
+                        services: Some(
+                            components
+                                .slot_lock
+                                .then_some(ListenerService::SlotLock)
+                                .into_iter()
+                                .collect(),
+                        ),
+                        ..ListenerConfig::new(address)
+                    },
+                    ListenerConfig {
+                        services: Some(
+                            components
+                                .admin
+                                .then_some(ListenerService::Admin)
+                                .into_iter()
+                                .collect(),
+                        ),
+                        ..ListenerConfig::new(admin_address)
+                    },
+                ])
+            }
+        }
+    }
+
+    /// Returns the components mounted by the configured profiles
+    pub fn components(&self) -> Components {
+        self.server
+            .profiles
+            .iter()
+            .fold(Components::default(), |components, profile| {
+                components.union(profile.components())
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults_to_full_profile() -> Result<()> {
+        let config = Config::parse("")?;
+        assert_eq!(config.server.profiles, vec![Profile::Full]);
+        assert_eq!(config.components(), Profile::Full.components());
+        Ok(())
+    }
+
+    #[test]
+    fn test_profiles_compose() -> Result<()> {
+        let config = Config::parse(
+            r#"
+            [server]
+            profiles = ["read-mirror", "admin-only"]
+            "#,
+        )?;
+        let components = config.components();
+        assert!(components.slot_lock);
+        assert!(!components.slot_lock_writes);
+        assert!(components.admin);
+        assert!(!components.rebroadcast);
+        Ok(())
+    }
+
+    #[test]
+    fn test_revert_executor_defaults() -> Result<()> {
+        let config = Config::parse(
+            r#"
+            [revert_executor]
+            url = "http://localhost:8080/reverts"
+            "#,
+        )?;
+        let revert_executor = config.revert_executor.unwrap();
+        assert_eq!(revert_executor.protocol, RevertExecutorProtocol::Http);
+        assert_eq!(revert_executor.max_attempts, 10);
+
+        assert!(Config::parse(
+            r#"
+            [revert_executor]
+            url = "http://localhost:50052"
+            protocol = "grpc"
+            max_attempts = 0
+            "#,
+        )
+        .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_contract_thresholds() -> Result<()> {
+        let config = Config::parse(
+            r#"
+            [contract_thresholds."0xBridge"]
+            confirmation_threshold = 6
+            revert_threshold = 36
+
+            [contract_thresholds."0xtoken"]
+            confirmation_threshold = 1
+            "#,
+        )?;
+        assert_eq!(
+            config.contract_thresholds["0xtoken"],
+            ThresholdOverride {
+                confirmation_threshold: Some(1),
+                revert_threshold: None,
+            }
+        );
+        assert_eq!(
+            config.contract_thresholds["0xBridge"].revert_threshold,
+            Some(36)
+        );
+
+        assert!(Config::parse(
+            r#"
+            [contract_thresholds."0xtoken"]
+            confirmation_threshold = 0
+            "#,
+        )
+        .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_namespace_thresholds() -> Result<()> {
+        let config = Config::parse(
+            r#"
+            [namespace_thresholds."testnet"]
+            confirmation_threshold = 1
+            revert_threshold = 3
+            "#,
+        )?;
+        assert_eq!(
+            config.namespace_thresholds["testnet"],
+            ThresholdOverride {
+                confirmation_threshold: Some(1),
+                revert_threshold: Some(3),
+            }
+        );
+
+        assert!(Config::parse(
+            r#"
+            [namespace_thresholds."testnet"]
+            revert_threshold = 0
+            "#,
+        )
+        .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_backup() -> Result<()> {
+        let config = Config::parse(
+            r#"
+            [backup]
+            path = "/var/backups/sova-sentinel"
+
+            [backup.s3]
+            endpoint = "http://localhost:9000"
+            bucket = "backups"
+            "#,
+        )?;
+        let backup = config.backup.unwrap();
+        assert_eq!(backup.interval_secs, 3600);
+        assert_eq!(backup.keep, 24);
+        let s3 = backup.s3.unwrap();
+        assert_eq!(s3.region, "us-east-1");
+        assert_eq!(s3.prefix, "");
+
+        assert!(Config::parse("[backup]\ninterval_secs = 60").is_err());
+        assert!(Config::parse("[backup]\npath = \"backups\"\nkeep = 0").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_retention() -> Result<()> {
+        let config = Config::parse(
+            r#"
+            [retention]
+            max_age_blocks = 100000
+            purge_after_days = 30
+            purge_path = "purged.db"
+            "#,
+        )?;
+        let retention = config.retention.unwrap();
+        assert_eq!(retention.max_age_blocks, Some(100000));
+        assert_eq!(retention.max_age_days, None);
+        assert_eq!(retention.purge_after_days, Some(30));
+        assert_eq!(retention.interval_secs, 3600);
+
+        assert!(Config::parse("[retention]\ninterval_secs = 60").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_maintenance() -> Result<()> {
+        let config = Config::parse("[maintenance]\nwindows = [\"02:00-04:00\", \"23:30-00:30\"]")?;
+        let maintenance = config.maintenance.unwrap();
+        assert_eq!(maintenance.interval_secs, 3600);
+        assert_eq!(maintenance.vacuum_pages, 1000);
+        assert_eq!(maintenance.windows()?.len(), 2);
+
+        assert!(Config::parse("[maintenance]\nwindows = [\"2am-4am\"]").is_err());
+        assert!(Config::parse("[maintenance]\ninterval_secs = 0").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_replication() -> Result<()> {
+        let config = Config::parse("[replication]\nprimary_url = \"http://primary:50051\"")?;
+        let replication = config.replication.unwrap();
+        assert_eq!(replication.primary_url, "http://primary:50051");
+        assert_eq!(replication.poll_interval_ms, 1000);
+
+        assert!(Config::parse("[replication]\npoll_interval_ms = 100").is_err());
+        assert!(Config::parse(
+            "[replication]\nprimary_url = \"http://primary\"\npoll_interval_ms = 0"
+        )
+        .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_alerting() -> Result<()> {
+        let config = Config::parse(
+            r#"
+            [alerting]
+            webhook_url = "https://alerts.example/hook"
+
+            [[alerting.rules]]
+            name = "revert-surge"
+            max_reverts = 10
+            window_blocks = 100
+
+            [[alerting.rules]]
+            name = "bridge-reverts"
+            max_reverts = 0
+            window_blocks = 1000
+            contract_address = "0x123"
+            "#,
+        )?;
+        let alerting = config.alerting.unwrap();
+        assert_eq!(
+            alerting.webhook_url.as_deref(),
+            Some("https://alerts.example/hook")
+        );
+        assert_eq!(alerting.rules.len(), 2);
+        assert_eq!(alerting.rules[0].namespace, None);
+        assert_eq!(alerting.rules[1].contract_address.as_deref(), Some("0x123"));
+
+        assert!(Config::parse(
+            "[alerting]
+rules = []"
+        )
+        .is_err());
+        assert!(Config::parse(
+            "[[alerting.rules]]
+name = \"a\"
+max_reverts = 1
+window_blocks = 0"
+        )
+        .is_err());
+        assert!(Config::parse(
+            "[[alerting.rules]]
+name = \"a\"
+max_reverts = 1
+window_blocks = 10
+\
+             [[alerting.rules]]
+name = \"a\"
+max_reverts = 2
+window_blocks = 10"
+        )
+        .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_rate_limit() -> Result<()> {
+        let config = Config::parse(
+            "[rate_limit]\nrequests_per_sec = 100\nmax_concurrent = 8\n\
+             [rate_limit.api_keys.indexer]\nrequests_per_sec = 1000\nburst = 2000",
+        )?;
+        let rate_limit = config.rate_limit.unwrap();
+        assert_eq!(rate_limit.api_key_header, "x-api-key");
+        assert_eq!(
+            rate_limit.default_quota(),
+            RateLimitQuota {
+                requests_per_sec: Some(100),
+                burst: None,
+                max_concurrent: Some(8),
+            }
+        );
+        assert_eq!(
+            rate_limit.api_keys["indexer"],
+            RateLimitQuota {
+                requests_per_sec: Some(1000),
+                burst: Some(2000),
+                max_concurrent: None,
+            }
+        );
+
+        assert!(Config::parse("[rate_limit]\nrequests_per_sec = 0").is_err());
+        assert!(Config::parse("[rate_limit]\nburst = 10").is_err());
+        assert!(Config::parse("[rate_limit]\napi_key_header = \"api key\"").is_err());
+        assert!(Config::parse("[rate_limit.api_keys.indexer]\nmax_concurrent = 0").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_identity() -> Result<()> {
+        let config = Config::parse(
+            "[identity]\nclient_cn_header = \"x-client-cn\"\ntrusted_proxies = [\"10.0.0.1\"]\n\
+             [identity.api_keys]\nsecret = \"indexer\"",
+        )?;
+        let identity = config.identity.unwrap();
+        assert_eq!(identity.api_key_header, "x-api-key");
+        assert_eq!(identity.api_keys["secret"], "indexer");
+        assert_eq!(identity.client_cn_header.as_deref(), Some("x-client-cn"));
+        assert_eq!(identity.trusted_proxies, ["10.0.0.1".parse::<IpAddr>()?]);
+        assert_eq!(identity.active_lock_limit("key:indexer"), None);
+
+        let config = Config::parse(
+            "[[listeners]]\naddress = \"[::]:50051\"\ntls_cert = \"cert.pem\"\n\
+             tls_key = \"key.pem\"\nclient_ca = \"ca.pem\"\n\
+             [identity]\nmax_active_locks = 1000\n\
+             [identity.active_lock_limits]\n\"cn:sequencer\" = 50000",
+        )?;
+        let identity = config.identity.unwrap();
+        assert_eq!(identity.active_lock_limit("cn:sequencer"), Some(50000));
+        assert_eq!(identity.active_lock_limit("key:indexer"), Some(1000));
+
+        assert!(Config::parse("[identity]").is_err());
+        // The header can't be trusted from every caller
+        assert!(Config::parse("[identity]\nclient_cn_header = \"x-client-cn\"").is_err());
+        assert!(Config::parse("[identity]\ntrusted_proxies = [\"10.0.0.1\"]").is_err());
+        assert!(Config::parse(
+            "[identity]\nclient_cn_header = \"client cn\"\ntrusted_proxies = [\"10.0.0.1\"]"
+        )
+        .is_err());
+        assert!(Config::parse("[identity.api_keys]\nsecret = \"\"").is_err());
+        assert!(Config::parse(
+            "[identity.api_keys]\nsecret = \"indexer\"\n\
+             [identity.active_lock_limits]\n\"key:backfill\" = 10"
+        )
+        .is_err());
+        assert!(Config::parse(
+            "[identity.api_keys]\nsecret = \"indexer\"\n\
+             [identity.active_lock_limits]\n\"indexer\" = 10"
+        )
+        .is_err());
+        assert!(Config::parse(
+            "[identity.api_keys]\nsecret = \"indexer\"\n\
+             [identity.active_lock_limits]\n\"key:indexer\" = 10"
+        )
+        .is_ok());
+        assert!(Config::parse(
+            "[[listeners]]\naddress = \"[::]:50051\"\ntls_cert = \"cert.pem\"\n\
+             tls_key = \"key.pem\"\nclient_ca = \"ca.pem\"\n\
+             [identity]\nmax_active_locks = 0"
+        )
+        .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_listeners() -> Result<()> {
+        let config = Config::parse(
+            "[[listeners]]\naddress = \"[::]:50051\"\n\
+             [[listeners]]\naddress = \"127.0.0.1:50052\"\nservices = [\"admin\"]\n\
+             auth_token_file = \"/run/secrets/admin-token\"",
+        )?;
+        assert_eq!(config.listeners.len(), 2);
+        assert!(config.listeners[0].serves(ListenerService::SlotLock));
+        assert!(config.listeners[0].serves(ListenerService::Admin));
+        assert_eq!(config.listeners[0].auth_token_file, None);
+        assert!(!config.listeners[1].serves(ListenerService::SlotLock));
+        assert!(config.listeners[1].serves(ListenerService::Admin));
+        assert_eq!(
+            config.listeners[1].auth_token_file.as_deref(),
+            Some("/run/secrets/admin-token")
+        );
+
+        assert!(Config::parse("[[listeners]]\naddress = \"localhost\"").is_err());
+        assert!(Config::parse(
+            "[[listeners]]\naddress = \"[::]:50051\"\n[[listeners]]\naddress = \"[::]:50051\""
+        )
+        .is_err());
+        assert!(Config::parse("[[listeners]]\naddress = \"[::]:50051\"\nservices = []").is_err());
+        assert!(
+            Config::parse("[[listeners]]\naddress = \"[::]:50051\"\nservices = [\"health\"]")
+                .is_err()
+        );
+        assert!(
+            Config::parse("[[listeners]]\naddress = \"[::]:50051\"\ntls_cert = \"cert.pem\"")
+                .is_err()
+        );
+        assert!(
+            Config::parse("[[listeners]]\naddress = \"[::]:50051\"\nclient_ca = \"ca.pem\"")
+                .is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_admin_listener() -> Result<()> {
+        let config = Config::default();
+        let listeners = config.listeners("[::]:50051", None)?;
+        assert_eq!(listeners, vec![ListenerConfig::new("[::]:50051")]);
+
+        let listeners = config.listeners("[::]:50051", Some("127.0.0.1:50052"))?;
+        assert_eq!(listeners.len(), 2);
+        assert_eq!(listeners[0].address, "[::]:50051");
+        assert!(!listeners[0].serves(ListenerService::Admin));
+        assert!(listeners[0].serves(ListenerService::SlotLock));
+        assert_eq!(listeners[1].address, "127.0.0.1:50052");
+        assert!(listeners[1].serves(ListenerService::Admin));
+        assert!(!listeners[1].serves(ListenerService::SlotLock));
+
+        let config = Config::parse("[[listeners]]\naddress = \"[::]:50051\"")?;
+        assert!(config
+            .listeners("[::]:50051", Some("127.0.0.1:50052"))
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_logging() -> Result<()> {
+        let logging = Config::default().logging;
+        assert_eq!(logging.level, None);
+        assert_eq!(logging.max_logged_slots, DEFAULT_MAX_LOGGED_SLOTS);
+
+        let config = Config::parse(
+            "[logging]\nlevel = \"info\"\nmax_logged_slots = 3\n\
+             [logging.modules]\n\"sova_sentinel_server::service::bitcoin\" = \"debug\"\n\
+             [logging.sample_rates]\nLockSlot = 0.1",
+        )?;
+        assert_eq!(config.logging.level.as_deref(), Some("info"));
+        assert_eq!(config.logging.max_logged_slots, 3);
+        assert_eq!(
+            config.logging.modules["sova_sentinel_server::service::bitcoin"],
+            "debug"
+        );
+        assert_eq!(config.logging.sample_rates["LockSlot"], 0.1);
+
+        assert!(Config::parse("[logging]\nlevel = \"loud\"").is_err());
+        assert!(Config::parse("[logging.modules]\n\"a=b\" = \"info\"").is_err());
+        assert!(Config::parse("[logging.sample_rates]\nLockSlot = 2.0").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_invalid_profiles() {
+        assert!(Config::parse("[server]\nprofiles = [\"everything\"]").is_err());
+        assert!(Config::parse("[server]\nprofiles = []").is_err());
+        assert!(Config::parse("[server]\nprofile = [\"full\"]").is_err());
+    }
+}
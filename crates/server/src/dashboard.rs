@@ -0,0 +1,244 @@
+//! Read-only HTML dashboard of the sentinel's locks, reverts and Bitcoin node, for operators
+//! without a Grafana deployment. Pages are rendered on the server and refresh themselves.
+
+use crate::db::{Database, LockedSlot};
+use crate::service::{network_name, BitcoinRpcServiceAPI};
+use anyhow::Result;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::{Html, IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+
+/// Locks listed in each of the active and reverted lock tables
+const DASHBOARD_ROWS: usize = 50;
+
+/// Sova blocks of each namespace the recent revert count covers
+const RECENT_BLOCKS: u64 = 100;
+
+/// Seconds after which the page reloads itself
+const REFRESH_SECS: u64 = 15;
+
+const STYLE: &str = "body{font-family:sans-serif;margin:2em;color:#222}\
+table{border-collapse:collapse;margin-bottom:2em}\
+th,td{border:1px solid #ccc;padding:4px 8px;text-align:left;font-family:monospace}\
+th{background:#f4f4f4}.error{color:#b00}";
+
+/// Renders the dashboard from the database and the Bitcoin node
+#[derive(Clone)]
+pub struct Dashboard {
+    db: Database,
+    bitcoin: Arc<dyn BitcoinRpcServiceAPI>,
+}
+
+impl Dashboard {
+    pub fn new(db: Database, bitcoin: Arc<dyn BitcoinRpcServiceAPI>) -> Self {
+        Self { db, bitcoin }
+    }
+
+    /// Serves the dashboard at `/`
+    pub fn router(self) -> Router {
+        Router::new().route("/", get(index)).with_state(self)
+    }
+
+    /// Renders the dashboard page. An unreachable Bitcoin node is shown on the page rather
+    /// than failing it.
+    pub async fn render(&self) -> Result<String> {
+        let active_locks = self.db.active_lock_count(None)?;
+        let recent_reverts = self.db.recent_reverts(None, None, RECENT_BLOCKS)?;
+        let stats = self.db.lock_stats(None, None)?;
+        let newest_locks = self.db.newest_active_locks(DASHBOARD_ROWS)?;
+        let reverted_locks = self.db.latest_reverted_locks(DASHBOARD_ROWS)?;
+
+        let mut page = format!(
+            "<!DOCTYPE html><html><head><meta charset=\"utf-8\">\
+             <meta http-equiv=\"refresh\" content=\"{}\">\
+             <title>Sova Sentinel</title><style>{}</style></head><body>\
+             <h1>Sova Sentinel {}</h1>",
+            REFRESH_SECS,
+            STYLE,
+            env!("CARGO_PKG_VERSION")
+        );
+
+        page.push_str("<h2>Overview</h2><table>");
+        write!(
+            page,
+            "<tr><th>Active locks</th><td>{}</td></tr>",
+            active_locks
+        )?;
+        write!(
+            page,
+            "<tr><th>Reverts in the last {} sova blocks</th><td>{}</td></tr>",
+            RECENT_BLOCKS, recent_reverts
+        )?;
+        match self.bitcoin.chain_info().await {
+            Ok(chain_info) => write!(
+                page,
+                "<tr><th>Bitcoin network</th><td>{}</td></tr>\
+                 <tr><th>Bitcoin tip height</th><td>{}</td></tr>",
+                network_name(chain_info.network),
+                chain_info.tip_height
+            )?,
+            Err(e) => write!(
+                page,
+                "<tr><th>Bitcoin node</th><td class=\"error\">unreachable: {}</td></tr>",
+                escape(&format!("{:#}", e))
+            )?,
+        }
+        page.push_str("</table>");
+
+        page.push_str(
+            "<h2>Contracts</h2><table><tr><th>Namespace</th><th>Contract</th><th>Active</th>\
+             <th>Unlocked</th><th>Reverted</th><th>Average duration (sova blocks)</th></tr>",
+        );
+        for contract in &stats {
+            write!(
+                page,
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{:.1}</td></tr>",
+                namespace_cell(&contract.namespace),
+                escape(&contract.contract_address),
+                contract.active,
+                contract.unlocked,
+                contract.reverted,
+                contract.average_duration_blocks()
+            )?;
+        }
+        page.push_str("</table>");
+
+        page.push_str(
+            "<h2>Newest active locks</h2><table><tr><th>Namespace</th><th>Contract</th>\
+             <th>Slot</th><th>Bitcoin transaction</th><th>Bitcoin block</th>\
+             <th>Sova block</th></tr>",
+        );
+        for lock in &newest_locks {
+            write!(
+                page,
+                "<tr>{}<td>{}</td><td>{}</td><td>{}</td></tr>",
+                lock_cells(lock),
+                escape(&lock.btc_txid),
+                lock.btc_block,
+                lock.start_block
+            )?;
+        }
+        page.push_str("</table>");
+
+        page.push_str(
+            "<h2>Latest reverts</h2><table><tr><th>Namespace</th><th>Contract</th><th>Slot</th>\
+             <th>Bitcoin transaction</th><th>Locked at sova block</th>\
+             <th>Reverted at sova block</th><th>Reason</th></tr>",
+        );
+        for lock in &reverted_locks {
+            write!(
+                page,
+                "<tr>{}<td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                lock_cells(lock),
+                escape(&lock.btc_txid),
+                lock.start_block,
+                lock.end_block.unwrap_or_default(),
+                lock.unlock_reason.map_or("", |reason| reason.as_str())
+            )?;
+        }
+        page.push_str("</table></body></html>");
+        Ok(page)
+    }
+}
+
+async fn index(State(dashboard): State<Dashboard>) -> Response {
+    match dashboard.render().await {
+        Ok(page) => Html(page).into_response(),
+        Err(e) => {
+            tracing::error!("Failed to render the dashboard: {:#}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", e)).into_response()
+        }
+    }
+}
+
+/// Serves the dashboard over HTTP until the process exits
+pub async fn serve(addr: SocketAddr, dashboard: Dashboard) -> Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    tracing::info!("Dashboard listening on {}", addr);
+    axum::serve(listener, dashboard.router()).await?;
+    Ok(())
+}
+
+fn namespace_cell(namespace: &str) -> String {
+    if namespace.is_empty() {
+        "<i>default</i>".to_string()
+    } else {
+        escape(namespace)
+    }
+}
+
+fn lock_cells(lock: &LockedSlot) -> String {
+    format!(
+        "<td>{}</td><td>{}</td><td>0x{}</td>",
+        namespace_cell(&lock.namespace),
+        escape(&lock.contract_address),
+        hex::encode(&lock.slot_index)
+    )
+}
+
+/// Escapes text for an HTML element or quoted attribute
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{SlotInsertData, UnlockReason};
+    use crate::service::{BitcoinRpcService, MockConfirmations, MockRpcClient};
+
+    fn slot(contract_address: &str, idx: u8) -> SlotInsertData {
+        SlotInsertData {
+            namespace: String::new(),
+            contract_address: contract_address.to_string(),
+            start_block: 100,
+            btc_block: 200,
+            slot_index: vec![idx],
+            btc_txid: "txid1".to_string(),
+            revert_value: vec![4],
+            current_value: vec![7],
+            metadata: Vec::new(),
+            labels: Default::default(),
+            alt_btc_txids: Vec::new(),
+            required_confirmed_txids: 1,
+            locked_by: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_render() -> Result<()> {
+        let db = Database::new(rusqlite::Connection::open_in_memory()?)?;
+        db.with_transaction(|tx| {
+            db.batch_insert_slot_locks(tx, &[slot("0x123", 1), slot("<script>", 2)])
+        })?;
+        db.unlock_slot("", "0x123", &[1], 120, UnlockReason::RevertThreshold)?;
+
+        let client = MockRpcClient::new(6, MockConfirmations::Never);
+        let bitcoin = BitcoinRpcService::new(Arc::new(client), 6, 0);
+        let page = Dashboard::new(db, Arc::new(bitcoin)).render().await?;
+
+        assert!(page.contains("<tr><th>Active locks</th><td>1</td></tr>"));
+        assert!(page.contains("<tr><th>Bitcoin network</th><td>regtest</td></tr>"));
+        assert!(page.contains("<td>120</td><td>revert-threshold</td>"));
+        assert!(page.contains("&lt;script&gt;"));
+        assert!(!page.contains("<script>"));
+        Ok(())
+    }
+}
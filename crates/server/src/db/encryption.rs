@@ -0,0 +1,181 @@
+//! At-rest encryption of the database file with SQLCipher. Lock rows hold pre-images of contract
+//! storage, which some operators don't want readable from a copied disk or backup.
+
+use anyhow::{Context, Result};
+use rusqlite::{Connection, OptionalExtension};
+use std::path::Path;
+use std::process::Command;
+use std::sync::Arc;
+
+/// Key of an encrypted database, a passphrase or a raw key written as `x'<64 hex digits>'`
+#[derive(Clone)]
+pub struct DatabaseKey(Arc<str>);
+
+impl std::fmt::Debug for DatabaseKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("DatabaseKey(..)")
+    }
+}
+
+impl DatabaseKey {
+    pub fn new(key: impl Into<String>) -> Result<Self> {
+        let key = key.into();
+        if key.is_empty() {
+            anyhow::bail!("Database key is empty");
+        }
+        Ok(Self(key.into()))
+    }
+
+    /// Reads a key from a file, ignoring surrounding whitespace
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read database key file {}", path.display()))?;
+        Self::new(contents.trim())
+            .with_context(|| format!("Invalid database key file {}", path.display()))
+    }
+
+    /// Runs `command` with `sh -c` and takes the key from its output, ignoring surrounding
+    /// whitespace, e.g. to decrypt a wrapped key with a KMS CLI
+    pub fn from_command(command: &str) -> Result<Self> {
+        let output = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .output()
+            .context("Failed to run database key command")?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "Database key command failed with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        let key = String::from_utf8(output.stdout)
+            .context("Database key command printed a key that isn't UTF-8")?;
+        Self::new(key.trim()).context("Database key command printed no key")
+    }
+
+    /// Keys a freshly opened connection, before anything else reads the file. Fails if SQLite
+    /// was built without SQLCipher, which would silently ignore the key and write the database
+    /// in plaintext, or if the key doesn't decrypt the file.
+    pub fn apply(&self, conn: &Connection) -> Result<()> {
+        if cipher_version(conn)?.is_none() {
+            anyhow::bail!(
+                "A database key is configured but the sentinel was built without SQLCipher, \
+                 rebuild it with `--features sqlcipher`"
+            );
+        }
+        conn.pragma_update(None, "key", &*self.0)?;
+        conn.query_row("SELECT COUNT(*) FROM sqlite_master", [], |row| {
+            row.get::<_, i64>(0)
+        })
+        .context(
+            "Failed to decrypt the database, check the key, or that the file is encrypted at all",
+        )?;
+        Ok(())
+    }
+}
+
+/// Returns the SQLCipher version SQLite was built with, `None` for plain SQLite
+pub fn cipher_version(conn: &Connection) -> Result<Option<String>> {
+    Ok(conn
+        .query_row("PRAGMA cipher_version", [], |row| row.get(0))
+        .optional()?)
+}
+
+/// Opens the database file at `path`, keyed with `key` if one is given
+pub(crate) fn open_connection(
+    path: &Path,
+    flags: rusqlite::OpenFlags,
+    key: Option<&DatabaseKey>,
+) -> Result<Connection> {
+    let conn = Connection::open_with_flags(path, flags)
+        .with_context(|| format!("Failed to open database {}", path.display()))?;
+    if let Some(key) = key {
+        key.apply(&conn)?;
+    }
+    Ok(conn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_sources() -> Result<()> {
+        assert!(DatabaseKey::new("").is_err());
+
+        let path = std::env::temp_dir().join(format!("db_key_{}", std::process::id()));
+        std::fs::write(&path, "  secret\n")?;
+        assert_eq!(&*DatabaseKey::load(&path)?.0, "secret");
+        std::fs::remove_file(&path)?;
+
+        assert_eq!(&*DatabaseKey::from_command("echo secret")?.0, "secret");
+        assert!(DatabaseKey::from_command("exit 1").is_err());
+        assert!(DatabaseKey::from_command("true").is_err());
+        assert_eq!(
+            format!("{:?}", DatabaseKey::new("secret")?),
+            "DatabaseKey(..)"
+        );
+        Ok(())
+    }
+
+    #[cfg(not(feature = "sqlcipher"))]
+    #[test]
+    fn test_key_requires_sqlcipher() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+        assert_eq!(cipher_version(&conn)?, None);
+        let err = DatabaseKey::new("secret")?.apply(&conn).unwrap_err();
+        assert!(err.to_string().contains("without SQLCipher"));
+        Ok(())
+    }
+
+    #[cfg(feature = "sqlcipher")]
+    #[test]
+    fn test_encrypted_database() -> Result<()> {
+        use crate::db::{Database, SlotInsertData};
+        use rusqlite::OpenFlags;
+
+        let dir = std::env::temp_dir().join(format!("encrypted_{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("slot_locks.db");
+        let backup_path = dir.join("backup.db");
+        let flags = OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE;
+        let key = DatabaseKey::new("secret")?;
+
+        let db = Database::open(&path, flags, Some(key.clone()))?;
+        db.with_transaction(|transaction| {
+            db.batch_insert_slot_locks(
+                transaction,
+                &[SlotInsertData {
+                    namespace: String::new(),
+                    contract_address: "0x123".to_string(),
+                    start_block: 100,
+                    btc_block: 200,
+                    slot_index: vec![1],
+                    btc_txid: "txid1".to_string(),
+                    revert_value: vec![0xc0, 0xff, 0xee],
+                    current_value: vec![],
+                    metadata: Vec::new(),
+                    labels: Default::default(),
+                    alt_btc_txids: Vec::new(),
+                    required_confirmed_txids: 1,
+                    locked_by: None,
+                }],
+            )
+        })?;
+        db.backup_to(&backup_path)?;
+        drop(db);
+
+        for path in [&path, &backup_path] {
+            let contents = std::fs::read(path)?;
+            assert!(!contents.starts_with(b"SQLite format 3"));
+            assert!(Database::open(path, flags, None).is_err());
+            assert!(Database::open(path, flags, Some(DatabaseKey::new("wrong")?)).is_err());
+            let db = Database::open(path, flags, Some(key.clone()))?;
+            assert!(db.is_slot_locked("", "0x123", &[1])?);
+        }
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,118 @@
+//! Bitcoin block headers synced for SPV verification. The headers form a single chain of
+//! consecutive heights starting at a checkpoint; a reorg truncates the chain before the new
+//! branch is appended.
+
+use super::Database;
+use anyhow::Result;
+use bitcoin::bip158::FilterHeader;
+use bitcoin::block::Header;
+use bitcoin::consensus::{deserialize, serialize};
+use bitcoin::BlockHash;
+use rusqlite::{OptionalExtension, Transaction};
+
+impl Database {
+    /// Returns the height and header of the highest stored block, `None` if no header is stored
+    pub fn header_tip(&self) -> Result<Option<(u64, Header)>> {
+        let conn = self.connection()?;
+        let tip: Option<(i64, Vec<u8>)> = conn
+            .query_row(
+                "SELECT height, header FROM block_headers ORDER BY height DESC LIMIT 1",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+        tip.map(|(height, header)| Ok((height as u64, deserialize(&header)?)))
+            .transpose()
+    }
+
+    /// Returns the stored header at `height`
+    pub fn block_header(&self, height: u64) -> Result<Option<Header>> {
+        let conn = self.connection()?;
+        let header: Option<Vec<u8>> = conn
+            .query_row(
+                "SELECT header FROM block_headers WHERE height = ?1",
+                [height as i64],
+                |row| row.get(0),
+            )
+            .optional()?;
+        header.map(|header| Ok(deserialize(&header)?)).transpose()
+    }
+
+    /// Returns the height of the stored block with hash `block_hash`
+    pub fn block_height(&self, block_hash: &BlockHash) -> Result<Option<u64>> {
+        let conn = self.connection()?;
+        let height: Option<i64> = conn
+            .query_row(
+                "SELECT height FROM block_headers WHERE block_hash = ?1",
+                [block_hash.to_string()],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(height.map(|height| height as u64))
+    }
+
+    /// Stores `headers` at consecutive heights from `start_height`. Fails if any of the heights
+    /// is already stored.
+    pub fn insert_block_headers(
+        &self,
+        transaction: &Transaction,
+        start_height: u64,
+        headers: &[Header],
+    ) -> Result<()> {
+        let mut stmt = transaction.prepare(
+            "INSERT INTO block_headers (height, block_hash, header) VALUES (?1, ?2, ?3)",
+        )?;
+        for (height, header) in (start_height..).zip(headers) {
+            stmt.execute(rusqlite::params![
+                height as i64,
+                header.block_hash().to_string(),
+                serialize(header),
+            ])?;
+        }
+        Ok(())
+    }
+
+    /// Returns the filter header stored for the block at `height`, `None` if its filter wasn't
+    /// checked yet
+    pub fn block_filter_header(&self, height: u64) -> Result<Option<FilterHeader>> {
+        let conn = self.connection()?;
+        let filter_header: Option<Vec<u8>> = conn
+            .query_row(
+                "SELECT filter_header FROM block_headers WHERE height = ?1",
+                [height as i64],
+                |row| row.get(0),
+            )
+            .optional()?
+            .flatten();
+        filter_header
+            .map(|filter_header| Ok(deserialize(&filter_header)?))
+            .transpose()
+    }
+
+    /// Stores the filter headers of the blocks at consecutive heights from `start_height`
+    pub fn set_block_filter_headers(
+        &self,
+        transaction: &Transaction,
+        start_height: u64,
+        filter_headers: &[FilterHeader],
+    ) -> Result<()> {
+        let mut stmt =
+            transaction.prepare("UPDATE block_headers SET filter_header = ?2 WHERE height = ?1")?;
+        for (height, filter_header) in (start_height..).zip(filter_headers) {
+            stmt.execute(rusqlite::params![height as i64, serialize(filter_header)])?;
+        }
+        Ok(())
+    }
+
+    /// Deletes the headers at `from_height` and above, returning how many were deleted
+    pub fn truncate_block_headers(
+        &self,
+        transaction: &Transaction,
+        from_height: u64,
+    ) -> Result<usize> {
+        Ok(transaction.execute(
+            "DELETE FROM block_headers WHERE height >= ?1",
+            [from_height as i64],
+        )?)
+    }
+}
@@ -0,0 +1,144 @@
+//! Value history of each slot. A row per lock is kept by triggers on the lock table (see
+//! migration 23), and outlives the lock when the retention policy purges it.
+
+use super::{Database, UnlockReason};
+use anyhow::Result;
+
+/// The values a lock of a slot recorded, and how it ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SlotValueChange {
+    pub lock_id: i64,
+    pub start_block: u64,
+    pub end_block: Option<u64>,
+    pub unlock_reason: Option<UnlockReason>,
+    pub btc_txid: String,
+    /// Value of the slot before the locked write
+    pub revert_value: Vec<u8>,
+    /// Value written under the lock
+    pub current_value: Vec<u8>,
+    /// Whether the lock has been purged from the lock table
+    pub purged: bool,
+}
+
+impl SlotValueChange {
+    /// Returns the value the slot settled on when the lock ended: the revert value if it was
+    /// reverted, the current value otherwise. `None` while the lock is active.
+    pub fn settled_value(&self) -> Option<&[u8]> {
+        self.end_block?;
+        match self.unlock_reason {
+            Some(reason) if reason.is_revert() => Some(&self.revert_value),
+            _ => Some(&self.current_value),
+        }
+    }
+}
+
+impl Database {
+    /// Returns the value history of a slot, oldest lock first
+    pub fn slot_value_history(
+        &self,
+        namespace: &str,
+        contract_address: &str,
+        slot_index: &[u8],
+    ) -> Result<Vec<SlotValueChange>> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare(
+            "SELECT h.lock_id, h.start_block, h.end_block, h.unlock_reason, h.btc_txid,
+                 h.revert_value, h.current_value, l.id IS NULL
+             FROM slot_value_history h
+             LEFT JOIN slot_locks l ON l.id = h.lock_id
+             WHERE h.namespace = ?1 AND h.contract_address = ?2 AND h.slot_index = ?3
+             ORDER BY h.start_block, h.lock_id",
+        )?;
+        let changes = stmt
+            .query_map(
+                rusqlite::params![namespace, contract_address, slot_index],
+                |row| {
+                    Ok(SlotValueChange {
+                        lock_id: row.get(0)?,
+                        start_block: row.get(1)?,
+                        end_block: row.get(2)?,
+                        unlock_reason: row.get(3)?,
+                        btc_txid: row.get(4)?,
+                        revert_value: row.get(5)?,
+                        current_value: row.get(6)?,
+                        purged: row.get(7)?,
+                    })
+                },
+            )?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(changes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::SlotInsertData;
+
+    fn slot(start_block: u64, revert_value: u8, current_value: u8) -> SlotInsertData {
+        SlotInsertData {
+            namespace: String::new(),
+            contract_address: "0x123".to_string(),
+            start_block,
+            btc_block: 200,
+            slot_index: vec![1],
+            btc_txid: format!("txid{}", start_block),
+            revert_value: vec![revert_value],
+            current_value: vec![current_value],
+            metadata: Vec::new(),
+            labels: Default::default(),
+            alt_btc_txids: Vec::new(),
+            required_confirmed_txids: 1,
+            locked_by: None,
+        }
+    }
+
+    #[test]
+    fn test_value_history() -> Result<()> {
+        let db = Database::new(rusqlite::Connection::open_in_memory()?)?;
+        db.with_transaction(|tx| db.batch_insert_slot_locks(tx, &[slot(100, 1, 2)]))?;
+        db.unlock_slot("", "0x123", &[1], 110, UnlockReason::Confirmed)?;
+        db.with_transaction(|tx| db.batch_insert_slot_locks(tx, &[slot(120, 2, 3)]))?;
+        db.unlock_slot("", "0x123", &[1], 130, UnlockReason::RevertThreshold)?;
+        db.with_transaction(|tx| db.batch_insert_slot_locks(tx, &[slot(140, 2, 4)]))?;
+
+        // Purging the first lock keeps its history
+        let ids: Vec<_> = db
+            .slot_lock_rows("", "0x123", &[1])?
+            .into_iter()
+            .filter(|row| row.lock.start_block == 100)
+            .map(|row| row.id)
+            .collect();
+        db.with_transaction(|tx| {
+            db.archive_lock_rows(tx, &ids)?;
+            db.delete_lock_rows(tx, &ids)
+        })?;
+
+        let history = db.slot_value_history("", "0x123", &[1])?;
+        let summary: Vec<_> = history
+            .iter()
+            .map(|change| {
+                (
+                    change.start_block,
+                    change.end_block,
+                    change.settled_value().map(<[u8]>::to_vec),
+                    change.purged,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (100, Some(110), Some(vec![2]), true),
+                (120, Some(130), Some(vec![2]), false),
+                (140, None, None, false),
+            ]
+        );
+        assert_eq!(
+            history[1].unlock_reason,
+            Some(UnlockReason::RevertThreshold)
+        );
+        assert!(db.slot_value_history("", "0x123", &[2])?.is_empty());
+        Ok(())
+    }
+}
@@ -1,39 +1,730 @@
 use anyhow::Result;
-use rusqlite::Connection;
+use rusqlite::{Connection, OptionalExtension};
+
+/// A schema migration, with an optional check run in the migration's transaction before it is
+/// applied, that can refuse to migrate a database the migration would corrupt
+struct Migration {
+    sql: &'static str,
+    precheck: Option<fn(&Connection) -> Result<()>>,
+}
+
+impl Migration {
+    const fn new(sql: &'static str) -> Self {
+        Self {
+            sql,
+            precheck: None,
+        }
+    }
+
+    const fn with_precheck(mut self, precheck: fn(&Connection) -> Result<()>) -> Self {
+        self.precheck = Some(precheck);
+        self
+    }
+}
+
+/// Schema migrations, applied in order. The schema version of a database is the number of
+/// migrations applied to it and is tracked in SQLite's `user_version` pragma.
+const MIGRATIONS: &[Migration] = &[
+    // 1: initial schema
+    Migration::new("CREATE TABLE IF NOT EXISTS slot_locks (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        start_block INTEGER NOT NULL,
+        end_block INTEGER,
+        btc_block INTEGER NOT NULL,
+        contract_address TEXT NOT NULL,
+        slot_index BLOB NOT NULL,
+        slot_index_int INTEGER,
+        btc_txid TEXT NOT NULL,
+        revert_value BLOB NOT NULL,
+        current_value BLOB NOT NULL,
+        created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+        updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        -- Removed for development
+        -- UNIQUE(contract_address, slot_index, end_block)
+    );
+
+    -- Create triggers for automatic timestamp updates
+    CREATE TRIGGER IF NOT EXISTS update_slot_locks_timestamp
+     AFTER UPDATE ON slot_locks
+     FOR EACH ROW
+     BEGIN
+         UPDATE slot_locks SET updated_at = CURRENT_TIMESTAMP
+         WHERE rowid = NEW.rowid;
+     END;"),
+    // 2: locks reverted because their Bitcoin transaction was double-spent
+    Migration::new("ALTER TABLE slot_locks ADD COLUMN double_spent INTEGER NOT NULL DEFAULT 0;"),
+    // 3: number of lock attempts per contract rejected because the slot was already locked
+    Migration::new("CREATE TABLE IF NOT EXISTS lock_conflicts (
+        contract_address TEXT PRIMARY KEY,
+        conflicts INTEGER NOT NULL DEFAULT 0,
+        last_conflict_at DATETIME
+    );"),
+    // 4: history of lock transactions replaced by fee bumps (RBF)
+    Migration::new("CREATE TABLE IF NOT EXISTS lock_tx_replacements (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        old_btc_txid TEXT NOT NULL,
+        new_btc_txid TEXT NOT NULL,
+        locks_updated INTEGER NOT NULL,
+        detected INTEGER NOT NULL,
+        replaced_at DATETIME DEFAULT CURRENT_TIMESTAMP
+    );"),
+    // 5: raw lock transactions submitted with lock requests, kept for rebroadcasting
+    Migration::new("CREATE TABLE IF NOT EXISTS lock_transactions (
+        btc_txid TEXT PRIMARY KEY,
+        raw_tx BLOB NOT NULL,
+        created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+    );"),
+    // 6: reverted locks to deliver to the revert executor, and the outcome of each delivery
+    Migration::new("CREATE TABLE IF NOT EXISTS revert_deliveries (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        contract_address TEXT NOT NULL,
+        slot_index BLOB NOT NULL,
+        btc_txid TEXT NOT NULL,
+        revert_value BLOB NOT NULL,
+        current_value BLOB NOT NULL,
+        reverted_at_block INTEGER NOT NULL,
+        reason TEXT NOT NULL,
+        status TEXT NOT NULL DEFAULT 'pending',
+        attempts INTEGER NOT NULL DEFAULT 0,
+        last_error TEXT,
+        created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+        delivered_at DATETIME
+    );
+
+    CREATE INDEX IF NOT EXISTS idx_revert_deliveries_status ON revert_deliveries (status, id);"),
+    // 7: why a lock was unlocked, replacing the double_spent flag. Locks unlocked before this
+    // migration keep a NULL reason.
+    Migration::new("ALTER TABLE slot_locks ADD COLUMN unlock_reason TEXT;
+    UPDATE slot_locks SET unlock_reason = 'double-spent' WHERE double_spent = 1;"),
+    // 8: txids in canonical form, lowercase hex without a 0x prefix, as lock requests are now
+    // normalized to
+    Migration::new("UPDATE slot_locks SET btc_txid = lower(trim(btc_txid));
+    UPDATE slot_locks SET btc_txid = substr(btc_txid, 3) WHERE btc_txid LIKE '0x%';
+    UPDATE revert_deliveries SET btc_txid = lower(trim(btc_txid));
+    UPDATE revert_deliveries SET btc_txid = substr(btc_txid, 3) WHERE btc_txid LIKE '0x%';
+    UPDATE lock_tx_replacements
+        SET old_btc_txid = lower(trim(old_btc_txid)), new_btc_txid = lower(trim(new_btc_txid));
+    UPDATE lock_tx_replacements SET old_btc_txid = substr(old_btc_txid, 3)
+        WHERE old_btc_txid LIKE '0x%';
+    UPDATE lock_tx_replacements SET new_btc_txid = substr(new_btc_txid, 3)
+        WHERE new_btc_txid LIKE '0x%';
+    -- Both forms of the same txid hold the same raw transaction, so either can be dropped
+    UPDATE OR REPLACE lock_transactions SET btc_txid = lower(trim(btc_txid));
+    UPDATE OR REPLACE lock_transactions SET btc_txid = substr(btc_txid, 3)
+        WHERE btc_txid LIKE '0x%';"),
+    // 9: indexes for exporting lock events by sova block range
+    Migration::new("CREATE INDEX IF NOT EXISTS idx_slot_locks_start_block ON slot_locks (start_block, id);
+    CREATE INDEX IF NOT EXISTS idx_slot_locks_end_block ON slot_locks (end_block, id);"),
+    // 10: slot indexes in canonical form, left-padded with zeros to 32 bytes, as lock requests
+    // are now normalized to. The lossy integer copy of the slot index is dropped.
+    Migration::new("UPDATE slot_locks
+        SET slot_index = unhex(substr(hex(zeroblob(32)) || hex(slot_index), -64))
+        WHERE length(slot_index) < 32;
+    UPDATE revert_deliveries
+        SET slot_index = unhex(substr(hex(zeroblob(32)) || hex(slot_index), -64))
+        WHERE length(slot_index) < 32;
+    ALTER TABLE slot_locks DROP COLUMN slot_index_int;")
+    .with_precheck(check_canonical_slots),
+    // 11: namespaces, so one sentinel can serve several Sova networks. Existing rows belong to
+    // the default namespace ''.
+    Migration::new("ALTER TABLE slot_locks ADD COLUMN namespace TEXT NOT NULL DEFAULT '';
+    CREATE INDEX IF NOT EXISTS idx_slot_locks_slot
+        ON slot_locks (namespace, contract_address, slot_index);
+    ALTER TABLE revert_deliveries ADD COLUMN namespace TEXT NOT NULL DEFAULT '';
+    ALTER TABLE lock_tx_replacements ADD COLUMN namespace TEXT NOT NULL DEFAULT '';
+    CREATE TABLE lock_conflicts_by_namespace (
+        namespace TEXT NOT NULL DEFAULT '',
+        contract_address TEXT NOT NULL,
+        conflicts INTEGER NOT NULL DEFAULT 0,
+        last_conflict_at DATETIME,
+        PRIMARY KEY (namespace, contract_address)
+    );
+    INSERT INTO lock_conflicts_by_namespace (contract_address, conflicts, last_conflict_at)
+        SELECT contract_address, conflicts, last_conflict_at FROM lock_conflicts;
+    DROP TABLE lock_conflicts;
+    ALTER TABLE lock_conflicts_by_namespace RENAME TO lock_conflicts;"),
+    // 12: caller-supplied metadata and labels of each lock, labels as a JSON object of strings
+    Migration::new("ALTER TABLE slot_locks ADD COLUMN metadata BLOB NOT NULL DEFAULT x'';
+    ALTER TABLE slot_locks ADD COLUMN labels TEXT NOT NULL DEFAULT '{}';"),
+    // 13: when an unlocked lock was archived by the retention policy, and when an archived lock
+    // was restored. Archived locks are hidden from queries until restored or purged, and restored
+    // locks are exempt from the retention policy.
+    Migration::new("ALTER TABLE slot_locks ADD COLUMN archived_at DATETIME;
+    ALTER TABLE slot_locks ADD COLUMN restored_at DATETIME;"),
+    // 14: index for looking up the locks of a Bitcoin transaction
+    Migration::new("CREATE INDEX IF NOT EXISTS idx_slot_locks_btc_txid ON slot_locks (namespace, btc_txid, id);"),
+    // 15: atomic lock groups, identified by the id of one of their locks. The locks of a group
+    // are unlocked or reverted together.
+    Migration::new("ALTER TABLE slot_locks ADD COLUMN lock_group INTEGER;
+    CREATE INDEX IF NOT EXISTS idx_slot_locks_lock_group ON slot_locks (lock_group)
+        WHERE lock_group IS NOT NULL;"),
+    // 16: slot reservations of the two-phase lock protocol, held until committed as locks or
+    // until they expire at a unix time
+    Migration::new("CREATE TABLE lock_reservations (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        namespace TEXT NOT NULL DEFAULT '',
+        expires_at INTEGER NOT NULL,
+        created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+    );
+    CREATE INDEX IF NOT EXISTS idx_lock_reservations_expires_at ON lock_reservations (expires_at);
+    CREATE TABLE lock_reservation_slots (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        reservation_id INTEGER NOT NULL REFERENCES lock_reservations (id),
+        contract_address TEXT NOT NULL,
+        slot_index BLOB NOT NULL,
+        start_block INTEGER NOT NULL,
+        btc_block INTEGER NOT NULL,
+        btc_txid TEXT NOT NULL,
+        revert_value BLOB NOT NULL,
+        current_value BLOB NOT NULL,
+        metadata BLOB NOT NULL DEFAULT x'',
+        labels TEXT NOT NULL DEFAULT '{}'
+    );
+    CREATE INDEX IF NOT EXISTS idx_lock_reservation_slots_slot
+        ON lock_reservation_slots (contract_address, slot_index);
+    CREATE INDEX IF NOT EXISTS idx_lock_reservation_slots_reservation
+        ON lock_reservation_slots (reservation_id);"),
+    // 17: alternative transactions settling a lock, as a JSON array of txids
+    Migration::new("ALTER TABLE slot_locks ADD COLUMN alt_btc_txids TEXT NOT NULL DEFAULT '[]';
+    ALTER TABLE lock_reservation_slots ADD COLUMN alt_btc_txids TEXT NOT NULL DEFAULT '[]';"),
+    // 18: how many of a lock's transactions must confirm before it unlocks
+    Migration::new("ALTER TABLE slot_locks ADD COLUMN required_confirmed_txids INTEGER NOT NULL DEFAULT 1;
+    ALTER TABLE lock_reservation_slots
+        ADD COLUMN required_confirmed_txids INTEGER NOT NULL DEFAULT 1;"),
+    // 19: Bitcoin block headers synced for SPV verification, consensus-encoded
+    Migration::new("CREATE TABLE IF NOT EXISTS block_headers (
+        height INTEGER PRIMARY KEY,
+        block_hash TEXT NOT NULL UNIQUE,
+        header BLOB NOT NULL
+    );"),
+    // 20: replication. lock_changes holds the sequence number of the latest change of each lock,
+    // maintained by triggers and tailed by standbys; existing locks count as changed. The single
+    // replication_state row holds the sentinel's role, fencing epoch and, on standbys, the last
+    // change applied from the primary.
+    Migration::new("CREATE TABLE IF NOT EXISTS lock_changes (
+        lock_id INTEGER PRIMARY KEY,
+        seq INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS idx_lock_changes_seq ON lock_changes (seq);
+    INSERT OR REPLACE INTO lock_changes (lock_id, seq) SELECT id, id FROM slot_locks;
+    CREATE TRIGGER IF NOT EXISTS log_slot_lock_insert
+     AFTER INSERT ON slot_locks
+     FOR EACH ROW
+     BEGIN
+         INSERT OR REPLACE INTO lock_changes (lock_id, seq)
+         VALUES (NEW.id, (SELECT COALESCE(MAX(seq), 0) + 1 FROM lock_changes));
+     END;
+    CREATE TRIGGER IF NOT EXISTS log_slot_lock_update
+     AFTER UPDATE ON slot_locks
+     FOR EACH ROW
+     BEGIN
+         INSERT OR REPLACE INTO lock_changes (lock_id, seq)
+         VALUES (NEW.id, (SELECT COALESCE(MAX(seq), 0) + 1 FROM lock_changes));
+     END;
+    CREATE TRIGGER IF NOT EXISTS log_slot_lock_delete
+     AFTER DELETE ON slot_locks
+     FOR EACH ROW
+     BEGIN
+         INSERT OR REPLACE INTO lock_changes (lock_id, seq)
+         VALUES (OLD.id, (SELECT COALESCE(MAX(seq), 0) + 1 FROM lock_changes));
+     END;
+    CREATE TABLE IF NOT EXISTS replication_state (
+        id INTEGER PRIMARY KEY CHECK (id = 1),
+        role TEXT NOT NULL,
+        epoch INTEGER NOT NULL,
+        applied_seq INTEGER NOT NULL DEFAULT 0
+    );"),
+    // 21: lock statistics maintained by triggers, so they can be read without scanning the lock
+    // table. lock_stats counts the active, unlocked and reverted locks of each contract, the
+    // total duration in sova blocks of its ended locks and the latest sova block it saw;
+    // lock_reverts counts reverts per sova block. Existing locks are counted when migrating.
+    Migration::new("CREATE TABLE IF NOT EXISTS lock_stats (
+        namespace TEXT NOT NULL,
+        contract_address TEXT NOT NULL,
+        active INTEGER NOT NULL DEFAULT 0,
+        unlocked INTEGER NOT NULL DEFAULT 0,
+        reverted INTEGER NOT NULL DEFAULT 0,
+        duration_blocks INTEGER NOT NULL DEFAULT 0,
+        latest_block INTEGER NOT NULL DEFAULT 0,
+        PRIMARY KEY (namespace, contract_address)
+    );
+    CREATE TABLE IF NOT EXISTS lock_reverts (
+        namespace TEXT NOT NULL,
+        contract_address TEXT NOT NULL,
+        block INTEGER NOT NULL,
+        reverts INTEGER NOT NULL DEFAULT 0,
+        PRIMARY KEY (namespace, block, contract_address)
+    );
+    INSERT OR REPLACE INTO lock_stats
+        (namespace, contract_address, active, unlocked, reverted, duration_blocks, latest_block)
+    SELECT namespace, contract_address,
+        SUM(end_block IS NULL),
+        SUM(end_block IS NOT NULL AND COALESCE(unlock_reason, '') NOT IN ('revert-threshold', 'double-spent', 'manual-revert')),
+        SUM(end_block IS NOT NULL AND COALESCE(unlock_reason, '') IN ('revert-threshold', 'double-spent', 'manual-revert')),
+        COALESCE(SUM(end_block - start_block), 0),
+        MAX(MAX(start_block), COALESCE(MAX(end_block), 0))
+    FROM slot_locks GROUP BY namespace, contract_address;
+    INSERT OR REPLACE INTO lock_reverts (namespace, contract_address, block, reverts)
+    SELECT namespace, contract_address, end_block, COUNT(*) FROM slot_locks
+    WHERE end_block IS NOT NULL AND COALESCE(unlock_reason, '') IN ('revert-threshold', 'double-spent', 'manual-revert')
+    GROUP BY namespace, contract_address, end_block;
+    CREATE TRIGGER IF NOT EXISTS count_slot_lock_insert
+     AFTER INSERT ON slot_locks
+     FOR EACH ROW
+     BEGIN
+         INSERT INTO lock_stats
+             (namespace, contract_address, active, unlocked, reverted, duration_blocks, latest_block)
+         VALUES (
+             NEW.namespace, NEW.contract_address,
+             (NEW.end_block IS NULL),
+             (NEW.end_block IS NOT NULL AND COALESCE(NEW.unlock_reason, '') NOT IN ('revert-threshold', 'double-spent', 'manual-revert')),
+             (NEW.end_block IS NOT NULL AND COALESCE(NEW.unlock_reason, '') IN ('revert-threshold', 'double-spent', 'manual-revert')),
+             COALESCE(NEW.end_block - NEW.start_block, 0),
+             MAX(NEW.start_block, COALESCE(NEW.end_block, 0))
+         )
+         ON CONFLICT (namespace, contract_address) DO UPDATE SET
+             active = active + excluded.active,
+             unlocked = unlocked + excluded.unlocked,
+             reverted = reverted + excluded.reverted,
+             duration_blocks = duration_blocks + excluded.duration_blocks,
+             latest_block = MAX(latest_block, excluded.latest_block);
+         INSERT INTO lock_reverts (namespace, contract_address, block, reverts)
+         SELECT NEW.namespace, NEW.contract_address, NEW.end_block, 1
+         WHERE NEW.end_block IS NOT NULL AND COALESCE(NEW.unlock_reason, '') IN ('revert-threshold', 'double-spent', 'manual-revert')
+         ON CONFLICT (namespace, block, contract_address) DO UPDATE SET reverts = reverts + 1;
+     END;
+    CREATE TRIGGER IF NOT EXISTS count_slot_lock_update
+     AFTER UPDATE OF namespace, contract_address, start_block, end_block, unlock_reason
+     ON slot_locks
+     FOR EACH ROW
+     BEGIN
+         INSERT INTO lock_stats
+             (namespace, contract_address, active, unlocked, reverted, duration_blocks, latest_block)
+         VALUES (
+             OLD.namespace, OLD.contract_address,
+             -(OLD.end_block IS NULL),
+             -(OLD.end_block IS NOT NULL AND COALESCE(OLD.unlock_reason, '') NOT IN ('revert-threshold', 'double-spent', 'manual-revert')),
+             -(OLD.end_block IS NOT NULL AND COALESCE(OLD.unlock_reason, '') IN ('revert-threshold', 'double-spent', 'manual-revert')),
+             -COALESCE(OLD.end_block - OLD.start_block, 0),
+             0
+         )
+         ON CONFLICT (namespace, contract_address) DO UPDATE SET
+             active = active + excluded.active,
+             unlocked = unlocked + excluded.unlocked,
+             reverted = reverted + excluded.reverted,
+             duration_blocks = duration_blocks + excluded.duration_blocks,
+             latest_block = MAX(latest_block, excluded.latest_block);
+         UPDATE lock_reverts SET reverts = reverts - 1
+         WHERE namespace = OLD.namespace AND contract_address = OLD.contract_address
+         AND block = OLD.end_block AND COALESCE(OLD.unlock_reason, '') IN ('revert-threshold', 'double-spent', 'manual-revert');
+         DELETE FROM lock_reverts
+         WHERE namespace = OLD.namespace AND contract_address = OLD.contract_address
+         AND block = OLD.end_block AND reverts <= 0;
+         INSERT INTO lock_stats
+             (namespace, contract_address, active, unlocked, reverted, duration_blocks, latest_block)
+         VALUES (
+             NEW.namespace, NEW.contract_address,
+             (NEW.end_block IS NULL),
+             (NEW.end_block IS NOT NULL AND COALESCE(NEW.unlock_reason, '') NOT IN ('revert-threshold', 'double-spent', 'manual-revert')),
+             (NEW.end_block IS NOT NULL AND COALESCE(NEW.unlock_reason, '') IN ('revert-threshold', 'double-spent', 'manual-revert')),
+             COALESCE(NEW.end_block - NEW.start_block, 0),
+             MAX(NEW.start_block, COALESCE(NEW.end_block, 0))
+         )
+         ON CONFLICT (namespace, contract_address) DO UPDATE SET
+             active = active + excluded.active,
+             unlocked = unlocked + excluded.unlocked,
+             reverted = reverted + excluded.reverted,
+             duration_blocks = duration_blocks + excluded.duration_blocks,
+             latest_block = MAX(latest_block, excluded.latest_block);
+         INSERT INTO lock_reverts (namespace, contract_address, block, reverts)
+         SELECT NEW.namespace, NEW.contract_address, NEW.end_block, 1
+         WHERE NEW.end_block IS NOT NULL AND COALESCE(NEW.unlock_reason, '') IN ('revert-threshold', 'double-spent', 'manual-revert')
+         ON CONFLICT (namespace, block, contract_address) DO UPDATE SET reverts = reverts + 1;
+     END;
+    CREATE TRIGGER IF NOT EXISTS count_slot_lock_delete
+     AFTER DELETE ON slot_locks
+     FOR EACH ROW
+     BEGIN
+         INSERT INTO lock_stats
+             (namespace, contract_address, active, unlocked, reverted, duration_blocks, latest_block)
+         VALUES (
+             OLD.namespace, OLD.contract_address,
+             -(OLD.end_block IS NULL),
+             -(OLD.end_block IS NOT NULL AND COALESCE(OLD.unlock_reason, '') NOT IN ('revert-threshold', 'double-spent', 'manual-revert')),
+             -(OLD.end_block IS NOT NULL AND COALESCE(OLD.unlock_reason, '') IN ('revert-threshold', 'double-spent', 'manual-revert')),
+             -COALESCE(OLD.end_block - OLD.start_block, 0),
+             0
+         )
+         ON CONFLICT (namespace, contract_address) DO UPDATE SET
+             active = active + excluded.active,
+             unlocked = unlocked + excluded.unlocked,
+             reverted = reverted + excluded.reverted,
+             duration_blocks = duration_blocks + excluded.duration_blocks,
+             latest_block = MAX(latest_block, excluded.latest_block);
+         UPDATE lock_reverts SET reverts = reverts - 1
+         WHERE namespace = OLD.namespace AND contract_address = OLD.contract_address
+         AND block = OLD.end_block AND COALESCE(OLD.unlock_reason, '') IN ('revert-threshold', 'double-spent', 'manual-revert');
+         DELETE FROM lock_reverts
+         WHERE namespace = OLD.namespace AND contract_address = OLD.contract_address
+         AND block = OLD.end_block AND reverts <= 0;
+     END;"),
+    // 22: index for paging through the locks of a contract in the order they were made
+    Migration::new("CREATE INDEX IF NOT EXISTS idx_slot_locks_contract ON slot_locks (namespace, contract_address, id);"),
+    // 23: value history of each slot, a row per lock with the values it recorded and how it
+    // ended, maintained by triggers and kept after the retention policy purges the lock
+    Migration::new("CREATE TABLE IF NOT EXISTS slot_value_history (
+        lock_id INTEGER PRIMARY KEY,
+        namespace TEXT NOT NULL,
+        contract_address TEXT NOT NULL,
+        slot_index BLOB NOT NULL,
+        start_block INTEGER NOT NULL,
+        end_block INTEGER,
+        unlock_reason TEXT,
+        btc_txid TEXT NOT NULL,
+        revert_value BLOB NOT NULL,
+        current_value BLOB NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS idx_slot_value_history_slot
+        ON slot_value_history (namespace, contract_address, slot_index, start_block);
+    INSERT OR REPLACE INTO slot_value_history (lock_id, namespace, contract_address, slot_index,
+        start_block, end_block, unlock_reason, btc_txid, revert_value, current_value)
+    SELECT id, namespace, contract_address, slot_index, start_block, end_block, unlock_reason,
+        btc_txid, revert_value, current_value
+    FROM slot_locks;
+    CREATE TRIGGER IF NOT EXISTS record_slot_value_insert
+     AFTER INSERT ON slot_locks
+     FOR EACH ROW
+     BEGIN
+         INSERT OR REPLACE INTO slot_value_history (lock_id, namespace, contract_address,
+             slot_index, start_block, end_block, unlock_reason, btc_txid, revert_value,
+             current_value)
+         VALUES (NEW.id, NEW.namespace, NEW.contract_address, NEW.slot_index, NEW.start_block,
+             NEW.end_block, NEW.unlock_reason, NEW.btc_txid, NEW.revert_value, NEW.current_value);
+     END;
+    CREATE TRIGGER IF NOT EXISTS record_slot_value_update
+     AFTER UPDATE OF start_block, end_block, unlock_reason, btc_txid, revert_value, current_value
+     ON slot_locks
+     FOR EACH ROW
+     BEGIN
+         UPDATE slot_value_history SET start_block = NEW.start_block, end_block = NEW.end_block,
+             unlock_reason = NEW.unlock_reason, btc_txid = NEW.btc_txid,
+             revert_value = NEW.revert_value, current_value = NEW.current_value
+         WHERE lock_id = NEW.id;
+     END;"),
+    // 24: authenticated caller each lock was made by, unknown for the locks made before
+    Migration::new("ALTER TABLE slot_locks ADD COLUMN locked_by TEXT;
+    ALTER TABLE lock_reservation_slots ADD COLUMN locked_by TEXT;"),
+    // 25: index for counting the active locks of each caller against its quota
+    Migration::new("CREATE INDEX IF NOT EXISTS idx_slot_locks_locked_by ON slot_locks (locked_by)
+     WHERE end_block IS NULL AND locked_by IS NOT NULL;"),
+    // 26: instance that wrote each lock_changes and lock_reverts row. The single
+    // sentinel_instance row holds the instance id of the sentinel serving the database, set at
+    // startup; existing rows keep a NULL instance.
+    Migration::new("CREATE TABLE IF NOT EXISTS sentinel_instance (
+        id INTEGER PRIMARY KEY CHECK (id = 1),
+        instance_id TEXT NOT NULL
+    );
+    ALTER TABLE lock_changes ADD COLUMN instance_id TEXT;
+    ALTER TABLE lock_reverts ADD COLUMN instance_id TEXT;
+    CREATE TRIGGER IF NOT EXISTS tag_lock_change
+     AFTER INSERT ON lock_changes
+     FOR EACH ROW
+     BEGIN
+         UPDATE lock_changes SET instance_id = (SELECT instance_id FROM sentinel_instance)
+         WHERE lock_id = NEW.lock_id;
+     END;
+    CREATE TRIGGER IF NOT EXISTS tag_lock_revert_insert
+     AFTER INSERT ON lock_reverts
+     FOR EACH ROW
+     BEGIN
+         UPDATE lock_reverts SET instance_id = (SELECT instance_id FROM sentinel_instance)
+         WHERE rowid = NEW.rowid;
+     END;
+    CREATE TRIGGER IF NOT EXISTS tag_lock_revert_update
+     AFTER UPDATE OF reverts ON lock_reverts
+     FOR EACH ROW
+     BEGIN
+         UPDATE lock_reverts SET instance_id = (SELECT instance_id FROM sentinel_instance)
+         WHERE rowid = NEW.rowid;
+     END;"),
+    // 27: BIP157 filter header of each block whose compact filter was checked, NULL for blocks
+    // not yet scanned
+    Migration::new("ALTER TABLE block_headers ADD COLUMN filter_header BLOB;"),
+];
+
+/// Schema version the server expects after all migrations have run
+pub const SCHEMA_VERSION: u32 = MIGRATIONS.len() as u32;
+
+pub fn schema_version(conn: &Connection) -> Result<u32> {
+    Ok(conn.pragma_query_value(None, "user_version", |row| row.get(0))?)
+}
 
 pub fn run_migrations(conn: &Connection) -> Result<()> {
-    // Create tables if they don't exist
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS slot_locks (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            start_block INTEGER NOT NULL,
-            end_block INTEGER,
-            btc_block INTEGER NOT NULL,
-            contract_address TEXT NOT NULL,
-            slot_index BLOB NOT NULL,
-            slot_index_int INTEGER,
-            btc_txid TEXT NOT NULL,
-            revert_value BLOB NOT NULL,
-            current_value BLOB NOT NULL,
-            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
-            updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
-            -- Removed for development
-            -- UNIQUE(contract_address, slot_index, end_block)
-        )",
-        [],
-    )?;
-
-    // Create triggers for automatic timestamp updates
-    conn.execute(
-        "CREATE TRIGGER IF NOT EXISTS update_slot_locks_timestamp 
-         AFTER UPDATE ON slot_locks
-         FOR EACH ROW
-         BEGIN
-             UPDATE slot_locks SET updated_at = CURRENT_TIMESTAMP
-             WHERE rowid = NEW.rowid;
-         END;",
-        [],
-    )?;
+    let current_version = schema_version(conn)?;
+    if current_version > SCHEMA_VERSION {
+        anyhow::bail!(
+            "Database schema version {} is newer than the supported version {}",
+            current_version,
+            SCHEMA_VERSION
+        );
+    }
+
+    for (idx, migration) in MIGRATIONS.iter().enumerate().skip(current_version as usize) {
+        let version = idx as u32 + 1;
+        let transaction = conn.unchecked_transaction()?;
+        if let Some(precheck) = migration.precheck {
+            precheck(&transaction)?;
+        }
+        transaction.execute_batch(migration.sql)?;
+        transaction.pragma_update(None, "user_version", version)?;
+        transaction.commit()?;
+        tracing::debug!("Applied database migration {}", version);
+    }
+
+    Ok(())
+}
 
+/// Fails if two active locks of a contract hold different forms of the same slot index, which
+/// migration 10 would turn into two active locks on one slot
+fn check_canonical_slots(conn: &Connection) -> Result<()> {
+    let collision = conn
+        .query_row(
+            "SELECT contract_address, hex(unhex(substr(hex(zeroblob(32)) || hex(slot_index), -64))),
+                 COUNT(*)
+             FROM slot_locks WHERE end_block IS NULL
+             GROUP BY contract_address, unhex(substr(hex(zeroblob(32)) || hex(slot_index), -64))
+             HAVING COUNT(*) > 1
+             LIMIT 1",
+            [],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, u64>(2)?,
+                ))
+            },
+        )
+        .optional()?;
+    if let Some((contract_address, slot_index, locks)) = collision {
+        anyhow::bail!(
+            "Can't canonicalize slot indexes: contract {} has {} active locks on slot 0x{}. \
+             Unlock all but one of them before upgrading.",
+            contract_address,
+            locks,
+            slot_index.to_lowercase()
+        );
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrations_are_idempotent() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+        assert_eq!(schema_version(&conn)?, 0);
+
+        run_migrations(&conn)?;
+        assert_eq!(schema_version(&conn)?, SCHEMA_VERSION);
+
+        run_migrations(&conn)?;
+        assert_eq!(schema_version(&conn)?, SCHEMA_VERSION);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_canonicalizes_txids() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+        for migration in &MIGRATIONS[..7] {
+            conn.execute_batch(migration.sql)?;
+        }
+        conn.pragma_update(None, "user_version", 7)?;
+        conn.execute_batch(
+            "INSERT INTO slot_locks (start_block, btc_block, contract_address, slot_index, btc_txid,
+                 revert_value, current_value)
+             VALUES (1, 1, '0x123', x'01', ' 0xABCD ', x'', x'');
+             INSERT INTO lock_transactions (btc_txid, raw_tx) VALUES ('0XABCD', x'01');
+             INSERT INTO lock_transactions (btc_txid, raw_tx) VALUES ('abcd', x'01');",
+        )?;
+
+        run_migrations(&conn)?;
+        let txid: String =
+            conn.query_row("SELECT btc_txid FROM slot_locks", [], |row| row.get(0))?;
+        assert_eq!(txid, "abcd");
+        let txids: u32 = conn.query_row(
+            "SELECT COUNT(*) FROM lock_transactions WHERE btc_txid = 'abcd'",
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!(txids, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_pads_slot_indexes() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+        for migration in &MIGRATIONS[..9] {
+            conn.execute_batch(migration.sql)?;
+        }
+        conn.pragma_update(None, "user_version", 9)?;
+        conn.execute_batch(
+            "INSERT INTO slot_locks (start_block, btc_block, contract_address, slot_index,
+                 slot_index_int, btc_txid, revert_value, current_value)
+             VALUES (1, 1, '0x123', x'0001', 1, 'abcd', x'', x'');",
+        )?;
+
+        run_migrations(&conn)?;
+        let slot_index: Vec<u8> =
+            conn.query_row("SELECT slot_index FROM slot_locks", [], |row| row.get(0))?;
+        let mut expected = vec![0; 32];
+        expected[31] = 1;
+        assert_eq!(slot_index, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_colliding_slot_indexes() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+        for migration in &MIGRATIONS[..9] {
+            conn.execute_batch(migration.sql)?;
+        }
+        conn.pragma_update(None, "user_version", 9)?;
+        conn.execute_batch(
+            "INSERT INTO slot_locks (start_block, btc_block, contract_address, slot_index,
+                 btc_txid, revert_value, current_value)
+             VALUES (1, 1, '0x123', x'01', 'abcd', x'', x''),
+                 (2, 2, '0x123', x'0001', 'abcd', x'', x'');",
+        )?;
+
+        let err = run_migrations(&conn).unwrap_err().to_string();
+        assert!(err.contains("2 active locks"), "{}", err);
+        assert_eq!(schema_version(&conn)?, 9);
+        let padded: u32 = conn.query_row(
+            "SELECT COUNT(*) FROM slot_locks WHERE length(slot_index) = 32",
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!(padded, 0);
+
+        // Once one of them is unlocked, the slot indexes are padded
+        conn.execute(
+            "UPDATE slot_locks SET end_block = 3 WHERE start_block = 1",
+            [],
+        )?;
+        run_migrations(&conn)?;
+        assert_eq!(schema_version(&conn)?, SCHEMA_VERSION);
+        Ok(())
+    }
+
+    #[test]
+    fn test_moves_rows_to_default_namespace() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+        for migration in &MIGRATIONS[..10] {
+            conn.execute_batch(migration.sql)?;
+        }
+        conn.pragma_update(None, "user_version", 10)?;
+        conn.execute_batch(
+            "INSERT INTO slot_locks (start_block, btc_block, contract_address, slot_index,
+                 btc_txid, revert_value, current_value)
+             VALUES (1, 1, '0x123', x'01', 'abcd', x'', x'');
+             INSERT INTO lock_conflicts (contract_address, conflicts) VALUES ('0x123', 2);",
+        )?;
+
+        run_migrations(&conn)?;
+        let namespace: String =
+            conn.query_row("SELECT namespace FROM slot_locks", [], |row| row.get(0))?;
+        assert_eq!(namespace, "");
+        let (namespace, conflicts): (String, i64) = conn.query_row(
+            "SELECT namespace, conflicts FROM lock_conflicts WHERE contract_address = '0x123'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        assert_eq!((namespace.as_str(), conflicts), ("", 2));
+        Ok(())
+    }
+
+    #[test]
+    fn test_counts_existing_locks() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+        for migration in &MIGRATIONS[..20] {
+            conn.execute_batch(migration.sql)?;
+        }
+        conn.pragma_update(None, "user_version", 20)?;
+        conn.execute_batch(
+            "INSERT INTO slot_locks (start_block, end_block, unlock_reason, btc_block,
+                 contract_address, slot_index, btc_txid, revert_value, current_value)
+             VALUES (1, NULL, NULL, 1, '0x123', x'01', 'abcd', x'', x''),
+                 (1, 5, NULL, 1, '0x123', x'02', 'abcd', x'', x''),
+                 (1, 9, 'double-spent', 1, '0x123', x'03', 'abcd', x'', x'');",
+        )?;
+
+        run_migrations(&conn)?;
+        let counts: (i64, i64, i64, i64) = conn.query_row(
+            "SELECT active, unlocked, reverted, duration_blocks FROM lock_stats",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )?;
+        assert_eq!(counts, (1, 1, 1, 12));
+        let reverts: (i64, i64) =
+            conn.query_row("SELECT block, reverts FROM lock_reverts", [], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })?;
+        assert_eq!(reverts, (9, 1));
+        Ok(())
+    }
+
+    #[test]
+    fn test_records_existing_values() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+        for migration in &MIGRATIONS[..22] {
+            conn.execute_batch(migration.sql)?;
+        }
+        conn.pragma_update(None, "user_version", 22)?;
+        conn.execute_batch(
+            "INSERT INTO slot_locks (start_block, end_block, unlock_reason, btc_block,
+                 contract_address, slot_index, btc_txid, revert_value, current_value)
+             VALUES (1, 9, 'double-spent', 1, '0x123', x'01', 'abcd', x'04', x'07');",
+        )?;
+
+        run_migrations(&conn)?;
+        let values: (i64, String, Vec<u8>, Vec<u8>) = conn.query_row(
+            "SELECT end_block, unlock_reason, revert_value, current_value FROM slot_value_history",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )?;
+        assert_eq!(values, (9, "double-spent".to_string(), vec![4], vec![7]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_tags_audit_rows_with_instance() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+        run_migrations(&conn)?;
+        conn.execute_batch(
+            "INSERT INTO sentinel_instance (id, instance_id) VALUES (1, 'sentinel-1');
+             INSERT INTO slot_locks (start_block, end_block, unlock_reason, btc_block,
+                 contract_address, slot_index, btc_txid, revert_value, current_value)
+             VALUES (1, NULL, NULL, 1, '0x123', x'01', 'abcd', x'', x'');
+             UPDATE slot_locks SET end_block = 5, unlock_reason = 'revert-threshold';",
+        )?;
+
+        let change: Option<String> =
+            conn.query_row("SELECT instance_id FROM lock_changes", [], |row| row.get(0))?;
+        assert_eq!(change.as_deref(), Some("sentinel-1"));
+        let revert: Option<String> =
+            conn.query_row("SELECT instance_id FROM lock_reverts", [], |row| row.get(0))?;
+        assert_eq!(revert.as_deref(), Some("sentinel-1"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_newer_schema() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+        conn.pragma_update(None, "user_version", SCHEMA_VERSION + 1)?;
+        assert!(run_migrations(&conn).is_err());
+        Ok(())
+    }
+}
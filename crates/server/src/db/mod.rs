@@ -1,12 +1,62 @@
+mod encryption;
+mod headers;
+mod history;
 mod migrations; // Declare the migrations module
-
-use anyhow::Result;
-use rusqlite::{Connection, ToSql, Transaction};
-use std::sync::{Arc, Mutex};
+mod redb_store;
+mod replication;
+mod reservations;
+mod schema;
+mod stats;
+mod store;
+
+pub(crate) use encryption::open_connection;
+pub use encryption::{cipher_version, DatabaseKey};
+pub use history::SlotValueChange;
+pub use migrations::SCHEMA_VERSION;
+pub use redb_store::{RedbStore, REDB_SCHEMA_VERSION};
+pub use replication::{LockChange, ReplicatedLock, ReplicationRole, ReplicationState};
+pub use schema::{ColumnSchema, IndexSchema, TableSchema};
+pub use stats::LockStats;
+pub use store::{SlotStore, StorageBackend};
+
+use crate::chaos::FaultInjector;
+use anyhow::{Context, Result};
+use bitcoin::hashes::{sha256, Hash};
+use rusqlite::{
+    Connection, DatabaseName, ErrorCode, OpenFlags, OptionalExtension, ToSql, Transaction,
+};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+/// Most slots bound in one batch statement, keeping statements within SQLite's limits on
+/// expression depth and bound parameters
+const MAX_SLOTS_PER_STATEMENT: usize = 500;
+
+/// Returns whether an error means the database file can't be used through its connection any more:
+/// an I/O error, corruption, or a file that is no longer a database
+pub fn is_fatal_error(e: &anyhow::Error) -> bool {
+    e.chain().any(|cause| {
+        matches!(
+            cause.downcast_ref::<rusqlite::Error>(),
+            Some(rusqlite::Error::SqliteFailure(err, _))
+                if matches!(
+                    err.code,
+                    ErrorCode::SystemIoFailure
+                        | ErrorCode::DatabaseCorrupt
+                        | ErrorCode::NotADatabase
+                        | ErrorCode::CannotOpen
+                )
+        )
+    })
+}
 
 #[derive(Clone)]
 pub struct Database {
     connection: Arc<Mutex<Connection>>,
+    faults: Option<Arc<FaultInjector>>,
+    key: Option<DatabaseKey>,
 }
 
 impl Database {
@@ -14,32 +64,243 @@ impl Database {
         crate::db::migrations::run_migrations(&connection)?;
         Ok(Self {
             connection: Arc::new(Mutex::new(connection)),
+            faults: None,
+            key: None,
         })
     }
 
-    pub fn with_transaction<F, T>(&self, f: F) -> Result<T>
-    where
-        F: FnOnce(&Transaction) -> Result<T>,
-    {
+    /// Opens the database file at `path` and applies pending migrations. With a key the file is
+    /// encrypted with SQLCipher, and so are the backups and purge databases written from it.
+    pub fn open(path: &Path, flags: OpenFlags, key: Option<DatabaseKey>) -> Result<Self> {
+        let conn = open_connection(path, flags, key.as_ref())?;
+        Ok(Self::new(conn)?.with_key(key))
+    }
+
+    /// Records the key the connection was opened with
+    pub(crate) fn with_key(mut self, key: Option<DatabaseKey>) -> Self {
+        self.key = key;
+        self
+    }
+
+    /// Key the database file is encrypted with, if any
+    pub fn key(&self) -> Option<&DatabaseKey> {
+        self.key.as_ref()
+    }
+
+    /// Delays or fails operations at random, see [`FaultInjector`]. Only this handle and the
+    /// clones made from it afterwards are affected.
+    pub fn with_faults(mut self, faults: Arc<FaultInjector>) -> Self {
+        self.faults = Some(faults);
+        self
+    }
+
+    /// Locks the connection, after any injected fault. Injected failures look like a busy
+    /// database.
+    fn connection(&self) -> Result<MutexGuard<'_, Connection>> {
+        if let Some(faults) = &self.faults {
+            faults.inject_blocking().map_err(|e| {
+                rusqlite::Error::SqliteFailure(
+                    rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_BUSY),
+                    Some(e.to_string()),
+                )
+            })?;
+        }
+        self.connection
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Failed to acquire database lock"))
+    }
+
+    /// Returns the schema version recorded in the database
+    pub fn schema_version(&self) -> Result<u32> {
+        let conn = self.connection()?;
+        migrations::schema_version(&conn)
+    }
+
+    /// Records the instance id of the sentinel serving the database, written to every lock
+    /// change and revert count it makes from then on
+    pub fn set_instance_id(&self, instance_id: &str) -> Result<()> {
+        let conn = self.connection()?;
+        conn.execute(
+            "INSERT OR REPLACE INTO sentinel_instance (id, instance_id) VALUES (1, ?1)",
+            [instance_id],
+        )?;
+        Ok(())
+    }
+
+    /// Reads the schema from the database file, failing if the file can't be read
+    pub fn probe(&self) -> Result<()> {
+        let conn = self.connection()?;
+        conn.query_row("SELECT COUNT(*) FROM sqlite_master", [], |row| {
+            row.get::<_, i64>(0)
+        })?;
+        Ok(())
+    }
+
+    /// Runs `PRAGMA quick_check`, returning the problems it finds, none if the database is intact
+    pub fn quick_check(&self) -> Result<Vec<String>> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare("PRAGMA quick_check")?;
+        let problems = stmt
+            .query_map([], |row| row.get::<_, String>(0))
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>());
+        match problems {
+            Ok(problems) if problems == ["ok"] => Ok(Vec::new()),
+            Ok(problems) => Ok(problems),
+            // Damage bad enough that the check can't walk the file is reported as an error
+            Err(rusqlite::Error::SqliteFailure(err, message))
+                if err.code == ErrorCode::DatabaseCorrupt =>
+            {
+                Ok(vec![message.unwrap_or_else(|| err.to_string())])
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Makes the connection reject every write, or accept them again
+    pub fn set_query_only(&self, query_only: bool) -> Result<()> {
+        let conn = self.connection()?;
+        conn.pragma_update(None, "query_only", query_only)?;
+        Ok(())
+    }
+
+    /// Switches the database to incremental auto-vacuum if it isn't already, which rewrites the
+    /// whole file with `VACUUM`. Returns whether it was switched.
+    pub fn enable_incremental_vacuum(&self) -> Result<bool> {
+        let conn = self.connection()?;
+        // 2 is INCREMENTAL
+        let auto_vacuum: i64 = conn.pragma_query_value(None, "auto_vacuum", |row| row.get(0))?;
+        if auto_vacuum == 2 {
+            return Ok(false);
+        }
+        conn.pragma_update(None, "auto_vacuum", "INCREMENTAL")?;
+        conn.execute_batch("VACUUM")?;
+        Ok(true)
+    }
+
+    /// Returns up to `pages` free pages to the filesystem, all of them if `pages` is 0. Only
+    /// shrinks databases in incremental auto-vacuum mode. Returns the pages freed.
+    pub fn incremental_vacuum(&self, pages: u64) -> Result<u64> {
+        let conn = self.connection()?;
+        let free_pages = |conn: &Connection| -> Result<u64> {
+            Ok(conn.pragma_query_value(None, "freelist_count", |row| row.get(0))?)
+        };
+        let before = free_pages(&conn)?;
+        {
+            // Frees a page per step
+            let mut stmt = conn.prepare(&format!("PRAGMA incremental_vacuum({})", pages))?;
+            let mut rows = stmt.query([])?;
+            while rows.next()?.is_some() {}
+        }
+        Ok(before.saturating_sub(free_pages(&conn)?))
+    }
+
+    /// Checkpoints the write-ahead log into the database file and truncates it. Returns the
+    /// pages checkpointed, `None` if the database isn't in WAL mode.
+    pub fn checkpoint(&self) -> Result<Option<u64>> {
+        let conn = self.connection()?;
+        let (busy, log_pages, checkpointed): (i64, i64, i64) =
+            conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })?;
+        if busy != 0 {
+            anyhow::bail!("WAL checkpoint was blocked by another connection");
+        }
+        Ok((log_pages >= 0).then_some(checkpointed as u64))
+    }
+
+    /// Writes a consistent snapshot of the database to `path` with SQLite's online backup API.
+    /// Writes wait until the snapshot is complete. The snapshot is written next to `path` and
+    /// renamed into place, so `path` never holds a partial backup.
+    pub fn backup_to(&self, path: &Path) -> Result<()> {
+        let mut partial = path.as_os_str().to_owned();
+        partial.push(".partial");
+        let partial = PathBuf::from(partial);
+        {
+            let conn = self.connection()?;
+            match &self.key {
+                // A backup is only readable with the key it was written with, so key it like the
+                // database rather than writing it in plaintext
+                Some(key) => {
+                    let mut backup = open_connection(
+                        &partial,
+                        OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE,
+                        Some(key),
+                    )?;
+                    let result = rusqlite::backup::Backup::new(&conn, &mut backup)?
+                        .run_to_completion(100, Duration::from_millis(250), None);
+                    result
+                }
+                None => conn.backup(DatabaseName::Main, &partial, None),
+            }
+            .with_context(|| format!("Failed to back up database to {}", partial.display()))?;
+        }
+        std::fs::rename(&partial, path)
+            .with_context(|| format!("Failed to move backup to {}", path.display()))?;
+        Ok(())
+    }
+
+    /// Makes this database and all its clones use the connection of `other`, e.g. after reopening
+    /// the database file
+    pub fn replace_connection(&self, other: Database) -> Result<()> {
+        if Arc::ptr_eq(&self.connection, &other.connection) {
+            return Ok(());
+        }
         let mut conn = self
             .connection
             .lock()
             .map_err(|_| anyhow::anyhow!("Failed to acquire database lock"))?;
+        let mut other = other
+            .connection
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Failed to acquire database lock"))?;
+        std::mem::swap(&mut *conn, &mut *other);
+        Ok(())
+    }
+
+    /// Describes the tables, columns and indexes of the database schema
+    pub fn describe_schema(&self) -> Result<Vec<TableSchema>> {
+        let conn = self.connection()?;
+        schema::describe_tables(&conn)
+    }
+
+    pub fn with_transaction<F, T>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&Transaction) -> Result<T>,
+    {
+        // Spans the wait for the connection too, which is where contention shows up
+        let _span = tracing::info_span!("db_transaction", db.system = "sqlite").entered();
+        let mut conn = self.connection()?;
         let transaction = conn.transaction()?;
         let result = f(&transaction)?;
         transaction.commit()?;
         Ok(result)
     }
 
-    pub fn is_slot_locked(&self, contract_address: &str, slot_index: &[u8]) -> Result<bool> {
-        let conn = self
-            .connection
-            .lock()
-            .map_err(|_| anyhow::anyhow!("Failed to acquire database lock"))?;
+    /// Runs `f` in a transaction that is rolled back afterwards, leaving the database unchanged
+    pub fn with_rolled_back_transaction<F, T>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&Transaction) -> Result<T>,
+    {
+        let _span = tracing::info_span!("db_transaction", db.system = "sqlite", rolled_back = true)
+            .entered();
+        let mut conn = self.connection()?;
+        let transaction = conn.transaction()?;
+        let result = f(&transaction)?;
+        transaction.rollback()?;
+        Ok(result)
+    }
+
+    pub fn is_slot_locked(
+        &self,
+        namespace: &str,
+        contract_address: &str,
+        slot_index: &[u8],
+    ) -> Result<bool> {
+        let conn = self.connection()?;
         let sql = is_slot_locked_query();
         let result = conn.query_row(
             &sql,
-            rusqlite::params![contract_address, slot_index],
+            rusqlite::params![contract_address, slot_index, namespace],
             |_| Ok(true),
         );
 
@@ -53,13 +314,14 @@ impl Database {
     pub fn is_slot_locked_with_transaction(
         &self,
         transaction: &Transaction,
+        namespace: &str,
         contract_address: &str,
         slot_index: &[u8],
     ) -> Result<bool> {
         let sql = is_slot_locked_query();
         let result = transaction.query_row(
             &sql,
-            rusqlite::params![contract_address, slot_index],
+            rusqlite::params![contract_address, slot_index, namespace],
             |_| Ok(true),
         );
 
@@ -73,18 +335,24 @@ impl Database {
     pub fn insert_slot_lock(&self, transaction: &Transaction, slot: &SlotInsertData) -> Result<()> {
         transaction.execute(
             "INSERT INTO slot_locks (
-                start_block, btc_block, contract_address, slot_index, 
-                slot_index_int, btc_txid, revert_value, current_value
-            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                start_block, btc_block, contract_address, slot_index,
+                btc_txid, revert_value, current_value, namespace, metadata, labels, alt_btc_txids,
+                required_confirmed_txids, locked_by
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
             rusqlite::params![
                 slot.start_block,
                 slot.btc_block,
                 slot.contract_address,
                 slot.slot_index,
-                slot.slot_index_int,
                 slot.btc_txid,
                 slot.revert_value,
                 slot.current_value,
+                slot.namespace,
+                slot.metadata,
+                serde_json::to_string(&slot.labels)?,
+                serde_json::to_string(&slot.alt_btc_txids)?,
+                slot.required_confirmed_txids,
+                slot.locked_by,
             ],
         )?;
 
@@ -94,6 +362,7 @@ impl Database {
     pub fn get_slot_with_transaction(
         &self,
         transaction: &Transaction,
+        namespace: &str,
         contract_address: &str,
         slot_index: &[u8],
         current_block: u64,
@@ -101,19 +370,13 @@ impl Database {
         let sql = get_slot_query();
         let result = transaction.query_row(
             &sql,
-            rusqlite::params![contract_address, slot_index, current_block as i64],
-            |row| {
-                Ok(LockedSlot {
-                    btc_txid: row.get(0)?,
-                    btc_block: row.get(1)?,
-                    contract_address: row.get(2)?,
-                    slot_index: row.get(3)?,
-                    revert_value: row.get(4)?,
-                    current_value: row.get(5)?,
-                    start_block: row.get(6)?,
-                    end_block: row.get(7)?,
-                })
-            },
+            rusqlite::params![
+                contract_address,
+                slot_index,
+                current_block as i64,
+                namespace
+            ],
+            locked_slot_from_row,
         );
 
         match result {
@@ -125,30 +388,40 @@ impl Database {
 
     pub fn get_slot(
         &self,
+        namespace: &str,
         contract_address: &str,
         slot_index: &[u8],
         current_block: u64,
     ) -> Result<Option<LockedSlot>> {
-        let mut conn = self
-            .connection
-            .lock()
-            .map_err(|_| anyhow::anyhow!("Failed to acquire database lock"))?;
+        let mut conn = self.connection()?;
         let transaction = conn.transaction()?;
-        self.get_slot_with_transaction(&transaction, contract_address, slot_index, current_block)
+        self.get_slot_with_transaction(
+            &transaction,
+            namespace,
+            contract_address,
+            slot_index,
+            current_block,
+        )
     }
 
     pub fn unlock_slot(
         &self,
+        namespace: &str,
         contract_address: &str,
         slot_index: &[u8],
         end_block: u64,
+        reason: UnlockReason,
     ) -> Result<()> {
-        let mut conn = self
-            .connection
-            .lock()
-            .map_err(|_| anyhow::anyhow!("Failed to acquire database lock"))?;
+        let mut conn = self.connection()?;
         let transaction = conn.transaction()?;
-        self.unlock_slot_with_transaction(&transaction, contract_address, slot_index, end_block)?;
+        self.unlock_slot_with_transaction(
+            &transaction,
+            namespace,
+            contract_address,
+            slot_index,
+            end_block,
+            reason,
+        )?;
         transaction.commit()?;
         Ok(())
     }
@@ -156,19 +429,652 @@ impl Database {
     pub fn unlock_slot_with_transaction(
         &self,
         transaction: &Transaction,
+        namespace: &str,
         contract_address: &str,
         slot_index: &[u8],
         end_block: u64,
+        reason: UnlockReason,
     ) -> Result<()> {
         let sql = unlock_slot_query();
         transaction.execute(
             &sql,
-            rusqlite::params![end_block, contract_address, slot_index],
+            rusqlite::params![end_block, contract_address, slot_index, reason, namespace],
         )?;
 
         Ok(())
     }
 
+    /// Records the reason of a lock unlocked before reasons were recorded, unless one was recorded
+    /// in the meantime. Returns the reason now stored for the unlock.
+    pub fn backfill_unlock_reason(
+        &self,
+        namespace: &str,
+        contract_address: &str,
+        slot_index: &[u8],
+        end_block: u64,
+        reason: UnlockReason,
+    ) -> Result<UnlockReason> {
+        self.with_transaction(|transaction| {
+            transaction.execute(
+                "UPDATE slot_locks SET unlock_reason = ?4 
+                 WHERE contract_address = ?1 AND slot_index = ?2 AND end_block = ?3 
+                 AND namespace = ?5 AND unlock_reason IS NULL",
+                rusqlite::params![contract_address, slot_index, end_block, reason, namespace],
+            )?;
+            let stored = transaction.query_row(
+                "SELECT unlock_reason FROM slot_locks 
+                 WHERE contract_address = ?1 AND slot_index = ?2 AND end_block = ?3 
+                 AND namespace = ?4 AND unlock_reason IS NOT NULL 
+                 LIMIT 1",
+                rusqlite::params![contract_address, slot_index, end_block, namespace],
+                |row| row.get(0),
+            )?;
+            Ok(stored)
+        })
+    }
+
+    /// Returns locks whose revert or current value matches `query`, if any, and that carry all
+    /// of `labels`, most recently locked first
+    pub fn search_locks(
+        &self,
+        query: Option<&ValueQuery>,
+        labels: &BTreeMap<String, String>,
+        namespace: Option<&str>,
+        contract_address: Option<&str>,
+        include_unlocked: bool,
+        limit: usize,
+    ) -> Result<Vec<LockedSlot>> {
+        let conn = self.connection()?;
+        let mut params: Vec<&dyn ToSql> = vec![&contract_address, &include_unlocked, &namespace];
+        // Byte patterns are matched by SQLite; hashes have to be computed for every candidate
+        let mut filters = String::new();
+        if let Some(ValueQuery::Contains(pattern)) = query {
+            params.push(pattern);
+            filters.push_str("AND (instr(revert_value, ?4) > 0 OR instr(current_value, ?4) > 0) ");
+        }
+        for (key, value) in labels {
+            params.push(key);
+            params.push(value);
+            filters.push_str(&format!(
+                "AND EXISTS (SELECT 1 FROM json_each(labels) WHERE key = ?{} AND value = ?{}) ",
+                params.len() - 1,
+                params.len()
+            ));
+        }
+        let sql = format!(
+            "SELECT btc_txid, btc_block, contract_address, slot_index, revert_value, current_value, start_block, end_block, unlock_reason, namespace, metadata, labels, lock_group, alt_btc_txids, required_confirmed_txids, CAST(strftime('%s', created_at) AS INTEGER) AS locked_at, locked_by 
+             FROM slot_locks 
+             WHERE (?1 IS NULL OR contract_address = ?1) 
+             AND (?2 OR end_block IS NULL) 
+             AND (?3 IS NULL OR namespace = ?3) 
+             AND archived_at IS NULL 
+             {}
+             ORDER BY start_block DESC, id DESC",
+            filters
+        );
+        let mut stmt = conn.prepare(&sql)?;
+        let mut rows = stmt.query(params.as_slice())?;
+
+        let mut locks = Vec::new();
+        while locks.len() < limit {
+            let Some(row) = rows.next()? else {
+                break;
+            };
+            let lock = locked_slot_from_row(row)?;
+            if query.is_none_or(|query| {
+                query.matches(&lock.revert_value) || query.matches(&lock.current_value)
+            }) {
+                locks.push(lock);
+            }
+        }
+        Ok(locks)
+    }
+
+    /// Returns the lock events of sova blocks `start_block` through `end_block` in canonical
+    /// order, starting after `after` and returning at most `limit` events
+    pub fn lock_events(
+        &self,
+        namespace: Option<&str>,
+        start_block: u64,
+        end_block: u64,
+        after: Option<LockEventCursor>,
+        limit: usize,
+    ) -> Result<Vec<LockEvent>> {
+        let conn = self.connection()?;
+        // Within a block, unlocks of locks made in earlier blocks come first, so a slot unlocked
+        // and locked again in the same block is exported in that order
+        let mut stmt = conn.prepare(
+            "SELECT block, rank, id, btc_txid, btc_block, contract_address, slot_index, revert_value, current_value, start_block, end_block, unlock_reason, namespace, metadata, labels, lock_group, alt_btc_txids, required_confirmed_txids, CAST(strftime('%s', created_at) AS INTEGER) AS locked_at, locked_by 
+             FROM (
+                 SELECT start_block AS block, 1 AS rank, * FROM slot_locks 
+                 WHERE start_block BETWEEN ?1 AND ?2 
+                 UNION ALL 
+                 SELECT end_block AS block, CASE WHEN start_block < end_block THEN 0 ELSE 2 END AS rank, * 
+                 FROM slot_locks 
+                 WHERE end_block BETWEEN ?1 AND ?2
+             ) 
+             WHERE (block, rank, id) > (?3, ?4, ?5) 
+             AND (?7 IS NULL OR namespace = ?7) 
+             AND archived_at IS NULL 
+             ORDER BY block, rank, id 
+             LIMIT ?6",
+        )?;
+        let (after_block, after_rank, after_id) = match after {
+            Some(cursor) => (cursor.block as i64, cursor.rank, cursor.lock_id),
+            None => (-1, 0, 0),
+        };
+        let events = stmt
+            .query_map(
+                rusqlite::params![
+                    start_block as i64,
+                    end_block as i64,
+                    after_block,
+                    after_rank,
+                    after_id,
+                    limit as i64,
+                    namespace
+                ],
+                |row| {
+                    let rank: u8 = row.get(1)?;
+                    Ok(LockEvent {
+                        cursor: LockEventCursor {
+                            block: row.get(0)?,
+                            rank,
+                            lock_id: row.get(2)?,
+                        },
+                        kind: if rank == 1 {
+                            LockEventKind::Locked
+                        } else {
+                            LockEventKind::Unlocked
+                        },
+                        lock: LockedSlot {
+                            btc_txid: row.get(3)?,
+                            btc_block: row.get(4)?,
+                            contract_address: row.get(5)?,
+                            slot_index: row.get(6)?,
+                            revert_value: row.get(7)?,
+                            current_value: row.get(8)?,
+                            start_block: row.get(9)?,
+                            end_block: row.get(10)?,
+                            unlock_reason: row.get(11)?,
+                            namespace: row.get(12)?,
+                            metadata: row.get(13)?,
+                            labels: json_from_row(row, 14)?,
+                            lock_group: row.get(15)?,
+                            alt_btc_txids: json_from_row(row, 16)?,
+                            required_confirmed_txids: row.get(17)?,
+                            locked_at: row.get("locked_at")?,
+                            locked_by: row.get("locked_by")?,
+                        },
+                    })
+                },
+            )?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(events)
+    }
+
+    /// Returns the number of active locks, in `namespace` or in all namespaces
+    pub fn active_lock_count(&self, namespace: Option<&str>) -> Result<u64> {
+        let conn = self.connection()?;
+        Ok(conn.query_row(
+            "SELECT COUNT(*) FROM slot_locks
+             WHERE end_block IS NULL AND (?1 IS NULL OR namespace = ?1)",
+            [namespace],
+            |row| row.get(0),
+        )?)
+    }
+
+    /// Returns the number of active locks `caller` made, in all namespaces, counting the slots
+    /// of its reservations that haven't expired at the unix time `now`
+    pub fn caller_active_locks(
+        &self,
+        transaction: &Transaction,
+        caller: &str,
+        now: u64,
+    ) -> Result<u64> {
+        Ok(transaction.query_row(
+            "SELECT (SELECT COUNT(*) FROM slot_locks WHERE locked_by = ?1 AND end_block IS NULL)
+                + (SELECT COUNT(*) FROM lock_reservation_slots s
+                   JOIN lock_reservations r ON r.id = s.reservation_id
+                   WHERE s.locked_by = ?1 AND r.expires_at > ?2)",
+            rusqlite::params![caller, now as i64],
+            |row| row.get(0),
+        )?)
+    }
+
+    /// Returns the slots of the active locks of a namespace that started at or before
+    /// `current_block`, ordered by contract address and slot index
+    pub fn active_lock_slots(
+        &self,
+        namespace: &str,
+        current_block: u64,
+    ) -> Result<Vec<(String, Vec<u8>)>> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare(
+            "SELECT contract_address, slot_index FROM slot_locks
+             WHERE end_block IS NULL AND namespace = ?1 AND start_block <= ?2
+             ORDER BY contract_address, slot_index",
+        )?;
+        let slots = stmt
+            .query_map(rusqlite::params![namespace, current_block as i64], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(slots)
+    }
+
+    /// Returns every active lock, ordered by namespace, contract address and slot index
+    pub fn active_locks(&self) -> Result<Vec<LockedSlot>> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare(
+            "SELECT btc_txid, btc_block, contract_address, slot_index, revert_value, current_value, start_block, end_block, unlock_reason, namespace, metadata, labels, lock_group, alt_btc_txids, required_confirmed_txids, CAST(strftime('%s', created_at) AS INTEGER) AS locked_at, locked_by 
+             FROM slot_locks 
+             WHERE end_block IS NULL 
+             ORDER BY namespace, contract_address, slot_index",
+        )?;
+        let locks = stmt
+            .query_map([], locked_slot_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(locks)
+    }
+
+    /// Returns the `limit` most recently made active locks, newest first
+    pub fn newest_active_locks(&self, limit: usize) -> Result<Vec<LockedSlot>> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare(
+            "SELECT btc_txid, btc_block, contract_address, slot_index, revert_value, current_value, start_block, end_block, unlock_reason, namespace, metadata, labels, lock_group, alt_btc_txids, required_confirmed_txids, CAST(strftime('%s', created_at) AS INTEGER) AS locked_at, locked_by 
+             FROM slot_locks 
+             WHERE end_block IS NULL 
+             ORDER BY id DESC 
+             LIMIT ?1",
+        )?;
+        let locks = stmt
+            .query_map([limit as i64], locked_slot_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(locks)
+    }
+
+    /// Returns the `limit` most recently reverted locks, latest first
+    pub fn latest_reverted_locks(&self, limit: usize) -> Result<Vec<LockedSlot>> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare(
+            "SELECT btc_txid, btc_block, contract_address, slot_index, revert_value, current_value, start_block, end_block, unlock_reason, namespace, metadata, labels, lock_group, alt_btc_txids, required_confirmed_txids, CAST(strftime('%s', created_at) AS INTEGER) AS locked_at, locked_by 
+             FROM slot_locks 
+             WHERE end_block IS NOT NULL 
+             AND unlock_reason IN ('revert-threshold', 'double-spent', 'manual-revert') 
+             AND archived_at IS NULL 
+             ORDER BY end_block DESC, id DESC 
+             LIMIT ?1",
+        )?;
+        let locks = stmt
+            .query_map([limit as i64], locked_slot_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(locks)
+    }
+
+    /// Returns the lock rows with ids above `after_id`, in id order, at most `limit` of them
+    pub fn lock_rows(
+        &self,
+        namespace: Option<&str>,
+        after_id: i64,
+        limit: usize,
+    ) -> Result<Vec<LockRow>> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare(
+            "SELECT btc_txid, btc_block, contract_address, slot_index, revert_value, current_value, start_block, end_block, unlock_reason, namespace, metadata, labels, lock_group, alt_btc_txids, required_confirmed_txids, id, created_at, CAST(strftime('%s', created_at) AS INTEGER) AS locked_at, locked_by 
+             FROM slot_locks 
+             WHERE id > ?1 AND (?2 IS NULL OR namespace = ?2) AND archived_at IS NULL 
+             ORDER BY id 
+             LIMIT ?3",
+        )?;
+        let rows = stmt
+            .query_map(
+                rusqlite::params![after_id, namespace, limit as i64],
+                lock_row_from_row,
+            )?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(rows)
+    }
+
+    /// Returns every lock of a slot, active and unlocked, oldest first
+    pub fn slot_lock_rows(
+        &self,
+        namespace: &str,
+        contract_address: &str,
+        slot_index: &[u8],
+    ) -> Result<Vec<LockRow>> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare(
+            "SELECT btc_txid, btc_block, contract_address, slot_index, revert_value, current_value, start_block, end_block, unlock_reason, namespace, metadata, labels, lock_group, alt_btc_txids, required_confirmed_txids, id, created_at, CAST(strftime('%s', created_at) AS INTEGER) AS locked_at, locked_by 
+             FROM slot_locks 
+             WHERE namespace = ?1 AND contract_address = ?2 AND slot_index = ?3 AND archived_at IS NULL 
+             ORDER BY start_block, id",
+        )?;
+        let rows = stmt
+            .query_map(
+                rusqlite::params![namespace, contract_address, slot_index],
+                lock_row_from_row,
+            )?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(rows)
+    }
+
+    /// Returns the lock holding a slot at the end of sova block `block`, if any, and the latest
+    /// lock of the slot that had ended by then. Archived locks count, as they are still known.
+    pub fn slot_lock_rows_at(
+        &self,
+        namespace: &str,
+        contract_address: &str,
+        slot_index: &[u8],
+        block: u64,
+    ) -> Result<(Option<LockRow>, Option<LockRow>)> {
+        let conn = self.connection()?;
+        let params = rusqlite::params![namespace, contract_address, slot_index, block as i64];
+        let holding = conn
+            .query_row(
+                "SELECT btc_txid, btc_block, contract_address, slot_index, revert_value, current_value, start_block, end_block, unlock_reason, namespace, metadata, labels, lock_group, alt_btc_txids, required_confirmed_txids, id, created_at, CAST(strftime('%s', created_at) AS INTEGER) AS locked_at, locked_by 
+                 FROM slot_locks 
+                 WHERE namespace = ?1 AND contract_address = ?2 AND slot_index = ?3 
+                 AND start_block <= ?4 AND (end_block IS NULL OR end_block > ?4) 
+                 ORDER BY start_block DESC, id DESC 
+                 LIMIT 1",
+                params,
+                lock_row_from_row,
+            )
+            .optional()?;
+        let last_ended = conn
+            .query_row(
+                "SELECT btc_txid, btc_block, contract_address, slot_index, revert_value, current_value, start_block, end_block, unlock_reason, namespace, metadata, labels, lock_group, alt_btc_txids, required_confirmed_txids, id, created_at, CAST(strftime('%s', created_at) AS INTEGER) AS locked_at, locked_by 
+                 FROM slot_locks 
+                 WHERE namespace = ?1 AND contract_address = ?2 AND slot_index = ?3 
+                 AND end_block <= ?4 
+                 ORDER BY end_block DESC, id DESC 
+                 LIMIT 1",
+                params,
+                lock_row_from_row,
+            )
+            .optional()?;
+        Ok((holding, last_ended))
+    }
+
+    /// Returns the locks of the namespace on `btc_txid`, only the active ones unless
+    /// `include_unlocked`, oldest first
+    pub fn txid_lock_rows(
+        &self,
+        namespace: &str,
+        btc_txid: &str,
+        include_unlocked: bool,
+    ) -> Result<Vec<LockRow>> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare(
+            "SELECT btc_txid, btc_block, contract_address, slot_index, revert_value, current_value, start_block, end_block, unlock_reason, namespace, metadata, labels, lock_group, alt_btc_txids, required_confirmed_txids, id, created_at, CAST(strftime('%s', created_at) AS INTEGER) AS locked_at, locked_by 
+             FROM slot_locks 
+             WHERE namespace = ?1 AND btc_txid = ?2 AND (?3 OR end_block IS NULL) AND archived_at IS NULL 
+             ORDER BY id",
+        )?;
+        let rows = stmt
+            .query_map(
+                rusqlite::params![namespace, btc_txid, include_unlocked],
+                lock_row_from_row,
+            )?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(rows)
+    }
+
+    /// Returns the locks of a contract made in sova blocks `start_block` through `end_block`
+    /// (inclusive), active and unlocked, with ids above `after_id`, in id order, at most `limit`
+    /// of them
+    pub fn contract_lock_rows(
+        &self,
+        namespace: &str,
+        contract_address: &str,
+        start_block: u64,
+        end_block: Option<u64>,
+        after_id: i64,
+        limit: usize,
+    ) -> Result<Vec<LockRow>> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare(
+            "SELECT btc_txid, btc_block, contract_address, slot_index, revert_value, current_value, start_block, end_block, unlock_reason, namespace, metadata, labels, lock_group, alt_btc_txids, required_confirmed_txids, id, created_at, CAST(strftime('%s', created_at) AS INTEGER) AS locked_at, locked_by 
+             FROM slot_locks 
+             WHERE namespace = ?1 AND contract_address = ?2 AND id > ?3 
+             AND start_block >= ?4 AND (?5 IS NULL OR start_block <= ?5) AND archived_at IS NULL 
+             ORDER BY id 
+             LIMIT ?6",
+        )?;
+        let rows = stmt
+            .query_map(
+                rusqlite::params![
+                    namespace,
+                    contract_address,
+                    after_id,
+                    start_block as i64,
+                    end_block.map(|block| block as i64),
+                    limit as i64
+                ],
+                lock_row_from_row,
+            )?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(rows)
+    }
+
+    /// Inserts lock rows exported from another sentinel, skipping rows that are already present
+    /// and active locks on slots that are already locked. Returns whether each row was inserted.
+    pub fn import_lock_rows(
+        &self,
+        transaction: &Transaction,
+        rows: &[LockRow],
+    ) -> Result<Vec<bool>> {
+        let mut imported = Vec::with_capacity(rows.len());
+        for row in rows {
+            let lock = &row.lock;
+            let result = transaction.query_row(
+                "SELECT 1 FROM slot_locks 
+                 WHERE namespace = ?1 AND contract_address = ?2 AND slot_index = ?3 
+                 AND start_block = ?4 AND btc_txid = ?5",
+                rusqlite::params![
+                    lock.namespace,
+                    lock.contract_address,
+                    lock.slot_index,
+                    lock.start_block,
+                    lock.btc_txid
+                ],
+                |_| Ok(()),
+            );
+            let exists = match result {
+                Ok(_) => true,
+                Err(rusqlite::Error::QueryReturnedNoRows) => false,
+                Err(e) => return Err(e.into()),
+            };
+            if exists
+                || (lock.end_block.is_none()
+                    && self.is_slot_locked_with_transaction(
+                        transaction,
+                        &lock.namespace,
+                        &lock.contract_address,
+                        &lock.slot_index,
+                    )?)
+            {
+                imported.push(false);
+                continue;
+            }
+
+            transaction.execute(
+                "INSERT INTO slot_locks (
+                    start_block, end_block, btc_block, contract_address, slot_index, btc_txid,
+                    revert_value, current_value, unlock_reason, namespace, metadata, labels,
+                    alt_btc_txids, required_confirmed_txids, created_at, locked_by
+                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14,
+                    COALESCE(?15, CURRENT_TIMESTAMP), ?16)",
+                rusqlite::params![
+                    lock.start_block,
+                    lock.end_block,
+                    lock.btc_block,
+                    lock.contract_address,
+                    lock.slot_index,
+                    lock.btc_txid,
+                    lock.revert_value,
+                    lock.current_value,
+                    lock.unlock_reason,
+                    lock.namespace,
+                    lock.metadata,
+                    serde_json::to_string(&lock.labels)?,
+                    serde_json::to_string(&lock.alt_btc_txids)?,
+                    lock.required_confirmed_txids,
+                    row.created_at,
+                    lock.locked_by,
+                ],
+            )?;
+            imported.push(true);
+        }
+        Ok(imported)
+    }
+
+    /// Returns each namespace with the latest sova block any of its locks was made or unlocked at
+    pub fn latest_sova_blocks(&self) -> Result<Vec<(String, u64)>> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare(
+            "SELECT namespace, MAX(MAX(start_block), COALESCE(MAX(end_block), 0)) 
+             FROM slot_locks 
+             GROUP BY namespace 
+             ORDER BY namespace",
+        )?;
+        let blocks = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(blocks)
+    }
+
+    /// Returns up to `limit` unlocked lock rows of a namespace, in id order, that were unlocked at
+    /// or before sova block `unlocked_by_block` or more than `unlocked_days_ago` days ago. A row
+    /// matching either limit is returned, and an unset limit matches nothing. Archived and
+    /// restored rows are left out.
+    pub fn prunable_lock_rows(
+        &self,
+        namespace: &str,
+        unlocked_by_block: Option<u64>,
+        unlocked_days_ago: Option<u64>,
+        limit: usize,
+    ) -> Result<Vec<LockRow>> {
+        let conn = self.connection()?;
+        // `updated_at` is bumped when a lock is unlocked, and unlocked rows aren't updated again
+        let mut stmt = conn.prepare(
+            "SELECT btc_txid, btc_block, contract_address, slot_index, revert_value, current_value, start_block, end_block, unlock_reason, namespace, metadata, labels, lock_group, alt_btc_txids, required_confirmed_txids, id, created_at, CAST(strftime('%s', created_at) AS INTEGER) AS locked_at, locked_by 
+             FROM slot_locks 
+             WHERE namespace = ?1 AND end_block IS NOT NULL 
+             AND archived_at IS NULL AND restored_at IS NULL 
+             AND (end_block <= ?2 
+                  OR updated_at <= datetime('now', '-' || ?3 || ' days')) 
+             ORDER BY id 
+             LIMIT ?4",
+        )?;
+        let rows = stmt
+            .query_map(
+                rusqlite::params![
+                    namespace,
+                    unlocked_by_block,
+                    unlocked_days_ago,
+                    limit as i64
+                ],
+                lock_row_from_row,
+            )?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(rows)
+    }
+
+    /// Marks the unlocked lock rows with the given ids archived, hiding them from queries until
+    /// they are restored. Returns how many were marked.
+    pub fn archive_lock_rows(&self, transaction: &Transaction, ids: &[i64]) -> Result<usize> {
+        let mut archived = 0;
+        for chunk in ids.chunks(MAX_SLOTS_PER_STATEMENT) {
+            let placeholders = vec!["?"; chunk.len()].join(", ");
+            archived += transaction.execute(
+                &format!(
+                    "UPDATE slot_locks SET archived_at = CURRENT_TIMESTAMP 
+                     WHERE end_block IS NOT NULL AND archived_at IS NULL AND id IN ({})",
+                    placeholders
+                ),
+                rusqlite::params_from_iter(chunk),
+            )?;
+        }
+        Ok(archived)
+    }
+
+    /// Returns up to `limit` lock rows, in id order, that were archived more than
+    /// `archived_days_ago` days ago
+    pub fn purgeable_lock_rows(
+        &self,
+        archived_days_ago: u64,
+        limit: usize,
+    ) -> Result<Vec<LockRow>> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare(
+            "SELECT btc_txid, btc_block, contract_address, slot_index, revert_value, current_value, start_block, end_block, unlock_reason, namespace, metadata, labels, lock_group, alt_btc_txids, required_confirmed_txids, id, created_at, CAST(strftime('%s', created_at) AS INTEGER) AS locked_at, locked_by 
+             FROM slot_locks 
+             WHERE archived_at <= datetime('now', '-' || ?1 || ' days') 
+             ORDER BY id 
+             LIMIT ?2",
+        )?;
+        let rows = stmt
+            .query_map(
+                rusqlite::params![archived_days_ago, limit as i64],
+                lock_row_from_row,
+            )?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(rows)
+    }
+
+    /// Deletes the archived lock rows with the given ids for good, returning how many were
+    /// deleted. Rows that aren't archived are never deleted.
+    pub fn delete_lock_rows(&self, transaction: &Transaction, ids: &[i64]) -> Result<usize> {
+        let mut deleted = 0;
+        for chunk in ids.chunks(MAX_SLOTS_PER_STATEMENT) {
+            let placeholders = vec!["?"; chunk.len()].join(", ");
+            deleted += transaction.execute(
+                &format!(
+                    "DELETE FROM slot_locks WHERE archived_at IS NOT NULL AND id IN ({})",
+                    placeholders
+                ),
+                rusqlite::params_from_iter(chunk),
+            )?;
+        }
+        Ok(deleted)
+    }
+
+    /// Restores the archived locks of a slot, exempting them from the retention policy. Returns
+    /// how many were restored.
+    pub fn restore_locks(
+        &self,
+        namespace: &str,
+        contract_address: &str,
+        slot_index: &[u8],
+    ) -> Result<usize> {
+        let conn = self.connection()?;
+        let restored = conn.execute(
+            "UPDATE slot_locks SET archived_at = NULL, restored_at = CURRENT_TIMESTAMP 
+             WHERE namespace = ?1 AND contract_address = ?2 AND slot_index = ?3 
+             AND archived_at IS NOT NULL",
+            rusqlite::params![namespace, contract_address, slot_index],
+        )?;
+        Ok(restored)
+    }
+
+    /// Returns the txids of active locks, most recently locked first
+    pub fn recent_active_txids(&self, limit: usize) -> Result<Vec<String>> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare(
+            "SELECT btc_txid FROM slot_locks
+             WHERE end_block IS NULL
+             GROUP BY btc_txid
+             ORDER BY MAX(start_block) DESC, MAX(id) DESC
+             LIMIT ?1",
+        )?;
+        let txids = stmt
+            .query_map([limit as i64], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+        Ok(txids)
+    }
+
     pub fn batch_insert_slot_locks(
         &self,
         transaction: &Transaction,
@@ -181,6 +1087,7 @@ impl Database {
         for slot in slots {
             let is_locked = self.is_slot_locked_with_transaction(
                 transaction,
+                &slot.namespace,
                 &slot.contract_address,
                 slot.slot_index.as_slice(),
             )?;
@@ -195,9 +1102,9 @@ impl Database {
             .map(|(slot, _)| slot)
             .collect();
 
-        if !slots_to_insert.is_empty() {
+        for slots_to_insert in slots_to_insert.chunks(MAX_SLOTS_PER_STATEMENT) {
             // Build multi-value insert query
-            let values_str = "(?, ?, ?, ?, ?, ?, ?, ?)"
+            let values_str = "(?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
                 .repeat(slots_to_insert.len())
                 .split(")(")
                 .collect::<Vec<_>>()
@@ -205,24 +1112,30 @@ impl Database {
 
             let sql = format!(
                 "INSERT INTO slot_locks (
-                    start_block, btc_block, contract_address, slot_index, 
-                    slot_index_int, btc_txid, revert_value, current_value
+                    start_block, btc_block, contract_address, slot_index,
+                    btc_txid, revert_value, current_value, namespace, metadata, labels,
+                    alt_btc_txids, required_confirmed_txids, locked_by
                 ) VALUES {}",
                 values_str,
             );
 
             // Flatten parameters
             let mut params: Vec<rusqlite::types::ToSqlOutput> =
-                Vec::with_capacity(slots_to_insert.len() * 8);
+                Vec::with_capacity(slots_to_insert.len() * 13);
             for slot in slots_to_insert {
                 params.push((slot.start_block as i64).into());
                 params.push((slot.btc_block as i64).into());
                 params.push(slot.contract_address.as_str().into());
                 params.push(slot.slot_index.as_slice().into());
-                params.push(slot.slot_index_int.to_sql().unwrap());
                 params.push(slot.btc_txid.as_str().into());
                 params.push(slot.revert_value.as_slice().into());
                 params.push(slot.current_value.as_slice().into());
+                params.push(slot.namespace.as_str().into());
+                params.push(slot.metadata.as_slice().into());
+                params.push(serde_json::to_string(&slot.labels)?.into());
+                params.push(serde_json::to_string(&slot.alt_btc_txids)?.into());
+                params.push(slot.required_confirmed_txids.into());
+                params.push(rusqlite::ToSql::to_sql(&slot.locked_by)?);
             }
 
             transaction.execute(&sql, rusqlite::params_from_iter(params))?;
@@ -234,6 +1147,7 @@ impl Database {
     pub fn batch_get_locked_slots(
         &self,
         transaction: &Transaction,
+        namespace: &str,
         slots: &[(&str, &[u8])], // Vec of (contract_address, slot_index)
         current_block: u64,      // Added parameter
     ) -> Result<Vec<Option<LockedSlot>>> {
@@ -241,112 +1155,405 @@ impl Database {
             return Ok(Vec::new());
         }
 
-        // Build query with multiple (contract_address, slot_index) pairs
-        let placeholders = (1..=slots.len())
-            .map(|i| {
-                format!(
-                    "(contract_address = ?{} AND slot_index = ?{})",
-                    i * 2 - 1,
-                    i * 2
-                )
+        // Build result map using both contract_address and slot_index as key
+        let mut slot_map = std::collections::HashMap::new();
+        for slots in slots.chunks(MAX_SLOTS_PER_STATEMENT) {
+            // Build query with multiple (contract_address, slot_index) pairs
+            let placeholders = (1..=slots.len())
+                .map(|i| {
+                    format!(
+                        "(contract_address = ?{} AND slot_index = ?{})",
+                        i * 2 - 1,
+                        i * 2
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(" OR ");
+
+            let sql = format!(
+                "SELECT btc_txid, btc_block, contract_address, slot_index, revert_value, current_value, start_block, end_block, unlock_reason, namespace, metadata, labels, lock_group, alt_btc_txids, required_confirmed_txids, CAST(strftime('%s', created_at) AS INTEGER) AS locked_at, locked_by 
+                 FROM slot_locks 
+                 WHERE ({}) 
+                 AND (end_block IS NULL OR end_block = ?{})
+                 AND start_block <= ?{}
+                 AND namespace = ?{}
+                 AND archived_at IS NULL",
+                placeholders,
+                slots.len() * 2 + 1,    // Parameter index for current_block in end_block check
+                slots.len() * 2 + 1,    // Reuse parameter index for start_block check
+                slots.len() * 2 + 2,    // Parameter index for namespace
+            );
+
+            // Flatten parameters
+            let mut params: Vec<rusqlite::types::ToSqlOutput> =
+                Vec::with_capacity(slots.len() * 2 + 2);
+            for (addr, idx) in slots {
+                params.push((*addr).into());
+                params.push((*idx).into());
+            }
+            params.push((current_block as i64).into()); // Add current_block parameter for end_block check
+            params.push(namespace.into());
+
+            // Execute query and build result map
+            let mut stmt = transaction.prepare(&sql)?;
+            let rows = stmt.query_map(rusqlite::params_from_iter(params), locked_slot_from_row)?;
+
+            for row in rows {
+                let slot = row?;
+                slot_map.insert(
+                    (slot.contract_address.clone(), slot.slot_index.clone()),
+                    slot,
+                );
+            }
+        }
+
+        // Maintain input order
+        Ok(slots
+            .iter()
+            .map(|(addr, idx)| {
+                slot_map
+                    .get(&((*addr).to_string(), (*idx).to_vec()))
+                    .cloned()
             })
-            .collect::<Vec<_>>()
-            .join(" OR ");
+            .collect())
+    }
 
-        let sql = format!(
-            "SELECT btc_txid, btc_block, contract_address, slot_index, revert_value, current_value, start_block, end_block 
+    pub fn batch_unlock_slots(
+        &self,
+        transaction: &Transaction,
+        namespace: &str,
+        slots: &[(&str, &[u8], u64)], // Vec of (contract_address, slot_index, end_block)
+        reason: UnlockReason,
+    ) -> Result<()> {
+        if slots.is_empty() {
+            return Ok(());
+        }
+
+        for slots in slots.chunks(MAX_SLOTS_PER_STATEMENT) {
+            // Build multi-value update query with parameter indices:
+            // ?1 is end_block (first parameter), ?2 is the unlock reason, ?3 the namespace
+            // Then for each slot: ?4,?5 for first slot's addr/idx, ?6,?7 for second slot's addr/idx, etc
+            let placeholders = (1..=slots.len())
+                .map(|i| {
+                    format!(
+                        "(contract_address = ?{} AND slot_index = ?{})",
+                        i * 2 + 2,
+                        i * 2 + 3
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(" OR ");
+
+            let sql = format!(
+                "UPDATE slot_locks 
+                 SET end_block = ?1, unlock_reason = ?2 
+                 WHERE ({}) AND namespace = ?3 AND end_block IS NULL",
+                placeholders
+            );
+
+            // Flatten parameters
+            let mut params: Vec<rusqlite::types::ToSqlOutput> =
+                Vec::with_capacity(3 + slots.len() * 2);
+            params.push((slots[0].2 as i64).into()); // end_block (same for all slots)
+            params.push(reason.as_str().into());
+            params.push(namespace.into());
+            for (addr, idx, _) in slots {
+                params.push((*addr).into());
+                params.push((*idx).into());
+            }
+
+            transaction.execute(&sql, rusqlite::params_from_iter(params))?;
+        }
+        Ok(())
+    }
+
+    /// Returns the active locks of the namespace on `btc_txid`
+    pub fn active_locks_by_txid(
+        &self,
+        transaction: &Transaction,
+        namespace: &str,
+        btc_txid: &str,
+    ) -> Result<Vec<LockedSlot>> {
+        let mut stmt = transaction.prepare(
+            "SELECT btc_txid, btc_block, contract_address, slot_index, revert_value, current_value, start_block, end_block, unlock_reason, namespace, metadata, labels, lock_group, alt_btc_txids, required_confirmed_txids, CAST(strftime('%s', created_at) AS INTEGER) AS locked_at, locked_by 
              FROM slot_locks 
-             WHERE ({}) 
-             AND (end_block IS NULL OR end_block = ?{})
-             AND start_block <= ?{}",  // Added start_block constraint
-            placeholders,
-            slots.len() * 2 + 1,    // Parameter index for current_block in end_block check
-            slots.len() * 2 + 1,    // Reuse parameter index for start_block check
-        );
+             WHERE btc_txid = ?1 AND namespace = ?2 AND end_block IS NULL 
+             ORDER BY id",
+        )?;
+        let locks = stmt
+            .query_map(rusqlite::params![btc_txid, namespace], locked_slot_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(locks)
+    }
 
-        // Flatten parameters
-        let mut params: Vec<rusqlite::types::ToSqlOutput> = Vec::with_capacity(slots.len() * 2 + 2);
-        for (addr, idx) in slots {
-            params.push((*addr).into());
-            params.push((*idx).into());
-        }
-        params.push((current_block as i64).into()); // Add current_block parameter for end_block check
-
-        // Execute query and build result map
-        let mut stmt = transaction.prepare(&sql)?;
-        let rows = stmt.query_map(rusqlite::params_from_iter(params), |row| {
-            Ok(LockedSlot {
-                btc_txid: row.get(0)?,
-                btc_block: row.get(1)?,
-                contract_address: row.get(2)?,
-                slot_index: row.get(3)?,
-                revert_value: row.get(4)?,
-                current_value: row.get(5)?,
-                start_block: row.get(6)?,
-                end_block: row.get(7)?,
-            })
-        })?;
+    /// Points all active locks of the namespace on `old_btc_txid` at its replacement and records the replacement.
+    /// `detected` tells whether the replacement was found by the server rather than reported by
+    /// the client. Returns the number of locks updated.
+    pub fn replace_lock_txid(
+        &self,
+        transaction: &Transaction,
+        namespace: &str,
+        old_btc_txid: &str,
+        new_btc_txid: &str,
+        detected: bool,
+    ) -> Result<usize> {
+        let updated = transaction.execute(
+            "UPDATE slot_locks SET btc_txid = ?2
+             WHERE btc_txid = ?1 AND namespace = ?3 AND end_block IS NULL",
+            rusqlite::params![old_btc_txid, new_btc_txid, namespace],
+        )?;
+        if updated > 0 {
+            transaction.execute(
+                "INSERT INTO lock_tx_replacements
+                    (old_btc_txid, new_btc_txid, locks_updated, detected, namespace)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                rusqlite::params![old_btc_txid, new_btc_txid, updated, detected, namespace],
+            )?;
+        }
+        Ok(updated)
+    }
+
+    /// Makes the active locks of `slots` an atomic group and returns the group's id
+    pub fn create_lock_group(
+        &self,
+        transaction: &Transaction,
+        namespace: &str,
+        slots: &[(&str, &[u8])], // Vec of (contract_address, slot_index)
+    ) -> Result<i64> {
+        let mut stmt = transaction.prepare(
+            "SELECT id FROM slot_locks 
+             WHERE namespace = ?1 AND contract_address = ?2 AND slot_index = ?3 AND end_block IS NULL",
+        )?;
+        let mut ids = Vec::with_capacity(slots.len());
+        for (contract_address, slot_index) in slots {
+            let id: i64 = stmt.query_row(
+                rusqlite::params![namespace, contract_address, slot_index],
+                |row| row.get(0),
+            )?;
+            ids.push(id);
+        }
+        // Lock ids are never reused, so neither are group ids
+        let group = ids
+            .iter()
+            .copied()
+            .min()
+            .context("A lock group needs a lock")?;
+        for ids in ids.chunks(MAX_SLOTS_PER_STATEMENT) {
+            let placeholders = vec!["?"; ids.len()].join(", ");
+            let sql = format!(
+                "UPDATE slot_locks SET lock_group = ? WHERE id IN ({})",
+                placeholders
+            );
+            transaction.execute(
+                &sql,
+                rusqlite::params_from_iter(std::iter::once(&group).chain(ids)),
+            )?;
+        }
+        Ok(group)
+    }
+
+    /// Returns the active locks of the given atomic groups
+    pub fn active_group_locks(
+        &self,
+        transaction: &Transaction,
+        groups: &[i64],
+    ) -> Result<Vec<LockedSlot>> {
+        let mut locks = Vec::new();
+        for groups in groups.chunks(MAX_SLOTS_PER_STATEMENT) {
+            let placeholders = vec!["?"; groups.len()].join(", ");
+            let sql = format!(
+                "SELECT btc_txid, btc_block, contract_address, slot_index, revert_value, current_value, start_block, end_block, unlock_reason, namespace, metadata, labels, lock_group, alt_btc_txids, required_confirmed_txids, CAST(strftime('%s', created_at) AS INTEGER) AS locked_at, locked_by 
+                 FROM slot_locks 
+                 WHERE lock_group IN ({}) AND end_block IS NULL AND archived_at IS NULL 
+                 ORDER BY id",
+                placeholders
+            );
+            let mut stmt = transaction.prepare(&sql)?;
+            let rows = stmt.query_map(rusqlite::params_from_iter(groups), locked_slot_from_row)?;
+            locks.extend(rows.collect::<rusqlite::Result<Vec<_>>>()?);
+        }
+        Ok(locks)
+    }
+
+    /// Counts lock attempts rejected with `AlreadyLocked`, one entry per rejected attempt
+    pub fn record_lock_conflicts(
+        &self,
+        transaction: &Transaction,
+        namespace: &str,
+        contract_addresses: &[&str],
+    ) -> Result<()> {
+        let mut conflicts: HashMap<&str, i64> = HashMap::new();
+        for contract_address in contract_addresses {
+            *conflicts.entry(*contract_address).or_default() += 1;
+        }
 
-        // Build result map using both contract_address and slot_index as key
-        let mut slot_map = std::collections::HashMap::new();
-        for row in rows {
-            let slot = row?;
-            slot_map.insert(
-                (slot.contract_address.clone(), slot.slot_index.clone()),
-                slot,
-            );
+        let mut stmt = transaction.prepare(
+            "INSERT INTO lock_conflicts (namespace, contract_address, conflicts, last_conflict_at)
+             VALUES (?1, ?2, ?3, CURRENT_TIMESTAMP)
+             ON CONFLICT(namespace, contract_address) DO UPDATE SET
+                conflicts = conflicts + excluded.conflicts,
+                last_conflict_at = excluded.last_conflict_at",
+        )?;
+        for (contract_address, count) in conflicts {
+            stmt.execute(rusqlite::params![namespace, contract_address, count])?;
         }
 
-        // Maintain input order
-        Ok(slots
-            .iter()
-            .map(|(addr, idx)| {
-                slot_map
-                    .get(&((*addr).to_string(), (*idx).to_vec()))
-                    .cloned()
-            })
-            .collect())
+        Ok(())
     }
 
-    pub fn batch_unlock_slots(
+    /// Returns the recorded lock conflicts, most conflicted contract first
+    pub fn lock_conflict_stats(
+        &self,
+        namespace: Option<&str>,
+        contract_address: Option<&str>,
+    ) -> Result<Vec<LockConflictStats>> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare(
+            "SELECT contract_address, conflicts, last_conflict_at, namespace FROM lock_conflicts
+             WHERE (?1 IS NULL OR contract_address = ?1) AND (?2 IS NULL OR namespace = ?2)
+             ORDER BY conflicts DESC, namespace, contract_address",
+        )?;
+        let stats = stmt
+            .query_map([contract_address, namespace], |row| {
+                Ok(LockConflictStats {
+                    contract_address: row.get(0)?,
+                    conflicts: row.get(1)?,
+                    last_conflict_at: row.get(2)?,
+                    namespace: row.get(3)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(stats)
+    }
+
+    /// Stores the raw Bitcoin transaction of a lock for rebroadcasting
+    pub fn insert_lock_transaction(
         &self,
         transaction: &Transaction,
-        slots: &[(&str, &[u8], u64)], // Vec of (contract_address, slot_index, end_block)
+        btc_txid: &str,
+        raw_tx: &[u8],
     ) -> Result<()> {
-        if slots.is_empty() {
-            return Ok(());
-        }
+        transaction.execute(
+            "INSERT OR IGNORE INTO lock_transactions (btc_txid, raw_tx) VALUES (?1, ?2)",
+            rusqlite::params![btc_txid, raw_tx],
+        )?;
+        Ok(())
+    }
 
-        // Build multi-value update query with parameter indices:
-        // ?1 is end_block (first parameter)
-        // Then for each slot: ?2,?3 for first slot's addr/idx, ?4,?5 for second slot's addr/idx, etc
-        let placeholders = (1..=slots.len())
-            .map(|i| {
-                format!(
-                    "(contract_address = ?{} AND slot_index = ?{})",
-                    i * 2,
-                    i * 2 + 1
-                )
-            })
-            .collect::<Vec<_>>()
-            .join(" OR ");
+    /// Returns the stored raw transactions that still back active locks, as (btc_txid, raw_tx)
+    pub fn pending_lock_transactions(&self) -> Result<Vec<(String, Vec<u8>)>> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare(
+            "SELECT btc_txid, raw_tx FROM lock_transactions t
+             WHERE EXISTS (
+                SELECT 1 FROM slot_locks s
+                WHERE s.btc_txid = t.btc_txid AND s.end_block IS NULL
+             )
+             ORDER BY created_at, btc_txid",
+        )?;
+        let transactions = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(transactions)
+    }
 
-        let sql = format!(
-            "UPDATE slot_locks 
-             SET end_block = ?1 
-             WHERE ({}) AND end_block IS NULL",
-            placeholders
-        );
+    /// Deletes stored raw transactions that no longer back any active lock
+    pub fn prune_lock_transactions(&self) -> Result<usize> {
+        let conn = self.connection()?;
+        let pruned = conn.execute(
+            "DELETE FROM lock_transactions
+             WHERE NOT EXISTS (
+                SELECT 1 FROM slot_locks s
+                WHERE s.btc_txid = lock_transactions.btc_txid AND s.end_block IS NULL
+             )",
+            [],
+        )?;
+        Ok(pruned)
+    }
 
-        // Flatten parameters
-        let mut params: Vec<rusqlite::types::ToSqlOutput> = Vec::with_capacity(1 + slots.len() * 2);
-        params.push((slots[0].2 as i64).into()); // end_block (same for all slots)
-        for (addr, idx, _) in slots {
-            params.push((*addr).into());
-            params.push((*idx).into());
+    /// Queues reverted locks for delivery to the revert executor
+    pub fn enqueue_revert_deliveries(
+        &self,
+        transaction: &Transaction,
+        slots: &[&LockedSlot],
+        reverted_at_block: u64,
+        reason: UnlockReason,
+    ) -> Result<()> {
+        let mut stmt = transaction.prepare(
+            "INSERT INTO revert_deliveries
+                (contract_address, slot_index, btc_txid, revert_value, current_value, reverted_at_block, reason, namespace)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        )?;
+        for slot in slots {
+            stmt.execute(rusqlite::params![
+                slot.contract_address,
+                slot.slot_index,
+                slot.btc_txid,
+                slot.revert_value,
+                slot.current_value,
+                reverted_at_block as i64,
+                reason,
+                slot.namespace,
+            ])?;
         }
+        Ok(())
+    }
+
+    /// Returns up to `limit` reverts still waiting for delivery, oldest first
+    pub fn pending_revert_deliveries(&self, limit: usize) -> Result<Vec<RevertDelivery>> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, contract_address, slot_index, btc_txid, revert_value, current_value,
+                    reverted_at_block, reason, attempts, namespace
+             FROM revert_deliveries
+             WHERE status = 'pending'
+             ORDER BY id
+             LIMIT ?1",
+        )?;
+        let deliveries = stmt
+            .query_map([limit as i64], |row| {
+                Ok(RevertDelivery {
+                    id: row.get(0)?,
+                    contract_address: row.get(1)?,
+                    slot_index: row.get(2)?,
+                    btc_txid: row.get(3)?,
+                    revert_value: row.get(4)?,
+                    current_value: row.get(5)?,
+                    reverted_at_block: row.get(6)?,
+                    reason: row.get(7)?,
+                    attempts: row.get(8)?,
+                    namespace: row.get(9)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(deliveries)
+    }
 
-        transaction.execute(&sql, rusqlite::params_from_iter(params))?;
+    /// Records the outcome of a delivery attempt. A failed delivery stays pending until it has
+    /// been attempted `max_attempts` times.
+    pub fn record_revert_delivery(
+        &self,
+        id: i64,
+        error: Option<&str>,
+        max_attempts: u32,
+    ) -> Result<()> {
+        let conn = self.connection()?;
+        match error {
+            None => conn.execute(
+                "UPDATE revert_deliveries
+                 SET status = 'delivered', attempts = attempts + 1, last_error = NULL,
+                     delivered_at = CURRENT_TIMESTAMP
+                 WHERE id = ?1",
+                [id],
+            )?,
+            Some(error) => conn.execute(
+                "UPDATE revert_deliveries
+                 SET attempts = attempts + 1, last_error = ?2,
+                     status = CASE WHEN attempts + 1 >= ?3 THEN 'failed' ELSE 'pending' END
+                 WHERE id = ?1",
+                rusqlite::params![id, error, max_attempts],
+            )?,
+        };
         Ok(())
     }
 }
@@ -356,18 +1563,21 @@ fn is_slot_locked_query() -> String {
     "SELECT 1 FROM slot_locks 
      WHERE contract_address = ?1 
      AND slot_index = ?2 
+     AND namespace = ?3 
      AND end_block IS NULL"
         .to_string()
 }
 
 // Helper function to get the SQL query for retrieving slot information
 fn get_slot_query() -> String {
-    "SELECT btc_txid, btc_block, contract_address, slot_index, revert_value, current_value, start_block, end_block 
+    "SELECT btc_txid, btc_block, contract_address, slot_index, revert_value, current_value, start_block, end_block, unlock_reason, namespace, metadata, labels, lock_group, alt_btc_txids, required_confirmed_txids, CAST(strftime('%s', created_at) AS INTEGER) AS locked_at, locked_by 
      FROM slot_locks 
      WHERE contract_address = ?1 
      AND slot_index = ?2 
+     AND namespace = ?4 
      AND (end_block IS NULL OR end_block = ?3)
      AND start_block <= ?3
+     AND archived_at IS NULL
      ORDER BY start_block, created_at DESC
      LIMIT 1"
         .to_string()
@@ -376,15 +1586,18 @@ fn get_slot_query() -> String {
 // Helper function to get the SQL query for unlocking a slot
 fn unlock_slot_query() -> String {
     "UPDATE slot_locks 
-     SET end_block = ?1 
+     SET end_block = ?1, unlock_reason = ?4 
      WHERE contract_address = ?2 
      AND slot_index = ?3 
+     AND namespace = ?5 
      AND end_block IS NULL"
         .to_string()
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct LockedSlot {
+    /// Sova network the lock belongs to, `""` for the default namespace
+    pub namespace: String,
     pub btc_txid: String,
     pub btc_block: u64,
     pub contract_address: String,
@@ -393,18 +1606,239 @@ pub struct LockedSlot {
     pub current_value: Vec<u8>,
     pub start_block: u64,
     pub end_block: Option<u64>,
+    /// Why the lock was unlocked, `None` while it is active or if it was unlocked before reasons
+    /// were recorded
+    pub unlock_reason: Option<UnlockReason>,
+    /// Opaque bytes the caller stored with the lock
+    pub metadata: Vec<u8>,
+    pub labels: BTreeMap<String, String>,
+    /// Atomic group the lock was made in, unlocked and reverted together with the rest of it
+    pub lock_group: Option<i64>,
+    /// Transactions that settle the lock in place of `btc_txid`, or along with it
+    pub alt_btc_txids: Vec<String>,
+    /// How many of [`Self::btc_txids`] must confirm before the lock unlocks. 0 is read as 1.
+    pub required_confirmed_txids: u32,
+    /// Unix time the lock was made at, `None` if unknown
+    pub locked_at: Option<u64>,
+    /// Authenticated caller that made the lock, `None` if unknown
+    pub locked_by: Option<String>,
+}
+
+impl LockedSlot {
+    /// The lock's transaction followed by its alternatives
+    pub fn btc_txids(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.btc_txid.as_str()).chain(self.alt_btc_txids.iter().map(String::as_str))
+    }
+}
+
+/// Reads a [`LockRow`] from the standard lock columns followed by `id` and `created_at`
+fn lock_row_from_row(row: &rusqlite::Row) -> rusqlite::Result<LockRow> {
+    Ok(LockRow {
+        lock: locked_slot_from_row(row)?,
+        id: row.get(15)?,
+        created_at: row.get(16)?,
+    })
+}
+
+fn locked_slot_from_row(row: &rusqlite::Row) -> rusqlite::Result<LockedSlot> {
+    Ok(LockedSlot {
+        btc_txid: row.get(0)?,
+        btc_block: row.get(1)?,
+        contract_address: row.get(2)?,
+        slot_index: row.get(3)?,
+        revert_value: row.get(4)?,
+        current_value: row.get(5)?,
+        start_block: row.get(6)?,
+        end_block: row.get(7)?,
+        unlock_reason: row.get(8)?,
+        namespace: row.get(9)?,
+        metadata: row.get(10)?,
+        labels: json_from_row(row, 11)?,
+        lock_group: row.get(12)?,
+        alt_btc_txids: json_from_row(row, 13)?,
+        required_confirmed_txids: row.get(14)?,
+        locked_at: row.get("locked_at")?,
+        locked_by: row.get("locked_by")?,
+    })
+}
+
+/// Reads a column holding JSON, like `labels` or `alt_btc_txids`
+fn json_from_row<T: serde::de::DeserializeOwned>(
+    row: &rusqlite::Row,
+    idx: usize,
+) -> rusqlite::Result<T> {
+    let json: String = row.get(idx)?;
+    serde_json::from_str(&json).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(idx, rusqlite::types::Type::Text, Box::new(e))
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockEventKind {
+    Locked,
+    Unlocked,
+}
+
+/// Position of a lock event in the canonical export order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LockEventCursor {
+    /// Sova block of the event
+    pub block: u64,
+    rank: u8,
+    /// Row id of the lock
+    pub lock_id: i64,
+}
+
+/// A lock or unlock of a slot, with the lock it belongs to
+#[derive(Debug, Clone)]
+pub struct LockEvent {
+    pub cursor: LockEventCursor,
+    pub kind: LockEventKind,
+    pub lock: LockedSlot,
+}
+
+/// A complete lock row, as exported and imported between sentinels
+#[derive(Debug, Clone)]
+pub struct LockRow {
+    /// Id of the row in the database it was read from
+    pub id: i64,
+    pub lock: LockedSlot,
+    /// UTC timestamp the lock was made at, formatted as "YYYY-MM-DD HH:MM:SS"
+    pub created_at: Option<String>,
+}
+
+/// Matches the revert or current value of a lock
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValueQuery {
+    /// The value contains these bytes
+    Contains(Vec<u8>),
+    /// The SHA-256 hash of the whole value is this
+    Sha256([u8; 32]),
+}
+
+impl ValueQuery {
+    pub fn matches(&self, value: &[u8]) -> bool {
+        match self {
+            Self::Contains(pattern) => {
+                !pattern.is_empty() && value.windows(pattern.len()).any(|window| window == pattern)
+            }
+            Self::Sha256(hash) => sha256::Hash::hash(value).to_byte_array() == *hash,
+        }
+    }
+}
+
+/// Why a lock was unlocked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UnlockReason {
+    /// The lock's Bitcoin transaction confirmed
+    Confirmed,
+    /// The lock's Bitcoin transaction didn't confirm before the revert threshold
+    RevertThreshold,
+    /// The lock's Bitcoin transaction was double-spent
+    DoubleSpent,
+    /// Unlocked through `BatchUnlockSlot` or `UnlockByTxid`
+    Manual,
+    /// Reverted through `UnlockByTxid`
+    ManualRevert,
+    /// Unlocked by an operator through the admin service
+    Admin,
+}
+
+impl UnlockReason {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Confirmed => "confirmed",
+            Self::RevertThreshold => "revert-threshold",
+            Self::DoubleSpent => "double-spent",
+            Self::Manual => "manual",
+            Self::ManualRevert => "manual-revert",
+            Self::Admin => "admin",
+        }
+    }
+
+    /// Whether the lock's values were reverted
+    pub fn is_revert(self) -> bool {
+        matches!(
+            self,
+            Self::RevertThreshold | Self::DoubleSpent | Self::ManualRevert
+        )
+    }
+}
+
+impl std::str::FromStr for UnlockReason {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "confirmed" => Ok(Self::Confirmed),
+            "revert-threshold" => Ok(Self::RevertThreshold),
+            "double-spent" => Ok(Self::DoubleSpent),
+            "manual" => Ok(Self::Manual),
+            "manual-revert" => Ok(Self::ManualRevert),
+            "admin" => Ok(Self::Admin),
+            other => Err(anyhow::anyhow!("Unknown unlock reason: {}", other)),
+        }
+    }
+}
+
+impl ToSql for UnlockReason {
+    fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
+        Ok(self.as_str().into())
+    }
+}
+
+impl rusqlite::types::FromSql for UnlockReason {
+    fn column_result(value: rusqlite::types::ValueRef<'_>) -> rusqlite::types::FromSqlResult<Self> {
+        value
+            .as_str()?
+            .parse()
+            .map_err(|e: anyhow::Error| rusqlite::types::FromSqlError::Other(e.into()))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LockConflictStats {
+    pub namespace: String,
+    pub contract_address: String,
+    pub conflicts: u64,
+    pub last_conflict_at: String,
+}
+
+/// A reverted lock queued for delivery to the revert executor
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RevertDelivery {
+    pub id: i64,
+    pub namespace: String,
+    pub contract_address: String,
+    pub slot_index: Vec<u8>,
+    pub btc_txid: String,
+    pub revert_value: Vec<u8>,
+    pub current_value: Vec<u8>,
+    pub reverted_at_block: u64,
+    /// Why the lock was reverted, `revert-threshold` or `double-spent`
+    pub reason: String,
+    /// Delivery attempts made so far
+    pub attempts: u32,
 }
 
 #[derive(Debug)]
 pub struct SlotInsertData {
+    pub namespace: String,
     pub contract_address: String,
     pub start_block: u64,
     pub btc_block: u64,
     pub slot_index: Vec<u8>,
-    pub slot_index_int: Option<i64>,
     pub btc_txid: String,
     pub revert_value: Vec<u8>,
     pub current_value: Vec<u8>,
+    pub metadata: Vec<u8>,
+    pub labels: BTreeMap<String, String>,
+    /// See [`LockedSlot::alt_btc_txids`]
+    pub alt_btc_txids: Vec<String>,
+    /// See [`LockedSlot::required_confirmed_txids`]
+    pub required_confirmed_txids: u32,
+    /// See [`LockedSlot::locked_by`]
+    pub locked_by: Option<String>,
 }
 
 #[cfg(test)]
@@ -430,32 +1864,37 @@ mod tests {
         let btc_block = 200;
 
         // Test initial state
-        assert!(!db.is_slot_locked(contract_addr, &slot_index)?);
+        assert!(!db.is_slot_locked("", contract_addr, &slot_index)?);
         assert!(db
-            .get_slot(contract_addr, &slot_index, start_block)?
+            .get_slot("", contract_addr, &slot_index, start_block)?
             .is_none());
 
         // Test inserting a slot lock
         db.with_transaction(|tx| {
             let slot = SlotInsertData {
+                namespace: String::new(),
                 contract_address: contract_addr.to_string(),
                 start_block,
                 btc_block,
                 slot_index: slot_index.clone(),
-                slot_index_int: None,
                 btc_txid: btc_txid.to_string(),
                 revert_value: revert_value.clone(),
                 current_value: current_value.clone(),
+                metadata: Vec::new(),
+                labels: Default::default(),
+                alt_btc_txids: Vec::new(),
+                required_confirmed_txids: 1,
+                locked_by: None,
             };
             db.insert_slot_lock(tx, &slot)
         })?;
 
         // Verify lock status
-        assert!(db.is_slot_locked(contract_addr, &slot_index)?);
+        assert!(db.is_slot_locked("", contract_addr, &slot_index)?);
 
         // Test getting slot information
         let slot = db
-            .get_slot(contract_addr, &slot_index, start_block)?
+            .get_slot("", contract_addr, &slot_index, start_block)?
             .unwrap();
         assert_eq!(slot.btc_txid, btc_txid);
         assert_eq!(slot.btc_block, btc_block);
@@ -468,10 +1907,16 @@ mod tests {
 
         // Test unlocking the slot
         let end_block = 150;
-        db.unlock_slot(contract_addr, &slot_index, end_block)?;
+        db.unlock_slot(
+            "",
+            contract_addr,
+            &slot_index,
+            end_block,
+            UnlockReason::Confirmed,
+        )?;
 
         // Verify unlock status
-        assert!(!db.is_slot_locked(contract_addr, &slot_index)?);
+        assert!(!db.is_slot_locked("", contract_addr, &slot_index)?);
 
         Ok(())
     }
@@ -481,24 +1926,34 @@ mod tests {
         let db = setup_test_db()?;
         let slot_data: Vec<SlotInsertData> = vec![
             SlotInsertData {
+                namespace: String::new(),
                 contract_address: "0x123".to_string(),
                 start_block: 100,
                 btc_block: 200,
                 slot_index: vec![1, 2, 3],
-                slot_index_int: None,
                 btc_txid: "txid1".to_string(),
                 revert_value: vec![4, 5, 6],
                 current_value: vec![7, 8, 9],
+                metadata: Vec::new(),
+                labels: Default::default(),
+                alt_btc_txids: Vec::new(),
+                required_confirmed_txids: 1,
+                locked_by: None,
             },
             SlotInsertData {
+                namespace: String::new(),
                 contract_address: "0x456".to_string(),
                 start_block: 101,
                 btc_block: 201,
                 slot_index: vec![2, 3, 4],
-                slot_index_int: None,
                 btc_txid: "txid2".to_string(),
                 revert_value: vec![5, 6, 7],
                 current_value: vec![8, 9, 10],
+                metadata: Vec::new(),
+                labels: Default::default(),
+                alt_btc_txids: Vec::new(),
+                required_confirmed_txids: 1,
+                locked_by: None,
             },
         ];
 
@@ -517,7 +1972,7 @@ mod tests {
         ];
 
         db.with_transaction(|tx| {
-            let results = db.batch_get_locked_slots(tx, &get_slots, 99)?;
+            let results = db.batch_get_locked_slots(tx, "", &get_slots, 99)?;
             assert_eq!(results.len(), 2);
             assert!(results[0].is_none()); // Should be None because current_block < start_block
             assert!(results[1].is_none());
@@ -526,7 +1981,7 @@ mod tests {
 
         // Test batch get with current_block = 101 (after both start blocks)
         db.with_transaction(|tx| {
-            let results = db.batch_get_locked_slots(tx, &get_slots, 101)?;
+            let results = db.batch_get_locked_slots(tx, "", &get_slots, 101)?;
             assert_eq!(results.len(), 2);
             assert!(results[0].is_some());
             assert!(results[1].is_some());
@@ -540,7 +1995,7 @@ mod tests {
 
         // Test batch get with current_block = 100 (equal to first start_block)
         db.with_transaction(|tx| {
-            let results = db.batch_get_locked_slots(tx, &get_slots, 100)?;
+            let results = db.batch_get_locked_slots(tx, "", &get_slots, 100)?;
             assert_eq!(results.len(), 2);
             assert!(results[0].is_some()); // First slot should be visible
             assert!(results[1].is_none()); // Second slot shouldn't be visible yet
@@ -554,13 +2009,13 @@ mod tests {
         ];
 
         db.with_transaction(|tx| {
-            db.batch_unlock_slots(tx, &unlock_slots)?;
+            db.batch_unlock_slots(tx, "", &unlock_slots, UnlockReason::Confirmed)?;
             Ok(())
         })?;
 
         // Verify unlocks
-        assert!(!db.is_slot_locked("0x123", &[1, 2, 3])?);
-        assert!(!db.is_slot_locked("0x456", &[2, 3, 4])?);
+        assert!(!db.is_slot_locked("", "0x123", &[1, 2, 3])?);
+        assert!(!db.is_slot_locked("", "0x456", &[2, 3, 4])?);
 
         Ok(())
     }
@@ -574,14 +2029,19 @@ mod tests {
         let handle = std::thread::spawn(move || {
             db_clone.with_transaction(|tx| {
                 let slot = SlotInsertData {
+                    namespace: String::new(),
                     contract_address: "0x123".to_string(),
                     start_block: 100,
                     btc_block: 200,
                     slot_index: vec![1, 2, 3],
-                    slot_index_int: None,
                     btc_txid: "txid1".to_string(),
                     revert_value: vec![4, 5, 6],
                     current_value: vec![7, 8, 9],
+                    metadata: Vec::new(),
+                    labels: Default::default(),
+                    alt_btc_txids: Vec::new(),
+                    required_confirmed_txids: 1,
+                    locked_by: None,
                 };
                 db_clone.insert_slot_lock(tx, &slot)
             })
@@ -590,14 +2050,19 @@ mod tests {
         // Try to lock the same slot in the main thread
         let _result = db.with_transaction(|tx| {
             let slot = SlotInsertData {
+                namespace: String::new(),
                 contract_address: "0x123".to_string(),
                 start_block: 101,
                 btc_block: 201,
                 slot_index: vec![1, 2, 3],
-                slot_index_int: None,
                 btc_txid: "txid2".to_string(),
                 revert_value: vec![5, 6, 7],
                 current_value: vec![8, 9, 10],
+                metadata: Vec::new(),
+                labels: Default::default(),
+                alt_btc_txids: Vec::new(),
+                required_confirmed_txids: 1,
+                locked_by: None,
             };
             db.insert_slot_lock(tx, &slot)
         });
@@ -606,7 +2071,7 @@ mod tests {
         handle.join().unwrap()?;
 
         // One of the operations should have failed due to the unique constraint
-        assert!(db.is_slot_locked("0x123", &[1, 2, 3])?);
+        assert!(db.is_slot_locked("", "0x123", &[1, 2, 3])?);
 
         Ok(())
     }
@@ -625,33 +2090,38 @@ mod tests {
         // Insert a slot lock
         db.with_transaction(|tx| {
             let slot = SlotInsertData {
+                namespace: String::new(),
                 contract_address: contract_addr.to_string(),
                 start_block,
                 btc_block,
                 slot_index: slot_index.clone(),
-                slot_index_int: None,
                 btc_txid: btc_txid.to_string(),
                 revert_value: revert_value.clone(),
                 current_value: current_value.clone(),
+                metadata: Vec::new(),
+                labels: Default::default(),
+                alt_btc_txids: Vec::new(),
+                required_confirmed_txids: 1,
+                locked_by: None,
             };
             db.insert_slot_lock(tx, &slot)
         })?;
 
         // Try to get slot at block 99 (before start_block)
-        let slot = db.get_slot(contract_addr, &slot_index, 99)?;
+        let slot = db.get_slot("", contract_addr, &slot_index, 99)?;
         assert!(
             slot.is_none(),
             "Slot should not be visible before start_block"
         );
 
         // Get slot at start_block
-        let slot = db.get_slot(contract_addr, &slot_index, start_block)?;
+        let slot = db.get_slot("", contract_addr, &slot_index, start_block)?;
         assert!(slot.is_some(), "Slot should be visible at start_block");
         let slot = slot.unwrap();
         assert_eq!(slot.start_block, start_block);
 
         // Get slot after start_block
-        let slot = db.get_slot(contract_addr, &slot_index, start_block + 1)?;
+        let slot = db.get_slot("", contract_addr, &slot_index, start_block + 1)?;
         assert!(slot.is_some(), "Slot should be visible after start_block");
         let slot = slot.unwrap();
         assert_eq!(slot.start_block, start_block);
@@ -674,25 +2144,35 @@ mod tests {
         // Insert two slot locks with the same start block
         db.with_transaction(|tx| {
             let slot1 = SlotInsertData {
+                namespace: String::new(),
                 contract_address: contract_addr.to_string(),
                 start_block,
                 btc_block,
                 slot_index: slot_index_1.clone(),
-                slot_index_int: None,
                 btc_txid: btc_txid.to_string(),
                 revert_value: revert_value.clone(),
                 current_value: current_value.clone(),
+                metadata: Vec::new(),
+                labels: Default::default(),
+                alt_btc_txids: Vec::new(),
+                required_confirmed_txids: 1,
+                locked_by: None,
             };
             db.insert_slot_lock(tx, &slot1)?;
             let slot2 = SlotInsertData {
+                namespace: String::new(),
                 contract_address: contract_addr.to_string(),
                 start_block,
                 btc_block,
                 slot_index: slot_index_2.clone(),
-                slot_index_int: None,
                 btc_txid: btc_txid.to_string(),
                 revert_value: revert_value.clone(),
                 current_value: current_value.clone(),
+                metadata: Vec::new(),
+                labels: Default::default(),
+                alt_btc_txids: Vec::new(),
+                required_confirmed_txids: 1,
+                locked_by: None,
             };
             db.insert_slot_lock(tx, &slot2)
         })?;
@@ -703,7 +2183,7 @@ mod tests {
         ];
 
         // Try to get slots at block 99 (before start_block)
-        let result = db.with_transaction(|tx| db.batch_get_locked_slots(tx, &slots, 99))?;
+        let result = db.with_transaction(|tx| db.batch_get_locked_slots(tx, "", &slots, 99))?;
         assert_eq!(result.len(), 2);
         assert!(
             result[0].is_none(),
@@ -716,7 +2196,7 @@ mod tests {
 
         // Get slots at start_block
         let result =
-            db.with_transaction(|tx| db.batch_get_locked_slots(tx, &slots, start_block))?;
+            db.with_transaction(|tx| db.batch_get_locked_slots(tx, "", &slots, start_block))?;
         assert_eq!(result.len(), 2);
         assert!(
             result[0].is_some(),
@@ -731,7 +2211,7 @@ mod tests {
 
         // Get slots after start_block
         let result =
-            db.with_transaction(|tx| db.batch_get_locked_slots(tx, &slots, start_block + 1))?;
+            db.with_transaction(|tx| db.batch_get_locked_slots(tx, "", &slots, start_block + 1))?;
         assert_eq!(result.len(), 2);
         assert!(
             result[0].is_some(),
@@ -744,6 +2224,476 @@ mod tests {
         assert_eq!(result[0].as_ref().unwrap().start_block, start_block);
         assert_eq!(result[1].as_ref().unwrap().start_block, start_block);
 
+        Ok(())
+    }
+    #[test]
+    fn test_describe_schema() -> Result<()> {
+        let db = setup_test_db()?;
+        assert_eq!(db.schema_version()?, SCHEMA_VERSION);
+
+        let tables = db.describe_schema()?;
+        let slot_locks = tables
+            .iter()
+            .find(|table| table.name == "slot_locks")
+            .expect("slot_locks table should be described");
+
+        let id = &slot_locks.columns[0];
+        assert_eq!(id.name, "id");
+        assert_eq!(id.data_type, "INTEGER");
+        assert!(id.primary_key);
+
+        let btc_txid = slot_locks
+            .columns
+            .iter()
+            .find(|column| column.name == "btc_txid")
+            .unwrap();
+        assert_eq!(btc_txid.data_type, "TEXT");
+        assert!(btc_txid.not_null);
+        assert!(!btc_txid.primary_key);
+
+        Ok(())
+    }
+    #[test]
+    fn test_recent_active_txids() -> Result<()> {
+        let db = setup_test_db()?;
+        let slots: Vec<SlotInsertData> = [(100, "txid1"), (102, "txid2"), (101, "txid3")]
+            .iter()
+            .enumerate()
+            .map(|(idx, (start_block, txid))| SlotInsertData {
+                namespace: String::new(),
+                contract_address: "0x123".to_string(),
+                start_block: *start_block,
+                btc_block: 200,
+                slot_index: vec![idx as u8],
+                btc_txid: txid.to_string(),
+                revert_value: vec![],
+                current_value: vec![],
+                metadata: Vec::new(),
+                labels: Default::default(),
+                alt_btc_txids: Vec::new(),
+                required_confirmed_txids: 1,
+                locked_by: None,
+            })
+            .collect();
+        db.with_transaction(|tx| db.batch_insert_slot_locks(tx, &slots))?;
+        db.unlock_slot("", "0x123", &[1], 150, UnlockReason::Confirmed)?;
+
+        // txid2 was unlocked, the others are ordered by start block
+        assert_eq!(db.recent_active_txids(10)?, vec!["txid3", "txid1"]);
+        assert_eq!(db.recent_active_txids(1)?, vec!["txid3"]);
+        assert_eq!(db.active_lock_count(None)?, 2);
+
+        Ok(())
+    }
+    #[test]
+    fn test_unlock_reason() -> Result<()> {
+        let db = setup_test_db()?;
+        let slots: Vec<SlotInsertData> = (0..3u8)
+            .map(|idx| SlotInsertData {
+                namespace: String::new(),
+                contract_address: "0x123".to_string(),
+                start_block: 100,
+                btc_block: 200,
+                slot_index: vec![idx],
+                btc_txid: "txid1".to_string(),
+                revert_value: vec![],
+                current_value: vec![],
+                metadata: Vec::new(),
+                labels: Default::default(),
+                alt_btc_txids: Vec::new(),
+                required_confirmed_txids: 1,
+                locked_by: None,
+            })
+            .collect();
+        db.with_transaction(|tx| {
+            db.batch_insert_slot_locks(tx, &slots)?;
+            db.batch_unlock_slots(tx, "", &[("0x123", &[0], 150)], UnlockReason::DoubleSpent)
+        })?;
+        db.unlock_slot("", "0x123", &[1], 150, UnlockReason::Confirmed)?;
+
+        let double_spent = db.get_slot("", "0x123", &[0], 150)?.unwrap();
+        assert_eq!(double_spent.unlock_reason, Some(UnlockReason::DoubleSpent));
+        assert_eq!(double_spent.end_block, Some(150));
+
+        let confirmed = db.get_slot("", "0x123", &[1], 150)?.unwrap();
+        assert_eq!(confirmed.unlock_reason, Some(UnlockReason::Confirmed));
+
+        let active = db.get_slot("", "0x123", &[2], 150)?.unwrap();
+        assert_eq!(active.unlock_reason, None);
+        assert_eq!(active.end_block, None);
+
+        Ok(())
+    }
+    #[test]
+    fn test_backfill_unlock_reason() -> Result<()> {
+        let db = setup_test_db()?;
+        db.with_transaction(|tx| {
+            db.batch_insert_slot_locks(
+                tx,
+                &[SlotInsertData {
+                    namespace: String::new(),
+                    contract_address: "0x123".to_string(),
+                    start_block: 100,
+                    btc_block: 200,
+                    slot_index: vec![1],
+                    btc_txid: "txid1".to_string(),
+                    revert_value: vec![],
+                    current_value: vec![],
+                    metadata: Vec::new(),
+                    labels: Default::default(),
+                    alt_btc_txids: Vec::new(),
+                    required_confirmed_txids: 1,
+                    locked_by: None,
+                }],
+            )?;
+            // An unlock from before reasons were recorded
+            tx.execute("UPDATE slot_locks SET end_block = 150", [])?;
+            Ok(())
+        })?;
+
+        let reason = db.backfill_unlock_reason("", "0x123", &[1], 150, UnlockReason::Confirmed)?;
+        assert_eq!(reason, UnlockReason::Confirmed);
+        // The first backfilled reason sticks
+        let reason =
+            db.backfill_unlock_reason("", "0x123", &[1], 150, UnlockReason::RevertThreshold)?;
+        assert_eq!(reason, UnlockReason::Confirmed);
+        assert_eq!(
+            db.get_slot("", "0x123", &[1], 150)?.unwrap().unlock_reason,
+            Some(UnlockReason::Confirmed)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lock_events() -> Result<()> {
+        let db = setup_test_db()?;
+        let slot = |start_block: u64| SlotInsertData {
+            namespace: String::new(),
+            contract_address: "0x123".to_string(),
+            start_block,
+            btc_block: 200,
+            slot_index: vec![1],
+            btc_txid: "txid1".to_string(),
+            revert_value: vec![],
+            current_value: vec![],
+            metadata: Vec::new(),
+            labels: Default::default(),
+            alt_btc_txids: Vec::new(),
+            required_confirmed_txids: 1,
+            locked_by: None,
+        };
+        db.with_transaction(|tx| db.batch_insert_slot_locks(tx, &[slot(100)]))?;
+        db.unlock_slot("", "0x123", &[1], 105, UnlockReason::Confirmed)?;
+        // Locked again and unlocked within block 105
+        db.with_transaction(|tx| db.batch_insert_slot_locks(tx, &[slot(105)]))?;
+        db.unlock_slot("", "0x123", &[1], 105, UnlockReason::Manual)?;
+
+        let events = db.lock_events(None, 100, 105, None, 10)?;
+        let summary: Vec<_> = events
+            .iter()
+            .map(|event| (event.cursor.block, event.kind, event.lock.start_block))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (100, LockEventKind::Locked, 100),
+                (105, LockEventKind::Unlocked, 100),
+                (105, LockEventKind::Locked, 105),
+                (105, LockEventKind::Unlocked, 105),
+            ]
+        );
+
+        // Paging resumes after the cursor, and the range bounds are inclusive
+        let page = db.lock_events(None, 100, 105, Some(events[1].cursor), 2)?;
+        assert_eq!(page.len(), 2);
+        assert_eq!(page[0].cursor, events[2].cursor);
+        assert_eq!(db.lock_events(None, 101, 104, None, 10)?.len(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_locks() -> Result<()> {
+        let db = setup_test_db()?;
+        let slots: Vec<SlotInsertData> = [
+            ("0x123", vec![0xaa, 0xbb, 0xcc], vec![0x01]),
+            ("0x456", vec![0x02], vec![0x00, 0xbb, 0xcc]),
+            ("0x456", vec![0x03], vec![0x04]),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(
+            |(idx, (contract, revert_value, current_value))| SlotInsertData {
+                namespace: String::new(),
+                contract_address: contract.to_string(),
+                start_block: 100 + idx as u64,
+                btc_block: 200,
+                slot_index: vec![idx as u8],
+                btc_txid: "txid1".to_string(),
+                revert_value,
+                current_value,
+                metadata: vec![idx as u8],
+                labels: [("deposit".to_string(), idx.to_string())].into(),
+                alt_btc_txids: Vec::new(),
+                required_confirmed_txids: 1,
+                locked_by: None,
+            },
+        )
+        .collect();
+        db.with_transaction(|tx| db.batch_insert_slot_locks(tx, &slots))?;
+        db.unlock_slot("", "0x123", &[0], 150, UnlockReason::Confirmed)?;
+
+        let no_labels = BTreeMap::new();
+        let pattern = ValueQuery::Contains(vec![0xbb, 0xcc]);
+        let locks = db.search_locks(Some(&pattern), &no_labels, None, None, true, 10)?;
+        let contracts: Vec<_> = locks
+            .iter()
+            .map(|lock| lock.contract_address.as_str())
+            .collect();
+        assert_eq!(contracts, vec!["0x456", "0x123"]);
+        // Unlocked locks and other contracts can be excluded
+        assert_eq!(
+            db.search_locks(Some(&pattern), &no_labels, None, None, false, 10)?
+                .len(),
+            1
+        );
+        assert!(db
+            .search_locks(Some(&pattern), &no_labels, None, Some("0x789"), true, 10)?
+            .is_empty());
+        assert_eq!(
+            db.search_locks(Some(&pattern), &no_labels, None, None, true, 1)?
+                .len(),
+            1
+        );
+
+        let hash = ValueQuery::Sha256(sha256::Hash::hash(&[0x04]).to_byte_array());
+        let locks = db.search_locks(Some(&hash), &no_labels, None, Some("0x456"), true, 10)?;
+        assert_eq!(locks.len(), 1);
+        assert_eq!(locks[0].slot_index, vec![2]);
+
+        // Labels narrow a value search or replace it
+        let deposit = |id: &str| BTreeMap::from([("deposit".to_string(), id.to_string())]);
+        let locks = db.search_locks(Some(&pattern), &deposit("0"), None, None, true, 10)?;
+        assert_eq!(locks.len(), 1);
+        assert_eq!(locks[0].contract_address, "0x123");
+        let locks = db.search_locks(None, &deposit("2"), None, None, true, 10)?;
+        assert_eq!(locks.len(), 1);
+        assert_eq!(locks[0].metadata, vec![2]);
+        assert_eq!(locks[0].labels, deposit("2"));
+        assert!(db
+            .search_locks(None, &deposit("3"), None, None, true, 10)?
+            .is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lock_conflict_stats() -> Result<()> {
+        let db = setup_test_db()?;
+        db.with_transaction(|tx| db.record_lock_conflicts(tx, "", &["0xabc", "0x123", "0xabc"]))?;
+        db.with_transaction(|tx| db.record_lock_conflicts(tx, "", &["0xabc"]))?;
+
+        let stats = db.lock_conflict_stats(None, None)?;
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats[0].contract_address, "0xabc");
+        assert_eq!(stats[0].conflicts, 3);
+        assert_eq!(stats[1].contract_address, "0x123");
+        assert_eq!(stats[1].conflicts, 1);
+
+        let stats = db.lock_conflict_stats(None, Some("0x123"))?;
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].conflicts, 1);
+        assert!(db.lock_conflict_stats(None, Some("0x456"))?.is_empty());
+
+        Ok(())
+    }
+    #[test]
+    fn test_replace_lock_txid() -> Result<()> {
+        let db = setup_test_db()?;
+        let slots: Vec<SlotInsertData> = (0..3u8)
+            .map(|idx| SlotInsertData {
+                namespace: String::new(),
+                contract_address: "0x123".to_string(),
+                start_block: 100,
+                btc_block: 200,
+                slot_index: vec![idx],
+                btc_txid: if idx < 2 { "txid1" } else { "txid2" }.to_string(),
+                revert_value: vec![],
+                current_value: vec![],
+                metadata: Vec::new(),
+                labels: Default::default(),
+                alt_btc_txids: Vec::new(),
+                required_confirmed_txids: 1,
+                locked_by: None,
+            })
+            .collect();
+        db.with_transaction(|tx| db.batch_insert_slot_locks(tx, &slots))?;
+        db.unlock_slot("", "0x123", &[1], 150, UnlockReason::Confirmed)?;
+
+        // Only the active lock on txid1 moves to the replacement
+        let updated =
+            db.with_transaction(|tx| db.replace_lock_txid(tx, "", "txid1", "txid3", false))?;
+        assert_eq!(updated, 1);
+        assert_eq!(
+            db.get_slot("", "0x123", &[0], 150)?.unwrap().btc_txid,
+            "txid3"
+        );
+        assert_eq!(
+            db.get_slot("", "0x123", &[1], 150)?.unwrap().btc_txid,
+            "txid1"
+        );
+        assert_eq!(
+            db.get_slot("", "0x123", &[2], 150)?.unwrap().btc_txid,
+            "txid2"
+        );
+
+        let updated =
+            db.with_transaction(|tx| db.replace_lock_txid(tx, "", "unknown", "txid4", true))?;
+        assert_eq!(updated, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lock_transactions() -> Result<()> {
+        let db = setup_test_db()?;
+        let slots: Vec<SlotInsertData> = (0..2u8)
+            .map(|idx| SlotInsertData {
+                namespace: String::new(),
+                contract_address: "0x123".to_string(),
+                start_block: 100,
+                btc_block: 200,
+                slot_index: vec![idx],
+                btc_txid: format!("txid{}", idx),
+                revert_value: vec![],
+                current_value: vec![],
+                metadata: Vec::new(),
+                labels: Default::default(),
+                alt_btc_txids: Vec::new(),
+                required_confirmed_txids: 1,
+                locked_by: None,
+            })
+            .collect();
+        db.with_transaction(|tx| {
+            db.batch_insert_slot_locks(tx, &slots)?;
+            db.insert_lock_transaction(tx, "txid0", &[0xaa])?;
+            db.insert_lock_transaction(tx, "txid1", &[0xbb])
+        })?;
+        assert_eq!(
+            db.pending_lock_transactions()?,
+            vec![
+                ("txid0".to_string(), vec![0xaa]),
+                ("txid1".to_string(), vec![0xbb])
+            ]
+        );
+
+        // Once its lock is released, a transaction is no longer rebroadcast
+        db.unlock_slot("", "0x123", &[0], 150, UnlockReason::Confirmed)?;
+        assert_eq!(
+            db.pending_lock_transactions()?,
+            vec![("txid1".to_string(), vec![0xbb])]
+        );
+        assert_eq!(db.prune_lock_transactions()?, 1);
+        assert_eq!(db.prune_lock_transactions()?, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_revert_deliveries() -> Result<()> {
+        let db = setup_test_db()?;
+        let slot = LockedSlot {
+            namespace: String::new(),
+            btc_txid: "txid1".to_string(),
+            btc_block: 200,
+            contract_address: "0x123".to_string(),
+            slot_index: vec![1],
+            revert_value: vec![4],
+            current_value: vec![7],
+            start_block: 100,
+            end_block: None,
+            unlock_reason: None,
+            metadata: Vec::new(),
+            labels: Default::default(),
+            lock_group: None,
+            alt_btc_txids: Vec::new(),
+            required_confirmed_txids: 1,
+            locked_at: None,
+            locked_by: None,
+        };
+        db.with_transaction(|tx| {
+            db.enqueue_revert_deliveries(tx, &[&slot, &slot], 150, UnlockReason::RevertThreshold)
+        })?;
+
+        let pending = db.pending_revert_deliveries(10)?;
+        assert_eq!(pending.len(), 2);
+        assert_eq!(pending[0].revert_value, vec![4]);
+        assert_eq!(pending[0].reverted_at_block, 150);
+
+        // The first delivery succeeds, the second fails until it runs out of attempts
+        db.record_revert_delivery(pending[0].id, None, 2)?;
+        db.record_revert_delivery(pending[1].id, Some("unreachable"), 2)?;
+        let pending = db.pending_revert_deliveries(10)?;
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].attempts, 1);
+
+        db.record_revert_delivery(pending[0].id, Some("unreachable"), 2)?;
+        assert!(db.pending_revert_deliveries(10)?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_import_lock_rows() -> Result<()> {
+        let db = setup_test_db()?;
+        let slots: Vec<SlotInsertData> = (0..3u8)
+            .map(|idx| SlotInsertData {
+                namespace: String::new(),
+                contract_address: "0x123".to_string(),
+                start_block: 100 + idx as u64,
+                btc_block: 200,
+                slot_index: vec![idx],
+                btc_txid: "txid1".to_string(),
+                revert_value: vec![idx],
+                current_value: vec![],
+                metadata: vec![idx],
+                labels: [("deposit".to_string(), idx.to_string())].into(),
+                alt_btc_txids: Vec::new(),
+                required_confirmed_txids: 1,
+                locked_by: None,
+            })
+            .collect();
+        db.with_transaction(|tx| db.batch_insert_slot_locks(tx, &slots))?;
+        db.unlock_slot("", "0x123", &[0], 150, UnlockReason::DoubleSpent)?;
+
+        let rows = db.lock_rows(None, 0, 10)?;
+        assert_eq!(rows.len(), 3);
+        assert_eq!(db.lock_rows(None, rows[0].id, 1)?[0].id, rows[1].id);
+        assert!(db.lock_rows(Some("testnet"), 0, 10)?.is_empty());
+
+        let imported_db = setup_test_db()?;
+        let imported =
+            imported_db.with_transaction(|tx| imported_db.import_lock_rows(tx, &rows))?;
+        assert_eq!(imported, vec![true; 3]);
+        let imported_rows = imported_db.lock_rows(None, 0, 10)?;
+        for (row, imported_row) in rows.iter().zip(&imported_rows) {
+            assert_eq!(row.lock, imported_row.lock);
+            assert_eq!(row.created_at, imported_row.created_at);
+        }
+
+        // Importing again skips every row
+        let imported =
+            imported_db.with_transaction(|tx| imported_db.import_lock_rows(tx, &rows))?;
+        assert_eq!(imported, vec![false; 3]);
+
+        // An active lock on a slot that is already locked is skipped too
+        let mut conflicting = rows[1].clone();
+        conflicting.lock.btc_txid = "txid2".to_string();
+        let imported =
+            imported_db.with_transaction(|tx| imported_db.import_lock_rows(tx, &[conflicting]))?;
+        assert_eq!(imported, vec![false]);
+        assert_eq!(imported_db.lock_rows(None, 0, 10)?.len(), 3);
+
         Ok(())
     }
 }
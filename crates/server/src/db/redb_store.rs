@@ -0,0 +1,1310 @@
+//! [`SlotStore`] kept in a redb file. Locks are stored by id with ordered indexes of the active
+//! lock of each slot, the locks of each slot and the active locks of each transaction and caller,
+//! so a lock,
+//! unlock or status query of a slot is a few B-tree lookups whatever the number of slots in the
+//! batch or in the store. Reads run on a snapshot without waiting for the writer; writers are
+//! still serialized.
+
+use super::{LockRow, LockedSlot, SlotInsertData, SlotStore, UnlockReason};
+use crate::backup::utc_date_time;
+use anyhow::{Context, Result};
+use redb::{ReadableTable, ReadableTableMetadata, TableDefinition, WriteTransaction};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Version of the layout of the tables below, bumped when it changes incompatibly
+pub const REDB_SCHEMA_VERSION: u32 = 2;
+
+/// (namespace, contract address, slot index)
+type SlotKey = (&'static str, &'static str, &'static [u8]);
+/// (namespace, contract address, slot index, id)
+type SlotIdKey = (&'static str, &'static str, &'static [u8], u64);
+
+/// Every lock, active and unlocked, by id
+const LOCKS: TableDefinition<u64, &[u8]> = TableDefinition::new("locks");
+/// Id of the active lock of each slot
+const ACTIVE_LOCKS: TableDefinition<SlotKey, u64> = TableDefinition::new("active_locks");
+/// Ids of every lock of each slot
+const SLOT_LOCKS: TableDefinition<SlotIdKey, ()> = TableDefinition::new("slot_locks");
+/// Ids of the active locks on each transaction, as (namespace, btc_txid, id)
+const TXID_LOCKS: TableDefinition<(&str, &str, u64), ()> = TableDefinition::new("txid_locks");
+/// Ids of the active locks made by each caller, as (caller, id)
+const CALLER_LOCKS: TableDefinition<(&str, u64), ()> = TableDefinition::new("caller_locks");
+/// Expiry and number of the slots each caller holds in each reservation, by (caller, reservation
+/// id)
+const CALLER_RESERVATIONS: TableDefinition<(&str, u64), (u64, u64)> =
+    TableDefinition::new("caller_reservations");
+/// Ids of the locks of each atomic group, as (group, id)
+const GROUP_LOCKS: TableDefinition<(u64, u64), ()> = TableDefinition::new("group_locks");
+/// Reservations by id
+const RESERVATIONS: TableDefinition<u64, &[u8]> = TableDefinition::new("reservations");
+/// Expiry of the reservations holding each slot, keyed by slot and reservation id
+const RESERVED_SLOTS: TableDefinition<SlotIdKey, u64> = TableDefinition::new("reserved_slots");
+/// Ids of the reservations, as (expires_at, id)
+const RESERVATION_EXPIRIES: TableDefinition<(u64, u64), ()> =
+    TableDefinition::new("reservation_expiries");
+/// Rejected lock attempts and the unix time of the last one, by (namespace, contract address)
+const LOCK_CONFLICTS: TableDefinition<(&str, &str), (u64, u64)> =
+    TableDefinition::new("lock_conflicts");
+/// Raw Bitcoin transactions of locks by txid
+const LOCK_TRANSACTIONS: TableDefinition<&str, &[u8]> = TableDefinition::new("lock_transactions");
+/// Recorded transaction replacements by id
+const TX_REPLACEMENTS: TableDefinition<u64, &[u8]> = TableDefinition::new("tx_replacements");
+/// Reverts queued for delivery by id
+const REVERT_DELIVERIES: TableDefinition<u64, &[u8]> = TableDefinition::new("revert_deliveries");
+/// Schema version and id sequences
+const META: TableDefinition<&str, u64> = TableDefinition::new("meta");
+
+const SCHEMA_VERSION_KEY: &str = "schema_version";
+const NEXT_LOCK_ID: &str = "next_lock_id";
+const NEXT_RESERVATION_ID: &str = "next_reservation_id";
+const NEXT_REPLACEMENT_ID: &str = "next_replacement_id";
+const NEXT_DELIVERY_ID: &str = "next_delivery_id";
+
+/// A lock as stored in [`LOCKS`], and a reserved slot as stored in a reservation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoredLock {
+    namespace: String,
+    btc_txid: String,
+    btc_block: u64,
+    contract_address: String,
+    #[serde(with = "hex")]
+    slot_index: Vec<u8>,
+    #[serde(with = "hex")]
+    revert_value: Vec<u8>,
+    #[serde(with = "hex")]
+    current_value: Vec<u8>,
+    start_block: u64,
+    end_block: Option<u64>,
+    unlock_reason: Option<String>,
+    #[serde(with = "hex")]
+    metadata: Vec<u8>,
+    labels: BTreeMap<String, String>,
+    lock_group: Option<i64>,
+    alt_btc_txids: Vec<String>,
+    required_confirmed_txids: u32,
+    /// Unix time the lock was made at
+    created_at: u64,
+    /// Absent from the locks stored before callers were recorded
+    #[serde(default)]
+    locked_by: Option<String>,
+}
+
+impl StoredLock {
+    fn new(slot: &SlotInsertData, created_at: u64) -> Self {
+        Self {
+            namespace: slot.namespace.clone(),
+            btc_txid: slot.btc_txid.clone(),
+            btc_block: slot.btc_block,
+            contract_address: slot.contract_address.clone(),
+            slot_index: slot.slot_index.clone(),
+            revert_value: slot.revert_value.clone(),
+            current_value: slot.current_value.clone(),
+            start_block: slot.start_block,
+            end_block: None,
+            unlock_reason: None,
+            metadata: slot.metadata.clone(),
+            labels: slot.labels.clone(),
+            lock_group: None,
+            alt_btc_txids: slot.alt_btc_txids.clone(),
+            required_confirmed_txids: slot.required_confirmed_txids,
+            created_at,
+            locked_by: slot.locked_by.clone(),
+        }
+    }
+
+    fn locked_slot(&self) -> Result<LockedSlot> {
+        Ok(LockedSlot {
+            namespace: self.namespace.clone(),
+            btc_txid: self.btc_txid.clone(),
+            btc_block: self.btc_block,
+            contract_address: self.contract_address.clone(),
+            slot_index: self.slot_index.clone(),
+            revert_value: self.revert_value.clone(),
+            current_value: self.current_value.clone(),
+            start_block: self.start_block,
+            end_block: self.end_block,
+            unlock_reason: self.unlock_reason.as_deref().map(str::parse).transpose()?,
+            metadata: self.metadata.clone(),
+            labels: self.labels.clone(),
+            lock_group: self.lock_group,
+            alt_btc_txids: self.alt_btc_txids.clone(),
+            required_confirmed_txids: self.required_confirmed_txids,
+            locked_at: Some(self.created_at),
+            locked_by: self.locked_by.clone(),
+        })
+    }
+
+    fn insert_data(self) -> SlotInsertData {
+        SlotInsertData {
+            namespace: self.namespace,
+            contract_address: self.contract_address,
+            start_block: self.start_block,
+            btc_block: self.btc_block,
+            slot_index: self.slot_index,
+            btc_txid: self.btc_txid,
+            revert_value: self.revert_value,
+            current_value: self.current_value,
+            metadata: self.metadata,
+            labels: self.labels,
+            alt_btc_txids: self.alt_btc_txids,
+            required_confirmed_txids: self.required_confirmed_txids,
+            locked_by: self.locked_by,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredReservation {
+    namespace: String,
+    expires_at: u64,
+    slots: Vec<StoredLock>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredReplacement {
+    namespace: String,
+    old_btc_txid: String,
+    new_btc_txid: String,
+    locks_updated: usize,
+    detected: bool,
+    created_at: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredRevertDelivery {
+    namespace: String,
+    contract_address: String,
+    #[serde(with = "hex")]
+    slot_index: Vec<u8>,
+    btc_txid: String,
+    #[serde(with = "hex")]
+    revert_value: Vec<u8>,
+    #[serde(with = "hex")]
+    current_value: Vec<u8>,
+    reverted_at_block: u64,
+    reason: String,
+    attempts: u32,
+}
+
+/// Embedded key-value backend of the slot lock service. Clones share the same database.
+#[derive(Clone)]
+pub struct RedbStore {
+    db: Arc<redb::Database>,
+}
+
+impl RedbStore {
+    /// Opens the store at `path`, creating it if it doesn't exist
+    pub fn open(path: &Path) -> Result<Self> {
+        let db = redb::Database::create(path)
+            .with_context(|| format!("Failed to open redb store {}", path.display()))?;
+        Self::new(db)
+    }
+
+    /// Opens a store kept in memory, lost when the last clone is dropped
+    pub fn in_memory() -> Result<Self> {
+        let db =
+            redb::Database::builder().create_with_backend(redb::backends::InMemoryBackend::new())?;
+        Self::new(db)
+    }
+
+    /// Creates the tables of a new store, or checks the schema version of an existing one
+    fn new(db: redb::Database) -> Result<Self> {
+        let transaction = db.begin_write()?;
+        {
+            transaction.open_table(LOCKS)?;
+            transaction.open_table(ACTIVE_LOCKS)?;
+            transaction.open_table(SLOT_LOCKS)?;
+            transaction.open_table(TXID_LOCKS)?;
+            transaction.open_table(CALLER_LOCKS)?;
+            transaction.open_table(CALLER_RESERVATIONS)?;
+            transaction.open_table(GROUP_LOCKS)?;
+            transaction.open_table(RESERVATIONS)?;
+            transaction.open_table(RESERVED_SLOTS)?;
+            transaction.open_table(RESERVATION_EXPIRIES)?;
+            transaction.open_table(LOCK_CONFLICTS)?;
+            transaction.open_table(LOCK_TRANSACTIONS)?;
+            transaction.open_table(TX_REPLACEMENTS)?;
+            transaction.open_table(REVERT_DELIVERIES)?;
+            let mut meta = transaction.open_table(META)?;
+            let version = meta.get(SCHEMA_VERSION_KEY)?.map(|version| version.value());
+            match version {
+                None => {
+                    meta.insert(SCHEMA_VERSION_KEY, u64::from(REDB_SCHEMA_VERSION))?;
+                }
+                Some(version) if version == u64::from(REDB_SCHEMA_VERSION) => {}
+                // Version 1 had no caller indexes
+                Some(1) => {
+                    index_callers(&transaction)?;
+                    meta.insert(SCHEMA_VERSION_KEY, u64::from(REDB_SCHEMA_VERSION))?;
+                }
+                Some(version) => anyhow::bail!(
+                    "redb store has schema version {}, this sentinel supports {}",
+                    version,
+                    REDB_SCHEMA_VERSION
+                ),
+            }
+        }
+        transaction.commit()?;
+        Ok(Self { db: Arc::new(db) })
+    }
+
+    /// Locks the slots that have no active lock, in order, returning whether each was locked.
+    /// The tables are opened once for the whole batch.
+    fn insert_locks(
+        &self,
+        transaction: &WriteTransaction,
+        slots: &[SlotInsertData],
+    ) -> Result<Vec<bool>> {
+        let mut meta = transaction.open_table(META)?;
+        let mut active = transaction.open_table(ACTIVE_LOCKS)?;
+        let mut locks = transaction.open_table(LOCKS)?;
+        let mut slot_locks = transaction.open_table(SLOT_LOCKS)?;
+        let mut txid_locks = transaction.open_table(TXID_LOCKS)?;
+        let mut caller_locks = transaction.open_table(CALLER_LOCKS)?;
+        let mut next = meta.get(NEXT_LOCK_ID)?.map_or(1, |id| id.value());
+        let created_at = unix_now();
+        let mut results = Vec::with_capacity(slots.len());
+        for slot in slots {
+            let key = (
+                slot.namespace.as_str(),
+                slot.contract_address.as_str(),
+                slot.slot_index.as_slice(),
+            );
+            if active.get(key)?.is_some() {
+                results.push(false);
+                continue;
+            }
+            let id = next;
+            next += 1;
+            active.insert(key, id)?;
+            locks.insert(
+                id,
+                serde_json::to_vec(&StoredLock::new(slot, created_at))?.as_slice(),
+            )?;
+            slot_locks.insert((key.0, key.1, key.2, id), ())?;
+            txid_locks.insert((key.0, slot.btc_txid.as_str(), id), ())?;
+            if let Some(caller) = &slot.locked_by {
+                caller_locks.insert((caller.as_str(), id), ())?;
+            }
+            results.push(true);
+        }
+        meta.insert(NEXT_LOCK_ID, next)?;
+        Ok(results)
+    }
+
+    /// Ends the active locks of the slots of a namespace that have one, given as (contract
+    /// address, slot index)
+    fn unlock<'s>(
+        &self,
+        transaction: &WriteTransaction,
+        namespace: &str,
+        slots: impl IntoIterator<Item = (&'s str, &'s [u8])>,
+        end_block: u64,
+        reason: UnlockReason,
+    ) -> Result<()> {
+        let mut active = transaction.open_table(ACTIVE_LOCKS)?;
+        let mut locks = transaction.open_table(LOCKS)?;
+        let mut txid_locks = transaction.open_table(TXID_LOCKS)?;
+        let mut caller_locks = transaction.open_table(CALLER_LOCKS)?;
+        for (contract_address, slot_index) in slots {
+            let Some(id) = active
+                .remove((namespace, contract_address, slot_index))?
+                .map(|id| id.value())
+            else {
+                continue;
+            };
+            let mut lock = read_lock(&locks, id)?;
+            lock.end_block = Some(end_block);
+            lock.unlock_reason = Some(reason.as_str().to_string());
+            locks.insert(id, serde_json::to_vec(&lock)?.as_slice())?;
+            txid_locks.remove((namespace, lock.btc_txid.as_str(), id))?;
+            if let Some(caller) = &lock.locked_by {
+                caller_locks.remove((caller.as_str(), id))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Deletes a reservation with its slots, returning it if it existed
+    fn delete_reservation(
+        &self,
+        transaction: &WriteTransaction,
+        reservation_id: u64,
+    ) -> Result<Option<StoredReservation>> {
+        let Some(reservation) = transaction
+            .open_table(RESERVATIONS)?
+            .remove(reservation_id)?
+            .map(|reservation| serde_json::from_slice::<StoredReservation>(reservation.value()))
+            .transpose()?
+        else {
+            return Ok(None);
+        };
+        let mut reserved = transaction.open_table(RESERVED_SLOTS)?;
+        for slot in &reservation.slots {
+            reserved.remove((
+                reservation.namespace.as_str(),
+                slot.contract_address.as_str(),
+                slot.slot_index.as_slice(),
+                reservation_id,
+            ))?;
+        }
+        transaction
+            .open_table(RESERVATION_EXPIRIES)?
+            .remove((reservation.expires_at, reservation_id))?;
+        let mut caller_reservations = transaction.open_table(CALLER_RESERVATIONS)?;
+        for caller in reservation_callers(&reservation).keys() {
+            caller_reservations.remove((caller.as_str(), reservation_id))?;
+        }
+        Ok(Some(reservation))
+    }
+}
+
+/// Number of the slots of a reservation held by each caller
+fn reservation_callers(reservation: &StoredReservation) -> BTreeMap<String, u64> {
+    let mut callers = BTreeMap::new();
+    for caller in reservation
+        .slots
+        .iter()
+        .filter_map(|slot| slot.locked_by.clone())
+    {
+        *callers.entry(caller).or_default() += 1;
+    }
+    callers
+}
+
+/// Fills the caller indexes from the active locks and reservations of a store written before
+/// they existed
+fn index_callers(transaction: &WriteTransaction) -> Result<()> {
+    let locks = transaction.open_table(LOCKS)?;
+    let mut caller_locks = transaction.open_table(CALLER_LOCKS)?;
+    for entry in transaction.open_table(ACTIVE_LOCKS)?.iter()? {
+        let id = entry?.1.value();
+        if let Some(caller) = read_lock(&locks, id)?.locked_by {
+            caller_locks.insert((caller.as_str(), id), ())?;
+        }
+    }
+    let mut caller_reservations = transaction.open_table(CALLER_RESERVATIONS)?;
+    for entry in transaction.open_table(RESERVATIONS)?.iter()? {
+        let entry = entry?;
+        let reservation: StoredReservation = serde_json::from_slice(entry.1.value())?;
+        for (caller, slots) in reservation_callers(&reservation) {
+            caller_reservations.insert(
+                (caller.as_str(), entry.0.value()),
+                (reservation.expires_at, slots),
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// Formats a unix time like SQLite's `CURRENT_TIMESTAMP`
+fn sqlite_timestamp(secs: u64) -> String {
+    let (year, month, day, hour, minute, second) = utc_date_time(secs);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year, month, day, hour, minute, second
+    )
+}
+
+/// Takes the next id of a sequence, starting at 1
+fn next_id(transaction: &WriteTransaction, sequence: &str) -> Result<u64> {
+    let mut meta = transaction.open_table(META)?;
+    let id = meta.get(sequence)?.map_or(1, |id| id.value());
+    meta.insert(sequence, id + 1)?;
+    Ok(id)
+}
+
+fn read_lock(locks: &impl ReadableTable<u64, &'static [u8]>, id: u64) -> Result<StoredLock> {
+    let lock = locks
+        .get(id)?
+        .with_context(|| format!("Lock {} is indexed but missing", id))?;
+    Ok(serde_json::from_slice(lock.value())?)
+}
+
+fn write_lock(transaction: &WriteTransaction, id: u64, lock: &StoredLock) -> Result<()> {
+    transaction
+        .open_table(LOCKS)?
+        .insert(id, serde_json::to_vec(lock)?.as_slice())?;
+    Ok(())
+}
+
+/// Returns the ids of every lock of a slot, oldest first
+fn slot_lock_ids(
+    slot_locks: &impl ReadableTable<SlotIdKey, ()>,
+    (namespace, contract_address, slot_index): (&str, &str, &[u8]),
+) -> Result<Vec<u64>> {
+    slot_locks
+        .range(
+            (namespace, contract_address, slot_index, 0)
+                ..=(namespace, contract_address, slot_index, u64::MAX),
+        )?
+        .map(|entry| Ok(entry?.0.value().3))
+        .collect()
+}
+
+/// Returns the locks of a slot visible at `current_block`, oldest first: the active lock and the
+/// locks unlocked in `current_block`, if they started at or before it
+fn visible_locks(
+    slot_locks: &impl ReadableTable<SlotIdKey, ()>,
+    locks: &impl ReadableTable<u64, &'static [u8]>,
+    key: (&str, &str, &[u8]),
+    current_block: u64,
+) -> Result<Vec<(u64, StoredLock)>> {
+    let mut visible = Vec::new();
+    for id in slot_lock_ids(slot_locks, key)? {
+        let lock = read_lock(locks, id)?;
+        if lock.start_block <= current_block
+            && lock
+                .end_block
+                .is_none_or(|end_block| end_block == current_block)
+        {
+            visible.push((id, lock));
+        }
+    }
+    Ok(visible)
+}
+
+/// [`SlotStore::get_slot`] against open tables: the earliest started visible lock, the most
+/// recently made one among those that started together
+fn find_slot(
+    slot_locks: &impl ReadableTable<SlotIdKey, ()>,
+    locks: &impl ReadableTable<u64, &'static [u8]>,
+    key: (&str, &str, &[u8]),
+    current_block: u64,
+) -> Result<Option<LockedSlot>> {
+    visible_locks(slot_locks, locks, key, current_block)?
+        .into_iter()
+        .min_by_key(|(id, lock)| (lock.start_block, std::cmp::Reverse(*id)))
+        .map(|(_, lock)| lock.locked_slot())
+        .transpose()
+}
+
+/// Returns the active locks of a namespace as (contract address, slot index, id), ordered by
+/// contract address and slot index
+fn namespace_active_locks(
+    active: &impl ReadableTable<SlotKey, u64>,
+    namespace: &str,
+) -> Result<Vec<(String, Vec<u8>, u64)>> {
+    let mut locks = Vec::new();
+    for entry in active.range((namespace, "", [].as_slice())..)? {
+        let (key, id) = entry?;
+        let (key_namespace, contract_address, slot_index) = key.value();
+        if key_namespace != namespace {
+            break;
+        }
+        locks.push((
+            contract_address.to_string(),
+            slot_index.to_vec(),
+            id.value(),
+        ));
+    }
+    Ok(locks)
+}
+
+impl SlotStore for RedbStore {
+    type Transaction<'t> = WriteTransaction;
+
+    fn with_transaction<F, T>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&Self::Transaction<'_>) -> Result<T>,
+    {
+        let transaction = self.db.begin_write()?;
+        let result = f(&transaction)?;
+        transaction.commit()?;
+        Ok(result)
+    }
+
+    fn with_rolled_back_transaction<F, T>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&Self::Transaction<'_>) -> Result<T>,
+    {
+        let transaction = self.db.begin_write()?;
+        let result = f(&transaction)?;
+        transaction.abort()?;
+        Ok(result)
+    }
+
+    fn schema_version(&self) -> Result<u32> {
+        let transaction = self.db.begin_read()?;
+        let version = transaction
+            .open_table(META)?
+            .get(SCHEMA_VERSION_KEY)?
+            .context("redb store has no schema version")?
+            .value();
+        Ok(u32::try_from(version)?)
+    }
+
+    fn is_slot_locked_with_transaction(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        namespace: &str,
+        contract_address: &str,
+        slot_index: &[u8],
+    ) -> Result<bool> {
+        Ok(transaction
+            .open_table(ACTIVE_LOCKS)?
+            .get((namespace, contract_address, slot_index))?
+            .is_some())
+    }
+
+    fn insert_slot_lock(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        slot: &SlotInsertData,
+    ) -> Result<()> {
+        // The active lock index holds one lock per slot, so unlike SQLite this can't double-lock
+        if !self.insert_locks(transaction, std::slice::from_ref(slot))?[0] {
+            anyhow::bail!(
+                "Slot {} of {} already has an active lock",
+                hex::encode(&slot.slot_index),
+                slot.contract_address
+            );
+        }
+        Ok(())
+    }
+
+    fn batch_insert_slot_locks(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        slots: &[SlotInsertData],
+    ) -> Result<Vec<bool>> {
+        self.insert_locks(transaction, slots)
+    }
+
+    fn get_slot(
+        &self,
+        namespace: &str,
+        contract_address: &str,
+        slot_index: &[u8],
+        current_block: u64,
+    ) -> Result<Option<LockedSlot>> {
+        let transaction = self.db.begin_read()?;
+        find_slot(
+            &transaction.open_table(SLOT_LOCKS)?,
+            &transaction.open_table(LOCKS)?,
+            (namespace, contract_address, slot_index),
+            current_block,
+        )
+    }
+
+    fn get_slot_with_transaction(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        namespace: &str,
+        contract_address: &str,
+        slot_index: &[u8],
+        current_block: u64,
+    ) -> Result<Option<LockedSlot>> {
+        find_slot(
+            &transaction.open_table(SLOT_LOCKS)?,
+            &transaction.open_table(LOCKS)?,
+            (namespace, contract_address, slot_index),
+            current_block,
+        )
+    }
+
+    fn batch_get_locked_slots(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        namespace: &str,
+        slots: &[(&str, &[u8])],
+        current_block: u64,
+    ) -> Result<Vec<Option<LockedSlot>>> {
+        let slot_locks = transaction.open_table(SLOT_LOCKS)?;
+        let locks = transaction.open_table(LOCKS)?;
+        slots
+            .iter()
+            .map(|&(contract_address, slot_index)| {
+                // The most recently made visible lock, like the SQLite backend's
+                visible_locks(
+                    &slot_locks,
+                    &locks,
+                    (namespace, contract_address, slot_index),
+                    current_block,
+                )?
+                .pop()
+                .map(|(_, lock)| lock.locked_slot())
+                .transpose()
+            })
+            .collect()
+    }
+
+    fn unlock_slot_with_transaction(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        namespace: &str,
+        contract_address: &str,
+        slot_index: &[u8],
+        end_block: u64,
+        reason: UnlockReason,
+    ) -> Result<()> {
+        self.unlock(
+            transaction,
+            namespace,
+            [(contract_address, slot_index)],
+            end_block,
+            reason,
+        )
+    }
+
+    fn batch_unlock_slots(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        namespace: &str,
+        slots: &[(&str, &[u8], u64)],
+        reason: UnlockReason,
+    ) -> Result<()> {
+        // Like the SQLite backend, every slot is unlocked at the first slot's end block
+        let Some(&(_, _, end_block)) = slots.first() else {
+            return Ok(());
+        };
+        self.unlock(
+            transaction,
+            namespace,
+            slots
+                .iter()
+                .map(|&(contract_address, slot_index, _)| (contract_address, slot_index)),
+            end_block,
+            reason,
+        )
+    }
+
+    fn backfill_unlock_reason(
+        &self,
+        namespace: &str,
+        contract_address: &str,
+        slot_index: &[u8],
+        end_block: u64,
+        reason: UnlockReason,
+    ) -> Result<UnlockReason> {
+        self.with_transaction(|transaction| {
+            let ids = slot_lock_ids(
+                &transaction.open_table(SLOT_LOCKS)?,
+                (namespace, contract_address, slot_index),
+            )?;
+            let mut stored = None;
+            for id in ids {
+                let mut lock = read_lock(&transaction.open_table(LOCKS)?, id)?;
+                if lock.end_block != Some(end_block) {
+                    continue;
+                }
+                if lock.unlock_reason.is_none() {
+                    lock.unlock_reason = Some(reason.as_str().to_string());
+                    write_lock(transaction, id, &lock)?;
+                }
+                stored = stored.or(lock.unlock_reason);
+            }
+            stored
+                .context("No lock of the slot was unlocked at the block")?
+                .parse()
+        })
+    }
+
+    fn active_lock_count(&self, namespace: Option<&str>) -> Result<u64> {
+        let transaction = self.db.begin_read()?;
+        let active = transaction.open_table(ACTIVE_LOCKS)?;
+        match namespace {
+            Some(namespace) => Ok(namespace_active_locks(&active, namespace)?.len() as u64),
+            None => Ok(active.len()?),
+        }
+    }
+
+    fn caller_active_locks(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        caller: &str,
+        now: u64,
+    ) -> Result<u64> {
+        let mut count = transaction
+            .open_table(CALLER_LOCKS)?
+            .range((caller, 0)..=(caller, u64::MAX))?
+            .count() as u64;
+        for entry in transaction
+            .open_table(CALLER_RESERVATIONS)?
+            .range((caller, 0)..=(caller, u64::MAX))?
+        {
+            let (expires_at, slots) = entry?.1.value();
+            if expires_at > now {
+                count += slots;
+            }
+        }
+        Ok(count)
+    }
+
+    fn active_lock_slots(
+        &self,
+        namespace: &str,
+        current_block: u64,
+    ) -> Result<Vec<(String, Vec<u8>)>> {
+        let transaction = self.db.begin_read()?;
+        let locks = transaction.open_table(LOCKS)?;
+        let mut slots = Vec::new();
+        for (contract_address, slot_index, id) in
+            namespace_active_locks(&transaction.open_table(ACTIVE_LOCKS)?, namespace)?
+        {
+            if read_lock(&locks, id)?.start_block <= current_block {
+                slots.push((contract_address, slot_index));
+            }
+        }
+        Ok(slots)
+    }
+
+    fn active_locks_by_txid(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        namespace: &str,
+        btc_txid: &str,
+    ) -> Result<Vec<LockedSlot>> {
+        let locks = transaction.open_table(LOCKS)?;
+        let mut by_txid = Vec::new();
+        for entry in transaction
+            .open_table(TXID_LOCKS)?
+            .range((namespace, btc_txid, 0)..=(namespace, btc_txid, u64::MAX))?
+        {
+            let id = entry?.0.value().2;
+            by_txid.push(read_lock(&locks, id)?.locked_slot()?);
+        }
+        Ok(by_txid)
+    }
+
+    fn replace_lock_txid(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        namespace: &str,
+        old_btc_txid: &str,
+        new_btc_txid: &str,
+        detected: bool,
+    ) -> Result<usize> {
+        let ids = {
+            let mut by_txid = transaction.open_table(TXID_LOCKS)?;
+            let ids = by_txid
+                .extract_from_if(
+                    (namespace, old_btc_txid, 0)..=(namespace, old_btc_txid, u64::MAX),
+                    |_, _| true,
+                )?
+                .map(|entry| Ok(entry?.0.value().2))
+                .collect::<Result<Vec<_>>>()?;
+            for &id in &ids {
+                by_txid.insert((namespace, new_btc_txid, id), ())?;
+            }
+            ids
+        };
+        for &id in &ids {
+            let mut lock = read_lock(&transaction.open_table(LOCKS)?, id)?;
+            lock.btc_txid = new_btc_txid.to_string();
+            write_lock(transaction, id, &lock)?;
+        }
+        if !ids.is_empty() {
+            let replacement = StoredReplacement {
+                namespace: namespace.to_string(),
+                old_btc_txid: old_btc_txid.to_string(),
+                new_btc_txid: new_btc_txid.to_string(),
+                locks_updated: ids.len(),
+                detected,
+                created_at: unix_now(),
+            };
+            let id = next_id(transaction, NEXT_REPLACEMENT_ID)?;
+            transaction
+                .open_table(TX_REPLACEMENTS)?
+                .insert(id, serde_json::to_vec(&replacement)?.as_slice())?;
+        }
+        Ok(ids.len())
+    }
+
+    fn slot_lock_rows(
+        &self,
+        namespace: &str,
+        contract_address: &str,
+        slot_index: &[u8],
+    ) -> Result<Vec<LockRow>> {
+        let transaction = self.db.begin_read()?;
+        let locks = transaction.open_table(LOCKS)?;
+        let mut rows = slot_lock_ids(
+            &transaction.open_table(SLOT_LOCKS)?,
+            (namespace, contract_address, slot_index),
+        )?
+        .into_iter()
+        .map(|id| {
+            let lock = read_lock(&locks, id)?;
+            Ok(LockRow {
+                id: id as i64,
+                lock: lock.locked_slot()?,
+                created_at: Some(sqlite_timestamp(lock.created_at)),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+        rows.sort_by_key(|row| (row.lock.start_block, row.id));
+        Ok(rows)
+    }
+
+    fn create_lock_group(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        namespace: &str,
+        slots: &[(&str, &[u8])],
+    ) -> Result<i64> {
+        let ids = {
+            let active = transaction.open_table(ACTIVE_LOCKS)?;
+            slots
+                .iter()
+                .map(|&(contract_address, slot_index)| {
+                    Ok(active
+                        .get((namespace, contract_address, slot_index))?
+                        .with_context(|| {
+                            format!(
+                                "Slot {} of {} has no active lock",
+                                hex::encode(slot_index),
+                                contract_address
+                            )
+                        })?
+                        .value())
+                })
+                .collect::<Result<Vec<u64>>>()?
+        };
+        // Lock ids are never reused, so neither are group ids
+        let group = ids
+            .iter()
+            .copied()
+            .min()
+            .context("A lock group needs a lock")?;
+        for &id in &ids {
+            let mut lock = read_lock(&transaction.open_table(LOCKS)?, id)?;
+            lock.lock_group = Some(group as i64);
+            write_lock(transaction, id, &lock)?;
+            transaction
+                .open_table(GROUP_LOCKS)?
+                .insert((group, id), ())?;
+        }
+        Ok(group as i64)
+    }
+
+    fn active_group_locks(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        groups: &[i64],
+    ) -> Result<Vec<LockedSlot>> {
+        let mut ids = Vec::new();
+        {
+            let group_locks = transaction.open_table(GROUP_LOCKS)?;
+            for &group in groups {
+                let group = group as u64;
+                for entry in group_locks.range((group, 0)..=(group, u64::MAX))? {
+                    ids.push(entry?.0.value().1);
+                }
+            }
+        }
+        ids.sort_unstable();
+        ids.dedup();
+        let locks = transaction.open_table(LOCKS)?;
+        let mut active = Vec::new();
+        for id in ids {
+            let lock = read_lock(&locks, id)?;
+            if lock.end_block.is_none() {
+                active.push(lock.locked_slot()?);
+            }
+        }
+        Ok(active)
+    }
+
+    fn reserve_slots(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        namespace: &str,
+        slots: &[SlotInsertData],
+        expires_at: u64,
+    ) -> Result<i64> {
+        let reservation_id = next_id(transaction, NEXT_RESERVATION_ID)?;
+        let reservation = StoredReservation {
+            namespace: namespace.to_string(),
+            expires_at,
+            slots: slots
+                .iter()
+                .map(|slot| StoredLock::new(slot, unix_now()))
+                .collect(),
+        };
+        {
+            let mut reserved = transaction.open_table(RESERVED_SLOTS)?;
+            for slot in slots {
+                reserved.insert(
+                    (
+                        namespace,
+                        slot.contract_address.as_str(),
+                        slot.slot_index.as_slice(),
+                        reservation_id,
+                    ),
+                    expires_at,
+                )?;
+            }
+        }
+        transaction
+            .open_table(RESERVATIONS)?
+            .insert(reservation_id, serde_json::to_vec(&reservation)?.as_slice())?;
+        transaction
+            .open_table(RESERVATION_EXPIRIES)?
+            .insert((expires_at, reservation_id), ())?;
+        let mut caller_reservations = transaction.open_table(CALLER_RESERVATIONS)?;
+        for (caller, slots) in reservation_callers(&reservation) {
+            caller_reservations.insert((caller.as_str(), reservation_id), (expires_at, slots))?;
+        }
+        Ok(reservation_id as i64)
+    }
+
+    fn reserved_slots(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        namespace: &str,
+        slots: &[(&str, &[u8])],
+        now: u64,
+    ) -> Result<Vec<bool>> {
+        let reserved = transaction.open_table(RESERVED_SLOTS)?;
+        slots
+            .iter()
+            .map(|&(contract_address, slot_index)| {
+                for entry in reserved.range(
+                    (namespace, contract_address, slot_index, 0)
+                        ..=(namespace, contract_address, slot_index, u64::MAX),
+                )? {
+                    if entry?.1.value() > now {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            })
+            .collect()
+    }
+
+    fn take_reservation(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        namespace: &str,
+        reservation_id: i64,
+        now: u64,
+    ) -> Result<Option<Vec<SlotInsertData>>> {
+        let Ok(reservation_id) = u64::try_from(reservation_id) else {
+            return Ok(None);
+        };
+        let found = transaction
+            .open_table(RESERVATIONS)?
+            .get(reservation_id)?
+            .map(|reservation| serde_json::from_slice::<StoredReservation>(reservation.value()))
+            .transpose()?
+            .is_some_and(|reservation| {
+                reservation.namespace == namespace && reservation.expires_at > now
+            });
+        if !found {
+            return Ok(None);
+        }
+        let reservation = self
+            .delete_reservation(transaction, reservation_id)?
+            .context("Reservation disappeared while it was taken")?;
+        Ok(Some(
+            reservation
+                .slots
+                .into_iter()
+                .map(|slot| SlotInsertData {
+                    namespace: namespace.to_string(),
+                    ..slot.insert_data()
+                })
+                .collect(),
+        ))
+    }
+
+    fn release_expired_reservations(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        now: u64,
+    ) -> Result<usize> {
+        let expired = transaction
+            .open_table(RESERVATION_EXPIRIES)?
+            .range(..=(now, u64::MAX))?
+            .map(|entry| Ok(entry?.0.value().1))
+            .collect::<Result<Vec<_>>>()?;
+        for &reservation_id in &expired {
+            self.delete_reservation(transaction, reservation_id)?;
+        }
+        Ok(expired.len())
+    }
+
+    fn record_lock_conflicts(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        namespace: &str,
+        contract_addresses: &[&str],
+    ) -> Result<()> {
+        let mut conflicts: HashMap<&str, u64> = HashMap::new();
+        for contract_address in contract_addresses {
+            *conflicts.entry(*contract_address).or_default() += 1;
+        }
+
+        let now = unix_now();
+        let mut table = transaction.open_table(LOCK_CONFLICTS)?;
+        for (contract_address, count) in conflicts {
+            let previous = table
+                .get((namespace, contract_address))?
+                .map_or(0, |stats| stats.value().0);
+            table.insert((namespace, contract_address), (previous + count, now))?;
+        }
+        Ok(())
+    }
+
+    fn insert_lock_transaction(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        btc_txid: &str,
+        raw_tx: &[u8],
+    ) -> Result<()> {
+        let mut table = transaction.open_table(LOCK_TRANSACTIONS)?;
+        if table.get(btc_txid)?.is_none() {
+            table.insert(btc_txid, raw_tx)?;
+        }
+        Ok(())
+    }
+
+    fn enqueue_revert_deliveries(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        slots: &[&LockedSlot],
+        reverted_at_block: u64,
+        reason: UnlockReason,
+    ) -> Result<()> {
+        for slot in slots {
+            let delivery = StoredRevertDelivery {
+                namespace: slot.namespace.clone(),
+                contract_address: slot.contract_address.clone(),
+                slot_index: slot.slot_index.clone(),
+                btc_txid: slot.btc_txid.clone(),
+                revert_value: slot.revert_value.clone(),
+                current_value: slot.current_value.clone(),
+                reverted_at_block,
+                reason: reason.as_str().to_string(),
+                attempts: 0,
+            };
+            let id = next_id(transaction, NEXT_DELIVERY_ID)?;
+            transaction
+                .open_table(REVERT_DELIVERIES)?
+                .insert(id, serde_json::to_vec(&delivery)?.as_slice())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn slot(slot_index: u8, btc_txid: &str, start_block: u64) -> SlotInsertData {
+        SlotInsertData {
+            namespace: String::new(),
+            contract_address: "0x123".to_string(),
+            start_block,
+            btc_block: 200,
+            slot_index: vec![slot_index],
+            btc_txid: btc_txid.to_string(),
+            revert_value: vec![4],
+            current_value: vec![7],
+            metadata: vec![9],
+            labels: [("deposit".to_string(), "7".to_string())].into(),
+            alt_btc_txids: vec!["txid9".to_string()],
+            required_confirmed_txids: 1,
+            locked_by: None,
+        }
+    }
+
+    #[test]
+    fn test_lock_operations() -> Result<()> {
+        let store = RedbStore::in_memory()?;
+        assert_eq!(store.schema_version()?, REDB_SCHEMA_VERSION);
+
+        let inserted = store.with_transaction(|tx| {
+            store.batch_insert_slot_locks(tx, &[slot(1, "txid1", 100), slot(2, "txid1", 100)])
+        })?;
+        assert_eq!(inserted, vec![true, true]);
+        let inserted = store.with_transaction(|tx| {
+            store.batch_insert_slot_locks(tx, &[slot(1, "txid2", 101), slot(3, "txid2", 101)])
+        })?;
+        assert_eq!(inserted, vec![false, true]);
+        assert!(store.get_slot("", "0x123", &[1], 99)?.is_none());
+        let lock = store.get_slot("", "0x123", &[1], 100)?.unwrap();
+        assert_eq!(lock.btc_txid, "txid1");
+        assert_eq!(lock.labels, slot(1, "", 0).labels);
+        assert!(store.get_slot("testnet", "0x123", &[1], 100)?.is_none());
+        assert_eq!(store.active_lock_count(None)?, 3);
+        assert_eq!(store.active_lock_count(Some("testnet"))?, 0);
+        assert_eq!(
+            store.active_lock_slots("", 100)?,
+            vec![
+                ("0x123".to_string(), vec![1]),
+                ("0x123".to_string(), vec![2])
+            ]
+        );
+
+        // A rolled back unlock leaves the lock active
+        store.with_rolled_back_transaction(|tx| {
+            store.batch_unlock_slots(tx, "", &[("0x123", &[1], 110)], UnlockReason::Manual)
+        })?;
+        assert_eq!(store.active_lock_count(None)?, 3);
+
+        store.with_transaction(|tx| {
+            store.batch_unlock_slots(
+                tx,
+                "",
+                &[("0x123", &[1], 110), ("0x123", &[2], 110)],
+                UnlockReason::Confirmed,
+            )
+        })?;
+        let unlocked = store.get_slot("", "0x123", &[1], 110)?.unwrap();
+        assert_eq!(unlocked.end_block, Some(110));
+        assert_eq!(unlocked.unlock_reason, Some(UnlockReason::Confirmed));
+        assert!(store.get_slot("", "0x123", &[1], 111)?.is_none());
+        assert_eq!(
+            store.backfill_unlock_reason("", "0x123", &[1], 110, UnlockReason::Manual)?,
+            UnlockReason::Confirmed
+        );
+
+        // Relocking in the unlock's block shows the new lock to batches
+        store.with_transaction(|tx| store.insert_slot_lock(tx, &slot(1, "txid3", 110)))?;
+        let locks = store.with_transaction(|tx| {
+            store.batch_get_locked_slots(tx, "", &[("0x123", &[1]), ("0x123", &[4])], 110)
+        })?;
+        assert_eq!(locks[0].as_ref().unwrap().btc_txid, "txid3");
+        assert!(locks[1].is_none());
+        let rows = store.slot_lock_rows("", "0x123", &[1])?;
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].lock.end_block, Some(110));
+        assert_eq!(rows[0].created_at.as_deref().map(str::len), Some(19));
+        Ok(())
+    }
+
+    #[test]
+    fn test_txids_groups_and_reservations() -> Result<()> {
+        let store = RedbStore::in_memory()?;
+        store.with_transaction(|tx| {
+            store.batch_insert_slot_locks(tx, &[slot(1, "txid1", 100), slot(2, "txid1", 100)])?;
+            assert_eq!(store.replace_lock_txid(tx, "", "txid1", "txid2", true)?, 2);
+            assert!(store.active_locks_by_txid(tx, "", "txid1")?.is_empty());
+            assert_eq!(store.active_locks_by_txid(tx, "", "txid2")?.len(), 2);
+
+            let group = store.create_lock_group(tx, "", &[("0x123", &[1]), ("0x123", &[2])])?;
+            assert!(store.create_lock_group(tx, "", &[("0x123", &[3])]).is_err());
+            store.unlock_slot_with_transaction(tx, "", "0x123", &[1], 110, UnlockReason::Admin)?;
+            let active = store.active_group_locks(tx, &[group])?;
+            assert_eq!(active.len(), 1);
+            assert_eq!(active[0].lock_group, Some(group));
+            Ok(())
+        })?;
+
+        let slots = [("0x123", [1u8].as_slice()), ("0x123", [2u8].as_slice())];
+        store.with_transaction(|tx| {
+            let expired = store.reserve_slots(tx, "", &[slot(1, "txid1", 100)], 1000)?;
+            let live = store.reserve_slots(tx, "", &[slot(1, "txid1", 100)], 2000)?;
+            assert_eq!(
+                store.reserved_slots(tx, "", &slots, 1500)?,
+                vec![true, false]
+            );
+            assert_eq!(
+                store.reserved_slots(tx, "testnet", &slots, 1500)?,
+                vec![false, false]
+            );
+            assert!(store.take_reservation(tx, "", expired, 1500)?.is_none());
+            assert_eq!(store.release_expired_reservations(tx, 1500)?, 1);
+
+            assert!(store.take_reservation(tx, "testnet", live, 1500)?.is_none());
+            let taken = store.take_reservation(tx, "", live, 1500)?.unwrap();
+            assert_eq!(taken[0].alt_btc_txids, vec!["txid9".to_string()]);
+            assert!(store.take_reservation(tx, "", live, 1500)?.is_none());
+            assert_eq!(
+                store.reserved_slots(tx, "", &slots, 1500)?,
+                vec![false, false]
+            );
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_caller_active_locks() -> Result<()> {
+        let store = RedbStore::in_memory()?;
+        let by = |slot_index: u8, caller: &str| SlotInsertData {
+            locked_by: Some(caller.to_string()),
+            ..slot(slot_index, "txid1", 100)
+        };
+        store.with_transaction(|tx| {
+            store.batch_insert_slot_locks(tx, &[by(1, "key:a"), by(2, "key:a"), by(3, "key:b")])?;
+            store.unlock_slot_with_transaction(tx, "", "0x123", &[2], 110, UnlockReason::Admin)?;
+            store.reserve_slots(tx, "", &[by(4, "key:a"), by(5, "key:b")], 2000)?;
+            store.reserve_slots(tx, "", &[by(6, "key:a")], 1000)?;
+
+            assert_eq!(store.caller_active_locks(tx, "key:a", 1500)?, 2);
+            assert_eq!(store.caller_active_locks(tx, "key:b", 1500)?, 2);
+            assert_eq!(store.caller_active_locks(tx, "key:c", 1500)?, 0);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_reopens_store() -> Result<()> {
+        let path =
+            std::env::temp_dir().join(format!("sova-sentinel-redb-{}.redb", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        {
+            let store = RedbStore::open(&path)?;
+            store.with_transaction(|tx| store.insert_slot_lock(tx, &slot(1, "txid1", 100)))?;
+        }
+        let store = RedbStore::open(&path)?;
+        assert!(store.get_slot("", "0x123", &[1], 100)?.is_some());
+        store.with_transaction(|tx| store.insert_slot_lock(tx, &slot(2, "txid1", 100)))?;
+        assert_eq!(store.slot_lock_rows("", "0x123", &[2])?[0].id, 2);
+        drop(store);
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_indexes_callers_of_version_1_stores() -> Result<()> {
+        let path =
+            std::env::temp_dir().join(format!("sova-sentinel-redb-v1-{}.redb", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let by = |slot_index: u8, caller: &str| SlotInsertData {
+            locked_by: Some(caller.to_string()),
+            ..slot(slot_index, "txid1", 100)
+        };
+        {
+            let store = RedbStore::open(&path)?;
+            store.with_transaction(|tx| {
+                store.batch_insert_slot_locks(tx, &[by(1, "key:a"), by(2, "key:a")])?;
+                store.reserve_slots(tx, "", &[by(3, "key:a")], 2000)?;
+                Ok(())
+            })?;
+        }
+        // Roll the store back to the layout without caller indexes
+        {
+            let db = redb::Database::create(&path)?;
+            let transaction = db.begin_write()?;
+            transaction.delete_table(CALLER_LOCKS)?;
+            transaction.delete_table(CALLER_RESERVATIONS)?;
+            transaction
+                .open_table(META)?
+                .insert(SCHEMA_VERSION_KEY, 1)?;
+            transaction.commit()?;
+        }
+
+        let store = RedbStore::open(&path)?;
+        assert_eq!(store.schema_version()?, REDB_SCHEMA_VERSION);
+        store.with_transaction(|tx| {
+            assert_eq!(store.caller_active_locks(tx, "key:a", 1500)?, 3);
+            Ok(())
+        })?;
+        drop(store);
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,233 @@
+//! Change log of the lock table tailed by standby sentinels, and the replication role and fencing
+//! epoch of this sentinel
+
+use super::{lock_row_from_row, Database, LockRow};
+use anyhow::Result;
+use rusqlite::OptionalExtension;
+
+/// Role of a sentinel in a primary/standby pair
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplicationRole {
+    /// Grants locks
+    Primary,
+    /// Tails a primary and rejects writes until promoted
+    Standby,
+    /// A former primary superseded by a promoted standby
+    Fenced,
+}
+
+impl ReplicationRole {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Primary => "primary",
+            Self::Standby => "standby",
+            Self::Fenced => "fenced",
+        }
+    }
+}
+
+impl std::str::FromStr for ReplicationRole {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "primary" => Ok(Self::Primary),
+            "standby" => Ok(Self::Standby),
+            "fenced" => Ok(Self::Fenced),
+            other => Err(anyhow::anyhow!("Unknown replication role: {}", other)),
+        }
+    }
+}
+
+/// Persisted replication state of the sentinel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReplicationState {
+    pub role: ReplicationRole,
+    pub epoch: u64,
+    /// Last change of the primary applied by a standby
+    pub applied_seq: u64,
+}
+
+/// Latest change of a lock
+#[derive(Debug, Clone)]
+pub struct LockChange {
+    pub seq: u64,
+    pub lock_id: i64,
+    /// The lock as stored after the change, `None` if it was deleted
+    pub lock: Option<ReplicatedLock>,
+    /// Instance id of the sentinel that made the change, if known
+    pub instance_id: Option<String>,
+}
+
+/// A row of the lock table with the bookkeeping columns replication copies along
+#[derive(Debug, Clone)]
+pub struct ReplicatedLock {
+    pub row: LockRow,
+    pub double_spent: bool,
+    pub updated_at: Option<String>,
+    pub archived_at: Option<String>,
+    pub restored_at: Option<String>,
+}
+
+impl Database {
+    /// Returns the latest change of each lock changed after `after_seq`, in sequence order, at
+    /// most `limit` of them
+    pub fn lock_changes(&self, after_seq: u64, limit: usize) -> Result<Vec<LockChange>> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare(
+            "SELECT s.btc_txid, s.btc_block, s.contract_address, s.slot_index, s.revert_value, s.current_value, s.start_block, s.end_block, s.unlock_reason, s.namespace, s.metadata, s.labels, s.lock_group, s.alt_btc_txids, s.required_confirmed_txids, s.id, s.created_at,
+                 s.double_spent, s.updated_at, s.archived_at, s.restored_at, c.seq, c.lock_id,
+                 CAST(strftime('%s', s.created_at) AS INTEGER) AS locked_at, s.locked_by,
+                 c.instance_id
+             FROM lock_changes c LEFT JOIN slot_locks s ON s.id = c.lock_id
+             WHERE c.seq > ?1
+             ORDER BY c.seq
+             LIMIT ?2",
+        )?;
+        let changes = stmt
+            .query_map(rusqlite::params![after_seq as i64, limit as i64], |row| {
+                let exists = row.get::<_, Option<i64>>(15)?.is_some();
+                let lock = if exists {
+                    Some(ReplicatedLock {
+                        row: lock_row_from_row(row)?,
+                        double_spent: row.get(17)?,
+                        updated_at: row.get(18)?,
+                        archived_at: row.get(19)?,
+                        restored_at: row.get(20)?,
+                    })
+                } else {
+                    None
+                };
+                Ok(LockChange {
+                    seq: row.get::<_, i64>(21)? as u64,
+                    lock_id: row.get(22)?,
+                    lock,
+                    instance_id: row.get(25)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(changes)
+    }
+
+    /// Returns the sequence number of the latest change of the lock table, 0 if there is none
+    pub fn last_lock_change_seq(&self) -> Result<u64> {
+        let conn = self.connection()?;
+        let seq: i64 = conn.query_row(
+            "SELECT COALESCE(MAX(seq), 0) FROM lock_changes",
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(seq as u64)
+    }
+
+    /// Applies changes read from a primary, replacing each lock with its changed row, and records
+    /// the last of them as applied. The lock change and revert count rows written for a change
+    /// are tagged with the instance of the primary that made it, not this one.
+    pub fn apply_lock_changes(&self, changes: &[LockChange]) -> Result<()> {
+        let Some(last) = changes.last() else {
+            return Ok(());
+        };
+        self.with_transaction(|transaction| {
+            for change in changes {
+                let Some(replicated) = &change.lock else {
+                    transaction
+                        .execute("DELETE FROM slot_locks WHERE id = ?1", [change.lock_id])?;
+                    tag_lock_change(transaction, change)?;
+                    continue;
+                };
+                let lock = &replicated.row.lock;
+                transaction.execute(
+                    "INSERT OR REPLACE INTO slot_locks (
+                        id, start_block, end_block, btc_block, contract_address, slot_index,
+                        btc_txid, revert_value, current_value, unlock_reason, namespace, metadata,
+                        labels, lock_group, alt_btc_txids, required_confirmed_txids, double_spent,
+                        created_at, updated_at, archived_at, restored_at, locked_by
+                    ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15,
+                        ?16, ?17, ?18, ?19, ?20, ?21, ?22)",
+                    rusqlite::params![
+                        change.lock_id,
+                        lock.start_block,
+                        lock.end_block,
+                        lock.btc_block,
+                        lock.contract_address,
+                        lock.slot_index,
+                        lock.btc_txid,
+                        lock.revert_value,
+                        lock.current_value,
+                        lock.unlock_reason,
+                        lock.namespace,
+                        lock.metadata,
+                        serde_json::to_string(&lock.labels)?,
+                        lock.lock_group,
+                        serde_json::to_string(&lock.alt_btc_txids)?,
+                        lock.required_confirmed_txids,
+                        replicated.double_spent,
+                        replicated.row.created_at,
+                        replicated.updated_at,
+                        replicated.archived_at,
+                        replicated.restored_at,
+                        lock.locked_by,
+                    ],
+                )?;
+                tag_lock_change(transaction, change)?;
+            }
+            transaction.execute(
+                "UPDATE replication_state SET applied_seq = ?1",
+                [last.seq as i64],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// Returns the persisted replication state, `None` before it is first saved
+    pub fn replication_state(&self) -> Result<Option<ReplicationState>> {
+        let conn = self.connection()?;
+        let state: Option<(String, i64, i64)> = conn
+            .query_row(
+                "SELECT role, epoch, applied_seq FROM replication_state WHERE id = 1",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .optional()?;
+        state
+            .map(|(role, epoch, applied_seq)| {
+                Ok(ReplicationState {
+                    role: role.parse()?,
+                    epoch: epoch as u64,
+                    applied_seq: applied_seq as u64,
+                })
+            })
+            .transpose()
+    }
+
+    pub fn save_replication_state(&self, state: &ReplicationState) -> Result<()> {
+        let conn = self.connection()?;
+        conn.execute(
+            "INSERT OR REPLACE INTO replication_state (id, role, epoch, applied_seq)
+             VALUES (1, ?1, ?2, ?3)",
+            rusqlite::params![
+                state.role.as_str(),
+                state.epoch as i64,
+                state.applied_seq as i64
+            ],
+        )?;
+        Ok(())
+    }
+}
+
+/// Tags the rows the triggers wrote for a replicated change with the instance that made it
+fn tag_lock_change(transaction: &rusqlite::Transaction<'_>, change: &LockChange) -> Result<()> {
+    transaction.execute(
+        "UPDATE lock_changes SET instance_id = ?2 WHERE lock_id = ?1",
+        rusqlite::params![change.lock_id, change.instance_id],
+    )?;
+    transaction.execute(
+        "UPDATE lock_reverts SET instance_id = ?2
+         WHERE (namespace, contract_address, block) IN (
+             SELECT namespace, contract_address, end_block FROM slot_locks
+             WHERE id = ?1 AND COALESCE(unlock_reason, '') IN
+                 ('revert-threshold', 'double-spent', 'manual-revert'))",
+        rusqlite::params![change.lock_id, change.instance_id],
+    )?;
+    Ok(())
+}
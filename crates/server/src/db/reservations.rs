@@ -0,0 +1,209 @@
+//! Slot reservations of the two-phase lock protocol. A reservation holds slots while the sova
+//! node builds a block and becomes locks once the block is finalized, or is released when it
+//! expires.
+
+use super::{Database, SlotInsertData};
+use anyhow::Result;
+use rusqlite::Transaction;
+
+impl Database {
+    /// Reserves `slots` until the unix time `expires_at` and returns the id of the reservation
+    pub fn reserve_slots(
+        &self,
+        transaction: &Transaction,
+        namespace: &str,
+        slots: &[SlotInsertData],
+        expires_at: u64,
+    ) -> Result<i64> {
+        transaction.execute(
+            "INSERT INTO lock_reservations (namespace, expires_at) VALUES (?1, ?2)",
+            rusqlite::params![namespace, expires_at as i64],
+        )?;
+        let reservation_id = transaction.last_insert_rowid();
+        let mut stmt = transaction.prepare(
+            "INSERT INTO lock_reservation_slots (
+                reservation_id, contract_address, slot_index, start_block, btc_block, btc_txid,
+                revert_value, current_value, metadata, labels, alt_btc_txids, required_confirmed_txids,
+                locked_by
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+        )?;
+        for slot in slots {
+            stmt.execute(rusqlite::params![
+                reservation_id,
+                slot.contract_address,
+                slot.slot_index,
+                slot.start_block as i64,
+                slot.btc_block as i64,
+                slot.btc_txid,
+                slot.revert_value,
+                slot.current_value,
+                slot.metadata,
+                serde_json::to_string(&slot.labels)?,
+                serde_json::to_string(&slot.alt_btc_txids)?,
+                slot.required_confirmed_txids,
+                slot.locked_by,
+            ])?;
+        }
+        Ok(reservation_id)
+    }
+
+    /// Returns whether each of `slots` is held by a reservation that hasn't expired at the unix
+    /// time `now`
+    pub fn reserved_slots(
+        &self,
+        transaction: &Transaction,
+        namespace: &str,
+        slots: &[(&str, &[u8])], // Vec of (contract_address, slot_index)
+        now: u64,
+    ) -> Result<Vec<bool>> {
+        let mut stmt = transaction.prepare(
+            "SELECT EXISTS (
+                SELECT 1 FROM lock_reservation_slots s
+                JOIN lock_reservations r ON r.id = s.reservation_id
+                WHERE r.namespace = ?1 AND s.contract_address = ?2 AND s.slot_index = ?3
+                AND r.expires_at > ?4
+            )",
+        )?;
+        slots
+            .iter()
+            .map(|(contract_address, slot_index)| {
+                Ok(stmt.query_row(
+                    rusqlite::params![namespace, contract_address, slot_index, now as i64],
+                    |row| row.get(0),
+                )?)
+            })
+            .collect()
+    }
+
+    /// Removes a reservation that hasn't expired at the unix time `now` and returns its slots,
+    /// or `None` if there is no such reservation
+    pub fn take_reservation(
+        &self,
+        transaction: &Transaction,
+        namespace: &str,
+        reservation_id: i64,
+        now: u64,
+    ) -> Result<Option<Vec<SlotInsertData>>> {
+        let found: bool = transaction.query_row(
+            "SELECT EXISTS (
+                SELECT 1 FROM lock_reservations WHERE id = ?1 AND namespace = ?2 AND expires_at > ?3
+            )",
+            rusqlite::params![reservation_id, namespace, now as i64],
+            |row| row.get(0),
+        )?;
+        if !found {
+            return Ok(None);
+        }
+
+        let mut stmt = transaction.prepare(
+            "SELECT contract_address, slot_index, start_block, btc_block, btc_txid, revert_value,
+                current_value, metadata, labels, alt_btc_txids, required_confirmed_txids, locked_by
+             FROM lock_reservation_slots WHERE reservation_id = ?1 ORDER BY id",
+        )?;
+        let slots = stmt
+            .query_map([reservation_id], |row| {
+                Ok(SlotInsertData {
+                    namespace: namespace.to_string(),
+                    contract_address: row.get(0)?,
+                    slot_index: row.get(1)?,
+                    start_block: row.get(2)?,
+                    btc_block: row.get(3)?,
+                    btc_txid: row.get(4)?,
+                    revert_value: row.get(5)?,
+                    current_value: row.get(6)?,
+                    metadata: row.get(7)?,
+                    labels: super::json_from_row(row, 8)?,
+                    alt_btc_txids: super::json_from_row(row, 9)?,
+                    required_confirmed_txids: row.get(10)?,
+                    locked_by: row.get(11)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        self.delete_reservations(transaction, "id = ?1", reservation_id)?;
+        Ok(Some(slots))
+    }
+
+    /// Releases the reservations expired at the unix time `now`. Returns how many were released.
+    pub fn release_expired_reservations(
+        &self,
+        transaction: &Transaction,
+        now: u64,
+    ) -> Result<usize> {
+        self.delete_reservations(transaction, "expires_at <= ?1", now as i64)
+    }
+
+    /// Deletes the reservations matching `filter`, which binds `?1` to `param`, with their slots
+    fn delete_reservations(
+        &self,
+        transaction: &Transaction,
+        filter: &str,
+        param: i64,
+    ) -> Result<usize> {
+        transaction.execute(
+            &format!(
+                "DELETE FROM lock_reservation_slots WHERE reservation_id IN (
+                    SELECT id FROM lock_reservations WHERE {}
+                )",
+                filter
+            ),
+            [param],
+        )?;
+        Ok(transaction.execute(
+            &format!("DELETE FROM lock_reservations WHERE {}", filter),
+            [param],
+        )?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reservations() -> Result<()> {
+        let db = Database::new(rusqlite::Connection::open_in_memory()?)?;
+        let slot = SlotInsertData {
+            namespace: String::new(),
+            contract_address: "0x123".to_string(),
+            start_block: 100,
+            btc_block: 200,
+            slot_index: vec![1],
+            btc_txid: "txid1".to_string(),
+            revert_value: vec![4],
+            current_value: vec![7],
+            metadata: Vec::new(),
+            labels: [("deposit".to_string(), "7".to_string())].into(),
+            alt_btc_txids: vec!["txid2".to_string()],
+            required_confirmed_txids: 1,
+            locked_by: None,
+        };
+        let slots = [("0x123", [1u8].as_slice()), ("0x123", [2u8].as_slice())];
+
+        let (expired, live) = db.with_transaction(|tx| {
+            let expired = db.reserve_slots(tx, "", std::slice::from_ref(&slot), 1000)?;
+            let live = db.reserve_slots(tx, "", std::slice::from_ref(&slot), 2000)?;
+            Ok((expired, live))
+        })?;
+        db.with_transaction(|tx| {
+            assert_eq!(db.reserved_slots(tx, "", &slots, 1500)?, vec![true, false]);
+            assert_eq!(
+                db.reserved_slots(tx, "testnet", &slots, 1500)?,
+                vec![false, false]
+            );
+            assert_eq!(db.reserved_slots(tx, "", &slots, 2000)?, vec![false, false]);
+
+            // Expired reservations can't be committed, and are released
+            assert!(db.take_reservation(tx, "", expired, 1500)?.is_none());
+            assert_eq!(db.release_expired_reservations(tx, 1500)?, 1);
+
+            assert!(db.take_reservation(tx, "testnet", live, 1500)?.is_none());
+            let taken = db.take_reservation(tx, "", live, 1500)?.unwrap();
+            assert_eq!(taken[0].labels, slot.labels);
+            assert_eq!(taken[0].alt_btc_txids, slot.alt_btc_txids);
+            assert!(db.take_reservation(tx, "", live, 1500)?.is_none());
+            assert_eq!(db.reserved_slots(tx, "", &slots, 1500)?, vec![false, false]);
+            Ok(())
+        })?;
+        Ok(())
+    }
+}
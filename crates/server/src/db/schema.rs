@@ -0,0 +1,91 @@
+use anyhow::Result;
+use rusqlite::Connection;
+
+#[derive(Debug, Clone)]
+pub struct TableSchema {
+    pub name: String,
+    pub columns: Vec<ColumnSchema>,
+    pub indexes: Vec<IndexSchema>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ColumnSchema {
+    pub name: String,
+    pub data_type: String,
+    pub not_null: bool,
+    pub default_value: Option<String>,
+    pub primary_key: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct IndexSchema {
+    pub name: String,
+    pub unique: bool,
+    pub columns: Vec<String>,
+}
+
+/// Reads table, column and index metadata for all user tables from the SQLite catalog
+pub fn describe_tables(conn: &Connection) -> Result<Vec<TableSchema>> {
+    let mut stmt = conn.prepare(
+        "SELECT name FROM sqlite_master
+         WHERE type = 'table' AND name NOT LIKE 'sqlite_%'
+         ORDER BY name",
+    )?;
+    let table_names = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    table_names
+        .into_iter()
+        .map(|name| {
+            Ok(TableSchema {
+                columns: describe_columns(conn, &name)?,
+                indexes: describe_indexes(conn, &name)?,
+                name,
+            })
+        })
+        .collect()
+}
+
+fn describe_columns(conn: &Connection, table: &str) -> Result<Vec<ColumnSchema>> {
+    let mut stmt = conn.prepare(
+        "SELECT name, type, \"notnull\", dflt_value, pk FROM pragma_table_info(?1) ORDER BY cid",
+    )?;
+    let columns = stmt
+        .query_map([table], |row| {
+            Ok(ColumnSchema {
+                name: row.get(0)?,
+                data_type: row.get(1)?,
+                not_null: row.get(2)?,
+                default_value: row.get(3)?,
+                primary_key: row.get::<_, i64>(4)? > 0,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(columns)
+}
+
+fn describe_indexes(conn: &Connection, table: &str) -> Result<Vec<IndexSchema>> {
+    let mut stmt =
+        conn.prepare("SELECT name, \"unique\" FROM pragma_index_list(?1) ORDER BY name")?;
+    let indexes = stmt
+        .query_map([table], |row| Ok((row.get::<_, String>(0)?, row.get(1)?)))?
+        .collect::<rusqlite::Result<Vec<(String, bool)>>>()?;
+
+    let mut column_stmt = conn.prepare("SELECT name FROM pragma_index_info(?1) ORDER BY seqno")?;
+    indexes
+        .into_iter()
+        .map(|(name, unique)| {
+            // Expression index columns have no name
+            let columns = column_stmt
+                .query_map([&name], |row| row.get::<_, Option<String>>(0))?
+                .map(|column| column.map(|c| c.unwrap_or_else(|| "<expression>".to_string())))
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(IndexSchema {
+                name,
+                unique,
+                columns,
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,165 @@
+//! Lock statistics. The counters are maintained by triggers on the lock table (see migration 21),
+//! so reading them costs a lookup per contract however many locks the database holds.
+
+use super::Database;
+use anyhow::Result;
+
+/// Locks of a contract held in the database. Locks purged by the retention policy no longer
+/// count.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LockStats {
+    pub namespace: String,
+    pub contract_address: String,
+    pub active: u64,
+    pub unlocked: u64,
+    pub reverted: u64,
+    /// Sum of the durations in sova blocks of the unlocked and reverted locks
+    pub duration_blocks: u64,
+}
+
+impl LockStats {
+    /// Average duration in sova blocks of the unlocked and reverted locks, 0 if there are none
+    pub fn average_duration_blocks(&self) -> f64 {
+        match self.unlocked + self.reverted {
+            0 => 0.0,
+            ended => self.duration_blocks as f64 / ended as f64,
+        }
+    }
+}
+
+impl Database {
+    /// Returns the lock statistics of each contract, by namespace and contract address
+    pub fn lock_stats(
+        &self,
+        namespace: Option<&str>,
+        contract_address: Option<&str>,
+    ) -> Result<Vec<LockStats>> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare(
+            "SELECT namespace, contract_address, active, unlocked, reverted, duration_blocks
+             FROM lock_stats
+             WHERE (?1 IS NULL OR namespace = ?1) AND (?2 IS NULL OR contract_address = ?2)
+             AND active + unlocked + reverted > 0
+             ORDER BY namespace, contract_address",
+        )?;
+        let stats = stmt
+            .query_map([namespace, contract_address], |row| {
+                Ok(LockStats {
+                    namespace: row.get(0)?,
+                    contract_address: row.get(1)?,
+                    active: row.get(2)?,
+                    unlocked: row.get(3)?,
+                    reverted: row.get(4)?,
+                    duration_blocks: row.get(5)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(stats)
+    }
+
+    /// Returns how many locks were reverted in the last `blocks` sova blocks of each namespace,
+    /// counting back from the latest sova block a lock of the namespace was made or ended in
+    pub fn recent_reverts(
+        &self,
+        namespace: Option<&str>,
+        contract_address: Option<&str>,
+        blocks: u64,
+    ) -> Result<u64> {
+        let conn = self.connection()?;
+        let reverts = conn.query_row(
+            "SELECT COALESCE(SUM(r.reverts), 0) FROM lock_reverts r
+             JOIN (
+                SELECT namespace, MAX(latest_block) AS latest_block FROM lock_stats
+                WHERE ?1 IS NULL OR namespace = ?1
+                GROUP BY namespace
+             ) l ON l.namespace = r.namespace
+             WHERE (?2 IS NULL OR r.contract_address = ?2) AND r.block > l.latest_block - ?3",
+            rusqlite::params![namespace, contract_address, blocks as i64],
+            |row| row.get(0),
+        )?;
+        Ok(reverts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{SlotInsertData, UnlockReason};
+
+    fn slot(namespace: &str, contract_address: &str, idx: u8, start_block: u64) -> SlotInsertData {
+        SlotInsertData {
+            namespace: namespace.to_string(),
+            contract_address: contract_address.to_string(),
+            start_block,
+            btc_block: 200,
+            slot_index: vec![idx],
+            btc_txid: "txid1".to_string(),
+            revert_value: vec![4],
+            current_value: vec![7],
+            metadata: Vec::new(),
+            labels: Default::default(),
+            alt_btc_txids: Vec::new(),
+            required_confirmed_txids: 1,
+            locked_by: None,
+        }
+    }
+
+    #[test]
+    fn test_lock_stats() -> Result<()> {
+        let db = Database::new(rusqlite::Connection::open_in_memory()?)?;
+        db.with_transaction(|tx| {
+            db.batch_insert_slot_locks(
+                tx,
+                &[
+                    slot("", "0x123", 1, 100),
+                    slot("", "0x123", 2, 100),
+                    slot("", "0x123", 3, 100),
+                    slot("", "0x456", 1, 100),
+                    slot("testnet", "0x123", 1, 5000),
+                ],
+            )
+        })?;
+        db.unlock_slot("", "0x123", &[1], 110, UnlockReason::Confirmed)?;
+        db.unlock_slot("", "0x123", &[2], 130, UnlockReason::RevertThreshold)?;
+        db.unlock_slot("", "0x456", &[1], 200, UnlockReason::DoubleSpent)?;
+        db.unlock_slot("testnet", "0x123", &[1], 5001, UnlockReason::ManualRevert)?;
+
+        let stats = db.lock_stats(Some(""), None)?;
+        assert_eq!(
+            stats[0],
+            LockStats {
+                namespace: String::new(),
+                contract_address: "0x123".to_string(),
+                active: 1,
+                unlocked: 1,
+                reverted: 1,
+                duration_blocks: 40,
+            }
+        );
+        assert_eq!(stats[0].average_duration_blocks(), 20.0);
+        assert_eq!((stats[1].reverted, stats[1].duration_blocks), (1, 100));
+        assert_eq!(db.lock_stats(None, Some("0x123"))?.len(), 2);
+
+        // Blocks count back from the latest block of each namespace, 200 and 5001
+        assert_eq!(db.recent_reverts(Some(""), None, 1)?, 1);
+        assert_eq!(db.recent_reverts(Some(""), None, 70)?, 1);
+        assert_eq!(db.recent_reverts(Some(""), None, 71)?, 2);
+        assert_eq!(db.recent_reverts(None, None, 100)?, 3);
+        assert_eq!(db.recent_reverts(None, Some("0x123"), 100)?, 2);
+
+        // Purged locks no longer count
+        let ids = db
+            .lock_rows(Some(""), 0, 100)?
+            .into_iter()
+            .filter(|row| row.lock.contract_address == "0x456")
+            .map(|row| row.id)
+            .collect::<Vec<_>>();
+        db.with_transaction(|tx| {
+            db.archive_lock_rows(tx, &ids)?;
+            db.delete_lock_rows(tx, &ids)
+        })?;
+        assert!(db.lock_stats(None, Some("0x456"))?.is_empty());
+        assert_eq!(db.recent_reverts(Some(""), None, 100)?, 1);
+        Ok(())
+    }
+}
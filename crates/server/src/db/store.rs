@@ -0,0 +1,533 @@
+//! Storage of the slot lock service. [`SlotStore`] is everything the service needs from its
+//! backend, so another backend only has to implement it; [`Database`] is the SQLite one.
+//! Operations taking a transaction run in one opened by [`SlotStore::with_transaction`], and see
+//! the writes made before them in it.
+
+use super::{Database, LockRow, LockedSlot, SlotInsertData, UnlockReason};
+use anyhow::Result;
+use std::str::FromStr;
+
+/// Backend the slot lock service stores its locks in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StorageBackend {
+    /// The SQLite database, shared with everything else
+    #[default]
+    Sqlite,
+    /// A separate redb file, see [`super::RedbStore`]
+    Redb,
+}
+
+impl FromStr for StorageBackend {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "sqlite" => Ok(Self::Sqlite),
+            "redb" => Ok(Self::Redb),
+            other => Err(anyhow::anyhow!("Unsupported storage backend: {}", other)),
+        }
+    }
+}
+
+pub trait SlotStore: Send + Sync + 'static {
+    /// Transaction of the backend
+    type Transaction<'t>;
+
+    /// Runs `f` in a transaction, committed if `f` succeeds
+    fn with_transaction<F, T>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&Self::Transaction<'_>) -> Result<T>;
+
+    /// Runs `f` in a transaction that is rolled back afterwards, leaving the store unchanged
+    fn with_rolled_back_transaction<F, T>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&Self::Transaction<'_>) -> Result<T>;
+
+    /// Returns the version of the store's schema
+    fn schema_version(&self) -> Result<u32>;
+
+    /// Returns whether a slot has an active lock
+    fn is_slot_locked_with_transaction(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        namespace: &str,
+        contract_address: &str,
+        slot_index: &[u8],
+    ) -> Result<bool>;
+
+    fn insert_slot_lock(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        slot: &SlotInsertData,
+    ) -> Result<()>;
+
+    /// Locks the slots that have no active lock, returning whether each was locked
+    fn batch_insert_slot_locks(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        slots: &[SlotInsertData],
+    ) -> Result<Vec<bool>>;
+
+    /// Returns the lock of a slot at `current_block`: its active lock, or the lock unlocked in
+    /// `current_block`, if it started at or before `current_block`
+    fn get_slot(
+        &self,
+        namespace: &str,
+        contract_address: &str,
+        slot_index: &[u8],
+        current_block: u64,
+    ) -> Result<Option<LockedSlot>>;
+
+    /// [`Self::get_slot`] within a transaction
+    fn get_slot_with_transaction(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        namespace: &str,
+        contract_address: &str,
+        slot_index: &[u8],
+        current_block: u64,
+    ) -> Result<Option<LockedSlot>>;
+
+    /// [`Self::get_slot`] of each of `slots`, given as (contract address, slot index)
+    fn batch_get_locked_slots(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        namespace: &str,
+        slots: &[(&str, &[u8])],
+        current_block: u64,
+    ) -> Result<Vec<Option<LockedSlot>>>;
+
+    /// Ends the active lock of a slot at `end_block`
+    fn unlock_slot_with_transaction(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        namespace: &str,
+        contract_address: &str,
+        slot_index: &[u8],
+        end_block: u64,
+        reason: UnlockReason,
+    ) -> Result<()>;
+
+    /// Ends the active locks of `slots`, given as (contract address, slot index, end block)
+    fn batch_unlock_slots(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        namespace: &str,
+        slots: &[(&str, &[u8], u64)],
+        reason: UnlockReason,
+    ) -> Result<()>;
+
+    /// Records the reason of a lock unlocked before reasons were recorded, unless one was recorded
+    /// in the meantime. Returns the reason now stored for the unlock.
+    fn backfill_unlock_reason(
+        &self,
+        namespace: &str,
+        contract_address: &str,
+        slot_index: &[u8],
+        end_block: u64,
+        reason: UnlockReason,
+    ) -> Result<UnlockReason>;
+
+    /// Returns the number of active locks, in `namespace` or in all namespaces
+    fn active_lock_count(&self, namespace: Option<&str>) -> Result<u64>;
+
+    /// Returns the number of active locks `caller` made, in all namespaces, counting the slots
+    /// of its reservations that haven't expired at the unix time `now`
+    fn caller_active_locks(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        caller: &str,
+        now: u64,
+    ) -> Result<u64>;
+
+    /// Returns the slots of the active locks of a namespace that started at or before
+    /// `current_block`, ordered by contract address and slot index
+    fn active_lock_slots(
+        &self,
+        namespace: &str,
+        current_block: u64,
+    ) -> Result<Vec<(String, Vec<u8>)>>;
+
+    /// Returns the active locks of the namespace on `btc_txid`
+    fn active_locks_by_txid(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        namespace: &str,
+        btc_txid: &str,
+    ) -> Result<Vec<LockedSlot>>;
+
+    /// Points all active locks of the namespace on `old_btc_txid` at its replacement and records
+    /// the replacement. Returns the number of locks updated.
+    fn replace_lock_txid(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        namespace: &str,
+        old_btc_txid: &str,
+        new_btc_txid: &str,
+        detected: bool,
+    ) -> Result<usize>;
+
+    /// Returns every lock of a slot, active and unlocked, oldest first
+    fn slot_lock_rows(
+        &self,
+        namespace: &str,
+        contract_address: &str,
+        slot_index: &[u8],
+    ) -> Result<Vec<LockRow>>;
+
+    /// Makes the active locks of `slots` an atomic group and returns the group's id
+    fn create_lock_group(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        namespace: &str,
+        slots: &[(&str, &[u8])],
+    ) -> Result<i64>;
+
+    /// Returns the active locks of the given atomic groups
+    fn active_group_locks(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        groups: &[i64],
+    ) -> Result<Vec<LockedSlot>>;
+
+    /// Reserves `slots` until the unix time `expires_at` and returns the id of the reservation
+    fn reserve_slots(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        namespace: &str,
+        slots: &[SlotInsertData],
+        expires_at: u64,
+    ) -> Result<i64>;
+
+    /// Returns whether each of `slots` is held by a reservation that hasn't expired at the unix
+    /// time `now`
+    fn reserved_slots(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        namespace: &str,
+        slots: &[(&str, &[u8])],
+        now: u64,
+    ) -> Result<Vec<bool>>;
+
+    /// Removes a reservation that hasn't expired at the unix time `now` and returns its slots,
+    /// or `None` if there is no such reservation
+    fn take_reservation(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        namespace: &str,
+        reservation_id: i64,
+        now: u64,
+    ) -> Result<Option<Vec<SlotInsertData>>>;
+
+    /// Releases the reservations expired at the unix time `now`. Returns how many were released.
+    fn release_expired_reservations(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        now: u64,
+    ) -> Result<usize>;
+
+    /// Counts lock attempts rejected with `AlreadyLocked`, one entry per rejected attempt
+    fn record_lock_conflicts(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        namespace: &str,
+        contract_addresses: &[&str],
+    ) -> Result<()>;
+
+    /// Stores the raw Bitcoin transaction of a lock for rebroadcasting
+    fn insert_lock_transaction(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        btc_txid: &str,
+        raw_tx: &[u8],
+    ) -> Result<()>;
+
+    /// Queues reverted locks for delivery to the revert executor
+    fn enqueue_revert_deliveries(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        slots: &[&LockedSlot],
+        reverted_at_block: u64,
+        reason: UnlockReason,
+    ) -> Result<()>;
+}
+
+impl SlotStore for Database {
+    type Transaction<'t> = rusqlite::Transaction<'t>;
+
+    fn with_transaction<F, T>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&Self::Transaction<'_>) -> Result<T>,
+    {
+        Database::with_transaction(self, f)
+    }
+
+    fn with_rolled_back_transaction<F, T>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&Self::Transaction<'_>) -> Result<T>,
+    {
+        Database::with_rolled_back_transaction(self, f)
+    }
+
+    fn schema_version(&self) -> Result<u32> {
+        Database::schema_version(self)
+    }
+
+    fn is_slot_locked_with_transaction(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        namespace: &str,
+        contract_address: &str,
+        slot_index: &[u8],
+    ) -> Result<bool> {
+        Database::is_slot_locked_with_transaction(
+            self,
+            transaction,
+            namespace,
+            contract_address,
+            slot_index,
+        )
+    }
+
+    fn insert_slot_lock(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        slot: &SlotInsertData,
+    ) -> Result<()> {
+        Database::insert_slot_lock(self, transaction, slot)
+    }
+
+    fn batch_insert_slot_locks(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        slots: &[SlotInsertData],
+    ) -> Result<Vec<bool>> {
+        Database::batch_insert_slot_locks(self, transaction, slots)
+    }
+
+    fn get_slot(
+        &self,
+        namespace: &str,
+        contract_address: &str,
+        slot_index: &[u8],
+        current_block: u64,
+    ) -> Result<Option<LockedSlot>> {
+        Database::get_slot(self, namespace, contract_address, slot_index, current_block)
+    }
+
+    fn get_slot_with_transaction(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        namespace: &str,
+        contract_address: &str,
+        slot_index: &[u8],
+        current_block: u64,
+    ) -> Result<Option<LockedSlot>> {
+        Database::get_slot_with_transaction(
+            self,
+            transaction,
+            namespace,
+            contract_address,
+            slot_index,
+            current_block,
+        )
+    }
+
+    fn batch_get_locked_slots(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        namespace: &str,
+        slots: &[(&str, &[u8])],
+        current_block: u64,
+    ) -> Result<Vec<Option<LockedSlot>>> {
+        Database::batch_get_locked_slots(self, transaction, namespace, slots, current_block)
+    }
+
+    fn unlock_slot_with_transaction(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        namespace: &str,
+        contract_address: &str,
+        slot_index: &[u8],
+        end_block: u64,
+        reason: UnlockReason,
+    ) -> Result<()> {
+        Database::unlock_slot_with_transaction(
+            self,
+            transaction,
+            namespace,
+            contract_address,
+            slot_index,
+            end_block,
+            reason,
+        )
+    }
+
+    fn batch_unlock_slots(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        namespace: &str,
+        slots: &[(&str, &[u8], u64)],
+        reason: UnlockReason,
+    ) -> Result<()> {
+        Database::batch_unlock_slots(self, transaction, namespace, slots, reason)
+    }
+
+    fn backfill_unlock_reason(
+        &self,
+        namespace: &str,
+        contract_address: &str,
+        slot_index: &[u8],
+        end_block: u64,
+        reason: UnlockReason,
+    ) -> Result<UnlockReason> {
+        Database::backfill_unlock_reason(
+            self,
+            namespace,
+            contract_address,
+            slot_index,
+            end_block,
+            reason,
+        )
+    }
+
+    fn active_lock_count(&self, namespace: Option<&str>) -> Result<u64> {
+        Database::active_lock_count(self, namespace)
+    }
+
+    fn caller_active_locks(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        caller: &str,
+        now: u64,
+    ) -> Result<u64> {
+        Database::caller_active_locks(self, transaction, caller, now)
+    }
+
+    fn active_lock_slots(
+        &self,
+        namespace: &str,
+        current_block: u64,
+    ) -> Result<Vec<(String, Vec<u8>)>> {
+        Database::active_lock_slots(self, namespace, current_block)
+    }
+
+    fn active_locks_by_txid(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        namespace: &str,
+        btc_txid: &str,
+    ) -> Result<Vec<LockedSlot>> {
+        Database::active_locks_by_txid(self, transaction, namespace, btc_txid)
+    }
+
+    fn replace_lock_txid(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        namespace: &str,
+        old_btc_txid: &str,
+        new_btc_txid: &str,
+        detected: bool,
+    ) -> Result<usize> {
+        Database::replace_lock_txid(
+            self,
+            transaction,
+            namespace,
+            old_btc_txid,
+            new_btc_txid,
+            detected,
+        )
+    }
+
+    fn slot_lock_rows(
+        &self,
+        namespace: &str,
+        contract_address: &str,
+        slot_index: &[u8],
+    ) -> Result<Vec<LockRow>> {
+        Database::slot_lock_rows(self, namespace, contract_address, slot_index)
+    }
+
+    fn create_lock_group(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        namespace: &str,
+        slots: &[(&str, &[u8])],
+    ) -> Result<i64> {
+        Database::create_lock_group(self, transaction, namespace, slots)
+    }
+
+    fn active_group_locks(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        groups: &[i64],
+    ) -> Result<Vec<LockedSlot>> {
+        Database::active_group_locks(self, transaction, groups)
+    }
+
+    fn reserve_slots(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        namespace: &str,
+        slots: &[SlotInsertData],
+        expires_at: u64,
+    ) -> Result<i64> {
+        Database::reserve_slots(self, transaction, namespace, slots, expires_at)
+    }
+
+    fn reserved_slots(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        namespace: &str,
+        slots: &[(&str, &[u8])],
+        now: u64,
+    ) -> Result<Vec<bool>> {
+        Database::reserved_slots(self, transaction, namespace, slots, now)
+    }
+
+    fn take_reservation(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        namespace: &str,
+        reservation_id: i64,
+        now: u64,
+    ) -> Result<Option<Vec<SlotInsertData>>> {
+        Database::take_reservation(self, transaction, namespace, reservation_id, now)
+    }
+
+    fn release_expired_reservations(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        now: u64,
+    ) -> Result<usize> {
+        Database::release_expired_reservations(self, transaction, now)
+    }
+
+    fn record_lock_conflicts(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        namespace: &str,
+        contract_addresses: &[&str],
+    ) -> Result<()> {
+        Database::record_lock_conflicts(self, transaction, namespace, contract_addresses)
+    }
+
+    fn insert_lock_transaction(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        btc_txid: &str,
+        raw_tx: &[u8],
+    ) -> Result<()> {
+        Database::insert_lock_transaction(self, transaction, btc_txid, raw_tx)
+    }
+
+    fn enqueue_revert_deliveries(
+        &self,
+        transaction: &Self::Transaction<'_>,
+        slots: &[&LockedSlot],
+        reverted_at_block: u64,
+        reason: UnlockReason,
+    ) -> Result<()> {
+        Database::enqueue_revert_deliveries(self, transaction, slots, reverted_at_block, reason)
+    }
+}
@@ -0,0 +1,36 @@
+use serde::Serialize;
+use sova_sentinel_proto::proto;
+use std::env;
+
+/// Labels identifying a sentinel deployment so events from several instances can be told apart
+/// once they are aggregated centrally.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct DeploymentLabels {
+    pub environment: String,
+    pub region: String,
+    pub instance_id: String,
+}
+
+impl DeploymentLabels {
+    /// Reads the labels from `SOVA_SENTINEL_ENVIRONMENT`, `SOVA_SENTINEL_REGION` and
+    /// `SOVA_SENTINEL_INSTANCE_ID`. The instance id falls back to `HOSTNAME` when unset.
+    pub fn from_env() -> Self {
+        Self {
+            environment: env::var("SOVA_SENTINEL_ENVIRONMENT").unwrap_or_default(),
+            region: env::var("SOVA_SENTINEL_REGION").unwrap_or_default(),
+            instance_id: env::var("SOVA_SENTINEL_INSTANCE_ID")
+                .or_else(|_| env::var("HOSTNAME"))
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl From<&DeploymentLabels> for proto::DeploymentLabels {
+    fn from(labels: &DeploymentLabels) -> Self {
+        Self {
+            environment: labels.environment.clone(),
+            region: labels.region.clone(),
+            instance_id: labels.instance_id.clone(),
+        }
+    }
+}
@@ -0,0 +1,366 @@
+//! Write-ahead journal of state-changing requests, kept in a file separate from the database.
+//!
+//! Each request is appended before it is handled and its outcome after, together with the lock
+//! changes committed in the meantime, so the lock table can be rebuilt from the journal alone and
+//! the requests leading up to a bug can be read back in order. Records are length-delimited
+//! [`JournalEntry`] messages, each synced to disk before the sentinel goes on.
+
+use crate::db::Database;
+use crate::proto::{journal_entry, JournalEntry, JournaledOutcome, JournaledRequest, LockChange};
+use crate::service::lock_change;
+use anyhow::{Context, Result};
+use prost::Message;
+use std::collections::BTreeSet;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tonic::Code;
+
+/// Number of lock changes recorded per outcome record
+const CHANGES_PER_RECORD: usize = 1000;
+
+/// Appends requests and their outcomes to a journal file
+#[derive(Clone)]
+pub struct Journal {
+    db: Database,
+    writer: Arc<Mutex<Writer>>,
+}
+
+struct Writer {
+    file: File,
+    next_seq: u64,
+    /// Last lock change recorded
+    change_seq: u64,
+}
+
+impl Journal {
+    /// Opens the journal at `path`, creating it if needed, and records the lock changes committed
+    /// since its last record. A record left incomplete by a crash is cut off.
+    pub fn open(path: &Path, db: Database) -> Result<Self> {
+        let bytes = match std::fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read journal {}", path.display()))
+            }
+        };
+        let (entries, complete_len) = decode_entries(&bytes);
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open journal {}", path.display()))?;
+        if complete_len < bytes.len() {
+            tracing::warn!(
+                "Cutting off an incomplete record at the end of journal {}",
+                path.display()
+            );
+            file.set_len(complete_len as u64)?;
+        }
+
+        let change_seq = entries
+            .iter()
+            .filter_map(|entry| match &entry.record {
+                Some(journal_entry::Record::Outcome(outcome)) => {
+                    outcome.changes.iter().map(|change| change.seq).max()
+                }
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0);
+        if db.last_lock_change_seq()? < change_seq {
+            tracing::warn!(
+                "Journal {} records lock changes the database doesn't have; was the database restored?",
+                path.display()
+            );
+        }
+        let journal = Self {
+            db,
+            writer: Arc::new(Mutex::new(Writer {
+                file,
+                next_seq: entries.last().map_or(1, |entry| entry.seq + 1),
+                change_seq,
+            })),
+        };
+        journal.sync()?;
+        Ok(journal)
+    }
+
+    /// Writes a request ahead of handling it, returning its sequence number. Changes committed
+    /// outside journaled requests since the last record are recorded first.
+    pub fn record_request(&self, method: &str, payload: Vec<u8>) -> Result<u64> {
+        let mut writer = self.lock_writer();
+        self.record_changes(&mut writer, 0, Code::Ok, "")?;
+        writer.append(journal_entry::Record::Request(JournaledRequest {
+            method: method.to_string(),
+            payload,
+        }))
+    }
+
+    /// Writes how the request `request_seq` ended, with the lock changes committed since the last
+    /// record
+    pub fn record_outcome(&self, request_seq: u64, code: Code, message: &str) -> Result<()> {
+        let mut writer = self.lock_writer();
+        if !self.record_changes(&mut writer, request_seq, code, message)? {
+            writer.append(outcome(request_seq, code, message, Vec::new()))?;
+        }
+        Ok(())
+    }
+
+    /// Writes a request that was handled without being journaled ahead, with its outcome, if it
+    /// committed lock changes. Used for status queries, which only change locks when they unlock
+    /// them.
+    pub fn record_if_changed(
+        &self,
+        method: &str,
+        payload: Vec<u8>,
+        code: Code,
+        message: &str,
+    ) -> Result<()> {
+        let mut writer = self.lock_writer();
+        if self.db.last_lock_change_seq()? <= writer.change_seq {
+            return Ok(());
+        }
+        let request_seq = writer.append(journal_entry::Record::Request(JournaledRequest {
+            method: method.to_string(),
+            payload,
+        }))?;
+        self.record_changes(&mut writer, request_seq, code, message)?;
+        Ok(())
+    }
+
+    /// Records the lock changes committed outside journaled requests, e.g. by pruning or
+    /// replication
+    pub fn sync(&self) -> Result<()> {
+        let mut writer = self.lock_writer();
+        self.record_changes(&mut writer, 0, Code::Ok, "")?;
+        Ok(())
+    }
+
+    /// Writes the lock changes committed since the last record as outcomes of `request_seq`.
+    /// Returns whether there were any.
+    fn record_changes(
+        &self,
+        writer: &mut Writer,
+        request_seq: u64,
+        code: Code,
+        message: &str,
+    ) -> Result<bool> {
+        let mut recorded = false;
+        loop {
+            let changes = self
+                .db
+                .lock_changes(writer.change_seq, CHANGES_PER_RECORD)?;
+            let Some(last) = changes.last() else {
+                return Ok(recorded);
+            };
+            let change_seq = last.seq;
+            writer.append(outcome(
+                request_seq,
+                code,
+                message,
+                changes.into_iter().map(Into::into).collect(),
+            ))?;
+            writer.change_seq = change_seq;
+            recorded = true;
+        }
+    }
+
+    fn lock_writer(&self) -> std::sync::MutexGuard<'_, Writer> {
+        self.writer.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Writer {
+    /// Appends a record and syncs it to disk, returning its sequence number
+    fn append(&mut self, record: journal_entry::Record) -> Result<u64> {
+        let seq = self.next_seq;
+        let entry = JournalEntry {
+            seq,
+            recorded_at_ms: SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64,
+            record: Some(record),
+        };
+        self.file
+            .write_all(&entry.encode_length_delimited_to_vec())?;
+        self.file.sync_data()?;
+        self.next_seq += 1;
+        Ok(seq)
+    }
+}
+
+fn outcome(
+    request_seq: u64,
+    code: Code,
+    message: &str,
+    changes: Vec<LockChange>,
+) -> journal_entry::Record {
+    journal_entry::Record::Outcome(JournaledOutcome {
+        request_seq,
+        code: code as i32,
+        message: message.to_string(),
+        changes,
+    })
+}
+
+/// Decodes the complete records of a journal, returning them with the length they take up
+fn decode_entries(bytes: &[u8]) -> (Vec<JournalEntry>, usize) {
+    let mut buf = bytes;
+    let mut entries = Vec::new();
+    while !buf.is_empty() {
+        let mut rest = buf;
+        match JournalEntry::decode_length_delimited(&mut rest) {
+            Ok(entry) => {
+                entries.push(entry);
+                buf = rest;
+            }
+            Err(_) => break,
+        }
+    }
+    (entries, bytes.len() - buf.len())
+}
+
+/// Reads the records of a journal, leaving out a record left incomplete by a crash
+pub fn read_journal(path: &Path) -> Result<Vec<JournalEntry>> {
+    let bytes = std::fs::read(path)
+        .with_context(|| format!("Failed to read journal {}", path.display()))?;
+    Ok(decode_entries(&bytes).0)
+}
+
+/// Result of replaying a journal
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ReplayStats {
+    /// Requests replayed
+    pub requests: u64,
+    /// Lock changes applied
+    pub changes: u64,
+    /// Requests without an outcome, which were interrupted by a crash
+    pub incomplete: Vec<u64>,
+}
+
+/// Applies the lock changes recorded in `entries` to `db`, stopping after record `until_seq`
+pub fn replay(
+    db: &Database,
+    entries: &[JournalEntry],
+    until_seq: Option<u64>,
+) -> Result<ReplayStats> {
+    let mut stats = ReplayStats::default();
+    let mut pending = BTreeSet::new();
+    for entry in entries {
+        if until_seq.is_some_and(|until_seq| entry.seq > until_seq) {
+            break;
+        }
+        match &entry.record {
+            Some(journal_entry::Record::Request(_)) => {
+                stats.requests += 1;
+                pending.insert(entry.seq);
+            }
+            Some(journal_entry::Record::Outcome(outcome)) => {
+                pending.remove(&outcome.request_seq);
+                let changes = outcome
+                    .changes
+                    .iter()
+                    .cloned()
+                    .map(lock_change)
+                    .collect::<Result<Vec<_>>>()
+                    .with_context(|| format!("Invalid change in journal record {}", entry.seq))?;
+                db.apply_lock_changes(&changes)?;
+                stats.changes += changes.len() as u64;
+            }
+            None => {}
+        }
+    }
+    stats.incomplete = pending.into_iter().collect();
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{LockRow, LockedSlot, UnlockReason};
+    use rusqlite::Connection;
+
+    fn insert_lock(db: &Database, slot_index: u8) -> Result<()> {
+        let row = LockRow {
+            id: 0,
+            lock: LockedSlot {
+                namespace: String::new(),
+                btc_txid: "ab".repeat(32),
+                btc_block: 10,
+                contract_address: "0x123".to_string(),
+                slot_index: vec![slot_index; 32],
+                revert_value: vec![1],
+                current_value: vec![2],
+                start_block: 100,
+                end_block: None,
+                unlock_reason: None,
+                metadata: Vec::new(),
+                labels: Default::default(),
+                lock_group: None,
+                alt_btc_txids: Vec::new(),
+                required_confirmed_txids: 1,
+                locked_at: None,
+                locked_by: None,
+            },
+            created_at: None,
+        };
+        db.with_transaction(|transaction| db.import_lock_rows(transaction, &[row]))?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_journal_replay() -> Result<()> {
+        let dir =
+            std::env::temp_dir().join(format!("sova-sentinel-journal-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("journal");
+        let db = Database::new(Connection::open_in_memory()?)?;
+        // Locks made before the journal was opened are recorded when it is
+        insert_lock(&db, 1)?;
+        let journal = Journal::open(&path, db.clone())?;
+
+        let seq = journal.record_request("/slot_lock.SlotLockService/LockSlot", vec![1, 2])?;
+        insert_lock(&db, 2)?;
+        journal.record_outcome(seq, Code::Ok, "")?;
+        journal.record_if_changed(
+            "/slot_lock.SlotLockService/GetSlotStatus",
+            vec![3],
+            Code::Ok,
+            "",
+        )?;
+        db.unlock_slot("", "0x123", &[1; 32], 105, UnlockReason::Confirmed)?;
+        journal.record_if_changed(
+            "/slot_lock.SlotLockService/GetSlotStatus",
+            vec![4],
+            Code::Ok,
+            "",
+        )?;
+        let interrupted = journal.record_request("/slot_lock.SlotLockService/LockSlot", vec![5])?;
+        drop(journal);
+
+        // A torn write at the end is cut off when the journal is reopened
+        let mut file = OpenOptions::new().append(true).open(&path)?;
+        file.write_all(&[0x20, 1, 2])?;
+        drop(file);
+        let journal = Journal::open(&path, db.clone())?;
+        journal.sync()?;
+        let entries = read_journal(&path)?;
+        assert_eq!(entries.last().unwrap().seq, interrupted);
+
+        let replayed = Database::new(Connection::open_in_memory()?)?;
+        let stats = replay(&replayed, &entries, None)?;
+        assert_eq!(stats.requests, 3);
+        assert_eq!(stats.incomplete, vec![interrupted]);
+        assert_eq!(replayed.active_locks()?, db.active_locks()?);
+        assert_eq!(replayed.active_locks()?.len(), 1);
+
+        // Replaying up to the first request leaves out the unlock
+        let replayed = Database::new(Connection::open_in_memory()?)?;
+        replay(&replayed, &entries, Some(seq + 1))?;
+        assert_eq!(replayed.active_locks()?.len(), 2);
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
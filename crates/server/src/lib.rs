@@ -1,4 +1,23 @@
+pub mod alerts;
+pub mod attestation;
+pub mod backup;
+pub mod chaos;
+pub mod commitment;
+pub mod config;
+pub mod dashboard;
 pub mod db;
+pub mod deployment;
+pub mod journal;
+pub mod maintenance;
+pub mod metrics;
+pub mod otlp;
+pub mod preflight;
+pub mod retention;
+pub mod sampling;
 pub mod service;
+pub mod spv;
+pub mod supervisor;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
 
 pub use sova_sentinel_proto::proto;
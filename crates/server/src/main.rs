@@ -1,40 +1,133 @@
 use anyhow::Result;
 use dotenv::dotenv;
-use sova_sentinel_proto::proto::health_server::HealthServer;
+use sova_sentinel_proto::batch_size::BatchSizeHints;
+use sova_sentinel_proto::fencing::EPOCH_KEY;
+use sova_sentinel_proto::proto::{
+    admin_service_server::AdminServiceServer, health_server::HealthServer,
+};
+use sova_sentinel_proto::request_id::REQUEST_ID_KEY;
+use sova_sentinel_proto::source::{INSTANCE_ID_KEY, ROLE_KEY};
 use sova_sentinel_server::{
-    db::Database,
-    proto::slot_lock_service_server::SlotLockServiceServer,
+    alerts::RevertAlerts,
+    attestation::AttestationKey,
+    backup::{BackupScheduler, S3Credentials, S3Store},
+    chaos::{ChaosRpcClient, FaultInjector},
+    commitment::LockCommitter,
+    config::{Config, ListenerService, RevertExecutorProtocol},
+    dashboard::{self, Dashboard},
+    db::{DatabaseKey, ReplicationRole, StorageBackend},
+    deployment::DeploymentLabels,
+    journal::Journal,
+    maintenance::MaintenanceScheduler,
+    metrics::{self, Metrics},
+    otlp,
+    preflight::{self, CorruptionPolicy},
+    retention::{Pruner, RetentionPolicy},
+    sampling::{self, LogFormat, TraceSampler},
     service::{
-        BitcoinCoreRpcClient, BitcoinRpcClient, BitcoinRpcService, ExternalRpcClient,
-        HealthService, SlotLockServiceImpl,
+        parse_network_name, slot_lock_server, AdminServiceImpl, AuthLayer, AuthToken,
+        BitcoinCoreRpcClient, BitcoinRpcClient, BitcoinRpcService, CircuitBreaker,
+        CompactFilterClient, ConfirmationCache, ContractThresholds, ExternalRpcClient,
+        GrpcRevertExecutor, HealthReporter, HealthService, HttpRevertExecutor, IdentityLayer,
+        InputWatcher, JournaledSlotLockService, MockConfirmations, MockRpcClient, RateLimitLayer,
+        RateLimiter, Rebroadcaster, Recorder, RecordingBitcoinService, Replication, Replicator,
+        RetryPolicy, RetryStrategy, RevertDispatcher, RevertExecutor, SlotEvents,
+        SlotLockServiceImpl, StaleBtcBlockPolicy, DEFAULT_MAX_BATCH_SIZE,
+        DEFAULT_RESERVATION_TIMEOUT, REQUEST_TIMEOUT,
     },
+    spv::{HeaderStore, HeaderSync},
+    supervisor::DatabaseSupervisor,
 };
-use std::{env, sync::Arc, time::Duration};
+use std::{env, net::SocketAddr, path::Path, sync::Arc, time::Duration};
 use tonic::transport::Server;
 use tower::ServiceBuilder;
 use tower_http::{
     classify::{GrpcCode, GrpcErrorsAsFailures, SharedClassifier},
     compression::CompressionLayer,
-    trace::{DefaultMakeSpan, TraceLayer},
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    sensitive_headers::SetSensitiveRequestHeadersLayer,
+    set_header::SetResponseHeaderLayer,
+    trace::TraceLayer,
 };
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Initialize tracing
-    tracing_subscriber::fmt::init();
     // Load .env file if it exists
     dotenv().ok();
+    let config = Config::from_env()?;
+    // Initialize tracing, exporting spans when an OpenTelemetry collector is configured
+    let log_format = env::var("SOVA_SENTINEL_LOG_FORMAT")
+        .unwrap_or_else(|_| "text".to_string())
+        .parse::<LogFormat>()
+        .map_err(|_| anyhow::anyhow!("SOVA_SENTINEL_LOG_FORMAT must be either 'text' or 'json'"))?;
+    let otlp = env::var("SOVA_SENTINEL_OTLP_ENDPOINT")
+        .ok()
+        .map(|endpoint| otlp::spawn_exporter(&endpoint, &DeploymentLabels::from_env()));
+    sampling::init_tracing(sampling::env_filter(&config.logging)?, log_format, otlp);
 
     // Get configuration from environment variables or use defaults
     let host = env::var("SOVA_SENTINEL_HOST").unwrap_or_else(|_| "[::1]".to_string());
     let port = env::var("SOVA_SENTINEL_PORT").unwrap_or_else(|_| "50051".to_string());
     let db_path = env::var("SOVA_SENTINEL_DB_PATH").unwrap_or_else(|_| "slot_locks.db".to_string());
+    let storage = env::var("SOVA_SENTINEL_STORAGE")
+        .unwrap_or_else(|_| "sqlite".to_string())
+        .parse::<StorageBackend>()
+        .map_err(|_| anyhow::anyhow!("SOVA_SENTINEL_STORAGE must be either 'sqlite' or 'redb'"))?;
+    let redb_path =
+        env::var("SOVA_SENTINEL_REDB_PATH").unwrap_or_else(|_| "slot_locks.redb".to_string());
+    let db_key = match (
+        env::var("SOVA_SENTINEL_DB_KEY").ok(),
+        env::var("SOVA_SENTINEL_DB_KEY_FILE").ok(),
+        env::var("SOVA_SENTINEL_DB_KEY_COMMAND").ok(),
+    ) {
+        (None, None, None) => None,
+        (Some(key), None, None) => Some(DatabaseKey::new(key)?),
+        (None, Some(path), None) => Some(DatabaseKey::load(Path::new(&path))?),
+        (None, None, Some(command)) => Some(DatabaseKey::from_command(&command)?),
+        _ => {
+            return Err(
+                "Set only one of SOVA_SENTINEL_DB_KEY, SOVA_SENTINEL_DB_KEY_FILE and \
+                        SOVA_SENTINEL_DB_KEY_COMMAND"
+                    .into(),
+            )
+        }
+    };
+    let on_corruption = env::var("SOVA_SENTINEL_ON_CORRUPTION")
+        .unwrap_or_else(|_| "safe-mode".to_string())
+        .parse::<CorruptionPolicy>()
+        .map_err(|_| {
+            anyhow::anyhow!("SOVA_SENTINEL_ON_CORRUPTION must be either 'safe-mode' or 'fail'")
+        })?;
+    if db_key.is_some() && storage != StorageBackend::Sqlite {
+        // Lock rows would be written to the redb file in plaintext
+        return Err("SOVA_SENTINEL_DB_KEY requires SOVA_SENTINEL_STORAGE=sqlite".into());
+    }
     let btc_rpc_url =
         env::var("BITCOIN_RPC_URL").unwrap_or_else(|_| "http://localhost:18443".to_string());
     let btc_rpc_user = env::var("BITCOIN_RPC_USER").unwrap_or_else(|_| "user".to_string());
     let btc_rpc_pass = env::var("BITCOIN_RPC_PASS").unwrap_or_else(|_| "pass".to_string());
     let rpc_connection_type =
         env::var("BITCOIN_RPC_CONNECTION_TYPE").unwrap_or_else(|_| "bitcoincore".to_string());
+    let btc_p2p_peer =
+        env::var("BITCOIN_P2P_PEER").unwrap_or_else(|_| "localhost:18444".to_string());
+    let btc_mock_confirmations = env::var("BITCOIN_MOCK_CONFIRM_AFTER")
+        .unwrap_or_else(|_| "3".to_string())
+        .parse::<MockConfirmations>()
+        .map_err(|_| {
+            anyhow::anyhow!("BITCOIN_MOCK_CONFIRM_AFTER must be a number of queries or 'never'")
+        })?;
+    let btc_mock_failure_rate = env::var("BITCOIN_MOCK_FAILURE_RATE")
+        .unwrap_or_else(|_| "0".to_string())
+        .parse::<f64>()
+        .ok()
+        .filter(|rate| (0.0..=1.0).contains(rate))
+        .ok_or_else(|| {
+            anyhow::anyhow!("BITCOIN_MOCK_FAILURE_RATE must be a number between 0 and 1")
+        })?;
+    let btc_network = env::var("BITCOIN_NETWORK")
+        .ok()
+        .map(|network| parse_network_name(&network))
+        .transpose()?;
 
     let btc_confirmation_threshold = env::var("BITCOIN_CONFIRMATION_THRESHOLD")
         .unwrap_or_else(|_| "6".to_string())
@@ -50,18 +143,386 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .unwrap_or_else(|_| "5".to_string())
         .parse::<u32>()
         .map_err(|_| anyhow::anyhow!("BITCOIN_RPC_MAX_RETRIES must be a positive integer"))?;
+    let btc_retry_strategy = env::var("BITCOIN_RPC_RETRY_STRATEGY")
+        .unwrap_or_else(|_| "exponential".to_string())
+        .parse::<RetryStrategy>()
+        .map_err(|_| {
+            anyhow::anyhow!("BITCOIN_RPC_RETRY_STRATEGY must be either 'exponential' or 'fixed'")
+        })?;
+    let btc_retry_base_delay_ms = env::var("BITCOIN_RPC_RETRY_BASE_DELAY_MS")
+        .unwrap_or_else(|_| "100".to_string())
+        .parse::<u64>()
+        .map_err(|_| {
+            anyhow::anyhow!("BITCOIN_RPC_RETRY_BASE_DELAY_MS must be a non-negative integer")
+        })?;
+    let btc_retry_jitter = env::var("BITCOIN_RPC_RETRY_JITTER")
+        .unwrap_or_else(|_| "true".to_string())
+        .parse::<bool>()
+        .map_err(|_| {
+            anyhow::anyhow!("BITCOIN_RPC_RETRY_JITTER must be either 'true' or 'false'")
+        })?;
+    let btc_retry_max_elapsed_ms = env::var("BITCOIN_RPC_RETRY_MAX_ELAPSED_MS")
+        .unwrap_or_else(|_| "0".to_string())
+        .parse::<u64>()
+        .map_err(|_| {
+            anyhow::anyhow!("BITCOIN_RPC_RETRY_MAX_ELAPSED_MS must be a non-negative integer")
+        })?;
+    let btc_call_budget_ms = env::var("BITCOIN_RPC_CALL_BUDGET_MS")
+        .unwrap_or_else(|_| "0".to_string())
+        .parse::<u64>()
+        .map_err(|_| {
+            anyhow::anyhow!("BITCOIN_RPC_CALL_BUDGET_MS must be a non-negative integer")
+        })?;
+    let btc_circuit_breaker_threshold = env::var("BITCOIN_RPC_CIRCUIT_BREAKER_THRESHOLD")
+        .unwrap_or_else(|_| "5".to_string())
+        .parse::<u32>()
+        .map_err(|_| {
+            anyhow::anyhow!("BITCOIN_RPC_CIRCUIT_BREAKER_THRESHOLD must be a non-negative integer")
+        })?;
+    let btc_circuit_breaker_cooldown_secs = env::var("BITCOIN_RPC_CIRCUIT_BREAKER_COOLDOWN_SECS")
+        .unwrap_or_else(|_| "30".to_string())
+        .parse::<u64>()
+        .map_err(|_| {
+            anyhow::anyhow!(
+                "BITCOIN_RPC_CIRCUIT_BREAKER_COOLDOWN_SECS must be a non-negative integer"
+            )
+        })?;
+    let btc_cache_ttl_secs = env::var("BITCOIN_CONFIRMATION_CACHE_TTL_SECS")
+        .unwrap_or_else(|_| "0".to_string())
+        .parse::<u64>()
+        .map_err(|_| {
+            anyhow::anyhow!("BITCOIN_CONFIRMATION_CACHE_TTL_SECS must be a non-negative integer")
+        })?;
+    let btc_cache_capacity = env::var("BITCOIN_CONFIRMATION_CACHE_CAPACITY")
+        .unwrap_or_else(|_| "100000".to_string())
+        .parse::<usize>()
+        .map_err(|_| {
+            anyhow::anyhow!("BITCOIN_CONFIRMATION_CACHE_CAPACITY must be a non-negative integer")
+        })?;
+    let btc_cache_warmup_txids = env::var("BITCOIN_CACHE_WARMUP_TXIDS")
+        .unwrap_or_else(|_| "0".to_string())
+        .parse::<usize>()
+        .map_err(|_| {
+            anyhow::anyhow!("BITCOIN_CACHE_WARMUP_TXIDS must be a non-negative integer")
+        })?;
+    let btc_double_spend_watch_capacity = env::var("BITCOIN_DOUBLE_SPEND_WATCH_CAPACITY")
+        .unwrap_or_else(|_| "0".to_string())
+        .parse::<usize>()
+        .map_err(|_| {
+            anyhow::anyhow!("BITCOIN_DOUBLE_SPEND_WATCH_CAPACITY must be a non-negative integer")
+        })?;
+    let btc_revert_dead_txs = env::var("BITCOIN_REVERT_DEAD_TXS")
+        .unwrap_or_else(|_| "false".to_string())
+        .parse::<bool>()
+        .map_err(|_| anyhow::anyhow!("BITCOIN_REVERT_DEAD_TXS must be either 'true' or 'false'"))?;
+    let btc_spv_start_height = env::var("BITCOIN_SPV_START_HEIGHT")
+        .ok()
+        .map(|height| height.parse::<u64>())
+        .transpose()
+        .map_err(|_| anyhow::anyhow!("BITCOIN_SPV_START_HEIGHT must be a non-negative integer"))?;
+    let btc_spv_start_hash = env::var("BITCOIN_SPV_START_HASH")
+        .ok()
+        .map(|hash| hash.parse::<bitcoin::BlockHash>())
+        .transpose()
+        .map_err(|_| anyhow::anyhow!("BITCOIN_SPV_START_HASH must be a block hash"))?;
+    let btc_spv_sync_interval_secs = env::var("BITCOIN_SPV_SYNC_INTERVAL_SECS")
+        .unwrap_or_else(|_| "30".to_string())
+        .parse::<u64>()
+        .ok()
+        .filter(|secs| *secs > 0)
+        .ok_or_else(|| {
+            anyhow::anyhow!("BITCOIN_SPV_SYNC_INTERVAL_SECS must be a positive integer")
+        })?;
+    let btc_rebroadcast_interval_secs = env::var("BITCOIN_REBROADCAST_INTERVAL_SECS")
+        .unwrap_or_else(|_| "600".to_string())
+        .parse::<u64>()
+        .map_err(|_| {
+            anyhow::anyhow!("BITCOIN_REBROADCAST_INTERVAL_SECS must be a non-negative integer")
+        })?;
+    let stale_btc_block_policy = env::var("SOVA_SENTINEL_STALE_BTC_BLOCK_POLICY")
+        .unwrap_or_else(|_| "reject".to_string())
+        .parse::<StaleBtcBlockPolicy>()
+        .map_err(|_| {
+            anyhow::anyhow!(
+                "SOVA_SENTINEL_STALE_BTC_BLOCK_POLICY must be one of 'reject', 'clamp' or 'locked'"
+            )
+        })?;
+
+    let preferred_batch_size = env::var("SOVA_SENTINEL_PREFERRED_BATCH_SIZE")
+        .unwrap_or_else(|_| "500".to_string())
+        .parse::<u32>()
+        .map_err(|_| {
+            anyhow::anyhow!("SOVA_SENTINEL_PREFERRED_BATCH_SIZE must be a non-negative integer")
+        })?;
+    let max_batch_size = env::var("SOVA_SENTINEL_MAX_BATCH_SIZE")
+        .unwrap_or_else(|_| DEFAULT_MAX_BATCH_SIZE.to_string())
+        .parse::<u32>()
+        .map_err(|_| {
+            anyhow::anyhow!("SOVA_SENTINEL_MAX_BATCH_SIZE must be a non-negative integer")
+        })?;
+    if max_batch_size > 0 && preferred_batch_size > max_batch_size {
+        return Err(
+            "SOVA_SENTINEL_PREFERRED_BATCH_SIZE must not exceed SOVA_SENTINEL_MAX_BATCH_SIZE"
+                .into(),
+        );
+    }
+    let batch_size_hints = BatchSizeHints {
+        preferred: preferred_batch_size,
+        max: max_batch_size,
+    };
+    let reservation_timeout_secs = env::var("SOVA_SENTINEL_RESERVATION_TIMEOUT_SECS")
+        .unwrap_or_else(|_| DEFAULT_RESERVATION_TIMEOUT.as_secs().to_string())
+        .parse::<u64>()
+        .map_err(|_| {
+            anyhow::anyhow!("SOVA_SENTINEL_RESERVATION_TIMEOUT_SECS must be a non-negative integer")
+        })?;
+
+    let db_supervisor_interval_secs = env::var("SOVA_SENTINEL_DB_SUPERVISOR_INTERVAL_SECS")
+        .unwrap_or_else(|_| "5".to_string())
+        .parse::<u64>()
+        .map_err(|_| {
+            anyhow::anyhow!(
+                "SOVA_SENTINEL_DB_SUPERVISOR_INTERVAL_SECS must be a non-negative integer"
+            )
+        })?;
+    let db_fallback_path = env::var("SOVA_SENTINEL_DB_FALLBACK_PATH").ok();
 
-    let addr = format!("{}:{}", host, port).parse()?;
+    let trace_sample_rate = env::var("SOVA_SENTINEL_TRACE_SAMPLE_RATE")
+        .unwrap_or_else(|_| "1".to_string())
+        .parse::<f64>()
+        .ok()
+        .filter(|rate| (0.0..=1.0).contains(rate))
+        .ok_or_else(|| {
+            anyhow::anyhow!("SOVA_SENTINEL_TRACE_SAMPLE_RATE must be a number between 0 and 1")
+        })?;
+    let trace_sampler = Arc::new(
+        TraceSampler::new(trace_sample_rate).with_method_rates(&config.logging.sample_rates),
+    );
 
-    // Initialize database with thread-safe configuration
-    let conn = rusqlite::Connection::open_with_flags(
-        &db_path,
-        rusqlite::OpenFlags::SQLITE_OPEN_READ_WRITE
-            | rusqlite::OpenFlags::SQLITE_OPEN_CREATE
-            | rusqlite::OpenFlags::SQLITE_OPEN_FULL_MUTEX,
-    )?;
+    // Fault injection, for development only
+    let fault_rate = |name: &str| {
+        env::var(name)
+            .unwrap_or_else(|_| "0".to_string())
+            .parse::<f64>()
+            .ok()
+            .filter(|rate| (0.0..=1.0).contains(rate))
+            .ok_or_else(|| anyhow::anyhow!("{} must be a number between 0 and 1", name))
+    };
+    let chaos_max_delay_ms = env::var("SOVA_SENTINEL_CHAOS_MAX_DELAY_MS")
+        .unwrap_or_else(|_| "1000".to_string())
+        .parse::<u64>()
+        .map_err(|_| {
+            anyhow::anyhow!("SOVA_SENTINEL_CHAOS_MAX_DELAY_MS must be a non-negative integer")
+        })?;
+    let db_faults = Arc::new(FaultInjector::new(
+        fault_rate("SOVA_SENTINEL_CHAOS_DB_FAILURE_RATE")?,
+        fault_rate("SOVA_SENTINEL_CHAOS_DB_DELAY_RATE")?,
+        Duration::from_millis(chaos_max_delay_ms),
+    ));
+    let bitcoin_faults = Arc::new(FaultInjector::new(
+        fault_rate("SOVA_SENTINEL_CHAOS_BITCOIN_FAILURE_RATE")?,
+        fault_rate("SOVA_SENTINEL_CHAOS_BITCOIN_DELAY_RATE")?,
+        Duration::from_millis(chaos_max_delay_ms),
+    ));
+    // Faults must never reach a sentinel guarding mainnet locks, so the network has to be known
+    // to be another one, or the Bitcoin node mocked
+    if db_faults.is_enabled() || bitcoin_faults.is_enabled() {
+        match btc_network {
+            Some(bitcoin::Network::Bitcoin) => {
+                return Err("SOVA_SENTINEL_CHAOS_* fault injection is refused on mainnet".into());
+            }
+            None if !rpc_connection_type.eq_ignore_ascii_case("mock") => {
+                return Err(
+                    "SOVA_SENTINEL_CHAOS_* fault injection requires BITCOIN_NETWORK to \
+                            name a test network, or BITCOIN_RPC_CONNECTION_TYPE=mock"
+                        .into(),
+                );
+            }
+            _ => {}
+        }
+    }
+
+    // Privileged endpoints, the admin service and metrics, can be bound apart from the data plane
+    let admin_host = env::var("SOVA_SENTINEL_ADMIN_HOST").unwrap_or_else(|_| host.clone());
+    let admin_port = env::var("SOVA_SENTINEL_ADMIN_PORT")
+        .unwrap_or_else(|_| "0".to_string())
+        .parse::<u16>()
+        .map_err(|_| anyhow::anyhow!("SOVA_SENTINEL_ADMIN_PORT must be a valid port number"))?;
+    let metrics_port = env::var("SOVA_SENTINEL_METRICS_PORT")
+        .unwrap_or_else(|_| "0".to_string())
+        .parse::<u16>()
+        .map_err(|_| anyhow::anyhow!("SOVA_SENTINEL_METRICS_PORT must be a valid port number"))?;
+    let dashboard_port = env::var("SOVA_SENTINEL_DASHBOARD_PORT")
+        .unwrap_or_else(|_| "0".to_string())
+        .parse::<u16>()
+        .map_err(|_| anyhow::anyhow!("SOVA_SENTINEL_DASHBOARD_PORT must be a valid port number"))?;
+
+    let journal_path = env::var("SOVA_SENTINEL_JOURNAL_PATH").ok();
+    let record_dir = env::var("SOVA_SENTINEL_RECORD_DIR").ok();
+    let mut shadow_mode = env::var("SOVA_SENTINEL_SHADOW_MODE")
+        .unwrap_or_else(|_| "false".to_string())
+        .parse::<bool>()
+        .map_err(|_| {
+            anyhow::anyhow!("SOVA_SENTINEL_SHADOW_MODE must be either 'true' or 'false'")
+        })?;
+    let implicit_unlocks = env::var("SOVA_SENTINEL_IMPLICIT_UNLOCKS")
+        .unwrap_or_else(|_| "false".to_string())
+        .parse::<bool>()
+        .map_err(|_| {
+            anyhow::anyhow!("SOVA_SENTINEL_IMPLICIT_UNLOCKS must be either 'true' or 'false'")
+        })?;
+    let attestation_key = env::var("SOVA_SENTINEL_SIGNING_KEY_FILE")
+        .ok()
+        .map(|path| AttestationKey::load(Path::new(&path)))
+        .transpose()?;
+    if let Some(key) = &attestation_key {
+        tracing::info!(
+            "Signing status responses with public key {}",
+            key.public_key()
+        );
+    }
+
+    let lock_commitment_interval_secs = env::var("SOVA_SENTINEL_LOCK_COMMITMENT_INTERVAL_SECS")
+        .unwrap_or_else(|_| "60".to_string())
+        .parse::<u64>()
+        .map_err(|_| {
+            anyhow::anyhow!(
+                "SOVA_SENTINEL_LOCK_COMMITMENT_INTERVAL_SECS must be a non-negative integer"
+            )
+        })?;
+
+    let mut components = config.components();
+    if shadow_mode {
+        // Nothing that changes locks or acts on them runs in shadow mode
+        tracing::warn!("Running in shadow mode: status queries are evaluated but not applied");
+        components.slot_lock_writes = false;
+        components.rebroadcast = false;
+    }
+    let sqlite_locks = storage == StorageBackend::Sqlite;
+    if !sqlite_locks {
+        // Everything that reads locks outside the SlotLock service reads them from SQLite, where
+        // it would find none. Lock commitments are only refused when asked for: by default they
+        // are just off.
+        let lock_commitments_set = env::var("SOVA_SENTINEL_LOCK_COMMITMENT_INTERVAL_SECS").is_ok()
+            && lock_commitment_interval_secs > 0;
+        for (configured, feature) in [
+            (config.retention.is_some(), "Retention"),
+            (config.backup.is_some(), "Backups"),
+            (config.replication.is_some(), "Replication"),
+            (config.revert_executor.is_some(), "Revert delivery"),
+            (
+                lock_commitments_set,
+                "SOVA_SENTINEL_LOCK_COMMITMENT_INTERVAL_SECS",
+            ),
+            (journal_path.is_some(), "SOVA_SENTINEL_JOURNAL_PATH"),
+            (record_dir.is_some(), "SOVA_SENTINEL_RECORD_DIR"),
+        ] {
+            if configured {
+                return Err(format!("{} requires SOVA_SENTINEL_STORAGE=sqlite", feature).into());
+            }
+        }
+        tracing::warn!(
+            "Storing locks in redb: rebroadcasting, cache warmup, lock commitments and the admin \
+             service, with its dashboard, are switched off"
+        );
+        components.rebroadcast = false;
+        components.cache_warmup = false;
+        components.admin = false;
+    }
+
+    let labels = DeploymentLabels::from_env();
+    let metrics = Arc::new(Metrics::new(&labels));
+
+    let admin_address = (admin_port > 0).then(|| format!("{}:{}", admin_host, admin_port));
+    let listeners = config
+        .listeners(&format!("{}:{}", host, port), admin_address.as_deref())?
+        .into_iter()
+        .map(|listener| {
+            let address = listener
+                .address
+                .parse::<SocketAddr>()
+                .map_err(|_| anyhow::anyhow!("Invalid listen address {}", listener.address))?;
+            for (service, mounted) in [
+                (ListenerService::SlotLock, components.slot_lock),
+                (ListenerService::Admin, components.admin),
+            ] {
+                if listener
+                    .services
+                    .as_ref()
+                    .is_some_and(|s| s.contains(&service))
+                    && !mounted
+                {
+                    if service == ListenerService::Admin && !sqlite_locks {
+                        anyhow::bail!(
+                            "Listener {} serves the admin service, which requires \
+                             SOVA_SENTINEL_STORAGE=sqlite",
+                            listener.address
+                        );
+                    }
+                    anyhow::bail!(
+                        "Listener {} serves {:?}, which no server profile mounts",
+                        listener.address,
+                        service
+                    );
+                }
+            }
+            let auth_token = listener
+                .auth_token_file
+                .as_deref()
+                .map(|path| AuthToken::load(Path::new(path)))
+                .transpose()?;
+            let tls = listener.tls_config()?;
+            Ok((address, listener, auth_token, tls))
+        })
+        .collect::<Result<Vec<_>>>()?;
 
-    let db = Database::new(conn)?;
+    // Check the database before anything else, so a misconfigured path fails before the Bitcoin
+    // node is contacted
+    let db = preflight::open_database(Path::new(&db_path), db_key)?;
+    let safe_mode = preflight::check_integrity(&db, on_corruption)?;
+    if safe_mode {
+        // The connection rejects writes; status queries are evaluated like in shadow mode so
+        // they don't fail on them
+        shadow_mode = true;
+        components.slot_lock_writes = false;
+        components.rebroadcast = false;
+    }
+    let db = if db_faults.is_enabled() {
+        tracing::warn!("Injecting database faults: {:?}", db_faults);
+        db.with_faults(db_faults)
+    } else {
+        db
+    };
+    // Tags the lock changes and revert counts this sentinel writes; a safe-mode connection
+    // rejects the write, and writes nothing to tag
+    if !safe_mode {
+        db.set_instance_id(&labels.instance_id)?;
+    }
+    let redb_store = (components.slot_lock && !sqlite_locks)
+        .then(|| preflight::open_redb_store(Path::new(&redb_path)))
+        .transpose()?;
+
+    // Headers are validated against the consensus rules of the network, so it must be explicit
+    let header_store = match (btc_spv_start_height, btc_network) {
+        (Some(_), None) => return Err("BITCOIN_SPV_START_HEIGHT requires BITCOIN_NETWORK".into()),
+        (Some(_), Some(network)) => Some(Arc::new(HeaderStore::new(db.clone(), network))),
+        (None, _) => None,
+    };
+
+    // The compact filter client follows the chain itself and keeps the header store in sync
+    let uses_compact_filters = rpc_connection_type.eq_ignore_ascii_case("compactfilters");
+    // It only watches transactions in memory and learns of pending ones again after a restart
+    // when they are rebroadcast
+    if uses_compact_filters
+        && components.slot_lock_writes
+        && !(components.rebroadcast && btc_rebroadcast_interval_secs > 0)
+    {
+        return Err(
+            "BITCOIN_RPC_CONNECTION_TYPE compactfilters requires rebroadcasting, which \
+                    SOVA_SENTINEL_STORAGE=redb and BITCOIN_REBROADCAST_INTERVAL_SECS=0 switch off"
+                .into(),
+        );
+    }
 
     // Create Bitcoin service
     let rpc_client: Arc<dyn BitcoinRpcClient> = match rpc_connection_type.to_lowercase().as_str() {
@@ -75,18 +536,413 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             btc_rpc_user.clone(),
             btc_rpc_pass.clone(),
         )),
+        "compactfilters" => {
+            let (Some(network), Some(header_store), Some(start_height), Some(start_hash)) = (
+                btc_network,
+                &header_store,
+                btc_spv_start_height,
+                btc_spv_start_hash,
+            ) else {
+                return Err("BITCOIN_RPC_CONNECTION_TYPE compactfilters requires \
+                            BITCOIN_SPV_START_HEIGHT and BITCOIN_SPV_START_HASH"
+                    .into());
+            };
+            let client = CompactFilterClient::new(
+                network,
+                btc_p2p_peer.clone(),
+                header_store.clone(),
+                start_height,
+                start_hash,
+                Duration::from_secs(btc_spv_sync_interval_secs),
+            );
+            tokio::spawn(client.clone().run());
+            Arc::new(client)
+        }
+        "mock" => {
+            tracing::warn!(
+                "Using the mock Bitcoin node: locks confirm without any Bitcoin transaction, \
+                 for local development only"
+            );
+            Arc::new(
+                MockRpcClient::new(btc_confirmation_threshold, btc_mock_confirmations)
+                    .with_failure_rate(btc_mock_failure_rate),
+            )
+        }
         other => {
             return Err(format!("Unsupported rpc_connection_type: {}", other).into());
         }
     };
+    let rpc_client: Arc<dyn BitcoinRpcClient> = if bitcoin_faults.is_enabled() {
+        tracing::warn!("Injecting Bitcoin RPC faults: {:?}", bitcoin_faults);
+        Arc::new(ChaosRpcClient::new(rpc_client, bitcoin_faults))
+    } else {
+        rpc_client
+    };
+
+    // A value of 0 disables the corresponding time limit
+    let retry_policy = RetryPolicy {
+        strategy: btc_retry_strategy,
+        max_retries: btc_max_retries,
+        base_delay: Duration::from_millis(btc_retry_base_delay_ms),
+        jitter: btc_retry_jitter,
+        max_elapsed: (btc_retry_max_elapsed_ms > 0)
+            .then(|| Duration::from_millis(btc_retry_max_elapsed_ms)),
+        call_budget: (btc_call_budget_ms > 0).then(|| Duration::from_millis(btc_call_budget_ms)),
+    };
 
     let bitcoin_service =
-        BitcoinRpcService::new(rpc_client, btc_confirmation_threshold, btc_max_retries);
+        BitcoinRpcService::new(rpc_client, btc_confirmation_threshold, btc_max_retries)
+            .with_retry_policy(retry_policy)
+            .with_circuit_breaker(CircuitBreaker::new(
+                btc_circuit_breaker_threshold,
+                Duration::from_secs(btc_circuit_breaker_cooldown_secs),
+            ))
+            .with_confirmation_cache(ConfirmationCache::new(
+                Duration::from_secs(btc_cache_ttl_secs),
+                btc_cache_capacity,
+            ))
+            .with_double_spend_detection(InputWatcher::new(btc_double_spend_watch_capacity))
+            .with_dead_tx_reverts(btc_revert_dead_txs);
+
+    let bitcoin_service = match &header_store {
+        Some(header_store) => bitcoin_service.with_spv_verification(header_store.clone()),
+        None => bitcoin_service,
+    };
+
+    if components.uses_bitcoin_node() && !uses_compact_filters {
+        preflight::check_bitcoin_node(&bitcoin_service, &btc_rpc_url, btc_network).await?;
+    }
+
+    // Prime the confirmation cache with the most recently active txids before serving, so the
+    // first block of status queries after a restart doesn't stampede the Bitcoin node
+    if components.cache_warmup && btc_cache_warmup_txids > 0 {
+        let txids = db.recent_active_txids(btc_cache_warmup_txids)?;
+        let warmed = bitcoin_service.warm_cache(&txids).await;
+        tracing::info!(
+            "Warmed confirmation cache with {}/{} recently active txids",
+            warmed,
+            txids.len()
+        );
+    }
+
+    let dashboard = (components.admin && dashboard_port > 0)
+        .then(|| Dashboard::new(db.clone(), Arc::new(bitcoin_service.clone())));
 
-    let service = SlotLockServiceImpl::new(db, bitcoin_service, btc_revert_threshold);
+    let health = HealthReporter::default();
+    health.set_serving(!safe_mode);
+    // An in-memory database has no file to lose or reopen, and reopening a corrupt one would
+    // lift safe mode
+    if db_supervisor_interval_secs > 0
+        && !preflight::is_in_memory(Path::new(&db_path))
+        && !safe_mode
+    {
+        let supervisor = DatabaseSupervisor::new(
+            db.clone(),
+            &db_path,
+            health.clone(),
+            Duration::from_secs(db_supervisor_interval_secs),
+        );
+        let supervisor = match &db_fallback_path {
+            Some(path) => supervisor.with_fallback(path),
+            None => supervisor,
+        };
+        tokio::spawn(supervisor.run());
+    }
 
+    // Safe mode leaves the corrupt database, and the backups taken before it was, untouched
+    if let (Some(maintenance), false) = (&config.maintenance, safe_mode) {
+        tokio::spawn(
+            MaintenanceScheduler::new(db.clone(), Duration::from_secs(maintenance.interval_secs))
+                .with_windows(maintenance.windows()?)
+                .with_vacuum_pages(maintenance.vacuum_pages)
+                .with_health(health.clone())
+                .with_metrics(metrics.clone())
+                .run(),
+        );
+    }
+
+    if let (Some(backup_config), false) = (&config.backup, safe_mode) {
+        let scheduler = BackupScheduler::new(
+            db.clone(),
+            Duration::from_secs(backup_config.interval_secs),
+            backup_config.keep,
+        );
+        let scheduler = match &backup_config.path {
+            Some(path) => scheduler.with_directory(path),
+            None => scheduler,
+        };
+        let scheduler = match &backup_config.s3 {
+            Some(s3_config) => {
+                let credentials = S3Credentials {
+                    access_key_id: env::var("AWS_ACCESS_KEY_ID").map_err(|_| {
+                        anyhow::anyhow!("AWS_ACCESS_KEY_ID is required for S3 backups")
+                    })?,
+                    secret_access_key: env::var("AWS_SECRET_ACCESS_KEY").map_err(|_| {
+                        anyhow::anyhow!("AWS_SECRET_ACCESS_KEY is required for S3 backups")
+                    })?,
+                };
+                scheduler.with_s3(S3Store::new(s3_config, credentials)?)
+            }
+            None => scheduler,
+        };
+        tokio::spawn(scheduler.run());
+    }
+
+    let pruner = match &config.retention {
+        Some(retention) => {
+            let pruner = Pruner::new(
+                db.clone(),
+                RetentionPolicy {
+                    max_age_blocks: retention.max_age_blocks,
+                    max_age_days: retention.max_age_days,
+                    purge_after_days: retention.purge_after_days,
+                },
+            )
+            .with_metrics(metrics.clone());
+            let pruner = match &retention.purge_path {
+                Some(path) => pruner.with_purge_path(path),
+                None => pruner,
+            };
+            if !shadow_mode {
+                tokio::spawn(
+                    pruner
+                        .clone()
+                        .run(Duration::from_secs(retention.interval_secs)),
+                );
+            }
+            pruner
+        }
+        None => Pruner::new(db.clone(), RetentionPolicy::default()).with_metrics(metrics.clone()),
+    };
+
+    if let (Some(header_store), Some(start_height), false) =
+        (header_store, btc_spv_start_height, uses_compact_filters)
+    {
+        let header_sync = HeaderSync::new(
+            header_store,
+            bitcoin_service.clone(),
+            start_height,
+            Duration::from_secs(btc_spv_sync_interval_secs),
+        );
+        let header_sync = match btc_spv_start_hash {
+            Some(hash) => header_sync.with_start_hash(hash),
+            None => header_sync,
+        };
+        tokio::spawn(header_sync.run());
+    }
+
+    let lock_commitments =
+        if components.slot_lock && sqlite_locks && lock_commitment_interval_secs > 0 {
+            let committer = LockCommitter::new(
+                db.clone(),
+                Duration::from_secs(lock_commitment_interval_secs),
+            );
+            committer.commit()?;
+            let commitments = committer.commitments();
+            tokio::spawn(committer.run());
+            Some(commitments)
+        } else {
+            None
+        };
+
+    let replication = Replication::load(
+        db.clone(),
+        config
+            .replication
+            .as_ref()
+            .map(|replication| replication.primary_url.clone()),
+    )?;
+    let replication_state = replication.state();
+    tracing::info!(
+        "Replication role: {}, epoch {}",
+        replication_state.role.as_str(),
+        replication_state.epoch
+    );
+    if let (Some(replication_config), ReplicationRole::Standby) =
+        (&config.replication, replication_state.role)
+    {
+        let replicator = Replicator::new(
+            replication.clone(),
+            Duration::from_millis(replication_config.poll_interval_ms),
+        );
+        tokio::spawn(async move {
+            if let Err(e) = replicator.run().await {
+                tracing::error!("Replication failed: {:#}", e);
+            }
+        });
+    }
+
+    // Status queries and writes only reach the database through the SlotLock service
+    let journal = match &journal_path {
+        Some(path) if components.slot_lock => {
+            let journal = Journal::open(Path::new(path), db.clone())?;
+            tracing::info!("Journaling state-changing requests to {}", path);
+            Some(journal)
+        }
+        _ => None,
+    };
+    let recorder = match &record_dir {
+        Some(dir) if components.slot_lock => {
+            let recorder = Recorder::create(
+                Path::new(dir),
+                &db,
+                btc_confirmation_threshold,
+                btc_revert_threshold,
+            )?;
+            tracing::warn!("Recording request traces to {}", dir);
+            Some(recorder)
+        }
+        _ => None,
+    };
+
+    if components.rebroadcast && btc_rebroadcast_interval_secs > 0 {
+        let rebroadcaster = Rebroadcaster::new(
+            db.clone(),
+            bitcoin_service.clone(),
+            Duration::from_secs(btc_rebroadcast_interval_secs),
+        );
+        tokio::spawn(rebroadcaster.run());
+    }
+
+    // Reverts are only produced by the SlotLock service, so no executor is needed without it
+    let revert_dispatcher = match &config.revert_executor {
+        Some(executor_config) if components.slot_lock && !shadow_mode => {
+            let executor: Arc<dyn RevertExecutor> = match executor_config.protocol {
+                RevertExecutorProtocol::Http => {
+                    Arc::new(HttpRevertExecutor::new(executor_config.url.clone()))
+                }
+                RevertExecutorProtocol::Grpc => {
+                    Arc::new(GrpcRevertExecutor::new(executor_config.url.clone())?)
+                }
+            };
+            Some(
+                RevertDispatcher::new(
+                    db.clone(),
+                    executor,
+                    Duration::from_secs(executor_config.retry_interval_secs),
+                    executor_config.max_attempts,
+                )
+                .with_labels(labels.clone()),
+            )
+        }
+        _ => None,
+    };
+    let revert_notify = revert_dispatcher.as_ref().map(RevertDispatcher::notifier);
+    if let Some(revert_dispatcher) = revert_dispatcher {
+        tokio::spawn(revert_dispatcher.run());
+    }
+
+    // Seeded from the config file, adjusted at runtime through the admin service
+    let contract_thresholds = Arc::new(ContractThresholds::new(config.contract_thresholds.clone()));
+    let admin_service = components.admin.then(|| {
+        AdminServiceServer::new(
+            AdminServiceImpl::new(db.clone())
+                .with_contract_thresholds(contract_thresholds.clone())
+                .with_pruner(pruner)
+                .with_replication(replication.clone()),
+        )
+    });
+    // Shared with the alert rules, which watch the lock changes the service publishes
+    let slot_events = SlotEvents::default();
+    if let (Some(alerting), true) = (&config.alerting, components.slot_lock) {
+        let alerts = RevertAlerts::new(alerting.rules.clone(), slot_events.clone())
+            .with_metrics(metrics.clone())
+            .with_labels(labels.clone());
+        let alerts = match &alerting.webhook_url {
+            Some(url) => alerts.with_webhook(url.clone()),
+            None => alerts,
+        };
+        tokio::spawn(alerts.run());
+    }
+    let slot_lock_service = components.slot_lock.then(|| {
+        let service = SlotLockServiceImpl::new(
+            db,
+            RecordingBitcoinService::new(bitcoin_service),
+            btc_revert_threshold,
+        )
+        .with_labels(labels.clone())
+        .with_metrics(metrics.clone())
+        .with_events(slot_events)
+        .with_max_logged_slots(config.logging.max_logged_slots)
+        .with_read_only(!components.slot_lock_writes)
+        .with_shadow(shadow_mode)
+        .with_implicit_unlocks(implicit_unlocks)
+        .with_replication(replication.clone())
+        .with_stale_btc_block_policy(stale_btc_block_policy)
+        .with_contract_thresholds(contract_thresholds)
+        .with_namespace_thresholds(config.namespace_thresholds.clone())
+        .with_batch_size_hints(batch_size_hints)
+        .with_reservation_timeout(Duration::from_secs(reservation_timeout_secs));
+        let service = match revert_notify {
+            Some(notify) => service.with_revert_delivery(notify),
+            None => service,
+        };
+        let service = match attestation_key {
+            Some(key) => service.with_attestation_key(key),
+            None => service,
+        };
+        let service = match &config.identity {
+            Some(identity) => service.with_active_lock_limits(identity.clone()),
+            None => service,
+        };
+        match lock_commitments {
+            Some(commitments) => service.with_lock_commitments(commitments),
+            None => service,
+        }
+    });
+    // Only one of them is served, the one of the backend locks are stored in
+    let (slot_lock_service, redb_slot_lock_service) = match (slot_lock_service, redb_store) {
+        (Some(service), Some(store)) => {
+            let service = JournaledSlotLockService::new(service.with_store(store));
+            (None, Some(slot_lock_server(service)))
+        }
+        (Some(service), None) => {
+            let service = JournaledSlotLockService::new(service);
+            let service = match journal {
+                Some(journal) => service.with_journal(journal),
+                None => service,
+            };
+            let service = match recorder {
+                Some(recorder) => service.with_recorder(recorder),
+                None => service,
+            };
+            (Some(slot_lock_server(service)), None)
+        }
+        (None, _) => (None, None),
+    };
+
+    tracing::info!(
+        "Deployment labels: environment={}, region={}, instance_id={}",
+        labels.environment,
+        labels.region,
+        labels.instance_id
+    );
+    tracing::info!(
+        "Server profiles: {:?}, components: {:?}",
+        config.server.profiles,
+        components
+    );
     tracing::info!("Database path: {}", db_path);
-    tracing::info!("SlotLock server listening on {}", addr);
+    if !sqlite_locks {
+        tracing::info!("Lock store path: {}", redb_path);
+    }
+
+    if components.metrics && metrics_port > 0 {
+        let metrics_addr = format!("{}:{}", admin_host, metrics_port).parse()?;
+        tokio::spawn(async move {
+            if let Err(e) = metrics::serve(metrics_addr, metrics).await {
+                tracing::error!("Metrics server failed: {}", e);
+            }
+        });
+    }
+    if let Some(dashboard) = dashboard {
+        let dashboard_addr = format!("{}:{}", admin_host, dashboard_port).parse()?;
+        tokio::spawn(async move {
+            if let Err(e) = dashboard::serve(dashboard_addr, dashboard).await {
+                tracing::error!("Dashboard server failed: {}", e);
+            }
+        });
+    }
 
     // Response classifier that doesn't consider `Ok`, `Invalid Argument`, or `Not Found` as
     // failures
@@ -94,21 +950,136 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .with_success(GrpcCode::InvalidArgument)
         .with_success(GrpcCode::NotFound);
 
+    // Credentials are redacted from the request spans
+    let mut sensitive_headers = vec![hyper::header::AUTHORIZATION];
+    if let Some(rate_limit) = &config.rate_limit {
+        sensitive_headers.push(rate_limit.api_key_header.parse()?);
+    }
+    if let Some(identity) = &config.identity {
+        sensitive_headers.push(identity.api_key_header.parse()?);
+    }
+    let role_replication = replication.clone();
+    let instance_id = hyper::header::HeaderValue::from_str(&labels.instance_id)
+        .ok()
+        .filter(|instance_id| !instance_id.is_empty());
     let middleware = ServiceBuilder::new()
         .layer(CompressionLayer::new())
+        .layer(SetSensitiveRequestHeadersLayer::new(sensitive_headers))
+        // Every response carries the sentinel's fencing epoch, so clients that talked to a
+        // promoted standby fence the primary it superseded on their next write
+        .layer(SetResponseHeaderLayer::overriding(
+            hyper::header::HeaderName::from_static(EPOCH_KEY),
+            move |_: &hyper::Response<_>| Some(replication.state().epoch.into()),
+        ))
+        // And names the node that answered, as status queries may be served by a standby
+        .layer(SetResponseHeaderLayer::overriding(
+            hyper::header::HeaderName::from_static(ROLE_KEY),
+            move |_: &hyper::Response<_>| {
+                Some(hyper::header::HeaderValue::from_static(
+                    role_replication.state().role.as_str(),
+                ))
+            },
+        ))
+        .layer(SetResponseHeaderLayer::overriding(
+            hyper::header::HeaderName::from_static(INSTANCE_ID_KEY),
+            move |_: &hyper::Response<_>| instance_id.clone(),
+        ))
+        // Every request gets an id, unless the caller sent one, which is logged with every line
+        // about the request and echoed in the response
+        .layer(SetRequestIdLayer::new(
+            hyper::header::HeaderName::from_static(REQUEST_ID_KEY),
+            MakeRequestUuid,
+        ))
+        .layer(PropagateRequestIdLayer::new(
+            hyper::header::HeaderName::from_static(REQUEST_ID_KEY),
+        ))
         .layer(
-            TraceLayer::new(SharedClassifier::new(classifier))
-                .make_span_with(DefaultMakeSpan::new().include_headers(true)),
+            TraceLayer::new(SharedClassifier::new(classifier)).make_span_with(
+                // Tag every request span with its id, with the deployment labels so aggregated
+                // logs can be attributed to the instance that produced them, with its sampling
+                // decision, and with the caller's trace context so exported spans join the
+                // caller's trace
+                move |request: &hyper::Request<_>| {
+                    let sampled = trace_sampler.sample(request.uri().path(), request.headers());
+                    tracing::info_span!(
+                        "request",
+                        request_id = request
+                            .headers()
+                            .get(REQUEST_ID_KEY)
+                            .and_then(|value| value.to_str().ok()),
+                        sampled,
+                        otel.name = %request.uri().path(),
+                        otel.kind = "server",
+                        traceparent = request
+                            .headers()
+                            .get("traceparent")
+                            .and_then(|value| value.to_str().ok()),
+                        method = %request.method(),
+                        uri = %request.uri(),
+                        version = ?request.version(),
+                        headers = ?request.headers(),
+                        environment = %labels.environment,
+                        region = %labels.region,
+                        instance_id = %labels.instance_id,
+                    )
+                },
+            ),
+        )
+        // Per-peer quotas, so one integration can't starve the database connection shared with
+        // the block-critical path
+        .option_layer(
+            config
+                .rate_limit
+                .clone()
+                .map(|rate_limit| RateLimitLayer::new(Arc::new(RateLimiter::new(rate_limit)))),
         )
-        .into_inner();
-
-    Server::builder()
-        .timeout(Duration::from_secs(20))
-        .layer(middleware)
-        .add_service(SlotLockServiceServer::new(service))
-        .add_service(HealthServer::new(HealthService))
-        .serve(addr)
-        .await?;
+        // Callers recorded with the locks they make
+        .option_layer(config.identity.clone().map(IdentityLayer::new));
+
+    let mut servers = Vec::with_capacity(listeners.len());
+    for (address, listener, auth_token, tls) in listeners {
+        tracing::info!(
+            "Listening on {}: services={}, auth_token={}, tls={}, client_ca={}",
+            address,
+            listener
+                .services
+                .as_ref()
+                .map_or("all".to_string(), |services| format!("{:?}", services)),
+            auth_token.is_some(),
+            tls.is_some(),
+            listener.client_ca.is_some()
+        );
+        let server = match tls {
+            Some(tls) => Server::builder().tls_config(tls)?,
+            None => Server::builder(),
+        };
+        let router = server
+            .timeout(REQUEST_TIMEOUT)
+            .layer(
+                middleware
+                    .clone()
+                    .option_layer(auth_token.map(AuthLayer::new))
+                    .into_inner(),
+            )
+            .add_service(HealthServer::new(HealthService::new(health.clone())))
+            .add_optional_service(
+                slot_lock_service
+                    .clone()
+                    .filter(|_| listener.serves(ListenerService::SlotLock)),
+            )
+            .add_optional_service(
+                redb_slot_lock_service
+                    .clone()
+                    .filter(|_| listener.serves(ListenerService::SlotLock)),
+            )
+            .add_optional_service(
+                admin_service
+                    .clone()
+                    .filter(|_| listener.serves(ListenerService::Admin)),
+            );
+        servers.push(router.serve(address));
+    }
+    futures::future::try_join_all(servers).await?;
 
     Ok(())
 }
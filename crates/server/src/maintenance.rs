@@ -0,0 +1,314 @@
+//! Scheduled database maintenance. During low-traffic windows the database is checked for
+//! corruption, its free pages are returned to the filesystem and its write-ahead log is
+//! checkpointed. A corrupt database takes the sentinel out of service.
+
+use crate::db::Database;
+use crate::metrics::Metrics;
+use crate::service::HealthReporter;
+use anyhow::{Context, Result};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const MINUTES_PER_DAY: u32 = 24 * 60;
+
+/// Daily UTC time range maintenance may run in, written `HH:MM-HH:MM`. A range ending before it
+/// starts spans midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MaintenanceWindow {
+    /// Minutes after midnight the window opens at
+    start: u32,
+    /// Minutes after midnight the window closes at, exclusive
+    end: u32,
+}
+
+impl MaintenanceWindow {
+    /// Returns whether the window is open at unix time `secs`
+    pub fn contains(&self, secs: u64) -> bool {
+        let minute = (secs / 60 % MINUTES_PER_DAY as u64) as u32;
+        if self.start <= self.end {
+            (self.start..self.end).contains(&minute)
+        } else {
+            minute >= self.start || minute < self.end
+        }
+    }
+}
+
+fn parse_minute(time: &str) -> Option<u32> {
+    let (hours, minutes) = time.split_once(':')?;
+    if hours.len() != 2 || minutes.len() != 2 {
+        return None;
+    }
+    let (hours, minutes) = (hours.parse::<u32>().ok()?, minutes.parse::<u32>().ok()?);
+    (hours < 24 && minutes < 60).then_some(hours * 60 + minutes)
+}
+
+impl FromStr for MaintenanceWindow {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let window = s
+            .split_once('-')
+            .and_then(|(start, end)| Some((parse_minute(start)?, parse_minute(end)?)));
+        match window {
+            Some((start, end)) if start != end => Ok(Self { start, end }),
+            _ => anyhow::bail!(
+                "Invalid maintenance window \"{}\", expected a UTC range like 02:00-04:00",
+                s
+            ),
+        }
+    }
+}
+
+/// Outcome of a maintenance run
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MaintenanceReport {
+    /// Problems `PRAGMA quick_check` found, none if the database is intact
+    pub problems: Vec<String>,
+    /// Whether the database was switched to incremental auto-vacuum with a full `VACUUM`
+    pub vacuum_enabled: bool,
+    /// Free pages returned to the filesystem
+    pub vacuumed_pages: u64,
+    /// Write-ahead log pages checkpointed, `None` if the database isn't in WAL mode
+    pub checkpointed_pages: Option<u64>,
+}
+
+/// Runs database maintenance every interval while one of its windows is open
+pub struct MaintenanceScheduler {
+    db: Database,
+    interval: Duration,
+    windows: Vec<MaintenanceWindow>,
+    vacuum_pages: u64,
+    health: HealthReporter,
+    metrics: Arc<Metrics>,
+}
+
+impl MaintenanceScheduler {
+    pub fn new(db: Database, interval: Duration) -> Self {
+        Self {
+            db,
+            interval,
+            windows: Vec::new(),
+            vacuum_pages: 0,
+            health: HealthReporter::default(),
+            metrics: Arc::new(Metrics::default()),
+        }
+    }
+
+    /// Only runs maintenance while one of `windows` is open, instead of at any time
+    pub fn with_windows(mut self, windows: Vec<MaintenanceWindow>) -> Self {
+        self.windows = windows;
+        self
+    }
+
+    /// Frees at most `pages` pages per run, 0 for all of them
+    pub fn with_vacuum_pages(mut self, pages: u64) -> Self {
+        self.vacuum_pages = pages;
+        self
+    }
+
+    /// Sets the health status flipped to `NOT_SERVING` when the database is corrupt
+    pub fn with_health(mut self, health: HealthReporter) -> Self {
+        self.health = health;
+        self
+    }
+
+    /// Sets the metrics maintenance runs are reported to
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Returns whether maintenance may run at unix time `secs`
+    pub fn is_open(&self, secs: u64) -> bool {
+        self.windows.is_empty() || self.windows.iter().any(|window| window.contains(secs))
+    }
+
+    /// Checks the database, then vacuums and checkpoints it if it is intact. A corrupt
+    /// database marks the sentinel `NOT_SERVING`, which it stays until restarted.
+    pub fn maintain(&self) -> Result<MaintenanceReport> {
+        let mut report = MaintenanceReport {
+            problems: self.db.quick_check().context("Integrity check failed")?,
+            ..Default::default()
+        };
+        if report.problems.is_empty() {
+            report.vacuum_enabled = self
+                .db
+                .enable_incremental_vacuum()
+                .context("Failed to enable incremental vacuum")?;
+            report.vacuumed_pages = self
+                .db
+                .incremental_vacuum(self.vacuum_pages)
+                .context("Incremental vacuum failed")?;
+            report.checkpointed_pages = self.db.checkpoint().context("WAL checkpoint failed")?;
+        } else {
+            self.health.set_serving(false);
+        }
+
+        let completed_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        self.metrics.record_maintenance(
+            !report.problems.is_empty(),
+            report.vacuumed_pages,
+            report.checkpointed_pages.unwrap_or(0),
+            completed_at,
+        );
+        Ok(report)
+    }
+
+    /// Runs maintenance every interval while a window is open, forever. Stops once the database
+    /// is found corrupt, leaving it for the operator to restore.
+    pub async fn run(self) {
+        let mut ticker = tokio::time::interval(self.interval);
+        loop {
+            ticker.tick().await;
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            if !self.is_open(now) {
+                continue;
+            }
+            match self.maintain() {
+                Ok(report) if !report.problems.is_empty() => {
+                    tracing::error!(
+                        "Database is corrupt, marking the sentinel NOT_SERVING until it is \
+                         restored: {}",
+                        report.problems.join("; ")
+                    );
+                    return;
+                }
+                Ok(report) => tracing::info!(
+                    "Database maintenance: intact, {}freed {} pages, checkpointed {:?} WAL pages",
+                    if report.vacuum_enabled {
+                        "switched to incremental vacuum, "
+                    } else {
+                        ""
+                    },
+                    report.vacuumed_pages,
+                    report.checkpointed_pages
+                ),
+                Err(e) => tracing::error!("Database maintenance failed: {:#}", e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{SlotInsertData, UnlockReason};
+
+    fn slots(count: u8) -> Vec<SlotInsertData> {
+        (0..count)
+            .map(|idx| SlotInsertData {
+                namespace: String::new(),
+                contract_address: "0x123".to_string(),
+                start_block: 100,
+                btc_block: 200,
+                slot_index: vec![idx],
+                btc_txid: "txid1".to_string(),
+                revert_value: vec![idx; 512],
+                current_value: vec![idx; 512],
+                metadata: Vec::new(),
+                labels: Default::default(),
+                alt_btc_txids: Vec::new(),
+                required_confirmed_txids: 1,
+                locked_by: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_windows() -> Result<()> {
+        let night = "02:00-04:00".parse::<MaintenanceWindow>()?;
+        assert!(!night.contains(2 * 3600 - 1));
+        assert!(night.contains(2 * 3600));
+        assert!(night.contains(86400 + 4 * 3600 - 1));
+        assert!(!night.contains(4 * 3600));
+
+        let midnight = "23:30-00:30".parse::<MaintenanceWindow>()?;
+        assert!(midnight.contains(23 * 3600 + 45 * 60));
+        assert!(midnight.contains(15 * 60));
+        assert!(!midnight.contains(12 * 3600));
+
+        for invalid in [
+            "02:00",
+            "2:00-04:00",
+            "02:00-24:00",
+            "02:60-03:00",
+            "02:00-02:00",
+        ] {
+            assert!(invalid.parse::<MaintenanceWindow>().is_err(), "{}", invalid);
+        }
+
+        let db = Database::new(rusqlite::Connection::open_in_memory()?)?;
+        let scheduler = MaintenanceScheduler::new(db, Duration::from_secs(60));
+        assert!(scheduler.is_open(12 * 3600));
+        let scheduler = scheduler.with_windows(vec![night]);
+        assert!(!scheduler.is_open(12 * 3600));
+        assert!(scheduler.is_open(3 * 3600));
+        Ok(())
+    }
+
+    #[test]
+    fn test_maintain_frees_pages() -> Result<()> {
+        let db = Database::new(rusqlite::Connection::open_in_memory()?)?;
+        let health = HealthReporter::default();
+        let scheduler = MaintenanceScheduler::new(db.clone(), Duration::from_secs(60))
+            .with_health(health.clone());
+
+        let report = scheduler.maintain()?;
+        assert!(report.problems.is_empty());
+        assert!(report.vacuum_enabled);
+        assert_eq!(report.checkpointed_pages, None);
+
+        db.with_transaction(|tx| db.batch_insert_slot_locks(tx, &slots(200)))?;
+        for idx in 0..200u8 {
+            db.unlock_slot("", "0x123", &[idx], 110, UnlockReason::Confirmed)?;
+        }
+        let rows = db.lock_rows(None, 0, 1000)?;
+        let ids: Vec<_> = rows.iter().map(|row| row.id).collect();
+        db.with_transaction(|tx| {
+            db.archive_lock_rows(tx, &ids)?;
+            db.delete_lock_rows(tx, &ids)
+        })?;
+
+        let report = scheduler.maintain()?;
+        assert!(!report.vacuum_enabled);
+        assert!(report.vacuumed_pages > 0);
+        assert_eq!(scheduler.maintain()?.vacuumed_pages, 0);
+        assert!(health.is_serving());
+        Ok(())
+    }
+
+    #[test]
+    fn test_corruption_stops_serving() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("maintenance_{}.db", std::process::id()));
+        {
+            let db = Database::new(rusqlite::Connection::open(&path)?)?;
+            db.with_transaction(|tx| db.batch_insert_slot_locks(tx, &slots(200)))?;
+        }
+        // Scribble over the middle of the file, past the schema on the first page
+        let mut contents = std::fs::read(&path)?;
+        let middle = contents.len() / 2;
+        contents[middle..middle + 4096].fill(0xff);
+        std::fs::write(&path, contents)?;
+
+        let health = HealthReporter::default();
+        let scheduler = MaintenanceScheduler::new(
+            Database::new(rusqlite::Connection::open(&path)?)?,
+            Duration::from_secs(60),
+        )
+        .with_health(health.clone());
+        let report = scheduler.maintain()?;
+        assert!(!report.problems.is_empty());
+        assert_eq!(report.vacuumed_pages, 0);
+        assert!(!health.is_serving());
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,448 @@
+use crate::db::UnlockReason;
+use crate::deployment::DeploymentLabels;
+use anyhow::Result;
+use http_body_util::Full;
+use hyper::body::Bytes;
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use prometheus::{
+    Encoder, GaugeVec, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge,
+    IntGaugeVec, Opts, Registry, TextEncoder,
+};
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::net::TcpListener;
+
+/// Number of settled locks per contract the revert rate is computed over
+const REVERT_RATE_WINDOW: usize = 100;
+
+/// Prometheus metrics exported by the server. Every metric carries the deployment labels.
+pub struct Metrics {
+    registry: Registry,
+    lock_conflicts: IntCounterVec,
+    archived_locks: IntCounter,
+    purged_locks: IntCounter,
+    maintenance_runs: IntCounter,
+    integrity_check_failures: IntCounter,
+    vacuumed_pages: IntCounter,
+    checkpointed_pages: IntCounter,
+    last_maintenance: IntGauge,
+    lock_duration_btc_blocks: HistogramVec,
+    lock_duration_seconds: HistogramVec,
+    revert_rate: GaugeVec,
+    /// Whether each of the latest settled locks of a contract was reverted, oldest first
+    recent_settlements: Mutex<HashMap<(String, String), VecDeque<bool>>>,
+    alerts_fired: IntCounterVec,
+    alert_firing: IntGaugeVec,
+}
+
+impl Metrics {
+    pub fn new(labels: &DeploymentLabels) -> Self {
+        let const_labels = HashMap::from([
+            ("environment".to_string(), labels.environment.clone()),
+            ("region".to_string(), labels.region.clone()),
+            ("instance_id".to_string(), labels.instance_id.clone()),
+        ]);
+        let registry = Registry::new_custom(Some("sova_sentinel".to_string()), Some(const_labels))
+            .expect("metric labels are valid");
+
+        let lock_conflicts = IntCounterVec::new(
+            Opts::new(
+                "lock_conflicts_total",
+                "Lock attempts rejected because the slot was already locked",
+            ),
+            &["contract_address"],
+        )
+        .expect("metric options are valid");
+        registry
+            .register(Box::new(lock_conflicts.clone()))
+            .expect("metric is registered once");
+
+        let archived_locks = IntCounter::new(
+            "archived_locks_total",
+            "Unlocked locks archived by the retention policy",
+        )
+        .expect("metric options are valid");
+        registry
+            .register(Box::new(archived_locks.clone()))
+            .expect("metric is registered once");
+
+        let purged_locks = IntCounter::new(
+            "purged_locks_total",
+            "Archived locks deleted by the retention policy",
+        )
+        .expect("metric options are valid");
+        registry
+            .register(Box::new(purged_locks.clone()))
+            .expect("metric is registered once");
+
+        let maintenance_runs = IntCounter::new(
+            "maintenance_runs_total",
+            "Scheduled database maintenance runs",
+        )
+        .expect("metric options are valid");
+        registry
+            .register(Box::new(maintenance_runs.clone()))
+            .expect("metric is registered once");
+
+        let integrity_check_failures = IntCounter::new(
+            "integrity_check_failures_total",
+            "Scheduled integrity checks that found the database corrupt",
+        )
+        .expect("metric options are valid");
+        registry
+            .register(Box::new(integrity_check_failures.clone()))
+            .expect("metric is registered once");
+
+        let vacuumed_pages = IntCounter::new(
+            "vacuumed_pages_total",
+            "Free database pages returned to the filesystem by incremental vacuum",
+        )
+        .expect("metric options are valid");
+        registry
+            .register(Box::new(vacuumed_pages.clone()))
+            .expect("metric is registered once");
+
+        let checkpointed_pages = IntCounter::new(
+            "checkpointed_pages_total",
+            "Write-ahead log pages checkpointed into the database by maintenance",
+        )
+        .expect("metric options are valid");
+        registry
+            .register(Box::new(checkpointed_pages.clone()))
+            .expect("metric is registered once");
+
+        let last_maintenance = IntGauge::new(
+            "last_maintenance_timestamp_seconds",
+            "Unix time the last scheduled database maintenance completed",
+        )
+        .expect("metric options are valid");
+        registry
+            .register(Box::new(last_maintenance.clone()))
+            .expect("metric is registered once");
+
+        let lock_duration_btc_blocks = HistogramVec::new(
+            HistogramOpts::new(
+                "lock_duration_btc_blocks",
+                "Bitcoin blocks from a lock's block until it was unlocked or reverted",
+            )
+            .buckets(vec![
+                1.0, 2.0, 3.0, 6.0, 12.0, 24.0, 48.0, 72.0, 144.0, 288.0, 1008.0,
+            ]),
+            &["unlock_reason"],
+        )
+        .expect("metric options are valid");
+        registry
+            .register(Box::new(lock_duration_btc_blocks.clone()))
+            .expect("metric is registered once");
+
+        let lock_duration_seconds = HistogramVec::new(
+            HistogramOpts::new(
+                "lock_duration_seconds",
+                "Time from a lock being made until it was unlocked or reverted",
+            )
+            .buckets(vec![
+                60.0, 300.0, 600.0, 1800.0, 3600.0, 7200.0, 14400.0, 43200.0, 86400.0, 259200.0,
+                604800.0,
+            ]),
+            &["unlock_reason"],
+        )
+        .expect("metric options are valid");
+        registry
+            .register(Box::new(lock_duration_seconds.clone()))
+            .expect("metric is registered once");
+
+        let revert_rate = GaugeVec::new(
+            Opts::new(
+                "revert_rate",
+                "Share of the latest 100 unlocked or reverted locks of a contract that were reverted",
+            ),
+            &["namespace", "contract_address"],
+        )
+        .expect("metric options are valid");
+        registry
+            .register(Box::new(revert_rate.clone()))
+            .expect("metric is registered once");
+
+        let alerts_fired = IntCounterVec::new(
+            Opts::new("alerts_fired_total", "Times an alert rule started firing"),
+            &["rule"],
+        )
+        .expect("metric options are valid");
+        registry
+            .register(Box::new(alerts_fired.clone()))
+            .expect("metric is registered once");
+
+        let alert_firing = IntGaugeVec::new(
+            Opts::new(
+                "alert_firing",
+                "Namespaces an alert rule is currently firing for",
+            ),
+            &["rule"],
+        )
+        .expect("metric options are valid");
+        registry
+            .register(Box::new(alert_firing.clone()))
+            .expect("metric is registered once");
+
+        Self {
+            registry,
+            lock_conflicts,
+            archived_locks,
+            purged_locks,
+            maintenance_runs,
+            integrity_check_failures,
+            vacuumed_pages,
+            checkpointed_pages,
+            last_maintenance,
+            lock_duration_btc_blocks,
+            lock_duration_seconds,
+            revert_rate,
+            recent_settlements: Mutex::default(),
+            alerts_fired,
+            alert_firing,
+        }
+    }
+
+    pub fn record_lock_conflict(&self, contract_address: &str) {
+        self.lock_conflicts
+            .with_label_values(&[contract_address])
+            .inc();
+    }
+
+    pub fn record_prune(&self, archived: u64, purged: u64) {
+        self.archived_locks.inc_by(archived);
+        self.purged_locks.inc_by(purged);
+    }
+
+    /// Records a maintenance run that completed at unix time `completed_at`
+    pub fn record_maintenance(
+        &self,
+        corrupt: bool,
+        vacuumed: u64,
+        checkpointed: u64,
+        completed_at: u64,
+    ) {
+        self.maintenance_runs.inc();
+        if corrupt {
+            self.integrity_check_failures.inc();
+        }
+        self.vacuumed_pages.inc_by(vacuumed);
+        self.checkpointed_pages.inc_by(checkpointed);
+        self.last_maintenance.set(completed_at as i64);
+    }
+
+    /// Records a lock of a contract in a namespace the sentinel unlocked or reverted `btc_blocks`
+    /// Bitcoin blocks and `seconds` after it was made
+    pub fn record_settlement(
+        &self,
+        namespace: &str,
+        contract_address: &str,
+        reason: UnlockReason,
+        btc_blocks: u64,
+        seconds: Option<u64>,
+    ) {
+        self.lock_duration_btc_blocks
+            .with_label_values(&[reason.as_str()])
+            .observe(btc_blocks as f64);
+        if let Some(seconds) = seconds {
+            self.lock_duration_seconds
+                .with_label_values(&[reason.as_str()])
+                .observe(seconds as f64);
+        }
+
+        let mut recent_settlements = self
+            .recent_settlements
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        let settlements = recent_settlements
+            .entry((namespace.to_string(), contract_address.to_string()))
+            .or_default();
+        if settlements.len() == REVERT_RATE_WINDOW {
+            settlements.pop_front();
+        }
+        settlements.push_back(reason.is_revert());
+        let reverts = settlements.iter().filter(|reverted| **reverted).count();
+        self.revert_rate
+            .with_label_values(&[namespace, contract_address])
+            .set(reverts as f64 / settlements.len() as f64);
+    }
+
+    /// Records an alert rule starting to fire, or resolving, for a namespace
+    pub fn record_alert(&self, rule: &str, firing: bool) {
+        let gauge = self.alert_firing.with_label_values(&[rule]);
+        if firing {
+            self.alerts_fired.with_label_values(&[rule]).inc();
+            gauge.inc();
+        } else {
+            gauge.dec();
+        }
+    }
+
+    /// Renders all metrics in the Prometheus text format
+    pub fn encode(&self) -> Result<String> {
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+        Ok(String::from_utf8(buffer)?)
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new(&DeploymentLabels::default())
+    }
+}
+
+/// Serves the metrics over HTTP at `/metrics` until the process exits
+pub async fn serve(addr: SocketAddr, metrics: Arc<Metrics>) -> Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    tracing::info!("Metrics server listening on {}", addr);
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let metrics = metrics.clone();
+        tokio::spawn(async move {
+            let service = service_fn(move |request: Request<hyper::body::Incoming>| {
+                let metrics = metrics.clone();
+                async move { Ok::<_, hyper::Error>(metrics_response(&request, &metrics)) }
+            });
+            if let Err(e) = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                tracing::debug!("Metrics connection error: {}", e);
+            }
+        });
+    }
+}
+
+fn metrics_response<B>(request: &Request<B>, metrics: &Metrics) -> Response<Full<Bytes>> {
+    if request.uri().path() != "/metrics" {
+        return Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Full::new(Bytes::new()))
+            .unwrap();
+    }
+
+    match metrics.encode() {
+        Ok(body) => Response::builder()
+            .header(hyper::header::CONTENT_TYPE, prometheus::TEXT_FORMAT)
+            .body(Full::new(Bytes::from(body)))
+            .unwrap(),
+        Err(e) => Response::builder()
+            .status(StatusCode::INTERNAL_SERVER_ERROR)
+            .body(Full::new(Bytes::from(e.to_string())))
+            .unwrap(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lock_conflicts_are_labeled() {
+        let metrics = Metrics::new(&DeploymentLabels {
+            environment: "testnet".to_string(),
+            region: "eu-west-1".to_string(),
+            instance_id: "sentinel-0".to_string(),
+        });
+        metrics.record_lock_conflict("0x123");
+        metrics.record_lock_conflict("0x123");
+
+        let output = metrics.encode().unwrap();
+        let line = output
+            .lines()
+            .find(|line| line.starts_with("sova_sentinel_lock_conflicts_total{"))
+            .unwrap();
+        assert!(line.contains(r#"contract_address="0x123""#));
+        assert!(line.contains(r#"environment="testnet""#));
+        assert!(line.ends_with(" 2"));
+    }
+
+    #[test]
+    fn test_settlements() {
+        let metrics = Metrics::default();
+        metrics.record_settlement("", "0x123", UnlockReason::Confirmed, 3, Some(1800));
+        metrics.record_settlement("", "0x123", UnlockReason::RevertThreshold, 7, None);
+        metrics.record_settlement("", "0x123", UnlockReason::Confirmed, 2, Some(900));
+        metrics.record_settlement("", "0x123", UnlockReason::DoubleSpent, 1, Some(60));
+        metrics.record_settlement("", "0x456", UnlockReason::Confirmed, 2, Some(600));
+
+        let value = |metric: &str, label: &str| {
+            let output = metrics.encode().unwrap();
+            output
+                .lines()
+                .find(|line| line.starts_with(&format!("{}{{", metric)) && line.contains(label))
+                .and_then(|line| line.rsplit(' ').next())
+                .map(str::to_string)
+        };
+        let revert_rate = "sova_sentinel_revert_rate";
+        assert_eq!(
+            value(revert_rate, r#"contract_address="0x123",namespace="""#).as_deref(),
+            Some("0.5")
+        );
+        assert_eq!(
+            value(revert_rate, r#"contract_address="0x456",namespace="""#).as_deref(),
+            Some("0")
+        );
+        let confirmed = r#"unlock_reason="confirmed""#;
+        let reverted = r#"unlock_reason="revert-threshold""#;
+        assert_eq!(
+            value("sova_sentinel_lock_duration_btc_blocks_count", confirmed).as_deref(),
+            Some("3")
+        );
+        assert_eq!(
+            value("sova_sentinel_lock_duration_btc_blocks_sum", reverted).as_deref(),
+            Some("7")
+        );
+        assert_eq!(
+            value("sova_sentinel_lock_duration_seconds_sum", confirmed).as_deref(),
+            Some("3300")
+        );
+        // Locks without a known creation time have no wall time
+        assert_eq!(
+            value("sova_sentinel_lock_duration_seconds_count", reverted),
+            None
+        );
+
+        // The same contract address in another namespace has its own revert rate
+        metrics.record_settlement("signet", "0x123", UnlockReason::Confirmed, 2, None);
+        assert_eq!(
+            value(
+                revert_rate,
+                r#"contract_address="0x123",namespace="signet""#
+            )
+            .as_deref(),
+            Some("0")
+        );
+
+        // Only the latest settlements count towards the revert rate
+        for _ in 0..REVERT_RATE_WINDOW {
+            metrics.record_settlement("", "0x123", UnlockReason::Confirmed, 2, None);
+        }
+        assert_eq!(
+            value(revert_rate, r#"contract_address="0x123",namespace="""#).as_deref(),
+            Some("0")
+        );
+    }
+
+    #[test]
+    fn test_metrics_response_paths() {
+        let metrics = Metrics::default();
+        let request = Request::get("/metrics").body(()).unwrap();
+        assert_eq!(
+            metrics_response(&request, &metrics).status(),
+            StatusCode::OK
+        );
+
+        let request = Request::get("/other").body(()).unwrap();
+        assert_eq!(
+            metrics_response(&request, &metrics).status(),
+            StatusCode::NOT_FOUND
+        );
+    }
+}
@@ -0,0 +1,475 @@
+//! Export of spans to an OpenTelemetry collector over OTLP/HTTP with JSON encoding. Request spans
+//! continue the trace of the caller's W3C `traceparent` header, and the spans of database
+//! transactions and Bitcoin RPC calls made while handling a request are exported as its children,
+//! so a slow call can be followed from the sova node down to bitcoind.
+
+use crate::deployment::DeploymentLabels;
+use crate::sampling::Unsampled;
+use serde_json::{json, Value};
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::Subscriber;
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+/// Span field carrying the caller's `traceparent` header on request spans
+pub const TRACEPARENT_FIELD: &str = "traceparent";
+
+/// Prefix of the targets of exported spans. Spans of dependencies, e.g. of the HTTP client the
+/// exporter itself uses, are skipped and their children attached to the nearest exported span.
+const EXPORTED_TARGET_PREFIX: &str = "sova_sentinel";
+
+/// Finished spans buffered for export. Spans are dropped while the buffer is full.
+const QUEUE_CAPACITY: usize = 4096;
+
+/// Most spans sent in one export request
+const MAX_BATCH_SIZE: usize = 512;
+
+/// Delay between export requests, during which finished spans accumulate into a batch
+const EXPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Timeout of one export request
+const EXPORT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Trace context propagated in a W3C `traceparent` header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceContext {
+    pub trace_id: [u8; 16],
+    pub span_id: [u8; 8],
+    pub sampled: bool,
+}
+
+/// Parses a version 00 `traceparent` header, `None` if it's malformed or has all-zero ids
+pub fn parse_traceparent(value: &str) -> Option<TraceContext> {
+    let mut parts = value.trim().split('-');
+    let (version, trace_id, span_id, flags) =
+        (parts.next()?, parts.next()?, parts.next()?, parts.next()?);
+    if version != "00" || parts.next().is_some() || flags.len() != 2 {
+        return None;
+    }
+    let mut context = TraceContext {
+        trace_id: [0; 16],
+        span_id: [0; 8],
+        sampled: u8::from_str_radix(flags, 16).ok()? & 0x01 != 0,
+    };
+    hex::decode_to_slice(trace_id, &mut context.trace_id).ok()?;
+    hex::decode_to_slice(span_id, &mut context.span_id).ok()?;
+    if context.trace_id == [0; 16] || context.span_id == [0; 8] {
+        return None;
+    }
+    Some(context)
+}
+
+/// Role of a span in a trace, as numbered by OTLP
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpanKind {
+    Internal = 1,
+    Server = 2,
+    Client = 3,
+}
+
+/// Span handed to the exporter once closed
+#[derive(Debug, Clone)]
+pub struct FinishedSpan {
+    pub trace_id: [u8; 16],
+    pub span_id: [u8; 8],
+    pub parent_span_id: Option<[u8; 8]>,
+    pub name: String,
+    pub kind: SpanKind,
+    pub start: SystemTime,
+    pub end: SystemTime,
+    /// Attributes as OTLP JSON key-values
+    pub attributes: Vec<Value>,
+}
+
+/// Ids of a span, stored in its extensions. Skipped spans carry the ids of their parent.
+#[derive(Clone, Copy)]
+struct SpanIds {
+    trace_id: [u8; 16],
+    span_id: [u8; 8],
+}
+
+/// Fields of an exported span collected while it's open
+struct SpanData {
+    parent_span_id: Option<[u8; 8]>,
+    name: String,
+    kind: SpanKind,
+    start: SystemTime,
+    attributes: Vec<Value>,
+    remote_parent: Option<TraceContext>,
+}
+
+struct SpanVisitor<'a>(&'a mut SpanData);
+
+impl SpanVisitor<'_> {
+    fn record(&mut self, field: &Field, value: Value) {
+        // Headers are already summarized by the span's other fields, and may be large
+        if field.name() != "headers" {
+            self.0
+                .attributes
+                .push(json!({ "key": field.name(), "value": value }));
+        }
+    }
+}
+
+impl Visit for SpanVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            TRACEPARENT_FIELD => self.0.remote_parent = parse_traceparent(value),
+            "otel.name" => self.0.name = value.to_string(),
+            "otel.kind" => {
+                self.0.kind = match value {
+                    "server" => SpanKind::Server,
+                    "client" => SpanKind::Client,
+                    _ => SpanKind::Internal,
+                }
+            }
+            _ => self.record(field, json!({ "stringValue": value })),
+        }
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.record(field, json!({ "boolValue": value }));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        // OTLP JSON encodes 64-bit integers as strings
+        self.record(field, json!({ "intValue": value.to_string() }));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.record(field, json!({ "intValue": value.to_string() }));
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.record(field, json!({ "doubleValue": value }));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.record_str(field, &format!("{:?}", value));
+    }
+}
+
+/// Returns a random non-zero id
+fn random_id() -> u64 {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    loop {
+        let id = RandomState::new().hash_one(COUNTER.fetch_add(1, Ordering::Relaxed));
+        if id != 0 {
+            return id;
+        }
+    }
+}
+
+/// Layer handing closed spans to the exporter. Spans of unsampled requests aren't exported.
+pub struct OtlpLayer {
+    spans: mpsc::Sender<FinishedSpan>,
+}
+
+impl OtlpLayer {
+    pub fn new(spans: mpsc::Sender<FinishedSpan>) -> Self {
+        Self { spans }
+    }
+}
+
+impl<S> Layer<S> for OtlpLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let parent = span
+            .parent()
+            .and_then(|parent| parent.extensions().get::<SpanIds>().copied());
+        if !span.metadata().target().starts_with(EXPORTED_TARGET_PREFIX) {
+            if let Some(parent) = parent {
+                span.extensions_mut().insert(parent);
+            }
+            return;
+        }
+
+        let mut data = SpanData {
+            parent_span_id: None,
+            name: span.name().to_string(),
+            kind: SpanKind::Internal,
+            start: SystemTime::now(),
+            attributes: Vec::new(),
+            remote_parent: None,
+        };
+        attrs.record(&mut SpanVisitor(&mut data));
+        let trace_id = match (parent, data.remote_parent) {
+            (Some(parent), _) => {
+                data.parent_span_id = Some(parent.span_id);
+                parent.trace_id
+            }
+            (None, Some(remote)) => {
+                data.parent_span_id = Some(remote.span_id);
+                remote.trace_id
+            }
+            (None, None) => {
+                let mut trace_id = [0; 16];
+                trace_id[..8].copy_from_slice(&random_id().to_be_bytes());
+                trace_id[8..].copy_from_slice(&random_id().to_be_bytes());
+                trace_id
+            }
+        };
+        let mut extensions = span.extensions_mut();
+        extensions.insert(SpanIds {
+            trace_id,
+            span_id: random_id().to_be_bytes(),
+        });
+        extensions.insert(data);
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            if let Some(data) = span.extensions_mut().get_mut::<SpanData>() {
+                values.record(&mut SpanVisitor(data));
+            }
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let Some(ids) = span.extensions().get::<SpanIds>().copied() else {
+            return;
+        };
+        let Some(data) = span.extensions_mut().remove::<SpanData>() else {
+            return;
+        };
+        if span
+            .scope()
+            .any(|span| span.extensions().get::<Unsampled>().is_some())
+        {
+            return;
+        }
+        // Never block the traced code on the collector
+        let _ = self.spans.try_send(FinishedSpan {
+            trace_id: ids.trace_id,
+            span_id: ids.span_id,
+            parent_span_id: data.parent_span_id,
+            name: data.name,
+            kind: data.kind,
+            start: data.start,
+            end: SystemTime::now(),
+            attributes: data.attributes,
+        });
+    }
+}
+
+/// Resource attributes identifying this sentinel instance to the collector
+fn resource_attributes(labels: &DeploymentLabels) -> Vec<Value> {
+    [
+        ("service.name", "sova-sentinel"),
+        ("service.version", env!("CARGO_PKG_VERSION")),
+        ("deployment.environment", labels.environment.as_str()),
+        ("cloud.region", labels.region.as_str()),
+        ("service.instance.id", labels.instance_id.as_str()),
+    ]
+    .into_iter()
+    .filter(|(_, value)| !value.is_empty())
+    .map(|(key, value)| json!({ "key": key, "value": { "stringValue": value } }))
+    .collect()
+}
+
+fn unix_nanos(time: SystemTime) -> String {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+        .to_string()
+}
+
+/// Encodes spans as an OTLP `ExportTraceServiceRequest` in JSON
+fn encode(resource: &[Value], spans: &[FinishedSpan]) -> Value {
+    let spans: Vec<Value> = spans
+        .iter()
+        .map(|span| {
+            let mut encoded = json!({
+                "traceId": hex::encode(span.trace_id),
+                "spanId": hex::encode(span.span_id),
+                "name": span.name,
+                "kind": span.kind as i32,
+                "startTimeUnixNano": unix_nanos(span.start),
+                "endTimeUnixNano": unix_nanos(span.end),
+                "attributes": span.attributes,
+            });
+            if let Some(parent_span_id) = span.parent_span_id {
+                encoded["parentSpanId"] = hex::encode(parent_span_id).into();
+            }
+            encoded
+        })
+        .collect();
+    json!({
+        "resourceSpans": [{
+            "resource": { "attributes": resource },
+            "scopeSpans": [{
+                "scope": { "name": "sova-sentinel", "version": env!("CARGO_PKG_VERSION") },
+                "spans": spans,
+            }],
+        }],
+    })
+}
+
+/// Starts exporting spans to the OTLP/HTTP collector at `endpoint`, e.g. `http://localhost:4318`,
+/// and returns the layer feeding it. Must be called within a Tokio runtime.
+pub fn spawn_exporter(endpoint: &str, labels: &DeploymentLabels) -> OtlpLayer {
+    let url = format!("{}/v1/traces", endpoint.trim_end_matches('/'));
+    let resource = resource_attributes(labels);
+    let (sender, mut receiver) = mpsc::channel(QUEUE_CAPACITY);
+    tokio::spawn(async move {
+        let client = reqwest::Client::new();
+        let mut batch = Vec::with_capacity(MAX_BATCH_SIZE);
+        while receiver.recv_many(&mut batch, MAX_BATCH_SIZE).await > 0 {
+            let result = client
+                .post(&url)
+                .timeout(EXPORT_TIMEOUT)
+                .json(&encode(&resource, &batch))
+                .send()
+                .await
+                .and_then(|response| response.error_for_status());
+            if let Err(e) = result {
+                tracing::warn!("Failed to export {} spans to {}: {}", batch.len(), url, e);
+            }
+            batch.clear();
+            tokio::time::sleep(EXPORT_INTERVAL).await;
+        }
+    });
+    OtlpLayer::new(sender)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::EnvFilter;
+
+    #[test]
+    fn test_parse_traceparent() {
+        let context =
+            parse_traceparent("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01").unwrap();
+        assert_eq!(
+            hex::encode(context.trace_id),
+            "4bf92f3577b34da6a3ce929d0e0e4736"
+        );
+        assert_eq!(hex::encode(context.span_id), "00f067aa0ba902b7");
+        assert!(context.sampled);
+        assert!(
+            !parse_traceparent("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00")
+                .unwrap()
+                .sampled
+        );
+
+        for malformed in [
+            "",
+            "01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e47-00f067aa0ba902b7-01",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-1",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+        ] {
+            assert_eq!(parse_traceparent(malformed), None, "{}", malformed);
+        }
+    }
+
+    #[test]
+    fn test_exports_spans_of_sampled_requests() {
+        let (sender, mut receiver) = mpsc::channel(16);
+        let subscriber = crate::sampling::subscriber(
+            EnvFilter::new("debug"),
+            std::io::sink,
+            crate::sampling::LogFormat::Text,
+            Some(OtlpLayer::new(sender)),
+        );
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!(
+                "request",
+                sampled = true,
+                otel.kind = "server",
+                traceparent = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            )
+            .in_scope(|| {
+                tracing::debug_span!(target: "hyper", "connection").in_scope(|| {
+                    tracing::info_span!(
+                        "bitcoin_rpc",
+                        otel.kind = "client",
+                        rpc.method = "getrawtransaction"
+                    )
+                    .in_scope(|| {});
+                });
+            });
+            tracing::info_span!("request", sampled = false).in_scope(|| {
+                tracing::info_span!("db_transaction").in_scope(|| {});
+            });
+            tracing::info_span!("db_transaction", attempt = 2_u64).in_scope(|| {});
+        });
+
+        let mut spans = Vec::new();
+        while let Ok(span) = receiver.try_recv() {
+            spans.push(span);
+        }
+        assert_eq!(spans.len(), 3);
+        let (rpc, request, transaction) = (&spans[0], &spans[1], &spans[2]);
+
+        // The request continues the caller's trace, and the RPC call is its child even through
+        // the skipped span of a dependency
+        assert_eq!(request.kind, SpanKind::Server);
+        assert_eq!(
+            hex::encode(request.trace_id),
+            "4bf92f3577b34da6a3ce929d0e0e4736"
+        );
+        assert_eq!(
+            request.parent_span_id.map(hex::encode).as_deref(),
+            Some("00f067aa0ba902b7")
+        );
+        assert_eq!(rpc.name, "bitcoin_rpc");
+        assert_eq!(rpc.kind, SpanKind::Client);
+        assert_eq!(rpc.trace_id, request.trace_id);
+        assert_eq!(rpc.parent_span_id, Some(request.span_id));
+        assert_eq!(
+            rpc.attributes,
+            vec![json!({ "key": "rpc.method", "value": { "stringValue": "getrawtransaction" } })]
+        );
+
+        // A span outside any request starts a new trace
+        assert_eq!(transaction.parent_span_id, None);
+        assert_ne!(transaction.trace_id, request.trace_id);
+        assert_ne!(transaction.trace_id, [0; 16]);
+
+        let encoded = encode(
+            &resource_attributes(&DeploymentLabels {
+                environment: "production".to_string(),
+                ..Default::default()
+            }),
+            &spans[2..],
+        );
+        let resource_spans = &encoded["resourceSpans"][0];
+        assert_eq!(
+            resource_spans["resource"]["attributes"][0],
+            json!({ "key": "service.name", "value": { "stringValue": "sova-sentinel" } })
+        );
+        assert_eq!(
+            resource_spans["resource"]["attributes"]
+                .as_array()
+                .unwrap()
+                .len(),
+            3
+        );
+        let span = &resource_spans["scopeSpans"][0]["spans"][0];
+        assert_eq!(span["traceId"], hex::encode(transaction.trace_id));
+        assert_eq!(span["kind"], 1);
+        assert!(span.get("parentSpanId").is_none());
+        assert_eq!(
+            span["attributes"][0],
+            json!({ "key": "attempt", "value": { "intValue": "2" } })
+        );
+    }
+}
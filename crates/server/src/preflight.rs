@@ -0,0 +1,312 @@
+//! Startup self-checks, run before the gRPC port is bound so a misconfigured sentinel fails with
+//! an actionable error instead of on its first requests.
+
+use crate::db::{open_connection, Database, DatabaseKey, RedbStore};
+use crate::service::{network_name, BitcoinRpcService, BitcoinRpcServiceAPI};
+use anyhow::{Context, Result};
+use bitcoin::Network;
+use std::fs::{self, OpenOptions};
+use std::path::Path;
+use std::str::FromStr;
+
+/// Database path that keeps the database in memory, starting empty on every boot
+pub const IN_MEMORY_DB_PATH: &str = ":memory:";
+
+/// Returns whether `db_path` selects an in-memory database
+pub fn is_in_memory(db_path: &Path) -> bool {
+    db_path == Path::new(IN_MEMORY_DB_PATH)
+}
+
+/// Warns loudly that nothing the sentinel stores survives a restart
+fn warn_ephemeral(what: &str) {
+    tracing::warn!("################################################################");
+    tracing::warn!("# EPHEMERAL MODE: the {} is kept in memory", what);
+    tracing::warn!("# Every lock is lost when the sentinel stops. Devnets and CI only.");
+    tracing::warn!("################################################################");
+}
+
+/// What the sentinel does when the startup integrity check finds the database corrupt
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CorruptionPolicy {
+    /// Start in read-only safe mode: nothing is written and health reports `NOT_SERVING`
+    #[default]
+    SafeMode,
+    /// Refuse to start
+    Fail,
+}
+
+impl FromStr for CorruptionPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "safe-mode" => Ok(Self::SafeMode),
+            "fail" => Ok(Self::Fail),
+            other => Err(anyhow::anyhow!("Unsupported corruption policy: {}", other)),
+        }
+    }
+}
+
+/// Logs a failed check before its error is returned, so it shows up with the rest of the startup
+/// logs
+fn failed(check: &str, e: anyhow::Error) -> anyhow::Error {
+    tracing::error!("Preflight check failed: {}: {:#}", check, e);
+    e
+}
+
+/// Fails unless SQLite can write the database file and create its journal next to it
+fn check_database_writable(db_path: &Path) -> Result<()> {
+    let dir = match db_path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    if !dir.is_dir() {
+        anyhow::bail!(
+            "Directory {} of SOVA_SENTINEL_DB_PATH does not exist, create it or point \
+             SOVA_SENTINEL_DB_PATH elsewhere",
+            dir.display()
+        );
+    }
+    if db_path.exists() {
+        OpenOptions::new()
+            .append(true)
+            .open(db_path)
+            .with_context(|| {
+                format!(
+                    "Database file {} is not writable, check its owner and permissions",
+                    db_path.display()
+                )
+            })?;
+    }
+    let probe = dir.join(".sova-sentinel-preflight");
+    fs::write(&probe, b"").with_context(|| {
+        format!(
+            "Directory {} is not writable, SQLite needs it for the database journal",
+            dir.display()
+        )
+    })?;
+    fs::remove_file(&probe)?;
+    Ok(())
+}
+
+/// Opens the database at `db_path` and applies pending migrations, after checking that the file
+/// and its directory are writable. [`IN_MEMORY_DB_PATH`] opens an empty database in memory. With
+/// a key the file is encrypted with SQLCipher.
+pub fn open_database(db_path: &Path, key: Option<DatabaseKey>) -> Result<Database> {
+    if is_in_memory(db_path) {
+        if key.is_some() {
+            return Err(failed(
+                "database",
+                anyhow::anyhow!("An in-memory database can't be encrypted, unset the database key"),
+            ));
+        }
+        let db = rusqlite::Connection::open_in_memory()
+            .map_err(anyhow::Error::from)
+            .and_then(Database::new)
+            .map_err(|e| failed("database", e))?;
+        warn_ephemeral("database");
+        return Ok(db);
+    }
+    check_database_writable(db_path).map_err(|e| failed("database", e))?;
+
+    let encrypted = key.is_some();
+    let conn = open_connection(
+        db_path,
+        rusqlite::OpenFlags::SQLITE_OPEN_READ_WRITE
+            | rusqlite::OpenFlags::SQLITE_OPEN_CREATE
+            | rusqlite::OpenFlags::SQLITE_OPEN_FULL_MUTEX,
+        key.as_ref(),
+    )
+    .map_err(|e| failed("database", e))?;
+    let db = Database::new(conn)
+        .with_context(|| {
+            format!(
+                "Failed to apply migrations to {}, restore it from a backup or check that it \
+                 wasn't written by a newer sentinel",
+                db_path.display()
+            )
+        })
+        .map_err(|e| failed("migrations", e))?
+        .with_key(key);
+
+    tracing::info!(
+        "Preflight: database {} is writable{} and at schema version {}",
+        db_path.display(),
+        if encrypted { ", encrypted" } else { "" },
+        db.schema_version()?
+    );
+    Ok(db)
+}
+
+/// Checks the database with `PRAGMA quick_check`. A corrupt database fails startup under
+/// [`CorruptionPolicy::Fail`], and is otherwise made read-only for safe mode. Returns whether the
+/// sentinel starts in safe mode.
+pub fn check_integrity(db: &Database, policy: CorruptionPolicy) -> Result<bool> {
+    let problems = db
+        .quick_check()
+        .context("Failed to check database integrity")
+        .map_err(|e| failed("integrity", e))?;
+    if problems.is_empty() {
+        tracing::info!("Preflight: database integrity check passed");
+        return Ok(false);
+    }
+    let problems = problems.join("; ");
+    if policy == CorruptionPolicy::Fail {
+        return Err(failed(
+            "integrity",
+            anyhow::anyhow!(
+                "Database is corrupt, restore it from a backup: {}",
+                problems
+            ),
+        ));
+    }
+    db.set_query_only(true)
+        .map_err(|e| failed("integrity", e))?;
+    tracing::error!("################################################################");
+    tracing::error!("# SAFE MODE: the database is corrupt: {}", problems);
+    tracing::error!("# Nothing is written and health reports NOT_SERVING. Restore the");
+    tracing::error!("# database from a backup and restart the sentinel.");
+    tracing::error!("################################################################");
+    Ok(true)
+}
+
+/// Opens the redb lock store at `path`, or an empty one in memory for [`IN_MEMORY_DB_PATH`]
+pub fn open_redb_store(path: &Path) -> Result<RedbStore> {
+    if is_in_memory(path) {
+        let store = RedbStore::in_memory().map_err(|e| failed("lock store", e))?;
+        warn_ephemeral("lock store");
+        return Ok(store);
+    }
+    RedbStore::open(path).map_err(|e| failed("lock store", e))
+}
+
+/// Checks that the Bitcoin node at `rpc_url` accepts the configured credentials, is on the
+/// expected network if one is set, and has txindex enabled
+pub async fn check_bitcoin_node(
+    bitcoin_service: &BitcoinRpcService,
+    rpc_url: &str,
+    expected_network: Option<Network>,
+) -> Result<()> {
+    let chain_info = bitcoin_service
+        .chain_info()
+        .await
+        .with_context(|| {
+            format!(
+                "Bitcoin node at {} is unreachable or rejected the credentials, check \
+                 BITCOIN_RPC_URL, BITCOIN_RPC_USER and BITCOIN_RPC_PASS",
+                rpc_url
+            )
+        })
+        .map_err(|e| failed("Bitcoin RPC", e))?;
+
+    // Refuse to serve locks against a node on the wrong chain, e.g. a production sentinel
+    // pointed at a regtest node
+    match expected_network {
+        Some(network) => bitcoin_service
+            .verify_network(network)
+            .await
+            .map_err(|e| failed("Bitcoin network", e))?,
+        None => {
+            tracing::warn!("BITCOIN_NETWORK is not set, the Bitcoin node's network is not verified")
+        }
+    }
+
+    bitcoin_service
+        .verify_txindex()
+        .await
+        .map_err(|e| failed("Bitcoin txindex", e))?;
+
+    tracing::info!(
+        "Preflight: Bitcoin node at {} is on {} at height {}",
+        rpc_url,
+        network_name(chain_info.network),
+        chain_info.tip_height
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_open_database_checks_directory() -> Result<()> {
+        let dir =
+            std::env::temp_dir().join(format!("sova-sentinel-preflight-{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+
+        let db_path = dir.join("slot_locks.db");
+        let db = open_database(&db_path, None)?;
+        assert!(db.schema_version()? > 0);
+        assert!(!dir.join(".sova-sentinel-preflight").exists());
+
+        let Err(error) = open_database(&dir.join("missing").join("slot_locks.db"), None) else {
+            panic!("Opened a database in a missing directory");
+        };
+        assert!(error.to_string().contains("does not exist"));
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_open_database_in_memory() -> Result<()> {
+        let db = open_database(Path::new(IN_MEMORY_DB_PATH), None)?;
+        assert!(db.schema_version()? > 0);
+        assert!(!Path::new(IN_MEMORY_DB_PATH).exists());
+        assert!(!is_in_memory(Path::new("slot_locks.db")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_integrity() -> Result<()> {
+        use crate::db::SlotInsertData;
+
+        let db_path =
+            std::env::temp_dir().join(format!("sova-sentinel-integrity-{}.db", std::process::id()));
+        let db = open_database(&db_path, None)?;
+        assert!(!check_integrity(&db, CorruptionPolicy::Fail)?);
+        let slot = |idx: u8| SlotInsertData {
+            namespace: String::new(),
+            contract_address: "0x123".to_string(),
+            start_block: 100,
+            btc_block: 200,
+            slot_index: vec![idx],
+            btc_txid: "txid1".to_string(),
+            revert_value: vec![idx; 512],
+            current_value: vec![idx; 512],
+            metadata: Vec::new(),
+            labels: Default::default(),
+            alt_btc_txids: Vec::new(),
+            required_confirmed_txids: 1,
+            locked_by: None,
+        };
+        let slots: Vec<_> = (0..200).map(slot).collect();
+        db.with_transaction(|tx| db.batch_insert_slot_locks(tx, &slots))?;
+        drop(db);
+
+        // Scribble over the middle of the file, past the schema on the first page
+        let mut contents = fs::read(&db_path)?;
+        let middle = contents.len() / 2;
+        contents[middle..middle + 4096].fill(0xff);
+        fs::write(&db_path, contents)?;
+
+        let db = open_database(&db_path, None)?;
+        let error = check_integrity(&db, CorruptionPolicy::Fail).unwrap_err();
+        assert!(error.to_string().contains("corrupt"));
+        assert!(check_integrity(&db, CorruptionPolicy::SafeMode)?);
+        assert!(db
+            .with_transaction(|tx| db.batch_insert_slot_locks(tx, &[slot(200)]))
+            .is_err());
+
+        assert_eq!(
+            "safe-mode".parse::<CorruptionPolicy>()?,
+            CorruptionPolicy::SafeMode
+        );
+        assert_eq!("fail".parse::<CorruptionPolicy>()?, CorruptionPolicy::Fail);
+        assert!("ignore".parse::<CorruptionPolicy>().is_err());
+
+        fs::remove_file(&db_path)?;
+        Ok(())
+    }
+}
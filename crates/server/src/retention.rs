@@ -0,0 +1,274 @@
+//! Retention of unlocked locks. Old unlocked locks are archived, hiding them from queries while
+//! they can still be restored, and archived locks are eventually purged, or moved to a separate
+//! database, so the hot table only holds the locks still worth querying.
+
+use crate::db::Database;
+use crate::metrics::Metrics;
+use anyhow::{Context, Result};
+use rusqlite::OpenFlags;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Locks read and updated per transaction
+const PRUNE_BATCH_SIZE: usize = 1000;
+
+/// How long unlocked locks are kept. A lock is archived once it exceeds either age limit; an
+/// unset limit never archives.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    /// Sova blocks an unlocked lock is kept for, counted back from the latest block of its
+    /// namespace
+    pub max_age_blocks: Option<u64>,
+    /// Days an unlocked lock is kept for
+    pub max_age_days: Option<u64>,
+    /// Days an archived lock can be restored for before it is purged, forever when unset
+    pub purge_after_days: Option<u64>,
+}
+
+impl RetentionPolicy {
+    pub fn is_empty(&self) -> bool {
+        self.max_age_blocks.is_none()
+            && self.max_age_days.is_none()
+            && self.purge_after_days.is_none()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PruneStats {
+    /// Locks archived
+    pub archived: u64,
+    /// Archived locks deleted for good, or moved to the purge database
+    pub purged: u64,
+}
+
+/// Archives unlocked locks beyond the retention policy and purges locks archived long enough ago
+#[derive(Clone)]
+pub struct Pruner {
+    db: Database,
+    policy: RetentionPolicy,
+    purge_path: Option<PathBuf>,
+    metrics: Arc<Metrics>,
+}
+
+impl Pruner {
+    pub fn new(db: Database, policy: RetentionPolicy) -> Self {
+        Self {
+            db,
+            policy,
+            purge_path: None,
+            metrics: Arc::new(Metrics::default()),
+        }
+    }
+
+    /// Moves purged locks to the database at `path`, created with the sentinel's schema if
+    /// needed, instead of dropping them
+    pub fn with_purge_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.purge_path = Some(path.into());
+        self
+    }
+
+    /// Sets the metrics counting archived and purged locks
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    pub fn policy(&self) -> RetentionPolicy {
+        self.policy
+    }
+
+    /// Archives the unlocked locks beyond `policy` in every namespace, then purges the locks
+    /// archived more than `policy.purge_after_days` ago
+    pub fn prune(&self, policy: RetentionPolicy) -> Result<PruneStats> {
+        let mut stats = PruneStats::default();
+        if policy.max_age_blocks.is_some() || policy.max_age_days.is_some() {
+            for (namespace, latest_block) in self.db.latest_sova_blocks()? {
+                let unlocked_by_block = policy
+                    .max_age_blocks
+                    .and_then(|max_age| latest_block.checked_sub(max_age));
+                loop {
+                    let rows = self.db.prunable_lock_rows(
+                        &namespace,
+                        unlocked_by_block,
+                        policy.max_age_days,
+                        PRUNE_BATCH_SIZE,
+                    )?;
+                    let ids: Vec<_> = rows.iter().map(|row| row.id).collect();
+                    stats.archived += self
+                        .db
+                        .with_transaction(|tx| self.db.archive_lock_rows(tx, &ids))?
+                        as u64;
+                    if rows.len() < PRUNE_BATCH_SIZE {
+                        break;
+                    }
+                }
+            }
+        }
+        if let Some(purge_after_days) = policy.purge_after_days {
+            stats.purged = self.purge(purge_after_days)?;
+        }
+
+        self.metrics.record_prune(stats.archived, stats.purged);
+        Ok(stats)
+    }
+
+    fn purge(&self, purge_after_days: u64) -> Result<u64> {
+        let purge_db = match &self.purge_path {
+            Some(path) => Some(
+                Database::open(
+                    path,
+                    OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE,
+                    self.db.key().cloned(),
+                )
+                .with_context(|| format!("Failed to open purge database {}", path.display()))?,
+            ),
+            None => None,
+        };
+
+        let mut purged = 0;
+        loop {
+            let rows = self
+                .db
+                .purgeable_lock_rows(purge_after_days, PRUNE_BATCH_SIZE)?;
+            // Copied before deleting, so a failure in between leaves the locks in both databases
+            // rather than in neither. Locks already copied are skipped.
+            if let Some(purge_db) = &purge_db {
+                purge_db.with_transaction(|tx| purge_db.import_lock_rows(tx, &rows))?;
+            }
+            let ids: Vec<_> = rows.iter().map(|row| row.id).collect();
+            purged += self
+                .db
+                .with_transaction(|tx| self.db.delete_lock_rows(tx, &ids))?
+                as u64;
+            if rows.len() < PRUNE_BATCH_SIZE {
+                return Ok(purged);
+            }
+        }
+    }
+
+    /// Prunes with the configured policy every interval, forever
+    pub async fn run(self, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            match self.prune(self.policy) {
+                Ok(stats) if stats != PruneStats::default() => tracing::info!(
+                    "Archived {} unlocked locks, purged {}",
+                    stats.archived,
+                    stats.purged
+                ),
+                Ok(_) => {}
+                Err(e) => tracing::error!("Failed to prune unlocked locks: {:#}", e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{SlotInsertData, UnlockReason};
+
+    fn insert_locks(db: &Database, namespace: &str, count: u8) -> Result<()> {
+        let slots: Vec<SlotInsertData> = (0..count)
+            .map(|idx| SlotInsertData {
+                namespace: namespace.to_string(),
+                contract_address: "0x123".to_string(),
+                start_block: 100 + idx as u64,
+                btc_block: 200,
+                slot_index: vec![idx],
+                btc_txid: "txid1".to_string(),
+                revert_value: vec![],
+                current_value: vec![],
+                metadata: Vec::new(),
+                labels: Default::default(),
+                alt_btc_txids: Vec::new(),
+                required_confirmed_txids: 1,
+                locked_by: None,
+            })
+            .collect();
+        db.with_transaction(|tx| db.batch_insert_slot_locks(tx, &slots))?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_archives_by_sova_block() -> Result<()> {
+        let db = Database::new(rusqlite::Connection::open_in_memory()?)?;
+        insert_locks(&db, "", 3)?;
+        insert_locks(&db, "testnet", 1)?;
+        db.unlock_slot("", "0x123", &[0], 110, UnlockReason::Confirmed)?;
+        db.unlock_slot("", "0x123", &[1], 150, UnlockReason::Confirmed)?;
+        db.unlock_slot("testnet", "0x123", &[0], 110, UnlockReason::Confirmed)?;
+        let metrics = Arc::new(Metrics::default());
+        let pruner = Pruner::new(db.clone(), RetentionPolicy::default()).with_metrics(metrics);
+
+        // The default namespace is at block 150, so only the lock unlocked at 110 is old enough.
+        // The testnet lock is as old as its namespace's latest block.
+        let stats = pruner.prune(RetentionPolicy {
+            max_age_blocks: Some(40),
+            ..Default::default()
+        })?;
+        assert_eq!(stats.archived, 1);
+        assert!(db
+            .lock_rows(None, 0, 10)?
+            .iter()
+            .all(|row| row.lock.namespace == "testnet" || row.lock.end_block != Some(110)));
+
+        // Active locks are never archived
+        let stats = pruner.prune(RetentionPolicy {
+            max_age_blocks: Some(0),
+            ..Default::default()
+        })?;
+        assert_eq!(stats.archived, 2);
+        assert_eq!(db.lock_rows(None, 0, 10)?.len(), 1);
+
+        // Restored locks are visible again and exempt from the policy
+        assert_eq!(db.restore_locks("", "0x123", &[0])?, 1);
+        let stats = pruner.prune(RetentionPolicy {
+            max_age_blocks: Some(0),
+            ..Default::default()
+        })?;
+        assert_eq!(stats.archived, 0);
+        assert_eq!(db.lock_rows(None, 0, 10)?.len(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_purges_archived_locks() -> Result<()> {
+        let purge_path =
+            std::env::temp_dir().join(format!("sova-sentinel-purged-{}.db", std::process::id()));
+        let db = Database::new(rusqlite::Connection::open_in_memory()?)?;
+        insert_locks(&db, "", 2)?;
+        db.unlock_slot("", "0x123", &[0], 110, UnlockReason::RevertThreshold)?;
+        let pruner =
+            Pruner::new(db.clone(), RetentionPolicy::default()).with_purge_path(&purge_path);
+
+        let stats = pruner.prune(RetentionPolicy {
+            max_age_days: Some(0),
+            purge_after_days: Some(0),
+            ..Default::default()
+        })?;
+        assert_eq!(
+            stats,
+            PruneStats {
+                archived: 1,
+                purged: 1
+            }
+        );
+        let purge_db = Database::new(rusqlite::Connection::open(&purge_path)?)?;
+        let purged = purge_db.lock_rows(None, 0, 10)?;
+        assert_eq!(purged.len(), 1);
+        assert_eq!(
+            purged[0].lock.unlock_reason,
+            Some(UnlockReason::RevertThreshold)
+        );
+        // Nothing is left to restore
+        assert_eq!(db.restore_locks("", "0x123", &[0])?, 0);
+        assert_eq!(db.lock_rows(None, 0, 10)?.len(), 1);
+
+        std::fs::remove_file(&purge_path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,362 @@
+//! Trace sampling for request spans. Mutating RPCs are always traced, read-only status calls are
+//! sampled at a configurable rate, and warnings and errors are logged whatever the decision, so
+//! high-QPS status polling doesn't drown the tracing backend. Also sets up the log subscriber,
+//! writing either text or JSON lines.
+
+use crate::config::LoggingConfig;
+use crate::otlp::{parse_traceparent, OtlpLayer};
+use anyhow::Result;
+use hyper::HeaderMap;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::{Level, Metadata, Subscriber};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{filter, fmt, EnvFilter};
+
+/// Span field recording whether a request was sampled
+pub const SAMPLED_FIELD: &str = "sampled";
+
+/// Format of log lines
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human-readable lines
+    #[default]
+    Text,
+    /// One JSON object per line, with the fields of the enclosing spans, for log aggregation
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            other => Err(anyhow::anyhow!("Unsupported log format: {}", other)),
+        }
+    }
+}
+
+/// Paths of the read-only status calls, which are sampled. Every other call is always traced.
+const SAMPLED_PATHS: &[&str] = &[
+    "/slot_lock.SlotLockService/GetSlotStatus",
+    "/slot_lock.SlotLockService/BatchGetSlotStatus",
+    "/slot_lock.SlotLockService/PeekSlotStatus",
+    "/slot_lock.SlotLockService/BatchPeekSlotStatus",
+    "/slot_lock.SlotLockService/GetServerInfo",
+    "/slot_lock.SlotLockService/GetSentinelInfo",
+    "/slot_lock.SlotLockService/GetPublicKey",
+    "/slot_lock.SlotLockService/GetLockCommitment",
+    "/slot_lock.SlotLockService/GetLockInclusionProof",
+    "/health.Health/Check",
+];
+
+/// Calls sampled evenly at a rate
+#[derive(Debug)]
+struct Rate {
+    rate: f64,
+    calls: AtomicU64,
+}
+
+impl Rate {
+    fn new(rate: f64) -> Self {
+        Self {
+            rate: rate.clamp(0.0, 1.0),
+            calls: AtomicU64::new(0),
+        }
+    }
+
+    fn sample(&self) -> bool {
+        let call = self.calls.fetch_add(1, Ordering::Relaxed);
+        // Sampled when the running count of sampled calls goes up, which spreads them evenly
+        ((call + 1) as f64 * self.rate).floor() > (call as f64 * self.rate).floor()
+    }
+}
+
+/// Decides which requests are traced
+#[derive(Debug)]
+pub struct TraceSampler {
+    rate: Rate,
+    method_rates: HashMap<String, Rate>,
+}
+
+impl TraceSampler {
+    /// Samples read-only status calls at `rate`, between 0 (never) and 1 (always)
+    pub fn new(rate: f64) -> Self {
+        Self {
+            rate: Rate::new(rate),
+            method_rates: HashMap::new(),
+        }
+    }
+
+    /// Samples the calls of the RPC methods in `rates`, by method name such as `LockSlot`, at
+    /// their own rates instead, whether they are status calls or not
+    pub fn with_method_rates(mut self, rates: &HashMap<String, f64>) -> Self {
+        self.method_rates = rates
+            .iter()
+            .map(|(method, rate)| (method.clone(), Rate::new(*rate)))
+            .collect();
+        self
+    }
+
+    /// Returns whether the request for `path` is traced. Status calls, and calls of methods with
+    /// a rate of their own, are sampled evenly at their rate, unless the caller already sampled
+    /// them through a W3C `traceparent` header.
+    pub fn sample(&self, path: &str, headers: &HeaderMap) -> bool {
+        let method = path.rsplit('/').next().unwrap_or_default();
+        let rate = match self.method_rates.get(method) {
+            Some(rate) => rate,
+            None if SAMPLED_PATHS.contains(&path) => &self.rate,
+            None => return true,
+        };
+        caller_sampled(headers) || rate.sample()
+    }
+}
+
+/// Returns whether the `traceparent` header has its sampled flag set
+fn caller_sampled(headers: &HeaderMap) -> bool {
+    headers
+        .get("traceparent")
+        .and_then(|value| value.to_str().ok())
+        .and_then(parse_traceparent)
+        .is_some_and(|context| context.sampled)
+}
+
+/// Marks spans whose `sampled` field is false
+pub(crate) struct Unsampled;
+
+struct SampledVisitor(Option<bool>);
+
+impl Visit for SampledVisitor {
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        if field.name() == SAMPLED_FIELD {
+            self.0 = Some(value);
+        }
+    }
+
+    fn record_debug(&mut self, _field: &Field, _value: &dyn std::fmt::Debug) {}
+}
+
+/// Records the sampling decision of new spans, so events inside them can be filtered
+struct SamplingLayer;
+
+impl<S> Layer<S> for SamplingLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut visitor = SampledVisitor(None);
+        attrs.record(&mut visitor);
+        if visitor.0 == Some(false) {
+            if let Some(span) = ctx.span(id) {
+                span.extensions_mut().insert(Unsampled);
+            }
+        }
+    }
+}
+
+/// Drops info and lower events inside unsampled spans. Warnings and errors are always kept.
+fn is_logged<S>(metadata: &Metadata<'_>, ctx: &Context<'_, S>) -> bool
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    if !metadata.is_event() || *metadata.level() <= Level::WARN {
+        return true;
+    }
+    ctx.lookup_current().is_none_or(|span| {
+        span.scope()
+            .all(|span| span.extensions().get::<Unsampled>().is_none())
+    })
+}
+
+/// Builds the log subscriber: `env_filter`, with events of unsampled requests dropped, lines
+/// written in `format`, and spans exported through `otlp` if set
+pub(crate) fn subscriber<W>(
+    env_filter: EnvFilter,
+    writer: W,
+    format: LogFormat,
+    otlp: Option<OtlpLayer>,
+) -> impl Subscriber + Send + Sync
+where
+    W: for<'a> fmt::MakeWriter<'a> + Send + Sync + 'static,
+{
+    let log = match format {
+        LogFormat::Text => fmt::layer().with_writer(writer).boxed(),
+        LogFormat::Json => fmt::layer()
+            .json()
+            .with_current_span(true)
+            .with_span_list(true)
+            .with_writer(writer)
+            .boxed(),
+    };
+    tracing_subscriber::registry()
+        .with(env_filter)
+        .with(SamplingLayer)
+        .with(log.with_filter(filter::dynamic_filter_fn(is_logged)))
+        .with(otlp)
+}
+
+/// Returns the filter of log events and spans: `RUST_LOG` when set, or else the levels of
+/// `logging`. Only errors are logged when neither sets a level.
+pub fn env_filter(logging: &LoggingConfig) -> Result<EnvFilter> {
+    if std::env::var_os(EnvFilter::DEFAULT_ENV).is_some() {
+        return Ok(EnvFilter::from_default_env());
+    }
+    configured_filter(logging)
+}
+
+fn configured_filter(logging: &LoggingConfig) -> Result<EnvFilter> {
+    let level = match &logging.level {
+        Some(level) => level.parse()?,
+        None => LevelFilter::ERROR,
+    };
+    let directives: Vec<_> = std::iter::once(level.to_string())
+        .chain(
+            logging
+                .modules
+                .iter()
+                .map(|(module, level)| format!("{}={}", module, level)),
+        )
+        .collect();
+    Ok(EnvFilter::builder().parse(directives.join(","))?)
+}
+
+/// Installs the log subscriber, filtered by `env_filter`, as the global default
+pub fn init_tracing(env_filter: EnvFilter, format: LogFormat, otlp: Option<OtlpLayer>) {
+    subscriber(env_filter, std::io::stdout, format, otlp).init();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_samples_status_calls() {
+        let sampler = TraceSampler::new(0.25);
+        let headers = HeaderMap::new();
+        let sampled = (0..100)
+            .filter(|_| sampler.sample("/slot_lock.SlotLockService/GetSlotStatus", &headers))
+            .count();
+        assert_eq!(sampled, 25);
+        assert!(sampler.sample("/slot_lock.SlotLockService/LockSlot", &headers));
+
+        let sampler = TraceSampler::new(0.0);
+        let mut headers = HeaderMap::new();
+        assert!(!sampler.sample("/health.Health/Check", &headers));
+        headers.insert(
+            "traceparent",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"
+                .parse()
+                .unwrap(),
+        );
+        assert!(sampler.sample("/health.Health/Check", &headers));
+
+        // Methods with a rate of their own are sampled at it, status calls or not
+        let sampler = TraceSampler::new(1.0).with_method_rates(&HashMap::from([
+            ("LockSlot".to_string(), 0.5),
+            ("GetSlotStatus".to_string(), 0.0),
+        ]));
+        let headers = HeaderMap::new();
+        let sampled = (0..10)
+            .filter(|_| sampler.sample("/slot_lock.SlotLockService/LockSlot", &headers))
+            .count();
+        assert_eq!(sampled, 5);
+        assert!(!sampler.sample("/slot_lock.SlotLockService/GetSlotStatus", &headers));
+        assert!(sampler.sample("/slot_lock.SlotLockService/BatchGetSlotStatus", &headers));
+    }
+
+    #[test]
+    fn test_env_filter_from_logging_config() -> Result<()> {
+        let logging = LoggingConfig {
+            level: Some("warn".to_string()),
+            modules: [(
+                "sova_sentinel_server::service::bitcoin".to_string(),
+                "debug".to_string(),
+            )]
+            .into(),
+            ..LoggingConfig::default()
+        };
+        let filter = configured_filter(&logging)?.to_string();
+        assert!(filter.contains("sova_sentinel_server::service::bitcoin=debug"));
+        assert!(filter.contains("warn"));
+        assert_eq!(
+            configured_filter(&LoggingConfig::default())?.to_string(),
+            "error"
+        );
+        Ok(())
+    }
+
+    #[derive(Clone, Default)]
+    struct Output(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Output {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_drops_events_of_unsampled_requests() {
+        let output = Output::default();
+        let writer = output.clone();
+        let subscriber = subscriber(
+            EnvFilter::new("info"),
+            move || writer.clone(),
+            LogFormat::Text,
+            None,
+        );
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("request", sampled = false).in_scope(|| {
+                tracing::info!("unsampled status query");
+                tracing::error!("unsampled failure");
+            });
+            tracing::info_span!("request", sampled = true).in_scope(|| {
+                tracing::info!("sampled lock");
+            });
+        });
+
+        let output = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        assert!(!output.contains("unsampled status query"));
+        assert!(output.contains("unsampled failure"));
+        assert!(output.contains("sampled lock"));
+    }
+
+    #[test]
+    fn test_json_lines_carry_request_id() {
+        let output = Output::default();
+        let writer = output.clone();
+        let subscriber = subscriber(
+            EnvFilter::new("info"),
+            move || writer.clone(),
+            LogFormat::Json,
+            None,
+        );
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("request", sampled = true, request_id = "abc-123").in_scope(|| {
+                tracing::info_span!("db_transaction").in_scope(|| tracing::info!("slot locked"));
+            });
+        });
+
+        let output = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        let line: serde_json::Value = serde_json::from_str(output.trim()).unwrap();
+        assert_eq!(line["fields"]["message"], "slot locked");
+        assert_eq!(line["spans"][0]["request_id"], "abc-123");
+        assert_eq!(line["span"]["name"], "db_transaction");
+
+        assert_eq!("JSON".parse::<LogFormat>().unwrap(), LogFormat::Json);
+        assert!("yaml".parse::<LogFormat>().is_err());
+    }
+}
@@ -0,0 +1,1746 @@
+use crate::config::ThresholdOverride;
+use crate::db::ReplicationRole;
+use crate::db::{self, Database, LockEventCursor, LockEventKind, LockedSlot, ValueQuery};
+use crate::retention::{Pruner, RetentionPolicy};
+use crate::service::replication::Replication;
+use crate::service::status::{
+    database_status, field, invalid_field, not_standby_status, replication_disabled_status,
+    FieldViolations,
+};
+use crate::service::thresholds::ContractThresholds;
+use futures::Stream;
+use sova_sentinel_proto::proto::{
+    admin_service_server::AdminService, lock_event, search_locks_request, ColumnSchema,
+    ContractThresholdOverride, DescribeSchemaRequest, DescribeSchemaResponse, ExportEventsRequest,
+    ExportLocksRequest, FenceRequest, FenceResponse, GetLockChangesRequest, GetLockChangesResponse,
+    GetLockConflictStatsRequest, GetLockConflictStatsResponse, GetLockHistoryRequest,
+    GetLockHistoryResponse, GetReplicationStatusRequest, GetSlotStateAtRequest,
+    GetSlotStateAtResponse, GetSlotValueHistoryRequest, GetSlotValueHistoryResponse,
+    GetStatsRequest, GetStatsResponse, ImportLocksResponse, IndexSchema,
+    ListContractThresholdsRequest, ListContractThresholdsResponse, ListLocksByContractRequest,
+    ListLocksByContractResponse, ListLocksByTxidRequest, ListLocksByTxidResponse,
+    LockConflictStats, LockEvent, LockMatch, LockRecord, LockStats, PromoteRequest,
+    PromoteResponse, PruneLocksRequest, PruneLocksResponse, ReplicationStatus, RestoreLocksRequest,
+    RestoreLocksResponse, SearchLocksRequest, SearchLocksResponse, SetContractThresholdsRequest,
+    SetContractThresholdsResponse, SlotValueChange, TableSchema,
+};
+use std::pin::Pin;
+use std::sync::Arc;
+
+/// Number of locks returned by a search that doesn't set a limit
+const DEFAULT_SEARCH_LIMIT: usize = 100;
+/// Number of events or locks read from the database at a time by an export
+const EXPORT_PAGE_SIZE: usize = 1000;
+/// Number of locks written to the database in each transaction of an import
+const IMPORT_CHUNK_SIZE: usize = 1000;
+/// Number of lock changes returned by GetLockChanges when the request doesn't set a limit
+const DEFAULT_CHANGES_LIMIT: usize = 500;
+/// Window in sova blocks GetStats counts reverts in when the request doesn't set one
+const DEFAULT_RECENT_BLOCKS: u64 = 100;
+/// Number of locks in a ListLocksByContract page when the request doesn't set a limit
+const DEFAULT_CONTRACT_LOCKS_LIMIT: usize = 100;
+/// Most locks in a ListLocksByContract page
+const MAX_CONTRACT_LOCKS_LIMIT: usize = 1000;
+use tonic::{Request, Response, Status, Streaming};
+
+pub struct AdminServiceImpl {
+    db: Database,
+    contract_thresholds: Arc<ContractThresholds>,
+    pruner: Pruner,
+    replication: Option<Replication>,
+}
+
+impl AdminServiceImpl {
+    pub fn new(db: Database) -> Self {
+        Self {
+            pruner: Pruner::new(db.clone(), RetentionPolicy::default()),
+            db,
+            contract_thresholds: Arc::new(ContractThresholds::default()),
+            replication: None,
+        }
+    }
+
+    /// Sets the replication state reported by `GetReplicationStatus` and changed by `Promote`
+    /// and `Fence`, shared with the SlotLock service
+    pub fn with_replication(mut self, replication: Replication) -> Self {
+        self.replication = Some(replication);
+        self
+    }
+
+    /// Sets the pruner run by `PruneLocks`, whose policy fills in the limits a request leaves
+    /// unset
+    pub fn with_pruner(mut self, pruner: Pruner) -> Self {
+        self.pruner = pruner;
+        self
+    }
+
+    /// Sets the per-contract threshold overrides adjusted by `SetContractThresholds`, shared with
+    /// the SlotLock service
+    pub fn with_contract_thresholds(
+        mut self,
+        contract_thresholds: Arc<ContractThresholds>,
+    ) -> Self {
+        self.contract_thresholds = contract_thresholds;
+        self
+    }
+}
+
+impl From<db::TableSchema> for TableSchema {
+    fn from(table: db::TableSchema) -> Self {
+        Self {
+            name: table.name,
+            columns: table
+                .columns
+                .into_iter()
+                .map(|column| ColumnSchema {
+                    name: column.name,
+                    data_type: column.data_type,
+                    not_null: column.not_null,
+                    default_value: column.default_value.unwrap_or_default(),
+                    primary_key: column.primary_key,
+                })
+                .collect(),
+            indexes: table
+                .indexes
+                .into_iter()
+                .map(|index| IndexSchema {
+                    name: index.name,
+                    unique: index.unique,
+                    columns: index.columns,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<db::LockConflictStats> for LockConflictStats {
+    fn from(stats: db::LockConflictStats) -> Self {
+        Self {
+            contract_address: stats.contract_address,
+            conflicts: stats.conflicts,
+            last_conflict_at: stats.last_conflict_at,
+            namespace: stats.namespace,
+        }
+    }
+}
+
+impl From<db::LockStats> for LockStats {
+    fn from(stats: db::LockStats) -> Self {
+        Self {
+            average_lock_duration_blocks: stats.average_duration_blocks(),
+            namespace: stats.namespace,
+            contract_address: stats.contract_address,
+            active_locks: stats.active,
+            unlocked_locks: stats.unlocked,
+            reverted_locks: stats.reverted,
+        }
+    }
+}
+
+impl From<db::LockEvent> for LockEvent {
+    fn from(event: db::LockEvent) -> Self {
+        let lock = event.lock;
+        let (kind, unlock_reason) = match event.kind {
+            LockEventKind::Locked => (lock_event::Kind::Locked, String::new()),
+            LockEventKind::Unlocked => (
+                lock_event::Kind::Unlocked,
+                lock.unlock_reason
+                    .map(|reason| reason.as_str().to_string())
+                    .unwrap_or_default(),
+            ),
+        };
+        Self {
+            kind: kind as i32,
+            sova_block: event.cursor.block,
+            lock_id: event.cursor.lock_id as u64,
+            contract_address: lock.contract_address,
+            slot_index: lock.slot_index,
+            revert_value: lock.revert_value,
+            current_value: lock.current_value,
+            btc_txid: lock.btc_txid,
+            btc_block: lock.btc_block,
+            lock_start_block: lock.start_block,
+            unlock_reason,
+            namespace: lock.namespace,
+            metadata: lock.metadata,
+            labels: lock.labels.into_iter().collect(),
+        }
+    }
+}
+
+impl From<db::SlotValueChange> for SlotValueChange {
+    fn from(change: db::SlotValueChange) -> Self {
+        Self {
+            settled_value: change.settled_value().map(<[u8]>::to_vec),
+            lock_id: change.lock_id as u64,
+            start_block: change.start_block,
+            end_block: change.end_block,
+            unlock_reason: change
+                .unlock_reason
+                .map(|reason| reason.as_str().to_string())
+                .unwrap_or_default(),
+            btc_txid: change.btc_txid,
+            revert_value: change.revert_value,
+            current_value: change.current_value,
+            purged: change.purged,
+        }
+    }
+}
+
+impl From<db::LockRow> for LockRecord {
+    fn from(row: db::LockRow) -> Self {
+        let lock = row.lock;
+        Self {
+            id: row.id as u64,
+            namespace: lock.namespace,
+            contract_address: lock.contract_address,
+            slot_index: lock.slot_index,
+            revert_value: lock.revert_value,
+            current_value: lock.current_value,
+            btc_txid: lock.btc_txid,
+            btc_block: lock.btc_block,
+            start_block: lock.start_block,
+            end_block: lock.end_block,
+            unlock_reason: lock
+                .unlock_reason
+                .map(|reason| reason.as_str().to_string())
+                .unwrap_or_default(),
+            metadata: lock.metadata,
+            labels: lock.labels.into_iter().collect(),
+            created_at: row.created_at.unwrap_or_default(),
+            alt_btc_txids: lock.alt_btc_txids,
+            required_confirmed_txids: lock.required_confirmed_txids,
+            locked_by: lock.locked_by.unwrap_or_default(),
+        }
+    }
+}
+
+/// Converts an imported record, adding its invalid fields to `violations`
+pub(super) fn lock_row(
+    path: &str,
+    mut record: LockRecord,
+    violations: &mut FieldViolations,
+) -> db::LockRow {
+    violations.check_namespace_at(path, &record.namespace);
+    violations.check_slot(path, &record.contract_address, &mut record.slot_index);
+    violations.normalize_txid(&field(path, "btc_txid"), &mut record.btc_txid);
+    violations.normalize_alt_txids(path, &record.btc_txid, &mut record.alt_btc_txids);
+    violations.normalize_required_txids(
+        path,
+        &record.alt_btc_txids,
+        &mut record.required_confirmed_txids,
+    );
+    violations.check_lock_data(path, &record.metadata, &record.labels);
+    if record
+        .end_block
+        .is_some_and(|end_block| end_block < record.start_block)
+    {
+        violations.add(
+            field(path, "end_block"),
+            "must not be lower than start_block",
+        );
+    }
+    let unlock_reason = match (record.end_block, record.unlock_reason.as_str()) {
+        (_, "") => None,
+        (None, _) => {
+            violations.add(
+                field(path, "unlock_reason"),
+                "must be empty while the lock is active",
+            );
+            None
+        }
+        (Some(_), reason) => match reason.parse::<db::UnlockReason>() {
+            Ok(reason) => Some(reason),
+            Err(e) => {
+                violations.add(field(path, "unlock_reason"), e.to_string());
+                None
+            }
+        },
+    };
+    if !record.created_at.is_empty() && !is_timestamp(&record.created_at) {
+        violations.add(
+            field(path, "created_at"),
+            "must be formatted as YYYY-MM-DD HH:MM:SS",
+        );
+    }
+
+    db::LockRow {
+        id: record.id as i64,
+        lock: LockedSlot {
+            btc_txid: record.btc_txid,
+            btc_block: record.btc_block,
+            contract_address: record.contract_address,
+            slot_index: record.slot_index,
+            revert_value: record.revert_value,
+            current_value: record.current_value,
+            start_block: record.start_block,
+            end_block: record.end_block,
+            unlock_reason,
+            namespace: record.namespace,
+            metadata: record.metadata,
+            labels: record.labels.into_iter().collect(),
+            // Group ids are local to a sentinel, so imported locks are evaluated on their own
+            lock_group: None,
+            alt_btc_txids: record.alt_btc_txids,
+            required_confirmed_txids: record.required_confirmed_txids,
+            locked_at: None,
+            locked_by: (!record.locked_by.is_empty()).then_some(record.locked_by),
+        },
+        created_at: (!record.created_at.is_empty()).then_some(record.created_at),
+    }
+}
+
+/// Returns whether `timestamp` is formatted as SQLite's `CURRENT_TIMESTAMP`, "YYYY-MM-DD HH:MM:SS"
+fn is_timestamp(timestamp: &str) -> bool {
+    const FORMAT: &[u8] = b"0000-00-00 00:00:00";
+    timestamp.len() == FORMAT.len()
+        && timestamp.bytes().zip(FORMAT).all(|(b, &f)| {
+            if f == b'0' {
+                b.is_ascii_digit()
+            } else {
+                b == f
+            }
+        })
+}
+
+/// Converts a search request's query, rejecting queries that would match every value
+fn value_query(
+    query: Option<search_locks_request::Query>,
+    has_labels: bool,
+) -> anyhow::Result<Option<ValueQuery>> {
+    match query {
+        Some(search_locks_request::Query::ValuePattern(pattern)) if !pattern.is_empty() => {
+            Ok(Some(ValueQuery::Contains(pattern)))
+        }
+        Some(search_locks_request::Query::ValueSha256(hash)) => {
+            let hash = hash
+                .try_into()
+                .map_err(|_| anyhow::anyhow!("value_sha256 must be 32 bytes"))?;
+            Ok(Some(ValueQuery::Sha256(hash)))
+        }
+        None if has_labels => Ok(None),
+        _ => Err(anyhow::anyhow!(
+            "Either a non-empty value_pattern or value_sha256 is required unless labels are set"
+        )),
+    }
+}
+
+#[tonic::async_trait]
+impl AdminService for AdminServiceImpl {
+    type ExportEventsStream = Pin<Box<dyn Stream<Item = Result<LockEvent, Status>> + Send>>;
+    type ExportLocksStream = Pin<Box<dyn Stream<Item = Result<LockRecord, Status>> + Send>>;
+
+    async fn describe_schema(
+        &self,
+        _request: Request<DescribeSchemaRequest>,
+    ) -> Result<Response<DescribeSchemaResponse>, Status> {
+        let schema_version = self.db.schema_version().map_err(database_status)?;
+        let tables = self.db.describe_schema().map_err(database_status)?;
+
+        Ok(Response::new(DescribeSchemaResponse {
+            schema_version,
+            tables: tables.into_iter().map(Into::into).collect(),
+        }))
+    }
+
+    async fn get_lock_conflict_stats(
+        &self,
+        request: Request<GetLockConflictStatsRequest>,
+    ) -> Result<Response<GetLockConflictStatsResponse>, Status> {
+        let req = request.into_inner();
+        let contract_address =
+            (!req.contract_address.is_empty()).then_some(req.contract_address.as_str());
+
+        let stats = self
+            .db
+            .lock_conflict_stats(req.namespace.as_deref(), contract_address)
+            .map_err(database_status)?;
+
+        Ok(Response::new(GetLockConflictStatsResponse {
+            contracts: stats.into_iter().map(Into::into).collect(),
+        }))
+    }
+
+    async fn get_stats(
+        &self,
+        request: Request<GetStatsRequest>,
+    ) -> Result<Response<GetStatsResponse>, Status> {
+        let req = request.into_inner();
+        let contract_address =
+            (!req.contract_address.is_empty()).then_some(req.contract_address.as_str());
+        let recent_blocks = match req.recent_blocks {
+            0 => DEFAULT_RECENT_BLOCKS,
+            blocks => blocks,
+        };
+
+        let stats = self
+            .db
+            .lock_stats(req.namespace.as_deref(), contract_address)
+            .map_err(database_status)?;
+        let recent_reverts = self
+            .db
+            .recent_reverts(req.namespace.as_deref(), contract_address, recent_blocks)
+            .map_err(database_status)?;
+        let total = stats
+            .iter()
+            .fold(db::LockStats::default(), |total, stats| db::LockStats {
+                active: total.active + stats.active,
+                unlocked: total.unlocked + stats.unlocked,
+                reverted: total.reverted + stats.reverted,
+                duration_blocks: total.duration_blocks + stats.duration_blocks,
+                ..total
+            });
+
+        Ok(Response::new(GetStatsResponse {
+            total: Some(total.into()),
+            contracts: stats.into_iter().map(Into::into).collect(),
+            recent_reverts,
+            recent_blocks,
+        }))
+    }
+
+    async fn search_locks(
+        &self,
+        request: Request<SearchLocksRequest>,
+    ) -> Result<Response<SearchLocksResponse>, Status> {
+        let req = request.into_inner();
+        let query = value_query(req.query, !req.labels.is_empty())
+            .map_err(|e| invalid_field("query", e.to_string()))?;
+        let labels = req.labels.into_iter().collect();
+        let contract_address =
+            (!req.contract_address.is_empty()).then_some(req.contract_address.as_str());
+        let limit = match req.limit {
+            0 => DEFAULT_SEARCH_LIMIT,
+            limit => limit as usize,
+        };
+
+        let locks = self
+            .db
+            .search_locks(
+                query.as_ref(),
+                &labels,
+                req.namespace.as_deref(),
+                contract_address,
+                req.include_unlocked,
+                limit,
+            )
+            .map_err(database_status)?;
+
+        Ok(Response::new(SearchLocksResponse {
+            locks: locks
+                .into_iter()
+                .map(|lock| LockMatch {
+                    revert_value_matched: query
+                        .as_ref()
+                        .is_some_and(|query| query.matches(&lock.revert_value)),
+                    current_value_matched: query
+                        .as_ref()
+                        .is_some_and(|query| query.matches(&lock.current_value)),
+                    contract_address: lock.contract_address,
+                    slot_index: lock.slot_index,
+                    revert_value: lock.revert_value,
+                    current_value: lock.current_value,
+                    btc_txid: lock.btc_txid,
+                    btc_block: lock.btc_block,
+                    start_block: lock.start_block,
+                    end_block: lock.end_block.unwrap_or_default(),
+                    unlock_reason: lock
+                        .unlock_reason
+                        .map(|reason| reason.as_str().to_string())
+                        .unwrap_or_default(),
+                    namespace: lock.namespace,
+                    metadata: lock.metadata,
+                    labels: lock.labels.into_iter().collect(),
+                })
+                .collect(),
+        }))
+    }
+
+    async fn set_contract_thresholds(
+        &self,
+        request: Request<SetContractThresholdsRequest>,
+    ) -> Result<Response<SetContractThresholdsResponse>, Status> {
+        let req = request.into_inner();
+        if req.contract_address.is_empty() {
+            return Err(invalid_field("contract_address", "is required"));
+        }
+        let thresholds = ThresholdOverride {
+            confirmation_threshold: (req.confirmation_threshold > 0)
+                .then_some(req.confirmation_threshold),
+            revert_threshold: (req.revert_threshold > 0).then_some(req.revert_threshold),
+        };
+
+        tracing::info!(
+            "SetContractThresholds: contract={}, confirmation_threshold={:?}, revert_threshold={:?}",
+            req.contract_address,
+            thresholds.confirmation_threshold,
+            thresholds.revert_threshold
+        );
+        self.contract_thresholds
+            .set(&req.contract_address, thresholds);
+
+        Ok(Response::new(SetContractThresholdsResponse {}))
+    }
+
+    async fn list_contract_thresholds(
+        &self,
+        _request: Request<ListContractThresholdsRequest>,
+    ) -> Result<Response<ListContractThresholdsResponse>, Status> {
+        Ok(Response::new(ListContractThresholdsResponse {
+            contracts: self
+                .contract_thresholds
+                .list()
+                .into_iter()
+                .map(|(contract_address, thresholds)| ContractThresholdOverride {
+                    contract_address,
+                    confirmation_threshold: thresholds.confirmation_threshold.unwrap_or_default(),
+                    revert_threshold: thresholds.revert_threshold.unwrap_or_default(),
+                })
+                .collect(),
+        }))
+    }
+
+    async fn export_events(
+        &self,
+        request: Request<ExportEventsRequest>,
+    ) -> Result<Response<Self::ExportEventsStream>, Status> {
+        let req = request.into_inner();
+        if req.end_sova_block < req.start_sova_block {
+            return Err(invalid_field(
+                "end_sova_block",
+                "must not be lower than start_sova_block",
+            ));
+        }
+        tracing::info!(
+            "ExportEvents: start_sova_block={}, end_sova_block={}, namespace={:?}",
+            req.start_sova_block,
+            req.end_sova_block,
+            req.namespace
+        );
+
+        // Reads the range a page at a time, each page resuming after the last event of the
+        // previous one. The state is `None` once the last page was read.
+        let db = self.db.clone();
+        let pages = futures::stream::unfold(Some(None::<LockEventCursor>), move |after| {
+            let db = db.clone();
+            let namespace = req.namespace.clone();
+            async move {
+                let after = after?;
+                match db.lock_events(
+                    namespace.as_deref(),
+                    req.start_sova_block,
+                    req.end_sova_block,
+                    after,
+                    EXPORT_PAGE_SIZE,
+                ) {
+                    Ok(events) => {
+                        let next = match events.last() {
+                            Some(event) if events.len() == EXPORT_PAGE_SIZE => {
+                                Some(Some(event.cursor))
+                            }
+                            _ => None,
+                        };
+                        let events: Vec<_> =
+                            events.into_iter().map(LockEvent::from).map(Ok).collect();
+                        Some((events, next))
+                    }
+                    Err(e) => Some((vec![Err(database_status(e))], None)),
+                }
+            }
+        });
+
+        Ok(Response::new(Box::pin(futures::StreamExt::flat_map(
+            pages,
+            futures::stream::iter,
+        ))))
+    }
+
+    async fn export_locks(
+        &self,
+        request: Request<ExportLocksRequest>,
+    ) -> Result<Response<Self::ExportLocksStream>, Status> {
+        let req = request.into_inner();
+        tracing::info!("ExportLocks: namespace={:?}", req.namespace);
+
+        // Reads the locks a page at a time, each page resuming after the id of the last lock of
+        // the previous one. The state is `None` once the last page was read.
+        let db = self.db.clone();
+        let pages = futures::stream::unfold(Some(0), move |after_id| {
+            let db = db.clone();
+            let namespace = req.namespace.clone();
+            async move {
+                let after_id = after_id?;
+                match db.lock_rows(namespace.as_deref(), after_id, EXPORT_PAGE_SIZE) {
+                    Ok(rows) => {
+                        let next = match rows.last() {
+                            Some(row) if rows.len() == EXPORT_PAGE_SIZE => Some(row.id),
+                            _ => None,
+                        };
+                        let records: Vec<_> =
+                            rows.into_iter().map(LockRecord::from).map(Ok).collect();
+                        Some((records, next))
+                    }
+                    Err(e) => Some((vec![Err(database_status(e))], None)),
+                }
+            }
+        });
+
+        Ok(Response::new(Box::pin(futures::StreamExt::flat_map(
+            pages,
+            futures::stream::iter,
+        ))))
+    }
+
+    async fn import_locks(
+        &self,
+        request: Request<Streaming<LockRecord>>,
+    ) -> Result<Response<ImportLocksResponse>, Status> {
+        let mut records = request.into_inner();
+        let mut response = ImportLocksResponse::default();
+        let mut index = 0;
+
+        // Imports a chunk at a time, each in its own transaction. An invalid record fails the
+        // import after the chunks before it were committed, which is harmless since importing the
+        // same locks again skips them.
+        let mut done = false;
+        while !done {
+            let mut violations = FieldViolations::default();
+            let mut rows = Vec::with_capacity(IMPORT_CHUNK_SIZE);
+            while rows.len() < IMPORT_CHUNK_SIZE {
+                let Some(record) = records.message().await? else {
+                    done = true;
+                    break;
+                };
+                rows.push(lock_row(
+                    &format!("records[{}]", index),
+                    record,
+                    &mut violations,
+                ));
+                index += 1;
+            }
+            if let Some(status) = violations.into_status() {
+                return Err(status);
+            }
+            if rows.is_empty() {
+                break;
+            }
+
+            let imported = self
+                .db
+                .with_transaction(|tx| self.db.import_lock_rows(tx, &rows))
+                .map_err(database_status)?;
+            let count = imported.iter().filter(|imported| **imported).count() as u64;
+            response.imported += count;
+            response.skipped += imported.len() as u64 - count;
+        }
+
+        tracing::info!(
+            "ImportLocks: imported={}, skipped={}",
+            response.imported,
+            response.skipped
+        );
+        Ok(Response::new(response))
+    }
+
+    async fn prune_locks(
+        &self,
+        request: Request<PruneLocksRequest>,
+    ) -> Result<Response<PruneLocksResponse>, Status> {
+        let req = request.into_inner();
+        let configured = self.pruner.policy();
+        let policy = RetentionPolicy {
+            max_age_blocks: req.max_age_blocks.or(configured.max_age_blocks),
+            max_age_days: req.max_age_days.or(configured.max_age_days),
+            purge_after_days: req.purge_after_days.or(configured.purge_after_days),
+        };
+        if policy.is_empty() {
+            return Err(invalid_field(
+                "max_age_blocks",
+                "max_age_blocks, max_age_days or purge_after_days is required without a configured retention policy",
+            ));
+        }
+
+        let stats = self.pruner.prune(policy).map_err(database_status)?;
+        tracing::info!(
+            "PruneLocks: max_age_blocks={:?}, max_age_days={:?}, purge_after_days={:?}, archived={}, purged={}",
+            policy.max_age_blocks,
+            policy.max_age_days,
+            policy.purge_after_days,
+            stats.archived,
+            stats.purged
+        );
+        Ok(Response::new(PruneLocksResponse {
+            archived: stats.archived,
+            purged: stats.purged,
+        }))
+    }
+
+    async fn restore_locks(
+        &self,
+        request: Request<RestoreLocksRequest>,
+    ) -> Result<Response<RestoreLocksResponse>, Status> {
+        let mut req = request.into_inner();
+        let mut violations = FieldViolations::default();
+        violations.check_namespace(&req.namespace);
+        violations.check_slot("", &req.contract_address, &mut req.slot_index);
+        if let Some(status) = violations.into_status() {
+            return Err(status);
+        }
+
+        let restored = self
+            .db
+            .restore_locks(&req.namespace, &req.contract_address, &req.slot_index)
+            .map_err(database_status)?;
+        tracing::info!(
+            "RestoreLocks: namespace={:?}, contract={}, slot={}, restored={}",
+            req.namespace,
+            req.contract_address,
+            hex::encode(&req.slot_index),
+            restored
+        );
+        Ok(Response::new(RestoreLocksResponse {
+            restored: restored as u64,
+        }))
+    }
+
+    async fn get_lock_history(
+        &self,
+        request: Request<GetLockHistoryRequest>,
+    ) -> Result<Response<GetLockHistoryResponse>, Status> {
+        let mut req = request.into_inner();
+        let mut violations = FieldViolations::default();
+        violations.check_namespace(&req.namespace);
+        violations.check_slot("", &req.contract_address, &mut req.slot_index);
+        if let Some(status) = violations.into_status() {
+            return Err(status);
+        }
+
+        let locks = self
+            .db
+            .slot_lock_rows(&req.namespace, &req.contract_address, &req.slot_index)
+            .map_err(database_status)?;
+        Ok(Response::new(GetLockHistoryResponse {
+            locks: locks.into_iter().map(LockRecord::from).collect(),
+        }))
+    }
+
+    async fn get_slot_state_at(
+        &self,
+        request: Request<GetSlotStateAtRequest>,
+    ) -> Result<Response<GetSlotStateAtResponse>, Status> {
+        let mut req = request.into_inner();
+        let mut violations = FieldViolations::default();
+        violations.check_namespace(&req.namespace);
+        violations.check_slot("", &req.contract_address, &mut req.slot_index);
+        if let Some(status) = violations.into_status() {
+            return Err(status);
+        }
+
+        let (lock, last_ended) = self
+            .db
+            .slot_lock_rows_at(
+                &req.namespace,
+                &req.contract_address,
+                &req.slot_index,
+                req.sova_block,
+            )
+            .map_err(database_status)?;
+        Ok(Response::new(GetSlotStateAtResponse {
+            locked: lock.is_some(),
+            lock: lock.map(LockRecord::from),
+            last_ended: last_ended.map(LockRecord::from),
+        }))
+    }
+
+    async fn get_slot_value_history(
+        &self,
+        request: Request<GetSlotValueHistoryRequest>,
+    ) -> Result<Response<GetSlotValueHistoryResponse>, Status> {
+        let mut req = request.into_inner();
+        let mut violations = FieldViolations::default();
+        violations.check_namespace(&req.namespace);
+        violations.check_slot("", &req.contract_address, &mut req.slot_index);
+        if let Some(status) = violations.into_status() {
+            return Err(status);
+        }
+
+        let changes = self
+            .db
+            .slot_value_history(&req.namespace, &req.contract_address, &req.slot_index)
+            .map_err(database_status)?;
+        Ok(Response::new(GetSlotValueHistoryResponse {
+            changes: changes.into_iter().map(SlotValueChange::from).collect(),
+        }))
+    }
+
+    async fn list_locks_by_txid(
+        &self,
+        request: Request<ListLocksByTxidRequest>,
+    ) -> Result<Response<ListLocksByTxidResponse>, Status> {
+        let mut req = request.into_inner();
+        let mut violations = FieldViolations::default();
+        violations.check_namespace(&req.namespace);
+        violations.normalize_txid("btc_txid", &mut req.btc_txid);
+        if let Some(status) = violations.into_status() {
+            return Err(status);
+        }
+
+        let locks = self
+            .db
+            .txid_lock_rows(&req.namespace, &req.btc_txid, req.include_unlocked)
+            .map_err(database_status)?;
+        Ok(Response::new(ListLocksByTxidResponse {
+            locks: locks.into_iter().map(LockRecord::from).collect(),
+        }))
+    }
+
+    async fn list_locks_by_contract(
+        &self,
+        request: Request<ListLocksByContractRequest>,
+    ) -> Result<Response<ListLocksByContractResponse>, Status> {
+        let req = request.into_inner();
+        let mut violations = FieldViolations::default();
+        violations.check_namespace(&req.namespace);
+        violations.check_contract_address("", &req.contract_address);
+        if req
+            .end_block
+            .is_some_and(|end_block| end_block < req.start_block)
+        {
+            violations.add("end_block", "must not be below start_block");
+        }
+        if let Some(status) = violations.into_status() {
+            return Err(status);
+        }
+        let limit = match req.limit {
+            0 => DEFAULT_CONTRACT_LOCKS_LIMIT,
+            limit => (limit as usize).min(MAX_CONTRACT_LOCKS_LIMIT),
+        };
+
+        // One lock past the page tells whether there is another page
+        let mut locks = self
+            .db
+            .contract_lock_rows(
+                &req.namespace,
+                &req.contract_address,
+                req.start_block,
+                req.end_block,
+                req.after_id as i64,
+                limit + 1,
+            )
+            .map_err(database_status)?;
+        let next_after_id = if locks.len() > limit {
+            locks.truncate(limit);
+            locks.last().map_or(0, |lock| lock.id as u64)
+        } else {
+            0
+        };
+        Ok(Response::new(ListLocksByContractResponse {
+            locks: locks.into_iter().map(LockRecord::from).collect(),
+            next_after_id,
+        }))
+    }
+
+    async fn get_lock_changes(
+        &self,
+        request: Request<GetLockChangesRequest>,
+    ) -> Result<Response<GetLockChangesResponse>, Status> {
+        let req = request.into_inner();
+        let limit = match req.limit {
+            0 => DEFAULT_CHANGES_LIMIT,
+            limit => limit as usize,
+        };
+        let status = self
+            .replication
+            .as_ref()
+            .map(Replication::status)
+            .transpose()
+            .map_err(database_status)?;
+        let changes = self
+            .db
+            .lock_changes(req.after_seq, limit)
+            .map_err(database_status)?;
+        Ok(Response::new(GetLockChangesResponse {
+            changes: changes.into_iter().map(Into::into).collect(),
+            status,
+        }))
+    }
+
+    async fn get_replication_status(
+        &self,
+        _request: Request<GetReplicationStatusRequest>,
+    ) -> Result<Response<ReplicationStatus>, Status> {
+        let replication = self
+            .replication
+            .as_ref()
+            .ok_or_else(replication_disabled_status)?;
+        Ok(Response::new(
+            replication.status().map_err(database_status)?,
+        ))
+    }
+
+    async fn promote(
+        &self,
+        _request: Request<PromoteRequest>,
+    ) -> Result<Response<PromoteResponse>, Status> {
+        let replication = self
+            .replication
+            .as_ref()
+            .ok_or_else(replication_disabled_status)?;
+        let role = replication.state().role;
+        if role != ReplicationRole::Standby {
+            return Err(not_standby_status(role.as_str()));
+        }
+
+        let primary_fenced = replication.promote().await.map_err(database_status)?;
+        Ok(Response::new(PromoteResponse {
+            status: Some(replication.status().map_err(database_status)?),
+            primary_fenced,
+        }))
+    }
+
+    async fn fence(
+        &self,
+        request: Request<FenceRequest>,
+    ) -> Result<Response<FenceResponse>, Status> {
+        let replication = self
+            .replication
+            .as_ref()
+            .ok_or_else(replication_disabled_status)?;
+        let epoch = request.into_inner().epoch;
+        if replication.fence(epoch).map_err(database_status)? {
+            tracing::info!("Fence: epoch={}", epoch);
+        }
+        Ok(Response::new(FenceResponse {
+            status: Some(replication.status().map_err(database_status)?),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+
+    #[tokio::test]
+    async fn test_describe_schema() -> Result<(), Box<dyn std::error::Error>> {
+        let db = Database::new(rusqlite::Connection::open_in_memory()?)?;
+        let service = AdminServiceImpl::new(db);
+
+        let response = service
+            .describe_schema(Request::new(DescribeSchemaRequest {}))
+            .await?;
+        assert_eq!(response.get_ref().schema_version, db::SCHEMA_VERSION);
+
+        let slot_locks = response
+            .get_ref()
+            .tables
+            .iter()
+            .find(|table| table.name == "slot_locks")
+            .unwrap();
+        let created_at = slot_locks
+            .columns
+            .iter()
+            .find(|column| column.name == "created_at")
+            .unwrap();
+        assert_eq!(created_at.default_value, "CURRENT_TIMESTAMP");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_lock_conflict_stats() -> Result<(), Box<dyn std::error::Error>> {
+        let db = Database::new(rusqlite::Connection::open_in_memory()?)?;
+        db.with_transaction(|tx| db.record_lock_conflicts(tx, "", &["0x123", "0x456", "0x123"]))?;
+        let service = AdminServiceImpl::new(db);
+
+        let response = service
+            .get_lock_conflict_stats(Request::new(GetLockConflictStatsRequest {
+                namespace: None,
+                contract_address: String::new(),
+            }))
+            .await?;
+        let contracts = &response.get_ref().contracts;
+        assert_eq!(contracts.len(), 2);
+        assert_eq!(contracts[0].contract_address, "0x123");
+        assert_eq!(contracts[0].conflicts, 2);
+        assert!(!contracts[0].last_conflict_at.is_empty());
+
+        let response = service
+            .get_lock_conflict_stats(Request::new(GetLockConflictStatsRequest {
+                namespace: None,
+                contract_address: "0x456".to_string(),
+            }))
+            .await?;
+        assert_eq!(response.get_ref().contracts.len(), 1);
+        assert_eq!(response.get_ref().contracts[0].conflicts, 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_stats() -> Result<(), Box<dyn std::error::Error>> {
+        let db = Database::new(rusqlite::Connection::open_in_memory()?)?;
+        let slots = ["0x123", "0x123", "0x456"]
+            .into_iter()
+            .enumerate()
+            .map(|(idx, contract_address)| db::SlotInsertData {
+                namespace: String::new(),
+                contract_address: contract_address.to_string(),
+                start_block: 100,
+                btc_block: 200,
+                slot_index: vec![idx as u8],
+                btc_txid: "txid1".to_string(),
+                revert_value: vec![4],
+                current_value: vec![7],
+                metadata: Vec::new(),
+                labels: Default::default(),
+                alt_btc_txids: Vec::new(),
+                required_confirmed_txids: 1,
+                locked_by: None,
+            })
+            .collect::<Vec<_>>();
+        db.with_transaction(|tx| db.batch_insert_slot_locks(tx, &slots))?;
+        db.unlock_slot("", "0x123", &[0], 110, db::UnlockReason::Confirmed)?;
+        db.unlock_slot("", "0x456", &[2], 400, db::UnlockReason::RevertThreshold)?;
+        let service = AdminServiceImpl::new(db);
+
+        let response = service
+            .get_stats(Request::new(GetStatsRequest {
+                namespace: None,
+                contract_address: String::new(),
+                recent_blocks: 0,
+            }))
+            .await?
+            .into_inner();
+        let total = response.total.unwrap();
+        assert_eq!(
+            (
+                total.active_locks,
+                total.unlocked_locks,
+                total.reverted_locks
+            ),
+            (1, 1, 1)
+        );
+        assert_eq!(total.average_lock_duration_blocks, 155.0);
+        assert_eq!(response.contracts.len(), 2);
+        assert_eq!(response.contracts[0].contract_address, "0x123");
+        assert_eq!(response.contracts[0].average_lock_duration_blocks, 10.0);
+        assert_eq!((response.recent_reverts, response.recent_blocks), (1, 100));
+
+        let response = service
+            .get_stats(Request::new(GetStatsRequest {
+                namespace: Some("testnet".to_string()),
+                contract_address: String::new(),
+                recent_blocks: 10,
+            }))
+            .await?
+            .into_inner();
+        assert!(response.contracts.is_empty());
+        assert_eq!(response.total.unwrap().active_locks, 0);
+        assert_eq!(response.recent_reverts, 0);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_search_locks() -> Result<(), Box<dyn std::error::Error>> {
+        let db = Database::new(rusqlite::Connection::open_in_memory()?)?;
+        db.with_transaction(|tx| {
+            db.batch_insert_slot_locks(
+                tx,
+                &[db::SlotInsertData {
+                    namespace: String::new(),
+                    contract_address: "0x123".to_string(),
+                    start_block: 100,
+                    btc_block: 200,
+                    slot_index: vec![1],
+                    btc_txid: "txid1".to_string(),
+                    revert_value: vec![0x0a, 0x0b],
+                    current_value: vec![0x0c],
+                    metadata: vec![0x0d],
+                    labels: [("deposit".to_string(), "42".to_string())].into(),
+                    alt_btc_txids: Vec::new(),
+                    required_confirmed_txids: 0,
+                    locked_by: None,
+                }],
+            )
+        })?;
+        let service = AdminServiceImpl::new(db);
+        let search = |query| SearchLocksRequest {
+            namespace: None,
+            query: Some(query),
+            contract_address: String::new(),
+            include_unlocked: false,
+            limit: 0,
+            labels: Default::default(),
+        };
+
+        let response = service
+            .search_locks(Request::new(search(
+                search_locks_request::Query::ValuePattern(vec![0x0b]),
+            )))
+            .await?;
+        let locks = &response.get_ref().locks;
+        assert_eq!(locks.len(), 1);
+        assert_eq!(locks[0].btc_txid, "txid1");
+        assert!(locks[0].revert_value_matched);
+        assert!(!locks[0].current_value_matched);
+
+        let status = service
+            .search_locks(Request::new(search(
+                search_locks_request::Query::ValuePattern(vec![]),
+            )))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        let status = service
+            .search_locks(Request::new(search(
+                search_locks_request::Query::ValueSha256(vec![0; 4]),
+            )))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+
+        // Labels alone are enough to search by
+        let by_label = |labels| SearchLocksRequest {
+            query: None,
+            labels,
+            ..search(search_locks_request::Query::ValuePattern(vec![]))
+        };
+        let response = service
+            .search_locks(Request::new(by_label(
+                [("deposit".to_string(), "42".to_string())].into(),
+            )))
+            .await?;
+        let locks = &response.get_ref().locks;
+        assert_eq!(locks.len(), 1);
+        assert_eq!(locks[0].metadata, vec![0x0d]);
+        assert!(!locks[0].revert_value_matched);
+        let status = service
+            .search_locks(Request::new(by_label(Default::default())))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_contract_thresholds() -> Result<(), Box<dyn std::error::Error>> {
+        let db = Database::new(rusqlite::Connection::open_in_memory()?)?;
+        let contract_thresholds = Arc::new(ContractThresholds::default());
+        let service =
+            AdminServiceImpl::new(db).with_contract_thresholds(contract_thresholds.clone());
+
+        for (contract_address, confirmation_threshold) in [("0xBridge", 12), ("0xtoken", 1)] {
+            service
+                .set_contract_thresholds(Request::new(SetContractThresholdsRequest {
+                    contract_address: contract_address.to_string(),
+                    confirmation_threshold,
+                    revert_threshold: 0,
+                }))
+                .await?;
+        }
+        assert_eq!(
+            contract_thresholds.get("0xbridge").confirmation_threshold,
+            Some(12)
+        );
+
+        // Unsetting both thresholds removes the override
+        service
+            .set_contract_thresholds(Request::new(SetContractThresholdsRequest {
+                contract_address: "0xTOKEN".to_string(),
+                confirmation_threshold: 0,
+                revert_threshold: 0,
+            }))
+            .await?;
+        let response = service
+            .list_contract_thresholds(Request::new(ListContractThresholdsRequest {}))
+            .await?;
+        assert_eq!(
+            response.get_ref().contracts,
+            vec![ContractThresholdOverride {
+                contract_address: "0xbridge".to_string(),
+                confirmation_threshold: 12,
+                revert_threshold: 0,
+            }]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_export_events() -> Result<(), Box<dyn std::error::Error>> {
+        let db = Database::new(rusqlite::Connection::open_in_memory()?)?;
+        let slots: Vec<db::SlotInsertData> = (0..3u8)
+            .map(|idx| db::SlotInsertData {
+                namespace: String::new(),
+                contract_address: "0x123".to_string(),
+                start_block: 100 + idx as u64,
+                btc_block: 200,
+                slot_index: vec![idx],
+                btc_txid: "txid1".to_string(),
+                revert_value: vec![],
+                current_value: vec![],
+                metadata: Vec::new(),
+                labels: Default::default(),
+                alt_btc_txids: Vec::new(),
+                required_confirmed_txids: 0,
+                locked_by: None,
+            })
+            .collect();
+        db.with_transaction(|tx| db.batch_insert_slot_locks(tx, &slots))?;
+        db.unlock_slot("", "0x123", &[0], 101, db::UnlockReason::RevertThreshold)?;
+        let service = AdminServiceImpl::new(db);
+
+        let response = service
+            .export_events(Request::new(ExportEventsRequest {
+                namespace: None,
+                start_sova_block: 100,
+                end_sova_block: 101,
+            }))
+            .await?;
+        let events: Vec<_> = response
+            .into_inner()
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect::<Result<_, _>>()?;
+        let summary: Vec<_> = events
+            .iter()
+            .map(|event| (event.sova_block, event.kind(), event.unlock_reason.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (100, lock_event::Kind::Locked, ""),
+                (101, lock_event::Kind::Unlocked, "revert-threshold"),
+                (101, lock_event::Kind::Locked, ""),
+            ]
+        );
+        assert_eq!(events[0].lock_id, events[1].lock_id);
+
+        let status = service
+            .export_events(Request::new(ExportEventsRequest {
+                namespace: None,
+                start_sova_block: 101,
+                end_sova_block: 100,
+            }))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_export_locks() -> Result<(), Box<dyn std::error::Error>> {
+        let db = Database::new(rusqlite::Connection::open_in_memory()?)?;
+        let slots: Vec<db::SlotInsertData> = ["", "testnet", ""]
+            .into_iter()
+            .enumerate()
+            .map(|(idx, namespace)| db::SlotInsertData {
+                namespace: namespace.to_string(),
+                contract_address: "0x123".to_string(),
+                start_block: 100,
+                btc_block: 200,
+                slot_index: vec![idx as u8],
+                btc_txid: "ab".repeat(32),
+                revert_value: vec![],
+                current_value: vec![],
+                metadata: Vec::new(),
+                labels: Default::default(),
+                alt_btc_txids: Vec::new(),
+                required_confirmed_txids: 0,
+                locked_by: None,
+            })
+            .collect();
+        db.with_transaction(|tx| db.batch_insert_slot_locks(tx, &slots))?;
+        db.unlock_slot("", "0x123", &[0], 101, db::UnlockReason::Confirmed)?;
+        let service = AdminServiceImpl::new(db);
+
+        let response = service
+            .export_locks(Request::new(ExportLocksRequest {
+                namespace: Some(String::new()),
+            }))
+            .await?;
+        let records: Vec<_> = response
+            .into_inner()
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect::<Result<_, _>>()?;
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].end_block, Some(101));
+        assert_eq!(records[0].unlock_reason, "confirmed");
+        assert!(!records[0].created_at.is_empty());
+        assert_eq!(records[1].end_block, None);
+
+        // Exported records convert back to the same locks
+        let mut violations = FieldViolations::default();
+        let row = lock_row("records[0]", records[0].clone(), &mut violations);
+        assert!(violations.is_empty());
+        assert_eq!(row.lock.unlock_reason, Some(db::UnlockReason::Confirmed));
+        assert_eq!(
+            row.created_at.as_deref(),
+            Some(records[0].created_at.as_str())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lock_row_violations() {
+        let mut violations = FieldViolations::default();
+        lock_row(
+            "records[3]",
+            LockRecord {
+                contract_address: "0x123".to_string(),
+                slot_index: vec![1],
+                btc_txid: "ab".repeat(32),
+                start_block: 100,
+                unlock_reason: "confirmed".to_string(),
+                created_at: "yesterday".to_string(),
+                ..Default::default()
+            },
+            &mut violations,
+        );
+        let status = violations.into_status().unwrap();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        let violations = sova_sentinel_proto::bad_request::field_violations(&status);
+        let fields: Vec<_> = violations
+            .iter()
+            .map(|violation| violation.field.as_str())
+            .collect();
+        assert_eq!(
+            fields,
+            vec!["records[3].unlock_reason", "records[3].created_at"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_prune_and_restore_locks() -> Result<(), Box<dyn std::error::Error>> {
+        let db = Database::new(rusqlite::Connection::open_in_memory()?)?;
+        let slots: Vec<db::SlotInsertData> = (0..2u8)
+            .map(|idx| db::SlotInsertData {
+                namespace: String::new(),
+                contract_address: "0x123".to_string(),
+                start_block: 100,
+                btc_block: 200,
+                slot_index: vec![0; 31].into_iter().chain([idx]).collect(),
+                btc_txid: "txid1".to_string(),
+                revert_value: vec![],
+                current_value: vec![],
+                metadata: Vec::new(),
+                labels: Default::default(),
+                alt_btc_txids: Vec::new(),
+                required_confirmed_txids: 0,
+                locked_by: None,
+            })
+            .collect();
+        db.with_transaction(|tx| db.batch_insert_slot_locks(tx, &slots))?;
+        db.unlock_slot(
+            "",
+            "0x123",
+            &slots[0].slot_index,
+            110,
+            db::UnlockReason::Confirmed,
+        )?;
+        let service = AdminServiceImpl::new(db.clone());
+
+        // Without a configured policy the request has to set a limit
+        let status = service
+            .prune_locks(Request::new(PruneLocksRequest::default()))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+
+        let service = service.with_pruner(Pruner::new(
+            db.clone(),
+            RetentionPolicy {
+                max_age_blocks: Some(1000),
+                ..Default::default()
+            },
+        ));
+        let response = service
+            .prune_locks(Request::new(PruneLocksRequest::default()))
+            .await?;
+        assert_eq!(response.get_ref().archived, 0);
+
+        let response = service
+            .prune_locks(Request::new(PruneLocksRequest {
+                max_age_blocks: Some(0),
+                ..Default::default()
+            }))
+            .await?;
+        assert_eq!(response.get_ref().archived, 1);
+        assert_eq!(db.lock_rows(None, 0, 10)?.len(), 1);
+
+        // Slot indexes are padded like in lock requests
+        let response = service
+            .restore_locks(Request::new(RestoreLocksRequest {
+                namespace: String::new(),
+                contract_address: "0x123".to_string(),
+                slot_index: vec![0],
+            }))
+            .await?;
+        assert_eq!(response.get_ref().restored, 1);
+        assert_eq!(db.lock_rows(None, 0, 10)?.len(), 2);
+
+        let status = service
+            .restore_locks(Request::new(RestoreLocksRequest::default()))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_lock_history() -> Result<(), Box<dyn std::error::Error>> {
+        let db = Database::new(rusqlite::Connection::open_in_memory()?)?;
+        let slot_index: Vec<u8> = vec![0; 31].into_iter().chain([1]).collect();
+        for (start_block, reason) in [
+            (100, Some(db::UnlockReason::RevertThreshold)),
+            (120, Some(db::UnlockReason::Confirmed)),
+            (130, None),
+        ] {
+            let slot = db::SlotInsertData {
+                namespace: String::new(),
+                contract_address: "0x123".to_string(),
+                start_block,
+                btc_block: 200,
+                slot_index: slot_index.clone(),
+                btc_txid: format!("txid{}", start_block),
+                revert_value: vec![],
+                current_value: vec![],
+                metadata: Vec::new(),
+                labels: Default::default(),
+                alt_btc_txids: Vec::new(),
+                required_confirmed_txids: 0,
+                locked_by: None,
+            };
+            db.with_transaction(|tx| db.batch_insert_slot_locks(tx, &[slot]))?;
+            if let Some(reason) = reason {
+                db.unlock_slot("", "0x123", &slot_index, start_block + 10, reason)?;
+            }
+        }
+        let service = AdminServiceImpl::new(db);
+
+        // Slot indexes are padded like in lock requests
+        let response = service
+            .get_lock_history(Request::new(GetLockHistoryRequest {
+                namespace: String::new(),
+                contract_address: "0x123".to_string(),
+                slot_index: vec![1],
+            }))
+            .await?;
+        let locks = &response.get_ref().locks;
+        assert_eq!(
+            locks
+                .iter()
+                .map(|lock| (
+                    lock.start_block,
+                    lock.end_block,
+                    lock.unlock_reason.as_str()
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                (100, Some(110), "revert-threshold"),
+                (120, Some(130), "confirmed"),
+                (130, None, ""),
+            ]
+        );
+        assert!(locks.iter().all(|lock| lock.slot_index == slot_index));
+
+        let response = service
+            .get_lock_history(Request::new(GetLockHistoryRequest {
+                namespace: "testnet".to_string(),
+                contract_address: "0x123".to_string(),
+                slot_index: vec![1],
+            }))
+            .await?;
+        assert!(response.get_ref().locks.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_slot_state_at() -> Result<(), Box<dyn std::error::Error>> {
+        let db = Database::new(rusqlite::Connection::open_in_memory()?)?;
+        let slot_index: Vec<u8> = vec![0; 31].into_iter().chain([1]).collect();
+        for (start_block, reason) in [
+            (100, Some(db::UnlockReason::RevertThreshold)),
+            (120, Some(db::UnlockReason::Confirmed)),
+            (130, None),
+        ] {
+            let slot = db::SlotInsertData {
+                namespace: String::new(),
+                contract_address: "0x123".to_string(),
+                start_block,
+                btc_block: 200,
+                slot_index: slot_index.clone(),
+                btc_txid: format!("txid{}", start_block),
+                revert_value: vec![],
+                current_value: vec![],
+                metadata: Vec::new(),
+                labels: Default::default(),
+                alt_btc_txids: Vec::new(),
+                required_confirmed_txids: 0,
+                locked_by: None,
+            };
+            db.with_transaction(|tx| db.batch_insert_slot_locks(tx, &[slot]))?;
+            if let Some(reason) = reason {
+                db.unlock_slot("", "0x123", &slot_index, start_block + 10, reason)?;
+            }
+        }
+        let service = AdminServiceImpl::new(db);
+
+        let mut states = Vec::new();
+        for sova_block in [99, 105, 110, 125, 130] {
+            let state = service
+                .get_slot_state_at(Request::new(GetSlotStateAtRequest {
+                    namespace: String::new(),
+                    contract_address: "0x123".to_string(),
+                    slot_index: vec![1],
+                    sova_block,
+                }))
+                .await?
+                .into_inner();
+            assert_eq!(state.locked, state.lock.is_some());
+            states.push((
+                state.lock.map(|lock| lock.btc_txid),
+                state.last_ended.map(|lock| lock.btc_txid),
+            ));
+        }
+        let txid = |block: u64| Some(format!("txid{}", block));
+        assert_eq!(
+            states,
+            vec![
+                (None, None),
+                (txid(100), None),
+                // A lock ending at a block no longer holds the slot at the end of it
+                (None, txid(100)),
+                (txid(120), txid(100)),
+                (txid(130), txid(120)),
+            ]
+        );
+
+        let status = service
+            .get_slot_state_at(Request::new(GetSlotStateAtRequest::default()))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_slot_value_history() -> Result<(), Box<dyn std::error::Error>> {
+        let db = Database::new(rusqlite::Connection::open_in_memory()?)?;
+        let slot_index: Vec<u8> = vec![0; 31].into_iter().chain([1]).collect();
+        let slot = db::SlotInsertData {
+            namespace: String::new(),
+            contract_address: "0x123".to_string(),
+            start_block: 100,
+            btc_block: 200,
+            slot_index: slot_index.clone(),
+            btc_txid: "txid1".to_string(),
+            revert_value: vec![4],
+            current_value: vec![7],
+            metadata: Vec::new(),
+            labels: Default::default(),
+            alt_btc_txids: Vec::new(),
+            required_confirmed_txids: 0,
+            locked_by: None,
+        };
+        db.with_transaction(|tx| db.batch_insert_slot_locks(tx, &[slot]))?;
+        db.unlock_slot("", "0x123", &slot_index, 110, db::UnlockReason::DoubleSpent)?;
+        let service = AdminServiceImpl::new(db);
+
+        // Slot indexes are padded like in lock requests
+        let response = service
+            .get_slot_value_history(Request::new(GetSlotValueHistoryRequest {
+                namespace: String::new(),
+                contract_address: "0x123".to_string(),
+                slot_index: vec![1],
+            }))
+            .await?;
+        let changes = &response.get_ref().changes;
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].end_block, Some(110));
+        assert_eq!(changes[0].unlock_reason, "double-spent");
+        assert_eq!(changes[0].settled_value, Some(vec![4]));
+        assert!(!changes[0].purged);
+
+        let status = service
+            .get_slot_value_history(Request::new(GetSlotValueHistoryRequest::default()))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_list_locks_by_txid() -> Result<(), Box<dyn std::error::Error>> {
+        let db = Database::new(rusqlite::Connection::open_in_memory()?)?;
+        let txid = "ab".repeat(32);
+        let slots: Vec<db::SlotInsertData> =
+            [(1, txid.clone()), (2, txid.clone()), (3, "cd".repeat(32))]
+                .into_iter()
+                .map(|(idx, btc_txid)| db::SlotInsertData {
+                    namespace: String::new(),
+                    contract_address: "0x123".to_string(),
+                    start_block: 100,
+                    btc_block: 200,
+                    slot_index: vec![0; 31].into_iter().chain([idx]).collect(),
+                    btc_txid,
+                    revert_value: vec![],
+                    current_value: vec![],
+                    metadata: Vec::new(),
+                    labels: Default::default(),
+                    alt_btc_txids: Vec::new(),
+                    required_confirmed_txids: 0,
+                    locked_by: None,
+                })
+                .collect();
+        db.with_transaction(|tx| db.batch_insert_slot_locks(tx, &slots))?;
+        db.unlock_slot(
+            "",
+            "0x123",
+            &slots[0].slot_index,
+            110,
+            db::UnlockReason::Confirmed,
+        )?;
+        let service = AdminServiceImpl::new(db);
+
+        // Txids are normalized like in lock requests
+        let response = service
+            .list_locks_by_txid(Request::new(ListLocksByTxidRequest {
+                namespace: String::new(),
+                btc_txid: format!("0x{}", txid.to_uppercase()),
+                include_unlocked: false,
+            }))
+            .await?;
+        let locks = &response.get_ref().locks;
+        assert_eq!(locks.len(), 1);
+        assert_eq!(locks[0].slot_index, slots[1].slot_index);
+
+        let response = service
+            .list_locks_by_txid(Request::new(ListLocksByTxidRequest {
+                namespace: String::new(),
+                btc_txid: txid,
+                include_unlocked: true,
+            }))
+            .await?;
+        assert_eq!(
+            response
+                .get_ref()
+                .locks
+                .iter()
+                .map(|lock| lock.end_block)
+                .collect::<Vec<_>>(),
+            vec![Some(110), None]
+        );
+
+        let status = service
+            .list_locks_by_txid(Request::new(ListLocksByTxidRequest::default()))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_list_locks_by_contract() -> Result<(), Box<dyn std::error::Error>> {
+        let db = Database::new(rusqlite::Connection::open_in_memory()?)?;
+        let slots: Vec<db::SlotInsertData> = [
+            ("0x123", 1, 100),
+            ("0x456", 2, 100),
+            ("0x123", 3, 105),
+            ("0x123", 4, 110),
+            ("0x123", 5, 120),
+        ]
+        .into_iter()
+        .map(|(contract_address, idx, start_block)| db::SlotInsertData {
+            namespace: String::new(),
+            contract_address: contract_address.to_string(),
+            start_block,
+            btc_block: 200,
+            slot_index: vec![0; 31].into_iter().chain([idx]).collect(),
+            btc_txid: "txid1".to_string(),
+            revert_value: vec![],
+            current_value: vec![],
+            metadata: Vec::new(),
+            labels: Default::default(),
+            alt_btc_txids: Vec::new(),
+            required_confirmed_txids: 0,
+            locked_by: None,
+        })
+        .collect();
+        for slot in &slots {
+            db.with_transaction(|tx| db.batch_insert_slot_locks(tx, std::slice::from_ref(slot)))?;
+        }
+        db.unlock_slot(
+            "",
+            "0x123",
+            &slots[0].slot_index,
+            130,
+            db::UnlockReason::RevertThreshold,
+        )?;
+        let service = AdminServiceImpl::new(db);
+
+        let request = ListLocksByContractRequest {
+            contract_address: "0x123".to_string(),
+            start_block: 100,
+            end_block: Some(110),
+            limit: 2,
+            ..Default::default()
+        };
+        let page = service
+            .list_locks_by_contract(Request::new(request.clone()))
+            .await?
+            .into_inner();
+        let blocks: Vec<_> = page.locks.iter().map(|lock| lock.start_block).collect();
+        assert_eq!(blocks, [100, 105]);
+        assert_eq!(page.locks[0].unlock_reason, "revert-threshold");
+        assert_eq!(page.next_after_id, page.locks[1].id);
+
+        let page = service
+            .list_locks_by_contract(Request::new(ListLocksByContractRequest {
+                after_id: page.next_after_id,
+                ..request.clone()
+            }))
+            .await?
+            .into_inner();
+        let blocks: Vec<_> = page.locks.iter().map(|lock| lock.start_block).collect();
+        assert_eq!(blocks, [110]);
+        assert_eq!(page.next_after_id, 0);
+
+        let page = service
+            .list_locks_by_contract(Request::new(ListLocksByContractRequest {
+                end_block: None,
+                limit: 0,
+                ..request.clone()
+            }))
+            .await?
+            .into_inner();
+        assert_eq!(page.locks.len(), 4);
+
+        let status = service
+            .list_locks_by_contract(Request::new(ListLocksByContractRequest {
+                start_block: 111,
+                ..request
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        let status = service
+            .list_locks_by_contract(Request::new(ListLocksByContractRequest::default()))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        Ok(())
+    }
+}
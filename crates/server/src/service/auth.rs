@@ -0,0 +1,150 @@
+use crate::service::health::HEALTH_PATH_PREFIX;
+use crate::service::status::unauthenticated_status;
+use anyhow::{Context as _, Result};
+use futures::future::BoxFuture;
+use std::path::Path;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tonic::body::BoxBody;
+use tower::{Layer, Service};
+
+/// Bearer token a listener requires in the `authorization` header of every request
+#[derive(Clone)]
+pub struct AuthToken(Arc<str>);
+
+impl AuthToken {
+    pub fn new(token: impl Into<String>) -> Result<Self> {
+        let token = token.into();
+        if token.is_empty() {
+            anyhow::bail!("Auth token is empty");
+        }
+        Ok(Self(token.into()))
+    }
+
+    /// Reads a token from a file, ignoring surrounding whitespace
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read auth token file {}", path.display()))?;
+        Self::new(contents.trim())
+            .with_context(|| format!("Invalid auth token file {}", path.display()))
+    }
+
+    /// Checks an `authorization` header value, in time independent of where it differs from the
+    /// token
+    fn accepts(&self, authorization: &[u8]) -> bool {
+        let Some(token) = authorization.strip_prefix(b"Bearer ") else {
+            return false;
+        };
+        let expected = self.0.as_bytes();
+        token.len() == expected.len()
+            && token
+                .iter()
+                .zip(expected)
+                .fold(0, |diff, (a, b)| diff | (a ^ b))
+                == 0
+    }
+}
+
+/// Layer rejecting requests without the listener's bearer token with `UNAUTHENTICATED`. Health
+/// checks are let through.
+#[derive(Clone)]
+pub struct AuthLayer {
+    token: AuthToken,
+}
+
+impl AuthLayer {
+    pub fn new(token: AuthToken) -> Self {
+        Self { token }
+    }
+}
+
+impl<S> Layer<S> for AuthLayer {
+    type Service = AuthService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        AuthService {
+            inner,
+            token: self.token.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct AuthService<S> {
+    inner: S,
+    token: AuthToken,
+}
+
+impl<S, B> Service<hyper::Request<B>> for AuthService<S>
+where
+    S: Service<hyper::Request<B>, Response = hyper::Response<BoxBody>>,
+    S::Future: Send + 'static,
+    S::Error: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: hyper::Request<B>) -> Self::Future {
+        let authorized = request.uri().path().starts_with(HEALTH_PATH_PREFIX)
+            || request
+                .headers()
+                .get(hyper::header::AUTHORIZATION)
+                .is_some_and(|value| self.token.accepts(value.as_bytes()));
+        if authorized {
+            Box::pin(self.inner.call(request))
+        } else {
+            Box::pin(std::future::ready(Ok(unauthenticated_status().into_http())))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_requires_bearer_token() -> Result<(), Box<dyn std::error::Error>> {
+        let service = AuthLayer::new(AuthToken::new("secret")?).layer(tower::service_fn(
+            |_: hyper::Request<()>| async {
+                Ok::<_, std::convert::Infallible>(hyper::Response::new(tonic::body::empty_body()))
+            },
+        ));
+        let code = |path: &str, authorization: Option<&str>| {
+            let service = service.clone();
+            let mut request = hyper::Request::builder().uri(path);
+            if let Some(authorization) = authorization {
+                request = request.header(hyper::header::AUTHORIZATION, authorization);
+            }
+            let request = request.body(()).unwrap();
+            async move {
+                let response = service.oneshot(request).await.unwrap();
+                tonic::Status::from_header_map(response.headers()).map(|status| status.code())
+            }
+        };
+
+        let path = "/admin.AdminService/Promote";
+        assert_eq!(code(path, Some("Bearer secret")).await, None);
+        for authorization in [
+            None,
+            Some("Bearer secrets"),
+            Some("Bearer other"),
+            Some("secret"),
+        ] {
+            assert_eq!(
+                code(path, authorization).await,
+                Some(tonic::Code::Unauthenticated)
+            );
+        }
+        // Health checks need no token
+        assert_eq!(code("/health.Health/Check", None).await, None);
+
+        assert!(AuthToken::new("").is_err());
+        Ok(())
+    }
+}
@@ -1,24 +1,33 @@
+use crate::service::circuit_breaker::CircuitBreaker;
+use crate::service::confirmation_cache::ConfirmationCache;
+use crate::service::double_spend::{is_replacement, DoubleSpendStatus, InputWatcher};
+use crate::service::retry::RetryPolicy;
+use crate::spv::HeaderStore;
 use anyhow::Result;
 use async_trait::async_trait;
-use bitcoin::Txid;
+use bitcoin::block::Header;
+use bitcoin::{BlockHash, Network, OutPoint, ScriptBuf, Txid};
 use bitcoincore_rpc::{jsonrpc, Auth, Client, Error, RpcApi};
+use futures::stream::{self, StreamExt};
 use reqwest::Client as HttpClient;
 use serde_json::json;
 use std::future::Future;
 use std::pin::Pin;
 use std::str::FromStr;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use thiserror::Error;
-use tokio_retry::{
-    strategy::{jitter, ExponentialBackoff},
-    Retry,
-};
+use tokio_retry::Retry;
+use tracing::Instrument;
 
 #[derive(Error, Debug)]
 pub enum BitcoinRpcError {
     #[error("Bitcoin node is unreachable after {attempts} attempts")]
     BitcoinNodeUnreachable { attempts: u32 },
+    #[error("Bitcoin RPC circuit breaker is open, retry after {retry_after:?}")]
+    CircuitOpen { retry_after: Duration },
+    #[error("Bitcoin RPC call exceeded its time budget of {budget:?}")]
+    BudgetExceeded { budget: Duration },
 }
 
 #[async_trait]
@@ -27,6 +36,226 @@ pub trait BitcoinRpcClient: Send + Sync {
         &self,
         txid: &Txid,
     ) -> Result<bitcoincore_rpc::json::GetRawTransactionResult, Error>;
+
+    /// Returns whether the output is unspent (`gettxout`). With `include_mempool`, outputs spent
+    /// by mempool transactions count as spent.
+    async fn is_output_unspent(
+        &self,
+        outpoint: &OutPoint,
+        include_mempool: bool,
+    ) -> Result<bool, Error>;
+
+    /// Returns the mempool transaction spending the output, if any (`gettxspendingprevout`)
+    async fn get_tx_spending_prevout(&self, outpoint: &OutPoint) -> Result<Option<Txid>, Error>;
+
+    /// Returns whether the transaction is in the node's mempool (`getmempoolentry`)
+    async fn is_in_mempool(&self, txid: &Txid) -> Result<bool, Error>;
+
+    /// Returns why the node's mempool would reject a raw transaction, if it would
+    /// (`testmempoolaccept`)
+    async fn test_mempool_accept(&self, raw_tx: &[u8]) -> Result<Option<String>, Error>;
+
+    /// Submits a raw transaction to the node's mempool (`sendrawtransaction`)
+    async fn send_raw_transaction(&self, raw_tx: &[u8]) -> Result<Txid, Error>;
+
+    /// Returns the node's network and tip height (`getblockchaininfo`)
+    async fn get_chain_info(&self) -> Result<ChainInfo, Error>;
+
+    /// Returns the txid of the coinbase transaction of the block at `height` (`getblockhash`,
+    /// `getblock`)
+    async fn get_coinbase_txid(&self, height: u64) -> Result<Txid, Error>;
+
+    /// Returns the header of the block at `height` of the node's active chain (`getblockhash`,
+    /// `getblockheader`)
+    async fn get_block_header(&self, height: u64) -> Result<Header, Error>;
+
+    /// Returns a serialized Merkle block proving the transaction is included in the block
+    /// (`gettxoutproof`)
+    async fn get_tx_out_proof(&self, txid: &Txid, block_hash: &BlockHash)
+        -> Result<Vec<u8>, Error>;
+}
+
+/// Network and tip height of the connected Bitcoin node
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChainInfo {
+    pub network: Network,
+    pub tip_height: u64,
+}
+
+/// Whether the Bitcoin node has a transaction without confirmations
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MempoolStatus {
+    /// The node has never reported the transaction
+    Unseen,
+    /// The transaction is in the node's mempool
+    InMempool,
+    /// The node dropped the transaction from its mempool after reporting it
+    Evicted,
+}
+
+/// Returns the name a network is reported under: `mainnet`, `testnet`, `testnet4`, `signet` or
+/// `regtest`
+pub fn network_name(network: Network) -> &'static str {
+    match network {
+        Network::Bitcoin => "mainnet",
+        Network::Testnet => "testnet",
+        network => network.to_core_arg(),
+    }
+}
+
+/// Parses a network name as returned by [`network_name`]
+pub fn parse_network_name(name: &str) -> Result<Network> {
+    match name {
+        "mainnet" => Ok(Network::Bitcoin),
+        "testnet" => Ok(Network::Testnet),
+        "testnet4" | "signet" | "regtest" => Ok(Network::from_core_arg(name)?),
+        _ => Err(anyhow::anyhow!(
+            "Unknown Bitcoin network '{}', expected mainnet, testnet, testnet4, signet or regtest",
+            name
+        )),
+    }
+}
+
+/// Returns the canonical form of a txid: 64 lowercase hex characters. Surrounding whitespace, a
+/// `0x` prefix and uppercase hex, as returned by some indexers, are accepted.
+pub fn normalize_txid(txid: &str) -> Result<String> {
+    let txid = txid.trim();
+    let txid = txid
+        .strip_prefix("0x")
+        .or_else(|| txid.strip_prefix("0X"))
+        .unwrap_or(txid);
+    if txid.len() != 64 || !txid.bytes().all(|b| b.is_ascii_hexdigit()) {
+        anyhow::bail!(
+            "Invalid transaction ID '{}': expected 64 hex characters",
+            txid
+        );
+    }
+    Ok(txid.to_ascii_lowercase())
+}
+
+/// Parses a txid in any of the forms accepted by [`normalize_txid`]
+fn parse_txid(txid: &str) -> Result<Txid> {
+    Ok(Txid::from_str(&normalize_txid(txid)?)?)
+}
+
+/// Parses a txid returned by a node or indexer, see [`normalize_txid`]
+fn parse_rpc_txid(txid: &serde_json::Value) -> Result<Txid, Error> {
+    let txid = txid.as_str().unwrap_or_default();
+    parse_txid(txid).map_err(|e| {
+        Error::JsonRpc(jsonrpc::error::Error::Transport(Box::new(
+            std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()),
+        )))
+    })
+}
+
+/// Returns the inputs and output scripts of a transaction
+fn transaction_io(
+    tx_info: &bitcoincore_rpc::json::GetRawTransactionResult,
+) -> (Vec<OutPoint>, Vec<ScriptBuf>) {
+    let inputs = tx_info
+        .vin
+        .iter()
+        .filter_map(|vin| Some(OutPoint::new(vin.txid?, vin.vout?)))
+        .collect();
+    let output_scripts = tx_info
+        .vout
+        .iter()
+        .map(|vout| ScriptBuf::from_bytes(vout.script_pub_key.hex.clone()))
+        .collect();
+    (inputs, output_scripts)
+}
+
+/// Extracts the spending txid from a `gettxspendingprevout` result for a single outpoint
+fn parse_spending_txid(result: serde_json::Value) -> Result<Option<Txid>, Error> {
+    match result.get(0).and_then(|entry| entry.get("spendingtxid")) {
+        Some(txid) => parse_rpc_txid(txid).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Maps a `getmempoolentry` result to whether the transaction is in the mempool
+fn parse_mempool_entry(result: Result<serde_json::Value, Error>) -> Result<bool, Error> {
+    match result {
+        Ok(_) => Ok(true),
+        // Error code -5 means the transaction is not in the mempool
+        Err(Error::JsonRpc(jsonrpc::error::Error::Rpc(ref rpcerr))) if rpcerr.code == -5 => {
+            Ok(false)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Extracts the reject reason from a `testmempoolaccept` result for a single transaction, `None`
+/// if it would be accepted
+fn parse_reject_reason(result: serde_json::Value) -> Result<Option<String>, Error> {
+    let entry = result.get(0);
+    match entry.and_then(|entry| entry.get("allowed")) {
+        Some(serde_json::Value::Bool(true)) => Ok(None),
+        Some(serde_json::Value::Bool(false)) => Ok(Some(
+            entry
+                .and_then(|entry| entry.get("reject-reason"))
+                .and_then(|reason| reason.as_str())
+                .unwrap_or("unknown")
+                .to_string(),
+        )),
+        _ => Err(Error::UnexpectedStructure),
+    }
+}
+
+/// Reject reasons of `testmempoolaccept` for transactions that can never be mined: their inputs
+/// are missing or already spent, or they break consensus rules. Policy rejections, e.g. a fee
+/// below the mempool minimum, may clear up later.
+const DEAD_REJECT_REASONS: &[&str] = &[
+    "missing-inputs",
+    "bad-txns-",
+    "mandatory-script-verify-flag-failed",
+];
+
+fn is_dead_reject_reason(reason: &str) -> bool {
+    DEAD_REJECT_REASONS
+        .iter()
+        .any(|dead| reason.starts_with(dead))
+}
+
+/// Extracts the coinbase txid from a `getblock` result with verbosity 1
+fn parse_coinbase_txid(result: serde_json::Value) -> Result<Txid, Error> {
+    parse_rpc_txid(
+        result
+            .get("tx")
+            .and_then(|txids| txids.get(0))
+            .unwrap_or(&serde_json::Value::Null),
+    )
+}
+
+/// Decodes a hex-encoded consensus value, like a `getblockheader` result
+fn parse_rpc_hex<T: bitcoin::consensus::Decodable>(result: serde_json::Value) -> Result<T, Error> {
+    let hex = result.as_str().unwrap_or_default();
+    bitcoin::consensus::encode::deserialize_hex(hex)
+        .map_err(|e| Error::JsonRpc(jsonrpc::error::Error::Transport(Box::new(e))))
+}
+
+/// Extracts the network and tip height from a `getblockchaininfo` result
+fn parse_chain_info(result: serde_json::Value) -> Result<ChainInfo, Error> {
+    let chain = result
+        .get("chain")
+        .and_then(|chain| chain.as_str())
+        .unwrap_or_default();
+    let network = Network::from_core_arg(chain)
+        .map_err(|e| Error::JsonRpc(jsonrpc::error::Error::Transport(Box::new(e))))?;
+    let tip_height = result
+        .get("blocks")
+        .and_then(|blocks| blocks.as_u64())
+        .ok_or_else(|| {
+            Error::JsonRpc(jsonrpc::error::Error::Rpc(jsonrpc::error::RpcError {
+                code: -32603,
+                message: "missing blocks".into(),
+                data: None,
+            }))
+        })?;
+    Ok(ChainInfo {
+        network,
+        tip_height,
+    })
 }
 
 pub struct BitcoinCoreRpcClient {
@@ -59,6 +288,68 @@ impl BitcoinRpcClient for BitcoinCoreRpcClient {
     ) -> Result<bitcoincore_rpc::json::GetRawTransactionResult, Error> {
         self.client.get_raw_transaction_info(txid, None)
     }
+
+    async fn is_output_unspent(
+        &self,
+        outpoint: &OutPoint,
+        include_mempool: bool,
+    ) -> Result<bool, Error> {
+        Ok(self
+            .client
+            .get_tx_out(&outpoint.txid, outpoint.vout, Some(include_mempool))?
+            .is_some())
+    }
+
+    async fn get_tx_spending_prevout(&self, outpoint: &OutPoint) -> Result<Option<Txid>, Error> {
+        let result = self.client.call(
+            "gettxspendingprevout",
+            &[json!([{ "txid": outpoint.txid.to_string(), "vout": outpoint.vout }])],
+        )?;
+        parse_spending_txid(result)
+    }
+
+    async fn is_in_mempool(&self, txid: &Txid) -> Result<bool, Error> {
+        parse_mempool_entry(
+            self.client
+                .call("getmempoolentry", &[json!(txid.to_string())]),
+        )
+    }
+
+    async fn test_mempool_accept(&self, raw_tx: &[u8]) -> Result<Option<String>, Error> {
+        parse_reject_reason(
+            self.client
+                .call("testmempoolaccept", &[json!([hex::encode(raw_tx)])])?,
+        )
+    }
+
+    async fn send_raw_transaction(&self, raw_tx: &[u8]) -> Result<Txid, Error> {
+        self.client.send_raw_transaction(raw_tx)
+    }
+
+    async fn get_chain_info(&self) -> Result<ChainInfo, Error> {
+        parse_chain_info(self.client.call("getblockchaininfo", &[])?)
+    }
+
+    async fn get_coinbase_txid(&self, height: u64) -> Result<Txid, Error> {
+        let block_hash = self.client.get_block_hash(height)?;
+        parse_coinbase_txid(
+            self.client
+                .call("getblock", &[json!(block_hash), json!(1)])?,
+        )
+    }
+
+    async fn get_block_header(&self, height: u64) -> Result<Header, Error> {
+        let block_hash = self.client.get_block_hash(height)?;
+        self.client.get_block_header(&block_hash)
+    }
+
+    async fn get_tx_out_proof(
+        &self,
+        txid: &Txid,
+        block_hash: &BlockHash,
+    ) -> Result<Vec<u8>, Error> {
+        self.client.get_tx_out_proof(&[*txid], Some(block_hash))
+    }
 }
 
 /// RPC client backed by an external HTTP service
@@ -151,25 +442,152 @@ impl BitcoinRpcClient for ExternalRpcClient {
         serde_json::from_value(res)
             .map_err(|e| Error::JsonRpc(jsonrpc::error::Error::Transport(Box::new(e))))
     }
+
+    async fn is_output_unspent(
+        &self,
+        outpoint: &OutPoint,
+        include_mempool: bool,
+    ) -> Result<bool, Error> {
+        let res = self
+            .make_rpc_call(
+                "gettxout",
+                vec![
+                    json!(outpoint.txid.to_string()),
+                    json!(outpoint.vout),
+                    json!(include_mempool),
+                ],
+            )
+            .await?;
+        Ok(!res.is_null())
+    }
+
+    async fn get_tx_spending_prevout(&self, outpoint: &OutPoint) -> Result<Option<Txid>, Error> {
+        let res = self
+            .make_rpc_call(
+                "gettxspendingprevout",
+                vec![json!([{ "txid": outpoint.txid.to_string(), "vout": outpoint.vout }])],
+            )
+            .await?;
+        parse_spending_txid(res)
+    }
+
+    async fn is_in_mempool(&self, txid: &Txid) -> Result<bool, Error> {
+        parse_mempool_entry(
+            self.make_rpc_call("getmempoolentry", vec![json!(txid.to_string())])
+                .await,
+        )
+    }
+
+    async fn test_mempool_accept(&self, raw_tx: &[u8]) -> Result<Option<String>, Error> {
+        parse_reject_reason(
+            self.make_rpc_call("testmempoolaccept", vec![json!([hex::encode(raw_tx)])])
+                .await?,
+        )
+    }
+
+    async fn send_raw_transaction(&self, raw_tx: &[u8]) -> Result<Txid, Error> {
+        let res = self
+            .make_rpc_call("sendrawtransaction", vec![json!(hex::encode(raw_tx))])
+            .await?;
+        serde_json::from_value(res)
+            .map_err(|e| Error::JsonRpc(jsonrpc::error::Error::Transport(Box::new(e))))
+    }
+
+    async fn get_chain_info(&self) -> Result<ChainInfo, Error> {
+        parse_chain_info(self.make_rpc_call("getblockchaininfo", vec![]).await?)
+    }
+
+    async fn get_coinbase_txid(&self, height: u64) -> Result<Txid, Error> {
+        let block_hash = self
+            .make_rpc_call("getblockhash", vec![json!(height)])
+            .await?;
+        parse_coinbase_txid(
+            self.make_rpc_call("getblock", vec![block_hash, json!(1)])
+                .await?,
+        )
+    }
+
+    async fn get_block_header(&self, height: u64) -> Result<Header, Error> {
+        let block_hash = self
+            .make_rpc_call("getblockhash", vec![json!(height)])
+            .await?;
+        parse_rpc_hex(
+            self.make_rpc_call("getblockheader", vec![block_hash, json!(false)])
+                .await?,
+        )
+    }
+
+    async fn get_tx_out_proof(
+        &self,
+        txid: &Txid,
+        block_hash: &BlockHash,
+    ) -> Result<Vec<u8>, Error> {
+        let res = self
+            .make_rpc_call(
+                "gettxoutproof",
+                vec![json!([txid.to_string()]), json!(block_hash.to_string())],
+            )
+            .await?;
+        hex::decode(res.as_str().unwrap_or_default())
+            .map_err(|e| Error::JsonRpc(jsonrpc::error::Error::Transport(Box::new(e))))
+    }
 }
 
 #[tonic::async_trait]
 pub trait BitcoinRpcServiceAPI: Send + Sync {
+    /// Returns the number of confirmations of a transaction, 0 if it is unconfirmed or unknown
+    async fn get_confirmations(&self, txid: &str) -> Result<u32>;
+
+    /// Returns the number of confirmations after which a transaction counts as confirmed
+    fn confirmation_threshold(&self) -> u32;
+
     /// Checks if a transaction has enough confirmations
     /// Returns Ok(true) if confirmed, Ok(false) if not confirmed enough, and Err if transaction not found or other error
-    async fn is_tx_confirmed(&self, txid: &str) -> Result<bool>;
+    async fn is_tx_confirmed(&self, txid: &str) -> Result<bool> {
+        Ok(self.get_confirmations(txid).await? >= self.confirmation_threshold())
+    }
+
+    /// Returns whether a transaction without confirmations is in the node's mempool
+    async fn mempool_status(&self, txid: &str) -> Result<MempoolStatus>;
+
+    /// Checks whether the inputs of an unconfirmed transaction have been spent by a conflicting
+    /// transaction
+    async fn check_double_spend(&self, _txid: &str) -> Result<DoubleSpendStatus> {
+        Ok(DoubleSpendStatus::None)
+    }
+
+    /// Broadcasts a raw transaction to the Bitcoin network. A transaction the node already has,
+    /// in its mempool or in a block, counts as broadcast.
+    async fn broadcast_transaction(&self, _raw_tx: &[u8]) -> Result<()> {
+        Err(anyhow::anyhow!("Transaction broadcast is not supported"))
+    }
+
+    /// Returns the network and tip height of the connected Bitcoin node
+    async fn chain_info(&self) -> Result<ChainInfo> {
+        Err(anyhow::anyhow!("Chain info is not supported"))
+    }
 }
 
+/// `sendrawtransaction` error code for a transaction that is already in the chain
+const RPC_VERIFY_ALREADY_IN_CHAIN: i32 = -27;
+
 type BitcoinRpcOperation<T> = Pin<Box<dyn Future<Output = Result<T, Error>> + Send>>;
 
 #[derive(Clone)]
 pub struct BitcoinRpcService {
     client: Arc<dyn BitcoinRpcClient>,
     confirmation_threshold: u32,
-    max_retries: u32,
-    base_delay: Duration,
+    retry_policy: RetryPolicy,
+    circuit_breaker: Arc<CircuitBreaker>,
+    cache: Arc<ConfirmationCache>,
+    input_watcher: Option<Arc<InputWatcher>>,
+    revert_dead_txs: bool,
+    header_store: Option<Arc<HeaderStore>>,
 }
 
+/// Maximum number of concurrent confirmation lookups when warming the cache
+const CACHE_WARMUP_CONCURRENCY: usize = 8;
+
 impl BitcoinRpcService {
     /// Creates a new BitcoinRpcService instance
     pub fn new(
@@ -180,8 +598,12 @@ impl BitcoinRpcService {
         Self {
             client,
             confirmation_threshold,
-            max_retries,
-            base_delay: Duration::from_millis(100),
+            retry_policy: RetryPolicy::exponential(max_retries, Duration::from_millis(100)),
+            circuit_breaker: Arc::new(CircuitBreaker::disabled()),
+            cache: Arc::new(ConfirmationCache::disabled()),
+            input_watcher: None,
+            revert_dead_txs: false,
+            header_store: None,
         }
     }
 
@@ -195,29 +617,197 @@ impl BitcoinRpcService {
         Self {
             client,
             confirmation_threshold,
-            max_retries,
-            base_delay,
+            retry_policy: RetryPolicy::exponential(max_retries, base_delay),
+            circuit_breaker: Arc::new(CircuitBreaker::disabled()),
+            cache: Arc::new(ConfirmationCache::disabled()),
+            input_watcher: None,
+            revert_dead_txs: false,
+            header_store: None,
         }
     }
 
-    /// Returns the current confirmation threshold
-    pub fn confirmation_threshold(&self) -> u32 {
-        self.confirmation_threshold
+    /// Retries Bitcoin RPC calls according to the given policy
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Wraps all Bitcoin RPC calls in the given circuit breaker
+    pub fn with_circuit_breaker(mut self, circuit_breaker: CircuitBreaker) -> Self {
+        self.circuit_breaker = Arc::new(circuit_breaker);
+        self
+    }
+
+    /// Serves confirmation checks from the given cache where possible
+    pub fn with_confirmation_cache(mut self, cache: ConfirmationCache) -> Self {
+        self.cache = Arc::new(cache);
+        self
+    }
+
+    /// Watches the inputs of unconfirmed transactions for conflicting spends
+    pub fn with_double_spend_detection(mut self, input_watcher: InputWatcher) -> Self {
+        self.input_watcher = Some(Arc::new(input_watcher));
+        self
+    }
+
+    /// Reports a watched transaction evicted from the mempool as conflicted when the mempool
+    /// rejects it as one that can never be mined, so its locks revert without waiting for the
+    /// revert threshold
+    pub fn with_dead_tx_reverts(mut self, revert_dead_txs: bool) -> Self {
+        self.revert_dead_txs = revert_dead_txs;
+        self
+    }
+
+    /// Counts the confirmations of a transaction that reached the confirmation threshold from a
+    /// Merkle proof against the synced headers of `header_store`, rather than trusting the node
+    pub fn with_spv_verification(mut self, header_store: Arc<HeaderStore>) -> Self {
+        self.header_store = Some(header_store);
+        self
+    }
+
+    /// Returns the header of the block at `height` of the node's active chain
+    pub async fn block_header(&self, height: u64) -> Result<Header> {
+        self.with_retry("get_block_header", || {
+            let client = self.client.clone();
+            Box::pin(async move { client.get_block_header(height).await })
+        })
+        .await
     }
 
+    /// Returns the confirmations of a mined transaction proven by a Merkle proof against the
+    /// header store, `None` if it can't be proven, e.g. because its block isn't synced yet
+    async fn proven_confirmations(
+        &self,
+        header_store: &HeaderStore,
+        tx_info: &bitcoincore_rpc::json::GetRawTransactionResult,
+    ) -> Result<Option<u32>> {
+        let txid = tx_info.txid;
+        let Some(block_hash) = tx_info.blockhash else {
+            tracing::warn!(
+                "Bitcoin node reported {} as confirmed without a block",
+                txid
+            );
+            return Ok(None);
+        };
+        let proof = self
+            .with_retry("get_tx_out_proof", || {
+                let client = self.client.clone();
+                Box::pin(async move { client.get_tx_out_proof(&txid, &block_hash).await })
+            })
+            .await?;
+        match header_store.confirmations(&txid, &proof) {
+            Ok(None) => tracing::warn!(
+                "Block {} of {} is not in the header store yet, its confirmations are not verified",
+                block_hash,
+                txid
+            ),
+            Err(e) => tracing::warn!("Failed to verify inclusion of {}: {:#}", txid, e),
+            Ok(confirmations) => return Ok(confirmations),
+        }
+        Ok(None)
+    }
+
+    /// Fetches the confirmation status of the given transactions and primes the confirmation
+    /// cache with the results, so the first status queries after a restart don't all hit the
+    /// Bitcoin node at once. Returns the number of transactions cached.
+    pub async fn warm_cache(&self, txids: &[String]) -> usize {
+        stream::iter(txids)
+            .map(|txid| async move {
+                match self.get_confirmations(txid).await {
+                    Ok(_) => true,
+                    Err(e) => {
+                        tracing::warn!("Failed to warm confirmation cache for {}: {}", txid, e);
+                        false
+                    }
+                }
+            })
+            .buffer_unordered(CACHE_WARMUP_CONCURRENCY)
+            .filter(|cached| futures::future::ready(*cached))
+            .count()
+            .await
+    }
+
+    /// Fails unless the connected node is on the expected network
+    pub async fn verify_network(&self, expected: Network) -> Result<()> {
+        let chain_info = self.chain_info().await?;
+        if chain_info.network != expected {
+            anyhow::bail!(
+                "Bitcoin node is on {} but BITCOIN_NETWORK is {}",
+                network_name(chain_info.network),
+                network_name(expected)
+            );
+        }
+        Ok(())
+    }
+
+    /// Fails unless the node can look up mined transactions by txid, which needs `txindex=1`.
+    /// Checked with the coinbase transaction of block 1, so it is skipped on a chain without
+    /// blocks.
+    pub async fn verify_txindex(&self) -> Result<()> {
+        if self.chain_info().await?.tip_height == 0 {
+            tracing::warn!("Bitcoin node has no blocks yet, txindex is not verified");
+            return Ok(());
+        }
+        let txid = self
+            .with_retry("get_coinbase_txid", || {
+                let client = self.client.clone();
+                Box::pin(async move { client.get_coinbase_txid(1).await })
+            })
+            .await?;
+        if self.fetch_transaction(txid).await?.is_none() {
+            anyhow::bail!(
+                "Bitcoin node can't look up transaction {} of block 1, start it with txindex=1",
+                txid
+            );
+        }
+        Ok(())
+    }
+
+    /// Fetches a transaction from the node, `None` if the node doesn't know it
+    async fn fetch_transaction(
+        &self,
+        txid: Txid,
+    ) -> Result<Option<bitcoincore_rpc::json::GetRawTransactionResult>> {
+        self.with_retry("get_raw_transaction_info", || {
+            let client = self.client.clone();
+            Box::pin(async move {
+                match client.get_raw_transaction_info(&txid).await {
+                    Ok(tx_info) => Ok(Some(tx_info)),
+                    Err(Error::JsonRpc(jsonrpc::error::Error::Rpc(ref rpcerr)))
+                        if rpcerr.code == -5 =>
+                    {
+                        // Error code -5 means transaction not found
+                        Ok(None)
+                    }
+                    Err(e) => Err(e),
+                }
+            })
+        })
+        .await
+    }
+
+    /// Runs `operation`, a call of the client's `method`, retrying while the node is unreachable.
+    /// Every attempt is traced in its own span.
     async fn with_retry<T>(
         &self,
+        method: &'static str,
         operation: impl Fn() -> BitcoinRpcOperation<T> + Send + Sync,
     ) -> Result<T>
     where
         T: Send,
     {
-        let strategy = ExponentialBackoff::from_millis(self.base_delay.as_millis() as u64)
-            .map(jitter)
-            .take((self.max_retries - 1) as usize);
+        // Fail fast while the node is known to be down instead of burning the retry budget
+        if let Err(retry_after) = self.circuit_breaker.try_acquire() {
+            return Err(BitcoinRpcError::CircuitOpen { retry_after }.into());
+        }
 
-        let result = Retry::spawn(strategy, || {
-            let operation = operation();
+        let strategy = self.retry_policy.delays(Instant::now());
+        let retry = Retry::spawn(strategy, || {
+            let operation = operation().instrument(tracing::info_span!(
+                "bitcoin_rpc",
+                otel.kind = "client",
+                rpc.method = method
+            ));
             async move {
                 match operation.await {
                     Ok(result) => Ok(Ok(result)),
@@ -231,16 +821,37 @@ impl BitcoinRpcService {
                     }
                 }
             }
-        })
-        .await;
+        });
+
+        // Bound the whole call, retries included, so one slow node can't eat the deadline of a
+        // request that checks many transactions
+        let result = match self.retry_policy.call_budget {
+            Some(budget) => match tokio::time::timeout(budget, retry).await {
+                Ok(result) => result,
+                Err(_) => {
+                    self.circuit_breaker.record_failure();
+                    return Err(BitcoinRpcError::BudgetExceeded { budget }.into());
+                }
+            },
+            None => retry.await,
+        };
 
         match result {
-            Ok(Ok(value)) => Ok(value),
-            Ok(Err(e)) => Err(anyhow::anyhow!("Operation failed: {}", e)),
-            Err(_e) => Err(BitcoinRpcError::BitcoinNodeUnreachable {
-                attempts: self.max_retries,
+            Ok(Ok(value)) => {
+                self.circuit_breaker.record_success();
+                Ok(value)
+            }
+            Ok(Err(e)) => {
+                self.circuit_breaker.record_success();
+                Err(anyhow::anyhow!("Operation failed: {}", e))
+            }
+            Err(_e) => {
+                self.circuit_breaker.record_failure();
+                Err(BitcoinRpcError::BitcoinNodeUnreachable {
+                    attempts: self.retry_policy.max_retries,
+                }
+                .into())
             }
-            .into()),
         }
     }
 
@@ -251,45 +862,211 @@ impl BitcoinRpcService {
 
 #[tonic::async_trait]
 impl BitcoinRpcServiceAPI for BitcoinRpcService {
-    async fn is_tx_confirmed(&self, txid: &str) -> Result<bool> {
-        let txid =
-            Txid::from_str(txid).map_err(|e| anyhow::anyhow!("Invalid transaction ID: {}", e))?;
+    async fn get_confirmations(&self, txid: &str) -> Result<u32> {
+        let txid = parse_txid(txid)?;
+
+        if let Some(confirmations) = self.cache.get(&txid, self.confirmation_threshold) {
+            return Ok(confirmations);
+        }
+
+        let tx_info = self.fetch_transaction(txid).await?;
+        let mut confirmations = tx_info
+            .as_ref()
+            .and_then(|tx_info| tx_info.confirmations)
+            .unwrap_or(0);
+        if let (Some(header_store), Some(tx_info)) = (&self.header_store, &tx_info) {
+            if confirmations >= self.confirmation_threshold {
+                confirmations = match self.proven_confirmations(header_store, tx_info).await? {
+                    Some(proven) => proven.min(confirmations),
+                    // Held just below the threshold until the transaction is proven
+                    None => self.confirmation_threshold.saturating_sub(1),
+                };
+            }
+        }
+
+        if let Some(input_watcher) = &self.input_watcher {
+            if confirmations >= self.confirmation_threshold {
+                input_watcher.forget(&txid);
+            } else if let Some(tx_info) = &tx_info {
+                let (inputs, output_scripts) = transaction_io(tx_info);
+                input_watcher.watch(txid, inputs, output_scripts, tx_info.hex.clone());
+            }
+        }
+
+        self.cache.insert(txid, confirmations);
+        Ok(confirmations)
+    }
 
-        let result = self
-            .with_retry(|| {
+    fn confirmation_threshold(&self) -> u32 {
+        self.confirmation_threshold
+    }
+
+    async fn mempool_status(&self, txid: &str) -> Result<MempoolStatus> {
+        let txid = parse_txid(txid)?;
+        let in_mempool = self
+            .with_retry("is_in_mempool", || {
                 let client = self.client.clone();
-                let threshold = self.confirmation_threshold;
-                Box::pin(async move {
-                    match client.get_raw_transaction_info(&txid).await {
-                        Ok(tx_info) => match tx_info.confirmations {
-                            Some(confirmations) => Ok(confirmations >= threshold),
-                            None => Ok(false),
-                        },
-                        Err(Error::JsonRpc(jsonrpc::error::Error::Rpc(ref rpcerr)))
-                            if rpcerr.code == -5 =>
-                        {
-                            // Error code -5 means transaction not found
-                            Ok(false)
-                        }
-                        Err(e) => Err(e),
-                    }
-                })
+                Box::pin(async move { client.is_in_mempool(&txid).await })
             })
             .await?;
+        if in_mempool {
+            return Ok(MempoolStatus::InMempool);
+        }
+        // The input watcher remembers the unconfirmed transactions the node reported
+        let seen = self
+            .input_watcher
+            .as_ref()
+            .is_some_and(|input_watcher| input_watcher.inputs(&txid).is_some());
+        Ok(if seen {
+            MempoolStatus::Evicted
+        } else {
+            MempoolStatus::Unseen
+        })
+    }
+
+    async fn check_double_spend(&self, txid: &str) -> Result<DoubleSpendStatus> {
+        let Some(input_watcher) = &self.input_watcher else {
+            return Ok(DoubleSpendStatus::None);
+        };
+        let txid = parse_txid(txid)?;
+        // Inputs are only known once the transaction has been seen by the node
+        let Some(inputs) = input_watcher.inputs(&txid) else {
+            return Ok(DoubleSpendStatus::None);
+        };
+
+        // As long as the node knows the transaction, in its mempool or in a block, no conflicting
+        // transaction can have spent its inputs
+        if self.fetch_transaction(txid).await?.is_some() {
+            return Ok(DoubleSpendStatus::None);
+        }
+
+        let mut status = DoubleSpendStatus::None;
+        for input in inputs {
+            let unspent_in_chain = self
+                .with_retry("is_output_unspent", || {
+                    let client = self.client.clone();
+                    Box::pin(async move { client.is_output_unspent(&input, false).await })
+                })
+                .await?;
+            if !unspent_in_chain {
+                return Ok(DoubleSpendStatus::Conflicted);
+            }
 
-        Ok(result)
+            let unspent = self
+                .with_retry("is_output_unspent", || {
+                    let client = self.client.clone();
+                    Box::pin(async move { client.is_output_unspent(&input, true).await })
+                })
+                .await?;
+            if unspent {
+                continue;
+            }
+            status = DoubleSpendStatus::AtRisk;
+
+            // A mempool transaction paying the same outputs is a fee bump of the original
+            let spending_txid = self
+                .with_retry("get_tx_spending_prevout", || {
+                    let client = self.client.clone();
+                    Box::pin(async move { client.get_tx_spending_prevout(&input).await })
+                })
+                .await?;
+            let Some(spending_txid) = spending_txid else {
+                continue;
+            };
+            let Some(spending_tx) = self.fetch_transaction(spending_txid).await? else {
+                continue;
+            };
+            let (spending_inputs, spending_scripts) = transaction_io(&spending_tx);
+            let original_scripts = input_watcher.output_scripts(&txid).unwrap_or_default();
+            if is_replacement(&original_scripts, &spending_scripts) {
+                input_watcher.watch(
+                    spending_txid,
+                    spending_inputs,
+                    spending_scripts,
+                    spending_tx.hex.clone(),
+                );
+                return Ok(DoubleSpendStatus::Replaced(spending_txid));
+            }
+        }
+
+        if status == DoubleSpendStatus::None && self.revert_dead_txs {
+            if let Some(raw_tx) = input_watcher.raw_tx(&txid) {
+                let raw_tx = Arc::new(raw_tx);
+                let reject_reason = self
+                    .with_retry("test_mempool_accept", || {
+                        let client = self.client.clone();
+                        let raw_tx = raw_tx.clone();
+                        Box::pin(async move { client.test_mempool_accept(&raw_tx).await })
+                    })
+                    .await?;
+                if let Some(reason) = reject_reason.filter(|reason| is_dead_reject_reason(reason)) {
+                    tracing::warn!(
+                        "Evicted transaction {} can't be mined anymore: {}",
+                        txid,
+                        reason
+                    );
+                    return Ok(DoubleSpendStatus::Conflicted);
+                }
+            }
+        }
+
+        Ok(status)
+    }
+
+    async fn broadcast_transaction(&self, raw_tx: &[u8]) -> Result<()> {
+        let raw_tx = Arc::new(raw_tx.to_vec());
+        self.with_retry("send_raw_transaction", || {
+            let client = self.client.clone();
+            let raw_tx = raw_tx.clone();
+            Box::pin(async move {
+                match client.send_raw_transaction(&raw_tx).await {
+                    Ok(_) => Ok(()),
+                    Err(Error::JsonRpc(jsonrpc::error::Error::Rpc(ref rpcerr)))
+                        if rpcerr.code == RPC_VERIFY_ALREADY_IN_CHAIN =>
+                    {
+                        Ok(())
+                    }
+                    Err(e) => Err(e),
+                }
+            })
+        })
+        .await
+    }
+
+    async fn chain_info(&self) -> Result<ChainInfo> {
+        self.with_retry("get_chain_info", || {
+            let client = self.client.clone();
+            Box::pin(async move { client.get_chain_info().await })
+        })
+        .await
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::service::retry::RetryStrategy;
     use bitcoin::{hashes::Hash, Txid, Wtxid};
+    use std::collections::HashMap;
     use std::sync::Mutex;
 
     struct MockBitcoinRpcClient {
         raw_transaction_info_config:
             Mutex<Option<MockCallConfig<bitcoincore_rpc::json::GetRawTransactionResult>>>,
+        spent_in_chain: Mutex<Vec<OutPoint>>,
+        spent_in_mempool: Mutex<Vec<OutPoint>>,
+        mempool_spenders: Mutex<HashMap<OutPoint, Txid>>,
+        // Transactions returned regardless of the call config
+        known_txs: Mutex<HashMap<Txid, bitcoincore_rpc::json::GetRawTransactionResult>>,
+        broadcasts: Mutex<Vec<Vec<u8>>>,
+        // RPC error code returned by sendrawtransaction, if any
+        broadcast_error: Mutex<Option<i32>>,
+        mempool: Mutex<Vec<Txid>>,
+        // Reject reason returned by testmempoolaccept, if any
+        reject_reason: Mutex<Option<String>>,
+        // Active chain, by height
+        headers: Mutex<Vec<Header>>,
+        proofs: Mutex<HashMap<Txid, Vec<u8>>>,
     }
 
     struct MockCallConfig<T> {
@@ -318,6 +1095,16 @@ mod tests {
         fn new() -> Self {
             Self {
                 raw_transaction_info_config: Mutex::new(None),
+                spent_in_chain: Mutex::new(Vec::new()),
+                spent_in_mempool: Mutex::new(Vec::new()),
+                mempool_spenders: Mutex::new(HashMap::new()),
+                known_txs: Mutex::new(HashMap::new()),
+                broadcasts: Mutex::new(Vec::new()),
+                broadcast_error: Mutex::new(None),
+                mempool: Mutex::new(Vec::new()),
+                reject_reason: Mutex::new(None),
+                headers: Mutex::new(Vec::new()),
+                proofs: Mutex::new(HashMap::new()),
             }
         }
 
@@ -377,8 +1164,11 @@ mod tests {
     impl BitcoinRpcClient for MockBitcoinRpcClient {
         async fn get_raw_transaction_info(
             &self,
-            _txid: &Txid,
+            txid: &Txid,
         ) -> Result<bitcoincore_rpc::json::GetRawTransactionResult, Error> {
+            if let Some(tx) = self.known_txs.lock().unwrap().get(txid) {
+                return Ok(tx.clone());
+            }
             let config = self.raw_transaction_info_config.lock().unwrap();
             match config.as_ref() {
                 Some(config) => {
@@ -400,6 +1190,90 @@ mod tests {
                 )))),
             }
         }
+
+        async fn is_output_unspent(
+            &self,
+            outpoint: &OutPoint,
+            include_mempool: bool,
+        ) -> Result<bool, Error> {
+            if self.spent_in_chain.lock().unwrap().contains(outpoint) {
+                return Ok(false);
+            }
+            let spent_in_mempool = self.spent_in_mempool.lock().unwrap().contains(outpoint)
+                || self.mempool_spenders.lock().unwrap().contains_key(outpoint);
+            Ok(!include_mempool || !spent_in_mempool)
+        }
+
+        async fn get_tx_spending_prevout(
+            &self,
+            outpoint: &OutPoint,
+        ) -> Result<Option<Txid>, Error> {
+            Ok(self.mempool_spenders.lock().unwrap().get(outpoint).copied())
+        }
+
+        async fn is_in_mempool(&self, txid: &Txid) -> Result<bool, Error> {
+            Ok(self.mempool.lock().unwrap().contains(txid))
+        }
+
+        async fn test_mempool_accept(&self, _raw_tx: &[u8]) -> Result<Option<String>, Error> {
+            Ok(self.reject_reason.lock().unwrap().clone())
+        }
+
+        async fn send_raw_transaction(&self, raw_tx: &[u8]) -> Result<Txid, Error> {
+            if let Some(code) = *self.broadcast_error.lock().unwrap() {
+                return Err(Error::JsonRpc(jsonrpc::error::Error::Rpc(
+                    jsonrpc::error::RpcError {
+                        code,
+                        message: "Transaction rejected".to_string(),
+                        data: None,
+                    },
+                )));
+            }
+            self.broadcasts.lock().unwrap().push(raw_tx.to_vec());
+            Ok(Txid::all_zeros())
+        }
+
+        async fn get_chain_info(&self) -> Result<ChainInfo, Error> {
+            // The tip of the mock chain, if it has headers
+            let tip_height = self.headers.lock().unwrap().len().checked_sub(1);
+            Ok(ChainInfo {
+                network: Network::Regtest,
+                tip_height: tip_height.map_or(150, |height| height as u64),
+            })
+        }
+
+        async fn get_coinbase_txid(&self, _height: u64) -> Result<Txid, Error> {
+            Ok(Txid::all_zeros())
+        }
+
+        async fn get_block_header(&self, height: u64) -> Result<Header, Error> {
+            self.headers
+                .lock()
+                .unwrap()
+                .get(height as usize)
+                .copied()
+                .ok_or_else(|| {
+                    Error::JsonRpc(jsonrpc::error::Error::Rpc(jsonrpc::error::RpcError {
+                        code: -8,
+                        message: "Block height out of range".to_string(),
+                        data: None,
+                    }))
+                })
+        }
+
+        async fn get_tx_out_proof(
+            &self,
+            txid: &Txid,
+            _block_hash: &BlockHash,
+        ) -> Result<Vec<u8>, Error> {
+            Ok(self
+                .proofs
+                .lock()
+                .unwrap()
+                .get(txid)
+                .cloned()
+                .unwrap_or_default())
+        }
     }
 
     // Helper function to create a test service
@@ -515,4 +1389,444 @@ mod tests {
             .await;
         assert!(result.is_err());
     }
+    #[tokio::test]
+    async fn test_circuit_breaker_fails_fast_when_open() {
+        let mock_client = Arc::new(MockBitcoinRpcClient::new());
+        mock_client.setup_with_connectivity_error(None);
+
+        let service = create_test_service(mock_client.clone(), 2)
+            .with_circuit_breaker(CircuitBreaker::new(2, Duration::from_secs(60)));
+        let txid = "0000000000000000000000000000000000000000000000000000000000000000";
+
+        // Two unreachable checks trip the breaker
+        for _ in 0..2 {
+            let err = service.is_tx_confirmed(txid).await.unwrap_err();
+            assert!(matches!(
+                err.downcast_ref::<BitcoinRpcError>(),
+                Some(BitcoinRpcError::BitcoinNodeUnreachable { .. })
+            ));
+        }
+
+        let attempts_before = *mock_client
+            .raw_transaction_info_config
+            .lock()
+            .unwrap()
+            .as_ref()
+            .unwrap()
+            .attempts
+            .lock()
+            .unwrap();
+
+        // Further checks fail fast without reaching the node
+        let err = service.is_tx_confirmed(txid).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<BitcoinRpcError>(),
+            Some(BitcoinRpcError::CircuitOpen { .. })
+        ));
+
+        let attempts_after = *mock_client
+            .raw_transaction_info_config
+            .lock()
+            .unwrap()
+            .as_ref()
+            .unwrap()
+            .attempts
+            .lock()
+            .unwrap();
+        assert_eq!(attempts_before, attempts_after);
+    }
+    #[tokio::test]
+    async fn test_call_budget_bounds_retries() {
+        let mock_client = Arc::new(MockBitcoinRpcClient::new());
+        mock_client.setup_with_connectivity_error(None);
+
+        let service = create_test_service(mock_client, 10).with_retry_policy(RetryPolicy {
+            strategy: RetryStrategy::Fixed,
+            jitter: false,
+            call_budget: Some(Duration::from_millis(20)),
+            ..RetryPolicy::exponential(10, Duration::from_millis(50))
+        });
+
+        let err = service
+            .is_tx_confirmed("0000000000000000000000000000000000000000000000000000000000000000")
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<BitcoinRpcError>(),
+            Some(BitcoinRpcError::BudgetExceeded { .. })
+        ));
+    }
+    // Creates an unconfirmed transaction spending `input` and paying to `script`
+    fn create_unconfirmed_tx(
+        txid: Txid,
+        input: OutPoint,
+        script: &[u8],
+    ) -> bitcoincore_rpc::json::GetRawTransactionResult {
+        let mut tx = MockBitcoinRpcClient::create_default_tx_result();
+        tx.txid = txid;
+        tx.confirmations = None;
+        tx.vin = vec![bitcoincore_rpc::json::GetRawTransactionResultVin {
+            sequence: 0,
+            coinbase: None,
+            txid: Some(input.txid),
+            vout: Some(input.vout),
+            script_sig: None,
+            txinwitness: None,
+        }];
+        tx.vout = vec![bitcoincore_rpc::json::GetRawTransactionResultVout {
+            value: bitcoin::Amount::from_sat(10_000),
+            n: 0,
+            script_pub_key: bitcoincore_rpc::json::GetRawTransactionResultVoutScriptPubKey {
+                asm: String::new(),
+                hex: script.to_vec(),
+                req_sigs: None,
+                type_: None,
+                addresses: vec![],
+                address: None,
+            },
+        }];
+        tx
+    }
+
+    // Creates a service whose node returns the given transaction once, then no longer knows it
+    fn create_dropped_tx_service(
+        mock_client: Arc<MockBitcoinRpcClient>,
+        tx: bitcoincore_rpc::json::GetRawTransactionResult,
+    ) -> BitcoinRpcService {
+        mock_client.setup_get_raw_transaction_info(
+            || {
+                Error::JsonRpc(jsonrpc::error::Error::Rpc(jsonrpc::error::RpcError {
+                    code: -5,
+                    message: "No such mempool or blockchain transaction".to_string(),
+                    data: None,
+                }))
+            },
+            tx,
+            Some(0),
+        );
+        create_test_service(mock_client, 1).with_double_spend_detection(InputWatcher::new(100))
+    }
+
+    #[tokio::test]
+    async fn test_double_spend_detection() {
+        let input = OutPoint::new(Txid::from_byte_array([1; 32]), 0);
+        let mock_client = Arc::new(MockBitcoinRpcClient::new());
+        let service = create_dropped_tx_service(
+            mock_client.clone(),
+            create_unconfirmed_tx(Txid::all_zeros(), input, &[0x51]),
+        );
+        let txid = "0000000000000000000000000000000000000000000000000000000000000000";
+
+        assert!(!service.is_tx_confirmed(txid).await.unwrap());
+        assert_eq!(
+            service.check_double_spend(txid).await.unwrap(),
+            DoubleSpendStatus::None
+        );
+
+        mock_client.spent_in_mempool.lock().unwrap().push(input);
+        assert_eq!(
+            service.check_double_spend(txid).await.unwrap(),
+            DoubleSpendStatus::AtRisk
+        );
+
+        mock_client.spent_in_chain.lock().unwrap().push(input);
+        assert_eq!(
+            service.check_double_spend(txid).await.unwrap(),
+            DoubleSpendStatus::Conflicted
+        );
+    }
+
+    #[tokio::test]
+    async fn test_evicted_tx_detection() {
+        let input = OutPoint::new(Txid::from_byte_array([1; 32]), 0);
+        let mock_client = Arc::new(MockBitcoinRpcClient::new());
+        let service = create_dropped_tx_service(
+            mock_client.clone(),
+            create_unconfirmed_tx(Txid::all_zeros(), input, &[0x51]),
+        )
+        .with_dead_tx_reverts(true);
+        let txid = "0000000000000000000000000000000000000000000000000000000000000000";
+        let unseen = "1111111111111111111111111111111111111111111111111111111111111111";
+
+        mock_client.mempool.lock().unwrap().push(Txid::all_zeros());
+        assert!(!service.is_tx_confirmed(txid).await.unwrap());
+        assert_eq!(
+            service.mempool_status(txid).await.unwrap(),
+            MempoolStatus::InMempool
+        );
+        assert_eq!(
+            service.mempool_status(unseen).await.unwrap(),
+            MempoolStatus::Unseen
+        );
+
+        mock_client.mempool.lock().unwrap().clear();
+        assert_eq!(
+            service.mempool_status(txid).await.unwrap(),
+            MempoolStatus::Evicted
+        );
+
+        // An evicted transaction is only dead if the mempool rejects it for good
+        *mock_client.reject_reason.lock().unwrap() = Some("mempool min fee not met".to_string());
+        assert_eq!(
+            service.check_double_spend(txid).await.unwrap(),
+            DoubleSpendStatus::None
+        );
+        *mock_client.reject_reason.lock().unwrap() =
+            Some("mandatory-script-verify-flag-failed (Invalid Schnorr signature)".to_string());
+        assert_eq!(
+            service.check_double_spend(txid).await.unwrap(),
+            DoubleSpendStatus::Conflicted
+        );
+    }
+
+    #[test]
+    fn test_parse_reject_reason() {
+        assert_eq!(
+            parse_reject_reason(json!([{ "txid": "00", "allowed": true }])).unwrap(),
+            None
+        );
+        assert_eq!(
+            parse_reject_reason(
+                json!([{ "txid": "00", "allowed": false, "reject-reason": "missing-inputs" }])
+            )
+            .unwrap()
+            .as_deref(),
+            Some("missing-inputs")
+        );
+        assert!(parse_reject_reason(json!([])).is_err());
+        assert!(is_dead_reject_reason("bad-txns-inputs-missingorspent"));
+        assert!(!is_dead_reject_reason("min relay fee not met"));
+    }
+
+    #[tokio::test]
+    async fn test_rbf_replacement_detection() {
+        let input = OutPoint::new(Txid::from_byte_array([1; 32]), 0);
+        let mock_client = Arc::new(MockBitcoinRpcClient::new());
+        let service = create_dropped_tx_service(
+            mock_client.clone(),
+            create_unconfirmed_tx(Txid::all_zeros(), input, &[0x51]),
+        );
+        let txid = "0000000000000000000000000000000000000000000000000000000000000000";
+        assert!(!service.is_tx_confirmed(txid).await.unwrap());
+
+        // A conflicting transaction paying elsewhere is not a replacement
+        let conflicting_txid = Txid::from_byte_array([2; 32]);
+        mock_client.known_txs.lock().unwrap().insert(
+            conflicting_txid,
+            create_unconfirmed_tx(conflicting_txid, input, &[0x52]),
+        );
+        mock_client
+            .mempool_spenders
+            .lock()
+            .unwrap()
+            .insert(input, conflicting_txid);
+        assert_eq!(
+            service.check_double_spend(txid).await.unwrap(),
+            DoubleSpendStatus::AtRisk
+        );
+
+        // A fee bump paying the same outputs is
+        let replacement_txid = Txid::from_byte_array([3; 32]);
+        mock_client.known_txs.lock().unwrap().insert(
+            replacement_txid,
+            create_unconfirmed_tx(replacement_txid, input, &[0x51]),
+        );
+        mock_client
+            .mempool_spenders
+            .lock()
+            .unwrap()
+            .insert(input, replacement_txid);
+        assert_eq!(
+            service.check_double_spend(txid).await.unwrap(),
+            DoubleSpendStatus::Replaced(replacement_txid)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_transaction() {
+        let mock_client = Arc::new(MockBitcoinRpcClient::new());
+        let service = create_test_service(mock_client.clone(), 1);
+
+        service.broadcast_transaction(&[1, 2, 3]).await.unwrap();
+        assert_eq!(*mock_client.broadcasts.lock().unwrap(), vec![vec![1, 2, 3]]);
+
+        // A transaction that is already mined counts as broadcast
+        *mock_client.broadcast_error.lock().unwrap() = Some(RPC_VERIFY_ALREADY_IN_CHAIN);
+        assert!(service.broadcast_transaction(&[1, 2, 3]).await.is_ok());
+
+        // Other rejections are reported
+        *mock_client.broadcast_error.lock().unwrap() = Some(-26);
+        assert!(service.broadcast_transaction(&[1, 2, 3]).await.is_err());
+    }
+
+    #[test]
+    fn test_parse_chain_info() {
+        let chain_info =
+            parse_chain_info(json!({ "chain": "test", "blocks": 2_500_000, "headers": 2_500_001 }))
+                .unwrap();
+        assert_eq!(chain_info.network, Network::Testnet);
+        assert_eq!(chain_info.tip_height, 2_500_000);
+        assert_eq!(network_name(chain_info.network), "testnet");
+        assert_eq!(network_name(Network::Bitcoin), "mainnet");
+
+        assert!(parse_chain_info(json!({ "chain": "nope", "blocks": 1 })).is_err());
+        assert!(parse_chain_info(json!({ "chain": "main" })).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_verify_network() {
+        let service = create_test_service(Arc::new(MockBitcoinRpcClient::new()), 1);
+
+        assert!(service.verify_network(Network::Regtest).await.is_ok());
+        let error = service.verify_network(Network::Bitcoin).await.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Bitcoin node is on regtest but BITCOIN_NETWORK is mainnet"
+        );
+
+        for network in [
+            Network::Bitcoin,
+            Network::Testnet,
+            Network::Testnet4,
+            Network::Signet,
+            Network::Regtest,
+        ] {
+            assert_eq!(parse_network_name(network_name(network)).unwrap(), network);
+        }
+        assert!(parse_network_name("bitcoin").is_err());
+    }
+
+    #[test]
+    fn test_normalize_txid() {
+        let txid = "ab".repeat(32);
+        assert_eq!(normalize_txid(&txid).unwrap(), txid);
+        assert_eq!(
+            normalize_txid(&format!(" 0x{} ", txid.to_uppercase())).unwrap(),
+            txid
+        );
+        assert_eq!(normalize_txid(&format!("0X{}", txid)).unwrap(), txid);
+
+        assert!(normalize_txid("").is_err());
+        assert!(normalize_txid("txid1").is_err());
+        assert!(normalize_txid(&"ab".repeat(33)).is_err());
+        assert!(normalize_txid(&"zz".repeat(32)).is_err());
+
+        assert_eq!(
+            parse_rpc_txid(&json!(format!("0x{}", txid.to_uppercase()))).unwrap(),
+            Txid::from_str(&txid).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_verify_txindex() {
+        let mock_client = Arc::new(MockBitcoinRpcClient::new());
+        mock_client.setup_get_raw_transaction_info(
+            || {
+                Error::JsonRpc(jsonrpc::error::Error::Rpc(jsonrpc::error::RpcError {
+                    code: -5,
+                    message: "No such mempool or blockchain transaction".to_string(),
+                    data: None,
+                }))
+            },
+            MockBitcoinRpcClient::create_default_tx_result(),
+            None,
+        );
+        let service = create_test_service(mock_client.clone(), 1);
+        let error = service.verify_txindex().await.unwrap_err();
+        assert!(error.to_string().contains("txindex=1"));
+
+        mock_client.known_txs.lock().unwrap().insert(
+            Txid::all_zeros(),
+            MockBitcoinRpcClient::create_default_tx_result(),
+        );
+        assert!(service.verify_txindex().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_warm_cache_primes_confirmations() {
+        let mock_client = Arc::new(MockBitcoinRpcClient::new());
+        // Succeeds on the first call only, so any further lookup would fail
+        mock_client.setup_with_connectivity_error(Some(0));
+
+        let service = create_test_service(mock_client.clone(), 1)
+            .with_confirmation_cache(ConfirmationCache::new(Duration::from_secs(60), 100));
+        let txid = "0000000000000000000000000000000000000000000000000000000000000000";
+
+        let warmed = service
+            .warm_cache(&[txid.to_string(), "not-a-txid".to_string()])
+            .await;
+        assert_eq!(warmed, 1);
+
+        // Served from the cache without reaching the node again
+        assert!(service.is_tx_confirmed(txid).await.unwrap());
+        assert!(service.is_tx_confirmed(txid).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_spv_verified_confirmations() -> Result<()> {
+        use crate::db::Database;
+        use crate::spv::tests::{merkle_proof, merkle_root, mine_header};
+        use crate::spv::{HeaderStore, HeaderSync};
+        use bitcoin::TxMerkleNode;
+
+        let txids: Vec<Txid> = (0..3u8).map(|idx| Txid::hash(&[idx])).collect();
+        let mut headers: Vec<Header> = Vec::new();
+        for height in 0..=10u32 {
+            let prev = headers
+                .last()
+                .map_or(BlockHash::all_zeros(), Header::block_hash);
+            let root = if height == 5 {
+                merkle_root(&txids)
+            } else {
+                TxMerkleNode::all_zeros()
+            };
+            headers.push(mine_header(prev, root, height));
+        }
+
+        let mock_client = Arc::new(MockBitcoinRpcClient::new());
+        *mock_client.headers.lock().unwrap() = headers.clone();
+        // The second transaction isn't in the block, the node serves it the proof of the first
+        let proof = merkle_proof(&headers[5], &txids, txids[0]);
+        for txid in &txids[..2] {
+            let mut tx = MockBitcoinRpcClient::create_default_tx_result();
+            tx.txid = *txid;
+            tx.blockhash = Some(headers[5].block_hash());
+            mock_client.known_txs.lock().unwrap().insert(*txid, tx);
+            mock_client
+                .proofs
+                .lock()
+                .unwrap()
+                .insert(*txid, proof.clone());
+        }
+        let store = Arc::new(HeaderStore::new(
+            Database::new(rusqlite::Connection::open_in_memory()?)?,
+            Network::Regtest,
+        ));
+        let service =
+            create_test_service(mock_client.clone(), 0).with_spv_verification(store.clone());
+
+        // Held below the threshold of 3 until the block is synced
+        assert_eq!(service.get_confirmations(&txids[0].to_string()).await?, 2);
+
+        let sync = HeaderSync::new(store.clone(), service.clone(), 2, Duration::from_secs(1));
+        assert_eq!(sync.sync().await?, 10);
+        assert_eq!(service.get_confirmations(&txids[0].to_string()).await?, 6);
+        assert_eq!(service.get_confirmations(&txids[1].to_string()).await?, 2);
+
+        // A reorg replaces the blocks from height 8
+        headers.truncate(8);
+        for height in 8..=11u32 {
+            let prev = headers.last().unwrap().block_hash();
+            headers.push(mine_header(prev, TxMerkleNode::all_zeros(), height + 100));
+        }
+        *mock_client.headers.lock().unwrap() = headers.clone();
+        assert_eq!(sync.sync().await?, 11);
+        assert_eq!(store.tip()?, Some((11, headers[11])));
+
+        // A checkpoint off the node's chain is rejected
+        let pinned = HeaderSync::new(store, service, 2, Duration::from_secs(1))
+            .with_start_hash(headers[3].block_hash());
+        assert!(pinned.sync().await.is_err());
+        Ok(())
+    }
 }
@@ -0,0 +1,169 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CircuitState {
+    Closed { consecutive_failures: u32 },
+    Open { until: Instant },
+    HalfOpen { probe_started: Instant },
+}
+
+/// Circuit breaker for calls to the Bitcoin node.
+///
+/// After `failure_threshold` consecutive connectivity failures the circuit opens and calls fail
+/// fast for `cooldown`. Once the cooldown elapses a single probe call is let through: success
+/// closes the circuit again, failure re-opens it for another cooldown period.
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    cooldown: Duration,
+    state: Mutex<CircuitState>,
+}
+
+impl CircuitBreaker {
+    /// Creates a new circuit breaker. A `failure_threshold` of 0 disables the breaker.
+    pub fn new(failure_threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            failure_threshold,
+            cooldown,
+            state: Mutex::new(CircuitState::Closed {
+                consecutive_failures: 0,
+            }),
+        }
+    }
+
+    /// Creates a circuit breaker that never opens
+    pub fn disabled() -> Self {
+        Self::new(0, Duration::ZERO)
+    }
+
+    /// Checks whether a call may proceed.
+    /// Returns `Err` with the suggested time to wait before retrying if the circuit is open.
+    pub fn try_acquire(&self) -> Result<(), Duration> {
+        if self.failure_threshold == 0 {
+            return Ok(());
+        }
+
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        match *state {
+            CircuitState::Closed { .. } => Ok(()),
+            CircuitState::Open { until } if now >= until => {
+                tracing::info!("Bitcoin RPC circuit half-open, probing node");
+                *state = CircuitState::HalfOpen { probe_started: now };
+                Ok(())
+            }
+            CircuitState::Open { until } => Err(until - now),
+            // Let another probe through if the previous one never reported back (e.g. the
+            // request was cancelled by the server timeout)
+            CircuitState::HalfOpen { probe_started } if now - probe_started >= self.cooldown => {
+                *state = CircuitState::HalfOpen { probe_started: now };
+                Ok(())
+            }
+            CircuitState::HalfOpen { probe_started } => Err(self.cooldown - (now - probe_started)),
+        }
+    }
+
+    /// Records a call that reached the Bitcoin node
+    pub fn record_success(&self) {
+        if self.failure_threshold == 0 {
+            return;
+        }
+
+        let mut state = self.state.lock().unwrap();
+        if !matches!(*state, CircuitState::Closed { .. }) {
+            tracing::info!("Bitcoin RPC circuit closed");
+        }
+        *state = CircuitState::Closed {
+            consecutive_failures: 0,
+        };
+    }
+
+    /// Records a call that failed because the Bitcoin node was unreachable
+    pub fn record_failure(&self) {
+        if self.failure_threshold == 0 {
+            return;
+        }
+
+        let mut state = self.state.lock().unwrap();
+        let open = CircuitState::Open {
+            until: Instant::now() + self.cooldown,
+        };
+        *state = match *state {
+            CircuitState::Closed {
+                consecutive_failures,
+            } if consecutive_failures + 1 < self.failure_threshold => CircuitState::Closed {
+                consecutive_failures: consecutive_failures + 1,
+            },
+            CircuitState::Closed { .. } | CircuitState::HalfOpen { .. } => {
+                tracing::warn!(
+                    "Bitcoin RPC circuit opened, failing fast for {:?}",
+                    self.cooldown
+                );
+                open
+            }
+            CircuitState::Open { .. } => open,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_opens_after_threshold() {
+        let breaker = CircuitBreaker::new(3, Duration::from_secs(60));
+
+        for _ in 0..2 {
+            assert!(breaker.try_acquire().is_ok());
+            breaker.record_failure();
+        }
+        assert!(breaker.try_acquire().is_ok());
+
+        breaker.record_failure();
+        let retry_after = breaker.try_acquire().unwrap_err();
+        assert!(retry_after <= Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_success_resets_failure_count() {
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(60));
+
+        breaker.record_failure();
+        breaker.record_success();
+        breaker.record_failure();
+        assert!(breaker.try_acquire().is_ok());
+    }
+
+    #[test]
+    fn test_half_open_allows_single_probe() {
+        let breaker = CircuitBreaker::new(1, Duration::from_millis(20));
+
+        breaker.record_failure();
+        assert!(breaker.try_acquire().is_err());
+
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(breaker.try_acquire().is_ok(), "probe should be let through");
+        assert!(breaker.try_acquire().is_err(), "only one probe at a time");
+
+        // Failed probe re-opens the circuit
+        breaker.record_failure();
+        assert!(breaker.try_acquire().is_err());
+
+        // Successful probe closes it
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(breaker.try_acquire().is_ok());
+        breaker.record_success();
+        assert!(breaker.try_acquire().is_ok());
+        assert!(breaker.try_acquire().is_ok());
+    }
+
+    #[test]
+    fn test_disabled_never_opens() {
+        let breaker = CircuitBreaker::disabled();
+        for _ in 0..10 {
+            breaker.record_failure();
+        }
+        assert!(breaker.try_acquire().is_ok());
+    }
+}
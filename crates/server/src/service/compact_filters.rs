@@ -0,0 +1,1059 @@
+//! Light-client backend following the Bitcoin chain over the P2P network with BIP157/158 compact
+//! block filters, so lock transactions are confirmed without any RPC node. Headers are validated
+//! into the header store, a block is only downloaded when its filter matches the outputs of a
+//! watched transaction, and the block's Merkle root is checked against its header before any of
+//! its transactions count as confirmed. A fork only replaces stored headers when it has more
+//! work, and each filter is checked against the peer's chain of BIP157 filter headers.
+
+use crate::service::bitcoin::{BitcoinRpcClient, ChainInfo};
+use crate::spv::HeaderStore;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use bitcoin::bip158::{BlockFilter, FilterHash, FilterHeader};
+use bitcoin::block::Header;
+use bitcoin::consensus::{deserialize, serialize};
+use bitcoin::hashes::Hash;
+use bitcoin::merkle_tree::MerkleBlock;
+use bitcoin::p2p::message::{NetworkMessage, RawNetworkMessage, MAX_MSG_SIZE};
+use bitcoin::p2p::message_blockdata::{GetHeadersMessage, Inventory};
+use bitcoin::p2p::message_filter::{CFHeaders, GetCFHeaders, GetCFilters};
+use bitcoin::p2p::message_network::VersionMessage;
+use bitcoin::p2p::{Address, Magic, ServiceFlags};
+use bitcoin::{BlockHash, Network, OutPoint, ScriptBuf, Transaction, Txid, Work};
+use bitcoincore_rpc::json::{
+    GetRawTransactionResult, GetRawTransactionResultVin, GetRawTransactionResultVinScriptSig,
+    GetRawTransactionResultVout, GetRawTransactionResultVoutScriptPubKey,
+};
+use bitcoincore_rpc::{jsonrpc, Error};
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::{mpsc, Notify};
+
+/// BIP158 basic filter type
+const BASIC_FILTER: u8 = 0;
+/// Most filters a peer serves per `getcfilters`
+const FILTER_BATCH_SIZE: u64 = 1000;
+/// Most headers a peer sends per `headers` message
+const MAX_HEADERS_PER_MESSAGE: usize = 2000;
+/// Blocks scanned back for a transaction first broadcast through the client, as it may already
+/// have been mined
+const RESCAN_BLOCKS: u64 = 144;
+/// How long to wait for a peer's response
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Transaction broadcast through the client, whose confirmation is watched
+struct WatchedTx {
+    tx: Transaction,
+    confirmation: Option<Confirmation>,
+    /// Height up to which block filters were checked for the transaction
+    scanned_to: u64,
+}
+
+/// Block a watched transaction was found in
+struct Confirmation {
+    block_hash: BlockHash,
+    height: u64,
+    /// Serialized Merkle block proving the transaction is in the block
+    proof: Vec<u8>,
+}
+
+#[derive(Default)]
+struct State {
+    txs: HashMap<Txid, WatchedTx>,
+    pending_broadcasts: Vec<Transaction>,
+}
+
+/// `BitcoinRpcClient` backed by a BIP157 peer instead of a Bitcoin node's RPC. Only transactions
+/// broadcast through the client are known, so locks must be submitted with their raw
+/// transaction. Output spends and mempool acceptance can't be queried. Watched transactions are
+/// kept in memory only: after a restart the client learns of them again when the rebroadcaster
+/// sends them.
+#[derive(Clone)]
+pub struct CompactFilterClient {
+    network: Network,
+    peer: String,
+    store: Arc<HeaderStore>,
+    start_height: u64,
+    start_hash: BlockHash,
+    interval: Duration,
+    state: Arc<Mutex<State>>,
+    broadcast: Arc<Notify>,
+}
+
+impl CompactFilterClient {
+    /// Follows the chain of `peer`, a `host:port` serving compact filters, from the checkpoint
+    /// block `start_hash` at `start_height`, syncing every interval
+    pub fn new(
+        network: Network,
+        peer: String,
+        store: Arc<HeaderStore>,
+        start_height: u64,
+        start_hash: BlockHash,
+        interval: Duration,
+    ) -> Self {
+        Self {
+            network,
+            peer,
+            store,
+            start_height,
+            start_hash,
+            interval,
+            state: Arc::new(Mutex::new(State::default())),
+            broadcast: Arc::new(Notify::new()),
+        }
+    }
+
+    /// Stays connected to the peer, syncing every interval and whenever a transaction is
+    /// broadcast, and reconnects after an interval when the connection fails
+    pub async fn run(self) {
+        loop {
+            match self.connect().await {
+                Ok(mut peer) => loop {
+                    if let Err(e) = self.sync(&mut peer).await {
+                        tracing::error!("Lost compact filter peer {}: {:#}", self.peer, e);
+                        break;
+                    }
+                    if let Err(e) = peer.idle(self.interval, &self.broadcast).await {
+                        tracing::error!("Lost compact filter peer {}: {:#}", self.peer, e);
+                        break;
+                    }
+                },
+                Err(e) => tracing::error!(
+                    "Failed to connect to compact filter peer {}: {:#}",
+                    self.peer,
+                    e
+                ),
+            }
+            tokio::time::sleep(self.interval).await;
+        }
+    }
+
+    async fn connect(&self) -> Result<Peer> {
+        let stream = tokio::net::TcpStream::connect(&self.peer).await?;
+        let start_height = self.tip_height()?;
+        Peer::handshake(stream, self.network.magic(), start_height).await
+    }
+
+    /// Syncs headers, scans filters for watched transactions and sends pending broadcasts
+    async fn sync(&self, peer: &mut Peer) -> Result<()> {
+        self.sync_headers(peer).await?;
+        self.scan_filters(peer).await?;
+        let pending = std::mem::take(&mut self.state.lock().unwrap().pending_broadcasts);
+        for tx in pending {
+            peer.send(NetworkMessage::Tx(tx)).await?;
+        }
+        Ok(())
+    }
+
+    async fn sync_headers(&self, peer: &mut Peer) -> Result<()> {
+        let checkpoint = self.store.header(self.start_height)?;
+        if checkpoint.is_none_or(|header| header.block_hash() != self.start_hash) {
+            // Without a locator, a peer sends the header of the stop block
+            peer.send(NetworkMessage::GetHeaders(GetHeadersMessage::new(
+                Vec::new(),
+                self.start_hash,
+            )))
+            .await?;
+            let header = peer
+                .receive(|message| match message {
+                    NetworkMessage::Headers(headers) => headers.first().copied(),
+                    _ => None,
+                })
+                .await?;
+            if header.block_hash() != self.start_hash {
+                anyhow::bail!(
+                    "Peer sent header {} for the checkpoint",
+                    header.block_hash()
+                );
+            }
+            self.store.set_checkpoint(self.start_height, &header)?;
+        }
+
+        loop {
+            peer.send(NetworkMessage::GetHeaders(GetHeadersMessage::new(
+                self.locator()?,
+                BlockHash::all_zeros(),
+            )))
+            .await?;
+            let headers = peer
+                .receive(|message| match message {
+                    NetworkMessage::Headers(headers) => Some(headers),
+                    _ => None,
+                })
+                .await?;
+            let Some(first) = headers.first() else {
+                return Ok(());
+            };
+            let fork_height = self
+                .store
+                .height(&first.prev_blockhash)?
+                .context("Peer sent headers that don't connect to the stored chain")?;
+            if fork_height < self.tip_height()? {
+                let fork_work = headers
+                    .iter()
+                    .fold(Work::from_be_bytes([0; 32]), |work, header| {
+                        work + header.work()
+                    });
+                if fork_work <= self.store.work_above(fork_height)? {
+                    anyhow::bail!(
+                        "Peer sent a fork from height {} without more work than the stored chain",
+                        fork_height
+                    );
+                }
+            }
+            if self.store.rewind(fork_height)? > 0 {
+                tracing::warn!("Block headers reorged back to height {}", fork_height);
+                self.unconfirm_above(fork_height);
+            }
+            self.store.extend(&headers)?;
+            if headers.len() < MAX_HEADERS_PER_MESSAGE {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Returns the hashes of the stored tip and its ancestors, sparser further back, down to
+    /// the checkpoint
+    fn locator(&self) -> Result<Vec<BlockHash>> {
+        let mut hashes = Vec::new();
+        let (mut height, mut step) = (self.tip_height()?, 1);
+        loop {
+            if let Some(header) = self.store.header(height)? {
+                hashes.push(header.block_hash());
+            }
+            if height <= self.start_height {
+                return Ok(hashes);
+            }
+            if hashes.len() >= 10 {
+                step *= 2;
+            }
+            height = height.saturating_sub(step).max(self.start_height);
+        }
+    }
+
+    /// Checks the filters of the blocks not yet scanned for unconfirmed watched transactions,
+    /// downloading the blocks that match
+    async fn scan_filters(&self, peer: &mut Peer) -> Result<()> {
+        let tip_height = self.tip_height()?;
+        // Snapshot, so transactions watched meanwhile are scanned from their own start
+        let scans: Vec<(Txid, u64, Vec<ScriptBuf>)> = {
+            let state = self.state.lock().unwrap();
+            state
+                .txs
+                .iter()
+                .filter(|(_, watched)| watched.confirmation.is_none())
+                .map(|(txid, watched)| {
+                    let scripts = watched
+                        .tx
+                        .output
+                        .iter()
+                        .filter(|output| !output.script_pubkey.is_op_return())
+                        .map(|output| output.script_pubkey.clone())
+                        .collect();
+                    (*txid, watched.scanned_to.max(self.start_height), scripts)
+                })
+                .collect()
+        };
+        let Some(mut height) = scans.iter().map(|(_, scanned_to, _)| scanned_to + 1).min() else {
+            return Ok(());
+        };
+
+        while height <= tip_height {
+            let stop_height = tip_height.min(height + FILTER_BATCH_SIZE - 1);
+            let stop_hash = self.block_hash(stop_height)?;
+            peer.send(NetworkMessage::GetCFHeaders(GetCFHeaders {
+                filter_type: BASIC_FILTER,
+                start_height: height as u32,
+                stop_hash,
+            }))
+            .await?;
+            let filter_headers = peer
+                .receive(|message| match message {
+                    NetworkMessage::CFHeaders(headers)
+                        if headers.filter_type == BASIC_FILTER
+                            && headers.stop_hash == stop_hash =>
+                    {
+                        Some(headers)
+                    }
+                    _ => None,
+                })
+                .await?;
+            let filter_hashes = self.check_filter_headers(height, stop_height, filter_headers)?;
+            peer.send(NetworkMessage::GetCFilters(GetCFilters {
+                filter_type: BASIC_FILTER,
+                start_height: height as u32,
+                stop_hash,
+            }))
+            .await?;
+
+            let mut matches = Vec::new();
+            for (height, filter_hash) in (height..=stop_height).zip(filter_hashes) {
+                let block_hash = self.block_hash(height)?;
+                let filter = peer
+                    .receive(|message| match message {
+                        NetworkMessage::CFilter(filter) if filter.block_hash == block_hash => {
+                            Some(filter)
+                        }
+                        _ => None,
+                    })
+                    .await?;
+                if FilterHash::hash(&filter.filter) != filter_hash {
+                    anyhow::bail!(
+                        "Peer sent a filter for block {} that doesn't match its filter header",
+                        block_hash
+                    );
+                }
+                let scripts = scans
+                    .iter()
+                    .filter(|(_, scanned_to, _)| *scanned_to < height)
+                    .flat_map(|(_, _, scripts)| scripts.iter().map(|script| script.as_bytes()));
+                if BlockFilter::new(&filter.filter).match_any(&block_hash, scripts)? {
+                    matches.push((height, block_hash));
+                }
+            }
+            for (height, block_hash) in matches {
+                self.fetch_block(peer, height, block_hash).await?;
+            }
+
+            let mut state = self.state.lock().unwrap();
+            for (txid, _, _) in &scans {
+                if let Some(watched) = state.txs.get_mut(txid) {
+                    watched.scanned_to = watched.scanned_to.max(stop_height);
+                }
+            }
+            height = stop_height + 1;
+        }
+        Ok(())
+    }
+
+    /// Checks the filter headers a peer sent for the blocks from `start_height` to
+    /// `stop_height` extend the ones stored from earlier scans and agree with them, stores them
+    /// and returns the filter hashes they commit to
+    fn check_filter_headers(
+        &self,
+        start_height: u64,
+        stop_height: u64,
+        filter_headers: CFHeaders,
+    ) -> Result<Vec<FilterHash>> {
+        if filter_headers.filter_hashes.len() as u64 != stop_height - start_height + 1 {
+            anyhow::bail!(
+                "Peer sent {} filter hashes for {} blocks",
+                filter_headers.filter_hashes.len(),
+                stop_height - start_height + 1
+            );
+        }
+        let mut previous = filter_headers.previous_filter_header;
+        if start_height > self.start_height {
+            let stored = self.store.filter_header(start_height - 1)?;
+            if stored.is_some_and(|stored| stored != previous) {
+                anyhow::bail!(
+                    "Peer's filter header at height {} differs from the one it sent before",
+                    start_height - 1
+                );
+            }
+        }
+        let mut chain: Vec<FilterHeader> = Vec::new();
+        for (height, filter_hash) in (start_height..).zip(&filter_headers.filter_hashes) {
+            previous = filter_hash.filter_header(&previous);
+            if self
+                .store
+                .filter_header(height)?
+                .is_some_and(|stored| stored != previous)
+            {
+                anyhow::bail!(
+                    "Peer's filter header at height {} differs from the one it sent before",
+                    height
+                );
+            }
+            chain.push(previous);
+        }
+        self.store.set_filter_headers(start_height, &chain)?;
+        Ok(filter_headers.filter_hashes)
+    }
+
+    /// Downloads a block whose filter matched and confirms the watched transactions in it
+    async fn fetch_block(&self, peer: &mut Peer, height: u64, block_hash: BlockHash) -> Result<()> {
+        peer.send(NetworkMessage::GetData(vec![Inventory::Block(block_hash)]))
+            .await?;
+        let block = peer
+            .receive(|message| match message {
+                NetworkMessage::Block(block) if block.block_hash() == block_hash => Some(block),
+                _ => None,
+            })
+            .await?;
+        if !block.check_merkle_root() {
+            anyhow::bail!("Peer sent block {} with an invalid Merkle root", block_hash);
+        }
+
+        let mut state = self.state.lock().unwrap();
+        for tx in &block.txdata {
+            let txid = tx.compute_txid();
+            let Some(watched) = state.txs.get_mut(&txid) else {
+                continue;
+            };
+            tracing::info!("Found transaction {} in block {}", txid, block_hash);
+            watched.confirmation = Some(Confirmation {
+                block_hash,
+                height,
+                proof: serialize(&MerkleBlock::from_block_with_predicate(
+                    &block,
+                    |candidate| *candidate == txid,
+                )),
+            });
+        }
+        Ok(())
+    }
+
+    /// Forgets the confirmations in blocks above `height`, so they are scanned again
+    fn unconfirm_above(&self, height: u64) {
+        let mut state = self.state.lock().unwrap();
+        for watched in state.txs.values_mut() {
+            if watched
+                .confirmation
+                .as_ref()
+                .is_some_and(|confirmation| confirmation.height > height)
+            {
+                watched.confirmation = None;
+            }
+            watched.scanned_to = watched.scanned_to.min(height);
+        }
+    }
+
+    fn tip_height(&self) -> Result<u64> {
+        Ok(self
+            .store
+            .tip()?
+            .map_or(self.start_height, |(height, _)| height))
+    }
+
+    fn block_hash(&self, height: u64) -> Result<BlockHash> {
+        Ok(self
+            .store
+            .header(height)?
+            .with_context(|| format!("Header store has no header at height {}", height))?
+            .block_hash())
+    }
+}
+
+fn rpc_error(code: i32, message: &str) -> Error {
+    Error::JsonRpc(jsonrpc::error::Error::Rpc(jsonrpc::error::RpcError {
+        code,
+        message: message.to_string(),
+        data: None,
+    }))
+}
+
+fn unsupported(method: &str) -> Error {
+    rpc_error(
+        -32601,
+        &format!("{} is not supported by the compact filter client", method),
+    )
+}
+
+/// Describes a transaction the way `getrawtransaction` does
+fn transaction_result(
+    tx: &Transaction,
+    block_hash: Option<BlockHash>,
+    confirmations: Option<u32>,
+) -> GetRawTransactionResult {
+    GetRawTransactionResult {
+        in_active_chain: None,
+        hex: serialize(tx),
+        txid: tx.compute_txid(),
+        hash: tx.compute_wtxid(),
+        size: tx.total_size(),
+        vsize: tx.vsize(),
+        version: tx.version.0 as u32,
+        locktime: tx.lock_time.to_consensus_u32(),
+        vin: tx
+            .input
+            .iter()
+            .map(|input| GetRawTransactionResultVin {
+                sequence: input.sequence.0,
+                coinbase: None,
+                txid: Some(input.previous_output.txid),
+                vout: Some(input.previous_output.vout),
+                script_sig: Some(GetRawTransactionResultVinScriptSig {
+                    asm: String::new(),
+                    hex: input.script_sig.to_bytes(),
+                }),
+                txinwitness: Some(input.witness.to_vec()),
+            })
+            .collect(),
+        vout: tx
+            .output
+            .iter()
+            .zip(0..)
+            .map(|(output, n)| GetRawTransactionResultVout {
+                value: output.value,
+                n,
+                script_pub_key: GetRawTransactionResultVoutScriptPubKey {
+                    asm: String::new(),
+                    hex: output.script_pubkey.to_bytes(),
+                    req_sigs: None,
+                    type_: None,
+                    addresses: Vec::new(),
+                    address: None,
+                },
+            })
+            .collect(),
+        blockhash: block_hash,
+        confirmations,
+        time: None,
+        blocktime: None,
+    }
+}
+
+#[async_trait]
+impl BitcoinRpcClient for CompactFilterClient {
+    async fn get_raw_transaction_info(
+        &self,
+        txid: &Txid,
+    ) -> Result<GetRawTransactionResult, Error> {
+        let tip_height = self
+            .tip_height()
+            .map_err(|e| rpc_error(-32603, &e.to_string()))?;
+        let state = self.state.lock().unwrap();
+        let watched = state
+            .txs
+            .get(txid)
+            .ok_or_else(|| rpc_error(-5, "No such transaction broadcast through this client"))?;
+        Ok(match &watched.confirmation {
+            Some(confirmation) => transaction_result(
+                &watched.tx,
+                Some(confirmation.block_hash),
+                Some(tip_height.saturating_sub(confirmation.height) as u32 + 1),
+            ),
+            None => transaction_result(&watched.tx, None, None),
+        })
+    }
+
+    async fn is_output_unspent(
+        &self,
+        _outpoint: &OutPoint,
+        _include_mempool: bool,
+    ) -> Result<bool, Error> {
+        Err(unsupported("gettxout"))
+    }
+
+    async fn get_tx_spending_prevout(&self, _outpoint: &OutPoint) -> Result<Option<Txid>, Error> {
+        Err(unsupported("gettxspendingprevout"))
+    }
+
+    /// Optimistic: a transaction broadcast through the client counts as in the mempool until it
+    /// is mined, even if the peer rejected or evicted it, and is unknown after a restart until
+    /// it is rebroadcast
+    async fn is_in_mempool(&self, txid: &Txid) -> Result<bool, Error> {
+        Ok(self
+            .state
+            .lock()
+            .unwrap()
+            .txs
+            .get(txid)
+            .is_some_and(|watched| watched.confirmation.is_none()))
+    }
+
+    async fn test_mempool_accept(&self, _raw_tx: &[u8]) -> Result<Option<String>, Error> {
+        Err(unsupported("testmempoolaccept"))
+    }
+
+    async fn send_raw_transaction(&self, raw_tx: &[u8]) -> Result<Txid, Error> {
+        let tx: Transaction =
+            deserialize(raw_tx).map_err(|_| rpc_error(-22, "TX decode failed"))?;
+        let txid = tx.compute_txid();
+        let scanned_to = self
+            .tip_height()
+            .map_err(|e| rpc_error(-32603, &e.to_string()))?
+            .saturating_sub(RESCAN_BLOCKS);
+        {
+            let mut state = self.state.lock().unwrap();
+            state.txs.entry(txid).or_insert_with(|| WatchedTx {
+                tx: tx.clone(),
+                confirmation: None,
+                scanned_to,
+            });
+            state.pending_broadcasts.push(tx);
+        }
+        self.broadcast.notify_one();
+        Ok(txid)
+    }
+
+    async fn get_chain_info(&self) -> Result<ChainInfo, Error> {
+        Ok(ChainInfo {
+            network: self.network,
+            tip_height: self
+                .tip_height()
+                .map_err(|e| rpc_error(-32603, &e.to_string()))?,
+        })
+    }
+
+    async fn get_coinbase_txid(&self, _height: u64) -> Result<Txid, Error> {
+        Err(unsupported("getblock"))
+    }
+
+    async fn get_block_header(&self, height: u64) -> Result<Header, Error> {
+        self.store
+            .header(height)
+            .map_err(|e| rpc_error(-32603, &e.to_string()))?
+            .ok_or_else(|| rpc_error(-8, "Block height out of range"))
+    }
+
+    async fn get_tx_out_proof(
+        &self,
+        txid: &Txid,
+        block_hash: &BlockHash,
+    ) -> Result<Vec<u8>, Error> {
+        let state = self.state.lock().unwrap();
+        state
+            .txs
+            .get(txid)
+            .and_then(|watched| watched.confirmation.as_ref())
+            .filter(|confirmation| confirmation.block_hash == *block_hash)
+            .map(|confirmation| confirmation.proof.clone())
+            .ok_or_else(|| rpc_error(-5, "Transaction not yet in block"))
+    }
+}
+
+/// Connection to a peer. Messages are read by a background task, so waiting for one can be
+/// abandoned without losing part of it.
+struct Peer {
+    magic: Magic,
+    messages: mpsc::Receiver<NetworkMessage>,
+    writer: Box<dyn AsyncWrite + Send + Unpin>,
+}
+
+impl Peer {
+    /// Exchanges `version` and `verack` messages, failing if the peer doesn't serve compact
+    /// filters
+    async fn handshake(
+        stream: impl AsyncRead + AsyncWrite + Send + 'static,
+        magic: Magic,
+        start_height: u64,
+    ) -> Result<Self> {
+        let (mut reader, writer) = tokio::io::split(stream);
+        let (sender, messages) = mpsc::channel(FILTER_BATCH_SIZE as usize);
+        tokio::spawn(async move {
+            loop {
+                match read_message(&mut reader, magic).await {
+                    Ok(message) => {
+                        if sender.send(message).await.is_err() {
+                            return;
+                        }
+                    }
+                    Err(e) => {
+                        tracing::debug!("Stopped reading from compact filter peer: {:#}", e);
+                        return;
+                    }
+                }
+            }
+        });
+        let mut peer = Self {
+            magic,
+            messages,
+            writer: Box::new(writer),
+        };
+
+        let unspecified = Address::new(
+            &SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+            ServiceFlags::NONE,
+        );
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        peer.send(NetworkMessage::Version(VersionMessage::new(
+            ServiceFlags::NONE,
+            now.as_secs() as i64,
+            unspecified.clone(),
+            unspecified,
+            // Only used to detect connections to ourselves
+            now.as_nanos() as u64,
+            format!("/sova-sentinel:{}/", env!("CARGO_PKG_VERSION")),
+            start_height as i32,
+        )))
+        .await?;
+        let services = peer
+            .receive(|message| match message {
+                NetworkMessage::Version(version) => Some(version.services),
+                _ => None,
+            })
+            .await?;
+        if !services.has(ServiceFlags::COMPACT_FILTERS) {
+            anyhow::bail!("Peer doesn't serve compact filters");
+        }
+        peer.send(NetworkMessage::Verack).await?;
+        peer.receive(|message| matches!(message, NetworkMessage::Verack).then_some(()))
+            .await?;
+        Ok(peer)
+    }
+
+    async fn send(&mut self, message: NetworkMessage) -> Result<()> {
+        let bytes = serialize(&RawNetworkMessage::new(self.magic, message));
+        self.writer.write_all(&bytes).await?;
+        Ok(())
+    }
+
+    /// Waits for the first message `accept` maps to a value, answering pings meanwhile and
+    /// dropping other messages
+    async fn receive<T>(
+        &mut self,
+        mut accept: impl FnMut(NetworkMessage) -> Option<T>,
+    ) -> Result<T> {
+        let deadline = tokio::time::Instant::now() + RESPONSE_TIMEOUT;
+        loop {
+            let message = tokio::time::timeout_at(deadline, self.messages.recv())
+                .await
+                .context("Peer didn't respond in time")?
+                .context("Peer disconnected")?;
+            if let NetworkMessage::Ping(nonce) = message {
+                self.send(NetworkMessage::Pong(nonce)).await?;
+            } else if let Some(value) = accept(message) {
+                return Ok(value);
+            }
+        }
+    }
+
+    /// Waits `duration` or until `wake` is notified, answering pings meanwhile
+    async fn idle(&mut self, duration: Duration, wake: &Notify) -> Result<()> {
+        let sleep = tokio::time::sleep(duration);
+        tokio::pin!(sleep);
+        loop {
+            tokio::select! {
+                _ = &mut sleep => return Ok(()),
+                _ = wake.notified() => return Ok(()),
+                message = self.messages.recv() => match message {
+                    Some(NetworkMessage::Ping(nonce)) => {
+                        self.send(NetworkMessage::Pong(nonce)).await?
+                    }
+                    Some(_) => {}
+                    None => anyhow::bail!("Peer disconnected"),
+                },
+            }
+        }
+    }
+}
+
+/// Reads one message, failing on a message for another network or one larger than the protocol
+/// allows
+async fn read_message(
+    reader: &mut (impl AsyncRead + Unpin),
+    magic: Magic,
+) -> Result<NetworkMessage> {
+    let mut bytes = vec![0; 24];
+    reader.read_exact(&mut bytes).await?;
+    let len = u32::from_le_bytes(bytes[16..20].try_into()?) as usize;
+    if len > MAX_MSG_SIZE {
+        anyhow::bail!("Peer sent a message of {} bytes", len);
+    }
+    bytes.resize(24 + len, 0);
+    reader.read_exact(&mut bytes[24..]).await?;
+    let message: RawNetworkMessage = deserialize(&bytes)?;
+    if *message.magic() != magic {
+        anyhow::bail!("Peer sent a message for another network");
+    }
+    Ok(message.into_payload())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+    use crate::spv::tests::{merkle_root, mine_header};
+    use bitcoin::{absolute::LockTime, transaction::Version, Amount, Block, TxIn, TxOut};
+
+    fn transaction(lock_time: u32, previous_output: OutPoint, script: &[u8]) -> Transaction {
+        Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::from_consensus(lock_time),
+            input: vec![TxIn {
+                previous_output,
+                ..Default::default()
+            }],
+            output: vec![TxOut {
+                value: Amount::from_sat(1000),
+                script_pubkey: ScriptBuf::from_bytes(script.to_vec()),
+            }],
+        }
+    }
+
+    fn filter(block: &Block) -> BlockFilter {
+        BlockFilter::new_script_filter(block, |_| Ok(ScriptBuf::new())).unwrap()
+    }
+
+    /// Mines the blocks at `heights` on top of `prev`, with coinbases told apart by `salt`
+    fn mine(
+        prev: Option<&Block>,
+        heights: std::ops::RangeInclusive<u32>,
+        salt: &[u8],
+    ) -> Vec<Block> {
+        let mut blocks: Vec<Block> = Vec::new();
+        for height in heights {
+            let script = [b"\x51".as_slice(), salt].concat();
+            let txdata = vec![transaction(height, OutPoint::null(), &script)];
+            let prev = blocks
+                .last()
+                .or(prev)
+                .map_or(BlockHash::all_zeros(), Block::block_hash);
+            let txids: Vec<Txid> = txdata.iter().map(Transaction::compute_txid).collect();
+            blocks.push(Block {
+                header: mine_header(prev, merkle_root(&txids), height),
+                txdata,
+            });
+        }
+        blocks
+    }
+
+    /// Serves `blocks` as a peer with compact filters, recording the transactions sent to it.
+    /// With `corrupt_filters`, the filters don't match the filter headers.
+    async fn serve(
+        stream: tokio::io::DuplexStream,
+        blocks: Vec<Block>,
+        received: Arc<Mutex<Vec<Txid>>>,
+        corrupt_filters: bool,
+    ) -> Result<()> {
+        let magic = Network::Regtest.magic();
+        let (mut reader, mut writer) = tokio::io::split(stream);
+        let height = |hash: &BlockHash| blocks.iter().position(|block| block.block_hash() == *hash);
+        loop {
+            let responses = match read_message(&mut reader, magic).await? {
+                NetworkMessage::Version(mut version) => {
+                    version.services = ServiceFlags::NETWORK | ServiceFlags::COMPACT_FILTERS;
+                    vec![NetworkMessage::Version(version), NetworkMessage::Verack]
+                }
+                NetworkMessage::GetHeaders(request) if request.locator_hashes.is_empty() => {
+                    let height = height(&request.stop_hash).unwrap();
+                    vec![NetworkMessage::Headers(vec![blocks[height].header])]
+                }
+                NetworkMessage::GetHeaders(request) => {
+                    let fork = request.locator_hashes.iter().find_map(height).unwrap();
+                    let headers = blocks[fork + 1..].iter().map(|block| block.header);
+                    vec![NetworkMessage::Headers(headers.collect())]
+                }
+                NetworkMessage::GetCFHeaders(request) => {
+                    let stop = height(&request.stop_hash).unwrap();
+                    let start = request.start_height as usize;
+                    let filter_hashes: Vec<FilterHash> = blocks[..=stop]
+                        .iter()
+                        .map(|block| FilterHash::hash(&filter(block).content))
+                        .collect();
+                    let previous_filter_header = filter_hashes[..start]
+                        .iter()
+                        .fold(FilterHeader::all_zeros(), |previous, filter_hash| {
+                            filter_hash.filter_header(&previous)
+                        });
+                    vec![NetworkMessage::CFHeaders(CFHeaders {
+                        filter_type: BASIC_FILTER,
+                        stop_hash: request.stop_hash,
+                        previous_filter_header,
+                        filter_hashes: filter_hashes[start..].to_vec(),
+                    })]
+                }
+                NetworkMessage::GetCFilters(request) => {
+                    let stop = height(&request.stop_hash).unwrap();
+                    blocks[request.start_height as usize..=stop]
+                        .iter()
+                        .map(|block| {
+                            let mut filter = filter(block).content;
+                            if corrupt_filters {
+                                filter.push(0);
+                            }
+                            NetworkMessage::CFilter(bitcoin::p2p::message_filter::CFilter {
+                                filter_type: BASIC_FILTER,
+                                block_hash: block.block_hash(),
+                                filter,
+                            })
+                        })
+                        .collect()
+                }
+                NetworkMessage::GetData(inventory) => inventory
+                    .iter()
+                    .filter_map(|item| match item {
+                        Inventory::Block(hash) => height(hash),
+                        _ => None,
+                    })
+                    .map(|height| NetworkMessage::Block(blocks[height].clone()))
+                    .collect(),
+                NetworkMessage::Tx(tx) => {
+                    received.lock().unwrap().push(tx.compute_txid());
+                    vec![]
+                }
+                NetworkMessage::Ping(nonce) => vec![NetworkMessage::Pong(nonce)],
+                _ => vec![],
+            };
+            for response in responses {
+                writer
+                    .write_all(&serialize(&RawNetworkMessage::new(magic, response)))
+                    .await?;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_confirms_watched_transactions() -> Result<()> {
+        let watched = transaction(
+            0,
+            OutPoint::new(Txid::hash(b"funding"), 0),
+            b"\x51\x20watched",
+        );
+        let unmined = transaction(
+            0,
+            OutPoint::new(Txid::hash(b"funding"), 1),
+            b"\x51\x20unmined",
+        );
+        let mut blocks: Vec<Block> = Vec::new();
+        for height in 0..=5u32 {
+            let mut txdata = vec![transaction(height, OutPoint::null(), b"\x51")];
+            if height == 3 {
+                txdata.push(watched.clone());
+            }
+            let prev = blocks
+                .last()
+                .map_or(BlockHash::all_zeros(), Block::block_hash);
+            let txids: Vec<Txid> = txdata.iter().map(Transaction::compute_txid).collect();
+            blocks.push(Block {
+                header: mine_header(prev, merkle_root(&txids), height),
+                txdata,
+            });
+        }
+
+        let store = Arc::new(HeaderStore::new(
+            Database::new(rusqlite::Connection::open_in_memory()?)?,
+            Network::Regtest,
+        ));
+        let client = CompactFilterClient::new(
+            Network::Regtest,
+            String::new(),
+            store.clone(),
+            0,
+            blocks[0].block_hash(),
+            Duration::from_secs(1),
+        );
+        let txid = client.send_raw_transaction(&serialize(&watched)).await?;
+        client.send_raw_transaction(&serialize(&unmined)).await?;
+        assert!(client.is_in_mempool(&txid).await?);
+        assert!(client
+            .get_raw_transaction_info(&txid)
+            .await?
+            .confirmations
+            .is_none());
+        assert!(client
+            .get_raw_transaction_info(&Txid::all_zeros())
+            .await
+            .is_err());
+
+        let (stream, peer_stream) = tokio::io::duplex(1 << 20);
+        let received = Arc::new(Mutex::new(Vec::new()));
+        tokio::spawn(serve(peer_stream, blocks.clone(), received.clone(), false));
+        let mut peer = Peer::handshake(stream, Network::Regtest.magic(), 0).await?;
+        client.sync(&mut peer).await?;
+
+        assert_eq!(client.get_chain_info().await?.tip_height, 5);
+        let tx_info = client.get_raw_transaction_info(&txid).await?;
+        assert_eq!(tx_info.confirmations, Some(3));
+        assert_eq!(tx_info.blockhash, Some(blocks[3].block_hash()));
+        assert_eq!(tx_info.vout[0].script_pub_key.hex, b"\x51\x20watched");
+        assert!(!client.is_in_mempool(&txid).await?);
+        let proof = client
+            .get_tx_out_proof(&txid, &blocks[3].block_hash())
+            .await?;
+        assert_eq!(store.confirmations(&txid, &proof)?, Some(3));
+
+        let unmined = client
+            .get_raw_transaction_info(&unmined.compute_txid())
+            .await?;
+        assert!(unmined.confirmations.is_none());
+
+        // The peer handles messages in order, so the broadcasts arrived before the pong
+        peer.send(NetworkMessage::Ping(1)).await?;
+        peer.receive(|message| matches!(message, NetworkMessage::Pong(1)).then_some(()))
+            .await?;
+        assert_eq!(received.lock().unwrap().len(), 2);
+        Ok(())
+    }
+
+    async fn connect(blocks: &[Block], corrupt_filters: bool) -> Result<Peer> {
+        let (stream, peer_stream) = tokio::io::duplex(1 << 20);
+        let received = Arc::new(Mutex::new(Vec::new()));
+        tokio::spawn(serve(
+            peer_stream,
+            blocks.to_vec(),
+            received,
+            corrupt_filters,
+        ));
+        Peer::handshake(stream, Network::Regtest.magic(), 0).await
+    }
+
+    #[tokio::test]
+    async fn test_only_reorgs_to_more_work() -> Result<()> {
+        let chain = mine(None, 0..=5, b"");
+        let store = Arc::new(HeaderStore::new(
+            Database::new(rusqlite::Connection::open_in_memory()?)?,
+            Network::Regtest,
+        ));
+        let client = CompactFilterClient::new(
+            Network::Regtest,
+            String::new(),
+            store.clone(),
+            0,
+            chain[0].block_hash(),
+            Duration::from_secs(1),
+        );
+        client.sync(&mut connect(&chain, false).await?).await?;
+        assert_eq!(client.tip_height()?, 5);
+
+        // A fork of the same length has no more work, so the stored chain stays
+        let fork = [&chain[..=3], &mine(Some(&chain[3]), 4..=5, b"fork")].concat();
+        assert!(client
+            .sync(&mut connect(&fork, false).await?)
+            .await
+            .is_err());
+        assert_eq!(client.block_hash(5)?, chain[5].block_hash());
+
+        let fork = [&chain[..=3], &mine(Some(&chain[3]), 4..=6, b"fork")].concat();
+        client.sync(&mut connect(&fork, false).await?).await?;
+        assert_eq!(client.tip_height()?, 6);
+        assert_eq!(client.block_hash(5)?, fork[5].block_hash());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_checks_filters_against_filter_headers() -> Result<()> {
+        let watched = transaction(
+            0,
+            OutPoint::new(Txid::hash(b"funding"), 0),
+            b"\x51\x20watched",
+        );
+        let chain = mine(None, 0..=3, b"");
+        let store = Arc::new(HeaderStore::new(
+            Database::new(rusqlite::Connection::open_in_memory()?)?,
+            Network::Regtest,
+        ));
+        let client = CompactFilterClient::new(
+            Network::Regtest,
+            String::new(),
+            store.clone(),
+            0,
+            chain[0].block_hash(),
+            Duration::from_secs(1),
+        );
+        client.send_raw_transaction(&serialize(&watched)).await?;
+
+        let error = client
+            .sync(&mut connect(&chain, true).await?)
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("filter header"), "{:#}", error);
+
+        client.sync(&mut connect(&chain, false).await?).await?;
+        let filter_header = store.filter_header(3)?.unwrap();
+        let expected = chain[..=3]
+            .iter()
+            .fold(FilterHeader::all_zeros(), |previous, block| {
+                filter(block).filter_header(&previous)
+            });
+        assert_eq!(filter_header, expected);
+        Ok(())
+    }
+}
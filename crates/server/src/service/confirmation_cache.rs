@@ -0,0 +1,126 @@
+use bitcoin::Txid;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+struct CacheEntry {
+    confirmations: u32,
+    fetched_at: Instant,
+}
+
+/// Cache of Bitcoin transaction confirmation counts.
+///
+/// Transactions that reached the confirmation threshold stay cached until evicted, since their
+/// confirmation status no longer changes. Transactions below the threshold are only served from
+/// the cache for `ttl`.
+pub struct ConfirmationCache {
+    ttl: Duration,
+    capacity: usize,
+    entries: Mutex<HashMap<Txid, CacheEntry>>,
+}
+
+impl ConfirmationCache {
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            ttl,
+            capacity,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Creates a cache that never stores anything
+    pub fn disabled() -> Self {
+        Self::new(Duration::ZERO, 0)
+    }
+
+    /// Returns the cached confirmation count if it is still usable for the given threshold
+    pub fn get(&self, txid: &Txid, threshold: u32) -> Option<u32> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .get(txid)
+            .filter(|entry| {
+                entry.confirmations >= threshold || entry.fetched_at.elapsed() < self.ttl
+            })
+            .map(|entry| entry.confirmations)
+    }
+
+    pub fn insert(&self, txid: Txid, confirmations: u32) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= self.capacity && !entries.contains_key(&txid) {
+            // Drop the oldest entry to make room
+            if let Some(oldest) = entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.fetched_at)
+                .map(|(txid, _)| *txid)
+            {
+                entries.remove(&oldest);
+            }
+        }
+        entries.insert(
+            txid,
+            CacheEntry {
+                confirmations,
+                fetched_at: Instant::now(),
+            },
+        );
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::hashes::Hash;
+
+    fn txid(byte: u8) -> Txid {
+        Txid::from_byte_array([byte; 32])
+    }
+
+    #[test]
+    fn test_confirmed_entries_do_not_expire() {
+        let cache = ConfirmationCache::new(Duration::ZERO, 10);
+        cache.insert(txid(1), 6);
+        cache.insert(txid(2), 2);
+
+        assert_eq!(cache.get(&txid(1), 6), Some(6));
+        // Below threshold and past the ttl
+        assert_eq!(cache.get(&txid(2), 6), None);
+    }
+
+    #[test]
+    fn test_unconfirmed_entries_served_within_ttl() {
+        let cache = ConfirmationCache::new(Duration::from_secs(60), 10);
+        cache.insert(txid(1), 0);
+        assert_eq!(cache.get(&txid(1), 6), Some(0));
+    }
+
+    #[test]
+    fn test_evicts_oldest_when_full() {
+        let cache = ConfirmationCache::new(Duration::from_secs(60), 2);
+        cache.insert(txid(1), 6);
+        cache.insert(txid(2), 6);
+        cache.insert(txid(3), 6);
+
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&txid(1), 6), None);
+        assert_eq!(cache.get(&txid(3), 6), Some(6));
+    }
+
+    #[test]
+    fn test_disabled_cache_stores_nothing() {
+        let cache = ConfirmationCache::disabled();
+        cache.insert(txid(1), 6);
+        assert!(cache.is_empty());
+    }
+}
@@ -0,0 +1,164 @@
+use bitcoin::{OutPoint, ScriptBuf, Txid};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// Result of checking a locked transaction's inputs for conflicting spends
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DoubleSpendStatus {
+    /// No conflicting spend of the transaction's inputs was found
+    None,
+    /// An input is spent by a conflicting transaction that is still in the mempool
+    AtRisk,
+    /// An input is spent by a conflicting transaction that has been mined
+    Conflicted,
+    /// The transaction was replaced (RBF) by a mempool transaction paying the same outputs
+    Replaced(Txid),
+}
+
+struct WatchedTx {
+    inputs: Vec<OutPoint>,
+    output_scripts: Vec<ScriptBuf>,
+    raw_tx: Vec<u8>,
+    seen_at: Instant,
+}
+
+/// Remembers the inputs and outputs of unconfirmed locked transactions, so they can still be
+/// checked for conflicting spends after the transaction itself has been dropped by the Bitcoin
+/// node.
+pub struct InputWatcher {
+    capacity: usize,
+    watched: Mutex<HashMap<Txid, WatchedTx>>,
+}
+
+impl InputWatcher {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            watched: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Starts watching the inputs of a transaction
+    pub fn watch(
+        &self,
+        txid: Txid,
+        inputs: Vec<OutPoint>,
+        output_scripts: Vec<ScriptBuf>,
+        raw_tx: Vec<u8>,
+    ) {
+        if self.capacity == 0 || inputs.is_empty() {
+            return;
+        }
+
+        let mut watched = self.watched.lock().unwrap();
+        if watched.len() >= self.capacity && !watched.contains_key(&txid) {
+            // Drop the transaction watched the longest to make room
+            if let Some(oldest) = watched
+                .iter()
+                .min_by_key(|(_, tx)| tx.seen_at)
+                .map(|(txid, _)| *txid)
+            {
+                watched.remove(&oldest);
+            }
+        }
+        watched.insert(
+            txid,
+            WatchedTx {
+                inputs,
+                output_scripts,
+                raw_tx,
+                seen_at: Instant::now(),
+            },
+        );
+    }
+
+    /// Stops watching a transaction, e.g. once it is confirmed
+    pub fn forget(&self, txid: &Txid) {
+        self.watched.lock().unwrap().remove(txid);
+    }
+
+    /// Returns the inputs of a watched transaction
+    pub fn inputs(&self, txid: &Txid) -> Option<Vec<OutPoint>> {
+        self.watched
+            .lock()
+            .unwrap()
+            .get(txid)
+            .map(|tx| tx.inputs.clone())
+    }
+
+    /// Returns the output scripts of a watched transaction
+    pub fn output_scripts(&self, txid: &Txid) -> Option<Vec<ScriptBuf>> {
+        self.watched
+            .lock()
+            .unwrap()
+            .get(txid)
+            .map(|tx| tx.output_scripts.clone())
+    }
+
+    /// Returns the serialized transaction of a watched transaction
+    pub fn raw_tx(&self, txid: &Txid) -> Option<Vec<u8>> {
+        self.watched
+            .lock()
+            .unwrap()
+            .get(txid)
+            .map(|tx| tx.raw_tx.clone())
+    }
+}
+
+/// Returns whether a conflicting transaction is an RBF replacement of the original, i.e. it still
+/// pays every output script of the original. Output values may differ, since a fee bump usually
+/// shrinks the change output.
+pub fn is_replacement(original_scripts: &[ScriptBuf], conflicting_scripts: &[ScriptBuf]) -> bool {
+    !original_scripts.is_empty()
+        && original_scripts
+            .iter()
+            .all(|script| conflicting_scripts.contains(script))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::hashes::Hash;
+
+    fn txid(byte: u8) -> Txid {
+        Txid::from_byte_array([byte; 32])
+    }
+
+    #[test]
+    fn test_watch_and_forget() {
+        let watcher = InputWatcher::new(10);
+        let inputs = vec![OutPoint::new(txid(9), 0), OutPoint::new(txid(9), 1)];
+
+        watcher.watch(txid(1), inputs.clone(), vec![ScriptBuf::new()], vec![]);
+        assert_eq!(watcher.inputs(&txid(1)), Some(inputs));
+
+        watcher.forget(&txid(1));
+        assert_eq!(watcher.inputs(&txid(1)), None);
+    }
+
+    #[test]
+    fn test_evicts_oldest_when_full() {
+        let watcher = InputWatcher::new(1);
+        watcher.watch(txid(1), vec![OutPoint::new(txid(9), 0)], vec![], vec![]);
+        watcher.watch(txid(2), vec![OutPoint::new(txid(9), 1)], vec![], vec![]);
+
+        assert_eq!(watcher.inputs(&txid(1)), None);
+        assert!(watcher.inputs(&txid(2)).is_some());
+    }
+
+    #[test]
+    fn test_is_replacement() {
+        let deposit = ScriptBuf::from_bytes(vec![0x51]);
+        let change = ScriptBuf::from_bytes(vec![0x52]);
+        let other = ScriptBuf::from_bytes(vec![0x53]);
+
+        let original = [deposit.clone(), change.clone()];
+        assert!(is_replacement(
+            &original,
+            &[change.clone(), deposit.clone()]
+        ));
+        assert!(!is_replacement(&original, &[deposit, other]));
+        assert!(!is_replacement(&[], &[change]));
+    }
+}
@@ -0,0 +1,121 @@
+//! Fan-out of committed lock changes to `SubscribeSlotEvents` subscribers
+
+use crate::service::status::subscriber_lagged_status;
+use futures::Stream;
+use sova_sentinel_proto::proto::SlotEvent;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tonic::Status;
+
+/// Events buffered for each subscriber. A subscriber further behind is disconnected.
+const EVENT_BUFFER: usize = 4096;
+
+/// Which events a subscriber receives
+#[derive(Debug, Clone, Default)]
+pub struct SlotEventFilter {
+    /// Only events of this namespace when set
+    pub namespace: Option<String>,
+    /// Only events of this contract when set
+    pub contract_address: Option<String>,
+}
+
+impl SlotEventFilter {
+    fn matches(&self, event: &SlotEvent) -> bool {
+        self.namespace
+            .as_ref()
+            .is_none_or(|namespace| *namespace == event.namespace)
+            && self
+                .contract_address
+                .as_ref()
+                .is_none_or(|contract_address| *contract_address == event.contract_address)
+    }
+}
+
+/// Broadcasts slot events to every subscriber
+#[derive(Debug, Clone)]
+pub struct SlotEvents {
+    sender: broadcast::Sender<SlotEvent>,
+}
+
+impl Default for SlotEvents {
+    fn default() -> Self {
+        Self {
+            sender: broadcast::channel(EVENT_BUFFER).0,
+        }
+    }
+}
+
+impl SlotEvents {
+    /// Sends events to the current subscribers. Only publish changes that were committed.
+    pub fn publish(&self, events: impl IntoIterator<Item = SlotEvent>) {
+        if self.sender.receiver_count() == 0 {
+            return;
+        }
+        for event in events {
+            // Fails only once every subscriber is gone
+            let _ = self.sender.send(event);
+        }
+    }
+
+    /// Streams the events published from now on that match `filter`. The stream ends with a
+    /// `SUBSCRIBER_LAGGED` error if the subscriber falls more than the buffer behind.
+    pub fn subscribe(
+        &self,
+        filter: SlotEventFilter,
+    ) -> impl Stream<Item = Result<SlotEvent, Status>> + Send + 'static {
+        let receiver = self.sender.subscribe();
+        futures::stream::unfold(Some((receiver, filter)), |state| async move {
+            let (mut receiver, filter) = state?;
+            loop {
+                match receiver.recv().await {
+                    Ok(event) if filter.matches(&event) => {
+                        return Some((Ok(event), Some((receiver, filter))))
+                    }
+                    Ok(_) => continue,
+                    Err(RecvError::Lagged(missed)) => {
+                        return Some((Err(subscriber_lagged_status(missed)), None))
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+    use sova_sentinel_proto::proto::slot_event;
+
+    fn event(contract_address: &str) -> SlotEvent {
+        SlotEvent {
+            kind: slot_event::Kind::Locked as i32,
+            contract_address: contract_address.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_filters_events() {
+        let events = SlotEvents::default();
+        let mut stream = Box::pin(events.subscribe(SlotEventFilter {
+            namespace: Some(String::new()),
+            contract_address: Some("0x456".to_string()),
+        }));
+
+        events.publish([event("0x123"), event("0x456")]);
+        let received = stream.next().await.unwrap().unwrap();
+        assert_eq!(received.contract_address, "0x456");
+    }
+
+    #[tokio::test]
+    async fn test_disconnects_lagging_subscribers() {
+        let events = SlotEvents::default();
+        let mut stream = Box::pin(events.subscribe(SlotEventFilter::default()));
+
+        events.publish((0..=EVENT_BUFFER).map(|_| event("0x123")));
+        let status = stream.next().await.unwrap().unwrap_err();
+        assert_eq!(status.code(), tonic::Code::Aborted);
+        assert!(stream.next().await.is_none());
+    }
+}
@@ -2,10 +2,49 @@ use sova_sentinel_proto::proto::{
     health_check_response::ServingStatus, health_server::Health, HealthCheckRequest,
     HealthCheckResponse,
 };
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use tonic::{Request, Response, Status};
 
+/// Path prefix of the health service, which is reachable without authentication or quotas so
+/// orchestrators can always probe the sentinel
+pub(crate) const HEALTH_PATH_PREFIX: &str = "/health.Health/";
+
+/// Serving status reported by the health service, shared with the tasks that can take the
+/// sentinel out of service. Clones share their status.
+#[derive(Debug, Clone)]
+pub struct HealthReporter {
+    serving: Arc<AtomicBool>,
+}
+
+impl Default for HealthReporter {
+    fn default() -> Self {
+        Self {
+            serving: Arc::new(AtomicBool::new(true)),
+        }
+    }
+}
+
+impl HealthReporter {
+    pub fn set_serving(&self, serving: bool) {
+        self.serving.store(serving, Ordering::Relaxed);
+    }
+
+    pub fn is_serving(&self) -> bool {
+        self.serving.load(Ordering::Relaxed)
+    }
+}
+
 #[derive(Default)]
-pub struct HealthService;
+pub struct HealthService {
+    reporter: HealthReporter,
+}
+
+impl HealthService {
+    pub fn new(reporter: HealthReporter) -> Self {
+        Self { reporter }
+    }
+}
 
 #[tonic::async_trait]
 impl Health for HealthService {
@@ -13,8 +52,13 @@ impl Health for HealthService {
         &self,
         _request: Request<HealthCheckRequest>,
     ) -> Result<Response<HealthCheckResponse>, Status> {
+        let status = if self.reporter.is_serving() {
+            ServingStatus::Serving
+        } else {
+            ServingStatus::NotServing
+        };
         Ok(Response::new(HealthCheckResponse {
-            status: ServingStatus::Serving as i32,
+            status: status as i32,
         }))
     }
 }
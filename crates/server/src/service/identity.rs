@@ -0,0 +1,268 @@
+use crate::config::IdentityConfig;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tonic::transport::server::{TcpConnectInfo, TlsConnectInfo};
+use tonic::Request;
+use tower::{Layer, Service};
+
+/// Authenticated caller of a request, recorded with every lock it makes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallerIdentity(pub String);
+
+/// Returns the authenticated caller of a request, if any
+pub fn caller_identity<T>(request: &Request<T>) -> Option<String> {
+    request
+        .extensions()
+        .get::<CallerIdentity>()
+        .map(|caller| caller.0.clone())
+}
+
+/// Layer identifying the caller of each request by the common name of the client certificate
+/// the listener verified, or one of the trusted proxies forwarded, or else by its API key.
+/// Requests from unknown callers go through without an identity.
+#[derive(Clone)]
+pub struct IdentityLayer {
+    config: Arc<IdentityConfig>,
+}
+
+impl IdentityLayer {
+    pub fn new(config: IdentityConfig) -> Self {
+        Self {
+            config: Arc::new(config),
+        }
+    }
+}
+
+impl<S> Layer<S> for IdentityLayer {
+    type Service = IdentityService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        IdentityService {
+            inner,
+            config: self.config.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct IdentityService<S> {
+    inner: S,
+    config: Arc<IdentityConfig>,
+}
+
+impl<S> IdentityService<S> {
+    fn identify<B>(&self, request: &hyper::Request<B>) -> Option<CallerIdentity> {
+        let header = |name: &str| {
+            request
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+        };
+        let extensions = request.extensions();
+        let tls = extensions.get::<TlsConnectInfo<TcpConnectInfo>>();
+        let certified = tls
+            .and_then(TlsConnectInfo::peer_certs)
+            .and_then(|certs| certs.first().and_then(|cert| common_name(cert)));
+        let remote_addr = extensions
+            .get::<TcpConnectInfo>()
+            .or(tls.map(TlsConnectInfo::get_ref))
+            .and_then(TcpConnectInfo::remote_addr);
+        let from_proxy = remote_addr.is_some_and(|addr| {
+            self.config
+                .trusted_proxies
+                .contains(&addr.ip().to_canonical())
+        });
+        let forwarded = self
+            .config
+            .client_cn_header
+            .as_deref()
+            .filter(|_| from_proxy)
+            .and_then(header)
+            .map(str::to_string);
+        certified
+            .or(forwarded)
+            .map(|common_name| format!("cn:{}", common_name))
+            .or_else(|| {
+                header(&self.config.api_key_header)
+                    .and_then(|api_key| self.config.api_keys.get(api_key))
+                    .map(|name| format!("key:{}", name))
+            })
+            .map(CallerIdentity)
+    }
+}
+
+/// Reads the last common name of a DER certificate's subject
+fn common_name(certificate: &[u8]) -> Option<String> {
+    const SEQUENCE: u8 = 0x30;
+    const SET: u8 = 0x31;
+    const OBJECT_IDENTIFIER: u8 = 0x06;
+    const COMMON_NAME: &[u8] = &[0x55, 0x04, 0x03];
+
+    let (certificate, _) = der_element(certificate, SEQUENCE)?;
+    let (mut tbs, _) = der_element(certificate, SEQUENCE)?;
+    // Skip the version, if present, the serial number, the signature algorithm, the issuer
+    // and the validity
+    if tbs.first() == Some(&0xa0) {
+        tbs = der_any(tbs)?.2;
+    }
+    for _ in 0..4 {
+        tbs = der_any(tbs)?.2;
+    }
+    let (mut subject, _) = der_element(tbs, SEQUENCE)?;
+    let mut name = None;
+    while !subject.is_empty() {
+        let (mut set, rest) = der_element(subject, SET)?;
+        subject = rest;
+        while !set.is_empty() {
+            let (attribute, rest) = der_element(set, SEQUENCE)?;
+            set = rest;
+            let (oid, value) = der_element(attribute, OBJECT_IDENTIFIER)?;
+            if oid == COMMON_NAME {
+                name = std::str::from_utf8(der_any(value)?.1).ok();
+            }
+        }
+    }
+    name.map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+}
+
+/// Splits the DER element with the `expected` tag off the front of `input`
+fn der_element(input: &[u8], expected: u8) -> Option<(&[u8], &[u8])> {
+    der_any(input)
+        .filter(|(tag, ..)| *tag == expected)
+        .map(|(_, contents, rest)| (contents, rest))
+}
+
+/// Splits the DER element at the front of `input` into its tag, contents and what follows it
+fn der_any(input: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, input) = input.split_first()?;
+    let (&first, mut input) = input.split_first()?;
+    let len = if first < 0x80 {
+        usize::from(first)
+    } else {
+        let octets = usize::from(first & 0x7f);
+        if octets == 0 || octets > std::mem::size_of::<usize>() || input.len() < octets {
+            return None;
+        }
+        let (len, rest) = input.split_at(octets);
+        input = rest;
+        len.iter()
+            .fold(0usize, |len, &octet| len << 8 | usize::from(octet))
+    };
+    (input.len() >= len).then(|| {
+        let (contents, rest) = input.split_at(len);
+        (tag, contents, rest)
+    })
+}
+
+impl<S, B> Service<hyper::Request<B>> for IdentityService<S>
+where
+    S: Service<hyper::Request<B>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: hyper::Request<B>) -> Self::Future {
+        // Only the layer vouches for a caller, whatever the request carried already
+        request.extensions_mut().remove::<CallerIdentity>();
+        if let Some(caller) = self.identify(&request) {
+            request.extensions_mut().insert(caller);
+        }
+        self.inner.call(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::net::SocketAddr;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_identifies_caller() {
+        let config = IdentityConfig {
+            api_key_header: "x-api-key".to_string(),
+            api_keys: HashMap::from([("secret".to_string(), "indexer".to_string())]),
+            client_cn_header: Some("x-client-cn".to_string()),
+            trusted_proxies: vec!["10.0.0.1".parse().unwrap()],
+            max_active_locks: None,
+            active_lock_limits: HashMap::new(),
+        };
+        let service = IdentityLayer::new(config).layer(tower::service_fn(
+            |request: hyper::Request<()>| async move {
+                Ok::<_, std::convert::Infallible>(
+                    request.extensions().get::<CallerIdentity>().cloned(),
+                )
+            },
+        ));
+        let caller_from = |remote_addr: &str, headers: &[(&str, &str)]| {
+            let mut request = hyper::Request::builder();
+            for (name, value) in headers {
+                request = request.header(*name, *value);
+            }
+            let service = service.clone();
+            let mut request = request.body(()).unwrap();
+            request.extensions_mut().insert(TcpConnectInfo {
+                local_addr: None,
+                remote_addr: Some(remote_addr.parse::<SocketAddr>().unwrap()),
+            });
+            async move {
+                service
+                    .oneshot(request)
+                    .await
+                    .unwrap()
+                    .map(|caller| caller.0)
+            }
+        };
+
+        let caller = |headers| caller_from("10.0.0.1:40000", headers);
+
+        assert_eq!(
+            caller(&[("x-api-key", "secret")]).await.as_deref(),
+            Some("key:indexer")
+        );
+        // The certificate's common name takes precedence over the API key
+        assert_eq!(
+            caller(&[("x-api-key", "secret"), ("x-client-cn", "sequencer-1")])
+                .await
+                .as_deref(),
+            Some("cn:sequencer-1")
+        );
+        // Only the trusted proxy can forward a common name
+        assert_eq!(
+            caller_from(
+                "10.0.0.2:40000",
+                &[("x-api-key", "secret"), ("x-client-cn", "sequencer-1")]
+            )
+            .await
+            .as_deref(),
+            Some("key:indexer")
+        );
+        assert_eq!(
+            caller_from("[::ffff:10.0.0.1]:40000", &[("x-client-cn", "sequencer-1")])
+                .await
+                .as_deref(),
+            Some("cn:sequencer-1")
+        );
+        assert_eq!(caller(&[("x-api-key", "other")]).await, None);
+        assert_eq!(caller(&[("x-client-cn", " ")]).await, None);
+        assert_eq!(caller(&[]).await, None);
+    }
+
+    #[test]
+    fn test_reads_certificate_common_name() {
+        let certificate = include_bytes!("../../testdata/tls/client.der");
+        assert_eq!(common_name(certificate).as_deref(), Some("sequencer"));
+        assert_eq!(common_name(&certificate[..100]), None);
+        assert_eq!(common_name(&[]), None);
+    }
+}
@@ -0,0 +1,297 @@
+use crate::journal::Journal;
+use crate::service::recording::Recorder;
+use crate::service::status::journal_failed_status;
+use prost::Message;
+use sova_sentinel_proto::proto::{
+    slot_lock_service_server::SlotLockService, BatchGetSlotStatusRequest,
+    BatchGetSlotStatusResponse, BatchLockSlotRequest, BatchLockSlotResponse,
+    BatchUnlockSlotRequest, BatchUnlockSlotResponse, CommitLockRequest, CommitLockResponse,
+    FinalizeBlockRequest, FinalizeBlockResponse, GetLockCommitmentRequest,
+    GetLockCommitmentResponse, GetLockInclusionProofRequest, GetLockInclusionProofResponse,
+    GetPublicKeyRequest, GetPublicKeyResponse, GetSentinelInfoRequest, GetSentinelInfoResponse,
+    GetServerInfoRequest, GetServerInfoResponse, GetSlotStatusRequest, GetSlotStatusResponse,
+    LockSlotRequest, LockSlotResponse, PrepareLockRequest, PrepareLockResponse,
+    ReplaceLockTxRequest, ReplaceLockTxResponse, SubscribeSlotEventsRequest, UnlockByTxidRequest,
+    UnlockByTxidResponse,
+};
+use std::future::Future;
+use tonic::{Code, Request, Response, Status};
+
+const LOCK_SLOT: &str = "/slot_lock.SlotLockService/LockSlot";
+const GET_SLOT_STATUS: &str = "/slot_lock.SlotLockService/GetSlotStatus";
+const BATCH_LOCK_SLOT: &str = "/slot_lock.SlotLockService/BatchLockSlot";
+const BATCH_GET_SLOT_STATUS: &str = "/slot_lock.SlotLockService/BatchGetSlotStatus";
+const BATCH_UNLOCK_SLOT: &str = "/slot_lock.SlotLockService/BatchUnlockSlot";
+const REPLACE_LOCK_TX: &str = "/slot_lock.SlotLockService/ReplaceLockTx";
+const UNLOCK_BY_TXID: &str = "/slot_lock.SlotLockService/UnlockByTxid";
+const PREPARE_LOCK: &str = "/slot_lock.SlotLockService/PrepareLock";
+const COMMIT_LOCK: &str = "/slot_lock.SlotLockService/CommitLock";
+const FINALIZE_BLOCK: &str = "/slot_lock.SlotLockService/FinalizeBlock";
+
+/// SlotLock service that writes the state-changing requests it serves to a journal, and traces
+/// them when recording
+pub struct JournaledSlotLockService<S> {
+    inner: S,
+    journal: Option<Journal>,
+    recorder: Option<Recorder>,
+}
+
+impl<S: SlotLockService> JournaledSlotLockService<S> {
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            journal: None,
+            recorder: None,
+        }
+    }
+
+    /// Sets the journal requests are written to. Without one, requests are passed through.
+    pub fn with_journal(mut self, journal: Journal) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    /// Sets the recorder requests are traced by
+    pub fn with_recorder(mut self, recorder: Recorder) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    /// Handles a request, tracing it if recording
+    async fn serve<T, R, F, Fut>(
+        &self,
+        method: &str,
+        request: Request<T>,
+        handle: F,
+    ) -> Result<Response<R>, Status>
+    where
+        T: Message,
+        R: Message,
+        F: FnOnce(Request<T>) -> Fut,
+        Fut: Future<Output = Result<Response<R>, Status>>,
+    {
+        match &self.recorder {
+            Some(recorder) => recorder.record(method, request, handle).await,
+            None => handle(request).await,
+        }
+    }
+
+    /// Writes a lock, unlock or replacement request to the journal before handling it, and its
+    /// outcome after. Fails the request if it can't be written.
+    async fn write<T, R, F, Fut>(
+        &self,
+        method: &str,
+        request: Request<T>,
+        handle: F,
+    ) -> Result<Response<R>, Status>
+    where
+        T: Message,
+        R: Message,
+        F: FnOnce(Request<T>) -> Fut,
+        Fut: Future<Output = Result<Response<R>, Status>>,
+    {
+        let Some(journal) = &self.journal else {
+            return self.serve(method, request, handle).await;
+        };
+        let seq = journal
+            .record_request(method, request.get_ref().encode_to_vec())
+            .map_err(journal_failed_status)?;
+        let result = self.serve(method, request, handle).await;
+        let (code, message) = outcome(&result);
+        if let Err(e) = journal.record_outcome(seq, code, message) {
+            tracing::error!("Failed to journal the outcome of request {}: {:#}", seq, e);
+        }
+        result
+    }
+
+    /// Handles a status query, writing it to the journal with its outcome if it unlocked slots
+    async fn query<T, R, F, Fut>(
+        &self,
+        method: &str,
+        request: Request<T>,
+        handle: F,
+    ) -> Result<Response<R>, Status>
+    where
+        T: Message,
+        R: Message,
+        F: FnOnce(Request<T>) -> Fut,
+        Fut: Future<Output = Result<Response<R>, Status>>,
+    {
+        let Some(journal) = &self.journal else {
+            return self.serve(method, request, handle).await;
+        };
+        let payload = request.get_ref().encode_to_vec();
+        let result = self.serve(method, request, handle).await;
+        let (code, message) = outcome(&result);
+        if let Err(e) = journal.record_if_changed(method, payload, code, message) {
+            tracing::error!("Failed to journal a {} request: {:#}", method, e);
+        }
+        result
+    }
+}
+
+fn outcome<R>(result: &Result<Response<R>, Status>) -> (Code, &str) {
+    match result {
+        Ok(_) => (Code::Ok, ""),
+        Err(status) => (status.code(), status.message()),
+    }
+}
+
+#[tonic::async_trait]
+impl<S: SlotLockService> SlotLockService for JournaledSlotLockService<S> {
+    type SubscribeSlotEventsStream = S::SubscribeSlotEventsStream;
+
+    async fn lock_slot(
+        &self,
+        request: Request<LockSlotRequest>,
+    ) -> Result<Response<LockSlotResponse>, Status> {
+        self.write(LOCK_SLOT, request, |request| self.inner.lock_slot(request))
+            .await
+    }
+
+    async fn get_slot_status(
+        &self,
+        request: Request<GetSlotStatusRequest>,
+    ) -> Result<Response<GetSlotStatusResponse>, Status> {
+        self.query(GET_SLOT_STATUS, request, |request| {
+            self.inner.get_slot_status(request)
+        })
+        .await
+    }
+
+    async fn batch_lock_slot(
+        &self,
+        request: Request<BatchLockSlotRequest>,
+    ) -> Result<Response<BatchLockSlotResponse>, Status> {
+        self.write(BATCH_LOCK_SLOT, request, |request| {
+            self.inner.batch_lock_slot(request)
+        })
+        .await
+    }
+
+    async fn batch_get_slot_status(
+        &self,
+        request: Request<BatchGetSlotStatusRequest>,
+    ) -> Result<Response<BatchGetSlotStatusResponse>, Status> {
+        self.query(BATCH_GET_SLOT_STATUS, request, |request| {
+            self.inner.batch_get_slot_status(request)
+        })
+        .await
+    }
+
+    async fn batch_unlock_slot(
+        &self,
+        request: Request<BatchUnlockSlotRequest>,
+    ) -> Result<Response<BatchUnlockSlotResponse>, Status> {
+        self.write(BATCH_UNLOCK_SLOT, request, |request| {
+            self.inner.batch_unlock_slot(request)
+        })
+        .await
+    }
+
+    async fn replace_lock_tx(
+        &self,
+        request: Request<ReplaceLockTxRequest>,
+    ) -> Result<Response<ReplaceLockTxResponse>, Status> {
+        self.write(REPLACE_LOCK_TX, request, |request| {
+            self.inner.replace_lock_tx(request)
+        })
+        .await
+    }
+
+    async fn unlock_by_txid(
+        &self,
+        request: Request<UnlockByTxidRequest>,
+    ) -> Result<Response<UnlockByTxidResponse>, Status> {
+        self.write(UNLOCK_BY_TXID, request, |request| {
+            self.inner.unlock_by_txid(request)
+        })
+        .await
+    }
+
+    async fn prepare_lock(
+        &self,
+        request: Request<PrepareLockRequest>,
+    ) -> Result<Response<PrepareLockResponse>, Status> {
+        self.write(PREPARE_LOCK, request, |request| {
+            self.inner.prepare_lock(request)
+        })
+        .await
+    }
+
+    async fn commit_lock(
+        &self,
+        request: Request<CommitLockRequest>,
+    ) -> Result<Response<CommitLockResponse>, Status> {
+        self.write(COMMIT_LOCK, request, |request| {
+            self.inner.commit_lock(request)
+        })
+        .await
+    }
+
+    async fn finalize_block(
+        &self,
+        request: Request<FinalizeBlockRequest>,
+    ) -> Result<Response<FinalizeBlockResponse>, Status> {
+        self.write(FINALIZE_BLOCK, request, |request| {
+            self.inner.finalize_block(request)
+        })
+        .await
+    }
+
+    async fn peek_slot_status(
+        &self,
+        request: Request<GetSlotStatusRequest>,
+    ) -> Result<Response<GetSlotStatusResponse>, Status> {
+        self.inner.peek_slot_status(request).await
+    }
+
+    async fn batch_peek_slot_status(
+        &self,
+        request: Request<BatchGetSlotStatusRequest>,
+    ) -> Result<Response<BatchGetSlotStatusResponse>, Status> {
+        self.inner.batch_peek_slot_status(request).await
+    }
+
+    async fn get_server_info(
+        &self,
+        request: Request<GetServerInfoRequest>,
+    ) -> Result<Response<GetServerInfoResponse>, Status> {
+        self.inner.get_server_info(request).await
+    }
+
+    async fn get_sentinel_info(
+        &self,
+        request: Request<GetSentinelInfoRequest>,
+    ) -> Result<Response<GetSentinelInfoResponse>, Status> {
+        self.inner.get_sentinel_info(request).await
+    }
+
+    async fn subscribe_slot_events(
+        &self,
+        request: Request<SubscribeSlotEventsRequest>,
+    ) -> Result<Response<Self::SubscribeSlotEventsStream>, Status> {
+        self.inner.subscribe_slot_events(request).await
+    }
+
+    async fn get_public_key(
+        &self,
+        request: Request<GetPublicKeyRequest>,
+    ) -> Result<Response<GetPublicKeyResponse>, Status> {
+        self.inner.get_public_key(request).await
+    }
+
+    async fn get_lock_commitment(
+        &self,
+        request: Request<GetLockCommitmentRequest>,
+    ) -> Result<Response<GetLockCommitmentResponse>, Status> {
+        self.inner.get_lock_commitment(request).await
+    }
+
+    async fn get_lock_inclusion_proof(
+        &self,
+        request: Request<GetLockInclusionProofRequest>,
+    ) -> Result<Response<GetLockInclusionProofResponse>, Status> {
+        self.inner.get_lock_inclusion_proof(request).await
+    }
+}
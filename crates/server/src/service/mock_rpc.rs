@@ -0,0 +1,257 @@
+use crate::service::bitcoin::{BitcoinRpcClient, ChainInfo};
+use async_trait::async_trait;
+use bitcoin::block::Header;
+use bitcoin::consensus::deserialize;
+use bitcoin::hashes::Hash;
+use bitcoin::{BlockHash, Network, OutPoint, Transaction, Txid, Wtxid};
+use bitcoincore_rpc::json::GetRawTransactionResult;
+use bitcoincore_rpc::{jsonrpc, Error};
+use std::collections::HashMap;
+use std::hash::{BuildHasher, RandomState};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// Height the mock chain's tip is reported at
+const MOCK_TIP_HEIGHT: u64 = 150;
+
+/// When transactions confirm on the mock node
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MockConfirmations {
+    /// A transaction stays in the mempool for its first `n` lookups, then has exactly enough
+    /// confirmations to count as confirmed
+    AfterQueries(u32),
+    /// Transactions stay in the mempool forever
+    Never,
+}
+
+impl FromStr for MockConfirmations {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "never" => Ok(Self::Never),
+            other => other
+                .parse()
+                .map(Self::AfterQueries)
+                .map_err(|_| anyhow::anyhow!("Unsupported mock confirmations: {}", other)),
+        }
+    }
+}
+
+/// In-process fake of a Bitcoin node, for running the sentinel locally without one. It knows
+/// every transaction it is asked about, confirms them as configured and can fail calls at
+/// random like an unreachable node. Transactions have no inputs, so they are never
+/// double-spent.
+pub struct MockRpcClient {
+    confirmation_threshold: u32,
+    confirmations: MockConfirmations,
+    failure_rate: f64,
+    random: RandomState,
+    calls: AtomicU64,
+    /// Lookups of each transaction so far
+    queries: Mutex<HashMap<Txid, u32>>,
+}
+
+impl MockRpcClient {
+    pub fn new(confirmation_threshold: u32, confirmations: MockConfirmations) -> Self {
+        Self {
+            confirmation_threshold,
+            confirmations,
+            failure_rate: 0.0,
+            random: RandomState::new(),
+            calls: AtomicU64::new(0),
+            queries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Fails this fraction of calls, between 0 and 1, as if the node were unreachable
+    pub fn with_failure_rate(mut self, failure_rate: f64) -> Self {
+        self.failure_rate = failure_rate;
+        self
+    }
+
+    /// Fails the call at random, at the failure rate
+    fn maybe_fail(&self) -> Result<(), Error> {
+        let call = self.calls.fetch_add(1, Ordering::Relaxed);
+        if (self.random.hash_one(call) as f64 / u64::MAX as f64) < self.failure_rate {
+            return Err(Error::JsonRpc(jsonrpc::error::Error::Transport(Box::new(
+                std::io::Error::new(
+                    std::io::ErrorKind::ConnectionRefused,
+                    "Mock Bitcoin node failure",
+                ),
+            ))));
+        }
+        Ok(())
+    }
+
+    fn is_confirmed(&self, queries: u32) -> bool {
+        match self.confirmations {
+            MockConfirmations::AfterQueries(n) => queries > n,
+            MockConfirmations::Never => false,
+        }
+    }
+}
+
+fn unsupported(method: &str) -> Error {
+    Error::JsonRpc(jsonrpc::error::Error::Rpc(jsonrpc::error::RpcError {
+        code: -32601,
+        message: format!("{} is not supported by the mock Bitcoin node", method),
+        data: None,
+    }))
+}
+
+#[async_trait]
+impl BitcoinRpcClient for MockRpcClient {
+    async fn get_raw_transaction_info(
+        &self,
+        txid: &Txid,
+    ) -> Result<GetRawTransactionResult, Error> {
+        self.maybe_fail()?;
+        let queries = {
+            let mut queries = self.queries.lock().unwrap();
+            let count = queries.entry(*txid).or_default();
+            *count += 1;
+            *count
+        };
+        let confirmed = self.is_confirmed(queries);
+        Ok(GetRawTransactionResult {
+            in_active_chain: None,
+            hex: Vec::new(),
+            txid: *txid,
+            hash: Wtxid::from_raw_hash(txid.to_raw_hash()),
+            size: 0,
+            vsize: 0,
+            version: 2,
+            locktime: 0,
+            vin: Vec::new(),
+            vout: Vec::new(),
+            blockhash: confirmed.then(BlockHash::all_zeros),
+            confirmations: confirmed.then_some(self.confirmation_threshold),
+            time: None,
+            blocktime: None,
+        })
+    }
+
+    async fn is_output_unspent(
+        &self,
+        _outpoint: &OutPoint,
+        _include_mempool: bool,
+    ) -> Result<bool, Error> {
+        self.maybe_fail()?;
+        Ok(true)
+    }
+
+    async fn get_tx_spending_prevout(&self, _outpoint: &OutPoint) -> Result<Option<Txid>, Error> {
+        self.maybe_fail()?;
+        Ok(None)
+    }
+
+    /// Transactions are in the mempool until they confirm
+    async fn is_in_mempool(&self, txid: &Txid) -> Result<bool, Error> {
+        self.maybe_fail()?;
+        let queries = self
+            .queries
+            .lock()
+            .unwrap()
+            .get(txid)
+            .copied()
+            .unwrap_or_default();
+        Ok(!self.is_confirmed(queries))
+    }
+
+    async fn test_mempool_accept(&self, _raw_tx: &[u8]) -> Result<Option<String>, Error> {
+        self.maybe_fail()?;
+        Ok(None)
+    }
+
+    async fn send_raw_transaction(&self, raw_tx: &[u8]) -> Result<Txid, Error> {
+        self.maybe_fail()?;
+        let tx: Transaction = deserialize(raw_tx).map_err(|_| {
+            Error::JsonRpc(jsonrpc::error::Error::Rpc(jsonrpc::error::RpcError {
+                code: -22,
+                message: "TX decode failed".to_string(),
+                data: None,
+            }))
+        })?;
+        Ok(tx.compute_txid())
+    }
+
+    async fn get_chain_info(&self) -> Result<ChainInfo, Error> {
+        self.maybe_fail()?;
+        Ok(ChainInfo {
+            network: Network::Regtest,
+            tip_height: MOCK_TIP_HEIGHT,
+        })
+    }
+
+    async fn get_coinbase_txid(&self, _height: u64) -> Result<Txid, Error> {
+        self.maybe_fail()?;
+        Ok(Txid::all_zeros())
+    }
+
+    async fn get_block_header(&self, _height: u64) -> Result<Header, Error> {
+        Err(unsupported("getblockheader"))
+    }
+
+    async fn get_tx_out_proof(
+        &self,
+        _txid: &Txid,
+        _block_hash: &BlockHash,
+    ) -> Result<Vec<u8>, Error> {
+        Err(unsupported("gettxoutproof"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::{BitcoinRpcService, BitcoinRpcServiceAPI, MempoolStatus};
+    use std::sync::Arc;
+
+    const TXID: &str = "abababababababababababababababababababababababababababababababab";
+
+    #[tokio::test]
+    async fn test_confirms_after_queries() -> anyhow::Result<()> {
+        let client = MockRpcClient::new(6, MockConfirmations::AfterQueries(2));
+        let txid = Txid::from_str(TXID)?;
+        for _ in 0..2 {
+            let tx = client.get_raw_transaction_info(&txid).await?;
+            assert_eq!(tx.confirmations, None);
+            assert!(client.is_in_mempool(&txid).await?);
+        }
+        assert_eq!(
+            client.get_raw_transaction_info(&txid).await?.confirmations,
+            Some(6)
+        );
+        assert!(!client.is_in_mempool(&txid).await?);
+
+        let service = BitcoinRpcService::new(
+            Arc::new(MockRpcClient::new(6, MockConfirmations::Never)),
+            6,
+            0,
+        );
+        assert_eq!(service.get_confirmations(TXID).await?, 0);
+        assert_eq!(
+            service.mempool_status(TXID).await?,
+            MempoolStatus::InMempool
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_fails_at_failure_rate() -> anyhow::Result<()> {
+        let client =
+            MockRpcClient::new(6, MockConfirmations::AfterQueries(0)).with_failure_rate(1.0);
+        assert!(client.get_chain_info().await.is_err());
+
+        let client = client.with_failure_rate(0.0);
+        assert_eq!(client.get_chain_info().await?.network, Network::Regtest);
+        assert_eq!(
+            "never".parse::<MockConfirmations>()?,
+            MockConfirmations::Never
+        );
+        assert!("soon".parse::<MockConfirmations>().is_err());
+        Ok(())
+    }
+}
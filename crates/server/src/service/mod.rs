@@ -1,10 +1,72 @@
+use crate::proto::slot_lock_service_server::{SlotLockService, SlotLockServiceServer};
+use std::time::Duration;
+use tonic::codec::CompressionEncoding;
+
+mod admin;
+mod auth;
 mod bitcoin;
+mod circuit_breaker;
+mod compact_filters;
+mod confirmation_cache;
+mod double_spend;
+mod events;
 mod health;
+mod identity;
+mod journal;
+mod mock_rpc;
+mod rate_limit;
+mod rebroadcast;
+mod recording;
+mod replication;
+mod retry;
+mod revert_executor;
 mod slot_lock;
+mod status;
+mod thresholds;
 
+pub use admin::AdminServiceImpl;
+pub use auth::{AuthLayer, AuthToken};
 pub use bitcoin::{
-    BitcoinCoreRpcClient, BitcoinRpcClient, BitcoinRpcService, BitcoinRpcServiceAPI,
-    ExternalRpcClient,
+    network_name, normalize_txid, parse_network_name, BitcoinCoreRpcClient, BitcoinRpcClient,
+    BitcoinRpcError, BitcoinRpcService, BitcoinRpcServiceAPI, ChainInfo, ExternalRpcClient,
+    MempoolStatus,
+};
+pub use circuit_breaker::CircuitBreaker;
+pub use compact_filters::CompactFilterClient;
+pub use confirmation_cache::ConfirmationCache;
+pub use double_spend::{DoubleSpendStatus, InputWatcher};
+pub use events::{SlotEventFilter, SlotEvents};
+pub use health::{HealthReporter, HealthService};
+pub use identity::{caller_identity, CallerIdentity, IdentityLayer};
+pub use journal::JournaledSlotLockService;
+pub use mock_rpc::{MockConfirmations, MockRpcClient};
+pub use rate_limit::{RateLimitLayer, RateLimiter};
+pub use rebroadcast::Rebroadcaster;
+pub use recording::{
+    describe_request, read_traces, replay_recording, Recorder, RecordingBitcoinService,
+    ReplayBitcoinService, ReplayMismatch, ReplayReport,
+};
+pub(crate) use replication::lock_change;
+pub use replication::{Replication, Replicator};
+pub use retry::{RetryPolicy, RetryStrategy};
+pub use revert_executor::{
+    GrpcRevertExecutor, HttpRevertExecutor, RevertDispatcher, RevertExecutor,
 };
-pub use health::HealthService;
-pub use slot_lock::SlotLockServiceImpl;
+pub use slot_lock::{
+    SlotLockServiceImpl, StaleBtcBlock, StaleBtcBlockPolicy, DEFAULT_MAX_BATCH_SIZE,
+    DEFAULT_MAX_LOGGED_SLOTS, DEFAULT_RESERVATION_TIMEOUT,
+};
+pub use thresholds::ContractThresholds;
+
+/// Time after which a call is cancelled, unless its caller set a shorter deadline
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Serves a slot lock service. Clients may compress requests, and get responses compressed the
+/// way they accept.
+pub fn slot_lock_server<S: SlotLockService>(service: S) -> SlotLockServiceServer<S> {
+    SlotLockServiceServer::new(service)
+        .accept_compressed(CompressionEncoding::Gzip)
+        .accept_compressed(CompressionEncoding::Zstd)
+        .send_compressed(CompressionEncoding::Gzip)
+        .send_compressed(CompressionEncoding::Zstd)
+}
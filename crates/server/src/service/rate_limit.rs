@@ -0,0 +1,498 @@
+use crate::config::{RateLimitConfig, RateLimitQuota};
+use crate::service::health::HEALTH_PATH_PREFIX;
+use crate::service::status::rate_limited_status;
+use futures::future::BoxFuture;
+use hyper::body::{Body, Frame, SizeHint};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tonic::body::BoxBody;
+use tonic::transport::server::{TcpConnectInfo, TlsConnectInfo};
+use tower::{Layer, Service};
+
+/// Peers tracked before idle ones are forgotten. IP addresses seen while none can be forgotten
+/// share the quota of [`Peer::Unknown`].
+const MAX_TRACKED_PEERS: usize = 10_000;
+/// Suggested retry delay for a peer at its concurrency limit
+const CONCURRENCY_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// Identity requests are limited by
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Peer {
+    ApiKey(String),
+    Ip(IpAddr),
+    /// Connections without a remote address, and IP addresses over the tracking limit, which
+    /// share one quota
+    Unknown,
+}
+
+#[derive(Debug)]
+struct PeerState {
+    tokens: f64,
+    refilled_at: Instant,
+    in_flight: u32,
+}
+
+impl PeerState {
+    /// Adds the tokens earned since the last refill, up to the burst
+    fn refill(&mut self, quota: &RateLimitQuota, now: Instant) {
+        if let Some(rate) = quota.requests_per_sec {
+            let earned = now.duration_since(self.refilled_at).as_secs_f64() * f64::from(rate);
+            self.tokens = (self.tokens + earned).min(burst(quota));
+        }
+        self.refilled_at = now;
+    }
+
+    fn is_idle(&self, quota: &RateLimitQuota) -> bool {
+        self.in_flight == 0 && (quota.requests_per_sec.is_none() || self.tokens >= burst(quota))
+    }
+}
+
+fn burst(quota: &RateLimitQuota) -> f64 {
+    f64::from(quota.burst.or(quota.requests_per_sec).unwrap_or(0))
+}
+
+/// Why a request was turned away, and when the peer can retry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Rejection {
+    quota: &'static str,
+    retry_after: Duration,
+}
+
+/// Per-peer request quotas. Each peer gets a token bucket of `requests_per_sec` refilling up to
+/// `burst`, and at most `max_concurrent` requests in flight.
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    peers: Mutex<HashMap<Peer, PeerState>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            peers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Identifies the sender of a request by its API key, when it has a quota of its own, or by
+    /// its IP address
+    fn peer<B>(&self, request: &hyper::Request<B>) -> Peer {
+        let api_key = request
+            .headers()
+            .get(self.config.api_key_header.as_str())
+            .and_then(|value| value.to_str().ok())
+            .filter(|api_key| self.config.api_keys.contains_key(*api_key));
+        if let Some(api_key) = api_key {
+            return Peer::ApiKey(api_key.to_string());
+        }
+        let extensions = request.extensions();
+        extensions
+            .get::<TcpConnectInfo>()
+            .or_else(|| {
+                extensions
+                    .get::<TlsConnectInfo<TcpConnectInfo>>()
+                    .map(TlsConnectInfo::get_ref)
+            })
+            .and_then(TcpConnectInfo::remote_addr)
+            .map_or(Peer::Unknown, |addr| Peer::Ip(addr.ip()))
+    }
+
+    fn quota(&self, peer: &Peer) -> RateLimitQuota {
+        match peer {
+            Peer::ApiKey(api_key) => self.config.api_keys[api_key],
+            _ => self.config.default_quota(),
+        }
+    }
+
+    /// Admits a request from `peer` at `now`, returning a permit that holds its concurrency slot
+    /// until dropped
+    fn acquire(self: &Arc<Self>, peer: Peer, now: Instant) -> Result<Permit, Rejection> {
+        let mut peers = self.peers.lock().unwrap();
+        if peers.len() >= MAX_TRACKED_PEERS && !peers.contains_key(&peer) {
+            peers.retain(|peer, state| {
+                let quota = self.quota(peer);
+                state.refill(&quota, now);
+                !state.is_idle(&quota)
+            });
+        }
+        // API keys are bounded by the config, IP addresses aren't
+        let peer = match peer {
+            Peer::Ip(_) if peers.len() >= MAX_TRACKED_PEERS && !peers.contains_key(&peer) => {
+                Peer::Unknown
+            }
+            peer => peer,
+        };
+        let quota = self.quota(&peer);
+        let state = peers.entry(peer.clone()).or_insert_with(|| PeerState {
+            tokens: burst(&quota),
+            refilled_at: now,
+            in_flight: 0,
+        });
+        state.refill(&quota, now);
+
+        if quota
+            .max_concurrent
+            .is_some_and(|max_concurrent| state.in_flight >= max_concurrent)
+        {
+            return Err(Rejection {
+                quota: "max_concurrent",
+                retry_after: CONCURRENCY_RETRY_DELAY,
+            });
+        }
+        if let Some(rate) = quota.requests_per_sec {
+            if state.tokens < 1.0 {
+                return Err(Rejection {
+                    quota: "requests_per_sec",
+                    retry_after: Duration::from_secs_f64((1.0 - state.tokens) / f64::from(rate)),
+                });
+            }
+            state.tokens -= 1.0;
+        }
+        state.in_flight += 1;
+        Ok(Permit {
+            limiter: self.clone(),
+            peer,
+        })
+    }
+}
+
+/// Concurrency slot of an admitted request
+struct Permit {
+    limiter: Arc<RateLimiter>,
+    peer: Peer,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        let mut peers = self.limiter.peers.lock().unwrap();
+        if let Some(state) = peers.get_mut(&self.peer) {
+            state.in_flight = state.in_flight.saturating_sub(1);
+        }
+    }
+}
+
+/// Response body holding the concurrency slot of its request until the body ends, so streaming
+/// responses count as in flight for as long as they stream
+struct PermitBody {
+    inner: BoxBody,
+    permit: Option<Permit>,
+}
+
+impl Body for PermitBody {
+    type Data = <BoxBody as Body>::Data;
+    type Error = <BoxBody as Body>::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.get_mut();
+        let frame = Pin::new(&mut this.inner).poll_frame(cx);
+        if matches!(frame, Poll::Ready(None)) {
+            this.permit = None;
+        }
+        frame
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+/// Layer rejecting requests over their peer's quota with `RESOURCE_EXHAUSTED`
+#[derive(Clone)]
+pub struct RateLimitLayer {
+    limiter: Arc<RateLimiter>,
+}
+
+impl RateLimitLayer {
+    pub fn new(limiter: Arc<RateLimiter>) -> Self {
+        Self { limiter }
+    }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimitService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimitService {
+            inner,
+            limiter: self.limiter.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RateLimitService<S> {
+    inner: S,
+    limiter: Arc<RateLimiter>,
+}
+
+impl<S, B> Service<hyper::Request<B>> for RateLimitService<S>
+where
+    S: Service<hyper::Request<B>, Response = hyper::Response<BoxBody>>,
+    S::Future: Send + 'static,
+    S::Error: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: hyper::Request<B>) -> Self::Future {
+        if request.uri().path().starts_with(HEALTH_PATH_PREFIX) {
+            return Box::pin(self.inner.call(request));
+        }
+        let peer = self.limiter.peer(&request);
+        match self.limiter.acquire(peer.clone(), Instant::now()) {
+            Ok(permit) => {
+                let response = self.inner.call(request);
+                Box::pin(async move {
+                    let response = response.await?;
+                    Ok(response.map(|inner| {
+                        tonic::body::boxed(PermitBody {
+                            inner,
+                            permit: Some(permit),
+                        })
+                    }))
+                })
+            }
+            Err(rejection) => {
+                tracing::debug!(
+                    "Rejected {} from {:?}: {} quota exceeded",
+                    request.uri().path(),
+                    peer,
+                    rejection.quota
+                );
+                let status = rate_limited_status(rejection.quota, rejection.retry_after);
+                Box::pin(std::future::ready(Ok(status.into_http())))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::SocketAddr;
+
+    fn limiter(quota: RateLimitQuota, api_keys: &[(&str, RateLimitQuota)]) -> Arc<RateLimiter> {
+        Arc::new(RateLimiter::new(RateLimitConfig {
+            requests_per_sec: quota.requests_per_sec,
+            burst: quota.burst,
+            max_concurrent: quota.max_concurrent,
+            api_key_header: "x-api-key".to_string(),
+            api_keys: api_keys
+                .iter()
+                .map(|(api_key, quota)| (api_key.to_string(), *quota))
+                .collect(),
+        }))
+    }
+
+    fn request(remote_addr: &str, api_key: Option<&str>) -> hyper::Request<()> {
+        let mut request = hyper::Request::builder().uri("/slot_lock.SlotLockService/LockSlot");
+        if let Some(api_key) = api_key {
+            request = request.header("x-api-key", api_key);
+        }
+        let mut request = request.body(()).unwrap();
+        request.extensions_mut().insert(TcpConnectInfo {
+            local_addr: None,
+            remote_addr: Some(remote_addr.parse::<SocketAddr>().unwrap()),
+        });
+        request
+    }
+
+    #[test]
+    fn test_token_bucket() {
+        let limiter = limiter(
+            RateLimitQuota {
+                requests_per_sec: Some(2),
+                burst: Some(3),
+                max_concurrent: None,
+            },
+            &[],
+        );
+        let peer = limiter.peer(&request("10.0.0.1:1000", None));
+        let start = Instant::now();
+
+        // The burst is admitted at once, then requests are paced at the rate
+        for _ in 0..3 {
+            assert!(limiter.acquire(peer.clone(), start).is_ok());
+        }
+        let rejection = limiter.acquire(peer.clone(), start).err().unwrap();
+        assert_eq!(rejection.quota, "requests_per_sec");
+        assert_eq!(rejection.retry_after, Duration::from_millis(500));
+        let later = start + Duration::from_millis(500);
+        assert!(limiter.acquire(peer.clone(), later).is_ok());
+        assert!(limiter.acquire(peer, later).is_err());
+
+        // Other peers have their own bucket
+        let other = limiter.peer(&request("10.0.0.2:1000", None));
+        assert!(limiter.acquire(other, later).is_ok());
+    }
+
+    #[test]
+    fn test_concurrency_limit() {
+        let limiter = limiter(
+            RateLimitQuota {
+                max_concurrent: Some(1),
+                ..Default::default()
+            },
+            &[],
+        );
+        let peer = limiter.peer(&request("10.0.0.1:1000", None));
+        let now = Instant::now();
+
+        let permit = limiter.acquire(peer.clone(), now).ok().unwrap();
+        let rejection = limiter.acquire(peer.clone(), now).err().unwrap();
+        assert_eq!(rejection.quota, "max_concurrent");
+        drop(permit);
+        assert!(limiter.acquire(peer, now).is_ok());
+    }
+
+    #[test]
+    fn test_caps_tracked_peers() {
+        let limiter = limiter(
+            RateLimitQuota {
+                max_concurrent: Some(1),
+                ..Default::default()
+            },
+            &[],
+        );
+        let now = Instant::now();
+        let ip = |i: usize| Peer::Ip(IpAddr::from([10, 0, (i >> 8) as u8, i as u8]));
+        let permits = (0..MAX_TRACKED_PEERS)
+            .map(|i| limiter.acquire(ip(i), now).ok().unwrap())
+            .collect::<Vec<_>>();
+
+        // No peer is idle, so new addresses share one quota instead of being tracked
+        let permit = limiter.acquire(ip(MAX_TRACKED_PEERS), now).ok().unwrap();
+        assert_eq!(permit.peer, Peer::Unknown);
+        assert!(limiter.acquire(ip(MAX_TRACKED_PEERS + 1), now).is_err());
+        assert_eq!(limiter.peers.lock().unwrap().len(), MAX_TRACKED_PEERS + 1);
+
+        // Once peers are idle they are forgotten to make room
+        drop(permits);
+        drop(permit);
+        let permit = limiter
+            .acquire(ip(MAX_TRACKED_PEERS + 1), now)
+            .ok()
+            .unwrap();
+        assert_eq!(permit.peer, ip(MAX_TRACKED_PEERS + 1));
+        assert_eq!(limiter.peers.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_api_key_quotas() {
+        let limiter = limiter(
+            RateLimitQuota {
+                requests_per_sec: Some(1),
+                ..Default::default()
+            },
+            &[(
+                "indexer",
+                RateLimitQuota {
+                    requests_per_sec: Some(10),
+                    ..Default::default()
+                },
+            )],
+        );
+        let now = Instant::now();
+
+        // Known keys are limited apart from their IP address, unknown ones by it
+        let peer = limiter.peer(&request("10.0.0.1:1000", Some("indexer")));
+        assert_eq!(peer, Peer::ApiKey("indexer".to_string()));
+        for _ in 0..10 {
+            assert!(limiter.acquire(peer.clone(), now).is_ok());
+        }
+        assert!(limiter.acquire(peer, now).is_err());
+        let peer = limiter.peer(&request("10.0.0.1:2000", Some("unknown")));
+        assert_eq!(peer, Peer::Ip("10.0.0.1".parse().unwrap()));
+        assert!(limiter.acquire(peer.clone(), now).is_ok());
+        assert!(limiter.acquire(peer, now).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rejects_with_resource_exhausted() -> Result<(), Box<dyn std::error::Error>> {
+        use tower::ServiceExt;
+
+        let limiter = limiter(
+            RateLimitQuota {
+                requests_per_sec: Some(1),
+                ..Default::default()
+            },
+            &[],
+        );
+        let service =
+            RateLimitLayer::new(limiter).layer(tower::service_fn(|_: hyper::Request<()>| async {
+                Ok::<_, std::convert::Infallible>(hyper::Response::new(tonic::body::empty_body()))
+            }));
+
+        let response = service
+            .clone()
+            .oneshot(request("10.0.0.1:1000", None))
+            .await?;
+        assert!(response.headers().get("grpc-status").is_none());
+        let response = service
+            .clone()
+            .oneshot(request("10.0.0.1:1000", None))
+            .await?;
+        let status = tonic::Status::from_header_map(response.headers()).unwrap();
+        assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+
+        // Health checks are never limited
+        let mut health = request("10.0.0.1:1000", None);
+        *health.uri_mut() = "/health.Health/Check".parse()?;
+        let response = service.oneshot(health).await?;
+        assert!(response.headers().get("grpc-status").is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_streams_hold_their_slot() -> Result<(), Box<dyn std::error::Error>> {
+        use http_body_util::BodyExt;
+        use tower::ServiceExt;
+
+        let limiter = limiter(
+            RateLimitQuota {
+                max_concurrent: Some(1),
+                ..Default::default()
+            },
+            &[],
+        );
+        let service =
+            RateLimitLayer::new(limiter).layer(tower::service_fn(|_: hyper::Request<()>| async {
+                Ok::<_, std::convert::Infallible>(hyper::Response::new(tonic::body::empty_body()))
+            }));
+        let rejected = |response: &hyper::Response<BoxBody>| {
+            tonic::Status::from_header_map(response.headers())
+                .is_some_and(|status| status.code() == tonic::Code::ResourceExhausted)
+        };
+
+        // The response is in flight until its body has been read to the end
+        let streaming = service
+            .clone()
+            .oneshot(request("10.0.0.1:1000", None))
+            .await?;
+        let response = service
+            .clone()
+            .oneshot(request("10.0.0.1:1000", None))
+            .await?;
+        assert!(rejected(&response));
+
+        streaming.into_body().collect().await?;
+        let response = service.oneshot(request("10.0.0.1:1000", None)).await?;
+        assert!(!rejected(&response));
+        Ok(())
+    }
+}
@@ -0,0 +1,101 @@
+use crate::db::Database;
+use crate::service::bitcoin::BitcoinRpcServiceAPI;
+use anyhow::Result;
+use std::time::Duration;
+
+/// Periodically rebroadcasts the raw transactions of pending locks, so they aren't dropped from
+/// mempools before they confirm
+pub struct Rebroadcaster<B: BitcoinRpcServiceAPI> {
+    db: Database,
+    bitcoin_service: B,
+    interval: Duration,
+}
+
+impl<B: BitcoinRpcServiceAPI> Rebroadcaster<B> {
+    pub fn new(db: Database, bitcoin_service: B, interval: Duration) -> Self {
+        Self {
+            db,
+            bitcoin_service,
+            interval,
+        }
+    }
+
+    /// Rebroadcasts every stored transaction that still backs an active lock and drops the rest.
+    /// Returns the number of transactions rebroadcast.
+    pub async fn rebroadcast_pending(&self) -> Result<usize> {
+        let pruned = self.db.prune_lock_transactions()?;
+        if pruned > 0 {
+            tracing::debug!("Pruned {} lock transactions without active locks", pruned);
+        }
+
+        let mut rebroadcast = 0;
+        for (btc_txid, raw_tx) in self.db.pending_lock_transactions()? {
+            match self.bitcoin_service.broadcast_transaction(&raw_tx).await {
+                Ok(()) => rebroadcast += 1,
+                Err(e) => {
+                    tracing::warn!("Failed to rebroadcast lock transaction {}: {}", btc_txid, e)
+                }
+            }
+        }
+        Ok(rebroadcast)
+    }
+
+    /// Rebroadcasts pending lock transactions every interval, forever
+    pub async fn run(self) {
+        let mut ticker = tokio::time::interval(self.interval);
+        // The first tick completes immediately; transactions were just broadcast when locked
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            match self.rebroadcast_pending().await {
+                Ok(count) if count > 0 => {
+                    tracing::info!("Rebroadcast {} pending lock transactions", count)
+                }
+                Ok(_) => {}
+                Err(e) => tracing::error!("Failed to rebroadcast lock transactions: {}", e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{SlotInsertData, UnlockReason};
+    use crate::test_util::MockBitcoinService;
+
+    #[tokio::test]
+    async fn test_rebroadcasts_only_pending_locks() -> Result<()> {
+        let db = Database::new(rusqlite::Connection::open_in_memory()?)?;
+        let slots: Vec<SlotInsertData> = (0..2u8)
+            .map(|idx| SlotInsertData {
+                namespace: String::new(),
+                contract_address: "0x123".to_string(),
+                start_block: 100,
+                btc_block: 200,
+                slot_index: vec![idx],
+                btc_txid: format!("txid{}", idx),
+                revert_value: vec![],
+                current_value: vec![],
+                metadata: Vec::new(),
+                labels: Default::default(),
+                alt_btc_txids: Vec::new(),
+                required_confirmed_txids: 1,
+                locked_by: None,
+            })
+            .collect();
+        db.with_transaction(|tx| {
+            db.batch_insert_slot_locks(tx, &slots)?;
+            db.insert_lock_transaction(tx, "txid0", &[0xaa])?;
+            db.insert_lock_transaction(tx, "txid1", &[0xbb])
+        })?;
+        db.unlock_slot("", "0x123", &[0], 150, UnlockReason::Confirmed)?;
+
+        let rebroadcaster =
+            Rebroadcaster::new(db, MockBitcoinService::default(), Duration::from_secs(60));
+        assert_eq!(rebroadcaster.rebroadcast_pending().await?, 1);
+        assert_eq!(rebroadcaster.bitcoin_service.broadcasts(), vec![vec![0xbb]]);
+
+        Ok(())
+    }
+}
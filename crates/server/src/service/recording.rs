@@ -0,0 +1,636 @@
+//! Record/replay debugging mode. In record mode the sentinel traces every state-changing SlotLock
+//! request with its response and the results of the Bitcoin RPC calls made while handling it.
+//! Replaying feeds the traces back through a [`SlotLockServiceImpl`] over a copy of the database
+//! taken when recording started, answering Bitcoin calls from the traces, and reports every
+//! response that differs from the recorded one.
+
+use crate::db::Database;
+use crate::service::bitcoin::{
+    network_name, parse_network_name, BitcoinRpcServiceAPI, ChainInfo, MempoolStatus,
+};
+use crate::service::double_spend::DoubleSpendStatus;
+use crate::service::slot_lock::SlotLockServiceImpl;
+use anyhow::{Context, Result};
+use bitcoin::Txid;
+use prost::Message;
+use rusqlite::{Connection, DatabaseName};
+use sova_sentinel_proto::proto::{
+    slot_lock_service_server::SlotLockService, BatchGetSlotStatusRequest,
+    BatchGetSlotStatusResponse, BatchLockSlotRequest, BatchLockSlotResponse,
+    BatchUnlockSlotRequest, BatchUnlockSlotResponse, CommitLockRequest, CommitLockResponse,
+    FinalizeBlockRequest, FinalizeBlockResponse, GetSlotStatusRequest, GetSlotStatusResponse,
+    LockSlotRequest, LockSlotResponse, PrepareLockRequest, PrepareLockResponse,
+    RecordedBitcoinCall, RecordedRequest, ReplaceLockTxRequest, ReplaceLockTxResponse,
+    UnlockByTxidRequest, UnlockByTxidResponse,
+};
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Debug;
+use std::fs::{File, OpenOptions};
+use std::future::Future;
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tonic::{Code, Request, Response, Status};
+
+/// Copy of the database taken when recording started, inside the recording directory
+pub const SNAPSHOT_FILE: &str = "snapshot.db";
+/// Length-delimited [`RecordedRequest`]s, inside the recording directory
+pub const TRACES_FILE: &str = "traces";
+
+tokio::task_local! {
+    /// Bitcoin calls made by the request being recorded on the current task
+    static BITCOIN_CALLS: RefCell<Vec<RecordedBitcoinCall>>;
+}
+
+/// Adds a Bitcoin call to the trace of the request being recorded, if any
+fn trace_call<T>(
+    call: &str,
+    argument: &str,
+    result: &Result<T>,
+    encode: impl FnOnce(&T) -> String,
+) {
+    let _ = BITCOIN_CALLS.try_with(|calls| {
+        calls.borrow_mut().push(RecordedBitcoinCall {
+            call: call.to_string(),
+            argument: argument.to_string(),
+            result: result.as_ref().ok().map(encode),
+            error: match result {
+                Ok(_) => String::new(),
+                Err(e) => format!("{:#}", e),
+            },
+        })
+    });
+}
+
+fn encode_mempool_status(status: &MempoolStatus) -> String {
+    match status {
+        MempoolStatus::Unseen => "unseen",
+        MempoolStatus::InMempool => "in_mempool",
+        MempoolStatus::Evicted => "evicted",
+    }
+    .to_string()
+}
+
+fn decode_mempool_status(status: &str) -> Result<MempoolStatus> {
+    match status {
+        "unseen" => Ok(MempoolStatus::Unseen),
+        "in_mempool" => Ok(MempoolStatus::InMempool),
+        "evicted" => Ok(MempoolStatus::Evicted),
+        other => Err(anyhow::anyhow!("Unknown mempool status: {}", other)),
+    }
+}
+
+fn encode_double_spend(status: &DoubleSpendStatus) -> String {
+    match status {
+        DoubleSpendStatus::None => "none".to_string(),
+        DoubleSpendStatus::AtRisk => "at_risk".to_string(),
+        DoubleSpendStatus::Conflicted => "conflicted".to_string(),
+        DoubleSpendStatus::Replaced(txid) => format!("replaced:{}", txid),
+    }
+}
+
+fn decode_double_spend(status: &str) -> Result<DoubleSpendStatus> {
+    match status {
+        "none" => Ok(DoubleSpendStatus::None),
+        "at_risk" => Ok(DoubleSpendStatus::AtRisk),
+        "conflicted" => Ok(DoubleSpendStatus::Conflicted),
+        other => match other.strip_prefix("replaced:") {
+            Some(txid) => Ok(DoubleSpendStatus::Replaced(txid.parse::<Txid>()?)),
+            None => Err(anyhow::anyhow!("Unknown double-spend status: {}", other)),
+        },
+    }
+}
+
+/// Bitcoin service that adds the calls made while a request is recorded to its trace. Outside
+/// recorded requests it only passes calls through.
+pub struct RecordingBitcoinService<B> {
+    inner: B,
+}
+
+impl<B> RecordingBitcoinService<B> {
+    pub fn new(inner: B) -> Self {
+        Self { inner }
+    }
+}
+
+#[tonic::async_trait]
+impl<B: BitcoinRpcServiceAPI> BitcoinRpcServiceAPI for RecordingBitcoinService<B> {
+    async fn get_confirmations(&self, txid: &str) -> Result<u32> {
+        let result = self.inner.get_confirmations(txid).await;
+        trace_call("get_confirmations", txid, &result, u32::to_string);
+        result
+    }
+
+    fn confirmation_threshold(&self) -> u32 {
+        self.inner.confirmation_threshold()
+    }
+
+    async fn mempool_status(&self, txid: &str) -> Result<MempoolStatus> {
+        let result = self.inner.mempool_status(txid).await;
+        trace_call("mempool_status", txid, &result, encode_mempool_status);
+        result
+    }
+
+    async fn check_double_spend(&self, txid: &str) -> Result<DoubleSpendStatus> {
+        let result = self.inner.check_double_spend(txid).await;
+        trace_call("check_double_spend", txid, &result, encode_double_spend);
+        result
+    }
+
+    async fn broadcast_transaction(&self, raw_tx: &[u8]) -> Result<()> {
+        let result = self.inner.broadcast_transaction(raw_tx).await;
+        trace_call(
+            "broadcast_transaction",
+            &hex::encode(raw_tx),
+            &result,
+            |_| String::new(),
+        );
+        result
+    }
+
+    async fn chain_info(&self) -> Result<ChainInfo> {
+        let result = self.inner.chain_info().await;
+        trace_call("chain_info", "", &result, |info| {
+            format!("{}:{}", network_name(info.network), info.tip_height)
+        });
+        result
+    }
+}
+
+/// Writes the traces of a recording
+#[derive(Clone)]
+pub struct Recorder {
+    traces: Arc<Mutex<(File, u64)>>,
+    confirmation_threshold: u32,
+    revert_threshold: u32,
+}
+
+impl Recorder {
+    /// Starts a recording in `dir`, copying the database there first. Fails if `dir` already
+    /// holds a recording.
+    pub fn create(
+        dir: &Path,
+        db: &Database,
+        confirmation_threshold: u32,
+        revert_threshold: u32,
+    ) -> Result<Self> {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create recording directory {}", dir.display()))?;
+        let traces = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(dir.join(TRACES_FILE))
+            .with_context(|| format!("{} already holds a recording", dir.display()))?;
+        db.backup_to(&dir.join(SNAPSHOT_FILE))?;
+        Ok(Self {
+            traces: Arc::new(Mutex::new((traces, 1))),
+            confirmation_threshold,
+            revert_threshold,
+        })
+    }
+
+    /// Handles `request` through `handle`, then writes a trace of it
+    pub async fn record<T, R, F, Fut>(
+        &self,
+        method: &str,
+        request: Request<T>,
+        handle: F,
+    ) -> Result<Response<R>, Status>
+    where
+        T: Message,
+        R: Message,
+        F: FnOnce(Request<T>) -> Fut,
+        Fut: Future<Output = Result<Response<R>, Status>>,
+    {
+        let encoded_request = request.get_ref().encode_to_vec();
+        let (result, bitcoin_calls) = BITCOIN_CALLS
+            .scope(RefCell::new(Vec::new()), async {
+                let result = handle(request).await;
+                (result, BITCOIN_CALLS.with(|calls| calls.take()))
+            })
+            .await;
+        let mut trace = RecordedRequest {
+            seq: 0,
+            method: method.to_string(),
+            request: encoded_request,
+            code: Code::Ok as i32,
+            message: String::new(),
+            response: Vec::new(),
+            bitcoin_calls,
+            confirmation_threshold: self.confirmation_threshold,
+            revert_threshold: self.revert_threshold,
+        };
+        match &result {
+            Ok(response) => trace.response = response.get_ref().encode_to_vec(),
+            Err(status) => {
+                trace.code = status.code() as i32;
+                trace.message = status.message().to_string();
+            }
+        }
+        if let Err(e) = self.write(trace) {
+            tracing::error!("Failed to record a {} request: {:#}", method, e);
+        }
+        result
+    }
+
+    fn write(&self, mut trace: RecordedRequest) -> Result<()> {
+        let mut traces = self.traces.lock().unwrap_or_else(|e| e.into_inner());
+        let (file, next_seq) = &mut *traces;
+        trace.seq = *next_seq;
+        file.write_all(&trace.encode_length_delimited_to_vec())?;
+        *next_seq += 1;
+        Ok(())
+    }
+}
+
+/// Bitcoin service answering calls from the traces being replayed. Clones share their traces.
+#[derive(Clone, Default)]
+pub struct ReplayBitcoinService {
+    state: Arc<Mutex<ReplayState>>,
+}
+
+#[derive(Default)]
+struct ReplayState {
+    confirmation_threshold: u32,
+    /// Recorded results of each call and argument, in the order they were made
+    calls: HashMap<(String, String), VecDeque<RecordedBitcoinCall>>,
+}
+
+impl ReplayBitcoinService {
+    /// Answers the calls of the next request from `trace`
+    fn load(&self, trace: &RecordedRequest) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.confirmation_threshold = trace.confirmation_threshold;
+        state.calls.clear();
+        for call in &trace.bitcoin_calls {
+            state
+                .calls
+                .entry((call.call.clone(), call.argument.clone()))
+                .or_default()
+                .push_back(call.clone());
+        }
+    }
+
+    /// Returns the recorded result of a call
+    fn answer(&self, call: &str, argument: &str) -> Result<String> {
+        let recorded = self
+            .state
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .calls
+            .get_mut(&(call.to_string(), argument.to_string()))
+            .and_then(VecDeque::pop_front)
+            .with_context(|| format!("No recorded result of {}({})", call, argument))?;
+        recorded
+            .result
+            .ok_or_else(|| anyhow::anyhow!("{}", recorded.error))
+    }
+}
+
+#[tonic::async_trait]
+impl BitcoinRpcServiceAPI for ReplayBitcoinService {
+    async fn get_confirmations(&self, txid: &str) -> Result<u32> {
+        Ok(self.answer("get_confirmations", txid)?.parse()?)
+    }
+
+    fn confirmation_threshold(&self) -> u32 {
+        self.state
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .confirmation_threshold
+    }
+
+    async fn mempool_status(&self, txid: &str) -> Result<MempoolStatus> {
+        decode_mempool_status(&self.answer("mempool_status", txid)?)
+    }
+
+    async fn check_double_spend(&self, txid: &str) -> Result<DoubleSpendStatus> {
+        decode_double_spend(&self.answer("check_double_spend", txid)?)
+    }
+
+    async fn broadcast_transaction(&self, raw_tx: &[u8]) -> Result<()> {
+        self.answer("broadcast_transaction", &hex::encode(raw_tx))?;
+        Ok(())
+    }
+
+    async fn chain_info(&self) -> Result<ChainInfo> {
+        let info = self.answer("chain_info", "")?;
+        let (network, tip_height) = info
+            .split_once(':')
+            .with_context(|| format!("Invalid chain info: {}", info))?;
+        Ok(ChainInfo {
+            network: parse_network_name(network)?,
+            tip_height: tip_height.parse()?,
+        })
+    }
+}
+
+/// A replayed request whose outcome differs from the recorded one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplayMismatch {
+    pub seq: u64,
+    pub method: String,
+    pub recorded: String,
+    pub replayed: String,
+}
+
+#[derive(Debug, Default)]
+pub struct ReplayReport {
+    /// Requests replayed
+    pub requests: u64,
+    pub mismatches: Vec<ReplayMismatch>,
+}
+
+/// Reads the traces of the recording in `dir`, leaving out a trace left incomplete by a crash
+pub fn read_traces(dir: &Path) -> Result<Vec<RecordedRequest>> {
+    let path = dir.join(TRACES_FILE);
+    let bytes =
+        std::fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let mut buf = bytes.as_slice();
+    let mut traces = Vec::new();
+    while let Ok(trace) = RecordedRequest::decode_length_delimited(&mut buf) {
+        traces.push(trace);
+    }
+    Ok(traces)
+}
+
+/// Replays the recording in `dir` through a SlotLock service over a fresh copy of its snapshot,
+/// set up by `configure` like the recording sentinel was
+pub async fn replay_recording(
+    dir: &Path,
+    configure: impl FnOnce(
+        SlotLockServiceImpl<ReplayBitcoinService>,
+    ) -> SlotLockServiceImpl<ReplayBitcoinService>,
+) -> Result<ReplayReport> {
+    let traces = read_traces(dir)?;
+    let mut connection = Connection::open_in_memory()?;
+    connection
+        .restore(
+            DatabaseName::Main,
+            dir.join(SNAPSHOT_FILE),
+            None::<fn(rusqlite::backup::Progress)>,
+        )
+        .context("Failed to load the recording's database snapshot")?;
+    let db = Database::new(connection)?;
+    let bitcoin_service = ReplayBitcoinService::default();
+    let revert_threshold = traces.first().map_or(0, |trace| trace.revert_threshold);
+    let service = configure(SlotLockServiceImpl::new(
+        db,
+        bitcoin_service.clone(),
+        revert_threshold,
+    ));
+
+    let mut report = ReplayReport::default();
+    for trace in &traces {
+        bitcoin_service.load(trace);
+        let (code, response) = match method_name(&trace.method) {
+            "LockSlot" => replay(trace, |r: Request<LockSlotRequest>| service.lock_slot(r)).await?,
+            "GetSlotStatus" => {
+                replay(trace, |r: Request<GetSlotStatusRequest>| {
+                    service.get_slot_status(r)
+                })
+                .await?
+            }
+            "BatchLockSlot" => {
+                replay(trace, |r: Request<BatchLockSlotRequest>| {
+                    service.batch_lock_slot(r)
+                })
+                .await?
+            }
+            "BatchGetSlotStatus" => {
+                replay(trace, |r: Request<BatchGetSlotStatusRequest>| {
+                    service.batch_get_slot_status(r)
+                })
+                .await?
+            }
+            "BatchUnlockSlot" => {
+                replay(trace, |r: Request<BatchUnlockSlotRequest>| {
+                    service.batch_unlock_slot(r)
+                })
+                .await?
+            }
+            "ReplaceLockTx" => {
+                replay(trace, |r: Request<ReplaceLockTxRequest>| {
+                    service.replace_lock_tx(r)
+                })
+                .await?
+            }
+            "UnlockByTxid" => {
+                replay(trace, |r: Request<UnlockByTxidRequest>| {
+                    service.unlock_by_txid(r)
+                })
+                .await?
+            }
+            "PrepareLock" => {
+                replay(trace, |r: Request<PrepareLockRequest>| {
+                    service.prepare_lock(r)
+                })
+                .await?
+            }
+            "CommitLock" => {
+                replay(trace, |r: Request<CommitLockRequest>| {
+                    service.commit_lock(r)
+                })
+                .await?
+            }
+            "FinalizeBlock" => {
+                replay(trace, |r: Request<FinalizeBlockRequest>| {
+                    service.finalize_block(r)
+                })
+                .await?
+            }
+            _ => anyhow::bail!("Trace {} has unknown method {}", trace.seq, trace.method),
+        };
+        report.requests += 1;
+        let recorded = describe_outcome(&trace.method, Code::from_i32(trace.code), &trace.response);
+        let replayed = describe_outcome(&trace.method, code, &response);
+        if recorded != replayed {
+            report.mismatches.push(ReplayMismatch {
+                seq: trace.seq,
+                method: trace.method.clone(),
+                recorded,
+                replayed,
+            });
+        }
+    }
+    Ok(report)
+}
+
+/// Handles the request of a trace through `handle`, returning the status code and the encoded
+/// response
+async fn replay<T, R, F, Fut>(trace: &RecordedRequest, handle: F) -> Result<(Code, Vec<u8>)>
+where
+    T: Message + Default,
+    R: Message,
+    F: FnOnce(Request<T>) -> Fut,
+    Fut: Future<Output = Result<Response<R>, Status>>,
+{
+    let request = T::decode(trace.request.as_slice())
+        .with_context(|| format!("Invalid request in trace {}", trace.seq))?;
+    Ok(match handle(Request::new(request)).await {
+        Ok(response) => (Code::Ok, response.into_inner().encode_to_vec()),
+        Err(status) => (status.code(), Vec::new()),
+    })
+}
+
+fn method_name(method: &str) -> &str {
+    method.rsplit('/').next().unwrap_or_default()
+}
+
+fn decode<T: Message + Default + Debug>(bytes: &[u8]) -> String {
+    match T::decode(bytes) {
+        Ok(message) => format!("{:?}", message),
+        Err(e) => format!("<undecodable: {}>", e),
+    }
+}
+
+/// Decodes a SlotLock request for display
+pub fn describe_request(method: &str, request: &[u8]) -> String {
+    match method_name(method) {
+        "LockSlot" => decode::<LockSlotRequest>(request),
+        "GetSlotStatus" => decode::<GetSlotStatusRequest>(request),
+        "BatchLockSlot" => decode::<BatchLockSlotRequest>(request),
+        "BatchGetSlotStatus" => decode::<BatchGetSlotStatusRequest>(request),
+        "BatchUnlockSlot" => decode::<BatchUnlockSlotRequest>(request),
+        "ReplaceLockTx" => decode::<ReplaceLockTxRequest>(request),
+        "UnlockByTxid" => decode::<UnlockByTxidRequest>(request),
+        "PrepareLock" => decode::<PrepareLockRequest>(request),
+        "CommitLock" => decode::<CommitLockRequest>(request),
+        "FinalizeBlock" => decode::<FinalizeBlockRequest>(request),
+        _ => format!("0x{}", hex::encode(request)),
+    }
+}
+
+/// Describes how a request ended for comparison. Signatures are left out, since the replaying
+/// service needn't have the recording sentinel's key.
+fn describe_outcome(method: &str, code: Code, response: &[u8]) -> String {
+    if code != Code::Ok {
+        return format!("{:?}", code);
+    }
+    match method_name(method) {
+        "LockSlot" => decode::<LockSlotResponse>(response),
+        "GetSlotStatus" => match GetSlotStatusResponse::decode(response) {
+            Ok(response) => format!(
+                "{:?}",
+                GetSlotStatusResponse {
+                    signature: Vec::new(),
+                    ..response
+                }
+            ),
+            Err(e) => format!("<undecodable: {}>", e),
+        },
+        "BatchLockSlot" => decode::<BatchLockSlotResponse>(response),
+        "BatchGetSlotStatus" => match BatchGetSlotStatusResponse::decode(response) {
+            Ok(response) => format!(
+                "{:?}",
+                BatchGetSlotStatusResponse {
+                    signature: Vec::new(),
+                    ..response
+                }
+            ),
+            Err(e) => format!("<undecodable: {}>", e),
+        },
+        "BatchUnlockSlot" => decode::<BatchUnlockSlotResponse>(response),
+        "ReplaceLockTx" => decode::<ReplaceLockTxResponse>(response),
+        "UnlockByTxid" => decode::<UnlockByTxidResponse>(response),
+        "PrepareLock" => decode::<PrepareLockResponse>(response),
+        "CommitLock" => decode::<CommitLockResponse>(response),
+        "FinalizeBlock" => decode::<FinalizeBlockResponse>(response),
+        _ => format!("0x{}", hex::encode(response)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::JournaledSlotLockService;
+    use crate::test_util::MockBitcoinService;
+    use sova_sentinel_proto::proto::{get_slot_status_response, SlotData, SlotIdentifier};
+
+    const TXID: &str = "1111111111111111111111111111111111111111111111111111111111111111";
+
+    #[tokio::test]
+    async fn test_record_and_replay() -> Result<()> {
+        let dir =
+            std::env::temp_dir().join(format!("sova-sentinel-recording-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let db = Database::new(Connection::open_in_memory()?)?;
+        let btc = MockBitcoinService::new();
+        let recorder = Recorder::create(&dir, &db, btc.confirmation_threshold(), 6)?;
+        assert!(Recorder::create(&dir, &db, btc.confirmation_threshold(), 6).is_err());
+        let service = JournaledSlotLockService::new(SlotLockServiceImpl::new(
+            db,
+            RecordingBitcoinService::new(btc.clone()),
+            6,
+        ))
+        .with_recorder(recorder);
+
+        let slots = [vec![1], vec![2]];
+        service
+            .batch_lock_slot(Request::new(BatchLockSlotRequest {
+                namespace: String::new(),
+                locked_at_block: 1000,
+                btc_block: 95,
+                slots: slots
+                    .iter()
+                    .map(|slot_index| SlotData {
+                        contract_address: "0x123".to_string(),
+                        slot_index: slot_index.clone(),
+                        revert_value: vec![4],
+                        current_value: vec![7],
+                        btc_txid: TXID.to_string(),
+                        metadata: Vec::new(),
+                        labels: Default::default(),
+                        alt_btc_txids: Vec::new(),
+                        required_confirmed_txids: 0,
+                    })
+                    .collect(),
+                contract_slots: Vec::new(),
+                atomic: false,
+            }))
+            .await?;
+        btc.add_confirmed_tx(TXID);
+        let status = service
+            .batch_get_slot_status(Request::new(BatchGetSlotStatusRequest {
+                namespace: String::new(),
+                current_block: 1001,
+                btc_block: 100,
+                slots: slots
+                    .iter()
+                    .map(|slot_index| SlotIdentifier {
+                        contract_address: "0x123".to_string(),
+                        slot_index: slot_index.clone(),
+                    })
+                    .collect(),
+                max_staleness_blocks: None,
+            }))
+            .await?;
+        assert!(status
+            .get_ref()
+            .slots
+            .iter()
+            .all(|slot| slot.status == get_slot_status_response::Status::Unlocked as i32));
+
+        let traces = read_traces(&dir)?;
+        assert_eq!(traces.len(), 2);
+        assert!(traces[1]
+            .bitcoin_calls
+            .iter()
+            .any(|call| call.call == "get_confirmations" && call.argument == TXID));
+
+        // The Bitcoin node has moved on, but replay answers from the traces
+        let report = replay_recording(&dir, |service| service).await?;
+        assert_eq!(report.requests, 2);
+        assert_eq!(report.mismatches, Vec::new());
+
+        // A service that behaves differently is caught, along with what follows from it
+        let report = replay_recording(&dir, |service| service.with_read_only(true)).await?;
+        let seqs: Vec<u64> = report.mismatches.iter().map(|m| m.seq).collect();
+        assert_eq!(seqs, vec![1, 2]);
+        assert_eq!(report.mismatches[0].replayed, "FailedPrecondition");
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,536 @@
+//! Primary/standby replication. A standby tails its primary's lock changes through the admin
+//! service and rejects writes until it is promoted. Every promotion raises the fencing epoch, and
+//! a primary that learns of a higher epoch, from the promoted standby or from a client that has
+//! talked to it, fences itself so two primaries can't both grant locks.
+
+use crate::db::{self, Database, ReplicationRole, ReplicationState};
+use crate::service::admin::lock_row;
+use crate::service::status::{
+    database_status, fenced_status, stale_replica_status, standby_read_status, standby_status,
+    FieldViolations,
+};
+use anyhow::{Context, Result};
+use sova_sentinel_proto::fencing;
+use sova_sentinel_proto::proto::{
+    admin_service_client::AdminServiceClient, replication_status, FenceRequest,
+    GetLockChangesRequest, LockChange, ReplicatedLock, ReplicationStatus,
+};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tonic::metadata::MetadataMap;
+use tonic::transport::{Channel, Endpoint};
+use tonic::Status;
+
+/// Number of changes a standby requests from its primary at a time
+const CHANGES_PAGE_SIZE: u32 = 500;
+/// How long a promotion waits for the primary to be reached
+const PRIMARY_TIMEOUT: Duration = Duration::from_secs(5);
+
+impl From<db::LockChange> for LockChange {
+    fn from(change: db::LockChange) -> Self {
+        Self {
+            seq: change.seq,
+            lock_id: change.lock_id as u64,
+            lock: change.lock.map(|lock| ReplicatedLock {
+                lock_group: lock.row.lock.lock_group,
+                record: Some(lock.row.into()),
+                double_spent: lock.double_spent,
+                updated_at: lock.updated_at.unwrap_or_default(),
+                archived_at: lock.archived_at.unwrap_or_default(),
+                restored_at: lock.restored_at.unwrap_or_default(),
+            }),
+            instance_id: change.instance_id.unwrap_or_default(),
+        }
+    }
+}
+
+/// Converts a change read from the primary, failing if its lock isn't valid
+pub(crate) fn lock_change(change: LockChange) -> Result<db::LockChange> {
+    let lock = match change.lock {
+        Some(lock) => {
+            let record = lock.record.context("Replicated lock has no record")?;
+            let mut violations = FieldViolations::default();
+            let mut row = lock_row("lock.record", record, &mut violations);
+            if let Some(status) = violations.into_status() {
+                anyhow::bail!(
+                    "Invalid lock {} in change {}: {}",
+                    change.lock_id,
+                    change.seq,
+                    status.message()
+                );
+            }
+            row.lock.lock_group = lock.lock_group;
+            Some(db::ReplicatedLock {
+                row,
+                double_spent: lock.double_spent,
+                updated_at: (!lock.updated_at.is_empty()).then_some(lock.updated_at),
+                archived_at: (!lock.archived_at.is_empty()).then_some(lock.archived_at),
+                restored_at: (!lock.restored_at.is_empty()).then_some(lock.restored_at),
+            })
+        }
+        None => None,
+    };
+    Ok(db::LockChange {
+        seq: change.seq,
+        lock_id: change.lock_id as i64,
+        lock,
+        instance_id: (!change.instance_id.is_empty()).then_some(change.instance_id),
+    })
+}
+
+/// Replication role and fencing epoch of this sentinel, shared by the services and the
+/// replicator. Changes are persisted before they take effect.
+#[derive(Clone)]
+pub struct Replication {
+    db: Database,
+    state: Arc<Mutex<ReplicationState>>,
+    primary_url: Option<String>,
+    /// On a primary the latest sova block it was queried or finalized at, on a standby its
+    /// primary's as of the last time it caught up. Not persisted, so a restarted standby answers
+    /// no bounded read until it has caught up.
+    latest_block: Arc<AtomicU64>,
+}
+
+impl Replication {
+    /// Loads the persisted state. A sentinel starting for the first time is a standby at epoch 0
+    /// if it has a `primary_url`, else a primary at epoch 1.
+    pub fn load(db: Database, primary_url: Option<String>) -> Result<Self> {
+        let state = match db.replication_state()? {
+            Some(state) => state,
+            None => {
+                let state = ReplicationState {
+                    role: if primary_url.is_some() {
+                        ReplicationRole::Standby
+                    } else {
+                        ReplicationRole::Primary
+                    },
+                    epoch: if primary_url.is_some() { 0 } else { 1 },
+                    applied_seq: 0,
+                };
+                db.save_replication_state(&state)?;
+                state
+            }
+        };
+        match (state.role, &primary_url) {
+            (ReplicationRole::Standby, None) => anyhow::bail!(
+                "This sentinel is a standby: configure replication.primary_url, or promote it"
+            ),
+            (role, Some(_)) if role != ReplicationRole::Standby => tracing::warn!(
+                "Ignoring replication.primary_url, this sentinel was promoted and is {}",
+                role.as_str()
+            ),
+            _ => {}
+        }
+        Ok(Self {
+            db,
+            state: Arc::new(Mutex::new(state)),
+            primary_url,
+            latest_block: Arc::new(AtomicU64::new(0)),
+        })
+    }
+
+    pub fn state(&self) -> ReplicationState {
+        *self.lock_state()
+    }
+
+    /// Admin service endpoint of the primary a standby tails
+    pub fn primary_url(&self) -> Option<&str> {
+        self.primary_url.as_deref()
+    }
+
+    pub fn status(&self) -> Result<ReplicationStatus> {
+        let state = self.state();
+        let role = match state.role {
+            ReplicationRole::Primary => replication_status::Role::Primary,
+            ReplicationRole::Standby => replication_status::Role::Standby,
+            ReplicationRole::Fenced => replication_status::Role::Fenced,
+        };
+        Ok(ReplicationStatus {
+            role: role as i32,
+            epoch: state.epoch,
+            applied_seq: state.applied_seq,
+            last_seq: self.db.last_lock_change_seq()?,
+            latest_block: self.latest_block.load(Ordering::Relaxed),
+        })
+    }
+
+    /// Records that a primary was queried or finalized at sova block `block`
+    pub fn observe_block(&self, block: u64) {
+        if self.state().role == ReplicationRole::Primary {
+            self.latest_block.fetch_max(block, Ordering::Relaxed);
+        }
+    }
+
+    /// Returns why a status query at sova block `current_block` must be rejected, `None` if
+    /// this sentinel may answer it. A primary answers every query, a standby only those allowing
+    /// for how far behind its primary it is, and a fenced primary, which no longer follows the
+    /// locks, none.
+    pub fn read_rejection(
+        &self,
+        current_block: u64,
+        max_staleness_blocks: Option<u64>,
+    ) -> Option<Status> {
+        let state = self.state();
+        match (state.role, max_staleness_blocks) {
+            (ReplicationRole::Primary, _) => {
+                self.observe_block(current_block);
+                None
+            }
+            (ReplicationRole::Standby, None) => Some(standby_read_status()),
+            (ReplicationRole::Standby, Some(max_staleness_blocks)) => {
+                let lag_blocks =
+                    current_block.saturating_sub(self.latest_block.load(Ordering::Relaxed));
+                (lag_blocks > max_staleness_blocks)
+                    .then(|| stale_replica_status(lag_blocks, max_staleness_blocks))
+            }
+            (ReplicationRole::Fenced, _) => Some(fenced_status(state.epoch)),
+        }
+    }
+
+    /// Records that a standby has applied every change its primary made before reaching
+    /// `primary_block`
+    pub(crate) fn caught_up(&self, primary_block: u64) {
+        if self.state().role == ReplicationRole::Standby {
+            self.latest_block
+                .fetch_max(primary_block, Ordering::Relaxed);
+        }
+    }
+
+    /// Raises the epoch to `epoch` if it is higher, fencing a primary. Returns whether it was.
+    pub fn fence(&self, epoch: u64) -> Result<bool> {
+        self.update(|state| {
+            if epoch <= state.epoch {
+                return false;
+            }
+            state.epoch = epoch;
+            if state.role == ReplicationRole::Primary {
+                state.role = ReplicationRole::Fenced;
+                tracing::warn!("Fenced by a primary at epoch {}, rejecting writes", epoch);
+            }
+            true
+        })
+    }
+
+    /// Returns why a write must be rejected, `None` if this sentinel is a primary. A write
+    /// carrying a higher epoch than the sentinel's fences it first.
+    pub fn write_rejection(&self, metadata: &MetadataMap) -> Option<Status> {
+        if let Some(epoch) = fencing::epoch(metadata) {
+            if let Err(e) = self.fence(epoch) {
+                return Some(database_status(e));
+            }
+        }
+        let state = self.state();
+        match state.role {
+            ReplicationRole::Primary => None,
+            ReplicationRole::Standby => Some(standby_status()),
+            ReplicationRole::Fenced => Some(fenced_status(state.epoch)),
+        }
+    }
+
+    /// Applies changes read from the primary at `primary_epoch`. Changes already applied are
+    /// skipped, and nothing is applied once the sentinel is no longer a standby.
+    fn apply(&self, changes: Vec<LockChange>, primary_epoch: u64) -> Result<usize> {
+        let mut state = self.lock_state();
+        if state.role != ReplicationRole::Standby {
+            return Ok(0);
+        }
+        let changes = changes
+            .into_iter()
+            .filter(|change| change.seq > state.applied_seq)
+            .map(lock_change)
+            .collect::<Result<Vec<_>>>()?;
+        let mut next = *state;
+        next.epoch = next.epoch.max(primary_epoch);
+        if let Some(last) = changes.last() {
+            next.applied_seq = last.seq;
+        }
+        self.db.apply_lock_changes(&changes)?;
+        if next != *state {
+            self.db.save_replication_state(&next)?;
+            *state = next;
+        }
+        Ok(changes.len())
+    }
+
+    /// Applies the primary's changes until none are left, returning how many were applied
+    async fn catch_up(&self, client: &mut AdminServiceClient<Channel>) -> Result<usize> {
+        let mut applied = 0;
+        loop {
+            let response = client
+                .get_lock_changes(GetLockChangesRequest {
+                    after_seq: self.state().applied_seq,
+                    limit: CHANGES_PAGE_SIZE,
+                })
+                .await?
+                .into_inner();
+            let primary_epoch = response.status.as_ref().map_or(0, |status| status.epoch);
+            if response.changes.is_empty() {
+                self.apply(Vec::new(), primary_epoch)?;
+                // The primary reads its latest block before its changes, so none made before it
+                // reached that block were left out
+                if let Some(status) = &response.status {
+                    self.caught_up(status.latest_block);
+                }
+                return Ok(applied);
+            }
+            let count = self.apply(response.changes, primary_epoch)?;
+            if count == 0 {
+                // Promoted concurrently, or the primary served only changes already applied
+                return Ok(applied);
+            }
+            applied += count;
+        }
+    }
+
+    /// Promotes a standby to primary at the epoch after its primary's. The primary is fenced
+    /// first, and its remaining changes applied, if it can be reached. Returns whether it was
+    /// fenced.
+    pub async fn promote(&self) -> Result<bool> {
+        let state = self.state();
+        if state.role != ReplicationRole::Standby {
+            anyhow::bail!("Only a standby can be promoted");
+        }
+        let epoch = state.epoch + 1;
+        let primary_fenced = match self.fence_primary(epoch).await {
+            Ok(()) => true,
+            Err(e) => {
+                tracing::warn!("Promoting without fencing the primary: {:#}", e);
+                false
+            }
+        };
+        self.update(|state| {
+            state.role = ReplicationRole::Primary;
+            state.epoch = state.epoch.max(epoch);
+            true
+        })?;
+        tracing::info!("Promoted to primary at epoch {}", self.state().epoch);
+        Ok(primary_fenced)
+    }
+
+    async fn fence_primary(&self, epoch: u64) -> Result<()> {
+        let url = self.primary_url.clone().context("No primary configured")?;
+        let channel = Endpoint::from_shared(url)?
+            .connect_timeout(PRIMARY_TIMEOUT)
+            .timeout(PRIMARY_TIMEOUT)
+            .connect()
+            .await?;
+        let mut client = AdminServiceClient::new(channel);
+        client.fence(FenceRequest { epoch }).await?;
+        let applied = self.catch_up(&mut client).await?;
+        tracing::info!(
+            "Fenced the primary and applied its last {} changes",
+            applied
+        );
+        Ok(())
+    }
+
+    /// Updates the state with `f`, persisting it if `f` returns true
+    fn update(&self, f: impl FnOnce(&mut ReplicationState) -> bool) -> Result<bool> {
+        let mut state = self.lock_state();
+        let mut next = *state;
+        if !f(&mut next) {
+            return Ok(false);
+        }
+        self.db.save_replication_state(&next)?;
+        *state = next;
+        Ok(true)
+    }
+
+    fn lock_state(&self) -> std::sync::MutexGuard<'_, ReplicationState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Tails the primary of a standby until it is promoted
+pub struct Replicator {
+    replication: Replication,
+    poll_interval: Duration,
+}
+
+impl Replicator {
+    pub fn new(replication: Replication, poll_interval: Duration) -> Self {
+        Self {
+            replication,
+            poll_interval,
+        }
+    }
+
+    /// Applies the primary's changes every poll interval while the sentinel is a standby
+    pub async fn run(self) -> Result<()> {
+        let url = self
+            .replication
+            .primary_url()
+            .context("No primary configured")?
+            .to_string();
+        let channel = Endpoint::from_shared(url.clone())?
+            .timeout(PRIMARY_TIMEOUT)
+            .connect_lazy();
+        let mut client = AdminServiceClient::new(channel);
+        tracing::info!("Replicating lock changes from {}", url);
+        let mut ticker = tokio::time::interval(self.poll_interval);
+        while self.replication.state().role == ReplicationRole::Standby {
+            ticker.tick().await;
+            match self.replication.catch_up(&mut client).await {
+                Ok(0) => {}
+                Ok(applied) => tracing::debug!("Applied {} lock changes from the primary", applied),
+                Err(e) => tracing::warn!("Failed to replicate from {}: {:#}", url, e),
+            }
+        }
+        tracing::info!("Stopped replicating from {}", url);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{LockRow, LockedSlot, UnlockReason};
+    use rusqlite::Connection;
+    use sova_sentinel_proto::error_info::{error_info, FENCED, STALE_REPLICA, STANDBY};
+
+    fn reason(status: &Status) -> String {
+        error_info(status).unwrap().reason
+    }
+
+    fn slot(slot_index: u8) -> LockedSlot {
+        LockedSlot {
+            namespace: String::new(),
+            btc_txid: "ab".repeat(32),
+            btc_block: 10,
+            contract_address: "0x123".to_string(),
+            slot_index: vec![slot_index; 32],
+            revert_value: vec![1],
+            current_value: vec![2],
+            start_block: 100,
+            end_block: None,
+            unlock_reason: None,
+            metadata: Vec::new(),
+            labels: Default::default(),
+            lock_group: None,
+            alt_btc_txids: Vec::new(),
+            required_confirmed_txids: 1,
+            locked_at: None,
+            locked_by: None,
+        }
+    }
+
+    #[test]
+    fn test_apply_primary_changes() -> Result<()> {
+        let primary_db = Database::new(Connection::open_in_memory()?)?;
+        let primary = Replication::load(primary_db.clone(), None)?;
+        assert_eq!(primary.state().role, ReplicationRole::Primary);
+        assert_eq!(primary.state().epoch, 1);
+
+        let standby_db = Database::new(Connection::open_in_memory()?)?;
+        let standby = Replication::load(standby_db.clone(), Some("http://primary".to_string()))?;
+        assert_eq!(standby.state().role, ReplicationRole::Standby);
+        primary_db.set_instance_id("primary-1")?;
+        standby_db.set_instance_id("standby-1")?;
+
+        let rows: Vec<_> = [slot(1), slot(2)]
+            .into_iter()
+            .map(|lock| LockRow {
+                id: 0,
+                lock,
+                created_at: None,
+            })
+            .collect();
+        primary_db
+            .with_transaction(|transaction| primary_db.import_lock_rows(transaction, &rows))?;
+        let changes = primary_db.lock_changes(0, 100)?;
+        assert_eq!(changes.len(), 2);
+        let changes: Vec<LockChange> = changes.into_iter().map(Into::into).collect();
+        assert_eq!(standby.apply(changes.clone(), 1)?, 2);
+        assert_eq!(standby.state().epoch, 1);
+        assert_eq!(standby.state().applied_seq, changes[1].seq);
+        // Changes already applied are skipped
+        assert_eq!(standby.apply(changes, 1)?, 0);
+
+        primary_db.unlock_slot("", "0x123", &[1; 32], 105, UnlockReason::RevertThreshold)?;
+        let after = standby.state().applied_seq;
+        let changes = primary_db.lock_changes(after, 100)?;
+        assert_eq!(changes.len(), 1);
+        standby.apply(changes.into_iter().map(Into::into).collect(), 1)?;
+        // The standby's audit rows name the primary that made the changes
+        assert!(standby_db
+            .lock_changes(0, 100)?
+            .iter()
+            .all(|change| change.instance_id.as_deref() == Some("primary-1")));
+        let revert_instance: Option<String> = standby_db.with_transaction(|transaction| {
+            Ok(transaction
+                .query_row("SELECT instance_id FROM lock_reverts", [], |row| row.get(0))?)
+        })?;
+        assert_eq!(revert_instance.as_deref(), Some("primary-1"));
+        assert_eq!(
+            standby_db.active_locks()?,
+            primary_db.active_locks()?,
+            "The standby holds the primary's active locks"
+        );
+        assert_eq!(standby_db.active_locks()?.len(), 1);
+
+        // A standby rejects writes, and reloads as a standby
+        let status = standby.write_rejection(&MetadataMap::new()).unwrap();
+        assert_eq!(reason(&status), STANDBY);
+        assert!(Replication::load(standby_db.clone(), None).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_bounded_reads() -> Result<()> {
+        let primary = Replication::load(Database::new(Connection::open_in_memory()?)?, None)?;
+        assert!(primary.read_rejection(120, None).is_none());
+        primary.observe_block(110);
+        assert_eq!(primary.status()?.latest_block, 120);
+
+        let standby = Replication::load(
+            Database::new(Connection::open_in_memory()?)?,
+            Some("http://primary".to_string()),
+        )?;
+        // Until it has caught up, a standby can't tell how stale it is
+        let status = standby.read_rejection(120, Some(10)).unwrap();
+        assert_eq!(reason(&status), STALE_REPLICA);
+        // Its own queries don't count as the primary's progress
+        standby.observe_block(120);
+        assert_eq!(standby.status()?.latest_block, 0);
+
+        standby.caught_up(primary.status()?.latest_block);
+        assert!(standby.read_rejection(120, Some(0)).is_none());
+        assert!(standby.read_rejection(125, Some(5)).is_none());
+        let status = standby.read_rejection(126, Some(5)).unwrap();
+        assert_eq!(reason(&status), STALE_REPLICA);
+        // Without a bound only the primary answers
+        let status = standby.read_rejection(120, None).unwrap();
+        assert_eq!(reason(&status), STANDBY);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_promote_and_fence() -> Result<()> {
+        let db = Database::new(Connection::open_in_memory()?)?;
+        let standby = Replication::load(db.clone(), Some("http://127.0.0.1:1".to_string()))?;
+        standby.apply(Vec::new(), 3)?;
+        assert_eq!(standby.state().epoch, 3);
+
+        // The primary can't be reached, so it isn't fenced
+        assert!(!standby.promote().await?);
+        assert_eq!(standby.state().role, ReplicationRole::Primary);
+        assert_eq!(standby.state().epoch, 4);
+        assert!(standby.write_rejection(&MetadataMap::new()).is_none());
+        assert!(standby.promote().await.is_err());
+
+        // A write from a client that has seen a higher epoch fences the primary
+        let mut metadata = MetadataMap::new();
+        fencing::insert_epoch(&mut metadata, 4);
+        assert!(standby.write_rejection(&metadata).is_none());
+        fencing::insert_epoch(&mut metadata, 5);
+        let status = standby.write_rejection(&metadata).unwrap();
+        assert_eq!(reason(&status), FENCED);
+        assert!(!standby.fence(5)?);
+
+        let reloaded = Replication::load(db, None)?;
+        assert_eq!(reloaded.state().role, ReplicationRole::Fenced);
+        assert_eq!(reloaded.state().epoch, 5);
+        Ok(())
+    }
+}
@@ -0,0 +1,136 @@
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use tokio_retry::strategy::jitter;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetryStrategy {
+    /// Delay doubles after every attempt, starting at the base delay
+    Exponential,
+    /// Every retry waits the base delay
+    Fixed,
+}
+
+impl FromStr for RetryStrategy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "exponential" => Ok(Self::Exponential),
+            "fixed" => Ok(Self::Fixed),
+            other => Err(anyhow::anyhow!("Unsupported retry strategy: {}", other)),
+        }
+    }
+}
+
+/// Retry policy for Bitcoin RPC calls
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub strategy: RetryStrategy,
+    /// Total number of attempts, including the first one
+    pub max_retries: u32,
+    pub base_delay: Duration,
+    /// Randomize each delay between zero and its nominal value
+    pub jitter: bool,
+    /// No retry is started once this much time has passed since the first attempt
+    pub max_elapsed: Option<Duration>,
+    /// Hard limit on the total time spent on one call, including all retries
+    pub call_budget: Option<Duration>,
+}
+
+impl RetryPolicy {
+    /// Exponential backoff with jitter and no time limits
+    pub fn exponential(max_retries: u32, base_delay: Duration) -> Self {
+        Self {
+            strategy: RetryStrategy::Exponential,
+            max_retries,
+            base_delay,
+            jitter: true,
+            max_elapsed: None,
+            call_budget: None,
+        }
+    }
+
+    /// Returns the delays to wait between attempts, starting from `started`
+    pub fn delays(&self, started: Instant) -> impl Iterator<Item = Duration> {
+        let policy = self.clone();
+        let max_elapsed = self.max_elapsed;
+        (0..self.max_retries.saturating_sub(1))
+            .map(move |retry| {
+                let delay = match policy.strategy {
+                    RetryStrategy::Exponential => {
+                        policy.base_delay.saturating_mul(2u32.saturating_pow(retry))
+                    }
+                    RetryStrategy::Fixed => policy.base_delay,
+                };
+                if policy.jitter {
+                    jitter(delay)
+                } else {
+                    delay
+                }
+            })
+            .take_while(move |delay| {
+                max_elapsed.is_none_or(|max_elapsed| started.elapsed() + *delay <= max_elapsed)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exponential_delays_double() {
+        let policy = RetryPolicy {
+            jitter: false,
+            ..RetryPolicy::exponential(5, Duration::from_millis(100))
+        };
+        let delays: Vec<_> = policy.delays(Instant::now()).collect();
+        assert_eq!(
+            delays,
+            [100, 200, 400, 800].map(Duration::from_millis).to_vec()
+        );
+    }
+
+    #[test]
+    fn test_fixed_delays() {
+        let policy = RetryPolicy {
+            strategy: RetryStrategy::Fixed,
+            jitter: false,
+            ..RetryPolicy::exponential(3, Duration::from_millis(50))
+        };
+        let delays: Vec<_> = policy.delays(Instant::now()).collect();
+        assert_eq!(delays, [50, 50].map(Duration::from_millis).to_vec());
+    }
+
+    #[test]
+    fn test_jitter_never_exceeds_nominal_delay() {
+        let policy = RetryPolicy::exponential(4, Duration::from_millis(100));
+        for (delay, nominal) in policy.delays(Instant::now()).zip([100, 200, 400]) {
+            assert!(delay <= Duration::from_millis(nominal));
+        }
+    }
+
+    #[test]
+    fn test_max_elapsed_stops_retries() {
+        let policy = RetryPolicy {
+            jitter: false,
+            max_elapsed: Some(Duration::from_millis(250)),
+            ..RetryPolicy::exponential(10, Duration::from_millis(100))
+        };
+        let delays: Vec<_> = policy.delays(Instant::now()).collect();
+        assert_eq!(delays, [100, 200].map(Duration::from_millis).to_vec());
+    }
+
+    #[test]
+    fn test_parse_strategy() {
+        assert_eq!(
+            "Exponential".parse::<RetryStrategy>().unwrap(),
+            RetryStrategy::Exponential
+        );
+        assert_eq!(
+            "fixed".parse::<RetryStrategy>().unwrap(),
+            RetryStrategy::Fixed
+        );
+        assert!("linear".parse::<RetryStrategy>().is_err());
+    }
+}
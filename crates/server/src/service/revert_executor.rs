@@ -0,0 +1,276 @@
+use crate::db::{Database, RevertDelivery};
+use crate::deployment::DeploymentLabels;
+use anyhow::Result;
+use async_trait::async_trait;
+use reqwest::Client as HttpClient;
+use serde_json::json;
+use sova_sentinel_proto::proto::{
+    revert_executor_client::RevertExecutorClient, ApplyRevertRequest,
+};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+use tonic::transport::Channel;
+
+/// Maximum number of pending reverts delivered per pass
+const DELIVERY_BATCH_SIZE: usize = 100;
+
+/// Applies reverted slot values on behalf of the sentinel
+#[async_trait]
+pub trait RevertExecutor: Send + Sync {
+    /// Applies a revert, sent along with the deployment labels of the sentinel
+    async fn apply_revert(
+        &self,
+        delivery: &RevertDelivery,
+        labels: &DeploymentLabels,
+    ) -> Result<()>;
+}
+
+/// Posts each revert as JSON to an HTTP endpoint. Byte fields are hex encoded with a `0x`
+/// prefix; any non-2xx response counts as a failed delivery.
+pub struct HttpRevertExecutor {
+    client: HttpClient,
+    url: String,
+}
+
+impl HttpRevertExecutor {
+    pub fn new(url: String) -> Self {
+        Self {
+            client: HttpClient::new(),
+            url,
+        }
+    }
+}
+
+#[async_trait]
+impl RevertExecutor for HttpRevertExecutor {
+    async fn apply_revert(
+        &self,
+        delivery: &RevertDelivery,
+        labels: &DeploymentLabels,
+    ) -> Result<()> {
+        let payload = json!({
+            "delivery_id": delivery.id,
+            "contract_address": delivery.contract_address,
+            "slot_index": format!("0x{}", hex::encode(&delivery.slot_index)),
+            "revert_value": format!("0x{}", hex::encode(&delivery.revert_value)),
+            "current_value": format!("0x{}", hex::encode(&delivery.current_value)),
+            "btc_txid": delivery.btc_txid,
+            "reverted_at_block": delivery.reverted_at_block,
+            "reason": delivery.reason,
+            "namespace": delivery.namespace,
+            "labels": labels,
+        });
+        self.client
+            .post(&self.url)
+            .json(&payload)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Calls the `revert_executor.RevertExecutor` gRPC service
+pub struct GrpcRevertExecutor {
+    client: RevertExecutorClient<Channel>,
+}
+
+impl GrpcRevertExecutor {
+    /// Connects lazily, so an executor that is down at startup doesn't stop the sentinel
+    pub fn new(url: String) -> Result<Self> {
+        let channel = Channel::from_shared(url)?.connect_lazy();
+        Ok(Self {
+            client: RevertExecutorClient::new(channel),
+        })
+    }
+}
+
+#[async_trait]
+impl RevertExecutor for GrpcRevertExecutor {
+    async fn apply_revert(
+        &self,
+        delivery: &RevertDelivery,
+        labels: &DeploymentLabels,
+    ) -> Result<()> {
+        self.client
+            .clone()
+            .apply_revert(ApplyRevertRequest {
+                delivery_id: delivery.id,
+                contract_address: delivery.contract_address.clone(),
+                slot_index: delivery.slot_index.clone(),
+                revert_value: delivery.revert_value.clone(),
+                current_value: delivery.current_value.clone(),
+                btc_txid: delivery.btc_txid.clone(),
+                reverted_at_block: delivery.reverted_at_block,
+                reason: delivery.reason.clone(),
+                namespace: delivery.namespace.clone(),
+                labels: Some(labels.into()),
+            })
+            .await?;
+        Ok(())
+    }
+}
+
+/// Delivers reverts queued in the database to the revert executor and records the outcome of
+/// every attempt
+pub struct RevertDispatcher {
+    db: Database,
+    executor: Arc<dyn RevertExecutor>,
+    notify: Arc<Notify>,
+    retry_interval: Duration,
+    max_attempts: u32,
+    labels: DeploymentLabels,
+}
+
+impl RevertDispatcher {
+    pub fn new(
+        db: Database,
+        executor: Arc<dyn RevertExecutor>,
+        retry_interval: Duration,
+        max_attempts: u32,
+    ) -> Self {
+        Self {
+            db,
+            executor,
+            notify: Arc::new(Notify::new()),
+            retry_interval,
+            max_attempts,
+            labels: DeploymentLabels::default(),
+        }
+    }
+
+    /// Sets the deployment labels sent with each revert
+    pub fn with_labels(mut self, labels: DeploymentLabels) -> Self {
+        self.labels = labels;
+        self
+    }
+
+    /// Returns the handle used to wake the dispatcher when new reverts are queued
+    pub fn notifier(&self) -> Arc<Notify> {
+        self.notify.clone()
+    }
+
+    /// Attempts every pending delivery once. Returns the number of reverts delivered.
+    pub async fn deliver_pending(&self) -> Result<usize> {
+        let mut delivered = 0;
+        for delivery in self.db.pending_revert_deliveries(DELIVERY_BATCH_SIZE)? {
+            match self.executor.apply_revert(&delivery, &self.labels).await {
+                Ok(()) => {
+                    self.db
+                        .record_revert_delivery(delivery.id, None, self.max_attempts)?;
+                    delivered += 1;
+                }
+                Err(e) => {
+                    tracing::warn!(
+                        "Failed to deliver revert {} of contract={}: {}",
+                        delivery.id,
+                        delivery.contract_address,
+                        e
+                    );
+                    self.db.record_revert_delivery(
+                        delivery.id,
+                        Some(&e.to_string()),
+                        self.max_attempts,
+                    )?;
+                }
+            }
+        }
+        Ok(delivered)
+    }
+
+    /// Delivers pending reverts whenever new ones are queued, retrying failed deliveries every
+    /// retry interval
+    pub async fn run(self) {
+        loop {
+            match self.deliver_pending().await {
+                Ok(count) if count > 0 => tracing::info!("Delivered {} reverts", count),
+                Ok(_) => {}
+                Err(e) => tracing::error!("Failed to deliver reverts: {}", e),
+            }
+            tokio::select! {
+                _ = self.notify.notified() => {}
+                _ = tokio::time::sleep(self.retry_interval) => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{LockedSlot, UnlockReason};
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct MockExecutor {
+        fail: Mutex<bool>,
+        applied: Mutex<Vec<(i64, String)>>,
+    }
+
+    #[async_trait]
+    impl RevertExecutor for MockExecutor {
+        async fn apply_revert(
+            &self,
+            delivery: &RevertDelivery,
+            labels: &DeploymentLabels,
+        ) -> Result<()> {
+            if *self.fail.lock().unwrap() {
+                anyhow::bail!("executor unavailable");
+            }
+            self.applied
+                .lock()
+                .unwrap()
+                .push((delivery.id, labels.instance_id.clone()));
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retries_failed_deliveries() -> Result<()> {
+        let db = Database::new(rusqlite::Connection::open_in_memory()?)?;
+        let slot = LockedSlot {
+            namespace: String::new(),
+            btc_txid: "txid1".to_string(),
+            btc_block: 200,
+            contract_address: "0x123".to_string(),
+            slot_index: vec![1],
+            revert_value: vec![4],
+            current_value: vec![7],
+            start_block: 100,
+            end_block: None,
+            unlock_reason: None,
+            metadata: Vec::new(),
+            labels: Default::default(),
+            lock_group: None,
+            alt_btc_txids: Vec::new(),
+            required_confirmed_txids: 1,
+            locked_at: None,
+            locked_by: None,
+        };
+        db.with_transaction(|tx| {
+            db.enqueue_revert_deliveries(tx, &[&slot], 150, UnlockReason::RevertThreshold)
+        })?;
+
+        let executor = Arc::new(MockExecutor::default());
+        let dispatcher =
+            RevertDispatcher::new(db.clone(), executor.clone(), Duration::from_secs(1), 3)
+                .with_labels(DeploymentLabels {
+                    instance_id: "sentinel-1".to_string(),
+                    ..Default::default()
+                });
+
+        *executor.fail.lock().unwrap() = true;
+        assert_eq!(dispatcher.deliver_pending().await?, 0);
+        assert_eq!(db.pending_revert_deliveries(10)?[0].attempts, 1);
+
+        *executor.fail.lock().unwrap() = false;
+        assert_eq!(dispatcher.deliver_pending().await?, 1);
+        assert!(db.pending_revert_deliveries(10)?.is_empty());
+        let applied = executor.applied.lock().unwrap().clone();
+        assert_eq!(applied.len(), 1);
+        assert_eq!(applied[0].1, "sentinel-1");
+
+        Ok(())
+    }
+}
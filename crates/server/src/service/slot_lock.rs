@@ -1,33 +1,870 @@
-use crate::db::{Database, SlotInsertData};
-use crate::service::bitcoin::BitcoinRpcServiceAPI;
+use crate::attestation::AttestationKey;
+use crate::commitment::{LockCommitment, LockCommitments};
+use crate::config::{IdentityConfig, ThresholdOverride};
+use crate::db::{Database, LockedSlot, SlotInsertData, SlotStore, UnlockReason};
+use crate::deployment::DeploymentLabels;
+use crate::metrics::Metrics;
+use crate::service::bitcoin::{network_name, BitcoinRpcServiceAPI, MempoolStatus};
+use crate::service::double_spend::DoubleSpendStatus;
+use crate::service::events::{SlotEventFilter, SlotEvents};
+use crate::service::identity::caller_identity;
+use crate::service::replication::Replication;
+use crate::service::status::{
+    active_lock_quota_status, batch_too_large_status, bitcoin_rpc_status,
+    current_value_mismatch_status, database_status, field, invalid_field,
+    lock_commitment_disabled_status, lock_not_committed_status, lock_tx_not_found_status,
+    no_signing_key_status, read_only_status, reservation_not_found_status, stale_btc_block_status,
+    unidentified_caller_status, FieldViolations,
+};
+use crate::service::thresholds::ContractThresholds;
+use futures::Stream;
 use hex;
+use sova_sentinel_proto::batch_size::BatchSizeHints;
 use sova_sentinel_proto::proto::{
-    get_slot_status_response, lock_slot_response,
+    get_slot_status_response::{self, ConfirmationStage},
+    lock_slot_response, slot_event,
     slot_lock_service_server::{SlotLockService, SlotLockServiceServer},
     slot_lock_status, BatchGetSlotStatusRequest, BatchGetSlotStatusResponse, BatchLockSlotRequest,
-    BatchLockSlotResponse, BatchUnlockSlotRequest, BatchUnlockSlotResponse, GetSlotStatusRequest,
-    GetSlotStatusResponse, LockSlotRequest, LockSlotResponse, SlotLockStatus,
+    BatchLockSlotResponse, BatchUnlockSlotRequest, BatchUnlockSlotResponse, CommitLockRequest,
+    CommitLockResponse, ContractSlots, FinalizeBlockRequest, FinalizeBlockResponse,
+    GetLockCommitmentRequest, GetLockCommitmentResponse, GetLockInclusionProofRequest,
+    GetLockInclusionProofResponse, GetPublicKeyRequest, GetPublicKeyResponse,
+    GetSentinelInfoRequest, GetSentinelInfoResponse, GetServerInfoRequest, GetServerInfoResponse,
+    GetSlotStatusRequest, GetSlotStatusResponse, LockSlotRequest, LockSlotResponse,
+    PrepareLockRequest, PrepareLockResponse, ReplaceLockTxRequest, ReplaceLockTxResponse, SlotData,
+    SlotEvent, SlotIdentifier, SlotLockStatus, SubscribeSlotEventsRequest, UnlockByTxidRequest,
+    UnlockByTxidResponse,
 };
+use std::collections::{HashMap, HashSet};
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+use tokio::sync::Notify;
+use tonic::metadata::MetadataMap;
 use tonic::{Request, Response, Status};
 
-pub struct SlotLockServiceImpl<B: BitcoinRpcServiceAPI> {
-    db: Database,
+/// How status queries handle a `btc_block` lower than the Bitcoin block a slot was locked at,
+/// e.g. from a client that hasn't caught up with the Bitcoin chain yet
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StaleBtcBlockPolicy {
+    /// Fail the request with `FAILED_PRECONDITION` and a `STALE_BTC_BLOCK` error reason
+    #[default]
+    Reject,
+    /// Treat the request as if no Bitcoin blocks passed since the lock
+    Clamp,
+    /// Report the slot as locked without checking or unlocking it
+    Locked,
+}
+
+impl FromStr for StaleBtcBlockPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "reject" => Ok(Self::Reject),
+            "clamp" => Ok(Self::Clamp),
+            "locked" => Ok(Self::Locked),
+            other => Err(anyhow::anyhow!(
+                "Unsupported stale btc_block policy: {}",
+                other
+            )),
+        }
+    }
+}
+
+/// A status query's `btc_block` is lower than the Bitcoin block the slot was locked at
+#[derive(Error, Debug)]
+#[error("Request btc_block {btc_block} is lower than btc_block {lock_btc_block} of the lock")]
+pub struct StaleBtcBlock {
+    pub btc_block: u64,
+    pub lock_btc_block: u64,
+}
+
+/// How a status query decides an active lock of an atomic group
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Verdict {
+    /// The lock stays locked
+    Pending,
+    /// The lock's Bitcoin transaction confirmed
+    Confirmed,
+    /// The lock is reverted for the given reason
+    Reverted(UnlockReason),
+}
+
+/// Largest number of slots accepted per batch request unless another limit is configured
+pub const DEFAULT_MAX_BATCH_SIZE: u32 = 10_000;
+
+/// Most slots listed in the log line of a batch request or response unless configured otherwise
+pub const DEFAULT_MAX_LOGGED_SLOTS: usize = 10;
+
+/// How long a `PrepareLock` reservation waits for its `CommitLock` unless configured otherwise
+pub const DEFAULT_RESERVATION_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Namespace, contract address and slot index of a lock
+type LockKey = (String, String, Vec<u8>);
+
+/// The slot lock service, over the Bitcoin node `B` and the storage backend `S`
+pub struct SlotLockServiceImpl<B: BitcoinRpcServiceAPI, S: SlotStore = Database> {
+    db: S,
     bitcoin_service: B,
     revert_threshold: u32,
+    labels: DeploymentLabels,
+    metrics: Arc<Metrics>,
+    read_only: bool,
+    shadow: bool,
+    implicit_unlocks: bool,
+    revert_notify: Option<Arc<Notify>>,
+    stale_btc_block_policy: StaleBtcBlockPolicy,
+    contract_thresholds: Arc<ContractThresholds>,
+    namespace_thresholds: HashMap<String, ThresholdOverride>,
+    batch_size_hints: BatchSizeHints,
+    max_logged_slots: usize,
+    reservation_timeout: Duration,
+    events: SlotEvents,
+    /// Locks already warned about as evicted, until their transactions are seen again
+    evicted_locks: Arc<Mutex<HashSet<LockKey>>>,
+    attestation_key: Option<AttestationKey>,
+    lock_commitments: Option<LockCommitments>,
+    replication: Option<Replication>,
+    /// Active lock limits of the callers, if any
+    identity: Option<Arc<IdentityConfig>>,
 }
 
-impl<B: BitcoinRpcServiceAPI> SlotLockServiceImpl<B> {
-    pub fn new(db: Database, bitcoin_service: B, revert_threshold: u32) -> Self {
+impl<B: BitcoinRpcServiceAPI, S: SlotStore> SlotLockServiceImpl<B, S> {
+    pub fn new(db: S, bitcoin_service: B, revert_threshold: u32) -> Self {
         Self {
             db,
             bitcoin_service,
             revert_threshold,
+            labels: DeploymentLabels::default(),
+            metrics: Arc::new(Metrics::default()),
+            read_only: false,
+            shadow: false,
+            implicit_unlocks: false,
+            revert_notify: None,
+            stale_btc_block_policy: StaleBtcBlockPolicy::default(),
+            contract_thresholds: Arc::new(ContractThresholds::default()),
+            namespace_thresholds: HashMap::new(),
+            batch_size_hints: BatchSizeHints {
+                preferred: 0,
+                max: DEFAULT_MAX_BATCH_SIZE,
+            },
+            max_logged_slots: DEFAULT_MAX_LOGGED_SLOTS,
+            reservation_timeout: DEFAULT_RESERVATION_TIMEOUT,
+            events: SlotEvents::default(),
+            evicted_locks: Arc::default(),
+            attestation_key: None,
+            lock_commitments: None,
+            replication: None,
+            identity: None,
+        }
+    }
+
+    /// Moves the service onto another store, keeping its settings
+    pub fn with_store<T: SlotStore>(self, db: T) -> SlotLockServiceImpl<B, T> {
+        SlotLockServiceImpl {
+            db,
+            bitcoin_service: self.bitcoin_service,
+            revert_threshold: self.revert_threshold,
+            labels: self.labels,
+            metrics: self.metrics,
+            read_only: self.read_only,
+            shadow: self.shadow,
+            implicit_unlocks: self.implicit_unlocks,
+            revert_notify: self.revert_notify,
+            stale_btc_block_policy: self.stale_btc_block_policy,
+            contract_thresholds: self.contract_thresholds,
+            namespace_thresholds: self.namespace_thresholds,
+            batch_size_hints: self.batch_size_hints,
+            max_logged_slots: self.max_logged_slots,
+            reservation_timeout: self.reservation_timeout,
+            events: self.events,
+            evicted_locks: self.evicted_locks,
+            attestation_key: self.attestation_key,
+            lock_commitments: self.lock_commitments,
+            replication: self.replication,
+            identity: self.identity,
         }
     }
 
+    /// Sets the deployment labels reported by `GetServerInfo`
+    pub fn with_labels(mut self, labels: DeploymentLabels) -> Self {
+        self.labels = labels;
+        self
+    }
+
+    /// Sets the metrics updated by lock operations
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Publishes lock changes to `events`, to share them with consumers besides
+    /// `SubscribeSlotEvents`
+    pub fn with_events(mut self, events: SlotEvents) -> Self {
+        self.events = events;
+        self
+    }
+
+    /// Rejects lock, unlock and replacement requests, serving status queries only
+    pub fn with_read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// Runs the sentinel in shadow mode: status queries decide and log which locks they would
+    /// unlock or revert, and report them as such, without changing the database or publishing
+    /// events. Writes are rejected as with [`Self::with_read_only`].
+    pub fn with_shadow(mut self, shadow: bool) -> Self {
+        self.shadow = shadow;
+        self
+    }
+
+    /// Lets `GetSlotStatus` and `BatchGetSlotStatus` end the locks they find settled, as they did
+    /// before `FinalizeBlock`, for nodes that don't call it yet
+    pub fn with_implicit_unlocks(mut self, implicit_unlocks: bool) -> Self {
+        self.implicit_unlocks = implicit_unlocks;
+        self
+    }
+
+    /// Rejects writes unless the sentinel is the primary of its replication pair, and fences it
+    /// when a write carries a higher epoch than its own
+    pub fn with_replication(mut self, replication: Replication) -> Self {
+        self.replication = Some(replication);
+        self
+    }
+
+    /// Queues every revert for delivery to the revert executor and wakes its dispatcher through
+    /// `notify`
+    pub fn with_revert_delivery(mut self, notify: Arc<Notify>) -> Self {
+        self.revert_notify = Some(notify);
+        self
+    }
+
+    /// Sets how status queries with a `btc_block` below the lock's are handled
+    pub fn with_stale_btc_block_policy(mut self, policy: StaleBtcBlockPolicy) -> Self {
+        self.stale_btc_block_policy = policy;
+        self
+    }
+
+    /// Applies per-contract threshold overrides, shared with the admin service that adjusts them
+    pub fn with_contract_thresholds(
+        mut self,
+        contract_thresholds: Arc<ContractThresholds>,
+    ) -> Self {
+        self.contract_thresholds = contract_thresholds;
+        self
+    }
+
+    /// Applies per-namespace threshold overrides, used for contracts without overrides of their
+    /// own
+    pub fn with_namespace_thresholds(
+        mut self,
+        namespace_thresholds: HashMap<String, ThresholdOverride>,
+    ) -> Self {
+        self.namespace_thresholds = namespace_thresholds;
+        self
+    }
+
+    /// Sets the batch sizes advertised to clients. Batch requests over `max` slots are rejected.
+    pub fn with_batch_size_hints(mut self, batch_size_hints: BatchSizeHints) -> Self {
+        self.batch_size_hints = batch_size_hints;
+        self
+    }
+
+    /// Sets how many slots the log line of a batch request or response lists before counting the
+    /// rest
+    pub fn with_max_logged_slots(mut self, max_logged_slots: usize) -> Self {
+        self.max_logged_slots = max_logged_slots;
+        self
+    }
+
+    /// Sets how long `PrepareLock` reservations are held before they are released uncommitted
+    pub fn with_reservation_timeout(mut self, timeout: Duration) -> Self {
+        self.reservation_timeout = timeout;
+        self
+    }
+
+    /// Signs status responses with `key`, whose public key is served by `GetPublicKey`
+    pub fn with_attestation_key(mut self, key: AttestationKey) -> Self {
+        self.attestation_key = Some(key);
+        self
+    }
+
+    /// Serves the lock commitments computed by a [`crate::commitment::LockCommitter`]
+    pub fn with_lock_commitments(mut self, commitments: LockCommitments) -> Self {
+        self.lock_commitments = Some(commitments);
+        self
+    }
+
+    /// Rejects locks that would take their caller past its active lock limit in `identity`
+    pub fn with_active_lock_limits(mut self, identity: IdentityConfig) -> Self {
+        self.identity = Some(Arc::new(identity));
+        self
+    }
+
     pub fn into_service(self) -> SlotLockServiceServer<Self> {
         SlotLockServiceServer::new(self)
     }
+
+    /// Returns the error for a batch of `batch_size` slots if it exceeds the maximum batch size
+    fn batch_too_large(&self, batch_size: usize) -> Option<Status> {
+        let max = self.batch_size_hints.max;
+        (max > 0 && batch_size > max as usize).then(|| batch_too_large_status(batch_size, max))
+    }
+
+    /// Returns the error for `new_locks` more locks by `caller` if they would take it past its
+    /// active lock limit. When any limit is set, unidentified callers can't lock at all.
+    fn active_lock_quota(
+        &self,
+        transaction: &S::Transaction<'_>,
+        caller: Option<&str>,
+        new_locks: usize,
+    ) -> anyhow::Result<Option<Status>> {
+        let Some(identity) = &self.identity else {
+            return Ok(None);
+        };
+        if new_locks == 0 {
+            return Ok(None);
+        }
+        let Some(caller) = caller else {
+            return Ok(identity
+                .limits_active_locks()
+                .then(unidentified_caller_status));
+        };
+        let Some(limit) = identity.active_lock_limit(caller) else {
+            return Ok(None);
+        };
+        let active = self
+            .db
+            .caller_active_locks(transaction, caller, unix_now())?;
+        Ok((active + new_locks as u64 > limit)
+            .then(|| active_lock_quota_status(caller, active, limit)))
+    }
+
+    /// Returns why a lock, unlock or replacement request must be rejected, `None` if it may write
+    fn write_rejection(&self, metadata: &MetadataMap) -> Option<Status> {
+        if self.read_only || self.shadow {
+            return Some(read_only_status());
+        }
+        self.replication.as_ref()?.write_rejection(metadata)
+    }
+
+    /// Returns why a status query must be rejected by this sentinel, `None` if it may answer it.
+    /// A query that ends the locks it finds settled is a write, and only the primary takes it.
+    fn read_rejection(
+        &self,
+        metadata: &MetadataMap,
+        current_block: u64,
+        max_staleness_blocks: Option<u64>,
+        peek: bool,
+    ) -> Option<Status> {
+        let replication = self.replication.as_ref()?;
+        if !peek && !self.shadow {
+            if let Some(status) = replication.write_rejection(metadata) {
+                return Some(status);
+            }
+        }
+        replication.read_rejection(current_block, max_staleness_blocks)
+    }
+
+    /// Runs the part of a status query that applies its decisions. The changes of a peek, or of
+    /// any query in shadow mode, are rolled back.
+    fn apply_status<T>(
+        &self,
+        peek: bool,
+        f: impl FnOnce(&S::Transaction<'_>) -> anyhow::Result<T>,
+    ) -> anyhow::Result<T> {
+        if peek || self.shadow {
+            self.db.with_rolled_back_transaction(f)
+        } else {
+            self.db.with_transaction(f)
+        }
+    }
+
+    /// Publishes the events of a status query. In shadow mode they are logged instead, and those
+    /// of a peek are dropped.
+    fn publish_status_events(&self, peek: bool, events: Vec<SlotEvent>) {
+        if peek {
+            return;
+        }
+        if !self.shadow {
+            self.events.publish(events);
+            return;
+        }
+        for event in events {
+            tracing::info!(
+                "Shadow mode, not applied: event={}, contract={}, slot={}, btc_txid={}, unlock_reason={}",
+                slot_event::Kind::try_from(event.kind)
+                    .map_or("UNKNOWN", |kind| kind.as_str_name()),
+                event.contract_address,
+                format_bytes(&event.slot_index),
+                event.btc_txid,
+                get_slot_status_response::UnlockReason::try_from(event.unlock_reason)
+                    .map_or("UNKNOWN", |reason| reason.as_str_name()),
+            );
+        }
+    }
+
+    /// Records the locks settled by a status query or FinalizeBlock in the metrics, unless its
+    /// changes were rolled back
+    fn record_settled_locks(&self, peek: bool, settled: &[SettledLock]) {
+        if peek || self.shadow {
+            return;
+        }
+        let now = unix_now();
+        for lock in settled {
+            self.metrics.record_settlement(
+                &lock.namespace,
+                &lock.contract_address,
+                lock.reason,
+                lock.btc_blocks,
+                lock.locked_at
+                    .map(|locked_at| now.saturating_sub(locked_at)),
+            );
+        }
+    }
+
+    /// Returns the latest lock commitment, signed if the sentinel has a signing key. `None` if
+    /// the sentinel doesn't compute commitments.
+    fn lock_commitment(&self) -> Option<(Arc<LockCommitment>, GetLockCommitmentResponse)> {
+        let commitment = self
+            .lock_commitments
+            .as_ref()
+            .and_then(LockCommitments::latest)?;
+        let mut response = GetLockCommitmentResponse {
+            root: commitment.root().to_vec(),
+            lock_count: commitment.lock_count() as u64,
+            computed_at: commitment.computed_at(),
+            signature: Vec::new(),
+        };
+        if let Some(key) = &self.attestation_key {
+            key.sign_lock_commitment(&mut response);
+        }
+        Some((commitment, response))
+    }
+
+    /// Wraps a batch response, attaching the batch size hints to its metadata so clients pick up
+    /// retuned limits without calling `GetServerInfo`
+    fn batch_response<T>(&self, message: T) -> Response<T> {
+        let mut response = Response::new(message);
+        self.batch_size_hints.insert_into(response.metadata_mut());
+        response
+    }
+
+    /// Queues reverted locks for the revert executor, if one is configured
+    fn enqueue_reverts(
+        &self,
+        transaction: &S::Transaction<'_>,
+        slots: &[&LockedSlot],
+        reverted_at_block: u64,
+        reason: UnlockReason,
+    ) -> anyhow::Result<()> {
+        if self.revert_notify.is_none() || slots.is_empty() {
+            return Ok(());
+        }
+        self.db
+            .enqueue_revert_deliveries(transaction, slots, reverted_at_block, reason)
+    }
+
+    /// Wakes the revert dispatcher after reverts were queued
+    fn notify_reverts(&self) {
+        if let Some(notify) = &self.revert_notify {
+            notify.notify_one();
+        }
+    }
+
+    /// Returns the number of Bitcoin blocks passed since a slot was locked, or `None` if the slot
+    /// is to be reported as locked because `btc_block` is stale
+    fn block_delta(&self, slot: &LockedSlot, btc_block: u64) -> Result<Option<u64>, StaleBtcBlock> {
+        match btc_block.checked_sub(slot.btc_block) {
+            Some(block_delta) => Ok(Some(block_delta)),
+            None => match self.stale_btc_block_policy {
+                StaleBtcBlockPolicy::Reject => Err(StaleBtcBlock {
+                    btc_block,
+                    lock_btc_block: slot.btc_block,
+                }),
+                StaleBtcBlockPolicy::Clamp => Ok(Some(0)),
+                StaleBtcBlockPolicy::Locked => Ok(None),
+            },
+        }
+    }
+
+    /// Returns the threshold overrides of a namespace, empty if it has none
+    fn namespace_thresholds(&self, namespace: &str) -> ThresholdOverride {
+        self.namespace_thresholds
+            .get(namespace)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Returns the confirmations after which a lock counts as confirmed: the override of its
+    /// contract, else of its namespace, else the sentinel-wide threshold
+    fn confirmation_threshold(&self, slot: &LockedSlot) -> u32 {
+        self.contract_thresholds
+            .get(&slot.contract_address)
+            .confirmation_threshold
+            .or(self
+                .namespace_thresholds(&slot.namespace)
+                .confirmation_threshold)
+            .unwrap_or_else(|| self.bitcoin_service.confirmation_threshold())
+    }
+
+    /// Returns the Bitcoin blocks after which an unconfirmed lock is reverted, overridden like
+    /// the confirmation threshold
+    fn revert_threshold(&self, slot: &LockedSlot) -> u64 {
+        self.contract_thresholds
+            .get(&slot.contract_address)
+            .revert_threshold
+            .or(self.namespace_thresholds(&slot.namespace).revert_threshold)
+            .unwrap_or(self.revert_threshold) as u64
+    }
+
+    /// Returns the number of Bitcoin blocks left before an active lock is reverted
+    fn blocks_until_revert(&self, slot: &LockedSlot, block_delta: u64) -> u64 {
+        (self.revert_threshold(slot) + 1).saturating_sub(block_delta)
+    }
+
+    /// Returns the recorded unlock reason of a lock unlocked by an earlier call. A lock unlocked
+    /// before reasons were recorded gets one derived from the Bitcoin block delta of this query,
+    /// which is stored so every later query for the same unlock gets the same answer, unless
+    /// `peek` is set.
+    fn recorded_unlock_reason(
+        &self,
+        slot: &LockedSlot,
+        btc_block: u64,
+        peek: bool,
+    ) -> anyhow::Result<UnlockReason> {
+        if let Some(reason) = slot.unlock_reason {
+            return Ok(reason);
+        }
+        let Some(end_block) = slot.end_block else {
+            anyhow::bail!("Slot is still locked");
+        };
+        // An unlocked slot can't be reported as locked, so a stale btc_block that isn't rejected
+        // counts as no Bitcoin blocks passed
+        let block_delta = self.block_delta(slot, btc_block)?.unwrap_or(0);
+        let reason = if block_delta > self.revert_threshold(slot) {
+            UnlockReason::RevertThreshold
+        } else {
+            UnlockReason::Confirmed
+        };
+        if peek || self.shadow {
+            return Ok(reason);
+        }
+        self.db.backfill_unlock_reason(
+            &slot.namespace,
+            &slot.contract_address,
+            &slot.slot_index,
+            end_block,
+            reason,
+        )
+    }
+
+    /// Returns whether a lock is confirmed, given the confirmations of each of its transactions in
+    /// [`LockedSlot::btc_txids`] order. The lock settles once its required number of them reach
+    /// the threshold.
+    fn is_confirmed(&self, slot: &LockedSlot, confirmations: &[u32]) -> bool {
+        settled_confirmations(slot, confirmations) >= self.confirmation_threshold(slot)
+    }
+
+    /// Returns how far an active lock is from confirming, given the confirmations of each of its
+    /// transactions. Without any confirmation, its transactions are looked up in the mempool.
+    async fn confirmation_stage(
+        &self,
+        slot: &LockedSlot,
+        confirmations: &[u32],
+    ) -> Result<ConfirmationStage, Status> {
+        if confirmations.iter().any(|confirmations| *confirmations > 0) {
+            return Ok(ConfirmationStage::Confirming);
+        }
+        let statuses = futures::future::try_join_all(
+            slot.btc_txids()
+                .map(|txid| self.bitcoin_service.mempool_status(txid)),
+        )
+        .await
+        .map_err(bitcoin_rpc_status)?;
+        Ok(if statuses.contains(&MempoolStatus::InMempool) {
+            ConfirmationStage::TxInMempool
+        } else if statuses.contains(&MempoolStatus::Evicted) {
+            ConfirmationStage::TxEvicted
+        } else {
+            ConfirmationStage::TxUnseen
+        })
+    }
+
+    /// Returns the warning event of a lock whose transactions were evicted from the mempool, only
+    /// the first time a status query finds them evicted
+    fn eviction_event(
+        &self,
+        slot: &LockedSlot,
+        stage: ConfirmationStage,
+        sova_block: u64,
+    ) -> Option<SlotEvent> {
+        let key = (
+            slot.namespace.clone(),
+            slot.contract_address.clone(),
+            slot.slot_index.clone(),
+        );
+        let mut evicted_locks = self.evicted_locks.lock().unwrap();
+        if stage != ConfirmationStage::TxEvicted {
+            evicted_locks.remove(&key);
+            return None;
+        }
+        if !evicted_locks.insert(key) {
+            return None;
+        }
+        tracing::warn!(
+            "Lock transaction evicted from the mempool: contract={}, slot={}, btc_txid={}",
+            slot.contract_address,
+            format_bytes(&slot.slot_index),
+            slot.btc_txid
+        );
+        Some(SlotEvent {
+            kind: slot_event::Kind::Evicted as i32,
+            sova_block,
+            namespace: slot.namespace.clone(),
+            contract_address: slot.contract_address.clone(),
+            slot_index: slot.slot_index.clone(),
+            revert_value: slot.revert_value.clone(),
+            current_value: slot.current_value.clone(),
+            btc_txid: slot.btc_txid.clone(),
+            btc_block: slot.btc_block,
+            lock_start_block: slot.start_block,
+            unlock_reason: get_slot_status_response::UnlockReason::Unspecified as i32,
+            locked_by: slot.locked_by.clone().unwrap_or_default(),
+        })
+    }
+
+    /// Decides each atomic group among the active `slots` as a unit: a group reverts as soon as
+    /// one of its locks reverts, and unlocks once all of its locks confirmed
+    fn group_verdicts(
+        &self,
+        slots: &[(bool, &LockedSlot)],
+        block_deltas: &[Option<u64>],
+        confirmed: &[bool],
+        double_spend_statuses: &HashMap<String, DoubleSpendStatus>,
+    ) -> HashMap<i64, Verdict> {
+        let mut verdicts = HashMap::new();
+        for (((_, slot), block_delta), is_confirmed) in
+            slots.iter().zip(block_deltas).zip(confirmed)
+        {
+            let Some(group) = slot.lock_group else {
+                continue;
+            };
+            let verdict = match block_delta {
+                None => Verdict::Pending,
+                Some(block_delta) if *block_delta > self.revert_threshold(slot) => {
+                    Verdict::Reverted(UnlockReason::RevertThreshold)
+                }
+                Some(_) if *is_confirmed => Verdict::Confirmed,
+                Some(_)
+                    if lock_double_spend_status(
+                        slot,
+                        double_spend_statuses.get(&slot.btc_txid),
+                    ) == DoubleSpendStatus::Conflicted =>
+                {
+                    Verdict::Reverted(UnlockReason::DoubleSpent)
+                }
+                Some(_) => Verdict::Pending,
+            };
+            verdicts
+                .entry(group)
+                .and_modify(|group_verdict| {
+                    *group_verdict = match (*group_verdict, verdict) {
+                        (Verdict::Reverted(reason), _) | (_, Verdict::Reverted(reason)) => {
+                            Verdict::Reverted(reason)
+                        }
+                        (Verdict::Confirmed, Verdict::Confirmed) => Verdict::Confirmed,
+                        _ => Verdict::Pending,
+                    }
+                })
+                .or_insert(verdict);
+        }
+        verdicts
+    }
+}
+
+/// Maps a failure to get the unlock reason of an unlocked slot to a gRPC status
+fn unlock_reason_status(e: anyhow::Error) -> Status {
+    match e.downcast::<StaleBtcBlock>() {
+        Ok(e) => stale_btc_block_status(e),
+        Err(e) => database_status(e),
+    }
+}
+
+/// The confirmations of a lock as a whole: those of its transaction with the most confirmations
+/// once its required number of transactions are counted, the M-th highest for M of N
+fn settled_confirmations(slot: &LockedSlot, confirmations: &[u32]) -> u32 {
+    let mut confirmations = confirmations.to_vec();
+    confirmations.sort_unstable_by(|a, b| b.cmp(a));
+    let required = slot.required_confirmed_txids.max(1) as usize;
+    confirmations.get(required - 1).copied().unwrap_or(0)
+}
+
+/// The double-spend status of a lock whose transaction has `status`. A lock isn't reverted when
+/// its transaction is double-spent while its alternatives can still make up the transactions it
+/// requires, but reported at risk until it confirms or hits the revert threshold.
+fn lock_double_spend_status(
+    slot: &LockedSlot,
+    status: Option<&DoubleSpendStatus>,
+) -> DoubleSpendStatus {
+    match status {
+        Some(DoubleSpendStatus::Conflicted)
+            if slot.alt_btc_txids.len() >= slot.required_confirmed_txids.max(1) as usize =>
+        {
+            DoubleSpendStatus::AtRisk
+        }
+        Some(status) => *status,
+        None => DoubleSpendStatus::None,
+    }
+}
+
+/// Builds a status response carrying the progress of a lock
+fn lock_progress(
+    slot: &LockedSlot,
+    confirmations: u32,
+    blocks_until_revert: u64,
+) -> GetSlotStatusResponse {
+    GetSlotStatusResponse {
+        contract_address: slot.contract_address.clone(),
+        slot_index: slot.slot_index.clone(),
+        btc_txid: slot.btc_txid.clone(),
+        btc_block: slot.btc_block,
+        start_block: slot.start_block,
+        confirmations,
+        blocks_until_revert,
+        ..Default::default()
+    }
+}
+
+/// Builds the event of a new lock
+fn lock_event(slot: &SlotInsertData) -> SlotEvent {
+    SlotEvent {
+        kind: slot_event::Kind::Locked as i32,
+        sova_block: slot.start_block,
+        namespace: slot.namespace.clone(),
+        contract_address: slot.contract_address.clone(),
+        slot_index: slot.slot_index.clone(),
+        revert_value: slot.revert_value.clone(),
+        current_value: slot.current_value.clone(),
+        btc_txid: slot.btc_txid.clone(),
+        btc_block: slot.btc_block,
+        lock_start_block: slot.start_block,
+        unlock_reason: get_slot_status_response::UnlockReason::Unspecified as i32,
+        locked_by: slot.locked_by.clone().unwrap_or_default(),
+    }
+}
+
+/// Builds the event of a lock ending at sova block `end_block`
+fn unlock_event(slot: &LockedSlot, end_block: u64, reason: UnlockReason) -> SlotEvent {
+    let kind = if reason.is_revert() {
+        slot_event::Kind::Reverted
+    } else {
+        slot_event::Kind::Unlocked
+    };
+    SlotEvent {
+        kind: kind as i32,
+        sova_block: end_block,
+        namespace: slot.namespace.clone(),
+        contract_address: slot.contract_address.clone(),
+        slot_index: slot.slot_index.clone(),
+        revert_value: slot.revert_value.clone(),
+        current_value: slot.current_value.clone(),
+        btc_txid: slot.btc_txid.clone(),
+        btc_block: slot.btc_block,
+        lock_start_block: slot.start_block,
+        unlock_reason: proto_unlock_reason(Some(reason)) as i32,
+        locked_by: slot.locked_by.clone().unwrap_or_default(),
+    }
+}
+
+/// A lock unlocked or reverted by a status query or FinalizeBlock, recorded in the metrics once
+/// the decision commits
+struct SettledLock {
+    namespace: String,
+    contract_address: String,
+    reason: UnlockReason,
+    /// Bitcoin blocks passed since the lock was made
+    btc_blocks: u64,
+    /// Unix time the lock was made at, if known
+    locked_at: Option<u64>,
+}
+
+impl SettledLock {
+    fn new(slot: &LockedSlot, reason: UnlockReason, btc_blocks: u64) -> Self {
+        Self {
+            namespace: slot.namespace.clone(),
+            contract_address: slot.contract_address.clone(),
+            reason,
+            btc_blocks,
+            locked_at: slot.locked_at,
+        }
+    }
+}
+
+/// Expands slots grouped by contract into the flat slot list of a batch lock request
+fn flatten_contract_slots(contract_slots: Vec<ContractSlots>) -> impl Iterator<Item = SlotData> {
+    contract_slots.into_iter().flat_map(|group| {
+        let contract_address = group.contract_address;
+        group.slots.into_iter().map(move |slot| SlotData {
+            contract_address: contract_address.clone(),
+            slot_index: slot.slot_index,
+            revert_value: slot.revert_value,
+            current_value: slot.current_value,
+            btc_txid: slot.btc_txid,
+            metadata: slot.metadata,
+            labels: slot.labels,
+            alt_btc_txids: slot.alt_btc_txids,
+            required_confirmed_txids: slot.required_confirmed_txids,
+        })
+    })
+}
+
+/// Builds the status response of a lock that was unlocked by an earlier call. The response only
+/// depends on the stored lock, so replaying a query always gets the same answer.
+fn unlocked_response(slot: &LockedSlot, reason: UnlockReason) -> GetSlotStatusResponse {
+    let (revert_value, current_value) = if reason.is_revert() {
+        (slot.revert_value.clone(), slot.current_value.clone())
+    } else {
+        (Vec::new(), Vec::new())
+    };
+    GetSlotStatusResponse {
+        status: unlocked_status(reason) as i32,
+        revert_value,
+        current_value,
+        unlock_reason: proto_unlock_reason(Some(reason)) as i32,
+        ..lock_progress(slot, 0, 0)
+    }
+}
+
+/// Returns the status reported for a lock unlocked for the given reason
+fn unlocked_status(reason: UnlockReason) -> get_slot_status_response::Status {
+    match reason {
+        UnlockReason::RevertThreshold | UnlockReason::ManualRevert => {
+            get_slot_status_response::Status::Reverted
+        }
+        UnlockReason::DoubleSpent => get_slot_status_response::Status::DoubleSpent,
+        UnlockReason::Confirmed | UnlockReason::Manual | UnlockReason::Admin => {
+            get_slot_status_response::Status::Unlocked
+        }
+    }
+}
+
+fn proto_unlock_reason(reason: Option<UnlockReason>) -> get_slot_status_response::UnlockReason {
+    match reason {
+        None => get_slot_status_response::UnlockReason::Unspecified,
+        Some(UnlockReason::Confirmed) => get_slot_status_response::UnlockReason::Confirmed,
+        Some(UnlockReason::RevertThreshold) => {
+            get_slot_status_response::UnlockReason::RevertThreshold
+        }
+        Some(UnlockReason::DoubleSpent) => get_slot_status_response::UnlockReason::DoubleSpent,
+        Some(UnlockReason::Manual) => get_slot_status_response::UnlockReason::Manual,
+        Some(UnlockReason::Admin) => get_slot_status_response::UnlockReason::Admin,
+        Some(UnlockReason::ManualRevert) => get_slot_status_response::UnlockReason::ManualRevert,
+    }
 }
 
 // Add this helper function near the top of the file, after the imports
@@ -47,6 +884,33 @@ fn format_bytes(bytes: &[u8]) -> String {
     }
 }
 
+/// Slots of a batch as listed in a log line: the first few, then a count of the others, so large
+/// batches don't flood the logs
+struct LoggedSlots<T> {
+    listed: Vec<T>,
+    total: usize,
+}
+
+impl<T> LoggedSlots<T> {
+    fn new<'a, S>(slots: &'a [S], max: usize, format: impl FnMut(&'a S) -> T) -> Self {
+        Self {
+            listed: slots.iter().take(max).map(format).collect(),
+            total: slots.len(),
+        }
+    }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for LoggedSlots<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut list = f.debug_list();
+        list.entries(&self.listed);
+        if self.total > self.listed.len() {
+            list.entry(&format_args!("... {} more", self.total - self.listed.len()));
+        }
+        list.finish()
+    }
+}
+
 // Add this helper struct for better debug formatting
 #[derive(Debug)]
 #[allow(dead_code)]
@@ -57,7 +921,7 @@ struct FormattedSlot<'a> {
 }
 
 impl<'a> FormattedSlot<'a> {
-    fn from_request_slot(slot: &'a sova_sentinel_proto::proto::SlotData) -> Self {
+    fn from_request_slot(slot: &'a SlotData) -> Self {
         Self {
             contract_address: &slot.contract_address,
             slot_index: format_bytes(&slot.slot_index),
@@ -65,7 +929,7 @@ impl<'a> FormattedSlot<'a> {
         }
     }
 
-    fn from_identifier(slot: &'a sova_sentinel_proto::proto::SlotIdentifier) -> Self {
+    fn from_identifier(slot: &'a SlotIdentifier) -> Self {
         Self {
             contract_address: &slot.contract_address,
             slot_index: format_bytes(&slot.slot_index),
@@ -74,11 +938,33 @@ impl<'a> FormattedSlot<'a> {
     }
 }
 
+/// Seconds since the Unix epoch, the clock reservations expire by
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// Decodes a hex-encoded raw transaction and checks that it is the lock's Bitcoin transaction
+fn decode_lock_transaction(raw_tx_hex: &str, btc_txid: &str) -> anyhow::Result<Vec<u8>> {
+    let raw_tx =
+        hex::decode(raw_tx_hex).map_err(|e| anyhow::anyhow!("Invalid raw_tx_hex: {}", e))?;
+    let tx: bitcoin::Transaction = bitcoin::consensus::deserialize(&raw_tx)
+        .map_err(|e| anyhow::anyhow!("Invalid raw transaction: {}", e))?;
+    let txid = tx.compute_txid().to_string();
+    if txid != btc_txid {
+        anyhow::bail!("Raw transaction has txid {}, expected {}", txid, btc_txid);
+    }
+    Ok(raw_tx)
+}
+
 // Add these helper functions after the imports
 fn lock_status_to_string(status: i32) -> &'static str {
     match status {
         x if x == slot_lock_status::Status::Locked as i32 => "Locked",
         x if x == slot_lock_status::Status::AlreadyLocked as i32 => "AlreadyLocked",
+        x if x == slot_lock_status::Status::NotLocked as i32 => "NotLocked",
+        x if x == slot_lock_status::Status::Reserved as i32 => "Reserved",
         _ => "Unknown",
     }
 }
@@ -88,168 +974,176 @@ fn get_status_to_string(status: i32) -> &'static str {
         x if x == get_slot_status_response::Status::Unlocked as i32 => "Unlocked",
         x if x == get_slot_status_response::Status::Locked as i32 => "Locked",
         x if x == get_slot_status_response::Status::Reverted as i32 => "Reverted",
+        x if x == get_slot_status_response::Status::AtRisk as i32 => "AtRisk",
+        x if x == get_slot_status_response::Status::DoubleSpent as i32 => "DoubleSpent",
         _ => "Unknown",
     }
 }
 
-#[tonic::async_trait]
-impl<B: BitcoinRpcServiceAPI + 'static> SlotLockService for SlotLockServiceImpl<B> {
-    async fn lock_slot(
+impl<B: BitcoinRpcServiceAPI + 'static, S: SlotStore> SlotLockServiceImpl<B, S> {
+    /// Reports the status of a slot, unlocking or reverting its lock as decided unless `peek` is
+    /// set
+    async fn slot_status(
         &self,
-        request: Request<LockSlotRequest>,
-    ) -> Result<Response<LockSlotResponse>, Status> {
-        let req = request.into_inner();
+        request: Request<GetSlotStatusRequest>,
+        peek: bool,
+    ) -> Result<Response<GetSlotStatusResponse>, Status> {
+        if let Some(status) = self.read_rejection(
+            request.metadata(),
+            request.get_ref().current_block,
+            request.get_ref().max_staleness_blocks,
+            peek,
+        ) {
+            return Err(status);
+        }
+        let mut req = request.into_inner();
+        let mut violations = FieldViolations::default();
+        violations.check_namespace(&req.namespace);
+        violations.check_slot("", &req.contract_address, &mut req.slot_index);
+        if let Some(status) = violations.into_status() {
+            return Err(status);
+        }
 
         tracing::info!(
-            "LockSlot request: contract={}, slot={}, locked_at_block={}, btc_block={}, btc_txid={}",
+            "GetSlotStatus request: contract={}, slot={}, current_block={}, btc_block={}",
             req.contract_address,
             format_bytes(&req.slot_index),
-            req.locked_at_block,
-            req.btc_block,
-            req.btc_txid
+            req.current_block,
+            req.btc_block
         );
 
-        let result = self
+        // Get slot info for Bitcoin RPC calls
+        let slot = self
             .db
-            .with_transaction(|transaction| {
-                // Check if slot is already locked within the transaction
-                let is_locked = self
-                    .db
-                    .is_slot_locked_with_transaction(
-                        transaction,
-                        &req.contract_address,
-                        &req.slot_index,
-                    )
-                    .map_err(|e| anyhow::anyhow!("Database error: {}", e))?;
+            .get_slot(
+                &req.namespace,
+                &req.contract_address,
+                &req.slot_index,
+                req.current_block,
+            )
+            .map_err(database_status)?;
 
-                if is_locked {
-                    return Ok(lock_slot_response::Status::AlreadyLocked as i32);
-                }
+        // Early return if no slot found
+        let Some(slot_info) = slot else {
+            return Ok(Response::new(GetSlotStatusResponse {
+                status: get_slot_status_response::Status::Unlocked as i32,
+                contract_address: req.contract_address,
+                slot_index: req.slot_index,
+                ..Default::default()
+            }));
+        };
 
-                // Try to parse slot_index as u64 for optional integer storage
-                let slot_index_int = if req.slot_index.len() <= 8 {
-                    let mut bytes = [0u8; 8];
-                    bytes[8 - req.slot_index.len()..].copy_from_slice(&req.slot_index);
-                    Some(i64::from_be_bytes(bytes))
-                } else {
-                    None
-                };
+        // If the slot was already unlocked in a previous call (end_block is set), report the
+        // status recorded with the unlock, so the same request always gets the same response
+        if slot_info.end_block.is_some() {
+            let reason = self
+                .recorded_unlock_reason(&slot_info, req.btc_block, peek)
+                .map_err(unlock_reason_status)?;
+            return Ok(Response::new(unlocked_response(&slot_info, reason)));
+        }
 
-                // Insert new lock
-                let slot = SlotInsertData {
-                    contract_address: req.contract_address.clone(),
-                    start_block: req.locked_at_block,
-                    btc_block: req.btc_block,
-                    slot_index: req.slot_index.clone(),
-                    slot_index_int,
-                    btc_txid: req.btc_txid.clone(),
-                    revert_value: req.revert_value.clone(),
-                    current_value: req.current_value.clone(),
-                };
-                self.db.insert_slot_lock(transaction, &slot)?;
+        // A lock of an atomic group is decided together with the rest of its group
+        if slot_info.lock_group.is_some() {
+            let response = self
+                .batch_slot_status(
+                    Request::new(BatchGetSlotStatusRequest {
+                        namespace: req.namespace,
+                        current_block: req.current_block,
+                        btc_block: req.btc_block,
+                        slots: vec![SlotIdentifier {
+                            contract_address: req.contract_address,
+                            slot_index: req.slot_index,
+                        }],
+                        max_staleness_blocks: req.max_staleness_blocks,
+                    }),
+                    peek,
+                )
+                .await?;
+            return response
+                .into_inner()
+                .slots
+                .pop()
+                .map(Response::new)
+                .ok_or_else(|| Status::internal("Missing status of the requested slot"));
+        }
 
-                Ok(lock_slot_response::Status::Locked as i32)
-            })
-            .map_err(|e| Status::internal(format!("Database error: {}", e)))?;
-
-        tracing::info!(
-            "LockSlot response: contract={}, slot={}, status={}",
-            req.contract_address,
-            format_bytes(&req.slot_index),
-            lock_status_to_string(result)
-        );
-
-        Ok(Response::new(LockSlotResponse {
-            status: result,
-            contract_address: req.contract_address,
-            slot_index: req.slot_index,
-        }))
-    }
-
-    async fn get_slot_status(
-        &self,
-        request: Request<GetSlotStatusRequest>,
-    ) -> Result<Response<GetSlotStatusResponse>, Status> {
-        let req = request.into_inner();
-
-        tracing::info!(
-            "GetSlotStatus request: contract={}, slot={}, current_block={}, btc_block={}",
-            req.contract_address,
-            format_bytes(&req.slot_index),
-            req.current_block,
-            req.btc_block
-        );
-
-        // Get slot info for Bitcoin RPC calls
-        let slot = self
-            .db
-            .get_slot(&req.contract_address, &req.slot_index, req.current_block)
-            .map_err(|e| Status::internal(format!("Database error: {}", e)))?;
-
-        // Early return if no slot found
-        let Some(slot_info) = slot else {
-            return Ok(Response::new(GetSlotStatusResponse {
-                status: get_slot_status_response::Status::Unlocked as i32,
-                contract_address: req.contract_address,
-                slot_index: req.slot_index,
-                revert_value: Vec::new(),
-                current_value: Vec::new(),
-            }));
-        };
-
-        let block_delta = req.btc_block - slot_info.btc_block;
-
-        // Check if slot was already unlocked in a previous call (end_block is set)
-        // If so, we need to return a consistent status based on when it was unlocked:
-        // - Reverted: if the unlock happened due to exceeding the revert threshold
-        // - Unlocked: if the unlock happened due to successful BTC confirmation
-        // This ensures the same request always gets the same response after unlock
-        if slot_info.end_block.is_some() {
-            let status = if block_delta > self.revert_threshold as u64 {
-                get_slot_status_response::Status::Reverted as i32
-            } else {
-                get_slot_status_response::Status::Unlocked as i32
-            };
-
-            return Ok(Response::new(GetSlotStatusResponse {
-                status,
-                contract_address: req.contract_address,
-                slot_index: req.slot_index,
-                revert_value: Vec::new(),
-                current_value: Vec::new(),
-            }));
-        }
-
-        // Check confirmation status if slot exists and is not unlocked
-        let confirmation_status = self
-            .bitcoin_service
-            .is_tx_confirmed(&slot_info.btc_txid)
-            .await
-            .map_err(|e| Status::internal(format!("Bitcoin RPC error: {}", e)))?;
+        let Some(block_delta) = self
+            .block_delta(&slot_info, req.btc_block)
+            .map_err(stale_btc_block_status)?
+        else {
+            tracing::debug!(
+                "Stale btc_block, reporting slot as locked: contract={}, slot={}, btc_block={}, lock_btc_block={}",
+                req.contract_address,
+                format_bytes(&req.slot_index),
+                req.btc_block,
+                slot_info.btc_block
+            );
+            return Ok(Response::new(GetSlotStatusResponse {
+                status: get_slot_status_response::Status::Locked as i32,
+                ..lock_progress(&slot_info, 0, self.blocks_until_revert(&slot_info, 0))
+            }));
+        };
+        let revert_threshold = self.revert_threshold(&slot_info);
+
+        // Check confirmation status if slot exists and is not unlocked, of the lock's transaction
+        // and its alternatives
+        let tx_confirmations = futures::future::try_join_all(
+            slot_info
+                .btc_txids()
+                .map(|txid| self.bitcoin_service.get_confirmations(txid)),
+        )
+        .await
+        .map_err(bitcoin_rpc_status)?;
+        let confirmations = settled_confirmations(&slot_info, &tx_confirmations);
+        let confirmation_status = self.is_confirmed(&slot_info, &tx_confirmations);
 
         tracing::debug!(
-            "Bitcoin tx confirmation check: txid={}, confirmed={}",
+            "Bitcoin tx confirmation check: txid={}, confirmations={}, confirmed={}",
             slot_info.btc_txid,
+            confirmations,
             confirmation_status
         );
 
-        // Do everything else within a transaction
-        let (status, revert_value, current_value) = self
-            .db
-            .with_transaction(|transaction| {
-                let slot = self
-                    .db
-                    .get_slot_with_transaction(
-                        transaction,
-                        &req.contract_address,
-                        &req.slot_index,
-                        req.current_block,
-                    )
-                    .map_err(|e| anyhow::anyhow!("Database error: {}", e))?;
+        // Only unconfirmed locks that haven't hit the revert threshold can still be double-spent,
+        // and stay locked
+        let (double_spend_status, confirmation_stage) =
+            if !confirmation_status && block_delta <= revert_threshold {
+                let status = self
+                    .bitcoin_service
+                    .check_double_spend(&slot_info.btc_txid)
+                    .await
+                    .map_err(bitcoin_rpc_status)?;
+                (
+                    lock_double_spend_status(&slot_info, Some(&status)),
+                    self.confirmation_stage(&slot_info, &tx_confirmations)
+                        .await?,
+                )
+            } else {
+                (DoubleSpendStatus::None, ConfirmationStage::Unspecified)
+            };
+
+        // Do everything else within a transaction. A peek doesn't count as having warned about an
+        // eviction.
+        let mut events: Vec<_> = if peek {
+            Vec::new()
+        } else {
+            self.eviction_event(&slot_info, confirmation_stage, req.current_block)
+                .into_iter()
+                .collect()
+        };
+        let (status, unlock_reason, revert_value, current_value) = self
+            .apply_status(peek, |transaction| {
+                let slot = self.db.get_slot_with_transaction(
+                    transaction,
+                    &req.namespace,
+                    &req.contract_address,
+                    &req.slot_index,
+                    req.current_block,
+                )?;
 
                 match slot {
                     Some(slot) => {
-                        if block_delta > self.revert_threshold as u64 {
+                        if block_delta > revert_threshold {
                             tracing::debug!(
                                 "Reverting slot: contract={}, slot={}, btc_blocks_passed={}",
                                 req.contract_address,
@@ -258,12 +1152,26 @@ impl<B: BitcoinRpcServiceAPI + 'static> SlotLockService for SlotLockServiceImpl<
                             );
                             self.db.unlock_slot_with_transaction(
                                 transaction,
+                                &req.namespace,
                                 &req.contract_address,
                                 &req.slot_index,
                                 req.current_block,
+                                UnlockReason::RevertThreshold,
+                            )?;
+                            events.push(unlock_event(
+                                &slot,
+                                req.current_block,
+                                UnlockReason::RevertThreshold,
+                            ));
+                            self.enqueue_reverts(
+                                transaction,
+                                &[&slot],
+                                req.current_block,
+                                UnlockReason::RevertThreshold,
                             )?;
                             Ok((
                                 get_slot_status_response::Status::Reverted as i32,
+                                Some(UnlockReason::RevertThreshold),
                                 slot.revert_value,
                                 slot.current_value,
                             ))
@@ -275,12 +1183,89 @@ impl<B: BitcoinRpcServiceAPI + 'static> SlotLockService for SlotLockServiceImpl<
                             );
                             self.db.unlock_slot_with_transaction(
                                 transaction,
+                                &req.namespace,
                                 &req.contract_address,
                                 &req.slot_index,
                                 req.current_block,
+                                UnlockReason::Confirmed,
                             )?;
+                            events.push(unlock_event(
+                                &slot,
+                                req.current_block,
+                                UnlockReason::Confirmed,
+                            ));
                             Ok((
                                 get_slot_status_response::Status::Unlocked as i32,
+                                Some(UnlockReason::Confirmed),
+                                Vec::new(),
+                                Vec::new(),
+                            ))
+                        } else if double_spend_status == DoubleSpendStatus::Conflicted {
+                            tracing::warn!(
+                                "Reverting double-spent slot: contract={}, slot={}, btc_txid={}",
+                                req.contract_address,
+                                format_bytes(&req.slot_index),
+                                slot.btc_txid
+                            );
+                            self.db.unlock_slot_with_transaction(
+                                transaction,
+                                &req.namespace,
+                                &req.contract_address,
+                                &req.slot_index,
+                                req.current_block,
+                                UnlockReason::DoubleSpent,
+                            )?;
+                            events.push(unlock_event(
+                                &slot,
+                                req.current_block,
+                                UnlockReason::DoubleSpent,
+                            ));
+                            self.enqueue_reverts(
+                                transaction,
+                                &[&slot],
+                                req.current_block,
+                                UnlockReason::DoubleSpent,
+                            )?;
+                            Ok((
+                                get_slot_status_response::Status::DoubleSpent as i32,
+                                Some(UnlockReason::DoubleSpent),
+                                slot.revert_value,
+                                slot.current_value,
+                            ))
+                        } else if let DoubleSpendStatus::Replaced(new_txid) = double_spend_status {
+                            tracing::info!(
+                                "Slot transaction replaced: contract={}, slot={}, btc_txid={}, new_btc_txid={}",
+                                req.contract_address,
+                                format_bytes(&req.slot_index),
+                                slot.btc_txid,
+                                new_txid
+                            );
+                            self.db.replace_lock_txid(
+                                transaction,
+                                &slot.namespace,
+                                &slot.btc_txid,
+                                &new_txid.to_string(),
+                                true,
+                            )?;
+                            Ok((
+                                get_slot_status_response::Status::Locked as i32,
+                                None,
+                                Vec::new(),
+                                Vec::new(),
+                            ))
+                        } else if double_spend_status == DoubleSpendStatus::AtRisk
+                            || confirmation_stage == ConfirmationStage::TxEvicted
+                        {
+                            tracing::warn!(
+                                "Slot at risk of double-spend: contract={}, slot={}, btc_txid={}, evicted={}",
+                                req.contract_address,
+                                format_bytes(&req.slot_index),
+                                slot.btc_txid,
+                                confirmation_stage == ConfirmationStage::TxEvicted
+                            );
+                            Ok((
+                                get_slot_status_response::Status::AtRisk as i32,
+                                None,
                                 Vec::new(),
                                 Vec::new(),
                             ))
@@ -293,6 +1278,7 @@ impl<B: BitcoinRpcServiceAPI + 'static> SlotLockService for SlotLockServiceImpl<
                             );
                             Ok((
                                 get_slot_status_response::Status::Locked as i32,
+                                None,
                                 Vec::new(),
                                 Vec::new(),
                             ))
@@ -306,13 +1292,24 @@ impl<B: BitcoinRpcServiceAPI + 'static> SlotLockService for SlotLockServiceImpl<
                         );
                         Ok((
                             get_slot_status_response::Status::Unlocked as i32,
+                            None,
                             Vec::new(),
                             Vec::new(),
                         ))
                     }
                 }
             })
-            .map_err(|e| Status::internal(format!("{}", e)))?;
+            .map_err(database_status)?;
+        self.publish_status_events(peek, events);
+        if let Some(reason) = unlock_reason {
+            self.record_settled_locks(peek, &[SettledLock::new(&slot_info, reason, block_delta)]);
+        }
+
+        if status == get_slot_status_response::Status::Reverted as i32
+            || status == get_slot_status_response::Status::DoubleSpent as i32
+        {
+            self.notify_reverts();
+        }
 
         tracing::info!(
             "GetSlotStatus response: contract={}, slot={}, status={}",
@@ -321,148 +1318,89 @@ impl<B: BitcoinRpcServiceAPI + 'static> SlotLockService for SlotLockServiceImpl<
             get_status_to_string(status)
         );
 
-        Ok(Response::new(GetSlotStatusResponse {
+        let still_locked = status == get_slot_status_response::Status::Locked as i32
+            || status == get_slot_status_response::Status::AtRisk as i32;
+        let blocks_until_revert = if still_locked {
+            self.blocks_until_revert(&slot_info, block_delta)
+        } else {
+            0
+        };
+        let mut response = GetSlotStatusResponse {
             status,
-            contract_address: req.contract_address,
-            slot_index: req.slot_index,
             revert_value,
             current_value,
-        }))
-    }
-
-    async fn batch_lock_slot(
-        &self,
-        request: Request<BatchLockSlotRequest>,
-    ) -> Result<Response<BatchLockSlotResponse>, Status> {
-        let req = request.into_inner();
-
-        // Return early if slots array is empty
-        if req.slots.is_empty() {
-            return Ok(Response::new(BatchLockSlotResponse { slots: vec![] }));
+            unlock_reason: proto_unlock_reason(unlock_reason) as i32,
+            ..lock_progress(&slot_info, confirmations, blocks_until_revert)
+        };
+        if still_locked {
+            response.confirmation_stage = confirmation_stage as i32;
+            response.required_confirmations = self.confirmation_threshold(&slot_info);
+        }
+        if let DoubleSpendStatus::Replaced(new_txid) = double_spend_status {
+            response.btc_txid = new_txid.to_string();
         }
 
-        // Log the request payload with formatted slots
-        let formatted_slots: Vec<_> = req
-            .slots
-            .iter()
-            .map(FormattedSlot::from_request_slot)
-            .collect();
-
-        tracing::info!(
-            "BatchLockSlot request: locked_at_block={}, btc_block={}, slots={:#?}",
-            req.locked_at_block,
-            req.btc_block,
-            formatted_slots
-        );
-
-        let result = self
-            .db
-            .with_transaction(|transaction| {
-                // Get all slot locks in one query
-                let slots_to_check: Vec<_> = req
-                    .slots
-                    .iter()
-                    .map(|slot| (slot.contract_address.as_str(), slot.slot_index.as_slice()))
-                    .collect();
-
-                let existing_slots = self.db.batch_get_locked_slots(
-                    transaction,
-                    &slots_to_check,
-                    req.locked_at_block,
-                )?;
-
-                let mut responses = Vec::with_capacity(req.slots.len());
-                let mut slots_to_insert = Vec::with_capacity(req.slots.len());
-
-                // Process each slot using the batch query results
-                for (idx, slot) in req.slots.iter().enumerate() {
-                    if existing_slots[idx].is_some() {
-                        responses.push(SlotLockStatus {
-                            contract_address: slot.contract_address.clone(),
-                            slot_index: slot.slot_index.clone(),
-                            status: slot_lock_status::Status::AlreadyLocked as i32,
-                        });
-                        continue;
-                    }
-
-                    // Try to parse slot_index as u64 for optional integer storage
-                    let slot_index_int = if slot.slot_index.len() <= 8 {
-                        let mut bytes = [0u8; 8];
-                        bytes[8 - slot.slot_index.len()..].copy_from_slice(&slot.slot_index);
-                        Some(i64::from_be_bytes(bytes))
-                    } else {
-                        None
-                    };
-
-                    slots_to_insert.push(SlotInsertData {
-                        contract_address: slot.contract_address.clone(),
-                        start_block: req.locked_at_block,
-                        btc_block: req.btc_block,
-                        slot_index: slot.slot_index.clone(),
-                        slot_index_int,
-                        btc_txid: slot.btc_txid.clone(),
-                        revert_value: slot.revert_value.clone(),
-                        current_value: slot.current_value.clone(),
-                    });
-
-                    responses.push(SlotLockStatus {
-                        contract_address: slot.contract_address.clone(),
-                        slot_index: slot.slot_index.clone(),
-                        status: slot_lock_status::Status::Locked as i32,
-                    });
-                }
-
-                // Insert all slots that can be locked
-                if !slots_to_insert.is_empty() {
-                    self.db
-                        .batch_insert_slot_locks(transaction, &slots_to_insert)?;
-                }
-
-                Ok(responses)
-            })
-            .map_err(|e| Status::internal(format!("Database error: {}", e)))?;
-
-        // Format the response slots
-        let formatted_response: Vec<_> = result
-            .iter()
-            .map(|status| {
-                format!(
-                    "{{ contract: {}, slot: {}, status: {} }}",
-                    status.contract_address,
-                    format_bytes(&status.slot_index),
-                    lock_status_to_string(status.status)
-                )
-            })
-            .collect();
-
-        tracing::info!("BatchLockSlot response: slots={:#?}", formatted_response);
-
-        Ok(Response::new(BatchLockSlotResponse { slots: result }))
+        Ok(Response::new(response))
     }
 
-    async fn batch_get_slot_status(
+    /// Reports the status of a batch of slots, unlocking or reverting their locks as decided unless
+    /// `peek` is set
+    async fn batch_slot_status(
         &self,
         request: Request<BatchGetSlotStatusRequest>,
+        peek: bool,
     ) -> Result<Response<BatchGetSlotStatusResponse>, Status> {
-        let req = request.into_inner();
+        if let Some(status) = self.read_rejection(
+            request.metadata(),
+            request.get_ref().current_block,
+            request.get_ref().max_staleness_blocks,
+            peek,
+        ) {
+            return Err(status);
+        }
+        let mut req = request.into_inner();
+        if let Some(status) = self.batch_too_large(req.slots.len()) {
+            return Err(status);
+        }
+        let mut violations = FieldViolations::default();
+        violations.check_namespace(&req.namespace);
+        for (idx, slot) in req.slots.iter_mut().enumerate() {
+            violations.check_slot(
+                &format!("slots[{}]", idx),
+                &slot.contract_address,
+                &mut slot.slot_index,
+            );
+        }
+        if let Some(status) = violations.into_status() {
+            return Err(status);
+        }
+        self.evaluate_slots(req, peek).await
+    }
 
+    /// Decides the locks of validated slots, ending those that settled unless `peek` is set
+    async fn evaluate_slots(
+        &self,
+        req: BatchGetSlotStatusRequest,
+        peek: bool,
+    ) -> Result<Response<BatchGetSlotStatusResponse>, Status> {
         // Return early if slots array is empty
         if req.slots.is_empty() {
-            return Ok(Response::new(BatchGetSlotStatusResponse { slots: vec![] }));
+            return Ok(self.batch_response(BatchGetSlotStatusResponse {
+                slots: vec![],
+                signature: Vec::new(),
+            }));
         }
 
         // Log the request payload with formatted slots
-        let formatted_slots: Vec<_> = req
-            .slots
-            .iter()
-            .map(FormattedSlot::from_identifier)
-            .collect();
-
         tracing::info!(
-            "BatchGetSlotStatus request: current_block={}, btc_block={}, slots={:#?}",
+            "BatchGetSlotStatus request: current_block={}, btc_block={}, slots={:?}",
             req.current_block,
             req.btc_block,
-            formatted_slots
+            LoggedSlots::new(
+                &req.slots,
+                self.max_logged_slots,
+                FormattedSlot::from_identifier
+            )
         );
 
         // Convert slots to database format
@@ -472,13 +1410,30 @@ impl<B: BitcoinRpcServiceAPI + 'static> SlotLockService for SlotLockServiceImpl<
             .map(|slot| (slot.contract_address.as_str(), slot.slot_index.as_slice()))
             .collect();
 
-        let existing_slots = self
+        let (existing_slots, group_locks) = self
             .db
             .with_transaction(|transaction| {
-                self.db
-                    .batch_get_locked_slots(transaction, &slots, req.current_block)
+                let existing_slots = self.db.batch_get_locked_slots(
+                    transaction,
+                    &req.namespace,
+                    &slots,
+                    req.current_block,
+                )?;
+                let groups: std::collections::BTreeSet<_> = existing_slots
+                    .iter()
+                    .flatten()
+                    .filter(|slot| slot.end_block.is_none())
+                    .filter_map(|slot| slot.lock_group)
+                    .collect();
+                let group_locks = if groups.is_empty() {
+                    Vec::new()
+                } else {
+                    let groups: Vec<_> = groups.into_iter().collect();
+                    self.db.active_group_locks(transaction, &groups)?
+                };
+                Ok((existing_slots, group_locks))
             })
-            .map_err(|e| Status::internal(format!("Database error: {}", e)))?;
+            .map_err(database_status)?;
 
         // Filter slots into unlocked (slots unlocked at this sova block) and locked arrays
         let (unlocked_slots, active_slots): (Vec<_>, Vec<_>) = existing_slots
@@ -488,33 +1443,15 @@ impl<B: BitcoinRpcServiceAPI + 'static> SlotLockService for SlotLockServiceImpl<
             .filter_map(|(idx, slot)| slot.as_ref().map(|s| (idx, s)))
             .partition(|(_, slot)| slot.end_block.is_some());
 
-        // For unlocked slots, check if they were reverted
+        // For unlocked slots, report the status recorded with the unlock
         let mut initial_slots: Vec<GetSlotStatusResponse> = unlocked_slots
             .iter()
             .map(|(_, slot)| {
-                let block_delta = req.btc_block - slot.btc_block;
-
-                GetSlotStatusResponse {
-                    status: if block_delta > self.revert_threshold as u64 {
-                        get_slot_status_response::Status::Reverted as i32
-                    } else {
-                        get_slot_status_response::Status::Unlocked as i32
-                    },
-                    contract_address: slot.contract_address.clone(),
-                    slot_index: slot.slot_index.clone(),
-                    revert_value: if block_delta > self.revert_threshold as u64 {
-                        slot.revert_value.clone()
-                    } else {
-                        Vec::new()
-                    },
-                    current_value: if block_delta > self.revert_threshold as u64 {
-                        slot.current_value.clone()
-                    } else {
-                        Vec::new()
-                    },
-                }
+                self.recorded_unlock_reason(slot, req.btc_block, peek)
+                    .map(|reason| unlocked_response(slot, reason))
             })
-            .collect();
+            .collect::<anyhow::Result<_>>()
+            .map_err(unlock_reason_status)?;
 
         // Add responses for slots that were never locked
         let mut not_locked_responses: Vec<GetSlotStatusResponse> = req
@@ -526,8 +1463,7 @@ impl<B: BitcoinRpcServiceAPI + 'static> SlotLockService for SlotLockServiceImpl<
                 status: get_slot_status_response::Status::Unlocked as i32,
                 contract_address: slot_req.contract_address.clone(),
                 slot_index: slot_req.slot_index.clone(),
-                revert_value: Vec::new(),
-                current_value: Vec::new(),
+                ..Default::default()
             })
             .collect();
 
@@ -545,24 +1481,52 @@ impl<B: BitcoinRpcServiceAPI + 'static> SlotLockService for SlotLockServiceImpl<
                 )
             };
 
-            let formatted_response: Vec<_> =
-                initial_slots.iter().map(format_response_slot).collect();
-
             tracing::info!(
-                "BatchGetSlotStatus response: slots={:#?}",
-                formatted_response
+                "BatchGetSlotStatus response: slots={:?}",
+                LoggedSlots::new(&initial_slots, self.max_logged_slots, format_response_slot)
             );
 
-            return Ok(Response::new(BatchGetSlotStatusResponse {
+            return Ok(self.batch_response(BatchGetSlotStatusResponse {
                 slots: initial_slots,
+                signature: Vec::new(),
             }));
         }
 
+        // The locks of an atomic group are decided together, so the rest of the groups of the
+        // requested slots is evaluated as well, without being reported
+        let mut active_slots: Vec<(bool, &LockedSlot)> = active_slots
+            .into_iter()
+            .map(|(_, slot)| (true, slot))
+            .collect();
+        let requested: std::collections::HashSet<_> = active_slots
+            .iter()
+            .map(|(_, slot)| (slot.contract_address.as_str(), slot.slot_index.as_slice()))
+            .collect();
+        active_slots.extend(
+            group_locks
+                .iter()
+                .filter(|lock| {
+                    !requested
+                        .contains(&(lock.contract_address.as_str(), lock.slot_index.as_slice()))
+                })
+                .map(|lock| (false, lock)),
+        );
+
+        // Bitcoin blocks passed since each active slot was locked, None for slots reported as
+        // locked because btc_block is stale
+        let block_deltas: Vec<_> = active_slots
+            .iter()
+            .map(|(_, slot)| self.block_delta(slot, req.btc_block))
+            .collect::<Result<_, _>>()
+            .map_err(stale_btc_block_status)?;
+
         // We have active slots, so we need to check confirmation status for each txid
-        // Collect unique txids from active slots
+        // Collect unique txids from active slots that aren't reported as locked anyway
         let unique_txids: std::collections::HashSet<_> = active_slots
             .iter()
-            .map(|(_, slot)| slot.btc_txid.clone())
+            .zip(&block_deltas)
+            .filter(|(_, block_delta)| block_delta.is_some())
+            .flat_map(|((_, slot), _)| slot.btc_txids().map(str::to_string))
             .collect();
 
         // Check confirmation status for unique active txids in parallel
@@ -570,71 +1534,219 @@ impl<B: BitcoinRpcServiceAPI + 'static> SlotLockService for SlotLockServiceImpl<
             .iter()
             .map(|txid| async move {
                 self.bitcoin_service
-                    .is_tx_confirmed(txid)
+                    .get_confirmations(txid)
                     .await
-                    .map(|confirmed| (txid.clone(), confirmed))
-                    .map_err(|e| Status::internal(format!("Bitcoin RPC error: {}", e)))
+                    .map(|confirmations| (txid.clone(), confirmations))
+                    .map_err(bitcoin_rpc_status)
             })
             .collect();
 
         // Execute all confirmation futures in parallel and collect results into a HashMap
-        let confirmation_statuses: std::collections::HashMap<_, _> =
+        let tx_confirmations: std::collections::HashMap<_, _> =
             futures::future::try_join_all(confirmation_futures)
                 .await?
                 .into_iter()
                 .collect();
-
-        // Map confirmation results back to active slots
+        // Map confirmation results back to active slots, against the threshold of each slot's
+        // contract
+        let txid_confirmations = |slot: &LockedSlot| -> Vec<u32> {
+            slot.btc_txids()
+                .map(|txid| tx_confirmations.get(txid).copied().unwrap_or(0))
+                .collect()
+        };
         let slot_confirmations: Vec<_> = active_slots
             .iter()
-            .map(|(_, slot)| {
-                confirmation_statuses
-                    .get(&slot.btc_txid)
-                    .copied()
-                    .unwrap_or(false)
+            .map(|(_, slot)| self.is_confirmed(slot, &txid_confirmations(slot)))
+            .collect();
+
+        // Check unconfirmed txids of slots that haven't hit the revert threshold for conflicting
+        // spends of their inputs
+        let unconfirmed_txids: std::collections::HashSet<_> = active_slots
+            .iter()
+            .zip(&block_deltas)
+            .zip(&slot_confirmations)
+            .filter(|(((_, slot), block_delta), is_confirmed)| {
+                block_delta.is_some_and(|block_delta| block_delta <= self.revert_threshold(slot))
+                    && !**is_confirmed
             })
+            .map(|(((_, slot), _), _)| slot.btc_txid.clone())
             .collect();
 
-        // Process results and update DB in same transaction
-        let locked_slots = self
-            .db
-            .with_transaction(|transaction| {
+        let double_spend_futures: Vec<_> = unconfirmed_txids
+            .iter()
+            .map(|txid| async move {
+                self.bitcoin_service
+                    .check_double_spend(txid)
+                    .await
+                    .map(|status| (txid.clone(), status))
+                    .map_err(bitcoin_rpc_status)
+            })
+            .collect();
+
+        let double_spend_statuses: std::collections::HashMap<_, _> =
+            futures::future::try_join_all(double_spend_futures)
+                .await?
+                .into_iter()
+                .collect();
+
+        // Confirmation stages of the requested slots that stay locked unless double-spent
+        let confirmation_stages = futures::future::try_join_all(
+            active_slots
+                .iter()
+                .zip(&block_deltas)
+                .zip(&slot_confirmations)
+                .map(
+                    |(((requested, slot), block_delta), is_confirmed)| async move {
+                        let stays_locked = block_delta
+                            .is_some_and(|block_delta| block_delta <= self.revert_threshold(slot))
+                            && !*is_confirmed;
+                        if *requested && stays_locked {
+                            self.confirmation_stage(slot, &txid_confirmations(slot))
+                                .await
+                        } else {
+                            Ok(ConfirmationStage::Unspecified)
+                        }
+                    },
+                ),
+        )
+        .await?;
+
+        let group_verdicts = self.group_verdicts(
+            &active_slots,
+            &block_deltas,
+            &slot_confirmations,
+            &double_spend_statuses,
+        );
+
+        // Process results and update DB in same transaction
+        let mut events: Vec<_> = active_slots
+            .iter()
+            .zip(&confirmation_stages)
+            .filter(|((requested, _), _)| *requested && !peek)
+            .filter_map(|((_, slot), stage)| self.eviction_event(slot, *stage, req.current_block))
+            .collect();
+        let mut settled = Vec::new();
+        let (locked_slots, any_reverted) = self
+            .apply_status(peek, |transaction| {
                 let mut slots = Vec::with_capacity(active_slots.len());
-                let mut slots_to_unlock = Vec::new();
+                let mut confirmed_slots = Vec::new();
+                let mut reverted_slots = Vec::new();
+                let mut double_spent_slots = Vec::new();
+                let mut replaced_txids = std::collections::HashSet::new();
 
                 // First pass: collect confirmation statuses and slots
-                for ((_, slot), is_confirmed) in active_slots.iter().zip(slot_confirmations.iter())
+                for ((((requested, slot), is_confirmed), block_delta), confirmation_stage) in
+                    active_slots
+                        .iter()
+                        .zip(slot_confirmations.iter())
+                        .zip(&block_deltas)
+                        .zip(&confirmation_stages)
                 {
-                    let block_delta = req.btc_block - slot.btc_block;
+                    let Some(block_delta) = *block_delta else {
+                        if *requested {
+                            slots.push(GetSlotStatusResponse {
+                                status: get_slot_status_response::Status::Locked as i32,
+                                ..lock_progress(slot, 0, self.blocks_until_revert(slot, 0))
+                            });
+                        }
+                        continue;
+                    };
+                    let double_spend_status =
+                        lock_double_spend_status(slot, double_spend_statuses.get(&slot.btc_txid));
+                    let (reverts, is_confirmed, double_spent) = match slot
+                        .lock_group
+                        .and_then(|group| group_verdicts.get(&group))
+                    {
+                        Some(Verdict::Reverted(UnlockReason::DoubleSpent)) => (false, false, true),
+                        Some(Verdict::Reverted(_)) => (true, false, false),
+                        Some(Verdict::Confirmed) => (false, true, false),
+                        Some(Verdict::Pending) => (false, false, false),
+                        None => (
+                            block_delta > self.revert_threshold(slot),
+                            *is_confirmed,
+                            double_spend_status == DoubleSpendStatus::Conflicted,
+                        ),
+                    };
 
-                    let (status, revert_value, current_value) =
-                        if block_delta > self.revert_threshold as u64 || *is_confirmed {
-                            // Slot needs to be unlocked for one of two reasons:
+                    let (status, unlock_reason, revert_value, current_value) =
+                        if reverts || is_confirmed || double_spent {
+                            // Slot needs to be unlocked for one of three reasons:
                             // 1. Bitcoin block delta exceeded revert threshold (too many blocks passed)
                             // 2. Bitcoin transaction is confirmed
-                            slots_to_unlock.push((
-                                slot.contract_address.as_str(),
-                                slot.slot_index.as_slice(),
-                                req.current_block,
-                            ));
-
-                            if block_delta > self.revert_threshold as u64 {
+                            // 3. Bitcoin transaction was double-spent by a mined conflicting transaction
+                            // For a lock of an atomic group, these are decided for the group as a whole
+                            if reverts {
                                 // Slot is being unlocked because too many BTC blocks passed without confirmation
                                 // In this case, we report it as "Reverted" and include the revert values
+                                reverted_slots.push(*slot);
                                 (
                                     get_slot_status_response::Status::Reverted as i32,
+                                    Some(UnlockReason::RevertThreshold),
                                     slot.revert_value.clone(),
                                     slot.current_value.clone(),
                                 )
-                            } else {
+                            } else if is_confirmed {
                                 // Slot is being unlocked because the Bitcoin transaction was confirmed
                                 // In this case, we report it as "Unlocked" and don't need values
+                                confirmed_slots.push(*slot);
                                 (
                                     get_slot_status_response::Status::Unlocked as i32,
+                                    Some(UnlockReason::Confirmed),
                                     Vec::new(),
                                     Vec::new(),
                                 )
+                            } else {
+                                // Slot is being reverted because the Bitcoin transaction can no
+                                // longer confirm
+                                tracing::warn!(
+                                "Reverting double-spent slot: contract={}, slot={}, btc_txid={}",
+                                slot.contract_address,
+                                format_bytes(&slot.slot_index),
+                                slot.btc_txid
+                            );
+                                double_spent_slots.push(*slot);
+                                (
+                                    get_slot_status_response::Status::DoubleSpent as i32,
+                                    Some(UnlockReason::DoubleSpent),
+                                    slot.revert_value.clone(),
+                                    slot.current_value.clone(),
+                                )
+                            }
+                        } else if let DoubleSpendStatus::Replaced(new_txid) = double_spend_status {
+                            // The transaction was fee-bumped, move all its locks to the replacement
+                            // once and keep the slot locked
+                            if replaced_txids.insert(slot.btc_txid.as_str()) {
+                                tracing::info!(
+                                    "Lock transaction replaced: btc_txid={}, new_btc_txid={}",
+                                    slot.btc_txid,
+                                    new_txid
+                                );
+                                self.db.replace_lock_txid(
+                                    transaction,
+                                    &slot.namespace,
+                                    &slot.btc_txid,
+                                    &new_txid.to_string(),
+                                    true,
+                                )?;
                             }
+                            (
+                                get_slot_status_response::Status::Locked as i32,
+                                None,
+                                Vec::new(),
+                                Vec::new(),
+                            )
+                        } else if double_spend_status == DoubleSpendStatus::AtRisk
+                            || *confirmation_stage == ConfirmationStage::TxEvicted
+                        {
+                            // An input is spent by a conflicting mempool transaction, or the
+                            // transaction was evicted, the slot stays locked until either is
+                            // resolved
+                            (
+                                get_slot_status_response::Status::AtRisk as i32,
+                                None,
+                                Vec::new(),
+                                Vec::new(),
+                            )
                         } else {
                             // Slot is locked and active:
                             // - Current block has reached or passed start block
@@ -642,28 +1754,96 @@ impl<B: BitcoinRpcServiceAPI + 'static> SlotLockService for SlotLockServiceImpl<
                             // - Bitcoin block delta has not exceeded revert threshold
                             (
                                 get_slot_status_response::Status::Locked as i32,
+                                None,
                                 Vec::new(),
                                 Vec::new(),
                             )
                         };
+                    if let Some(reason) = unlock_reason {
+                        settled.push(SettledLock::new(slot, reason, block_delta));
+                    }
 
-                    slots.push(GetSlotStatusResponse {
+                    let still_locked = status == get_slot_status_response::Status::Locked as i32
+                        || status == get_slot_status_response::Status::AtRisk as i32;
+                    let blocks_until_revert = if still_locked {
+                        self.blocks_until_revert(slot, block_delta)
+                    } else {
+                        0
+                    };
+                    let confirmations = settled_confirmations(slot, &txid_confirmations(slot));
+                    let mut response = GetSlotStatusResponse {
                         status,
-                        contract_address: slot.contract_address.clone(),
-                        slot_index: slot.slot_index.clone(),
                         revert_value,
                         current_value,
-                    });
+                        unlock_reason: proto_unlock_reason(unlock_reason) as i32,
+                        ..lock_progress(slot, confirmations, blocks_until_revert)
+                    };
+                    if still_locked {
+                        response.confirmation_stage = *confirmation_stage as i32;
+                        response.required_confirmations = self.confirmation_threshold(slot);
+                    }
+                    if let DoubleSpendStatus::Replaced(new_txid) = double_spend_status {
+                        response.btc_txid = new_txid.to_string();
+                    }
+                    if *requested {
+                        slots.push(response);
+                    }
                 }
 
-                // Batch unlock all slots that need unlocking
-                if !slots_to_unlock.is_empty() {
-                    self.db.batch_unlock_slots(transaction, &slots_to_unlock)?;
+                // Batch unlock all slots that need unlocking, once per unlock reason
+                for (reason, unlocked) in [
+                    (UnlockReason::Confirmed, &confirmed_slots),
+                    (UnlockReason::RevertThreshold, &reverted_slots),
+                    (UnlockReason::DoubleSpent, &double_spent_slots),
+                ] {
+                    let slots_to_unlock: Vec<_> = unlocked
+                        .iter()
+                        .map(|slot| {
+                            (
+                                slot.contract_address.as_str(),
+                                slot.slot_index.as_slice(),
+                                req.current_block,
+                            )
+                        })
+                        .collect();
+                    self.db.batch_unlock_slots(
+                        transaction,
+                        &req.namespace,
+                        &slots_to_unlock,
+                        reason,
+                    )?;
+                    events.extend(
+                        unlocked
+                            .iter()
+                            .map(|slot| unlock_event(slot, req.current_block, reason)),
+                    );
                 }
 
-                Ok(slots)
+                self.enqueue_reverts(
+                    transaction,
+                    &reverted_slots,
+                    req.current_block,
+                    UnlockReason::RevertThreshold,
+                )?;
+                self.enqueue_reverts(
+                    transaction,
+                    &double_spent_slots,
+                    req.current_block,
+                    UnlockReason::DoubleSpent,
+                )?;
+
+                Ok((
+                    slots,
+                    !reverted_slots.is_empty() || !double_spent_slots.is_empty(),
+                ))
             })
-            .map_err(|e| Status::internal(format!("{}", e)))?;
+            .map_err(database_status)?;
+        self.publish_status_events(peek, events);
+        self.record_settled_locks(peek, &settled);
+
+        if any_reverted {
+            self.notify_reverts();
+        }
 
         // Combine all responses
         let mut all_slots = initial_slots;
@@ -680,15 +1860,357 @@ impl<B: BitcoinRpcServiceAPI + 'static> SlotLockService for SlotLockServiceImpl<
             )
         };
 
-        let formatted_response: Vec<_> = all_slots.iter().map(format_response_slot).collect();
-
         tracing::info!(
-            "BatchGetSlotStatus response: slots={:#?}",
-            formatted_response
+            "BatchGetSlotStatus response: slots={:?}",
+            LoggedSlots::new(&all_slots, self.max_logged_slots, format_response_slot)
         );
 
-        Ok(Response::new(BatchGetSlotStatusResponse {
+        Ok(self.batch_response(BatchGetSlotStatusResponse {
             slots: all_slots,
+            signature: Vec::new(),
+        }))
+    }
+}
+
+#[tonic::async_trait]
+impl<B: BitcoinRpcServiceAPI + 'static, S: SlotStore> SlotLockService
+    for SlotLockServiceImpl<B, S>
+{
+    type SubscribeSlotEventsStream = Pin<Box<dyn Stream<Item = Result<SlotEvent, Status>> + Send>>;
+
+    async fn lock_slot(
+        &self,
+        request: Request<LockSlotRequest>,
+    ) -> Result<Response<LockSlotResponse>, Status> {
+        if let Some(status) = self.write_rejection(request.metadata()) {
+            return Err(status);
+        }
+        let locked_by = caller_identity(&request);
+        let mut req = request.into_inner();
+        let mut violations = FieldViolations::default();
+        violations.check_namespace(&req.namespace);
+        violations.check_slot("", &req.contract_address, &mut req.slot_index);
+        violations.normalize_txid("btc_txid", &mut req.btc_txid);
+        violations.normalize_alt_txids("", &req.btc_txid, &mut req.alt_btc_txids);
+        violations.normalize_required_txids(
+            "",
+            &req.alt_btc_txids,
+            &mut req.required_confirmed_txids,
+        );
+        violations.check_lock_data("", &req.metadata, &req.labels);
+        if let Some(status) = violations.into_status() {
+            return Err(status);
+        }
+
+        tracing::info!(
+            "LockSlot request: contract={}, slot={}, locked_at_block={}, btc_block={}, btc_txid={}",
+            req.contract_address,
+            format_bytes(&req.slot_index),
+            req.locked_at_block,
+            req.btc_block,
+            req.btc_txid
+        );
+
+        let raw_tx = if req.raw_tx_hex.is_empty() {
+            None
+        } else {
+            Some(
+                decode_lock_transaction(&req.raw_tx_hex, &req.btc_txid)
+                    .map_err(|e| invalid_field("raw_tx_hex", e.to_string()))?,
+            )
+        };
+
+        let mut events = Vec::new();
+        let result = self
+            .db
+            .with_transaction(|transaction| {
+                // Check if slot is already locked or reserved within the transaction
+                let is_locked = self.db.is_slot_locked_with_transaction(
+                    transaction,
+                    &req.namespace,
+                    &req.contract_address,
+                    &req.slot_index,
+                )? || self.db.reserved_slots(
+                    transaction,
+                    &req.namespace,
+                    &[(req.contract_address.as_str(), req.slot_index.as_slice())],
+                    unix_now(),
+                )?[0];
+
+                if is_locked {
+                    self.db.record_lock_conflicts(
+                        transaction,
+                        &req.namespace,
+                        &[req.contract_address.as_str()],
+                    )?;
+                    return Ok(Ok(lock_slot_response::Status::AlreadyLocked as i32));
+                }
+                if let Some(status) =
+                    self.active_lock_quota(transaction, locked_by.as_deref(), 1)?
+                {
+                    return Ok(Err(status));
+                }
+
+                // Insert new lock
+                let slot = SlotInsertData {
+                    namespace: req.namespace.clone(),
+                    contract_address: req.contract_address.clone(),
+                    start_block: req.locked_at_block,
+                    btc_block: req.btc_block,
+                    slot_index: req.slot_index.clone(),
+                    btc_txid: req.btc_txid.clone(),
+                    revert_value: req.revert_value.clone(),
+                    current_value: req.current_value.clone(),
+                    metadata: req.metadata.clone(),
+                    labels: req.labels.clone().into_iter().collect(),
+                    alt_btc_txids: req.alt_btc_txids.clone(),
+                    required_confirmed_txids: req.required_confirmed_txids,
+                    locked_by: locked_by.clone(),
+                };
+                self.db.insert_slot_lock(transaction, &slot)?;
+                if let Some(raw_tx) = &raw_tx {
+                    self.db
+                        .insert_lock_transaction(transaction, &req.btc_txid, raw_tx)?;
+                }
+                events.push(lock_event(&slot));
+
+                Ok(Ok(lock_slot_response::Status::Locked as i32))
+            })
+            .map_err(database_status)??;
+        self.events.publish(events);
+
+        if result == lock_slot_response::Status::AlreadyLocked as i32 {
+            self.metrics.record_lock_conflict(&req.contract_address);
+        } else if let Some(raw_tx) = &raw_tx {
+            // The lock is already committed, so a failed broadcast is left to the rebroadcast task
+            if let Err(e) = self.bitcoin_service.broadcast_transaction(raw_tx).await {
+                tracing::warn!(
+                    "Failed to broadcast lock transaction {}: {}",
+                    req.btc_txid,
+                    e
+                );
+            }
+        }
+
+        tracing::info!(
+            "LockSlot response: contract={}, slot={}, status={}",
+            req.contract_address,
+            format_bytes(&req.slot_index),
+            lock_status_to_string(result)
+        );
+
+        Ok(Response::new(LockSlotResponse {
+            status: result,
+            contract_address: req.contract_address,
+            slot_index: req.slot_index,
+        }))
+    }
+
+    async fn batch_lock_slot(
+        &self,
+        request: Request<BatchLockSlotRequest>,
+    ) -> Result<Response<BatchLockSlotResponse>, Status> {
+        if let Some(status) = self.write_rejection(request.metadata()) {
+            return Err(status);
+        }
+        let locked_by = caller_identity(&request);
+        let mut req = request.into_inner();
+        let mut violations = FieldViolations::default();
+        violations.check_namespace(&req.namespace);
+        for (idx, slot) in req.slots.iter_mut().enumerate() {
+            let path = format!("slots[{}]", idx);
+            violations.check_slot(&path, &slot.contract_address, &mut slot.slot_index);
+            violations.normalize_txid(&field(&path, "btc_txid"), &mut slot.btc_txid);
+            violations.normalize_alt_txids(&path, &slot.btc_txid, &mut slot.alt_btc_txids);
+            violations.normalize_required_txids(
+                &path,
+                &slot.alt_btc_txids,
+                &mut slot.required_confirmed_txids,
+            );
+            violations.check_lock_data(&path, &slot.metadata, &slot.labels);
+        }
+        for (group_idx, group) in req.contract_slots.iter_mut().enumerate() {
+            for (idx, slot) in group.slots.iter_mut().enumerate() {
+                let path = format!("contract_slots[{}].slots[{}]", group_idx, idx);
+                violations.check_slot(&path, &group.contract_address, &mut slot.slot_index);
+                violations.normalize_txid(&field(&path, "btc_txid"), &mut slot.btc_txid);
+                violations.normalize_alt_txids(&path, &slot.btc_txid, &mut slot.alt_btc_txids);
+                violations.normalize_required_txids(
+                    &path,
+                    &slot.alt_btc_txids,
+                    &mut slot.required_confirmed_txids,
+                );
+                violations.check_lock_data(&path, &slot.metadata, &slot.labels);
+            }
+        }
+        if let Some(status) = violations.into_status() {
+            return Err(status);
+        }
+        let contract_slots = std::mem::take(&mut req.contract_slots);
+        req.slots.extend(flatten_contract_slots(contract_slots));
+        if let Some(status) = self.batch_too_large(req.slots.len()) {
+            return Err(status);
+        }
+
+        // Return early if slots array is empty
+        if req.slots.is_empty() {
+            return Ok(self.batch_response(BatchLockSlotResponse {
+                slots: vec![],
+                lock_group: 0,
+            }));
+        }
+
+        // Log the request payload with formatted slots
+        tracing::info!(
+            "BatchLockSlot request: locked_at_block={}, btc_block={}, atomic={}, slots={:?}",
+            req.locked_at_block,
+            req.btc_block,
+            req.atomic,
+            LoggedSlots::new(
+                &req.slots,
+                self.max_logged_slots,
+                FormattedSlot::from_request_slot
+            )
+        );
+
+        let mut events = Vec::new();
+        let result = self
+            .db
+            .with_transaction(|transaction| {
+                // Get all slot locks in one query
+                let slots_to_check: Vec<_> = req
+                    .slots
+                    .iter()
+                    .map(|slot| (slot.contract_address.as_str(), slot.slot_index.as_slice()))
+                    .collect();
+
+                let existing_slots = self.db.batch_get_locked_slots(
+                    transaction,
+                    &req.namespace,
+                    &slots_to_check,
+                    req.locked_at_block,
+                )?;
+                let reserved = self.db.reserved_slots(
+                    transaction,
+                    &req.namespace,
+                    &slots_to_check,
+                    unix_now(),
+                )?;
+
+                let mut responses = Vec::with_capacity(req.slots.len());
+                let mut slots_to_insert = Vec::with_capacity(req.slots.len());
+                let mut conflicts = Vec::new();
+                let mut locked_in_batch = std::collections::HashSet::new();
+
+                // Process each slot using the batch query results. A slot listed more than once is
+                // locked by its first entry.
+                for (idx, slot) in req.slots.iter().enumerate() {
+                    if existing_slots[idx].is_some()
+                        || reserved[idx]
+                        || !locked_in_batch.insert(slots_to_check[idx])
+                    {
+                        conflicts.push(slot.contract_address.as_str());
+                        responses.push(SlotLockStatus {
+                            contract_address: slot.contract_address.clone(),
+                            slot_index: slot.slot_index.clone(),
+                            status: slot_lock_status::Status::AlreadyLocked as i32,
+                        });
+                        continue;
+                    }
+
+                    slots_to_insert.push(SlotInsertData {
+                        namespace: req.namespace.clone(),
+                        contract_address: slot.contract_address.clone(),
+                        start_block: req.locked_at_block,
+                        btc_block: req.btc_block,
+                        slot_index: slot.slot_index.clone(),
+                        btc_txid: slot.btc_txid.clone(),
+                        revert_value: slot.revert_value.clone(),
+                        current_value: slot.current_value.clone(),
+                        metadata: slot.metadata.clone(),
+                        labels: slot.labels.clone().into_iter().collect(),
+                        alt_btc_txids: slot.alt_btc_txids.clone(),
+                        required_confirmed_txids: slot.required_confirmed_txids,
+                        locked_by: locked_by.clone(),
+                    });
+
+                    responses.push(SlotLockStatus {
+                        contract_address: slot.contract_address.clone(),
+                        slot_index: slot.slot_index.clone(),
+                        status: slot_lock_status::Status::Locked as i32,
+                    });
+                }
+
+                // An atomic batch locks every slot or none
+                if req.atomic && !conflicts.is_empty() {
+                    slots_to_insert.clear();
+                    for response in &mut responses {
+                        if response.status == slot_lock_status::Status::Locked as i32 {
+                            response.status = slot_lock_status::Status::NotLocked as i32;
+                        }
+                    }
+                }
+                // A batch over the caller's quota locks none of its slots
+                if let Some(status) = self.active_lock_quota(
+                    transaction,
+                    locked_by.as_deref(),
+                    slots_to_insert.len(),
+                )? {
+                    return Ok(Err(status));
+                }
+
+                // Insert all slots that can be locked
+                let mut lock_group = 0;
+                if !slots_to_insert.is_empty() {
+                    self.db
+                        .batch_insert_slot_locks(transaction, &slots_to_insert)?;
+                    events.extend(slots_to_insert.iter().map(lock_event));
+                    if req.atomic {
+                        let slots: Vec<_> = slots_to_insert
+                            .iter()
+                            .map(|slot| {
+                                (slot.contract_address.as_str(), slot.slot_index.as_slice())
+                            })
+                            .collect();
+                        lock_group =
+                            self.db
+                                .create_lock_group(transaction, &req.namespace, &slots)?
+                                as u64;
+                    }
+                }
+                if !conflicts.is_empty() {
+                    self.db
+                        .record_lock_conflicts(transaction, &req.namespace, &conflicts)?;
+                }
+
+                Ok(Ok((responses, lock_group)))
+            })
+            .map_err(database_status)??;
+        let (result, lock_group) = result;
+        self.events.publish(events);
+
+        for status in &result {
+            if status.status == slot_lock_status::Status::AlreadyLocked as i32 {
+                self.metrics.record_lock_conflict(&status.contract_address);
+            }
+        }
+
+        tracing::info!(
+            "BatchLockSlot response: lock_group={}, slots={:?}",
+            lock_group,
+            LoggedSlots::new(&result, self.max_logged_slots, |status| {
+                format!(
+                    "{{ contract: {}, slot: {}, status: {} }}",
+                    status.contract_address,
+                    format_bytes(&status.slot_index),
+                    lock_status_to_string(status.status)
+                )
+            })
+        );
+
+        Ok(self.batch_response(BatchLockSlotResponse {
+            slots: result,
+            lock_group,
         }))
     }
 
@@ -696,11 +2218,39 @@ impl<B: BitcoinRpcServiceAPI + 'static> SlotLockService for SlotLockServiceImpl<
         &self,
         request: Request<BatchUnlockSlotRequest>,
     ) -> Result<Response<BatchUnlockSlotResponse>, Status> {
-        let req = request.into_inner();
+        if let Some(status) = self.write_rejection(request.metadata()) {
+            return Err(status);
+        }
+        let mut req = request.into_inner();
+        if let Some(status) = self.batch_too_large(req.slots.len()) {
+            return Err(status);
+        }
+        let mut violations = FieldViolations::default();
+        violations.check_namespace(&req.namespace);
+        for (idx, slot) in req.slots.iter_mut().enumerate() {
+            violations.check_slot(
+                &format!("slots[{}]", idx),
+                &slot.contract_address,
+                &mut slot.slot_index,
+            );
+        }
+        for (idx, expected) in req.expected_current_values.iter_mut().enumerate() {
+            let path = format!("expected_current_values[{}]", idx);
+            violations.check_slot(&path, &expected.contract_address, &mut expected.slot_index);
+            if !req.slots.iter().any(|slot| {
+                slot.contract_address == expected.contract_address
+                    && slot.slot_index == expected.slot_index
+            }) {
+                violations.add(&path, "Slot isn't one of the slots to unlock");
+            }
+        }
+        if let Some(status) = violations.into_status() {
+            return Err(status);
+        }
 
         // Return early if slots array is empty
         if req.slots.is_empty() {
-            return Ok(Response::new(BatchUnlockSlotResponse { slots: vec![] }));
+            return Ok(self.batch_response(BatchUnlockSlotResponse { slots: vec![] }));
         }
 
         tracing::info!(
@@ -723,54 +2273,729 @@ impl<B: BitcoinRpcServiceAPI + 'static> SlotLockService for SlotLockServiceImpl<
             })
             .collect();
 
-        // Unlock slots in a transaction
-        self.db
+        // Unlock slots in a transaction, looking up the active locks first for their events
+        let events = self
+            .db
             .with_transaction(|transaction| {
-                self.db.batch_unlock_slots(transaction, &slots_to_unlock)
-            })
-            .map_err(|e| Status::internal(format!("Database error: {}", e)))?;
+                let slots: Vec<_> = slots_to_unlock
+                    .iter()
+                    .map(|(contract_address, slot_index, _)| (*contract_address, *slot_index))
+                    .collect();
+                // Unlocking ignores the start block, so look up locks starting at any block
+                let locks = self.db.batch_get_locked_slots(
+                    transaction,
+                    &req.namespace,
+                    &slots,
+                    i64::MAX as u64,
+                )?;
+                // Nothing is unlocked unless every expected value still holds
+                for expected in &req.expected_current_values {
+                    let current_value = locks
+                        .iter()
+                        .flatten()
+                        .find(|lock| {
+                            lock.end_block.is_none()
+                                && lock.contract_address == expected.contract_address
+                                && lock.slot_index == expected.slot_index
+                        })
+                        .map(|lock| lock.current_value.as_slice());
+                    if current_value != Some(expected.current_value.as_slice()) {
+                        return Ok(Err(current_value_mismatch_status(
+                            &expected.contract_address,
+                            &expected.slot_index,
+                            &expected.current_value,
+                            current_value,
+                        )));
+                    }
+                }
+                self.db.batch_unlock_slots(
+                    transaction,
+                    &req.namespace,
+                    &slots_to_unlock,
+                    UnlockReason::Manual,
+                )?;
+                Ok(Ok(locks
+                    .iter()
+                    .flatten()
+                    .filter(|lock| lock.end_block.is_none())
+                    .map(|lock| unlock_event(lock, req.current_block, UnlockReason::Manual))
+                    .collect::<Vec<_>>()))
+            })
+            .map_err(database_status)??;
+        self.events.publish(events);
 
         // Transform slots back to response format
         let slots = req.slots.to_vec();
 
         tracing::info!("BatchUnlockSlot response: unlocked {} slots", slots.len());
 
-        Ok(Response::new(BatchUnlockSlotResponse { slots }))
+        Ok(self.batch_response(BatchUnlockSlotResponse { slots }))
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use sova_sentinel_proto::proto::{SlotData, SlotIdentifier};
-    use std::sync::{Arc, Mutex};
+    async fn replace_lock_tx(
+        &self,
+        request: Request<ReplaceLockTxRequest>,
+    ) -> Result<Response<ReplaceLockTxResponse>, Status> {
+        if let Some(status) = self.write_rejection(request.metadata()) {
+            return Err(status);
+        }
+        let mut req = request.into_inner();
+
+        tracing::info!(
+            "ReplaceLockTx request: old_btc_txid={}, new_btc_txid={}",
+            req.old_btc_txid,
+            req.new_btc_txid
+        );
+
+        let mut violations = FieldViolations::default();
+        violations.check_namespace(&req.namespace);
+        violations.normalize_txid("old_btc_txid", &mut req.old_btc_txid);
+        violations.normalize_txid("new_btc_txid", &mut req.new_btc_txid);
+        if violations.is_empty() && req.old_btc_txid == req.new_btc_txid {
+            violations.add("new_btc_txid", "must differ from old_btc_txid");
+        }
+        if let Some(status) = violations.into_status() {
+            return Err(status);
+        }
+
+        let replaced_locks = self
+            .db
+            .with_transaction(|transaction| {
+                self.db.replace_lock_txid(
+                    transaction,
+                    &req.namespace,
+                    &req.old_btc_txid,
+                    &req.new_btc_txid,
+                    false,
+                )
+            })
+            .map_err(database_status)?;
+
+        if replaced_locks == 0 {
+            return Err(lock_tx_not_found_status(&req.old_btc_txid));
+        }
+
+        tracing::info!(
+            "ReplaceLockTx response: old_btc_txid={}, new_btc_txid={}, replaced_locks={}",
+            req.old_btc_txid,
+            req.new_btc_txid,
+            replaced_locks
+        );
+
+        Ok(Response::new(ReplaceLockTxResponse {
+            replaced_locks: replaced_locks as u32,
+        }))
+    }
+
+    async fn unlock_by_txid(
+        &self,
+        request: Request<UnlockByTxidRequest>,
+    ) -> Result<Response<UnlockByTxidResponse>, Status> {
+        if let Some(status) = self.write_rejection(request.metadata()) {
+            return Err(status);
+        }
+        let mut req = request.into_inner();
+
+        tracing::info!(
+            "UnlockByTxid request: btc_txid={}, current_block={}, revert={}",
+            req.btc_txid,
+            req.current_block,
+            req.revert
+        );
+
+        let mut violations = FieldViolations::default();
+        violations.check_namespace(&req.namespace);
+        violations.normalize_txid("btc_txid", &mut req.btc_txid);
+        if let Some(status) = violations.into_status() {
+            return Err(status);
+        }
+
+        let reason = if req.revert {
+            UnlockReason::ManualRevert
+        } else {
+            UnlockReason::Manual
+        };
+        let locks = self
+            .db
+            .with_transaction(|transaction| {
+                let locks =
+                    self.db
+                        .active_locks_by_txid(transaction, &req.namespace, &req.btc_txid)?;
+                let slots_to_unlock: Vec<_> = locks
+                    .iter()
+                    .map(|lock| {
+                        (
+                            lock.contract_address.as_str(),
+                            lock.slot_index.as_slice(),
+                            req.current_block,
+                        )
+                    })
+                    .collect();
+                self.db.batch_unlock_slots(
+                    transaction,
+                    &req.namespace,
+                    &slots_to_unlock,
+                    reason,
+                )?;
+                if req.revert {
+                    let reverted: Vec<_> = locks.iter().collect();
+                    self.enqueue_reverts(transaction, &reverted, req.current_block, reason)?;
+                }
+                Ok(locks)
+            })
+            .map_err(database_status)?;
+
+        if locks.is_empty() {
+            return Err(lock_tx_not_found_status(&req.btc_txid));
+        }
+        self.events.publish(
+            locks
+                .iter()
+                .map(|lock| unlock_event(lock, req.current_block, reason)),
+        );
+        if req.revert {
+            self.notify_reverts();
+        }
+
+        tracing::info!(
+            "UnlockByTxid response: btc_txid={}, reason={}, unlocked_locks={}",
+            req.btc_txid,
+            reason.as_str(),
+            locks.len()
+        );
+
+        Ok(Response::new(UnlockByTxidResponse {
+            slots: locks
+                .into_iter()
+                .map(|lock| SlotIdentifier {
+                    contract_address: lock.contract_address,
+                    slot_index: lock.slot_index,
+                })
+                .collect(),
+        }))
+    }
+
+    async fn prepare_lock(
+        &self,
+        request: Request<PrepareLockRequest>,
+    ) -> Result<Response<PrepareLockResponse>, Status> {
+        if let Some(status) = self.write_rejection(request.metadata()) {
+            return Err(status);
+        }
+        let locked_by = caller_identity(&request);
+        let mut req = request.into_inner();
+        let mut violations = FieldViolations::default();
+        violations.check_namespace(&req.namespace);
+        for (idx, slot) in req.slots.iter_mut().enumerate() {
+            let path = format!("slots[{}]", idx);
+            violations.check_slot(&path, &slot.contract_address, &mut slot.slot_index);
+            violations.normalize_txid(&field(&path, "btc_txid"), &mut slot.btc_txid);
+            violations.normalize_alt_txids(&path, &slot.btc_txid, &mut slot.alt_btc_txids);
+            violations.normalize_required_txids(
+                &path,
+                &slot.alt_btc_txids,
+                &mut slot.required_confirmed_txids,
+            );
+            violations.check_lock_data(&path, &slot.metadata, &slot.labels);
+        }
+        if let Some(status) = violations.into_status() {
+            return Err(status);
+        }
+        if let Some(status) = self.batch_too_large(req.slots.len()) {
+            return Err(status);
+        }
+
+        tracing::info!(
+            "PrepareLock request: locked_at_block={}, btc_block={}, slots={:?}",
+            req.locked_at_block,
+            req.btc_block,
+            LoggedSlots::new(
+                &req.slots,
+                self.max_logged_slots,
+                FormattedSlot::from_request_slot
+            )
+        );
+
+        let now = unix_now();
+        let (result, reservation_id) = self
+            .db
+            .with_transaction(|transaction| {
+                self.db.release_expired_reservations(transaction, now)?;
+                let slots_to_check: Vec<_> = req
+                    .slots
+                    .iter()
+                    .map(|slot| (slot.contract_address.as_str(), slot.slot_index.as_slice()))
+                    .collect();
+                let existing_slots = self.db.batch_get_locked_slots(
+                    transaction,
+                    &req.namespace,
+                    &slots_to_check,
+                    req.locked_at_block,
+                )?;
+                let reserved =
+                    self.db
+                        .reserved_slots(transaction, &req.namespace, &slots_to_check, now)?;
+
+                let mut responses = Vec::with_capacity(req.slots.len());
+                let mut slots_to_reserve = Vec::with_capacity(req.slots.len());
+                let mut conflicts = Vec::new();
+                let mut reserved_in_batch = std::collections::HashSet::new();
+                for (idx, slot) in req.slots.iter().enumerate() {
+                    let status = if existing_slots[idx].is_some()
+                        || reserved[idx]
+                        || !reserved_in_batch.insert(slots_to_check[idx])
+                    {
+                        conflicts.push(slot.contract_address.as_str());
+                        slot_lock_status::Status::AlreadyLocked
+                    } else {
+                        slots_to_reserve.push(SlotInsertData {
+                            namespace: req.namespace.clone(),
+                            contract_address: slot.contract_address.clone(),
+                            start_block: req.locked_at_block,
+                            btc_block: req.btc_block,
+                            slot_index: slot.slot_index.clone(),
+                            btc_txid: slot.btc_txid.clone(),
+                            revert_value: slot.revert_value.clone(),
+                            current_value: slot.current_value.clone(),
+                            metadata: slot.metadata.clone(),
+                            labels: slot.labels.clone().into_iter().collect(),
+                            alt_btc_txids: slot.alt_btc_txids.clone(),
+                            required_confirmed_txids: slot.required_confirmed_txids,
+                            locked_by: locked_by.clone(),
+                        });
+                        slot_lock_status::Status::Reserved
+                    };
+                    responses.push(SlotLockStatus {
+                        contract_address: slot.contract_address.clone(),
+                        slot_index: slot.slot_index.clone(),
+                        status: status as i32,
+                    });
+                }
+
+                if let Some(status) = self.active_lock_quota(
+                    transaction,
+                    locked_by.as_deref(),
+                    slots_to_reserve.len(),
+                )? {
+                    return Ok(Err(status));
+                }
+                let mut reservation_id = 0;
+                if !slots_to_reserve.is_empty() {
+                    reservation_id = self.db.reserve_slots(
+                        transaction,
+                        &req.namespace,
+                        &slots_to_reserve,
+                        now + self.reservation_timeout.as_secs(),
+                    )? as u64;
+                }
+                if !conflicts.is_empty() {
+                    self.db
+                        .record_lock_conflicts(transaction, &req.namespace, &conflicts)?;
+                }
+                Ok(Ok((responses, reservation_id)))
+            })
+            .map_err(database_status)??;
+
+        for status in &result {
+            if status.status == slot_lock_status::Status::AlreadyLocked as i32 {
+                self.metrics.record_lock_conflict(&status.contract_address);
+            }
+        }
+        tracing::info!(
+            "PrepareLock response: reservation_id={}, reserved={}, conflicts={}",
+            reservation_id,
+            result
+                .iter()
+                .filter(|status| status.status == slot_lock_status::Status::Reserved as i32)
+                .count(),
+            result
+                .iter()
+                .filter(|status| status.status == slot_lock_status::Status::AlreadyLocked as i32)
+                .count()
+        );
+
+        Ok(self.batch_response(PrepareLockResponse {
+            reservation_id,
+            slots: result,
+        }))
+    }
+
+    async fn commit_lock(
+        &self,
+        request: Request<CommitLockRequest>,
+    ) -> Result<Response<CommitLockResponse>, Status> {
+        if let Some(status) = self.write_rejection(request.metadata()) {
+            return Err(status);
+        }
+        let req = request.into_inner();
+        let mut violations = FieldViolations::default();
+        violations.check_namespace(&req.namespace);
+        if let Some(status) = violations.into_status() {
+            return Err(status);
+        }
 
-    #[derive(Clone)]
-    struct MockBitcoinService {
-        confirmed_txs: Arc<Mutex<Vec<String>>>,
+        tracing::info!("CommitLock request: reservation_id={}", req.reservation_id);
+
+        let mut events = Vec::new();
+        let result = self
+            .db
+            .with_transaction(|transaction| {
+                let Some(slots) = self.db.take_reservation(
+                    transaction,
+                    &req.namespace,
+                    req.reservation_id as i64,
+                    unix_now(),
+                )?
+                else {
+                    return Ok(None);
+                };
+
+                // Reserved slots can't be locked through the lock RPCs, but imported or restored
+                // locks may have taken them since
+                let slots_to_check: Vec<_> = slots
+                    .iter()
+                    .map(|slot| (slot.contract_address.as_str(), slot.slot_index.as_slice()))
+                    .collect();
+                let locked_at_block = slots.first().map_or(0, |slot| slot.start_block);
+                let existing_slots = self.db.batch_get_locked_slots(
+                    transaction,
+                    &req.namespace,
+                    &slots_to_check,
+                    locked_at_block,
+                )?;
+
+                let mut responses = Vec::with_capacity(slots.len());
+                let mut slots_to_insert = Vec::with_capacity(slots.len());
+                for (slot, existing) in slots.into_iter().zip(existing_slots) {
+                    let status = if existing.is_some() {
+                        slot_lock_status::Status::AlreadyLocked
+                    } else {
+                        slot_lock_status::Status::Locked
+                    };
+                    responses.push(SlotLockStatus {
+                        contract_address: slot.contract_address.clone(),
+                        slot_index: slot.slot_index.clone(),
+                        status: status as i32,
+                    });
+                    if existing.is_none() {
+                        slots_to_insert.push(slot);
+                    }
+                }
+                if !slots_to_insert.is_empty() {
+                    self.db
+                        .batch_insert_slot_locks(transaction, &slots_to_insert)?;
+                    events.extend(slots_to_insert.iter().map(lock_event));
+                }
+                Ok(Some(responses))
+            })
+            .map_err(database_status)?
+            .ok_or_else(|| reservation_not_found_status(req.reservation_id))?;
+        self.events.publish(events);
+
+        for status in &result {
+            if status.status == slot_lock_status::Status::AlreadyLocked as i32 {
+                self.metrics.record_lock_conflict(&status.contract_address);
+            }
+        }
+        tracing::info!(
+            "CommitLock response: reservation_id={}, locked={}",
+            req.reservation_id,
+            result
+                .iter()
+                .filter(|status| status.status == slot_lock_status::Status::Locked as i32)
+                .count()
+        );
+
+        Ok(self.batch_response(CommitLockResponse { slots: result }))
+    }
+
+    async fn get_slot_status(
+        &self,
+        request: Request<GetSlotStatusRequest>,
+    ) -> Result<Response<GetSlotStatusResponse>, Status> {
+        let req = request.get_ref().clone();
+        let mut response = self.slot_status(request, !self.implicit_unlocks).await?;
+        if let Some(key) = &self.attestation_key {
+            key.sign_slot_status(&req, response.get_mut());
+        }
+        Ok(response)
+    }
+
+    async fn batch_get_slot_status(
+        &self,
+        request: Request<BatchGetSlotStatusRequest>,
+    ) -> Result<Response<BatchGetSlotStatusResponse>, Status> {
+        let req = request.get_ref().clone();
+        let mut response = self
+            .batch_slot_status(request, !self.implicit_unlocks)
+            .await?;
+        if let Some(key) = &self.attestation_key {
+            key.sign_batch_slot_status(&req, response.get_mut());
+        }
+        Ok(response)
     }
 
-    impl MockBitcoinService {
-        fn new() -> Self {
-            Self {
-                confirmed_txs: Arc::new(Mutex::new(Vec::new())),
+    async fn finalize_block(
+        &self,
+        request: Request<FinalizeBlockRequest>,
+    ) -> Result<Response<FinalizeBlockResponse>, Status> {
+        // In shadow mode the block is decided and rolled back like a status query
+        if !self.shadow {
+            if let Some(status) = self.write_rejection(request.metadata()) {
+                return Err(status);
             }
         }
+        let req = request.into_inner();
+        let mut violations = FieldViolations::default();
+        violations.check_namespace(&req.namespace);
+        if let Some(status) = violations.into_status() {
+            return Err(status);
+        }
+        if let Some(replication) = &self.replication {
+            replication.observe_block(req.current_block);
+        }
+
+        let slots = self
+            .db
+            .active_lock_slots(&req.namespace, req.current_block)
+            .map_err(database_status)?
+            .into_iter()
+            .map(|(contract_address, slot_index)| SlotIdentifier {
+                contract_address,
+                slot_index,
+            })
+            .collect();
+        let statuses = self
+            .evaluate_slots(
+                BatchGetSlotStatusRequest {
+                    namespace: req.namespace.clone(),
+                    current_block: req.current_block,
+                    btc_block: req.btc_block,
+                    slots,
+                    max_staleness_blocks: None,
+                },
+                false,
+            )
+            .await?
+            .into_inner()
+            .slots;
+        let ended: Vec<_> = statuses
+            .into_iter()
+            .filter(|slot| {
+                slot.status != get_slot_status_response::Status::Locked as i32
+                    && slot.status != get_slot_status_response::Status::AtRisk as i32
+            })
+            .collect();
+        let active_locks = self
+            .db
+            .active_lock_count(Some(&req.namespace))
+            .map_err(database_status)?;
+
+        tracing::info!(
+            "FinalizeBlock: namespace={}, current_block={}, btc_block={}, ended={}, active_locks={}",
+            req.namespace,
+            req.current_block,
+            req.btc_block,
+            ended.len(),
+            active_locks
+        );
+        Ok(Response::new(FinalizeBlockResponse {
+            ended,
+            active_locks,
+        }))
+    }
+
+    async fn peek_slot_status(
+        &self,
+        request: Request<GetSlotStatusRequest>,
+    ) -> Result<Response<GetSlotStatusResponse>, Status> {
+        self.slot_status(request, true).await
+    }
+
+    async fn batch_peek_slot_status(
+        &self,
+        request: Request<BatchGetSlotStatusRequest>,
+    ) -> Result<Response<BatchGetSlotStatusResponse>, Status> {
+        self.batch_slot_status(request, true).await
+    }
+
+    async fn get_server_info(
+        &self,
+        _request: Request<GetServerInfoRequest>,
+    ) -> Result<Response<GetServerInfoResponse>, Status> {
+        Ok(Response::new(GetServerInfoResponse {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            labels: Some((&self.labels).into()),
+            preferred_batch_size: self.batch_size_hints.preferred,
+            max_batch_size: self.batch_size_hints.max,
+        }))
+    }
+
+    async fn get_public_key(
+        &self,
+        _request: Request<GetPublicKeyRequest>,
+    ) -> Result<Response<GetPublicKeyResponse>, Status> {
+        let key = self
+            .attestation_key
+            .as_ref()
+            .ok_or_else(no_signing_key_status)?;
+        Ok(Response::new(GetPublicKeyResponse {
+            public_key: key.public_key().serialize().to_vec(),
+        }))
+    }
+
+    async fn get_lock_commitment(
+        &self,
+        _request: Request<GetLockCommitmentRequest>,
+    ) -> Result<Response<GetLockCommitmentResponse>, Status> {
+        let (_, response) = self
+            .lock_commitment()
+            .ok_or_else(lock_commitment_disabled_status)?;
+        Ok(Response::new(response))
+    }
+
+    async fn get_lock_inclusion_proof(
+        &self,
+        request: Request<GetLockInclusionProofRequest>,
+    ) -> Result<Response<GetLockInclusionProofResponse>, Status> {
+        let mut req = request.into_inner();
+        let mut violations = FieldViolations::default();
+        violations.check_namespace(&req.namespace);
+        violations.check_slot("", &req.contract_address, &mut req.slot_index);
+        if let Some(status) = violations.into_status() {
+            return Err(status);
+        }
+
+        let (commitment, commitment_response) = self
+            .lock_commitment()
+            .ok_or_else(lock_commitment_disabled_status)?;
+        let (lock, leaf_index, siblings) = commitment
+            .proof(&req.namespace, &req.contract_address, &req.slot_index)
+            .ok_or_else(|| lock_not_committed_status(&req.contract_address, &req.slot_index))?;
+        Ok(Response::new(GetLockInclusionProofResponse {
+            lock: Some(lock.clone()),
+            leaf_index: leaf_index as u64,
+            siblings: siblings.iter().map(|sibling| sibling.to_vec()).collect(),
+            commitment: Some(commitment_response),
+        }))
+    }
 
-        fn add_confirmed_tx(&self, txid: &str) {
-            let mut txs = self.confirmed_txs.lock().unwrap();
-            println!("adding confirmed tx: {}", txid);
-            txs.push(txid.to_string());
+    async fn get_sentinel_info(
+        &self,
+        request: Request<GetSentinelInfoRequest>,
+    ) -> Result<Response<GetSentinelInfoResponse>, Status> {
+        let req = request.into_inner();
+        let mut violations = FieldViolations::default();
+        violations.check_namespace(&req.namespace);
+        if let Some(status) = violations.into_status() {
+            return Err(status);
         }
+        let chain_info = self
+            .bitcoin_service
+            .chain_info()
+            .await
+            .map_err(bitcoin_rpc_status)?;
+        let schema_version = self.db.schema_version().map_err(database_status)?;
+        let active_locks = self
+            .db
+            .active_lock_count(Some(&req.namespace))
+            .map_err(database_status)?;
+        let thresholds = self.namespace_thresholds(&req.namespace);
+
+        Ok(Response::new(GetSentinelInfoResponse {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            confirmation_threshold: thresholds
+                .confirmation_threshold
+                .unwrap_or_else(|| self.bitcoin_service.confirmation_threshold()),
+            revert_threshold: thresholds.revert_threshold.unwrap_or(self.revert_threshold),
+            bitcoin_network: network_name(chain_info.network).to_string(),
+            bitcoin_tip_height: chain_info.tip_height,
+            schema_version,
+            active_locks,
+            shadow_mode: self.shadow,
+        }))
     }
 
-    #[tonic::async_trait]
-    impl BitcoinRpcServiceAPI for MockBitcoinService {
-        async fn is_tx_confirmed(&self, txid: &str) -> anyhow::Result<bool> {
-            let txs = self.confirmed_txs.lock().unwrap();
-            println!("txid: {}, confirmed_txs: {:?}", txid, *txs);
-            Ok(txs.contains(&txid.to_string()))
+    async fn subscribe_slot_events(
+        &self,
+        request: Request<SubscribeSlotEventsRequest>,
+    ) -> Result<Response<Self::SubscribeSlotEventsStream>, Status> {
+        let req = request.into_inner();
+        let mut violations = FieldViolations::default();
+        if let Some(namespace) = &req.namespace {
+            violations.check_namespace(namespace);
         }
+        if let Some(status) = violations.into_status() {
+            return Err(status);
+        }
+        tracing::info!(
+            "SubscribeSlotEvents: namespace={:?}, contract={}",
+            req.namespace,
+            req.contract_address
+        );
+
+        Ok(Response::new(Box::pin(self.events.subscribe(
+            SlotEventFilter {
+                namespace: req.namespace,
+                contract_address:
+                    (!req.contract_address.is_empty()).then_some(req.contract_address),
+            },
+        ))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{
+        MockBitcoinService, DEFAULT_CONFIRMATION_THRESHOLD as MOCK_CONFIRMATION_THRESHOLD,
+    };
+    use sova_sentinel_proto::proto::{ContractSlotData, ExpectedCurrentValue};
+
+    // Lock requests only accept canonical txids
+    const TXID1: &str = "1111111111111111111111111111111111111111111111111111111111111111";
+    const TXID2: &str = "2222222222222222222222222222222222222222222222222222222222222222";
+    const TXID3: &str = "3333333333333333333333333333333333333333333333333333333333333333";
+    const TXID4: &str = "4444444444444444444444444444444444444444444444444444444444444444";
+
+    /// Canonical 32-byte form of a one-byte slot index
+    fn slot_index(idx: u8) -> Vec<u8> {
+        let mut slot_index = vec![0; 32];
+        slot_index[31] = idx;
+        slot_index
+    }
+
+    /// Finalizes a Sova block of the default namespace, as the node does once per block
+    async fn finalize<B: BitcoinRpcServiceAPI + 'static>(
+        service: &SlotLockServiceImpl<B>,
+        current_block: u64,
+        btc_block: u64,
+    ) -> Result<FinalizeBlockResponse, Status> {
+        Ok(service
+            .finalize_block(Request::new(FinalizeBlockRequest {
+                namespace: String::new(),
+                current_block,
+                btc_block,
+            }))
+            .await?
+            .into_inner())
+    }
+
+    #[test]
+    fn test_logged_slots_are_truncated() {
+        let slots = [1, 2, 3, 4, 5];
+        assert_eq!(
+            format!("{:?}", LoggedSlots::new(&slots, 2, |slot| slot * 10)),
+            "[10, 20, ... 3 more]"
+        );
+        assert_eq!(
+            format!("{:?}", LoggedSlots::new(&slots, 5, |slot| *slot)),
+            "[1, 2, 3, 4, 5]"
+        );
+        assert_eq!(
+            format!("{:?}", LoggedSlots::new(&slots, 0, |slot| *slot)),
+            "[... 5 more]"
+        );
     }
 
     #[tokio::test]
@@ -780,13 +3005,19 @@ mod tests {
         let service = SlotLockServiceImpl::new(db, btc, 6);
 
         let request = Request::new(LockSlotRequest {
+            namespace: String::new(),
             locked_at_block: 1000,
             btc_block: 100,
             contract_address: "0x123".to_string(),
             slot_index: vec![1, 2, 3],
             revert_value: vec![4, 5, 6],
             current_value: vec![7, 8, 9],
-            btc_txid: "txid1".to_string(),
+            btc_txid: TXID1.to_string(),
+            raw_tx_hex: String::new(),
+            metadata: Vec::new(),
+            labels: Default::default(),
+            alt_btc_txids: Vec::new(),
+            required_confirmed_txids: 0,
         });
 
         // Test successful lock
@@ -798,13 +3029,19 @@ mod tests {
 
         // Test already locked
         let request = Request::new(LockSlotRequest {
+            namespace: String::new(),
             locked_at_block: 1000,
             btc_block: 100,
             contract_address: "0x123".to_string(),
             slot_index: vec![1, 2, 3],
             revert_value: vec![4, 5, 6],
             current_value: vec![7, 8, 9],
-            btc_txid: "txid2".to_string(),
+            btc_txid: TXID2.to_string(),
+            raw_tx_hex: String::new(),
+            metadata: Vec::new(),
+            labels: Default::default(),
+            alt_btc_txids: Vec::new(),
+            required_confirmed_txids: 0,
         });
 
         let response = service.lock_slot(request).await?;
@@ -817,29 +3054,347 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_get_slot_status_unlocked() -> Result<(), Box<dyn std::error::Error>> {
+    async fn test_lock_slot_validates_metadata_and_labels() -> Result<(), Box<dyn std::error::Error>>
+    {
         let db = crate::db::Database::new(rusqlite::Connection::open_in_memory()?)?;
-        let btc = MockBitcoinService::new();
-        let service = SlotLockServiceImpl::new(db, btc.clone(), 6);
-
+        let service = SlotLockServiceImpl::new(db, MockBitcoinService::new(), 6);
+
+        let status = service
+            .lock_slot(Request::new(LockSlotRequest {
+                namespace: String::new(),
+                locked_at_block: 1000,
+                btc_block: 100,
+                contract_address: "0x123".to_string(),
+                slot_index: vec![1],
+                revert_value: vec![],
+                current_value: vec![],
+                btc_txid: TXID1.to_string(),
+                raw_tx_hex: String::new(),
+                metadata: vec![0; 1025],
+                labels: [
+                    ("deposit".to_string(), "42".to_string()),
+                    ("user id".to_string(), "alice".to_string()),
+                ]
+                .into(),
+                alt_btc_txids: Vec::new(),
+                required_confirmed_txids: 0,
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        let violations = sova_sentinel_proto::bad_request::field_violations(&status);
+        let fields: Vec<_> = violations
+            .iter()
+            .map(|violation| violation.field.as_str())
+            .collect();
+        assert_eq!(fields, vec!["metadata", "labels[\"user id\"]"]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_lock_slot_normalizes_txid() -> Result<(), Box<dyn std::error::Error>> {
+        let db = crate::db::Database::new(rusqlite::Connection::open_in_memory()?)?;
+        let service = SlotLockServiceImpl::new(db, MockBitcoinService::new(), 6);
+        let lock_request = |btc_txid: &str| LockSlotRequest {
+            namespace: String::new(),
+            locked_at_block: 1000,
+            btc_block: 100,
+            contract_address: "0x123".to_string(),
+            slot_index: vec![1],
+            revert_value: vec![4],
+            current_value: vec![7],
+            btc_txid: btc_txid.to_string(),
+            raw_tx_hex: String::new(),
+            metadata: Vec::new(),
+            labels: Default::default(),
+            alt_btc_txids: Vec::new(),
+            required_confirmed_txids: 0,
+        };
+
+        let status = service
+            .lock_slot(Request::new(lock_request("txid1")))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        let status = service
+            .batch_lock_slot(Request::new(BatchLockSlotRequest {
+                namespace: String::new(),
+                locked_at_block: 1000,
+                btc_block: 100,
+                slots: vec![SlotData {
+                    contract_address: "0x123".to_string(),
+                    slot_index: vec![1],
+                    revert_value: vec![4],
+                    current_value: vec![7],
+                    btc_txid: format!("{}0", TXID1),
+                    metadata: Vec::new(),
+                    labels: Default::default(),
+                    alt_btc_txids: Vec::new(),
+                    required_confirmed_txids: 0,
+                }],
+                contract_slots: Vec::new(),
+                atomic: false,
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        let violations = sova_sentinel_proto::bad_request::field_violations(&status);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].field, "slots[0].btc_txid");
+
+        let txid = "ab".repeat(32);
+        service
+            .lock_slot(Request::new(lock_request(&format!(
+                "0x{}",
+                txid.to_uppercase()
+            ))))
+            .await?;
+        let response = service
+            .get_slot_status(Request::new(GetSlotStatusRequest {
+                namespace: String::new(),
+                contract_address: "0x123".to_string(),
+                current_block: 1000,
+                slot_index: vec![1],
+                btc_block: 100,
+                max_staleness_blocks: None,
+            }))
+            .await?
+            .into_inner();
+        assert_eq!(response.btc_txid, txid);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_slot_index_encodings_share_a_lock() -> Result<(), Box<dyn std::error::Error>> {
+        let db = crate::db::Database::new(rusqlite::Connection::open_in_memory()?)?;
+        let service = SlotLockServiceImpl::new(db, MockBitcoinService::new(), 6);
+        let slot = |slot_index: Vec<u8>| SlotData {
+            contract_address: "0x123".to_string(),
+            slot_index,
+            revert_value: vec![4],
+            current_value: vec![7],
+            btc_txid: TXID1.to_string(),
+            metadata: Vec::new(),
+            labels: Default::default(),
+            alt_btc_txids: Vec::new(),
+            required_confirmed_txids: 0,
+        };
+
+        // Two encodings of slot 2 in one batch lock it once
+        let response = service
+            .batch_lock_slot(Request::new(BatchLockSlotRequest {
+                namespace: String::new(),
+                locked_at_block: 1000,
+                btc_block: 100,
+                slots: vec![slot(vec![2]), slot(vec![0, 0, 2])],
+                contract_slots: Vec::new(),
+                atomic: false,
+            }))
+            .await?
+            .into_inner();
+        let statuses: Vec<_> = response.slots.iter().map(|slot| slot.status).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                slot_lock_status::Status::Locked as i32,
+                slot_lock_status::Status::AlreadyLocked as i32,
+            ]
+        );
+
+        let response = service
+            .lock_slot(Request::new(LockSlotRequest {
+                namespace: String::new(),
+                locked_at_block: 1000,
+                btc_block: 100,
+                contract_address: "0x123".to_string(),
+                slot_index: slot_index(2),
+                revert_value: vec![4],
+                current_value: vec![7],
+                btc_txid: TXID1.to_string(),
+                raw_tx_hex: String::new(),
+                metadata: Vec::new(),
+                labels: Default::default(),
+                alt_btc_txids: Vec::new(),
+                required_confirmed_txids: 0,
+            }))
+            .await?;
+        assert_eq!(
+            response.get_ref().status,
+            lock_slot_response::Status::AlreadyLocked as i32
+        );
+        let response = service
+            .get_slot_status(Request::new(GetSlotStatusRequest {
+                namespace: String::new(),
+                contract_address: "0x123".to_string(),
+                current_block: 1000,
+                slot_index: vec![0, 2],
+                btc_block: 100,
+                max_staleness_blocks: None,
+            }))
+            .await?
+            .into_inner();
+        assert_eq!(
+            response.status,
+            get_slot_status_response::Status::Locked as i32
+        );
+        assert_eq!(response.slot_index, slot_index(2));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_batch_validation() -> Result<(), Box<dyn std::error::Error>> {
+        let db = crate::db::Database::new(rusqlite::Connection::open_in_memory()?)?;
+        let service = SlotLockServiceImpl::new(db, MockBitcoinService::new(), 6);
+        let status_request = |contract_addresses: Vec<String>| BatchGetSlotStatusRequest {
+            namespace: String::new(),
+            current_block: 1000,
+            btc_block: 100,
+            slots: contract_addresses
+                .into_iter()
+                .map(|contract_address| SlotIdentifier {
+                    contract_address,
+                    slot_index: vec![1],
+                })
+                .collect(),
+            max_staleness_blocks: None,
+        };
+
+        let status = service
+            .batch_get_slot_status(Request::new(status_request(vec![
+                "0x123".to_string(),
+                "bridge".to_string(),
+            ])))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        let violations = sova_sentinel_proto::bad_request::field_violations(&status);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].field, "slots[1].contract_address");
+
+        // Batches are bounded even without a configured maximum
+        let max_batch_size = DEFAULT_MAX_BATCH_SIZE as usize;
+        let status = service
+            .batch_get_slot_status(Request::new(status_request(vec![
+                "0x123".to_string();
+                max_batch_size + 1
+            ])))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        service
+            .batch_get_slot_status(Request::new(status_request(vec![
+                "0x123".to_string();
+                max_batch_size
+            ])))
+            .await?;
+
+        // Batches larger than one database statement are split across statements
+        let slots: Vec<_> = (0..1200u16)
+            .map(|idx| SlotIdentifier {
+                contract_address: "0x456".to_string(),
+                slot_index: idx.to_be_bytes().to_vec(),
+            })
+            .collect();
+        service
+            .batch_lock_slot(Request::new(BatchLockSlotRequest {
+                namespace: String::new(),
+                locked_at_block: 1000,
+                btc_block: 100,
+                slots: slots
+                    .iter()
+                    .map(|slot| SlotData {
+                        contract_address: slot.contract_address.clone(),
+                        slot_index: slot.slot_index.clone(),
+                        revert_value: vec![4],
+                        current_value: vec![7],
+                        btc_txid: TXID1.to_string(),
+                        metadata: Vec::new(),
+                        labels: Default::default(),
+                        alt_btc_txids: Vec::new(),
+                        required_confirmed_txids: 0,
+                    })
+                    .collect(),
+                contract_slots: Vec::new(),
+                atomic: false,
+            }))
+            .await?;
+        let response = service
+            .batch_get_slot_status(Request::new(BatchGetSlotStatusRequest {
+                namespace: String::new(),
+                current_block: 1000,
+                btc_block: 100,
+                slots: slots.clone(),
+                max_staleness_blocks: None,
+            }))
+            .await?
+            .into_inner();
+        assert!(response
+            .slots
+            .iter()
+            .all(|slot| slot.status == get_slot_status_response::Status::Locked as i32));
+        service
+            .batch_unlock_slot(Request::new(BatchUnlockSlotRequest {
+                namespace: String::new(),
+                current_block: 1001,
+                btc_block: 101,
+                slots: slots.clone(),
+                expected_current_values: Vec::new(),
+            }))
+            .await?;
+        let response = service
+            .batch_get_slot_status(Request::new(BatchGetSlotStatusRequest {
+                namespace: String::new(),
+                current_block: 1002,
+                btc_block: 101,
+                slots,
+                max_staleness_blocks: None,
+            }))
+            .await?
+            .into_inner();
+        assert_eq!(response.slots.len(), 1200);
+        assert!(response
+            .slots
+            .iter()
+            .all(|slot| slot.status == get_slot_status_response::Status::Unlocked as i32));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_slot_status_unlocked() -> Result<(), Box<dyn std::error::Error>> {
+        let db = crate::db::Database::new(rusqlite::Connection::open_in_memory()?)?;
+        let btc = MockBitcoinService::new();
+        let service = SlotLockServiceImpl::new(db, btc.clone(), 6);
+
         // Lock a slot first
         let lock_request = Request::new(LockSlotRequest {
+            namespace: String::new(),
             locked_at_block: 1000,
             btc_block: 95,
             contract_address: "0x123".to_string(),
             slot_index: vec![1, 2, 3],
             revert_value: vec![4, 5, 6],
             current_value: vec![7, 8, 9],
-            btc_txid: "txid1".to_string(),
+            btc_txid: TXID1.to_string(),
+            raw_tx_hex: String::new(),
+            metadata: Vec::new(),
+            labels: Default::default(),
+            alt_btc_txids: Vec::new(),
+            required_confirmed_txids: 0,
         });
         service.lock_slot(lock_request).await?;
 
         // Test locked status
         let request = Request::new(GetSlotStatusRequest {
+            namespace: String::new(),
             current_block: 1001,
             btc_block: 96,
             contract_address: "0x123".to_string(),
             slot_index: vec![1, 2, 3],
+            max_staleness_blocks: None,
         });
 
         let response = service.get_slot_status(request).await?;
@@ -849,14 +3404,16 @@ mod tests {
         );
 
         // Can modify mock after it's moved
-        btc.add_confirmed_tx("txid1");
+        btc.add_confirmed_tx(TXID1);
 
         // Test confirmed transaction
         let request = Request::new(GetSlotStatusRequest {
+            namespace: String::new(),
             current_block: 1002,
             btc_block: 100,
             contract_address: "0x123".to_string(),
             slot_index: vec![1, 2, 3],
+            max_staleness_blocks: None,
         });
 
         let response = service.get_slot_status(request).await?;
@@ -872,26 +3429,37 @@ mod tests {
     async fn test_get_slot_status_revert() -> Result<(), Box<dyn std::error::Error>> {
         let db = crate::db::Database::new(rusqlite::Connection::open_in_memory()?)?;
         let btc = MockBitcoinService::new();
-        let service = SlotLockServiceImpl::new(db, btc.clone(), 6);
+        let metrics = Arc::new(Metrics::default());
+        let service = SlotLockServiceImpl::new(db, btc.clone(), 6)
+            .with_implicit_unlocks(true)
+            .with_metrics(metrics.clone());
 
         // Lock a slot at btc_block 100
         let lock_request = Request::new(LockSlotRequest {
+            namespace: String::new(),
             locked_at_block: 1000,
             btc_block: 100,
             contract_address: "0x123".to_string(),
             slot_index: vec![1, 2, 3],
             revert_value: vec![4, 5, 6],
             current_value: vec![7, 8, 9],
-            btc_txid: "txid1".to_string(),
+            btc_txid: TXID1.to_string(),
+            raw_tx_hex: String::new(),
+            metadata: Vec::new(),
+            labels: Default::default(),
+            alt_btc_txids: Vec::new(),
+            required_confirmed_txids: 0,
         });
         service.lock_slot(lock_request).await?;
 
         // Check status - should be reverted since block delta > 6
         let request = Request::new(GetSlotStatusRequest {
+            namespace: String::new(),
             current_block: 1000,
             btc_block: 110,
             contract_address: "0x123".to_string(),
             slot_index: vec![1, 2, 3],
+            max_staleness_blocks: None,
         });
 
         let response = service.get_slot_status(request).await?;
@@ -902,6 +3470,18 @@ mod tests {
         assert_eq!(response.get_ref().revert_value, vec![4, 5, 6]);
         assert_eq!(response.get_ref().current_value, vec![7, 8, 9]);
 
+        // The revert is recorded in the metrics
+        let output = metrics.encode()?;
+        assert!(output.lines().any(|line| line
+            .starts_with("sova_sentinel_lock_duration_btc_blocks_sum{")
+            && line.ends_with(" 10")));
+        assert!(output.lines().any(|line| line
+            .starts_with("sova_sentinel_lock_duration_seconds_count{")
+            && line.ends_with(" 1")));
+        assert!(output.lines().any(|line| line
+            .starts_with(r#"sova_sentinel_revert_rate{contract_address="0x123",namespace="""#)
+            && line.ends_with(" 1")));
+
         Ok(())
     }
 
@@ -913,22 +3493,30 @@ mod tests {
 
         // Lock a slot
         let lock_request = Request::new(LockSlotRequest {
+            namespace: String::new(),
             locked_at_block: 1000,
             btc_block: 98, // Only 2 blocks old
             contract_address: "0x123".to_string(),
             slot_index: vec![1, 2, 3],
             revert_value: vec![4, 5, 6],
             current_value: vec![7, 8, 9],
-            btc_txid: "txid1".to_string(),
+            btc_txid: TXID1.to_string(),
+            raw_tx_hex: String::new(),
+            metadata: Vec::new(),
+            labels: Default::default(),
+            alt_btc_txids: Vec::new(),
+            required_confirmed_txids: 0,
         });
         service.lock_slot(lock_request).await?;
 
         // Check status - should be locked since block delta < 6 and tx not confirmed
         let request = Request::new(GetSlotStatusRequest {
+            namespace: String::new(),
             current_block: 1000,
             btc_block: 100,
             contract_address: "0x123".to_string(),
             slot_index: vec![1, 2, 3],
+            max_staleness_blocks: None,
         });
 
         let response = service.get_slot_status(request).await?;
@@ -950,6 +3538,7 @@ mod tests {
 
         // Test batch lock
         let request = Request::new(BatchLockSlotRequest {
+            namespace: String::new(),
             locked_at_block: 1000,
             btc_block: 95,
             slots: vec![
@@ -958,16 +3547,26 @@ mod tests {
                     slot_index: vec![1, 2, 3],
                     revert_value: vec![4, 5, 6],
                     current_value: vec![7, 8, 9],
-                    btc_txid: "txid1".to_string(),
+                    btc_txid: TXID1.to_string(),
+                    metadata: Vec::new(),
+                    labels: Default::default(),
+                    alt_btc_txids: Vec::new(),
+                    required_confirmed_txids: 0,
                 },
                 sova_sentinel_proto::proto::SlotData {
                     contract_address: "0x456".to_string(),
                     slot_index: vec![2, 3, 4],
                     revert_value: vec![5, 6, 7],
                     current_value: vec![8, 9, 10],
-                    btc_txid: "txid2".to_string(),
+                    btc_txid: TXID2.to_string(),
+                    metadata: Vec::new(),
+                    labels: Default::default(),
+                    alt_btc_txids: Vec::new(),
+                    required_confirmed_txids: 0,
                 },
             ],
+            contract_slots: Vec::new(),
+            atomic: false,
         });
 
         let response = service.batch_lock_slot(request).await?;
@@ -988,10 +3587,13 @@ mod tests {
     async fn test_batch_lock_slot() -> Result<(), Box<dyn std::error::Error>> {
         let db = crate::db::Database::new(rusqlite::Connection::open_in_memory()?)?;
         let btc = MockBitcoinService::new();
-        let service = SlotLockServiceImpl::new(db, btc.clone(), 6);
+        let metrics = Arc::new(Metrics::default());
+        let service =
+            SlotLockServiceImpl::new(db.clone(), btc.clone(), 6).with_metrics(metrics.clone());
 
         // Test initial batch lock
         let request = Request::new(BatchLockSlotRequest {
+            namespace: String::new(),
             locked_at_block: 1000,
             btc_block: 95,
             slots: vec![
@@ -1000,16 +3602,26 @@ mod tests {
                     slot_index: vec![1, 2, 3],
                     revert_value: vec![4, 5, 6],
                     current_value: vec![7, 8, 9],
-                    btc_txid: "txid1".to_string(),
+                    btc_txid: TXID1.to_string(),
+                    metadata: Vec::new(),
+                    labels: Default::default(),
+                    alt_btc_txids: Vec::new(),
+                    required_confirmed_txids: 0,
                 },
                 sova_sentinel_proto::proto::SlotData {
                     contract_address: "0x456".to_string(),
                     slot_index: vec![2, 3, 4],
                     revert_value: vec![5, 6, 7],
                     current_value: vec![8, 9, 10],
-                    btc_txid: "txid2".to_string(),
+                    btc_txid: TXID2.to_string(),
+                    metadata: Vec::new(),
+                    labels: Default::default(),
+                    alt_btc_txids: Vec::new(),
+                    required_confirmed_txids: 0,
                 },
             ],
+            contract_slots: Vec::new(),
+            atomic: false,
         });
 
         let response = service.batch_lock_slot(request).await?;
@@ -1025,6 +3637,7 @@ mod tests {
 
         // Test attempting to lock already locked slots
         let request = Request::new(BatchLockSlotRequest {
+            namespace: String::new(),
             locked_at_block: 1000,
             btc_block: 95,
             slots: vec![
@@ -1033,16 +3646,26 @@ mod tests {
                     slot_index: vec![1, 2, 3],
                     revert_value: vec![1, 1, 1],
                     current_value: vec![2, 2, 2],
-                    btc_txid: "txid3".to_string(),
+                    btc_txid: TXID3.to_string(),
+                    metadata: Vec::new(),
+                    labels: Default::default(),
+                    alt_btc_txids: Vec::new(),
+                    required_confirmed_txids: 0,
                 },
                 sova_sentinel_proto::proto::SlotData {
                     contract_address: "0x789".to_string(), // New slot
                     slot_index: vec![3, 4, 5],
                     revert_value: vec![6, 7, 8],
                     current_value: vec![9, 10, 11],
-                    btc_txid: "txid4".to_string(),
+                    btc_txid: TXID4.to_string(),
+                    metadata: Vec::new(),
+                    labels: Default::default(),
+                    alt_btc_txids: Vec::new(),
+                    required_confirmed_txids: 0,
                 },
             ],
+            contract_slots: Vec::new(),
+            atomic: false,
         });
 
         let response = service.batch_lock_slot(request).await?;
@@ -1056,6 +3679,108 @@ mod tests {
             slot_lock_status::Status::Locked as i32
         );
 
+        // The conflict is counted in the database and the metrics
+        let stats = db.lock_conflict_stats(None, None)?;
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].contract_address, "0x123");
+        assert_eq!(stats[0].conflicts, 1);
+        assert!(metrics
+            .encode()?
+            .contains(r#"lock_conflicts_total{contract_address="0x123""#));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_batch_lock_contract_slots() -> Result<(), Box<dyn std::error::Error>> {
+        let db = crate::db::Database::new(rusqlite::Connection::open_in_memory()?)?;
+        let service = SlotLockServiceImpl::new(db, MockBitcoinService::new(), 6);
+
+        let contract_slot = |slot_index: u8| ContractSlotData {
+            slot_index: vec![slot_index],
+            revert_value: vec![4],
+            current_value: vec![7],
+            btc_txid: TXID1.to_string(),
+            metadata: Vec::new(),
+            labels: Default::default(),
+            alt_btc_txids: Vec::new(),
+            required_confirmed_txids: 0,
+        };
+        let response = service
+            .batch_lock_slot(Request::new(BatchLockSlotRequest {
+                namespace: String::new(),
+                locked_at_block: 1000,
+                btc_block: 100,
+                slots: vec![SlotData {
+                    contract_address: "0x456".to_string(),
+                    slot_index: vec![1],
+                    revert_value: vec![4],
+                    current_value: vec![7],
+                    btc_txid: TXID1.to_string(),
+                    metadata: Vec::new(),
+                    labels: Default::default(),
+                    alt_btc_txids: Vec::new(),
+                    required_confirmed_txids: 0,
+                }],
+                contract_slots: vec![ContractSlots {
+                    contract_address: "0x123".to_string(),
+                    slots: vec![contract_slot(1), contract_slot(2)],
+                }],
+                atomic: false,
+            }))
+            .await?
+            .into_inner();
+
+        // Flat slots come first, then the grouped slots in order
+        let statuses: Vec<_> = response
+            .slots
+            .iter()
+            .map(|slot| {
+                (
+                    slot.contract_address.as_str(),
+                    slot.slot_index.clone(),
+                    slot.status,
+                )
+            })
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                (
+                    "0x456",
+                    slot_index(1),
+                    slot_lock_status::Status::Locked as i32
+                ),
+                (
+                    "0x123",
+                    slot_index(1),
+                    slot_lock_status::Status::Locked as i32
+                ),
+                (
+                    "0x123",
+                    slot_index(2),
+                    slot_lock_status::Status::Locked as i32
+                ),
+            ]
+        );
+
+        let status = service
+            .get_slot_status(Request::new(GetSlotStatusRequest {
+                namespace: String::new(),
+                current_block: 1000,
+                btc_block: 100,
+                contract_address: "0x123".to_string(),
+                slot_index: vec![2],
+                max_staleness_blocks: None,
+            }))
+            .await?
+            .into_inner();
+        assert_eq!(
+            status.status,
+            get_slot_status_response::Status::Locked as i32
+        );
+        assert_eq!(status.btc_txid, TXID1);
+
         Ok(())
     }
 
@@ -1067,6 +3792,7 @@ mod tests {
 
         // First lock some slots
         let request = Request::new(BatchLockSlotRequest {
+            namespace: String::new(),
             locked_at_block: 1000,
             btc_block: 95,
             slots: vec![
@@ -1075,24 +3801,35 @@ mod tests {
                     slot_index: vec![1, 2, 3],
                     revert_value: vec![4, 5, 6],
                     current_value: vec![7, 8, 9],
-                    btc_txid: "txid1".to_string(),
+                    btc_txid: TXID1.to_string(),
+                    metadata: Vec::new(),
+                    labels: Default::default(),
+                    alt_btc_txids: Vec::new(),
+                    required_confirmed_txids: 0,
                 },
                 sova_sentinel_proto::proto::SlotData {
                     contract_address: "0x456".to_string(),
                     slot_index: vec![2, 3, 4],
                     revert_value: vec![5, 6, 7],
                     current_value: vec![8, 9, 10],
-                    btc_txid: "txid1".to_string(),
+                    btc_txid: TXID1.to_string(),
+                    metadata: Vec::new(),
+                    labels: Default::default(),
+                    alt_btc_txids: Vec::new(),
+                    required_confirmed_txids: 0,
                 },
             ],
+            contract_slots: Vec::new(),
+            atomic: false,
         });
         service.batch_lock_slot(request).await?;
 
         // Confirm the transaction
-        btc.add_confirmed_tx("txid1");
+        btc.add_confirmed_tx(TXID1);
 
         // Check status - should be unlocked since tx is confirmed
         let request = Request::new(BatchGetSlotStatusRequest {
+            namespace: String::new(),
             current_block: 1001,
             btc_block: 100,
             slots: vec![
@@ -1105,6 +3842,7 @@ mod tests {
                     slot_index: vec![2, 3, 4],
                 },
             ],
+            max_staleness_blocks: None,
         });
 
         let response = service.batch_get_slot_status(request).await?;
@@ -1129,6 +3867,7 @@ mod tests {
 
         // First lock some slots at block 100
         let request = Request::new(BatchLockSlotRequest {
+            namespace: String::new(),
             locked_at_block: 1000,
             btc_block: 100,
             slots: vec![
@@ -1137,21 +3876,32 @@ mod tests {
                     slot_index: vec![1, 2, 3],
                     revert_value: vec![4, 5, 6],
                     current_value: vec![7, 8, 9],
-                    btc_txid: "txid1".to_string(),
+                    btc_txid: TXID1.to_string(),
+                    metadata: Vec::new(),
+                    labels: Default::default(),
+                    alt_btc_txids: Vec::new(),
+                    required_confirmed_txids: 0,
                 },
                 sova_sentinel_proto::proto::SlotData {
                     contract_address: "0x456".to_string(),
                     slot_index: vec![2, 3, 4],
                     revert_value: vec![5, 6, 7],
                     current_value: vec![8, 9, 10],
-                    btc_txid: "txid1".to_string(),
+                    btc_txid: TXID1.to_string(),
+                    metadata: Vec::new(),
+                    labels: Default::default(),
+                    alt_btc_txids: Vec::new(),
+                    required_confirmed_txids: 0,
                 },
             ],
+            contract_slots: Vec::new(),
+            atomic: false,
         });
         service.batch_lock_slot(request).await?;
 
         // Check status - should be reverted since block delta > 6
         let request = Request::new(BatchGetSlotStatusRequest {
+            namespace: String::new(),
             current_block: 1001,
             btc_block: 110,
             slots: vec![
@@ -1164,6 +3914,7 @@ mod tests {
                     slot_index: vec![2, 3, 4],
                 },
             ],
+            max_staleness_blocks: None,
         });
 
         let response = service.batch_get_slot_status(request).await?;
@@ -1192,22 +3943,30 @@ mod tests {
 
         // Lock a slot for a future block
         let lock_request = Request::new(LockSlotRequest {
+            namespace: String::new(),
             locked_at_block: 1001,
             btc_block: 100,
             contract_address: "0x123".to_string(),
             slot_index: vec![1, 2, 3],
             revert_value: vec![4, 5, 6],
             current_value: vec![7, 8, 9],
-            btc_txid: "txid1".to_string(),
+            btc_txid: TXID1.to_string(),
+            raw_tx_hex: String::new(),
+            metadata: Vec::new(),
+            labels: Default::default(),
+            alt_btc_txids: Vec::new(),
+            required_confirmed_txids: 0,
         });
         service.lock_slot(lock_request).await?;
 
         // Check status at block 1000 (before the lock's start_block)
         let request = Request::new(GetSlotStatusRequest {
+            namespace: String::new(),
             current_block: 1000,
             btc_block: 100,
             contract_address: "0x123".to_string(),
             slot_index: vec![1, 2, 3],
+            max_staleness_blocks: None,
         });
 
         let response = service.get_slot_status(request).await?;
@@ -1221,10 +3980,12 @@ mod tests {
 
         // Now check at block 1001 (equal to the lock's start_block)
         let request = Request::new(GetSlotStatusRequest {
+            namespace: String::new(),
             current_block: 1001, // Current block equals locked_block
             btc_block: 100,
             contract_address: "0x123".to_string(),
             slot_index: vec![1, 2, 3],
+            max_staleness_blocks: None,
         });
 
         let response = service.get_slot_status(request).await?;
@@ -1245,6 +4006,7 @@ mod tests {
 
         // Lock slots for a future block
         let request = Request::new(BatchLockSlotRequest {
+            namespace: String::new(),
             locked_at_block: 1001,
             btc_block: 100,
             slots: vec![
@@ -1253,21 +4015,32 @@ mod tests {
                     slot_index: vec![1, 2, 3],
                     revert_value: vec![4, 5, 6],
                     current_value: vec![7, 8, 9],
-                    btc_txid: "txid1".to_string(),
+                    btc_txid: TXID1.to_string(),
+                    metadata: Vec::new(),
+                    labels: Default::default(),
+                    alt_btc_txids: Vec::new(),
+                    required_confirmed_txids: 0,
                 },
                 sova_sentinel_proto::proto::SlotData {
                     contract_address: "0x456".to_string(),
                     slot_index: vec![2, 3, 4],
                     revert_value: vec![5, 6, 7],
                     current_value: vec![8, 9, 10],
-                    btc_txid: "txid2".to_string(),
+                    btc_txid: TXID2.to_string(),
+                    metadata: Vec::new(),
+                    labels: Default::default(),
+                    alt_btc_txids: Vec::new(),
+                    required_confirmed_txids: 0,
                 },
             ],
+            contract_slots: Vec::new(),
+            atomic: false,
         });
         service.batch_lock_slot(request).await?;
 
         // Check status at block 1000 (before the lock's start_block)
         let request = Request::new(BatchGetSlotStatusRequest {
+            namespace: String::new(),
             current_block: 1000,
             btc_block: 100,
             slots: vec![
@@ -1280,6 +4053,7 @@ mod tests {
                     slot_index: vec![2, 3, 4],
                 },
             ],
+            max_staleness_blocks: None,
         });
 
         let response = service.batch_get_slot_status(request).await?;
@@ -1296,6 +4070,7 @@ mod tests {
 
         // Now check at block 1001 (equal to the lock's start_block)
         let request = Request::new(BatchGetSlotStatusRequest {
+            namespace: String::new(),
             current_block: 1001, // Current block equals locked_block
             btc_block: 100,
             slots: vec![
@@ -1308,6 +4083,7 @@ mod tests {
                     slot_index: vec![2, 3, 4],
                 },
             ],
+            max_staleness_blocks: None,
         });
 
         let response = service.batch_get_slot_status(request).await?;
@@ -1338,10 +4114,11 @@ mod tests {
         let slot_b_index = vec![4, 5, 6];
         let revert_value = vec![7, 8, 9];
         let current_value = vec![10, 11, 12];
-        let btc_txid = "txid123";
+        let btc_txid = TXID1;
 
         // Initial check that slots are unlocked
         let get_status_req = Request::new(BatchGetSlotStatusRequest {
+            namespace: String::new(),
             current_block: 2,
             btc_block: 101,
             slots: vec![
@@ -1354,6 +4131,7 @@ mod tests {
                     slot_index: slot_b_index.clone(),
                 },
             ],
+            max_staleness_blocks: None,
         });
 
         let response = service.batch_get_slot_status(get_status_req).await?;
@@ -1369,6 +4147,7 @@ mod tests {
 
         // Lock both slots
         let lock_req = Request::new(BatchLockSlotRequest {
+            namespace: String::new(),
             locked_at_block: 3,
             btc_block: 101,
             slots: vec![
@@ -1378,6 +4157,10 @@ mod tests {
                     revert_value: revert_value.clone(),
                     current_value: current_value.clone(),
                     btc_txid: btc_txid.to_string(),
+                    metadata: Vec::new(),
+                    labels: Default::default(),
+                    alt_btc_txids: Vec::new(),
+                    required_confirmed_txids: 0,
                 },
                 sova_sentinel_proto::proto::SlotData {
                     contract_address: contract_address.to_string(),
@@ -1385,8 +4168,14 @@ mod tests {
                     revert_value: revert_value.clone(),
                     current_value: current_value.clone(),
                     btc_txid: btc_txid.to_string(),
+                    metadata: Vec::new(),
+                    labels: Default::default(),
+                    alt_btc_txids: Vec::new(),
+                    required_confirmed_txids: 0,
                 },
             ],
+            contract_slots: Vec::new(),
+            atomic: false,
         });
 
         let response = service.batch_lock_slot(lock_req).await?;
@@ -1402,6 +4191,7 @@ mod tests {
 
         // Check status at block 2 (before lock block) - should be unlocked
         let get_status_req = Request::new(BatchGetSlotStatusRequest {
+            namespace: String::new(),
             current_block: 2,
             btc_block: 101,
             slots: vec![
@@ -1414,6 +4204,7 @@ mod tests {
                     slot_index: slot_b_index.clone(),
                 },
             ],
+            max_staleness_blocks: None,
         });
 
         let response = service.batch_get_slot_status(get_status_req).await?;
@@ -1429,6 +4220,7 @@ mod tests {
 
         // Try to lock again - should be already locked
         let lock_req = Request::new(BatchLockSlotRequest {
+            namespace: String::new(),
             locked_at_block: 3,
             btc_block: 101,
             slots: vec![
@@ -1438,6 +4230,10 @@ mod tests {
                     revert_value: revert_value.clone(),
                     current_value: current_value.clone(),
                     btc_txid: btc_txid.to_string(),
+                    metadata: Vec::new(),
+                    labels: Default::default(),
+                    alt_btc_txids: Vec::new(),
+                    required_confirmed_txids: 0,
                 },
                 sova_sentinel_proto::proto::SlotData {
                     contract_address: contract_address.to_string(),
@@ -1445,8 +4241,14 @@ mod tests {
                     revert_value: revert_value.clone(),
                     current_value: current_value.clone(),
                     btc_txid: btc_txid.to_string(),
+                    metadata: Vec::new(),
+                    labels: Default::default(),
+                    alt_btc_txids: Vec::new(),
+                    required_confirmed_txids: 0,
                 },
             ],
+            contract_slots: Vec::new(),
+            atomic: false,
         });
 
         let response = service.batch_lock_slot(lock_req).await?;
@@ -1462,6 +4264,7 @@ mod tests {
 
         // Check individual slot status at block 3 with high btc block - should be reverted
         let get_status_req = Request::new(BatchGetSlotStatusRequest {
+            namespace: String::new(),
             current_block: 3,
             btc_block: 221,
             slots: vec![
@@ -1474,6 +4277,7 @@ mod tests {
                     slot_index: slot_b_index.clone(),
                 },
             ],
+            max_staleness_blocks: None,
         });
 
         let response = service.batch_get_slot_status(get_status_req).await?;
@@ -1489,6 +4293,7 @@ mod tests {
 
         // Repeat the previous check, the result should be the same
         let get_status_req = Request::new(BatchGetSlotStatusRequest {
+            namespace: String::new(),
             current_block: 3,
             btc_block: 221,
             slots: vec![
@@ -1501,6 +4306,7 @@ mod tests {
                     slot_index: slot_b_index.clone(),
                 },
             ],
+            max_staleness_blocks: None,
         });
 
         let response = service.batch_get_slot_status(get_status_req).await?;
@@ -1514,8 +4320,12 @@ mod tests {
             get_slot_status_response::Status::Reverted as i32
         );
 
+        // The node finalizes block 3, ending the reverted locks
+        finalize(&service, 3, 221).await?;
+
         // Lock slots again at new block height
         let lock_req = Request::new(BatchLockSlotRequest {
+            namespace: String::new(),
             locked_at_block: 4,
             btc_block: 221,
             slots: vec![
@@ -1525,6 +4335,10 @@ mod tests {
                     revert_value: revert_value.clone(),
                     current_value: current_value.clone(),
                     btc_txid: btc_txid.to_string(),
+                    metadata: Vec::new(),
+                    labels: Default::default(),
+                    alt_btc_txids: Vec::new(),
+                    required_confirmed_txids: 0,
                 },
                 sova_sentinel_proto::proto::SlotData {
                     contract_address: contract_address.to_string(),
@@ -1532,8 +4346,14 @@ mod tests {
                     revert_value: revert_value.clone(),
                     current_value: current_value.clone(),
                     btc_txid: btc_txid.to_string(),
+                    metadata: Vec::new(),
+                    labels: Default::default(),
+                    alt_btc_txids: Vec::new(),
+                    required_confirmed_txids: 0,
                 },
             ],
+            contract_slots: Vec::new(),
+            atomic: false,
         });
 
         let response = service.batch_lock_slot(lock_req).await?;
@@ -1549,6 +4369,7 @@ mod tests {
 
         // Check batch status at block 3 - should still be reverted
         let get_status_req = Request::new(BatchGetSlotStatusRequest {
+            namespace: String::new(),
             current_block: 3,
             btc_block: 221,
             slots: vec![
@@ -1561,6 +4382,7 @@ mod tests {
                     slot_index: slot_b_index.clone(),
                 },
             ],
+            max_staleness_blocks: None,
         });
 
         let response = service.batch_get_slot_status(get_status_req).await?;
@@ -1586,13 +4408,19 @@ mod tests {
 
         // Lock a slot at block 1000
         let lock_request = Request::new(LockSlotRequest {
+            namespace: String::new(),
             locked_at_block: 1000, // Start block
             btc_block: 100,
             contract_address: "0x123".to_string(),
             slot_index: vec![1, 2, 3],
             revert_value: vec![4, 5, 6],
             current_value: vec![7, 8, 9],
-            btc_txid: "txid1".to_string(),
+            btc_txid: TXID1.to_string(),
+            raw_tx_hex: String::new(),
+            metadata: Vec::new(),
+            labels: Default::default(),
+            alt_btc_txids: Vec::new(),
+            required_confirmed_txids: 0,
         });
 
         let response = service.lock_slot(lock_request).await?;
@@ -1603,10 +4431,12 @@ mod tests {
 
         // Check status at block 999 (before start_block)
         let status_request = Request::new(GetSlotStatusRequest {
+            namespace: String::new(),
             current_block: 999,
             btc_block: 100,
             contract_address: "0x123".to_string(),
             slot_index: vec![1, 2, 3],
+            max_staleness_blocks: None,
         });
 
         let response = service.get_slot_status(status_request).await?;
@@ -1618,10 +4448,12 @@ mod tests {
 
         // Check status at start_block
         let status_request = Request::new(GetSlotStatusRequest {
+            namespace: String::new(),
             current_block: 1000,
             btc_block: 100,
             contract_address: "0x123".to_string(),
             slot_index: vec![1, 2, 3],
+            max_staleness_blocks: None,
         });
 
         let response = service.get_slot_status(status_request).await?;
@@ -1644,6 +4476,7 @@ mod tests {
 
         // Lock two slots
         let lock_request = Request::new(BatchLockSlotRequest {
+            namespace: String::new(),
             locked_at_block: 1000,
             btc_block: 100,
             slots: vec![
@@ -1652,16 +4485,26 @@ mod tests {
                     slot_index: vec![1, 2, 3],
                     revert_value: vec![4, 5, 6],
                     current_value: vec![7, 8, 9],
-                    btc_txid: "txid1".to_string(),
+                    btc_txid: TXID1.to_string(),
+                    metadata: Vec::new(),
+                    labels: Default::default(),
+                    alt_btc_txids: Vec::new(),
+                    required_confirmed_txids: 0,
                 },
                 SlotData {
                     contract_address: "0x123".to_string(),
                     slot_index: vec![4, 5, 6],
                     revert_value: vec![7, 8, 9],
                     current_value: vec![10, 11, 12],
-                    btc_txid: "txid2".to_string(),
+                    btc_txid: TXID2.to_string(),
+                    metadata: Vec::new(),
+                    labels: Default::default(),
+                    alt_btc_txids: Vec::new(),
+                    required_confirmed_txids: 0,
                 },
             ],
+            contract_slots: Vec::new(),
+            atomic: false,
         });
 
         let response = service.batch_lock_slot(lock_request).await?;
@@ -1677,6 +4520,7 @@ mod tests {
 
         // Check status at block 999 (before start_block)
         let status_request = Request::new(BatchGetSlotStatusRequest {
+            namespace: String::new(),
             current_block: 999,
             btc_block: 100,
             slots: vec![
@@ -1689,6 +4533,7 @@ mod tests {
                     slot_index: vec![4, 5, 6],
                 },
             ],
+            max_staleness_blocks: None,
         });
 
         let response = service.batch_get_slot_status(status_request).await?;
@@ -1706,6 +4551,7 @@ mod tests {
 
         // Check status at start_block
         let status_request = Request::new(BatchGetSlotStatusRequest {
+            namespace: String::new(),
             current_block: 1000,
             btc_block: 100,
             slots: vec![
@@ -1718,6 +4564,7 @@ mod tests {
                     slot_index: vec![4, 5, 6],
                 },
             ],
+            max_staleness_blocks: None,
         });
 
         let response = service.batch_get_slot_status(status_request).await?;
@@ -1735,4 +4582,2665 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_batch_size_hints() -> Result<(), Box<dyn std::error::Error>> {
+        let db = crate::db::Database::new(rusqlite::Connection::open_in_memory()?)?;
+        let hints = BatchSizeHints {
+            preferred: 1,
+            max: 2,
+        };
+        let service =
+            SlotLockServiceImpl::new(db, MockBitcoinService::new(), 6).with_batch_size_hints(hints);
+
+        let info = service
+            .get_server_info(Request::new(GetServerInfoRequest {}))
+            .await?;
+        assert_eq!(info.get_ref().preferred_batch_size, 1);
+        assert_eq!(info.get_ref().max_batch_size, 2);
+
+        let slot = |slot_index: u8| SlotIdentifier {
+            contract_address: "0x123".to_string(),
+            slot_index: vec![slot_index],
+        };
+        let response = service
+            .batch_get_slot_status(Request::new(BatchGetSlotStatusRequest {
+                namespace: String::new(),
+                current_block: 100,
+                btc_block: 200,
+                slots: vec![slot(1), slot(2)],
+                max_staleness_blocks: None,
+            }))
+            .await?;
+        assert_eq!(
+            BatchSizeHints::from_metadata(response.metadata()),
+            Some(hints)
+        );
+
+        let status = service
+            .batch_unlock_slot(Request::new(BatchUnlockSlotRequest {
+                namespace: String::new(),
+                current_block: 100,
+                btc_block: 200,
+                slots: vec![slot(1), slot(2), slot(3)],
+                expected_current_values: Vec::new(),
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        let info = sova_sentinel_proto::error_info::error_info(&status).unwrap();
+        assert_eq!(
+            info.reason,
+            sova_sentinel_proto::error_info::BATCH_TOO_LARGE
+        );
+        assert_eq!(info.metadata["max_batch_size"], "2");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_server_info() -> Result<(), Box<dyn std::error::Error>> {
+        let db = crate::db::Database::new(rusqlite::Connection::open_in_memory()?)?;
+        let btc = MockBitcoinService::new();
+        let labels = DeploymentLabels {
+            environment: "testnet".to_string(),
+            region: "eu-west-1".to_string(),
+            instance_id: "sentinel-0".to_string(),
+        };
+        let service = SlotLockServiceImpl::new(db, btc, 6).with_labels(labels);
+
+        let response = service
+            .get_server_info(Request::new(GetServerInfoRequest {}))
+            .await?;
+        assert_eq!(response.get_ref().version, env!("CARGO_PKG_VERSION"));
+
+        let labels = response.get_ref().labels.as_ref().unwrap();
+        assert_eq!(labels.environment, "testnet");
+        assert_eq!(labels.region, "eu-west-1");
+        assert_eq!(labels.instance_id, "sentinel-0");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_signed_status_responses() -> Result<(), Box<dyn std::error::Error>> {
+        let db = crate::db::Database::new(rusqlite::Connection::open_in_memory()?)?;
+        let unsigned = SlotLockServiceImpl::new(db.clone(), MockBitcoinService::new(), 6);
+        let status = unsigned
+            .get_public_key(Request::new(GetPublicKeyRequest {}))
+            .await
+            .unwrap_err();
+        let info = sova_sentinel_proto::error_info::error_info(&status).unwrap();
+        assert_eq!(info.reason, sova_sentinel_proto::error_info::NO_SIGNING_KEY);
+
+        let service = SlotLockServiceImpl::new(db, MockBitcoinService::new(), 6)
+            .with_attestation_key(AttestationKey::from_hex(&"01".repeat(32))?);
+        let public_key = service
+            .get_public_key(Request::new(GetPublicKeyRequest {}))
+            .await?
+            .into_inner()
+            .public_key;
+        let public_key = bitcoin::secp256k1::XOnlyPublicKey::from_slice(&public_key)?;
+
+        let request = GetSlotStatusRequest {
+            namespace: String::new(),
+            current_block: 1000,
+            btc_block: 100,
+            contract_address: "0x123".to_string(),
+            slot_index: vec![1],
+            max_staleness_blocks: None,
+        };
+        let response = service
+            .get_slot_status(Request::new(request.clone()))
+            .await?
+            .into_inner();
+        crate::attestation::verify_slot_status(&public_key, &request, &response)?;
+
+        let request = BatchGetSlotStatusRequest {
+            namespace: String::new(),
+            current_block: 1000,
+            btc_block: 100,
+            slots: vec![SlotIdentifier {
+                contract_address: "0x123".to_string(),
+                slot_index: vec![1],
+            }],
+            max_staleness_blocks: None,
+        };
+        let response = service
+            .batch_get_slot_status(Request::new(request.clone()))
+            .await?
+            .into_inner();
+        assert!(response.slots[0].signature.is_empty());
+        crate::attestation::verify_batch_slot_status(&public_key, &request, &response)?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_lock_commitment() -> Result<(), Box<dyn std::error::Error>> {
+        let db = crate::db::Database::new(rusqlite::Connection::open_in_memory()?)?;
+        let committer = crate::commitment::LockCommitter::new(db.clone(), Duration::from_secs(60));
+        let key = AttestationKey::from_hex(&"01".repeat(32))?;
+        let service = SlotLockServiceImpl::new(db, MockBitcoinService::new(), 6)
+            .with_attestation_key(key.clone())
+            .with_lock_commitments(committer.commitments());
+
+        let status = service
+            .get_lock_commitment(Request::new(GetLockCommitmentRequest {}))
+            .await
+            .unwrap_err();
+        let info = sova_sentinel_proto::error_info::error_info(&status).unwrap();
+        assert_eq!(
+            info.reason,
+            sova_sentinel_proto::error_info::LOCK_COMMITMENT_DISABLED
+        );
+
+        for slot in 1..=3 {
+            service
+                .lock_slot(Request::new(LockSlotRequest {
+                    namespace: String::new(),
+                    locked_at_block: 1000,
+                    btc_block: 100,
+                    contract_address: "0x123".to_string(),
+                    slot_index: vec![slot],
+                    revert_value: vec![],
+                    current_value: vec![slot],
+                    btc_txid: TXID1.to_string(),
+                    raw_tx_hex: String::new(),
+                    metadata: Vec::new(),
+                    labels: Default::default(),
+                    alt_btc_txids: Vec::new(),
+                    required_confirmed_txids: 0,
+                }))
+                .await?;
+        }
+        committer.commit()?;
+
+        let commitment = service
+            .get_lock_commitment(Request::new(GetLockCommitmentRequest {}))
+            .await?
+            .into_inner();
+        assert_eq!(commitment.lock_count, 3);
+        crate::attestation::verify_lock_commitment(&key.public_key(), &commitment)?;
+
+        let proof = service
+            .get_lock_inclusion_proof(Request::new(GetLockInclusionProofRequest {
+                namespace: String::new(),
+                contract_address: "0x123".to_string(),
+                slot_index: vec![2],
+            }))
+            .await?
+            .into_inner();
+        let lock = proof.lock.unwrap();
+        assert_eq!(lock.current_value, vec![2]);
+        assert_eq!(proof.commitment.as_ref(), Some(&commitment));
+        assert!(crate::commitment::verify_inclusion(
+            &commitment.root,
+            &lock,
+            proof.leaf_index,
+            commitment.lock_count,
+            &proof.siblings
+        ));
+
+        let status = service
+            .get_lock_inclusion_proof(Request::new(GetLockInclusionProofRequest {
+                namespace: String::new(),
+                contract_address: "0x123".to_string(),
+                slot_index: vec![4],
+            }))
+            .await
+            .unwrap_err();
+        let info = sova_sentinel_proto::error_info::error_info(&status).unwrap();
+        assert_eq!(
+            info.reason,
+            sova_sentinel_proto::error_info::LOCK_NOT_COMMITTED
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_sentinel_info() -> Result<(), Box<dyn std::error::Error>> {
+        let db = crate::db::Database::new(rusqlite::Connection::open_in_memory()?)?;
+        let service = SlotLockServiceImpl::new(db, MockBitcoinService::new(), 6);
+        service
+            .lock_slot(Request::new(LockSlotRequest {
+                namespace: String::new(),
+                locked_at_block: 1000,
+                btc_block: 100,
+                contract_address: "0x123".to_string(),
+                slot_index: vec![1],
+                revert_value: vec![],
+                current_value: vec![],
+                btc_txid: TXID1.to_string(),
+                raw_tx_hex: String::new(),
+                metadata: Vec::new(),
+                labels: Default::default(),
+                alt_btc_txids: Vec::new(),
+                required_confirmed_txids: 0,
+            }))
+            .await?;
+
+        let info = service
+            .get_sentinel_info(Request::new(GetSentinelInfoRequest::default()))
+            .await?
+            .into_inner();
+        assert_eq!(
+            info,
+            GetSentinelInfoResponse {
+                version: env!("CARGO_PKG_VERSION").to_string(),
+                confirmation_threshold: MOCK_CONFIRMATION_THRESHOLD,
+                revert_threshold: 6,
+                bitcoin_network: "regtest".to_string(),
+                bitcoin_tip_height: 150,
+                schema_version: crate::db::SCHEMA_VERSION,
+                active_locks: 1,
+                shadow_mode: false,
+            }
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_double_spend_statuses() -> Result<(), Box<dyn std::error::Error>> {
+        let db = crate::db::Database::new(rusqlite::Connection::open_in_memory()?)?;
+        let btc = MockBitcoinService::new();
+        let service = SlotLockServiceImpl::new(db, btc.clone(), 6);
+
+        for (slot_index, txid) in [(1u8, TXID1), (2u8, TXID2)] {
+            service
+                .lock_slot(Request::new(LockSlotRequest {
+                    namespace: String::new(),
+                    locked_at_block: 1000,
+                    btc_block: 100,
+                    contract_address: "0x123".to_string(),
+                    slot_index: vec![slot_index],
+                    revert_value: vec![4, 5, 6],
+                    current_value: vec![7, 8, 9],
+                    btc_txid: txid.to_string(),
+                    raw_tx_hex: String::new(),
+                    metadata: Vec::new(),
+                    labels: Default::default(),
+                    alt_btc_txids: Vec::new(),
+                    required_confirmed_txids: 0,
+                }))
+                .await?;
+        }
+        let get_status = |slot_index: u8| {
+            Request::new(GetSlotStatusRequest {
+                namespace: String::new(),
+                current_block: 1001,
+                btc_block: 101,
+                contract_address: "0x123".to_string(),
+                slot_index: vec![slot_index],
+                max_staleness_blocks: None,
+            })
+        };
+
+        // A conflicting spend in the mempool only flags the slot
+        btc.set_double_spend(TXID1, DoubleSpendStatus::AtRisk);
+        let response = service.get_slot_status(get_status(1)).await?;
+        assert_eq!(
+            response.get_ref().status,
+            get_slot_status_response::Status::AtRisk as i32
+        );
+        assert!(response.get_ref().revert_value.is_empty());
+
+        // A mined conflicting spend reverts the slot at the next finalized block
+        btc.set_double_spend(TXID1, DoubleSpendStatus::Conflicted);
+        let response = service.get_slot_status(get_status(1)).await?;
+        assert_eq!(
+            response.get_ref().status,
+            get_slot_status_response::Status::DoubleSpent as i32
+        );
+        assert_eq!(response.get_ref().revert_value, vec![4, 5, 6]);
+        let finalized = finalize(&service, 1001, 101).await?;
+        assert_eq!(finalized.ended, vec![response.into_inner()]);
+
+        // The recorded outcome is returned for the same block even after the conflict is gone
+        btc.set_double_spend(TXID1, DoubleSpendStatus::None);
+        let response = service.get_slot_status(get_status(1)).await?;
+        assert_eq!(
+            response.get_ref().status,
+            get_slot_status_response::Status::DoubleSpent as i32
+        );
+
+        // The batch path reports the same statuses
+        btc.set_double_spend(TXID2, DoubleSpendStatus::Conflicted);
+        let response = service
+            .batch_get_slot_status(Request::new(BatchGetSlotStatusRequest {
+                namespace: String::new(),
+                current_block: 1001,
+                btc_block: 101,
+                slots: vec![
+                    SlotIdentifier {
+                        contract_address: "0x123".to_string(),
+                        slot_index: vec![1],
+                    },
+                    SlotIdentifier {
+                        contract_address: "0x123".to_string(),
+                        slot_index: vec![2],
+                    },
+                ],
+                max_staleness_blocks: None,
+            }))
+            .await?;
+        for slot in &response.get_ref().slots {
+            assert_eq!(
+                slot.status,
+                get_slot_status_response::Status::DoubleSpent as i32
+            );
+            assert_eq!(slot.revert_value, vec![4, 5, 6]);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_slot_status_reports_lock_progress() -> Result<(), Box<dyn std::error::Error>> {
+        let db = crate::db::Database::new(rusqlite::Connection::open_in_memory()?)?;
+        let btc = MockBitcoinService::new();
+        let service = SlotLockServiceImpl::new(db, btc.clone(), 6);
+
+        service
+            .lock_slot(Request::new(LockSlotRequest {
+                namespace: String::new(),
+                locked_at_block: 1000,
+                btc_block: 100,
+                contract_address: "0x123".to_string(),
+                slot_index: vec![1],
+                revert_value: vec![4],
+                current_value: vec![7],
+                btc_txid: TXID1.to_string(),
+                raw_tx_hex: String::new(),
+                metadata: Vec::new(),
+                labels: Default::default(),
+                alt_btc_txids: Vec::new(),
+                required_confirmed_txids: 0,
+            }))
+            .await?;
+        let status_request = || GetSlotStatusRequest {
+            namespace: String::new(),
+            current_block: 1001,
+            btc_block: 102,
+            contract_address: "0x123".to_string(),
+            slot_index: vec![1],
+            max_staleness_blocks: None,
+        };
+
+        let response = service
+            .get_slot_status(Request::new(status_request()))
+            .await?
+            .into_inner();
+        assert_eq!(
+            response.status,
+            get_slot_status_response::Status::Locked as i32
+        );
+        assert_eq!(response.btc_txid, TXID1);
+        assert_eq!(response.btc_block, 100);
+        assert_eq!(response.start_block, 1000);
+        assert_eq!(response.confirmations, 0);
+        assert_eq!(response.blocks_until_revert, 5);
+
+        // The batch variant reports the same progress
+        let batch_response = service
+            .batch_get_slot_status(Request::new(BatchGetSlotStatusRequest {
+                namespace: String::new(),
+                current_block: 1001,
+                btc_block: 102,
+                slots: vec![SlotIdentifier {
+                    contract_address: "0x123".to_string(),
+                    slot_index: vec![1],
+                }],
+                max_staleness_blocks: None,
+            }))
+            .await?
+            .into_inner();
+        assert_eq!(batch_response.slots, vec![response]);
+
+        btc.add_confirmed_tx(TXID1);
+        let response = service
+            .get_slot_status(Request::new(status_request()))
+            .await?
+            .into_inner();
+        assert_eq!(
+            response.status,
+            get_slot_status_response::Status::Unlocked as i32
+        );
+        assert_eq!(response.btc_txid, TXID1);
+        assert_eq!(response.confirmations, MOCK_CONFIRMATION_THRESHOLD);
+        assert_eq!(response.blocks_until_revert, 0);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_slot_status_reports_confirmation_stage() -> Result<(), Box<dyn std::error::Error>>
+    {
+        use futures::StreamExt;
+
+        let db = crate::db::Database::new(rusqlite::Connection::open_in_memory()?)?;
+        let btc = MockBitcoinService::new();
+        let service = SlotLockServiceImpl::new(db, btc.clone(), 6);
+        for (slot_index, btc_txid) in [(1, TXID1), (2, TXID2)] {
+            service
+                .lock_slot(Request::new(LockSlotRequest {
+                    locked_at_block: 1000,
+                    btc_block: 100,
+                    contract_address: "0x123".to_string(),
+                    slot_index: vec![slot_index],
+                    btc_txid: btc_txid.to_string(),
+                    ..Default::default()
+                }))
+                .await?;
+        }
+        let mut events = service
+            .subscribe_slot_events(Request::new(SubscribeSlotEventsRequest {
+                namespace: Some(String::new()),
+                contract_address: String::new(),
+            }))
+            .await?
+            .into_inner();
+        // Checks that both status RPCs report the slot at `stage`, returning the single response
+        let status = |slot_index: u8, stage: ConfirmationStage| {
+            let service = &service;
+            async move {
+                let response = service
+                    .get_slot_status(Request::new(GetSlotStatusRequest {
+                        namespace: String::new(),
+                        current_block: 1001,
+                        btc_block: 101,
+                        contract_address: "0x123".to_string(),
+                        slot_index: vec![slot_index],
+                        max_staleness_blocks: None,
+                    }))
+                    .await
+                    .unwrap()
+                    .into_inner();
+                assert_eq!(response.confirmation_stage, stage as i32);
+                let batch_response = service
+                    .batch_get_slot_status(Request::new(BatchGetSlotStatusRequest {
+                        namespace: String::new(),
+                        current_block: 1001,
+                        btc_block: 101,
+                        slots: vec![SlotIdentifier {
+                            contract_address: "0x123".to_string(),
+                            slot_index: vec![slot_index],
+                        }],
+                        max_staleness_blocks: None,
+                    }))
+                    .await
+                    .unwrap()
+                    .into_inner();
+                assert_eq!(batch_response.slots[0].confirmation_stage, stage as i32);
+                response
+            }
+        };
+
+        let response = status(1, ConfirmationStage::TxUnseen).await;
+        assert_eq!(
+            response.status,
+            get_slot_status_response::Status::Locked as i32
+        );
+        assert_eq!(response.required_confirmations, 6);
+
+        btc.set_mempool_status(TXID1, MempoolStatus::InMempool);
+        status(1, ConfirmationStage::TxInMempool).await;
+
+        btc.set_confirmations(TXID1, 3);
+        let response = status(1, ConfirmationStage::Confirming).await;
+        assert_eq!(response.confirmations, 3);
+
+        // An evicted transaction puts its lock at risk, with a single warning event
+        // once the block is finalized
+        btc.set_mempool_status(TXID2, MempoolStatus::Evicted);
+        let response = status(2, ConfirmationStage::TxEvicted).await;
+        assert_eq!(
+            response.status,
+            get_slot_status_response::Status::AtRisk as i32
+        );
+        finalize(&service, 1001, 101).await?;
+        let event = events.next().await.unwrap()?;
+        assert_eq!(event.kind, slot_event::Kind::Evicted as i32);
+        assert_eq!(event.slot_index[31], 2);
+
+        // Unlocked slots have no stage
+        btc.add_confirmed_tx(TXID1);
+        let response = status(1, ConfirmationStage::Unspecified).await;
+        assert_eq!(
+            response.status,
+            get_slot_status_response::Status::Unlocked as i32
+        );
+        assert_eq!(response.required_confirmations, 0);
+        finalize(&service, 1001, 101).await?;
+        let event = events.next().await.unwrap()?;
+        assert_eq!(event.kind, slot_event::Kind::Unlocked as i32);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_only_rejects_writes() -> Result<(), Box<dyn std::error::Error>> {
+        let db = crate::db::Database::new(rusqlite::Connection::open_in_memory()?)?;
+        let service =
+            SlotLockServiceImpl::new(db, MockBitcoinService::new(), 6).with_read_only(true);
+
+        let status = service
+            .lock_slot(Request::new(LockSlotRequest {
+                namespace: String::new(),
+                locked_at_block: 1000,
+                btc_block: 100,
+                contract_address: "0x123".to_string(),
+                slot_index: vec![1],
+                revert_value: vec![],
+                current_value: vec![],
+                btc_txid: TXID1.to_string(),
+                raw_tx_hex: String::new(),
+                metadata: Vec::new(),
+                labels: Default::default(),
+                alt_btc_txids: Vec::new(),
+                required_confirmed_txids: 0,
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+
+        // Status queries are still served
+        let response = service
+            .get_slot_status(Request::new(GetSlotStatusRequest {
+                namespace: String::new(),
+                current_block: 1000,
+                btc_block: 100,
+                contract_address: "0x123".to_string(),
+                slot_index: vec![1],
+                max_staleness_blocks: None,
+            }))
+            .await?;
+        assert_eq!(
+            response.get_ref().status,
+            get_slot_status_response::Status::Unlocked as i32
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_shadow_mode_leaves_locks() -> Result<(), Box<dyn std::error::Error>> {
+        let db = crate::db::Database::new(rusqlite::Connection::open_in_memory()?)?;
+        let btc = MockBitcoinService::new();
+        let service = SlotLockServiceImpl::new(db.clone(), btc.clone(), 6);
+        let shadow = SlotLockServiceImpl::new(db.clone(), btc.clone(), 6).with_shadow(true);
+
+        // The second lock is past the revert threshold by btc_block 105
+        for (idx, txid, btc_block) in [(1, TXID1, 100), (2, TXID2, 90)] {
+            service
+                .lock_slot(Request::new(LockSlotRequest {
+                    namespace: String::new(),
+                    locked_at_block: 1000,
+                    btc_block,
+                    contract_address: "0x123".to_string(),
+                    slot_index: slot_index(idx),
+                    revert_value: vec![4],
+                    current_value: vec![7],
+                    btc_txid: txid.to_string(),
+                    raw_tx_hex: String::new(),
+                    metadata: Vec::new(),
+                    labels: Default::default(),
+                    alt_btc_txids: Vec::new(),
+                    required_confirmed_txids: 0,
+                }))
+                .await?;
+        }
+        btc.add_confirmed_tx(TXID1);
+        let status_request = |idx, btc_block| GetSlotStatusRequest {
+            namespace: String::new(),
+            current_block: 1001,
+            btc_block,
+            contract_address: "0x123".to_string(),
+            slot_index: slot_index(idx),
+            max_staleness_blocks: None,
+        };
+
+        // Shadow queries report what they would do, every time, without applying it
+        for _ in 0..2 {
+            let response = shadow
+                .get_slot_status(Request::new(status_request(1, 101)))
+                .await?;
+            assert_eq!(
+                response.get_ref().status,
+                get_slot_status_response::Status::Unlocked as i32
+            );
+            let response = shadow
+                .batch_get_slot_status(Request::new(BatchGetSlotStatusRequest {
+                    namespace: String::new(),
+                    current_block: 1001,
+                    btc_block: 105,
+                    slots: [1, 2]
+                        .map(|idx| SlotIdentifier {
+                            contract_address: "0x123".to_string(),
+                            slot_index: slot_index(idx),
+                        })
+                        .to_vec(),
+                    max_staleness_blocks: None,
+                }))
+                .await?;
+            let statuses: Vec<_> = response.get_ref().slots.iter().map(|s| s.status).collect();
+            assert_eq!(
+                statuses,
+                vec![
+                    get_slot_status_response::Status::Unlocked as i32,
+                    get_slot_status_response::Status::Reverted as i32
+                ]
+            );
+            let finalized = finalize(&shadow, 1001, 105).await?;
+            let ended: Vec<_> = finalized.ended.iter().map(|s| s.status).collect();
+            assert_eq!(ended, statuses);
+            assert_eq!(db.active_locks()?.len(), 2);
+        }
+
+        // Writes are rejected as on a read-only sentinel
+        let status = shadow
+            .unlock_by_txid(Request::new(UnlockByTxidRequest {
+                namespace: String::new(),
+                btc_txid: TXID2.to_string(),
+                current_block: 1001,
+                revert: false,
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+
+        // The live service applies the same decisions when the block is finalized
+        let finalized = finalize(&service, 1001, 105).await?;
+        assert_eq!(finalized.ended.len(), 2);
+        assert_eq!(finalized.active_locks, 0);
+        assert!(db.active_locks()?.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_finalize_block() -> Result<(), Box<dyn std::error::Error>> {
+        let db = crate::db::Database::new(rusqlite::Connection::open_in_memory()?)?;
+        let btc = MockBitcoinService::new();
+        let service = SlotLockServiceImpl::new(db.clone(), btc.clone(), 6);
+
+        for (idx, btc_txid, locked_at_block) in [(1, TXID1, 1000), (2, TXID2, 1005)] {
+            service
+                .lock_slot(Request::new(LockSlotRequest {
+                    namespace: String::new(),
+                    locked_at_block,
+                    btc_block: 100,
+                    contract_address: "0x123".to_string(),
+                    slot_index: slot_index(idx),
+                    revert_value: vec![4],
+                    current_value: vec![7],
+                    btc_txid: btc_txid.to_string(),
+                    raw_tx_hex: String::new(),
+                    metadata: Vec::new(),
+                    labels: Default::default(),
+                    alt_btc_txids: Vec::new(),
+                    required_confirmed_txids: 0,
+                }))
+                .await?;
+        }
+        btc.add_confirmed_tx(TXID1);
+        btc.add_confirmed_tx(TXID2);
+
+        // Status queries report the settled lock without ending it
+        let response = service
+            .get_slot_status(Request::new(GetSlotStatusRequest {
+                namespace: String::new(),
+                current_block: 1001,
+                btc_block: 101,
+                contract_address: "0x123".to_string(),
+                slot_index: slot_index(1),
+                max_staleness_blocks: None,
+            }))
+            .await?
+            .into_inner();
+        assert_eq!(
+            response.status,
+            get_slot_status_response::Status::Unlocked as i32
+        );
+        assert_eq!(db.active_locks()?.len(), 2);
+
+        // Finalizing the block ends it, leaving the lock taken after the block
+        let finalized = finalize(&service, 1001, 101).await?;
+        assert_eq!(finalized.ended, vec![response]);
+        assert_eq!(finalized.active_locks, 1);
+
+        // Finalizing it again is a no-op
+        let finalized = finalize(&service, 1001, 101).await?;
+        assert!(finalized.ended.is_empty());
+        assert_eq!(finalized.active_locks, 1);
+
+        let status = service
+            .finalize_block(Request::new(FinalizeBlockRequest {
+                namespace: "test net".to_string(),
+                current_block: 1001,
+                btc_block: 101,
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_peek_slot_status() -> Result<(), Box<dyn std::error::Error>> {
+        use futures::StreamExt;
+
+        let db = crate::db::Database::new(rusqlite::Connection::open_in_memory()?)?;
+        let btc = MockBitcoinService::new();
+        let service = SlotLockServiceImpl::new(db.clone(), btc.clone(), 6)
+            .with_attestation_key(AttestationKey::from_hex(&"01".repeat(32))?)
+            .with_implicit_unlocks(true);
+        let mut events = service
+            .subscribe_slot_events(Request::new(SubscribeSlotEventsRequest::default()))
+            .await?
+            .into_inner();
+
+        service
+            .lock_slot(Request::new(LockSlotRequest {
+                namespace: String::new(),
+                locked_at_block: 1000,
+                btc_block: 100,
+                contract_address: "0x123".to_string(),
+                slot_index: slot_index(1),
+                revert_value: vec![4],
+                current_value: vec![7],
+                btc_txid: TXID1.to_string(),
+                raw_tx_hex: String::new(),
+                metadata: Vec::new(),
+                labels: Default::default(),
+                alt_btc_txids: Vec::new(),
+                required_confirmed_txids: 0,
+            }))
+            .await?;
+        assert_eq!(
+            events.next().await.unwrap()?.kind,
+            slot_event::Kind::Locked as i32
+        );
+        btc.add_confirmed_tx(TXID1);
+        let request = GetSlotStatusRequest {
+            namespace: String::new(),
+            current_block: 1001,
+            btc_block: 101,
+            contract_address: "0x123".to_string(),
+            slot_index: slot_index(1),
+            max_staleness_blocks: None,
+        };
+
+        // Peeks report the settled lock without ending it or signing
+        let peeked = service
+            .peek_slot_status(Request::new(request.clone()))
+            .await?
+            .into_inner();
+        assert_eq!(
+            peeked.status,
+            get_slot_status_response::Status::Unlocked as i32
+        );
+        assert!(peeked.signature.is_empty());
+        let batch = service
+            .batch_peek_slot_status(Request::new(BatchGetSlotStatusRequest {
+                namespace: String::new(),
+                current_block: 1001,
+                btc_block: 101,
+                slots: vec![SlotIdentifier {
+                    contract_address: "0x123".to_string(),
+                    slot_index: slot_index(1),
+                }],
+                max_staleness_blocks: None,
+            }))
+            .await?
+            .into_inner();
+        assert_eq!(batch.slots, vec![peeked.clone()]);
+        assert_eq!(db.active_locks()?.len(), 1);
+
+        // A regular query with implicit unlocks ends it, with the same status
+        let response = service
+            .get_slot_status(Request::new(request.clone()))
+            .await?
+            .into_inner();
+        assert_eq!(
+            GetSlotStatusResponse {
+                signature: Vec::new(),
+                ..response
+            },
+            peeked
+        );
+        assert!(db.active_locks()?.is_empty());
+        assert_eq!(
+            events.next().await.unwrap()?.kind,
+            slot_event::Kind::Unlocked as i32
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_standby_reads() -> Result<(), Box<dyn std::error::Error>> {
+        use sova_sentinel_proto::error_info::{error_info, STALE_REPLICA, STANDBY};
+
+        let db = crate::db::Database::new(rusqlite::Connection::open_in_memory()?)?;
+        let replication = Replication::load(db.clone(), Some("http://primary".to_string()))?;
+        let service = SlotLockServiceImpl::new(db, MockBitcoinService::new(), 6)
+            .with_replication(replication.clone());
+        let request = |max_staleness_blocks| GetSlotStatusRequest {
+            namespace: String::new(),
+            current_block: 1005,
+            btc_block: 101,
+            contract_address: "0x123".to_string(),
+            slot_index: slot_index(1),
+            max_staleness_blocks,
+        };
+        let reason = |status: Status| error_info(&status).unwrap().reason;
+
+        // Without a bound, or before it has caught up with its primary, a standby doesn't answer
+        let status = service
+            .peek_slot_status(Request::new(request(None)))
+            .await
+            .unwrap_err();
+        assert_eq!(reason(status), STANDBY);
+        let status = service
+            .peek_slot_status(Request::new(request(Some(10))))
+            .await
+            .unwrap_err();
+        assert_eq!(reason(status), STALE_REPLICA);
+
+        replication.caught_up(1000);
+        let response = service
+            .peek_slot_status(Request::new(request(Some(5))))
+            .await?
+            .into_inner();
+        assert_eq!(
+            response.status,
+            get_slot_status_response::Status::Unlocked as i32
+        );
+        let status = service
+            .batch_peek_slot_status(Request::new(BatchGetSlotStatusRequest {
+                namespace: String::new(),
+                current_block: 1005,
+                btc_block: 101,
+                slots: Vec::new(),
+                max_staleness_blocks: Some(4),
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(reason(status), STALE_REPLICA);
+
+        // A query that may end locks is left to the primary, whatever its bound
+        let service = service.with_implicit_unlocks(true);
+        let status = service
+            .get_slot_status(Request::new(request(Some(5))))
+            .await
+            .unwrap_err();
+        assert_eq!(reason(status), STANDBY);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reverts_are_queued_for_delivery() -> Result<(), Box<dyn std::error::Error>> {
+        let db = crate::db::Database::new(rusqlite::Connection::open_in_memory()?)?;
+        let notify = Arc::new(Notify::new());
+        let service = SlotLockServiceImpl::new(db.clone(), MockBitcoinService::new(), 6)
+            .with_revert_delivery(notify.clone());
+
+        service
+            .lock_slot(Request::new(LockSlotRequest {
+                namespace: String::new(),
+                locked_at_block: 1000,
+                btc_block: 100,
+                contract_address: "0x123".to_string(),
+                slot_index: vec![1],
+                revert_value: vec![4],
+                current_value: vec![7],
+                btc_txid: TXID1.to_string(),
+                raw_tx_hex: String::new(),
+                metadata: Vec::new(),
+                labels: Default::default(),
+                alt_btc_txids: Vec::new(),
+                required_confirmed_txids: 0,
+            }))
+            .await?;
+        let response = service
+            .batch_get_slot_status(Request::new(BatchGetSlotStatusRequest {
+                namespace: String::new(),
+                current_block: 1001,
+                btc_block: 107,
+                slots: vec![SlotIdentifier {
+                    contract_address: "0x123".to_string(),
+                    slot_index: vec![1],
+                }],
+                max_staleness_blocks: None,
+            }))
+            .await?;
+        assert_eq!(
+            response.get_ref().slots[0].status,
+            get_slot_status_response::Status::Reverted as i32
+        );
+        assert!(db.pending_revert_deliveries(10)?.is_empty());
+
+        finalize(&service, 1001, 107).await?;
+        let deliveries = db.pending_revert_deliveries(10)?;
+        assert_eq!(deliveries.len(), 1);
+        assert_eq!(deliveries[0].revert_value, vec![4]);
+        assert_eq!(deliveries[0].reason, UnlockReason::RevertThreshold.as_str());
+        assert_eq!(deliveries[0].reverted_at_block, 1001);
+        // The dispatcher was woken up
+        tokio::time::timeout(std::time::Duration::from_secs(1), notify.notified()).await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_status_reports_recorded_unlock_reason() -> Result<(), Box<dyn std::error::Error>>
+    {
+        let db = crate::db::Database::new(rusqlite::Connection::open_in_memory()?)?;
+        let btc = MockBitcoinService::new();
+        let service = SlotLockServiceImpl::new(db, btc.clone(), 6);
+
+        for (idx, btc_txid) in [(1u8, TXID1), (2, TXID2)] {
+            service
+                .lock_slot(Request::new(LockSlotRequest {
+                    namespace: String::new(),
+                    locked_at_block: 1000,
+                    btc_block: 100,
+                    contract_address: "0x123".to_string(),
+                    slot_index: vec![idx],
+                    revert_value: vec![4],
+                    current_value: vec![7],
+                    btc_txid: btc_txid.to_string(),
+                    raw_tx_hex: String::new(),
+                    metadata: Vec::new(),
+                    labels: Default::default(),
+                    alt_btc_txids: Vec::new(),
+                    required_confirmed_txids: 0,
+                }))
+                .await?;
+        }
+        service
+            .batch_unlock_slot(Request::new(BatchUnlockSlotRequest {
+                namespace: String::new(),
+                current_block: 1001,
+                btc_block: 101,
+                slots: vec![SlotIdentifier {
+                    contract_address: "0x123".to_string(),
+                    slot_index: vec![1],
+                }],
+                expected_current_values: Vec::new(),
+            }))
+            .await?;
+        btc.add_confirmed_tx(TXID2);
+        let status_request = |current_block: u64, idx: u8, btc_block: u64| GetSlotStatusRequest {
+            namespace: String::new(),
+            current_block,
+            btc_block,
+            contract_address: "0x123".to_string(),
+            slot_index: vec![idx],
+            max_staleness_blocks: None,
+        };
+        let finalized = finalize(&service, 1002, 102).await?;
+        assert_eq!(
+            finalized.ended[0].unlock_reason,
+            get_slot_status_response::UnlockReason::Confirmed as i32
+        );
+
+        // Asked again at their unlock block with a Bitcoin block past the revert threshold, both
+        // locks still report how they were unlocked instead of being reported as reverted
+        for (current_block, idx, reason) in [
+            (1001, 1, get_slot_status_response::UnlockReason::Manual),
+            (1002, 2, get_slot_status_response::UnlockReason::Confirmed),
+        ] {
+            let response = service
+                .get_slot_status(Request::new(status_request(current_block, idx, 200)))
+                .await?
+                .into_inner();
+            assert_eq!(
+                response.status,
+                get_slot_status_response::Status::Unlocked as i32
+            );
+            assert_eq!(response.unlock_reason, reason as i32);
+            assert!(response.revert_value.is_empty());
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_legacy_unlock_status_is_deterministic() -> Result<(), Box<dyn std::error::Error>>
+    {
+        let db = crate::db::Database::new(rusqlite::Connection::open_in_memory()?)?;
+        // The reason is backfilled by status queries that apply their outcome
+        let service = SlotLockServiceImpl::new(db.clone(), MockBitcoinService::new(), 6)
+            .with_implicit_unlocks(true);
+
+        service
+            .lock_slot(Request::new(LockSlotRequest {
+                namespace: String::new(),
+                locked_at_block: 1000,
+                btc_block: 100,
+                contract_address: "0x123".to_string(),
+                slot_index: vec![1],
+                revert_value: vec![4],
+                current_value: vec![7],
+                btc_txid: TXID1.to_string(),
+                raw_tx_hex: String::new(),
+                metadata: Vec::new(),
+                labels: Default::default(),
+                alt_btc_txids: Vec::new(),
+                required_confirmed_txids: 0,
+            }))
+            .await?;
+        // An unlock from before reasons were recorded
+        db.with_transaction(|tx| {
+            tx.execute("UPDATE slot_locks SET end_block = 1001", [])?;
+            Ok(())
+        })?;
+
+        let status_request = |btc_block: u64| GetSlotStatusRequest {
+            namespace: String::new(),
+            current_block: 1001,
+            btc_block,
+            contract_address: "0x123".to_string(),
+            slot_index: vec![1],
+            max_staleness_blocks: None,
+        };
+        let first = service
+            .get_slot_status(Request::new(status_request(101)))
+            .await?
+            .into_inner();
+        assert_eq!(
+            first.status,
+            get_slot_status_response::Status::Unlocked as i32
+        );
+
+        // A replay with a Bitcoin block past the revert threshold gets the same answer
+        let replay = service
+            .batch_get_slot_status(Request::new(BatchGetSlotStatusRequest {
+                namespace: String::new(),
+                current_block: 1001,
+                btc_block: 200,
+                slots: vec![SlotIdentifier {
+                    contract_address: "0x123".to_string(),
+                    slot_index: vec![1],
+                }],
+                max_staleness_blocks: None,
+            }))
+            .await?
+            .into_inner();
+        assert_eq!(replay.slots, vec![first]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stale_btc_block_policies() -> Result<(), Box<dyn std::error::Error>> {
+        let btc = MockBitcoinService::new();
+        btc.add_confirmed_tx(TXID1);
+        let service_with_lock = |policy| {
+            let btc = btc.clone();
+            async move {
+                let db = crate::db::Database::new(rusqlite::Connection::open_in_memory()?)?;
+                let service =
+                    SlotLockServiceImpl::new(db, btc, 6).with_stale_btc_block_policy(policy);
+                service
+                    .lock_slot(Request::new(LockSlotRequest {
+                        namespace: String::new(),
+                        locked_at_block: 1000,
+                        btc_block: 100,
+                        contract_address: "0x123".to_string(),
+                        slot_index: vec![1],
+                        revert_value: vec![4],
+                        current_value: vec![7],
+                        btc_txid: TXID1.to_string(),
+                        raw_tx_hex: String::new(),
+                        metadata: Vec::new(),
+                        labels: Default::default(),
+                        alt_btc_txids: Vec::new(),
+                        required_confirmed_txids: 0,
+                    }))
+                    .await?;
+                Ok::<_, Box<dyn std::error::Error>>(service)
+            }
+        };
+        // The client's Bitcoin view is one block behind the lock
+        let status_request = |btc_block: u64| GetSlotStatusRequest {
+            namespace: String::new(),
+            current_block: 1001,
+            btc_block,
+            contract_address: "0x123".to_string(),
+            slot_index: vec![1],
+            max_staleness_blocks: None,
+        };
+        let batch_request = |btc_block: u64| BatchGetSlotStatusRequest {
+            namespace: String::new(),
+            current_block: 1001,
+            btc_block,
+            slots: vec![SlotIdentifier {
+                contract_address: "0x123".to_string(),
+                slot_index: vec![1],
+            }],
+            max_staleness_blocks: None,
+        };
+
+        let service = service_with_lock(StaleBtcBlockPolicy::Reject).await?;
+        let status = service
+            .get_slot_status(Request::new(status_request(99)))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+        let info = sova_sentinel_proto::error_info::error_info(&status).unwrap();
+        assert_eq!(
+            info.reason,
+            sova_sentinel_proto::error_info::STALE_BTC_BLOCK
+        );
+        assert_eq!(info.metadata["lock_btc_block"], "100");
+        let status = service
+            .batch_get_slot_status(Request::new(batch_request(99)))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+
+        // Reported as locked without checking the transaction, so the slot stays locked
+        let service = service_with_lock(StaleBtcBlockPolicy::Locked).await?;
+        let response = service
+            .get_slot_status(Request::new(status_request(99)))
+            .await?
+            .into_inner();
+        assert_eq!(
+            response.status,
+            get_slot_status_response::Status::Locked as i32
+        );
+        assert_eq!(response.blocks_until_revert, 7);
+        let response = service
+            .batch_get_slot_status(Request::new(batch_request(99)))
+            .await?
+            .into_inner();
+        assert_eq!(
+            response.slots[0].status,
+            get_slot_status_response::Status::Locked as i32
+        );
+        let response = service
+            .get_slot_status(Request::new(status_request(100)))
+            .await?
+            .into_inner();
+        assert_eq!(
+            response.status,
+            get_slot_status_response::Status::Unlocked as i32
+        );
+
+        // Clamped to no Bitcoin blocks passed, so the confirmed transaction unlocks the slot
+        let service = service_with_lock(StaleBtcBlockPolicy::Clamp).await?;
+        let response = service
+            .batch_get_slot_status(Request::new(batch_request(99)))
+            .await?
+            .into_inner();
+        assert_eq!(
+            response.slots[0].unlock_reason,
+            get_slot_status_response::UnlockReason::Confirmed as i32
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_contract_threshold_overrides() -> Result<(), Box<dyn std::error::Error>> {
+        let db = crate::db::Database::new(rusqlite::Connection::open_in_memory()?)?;
+        let btc = MockBitcoinService::new();
+        btc.set_confirmations(TXID1, 2);
+        let contract_thresholds = Arc::new(ContractThresholds::new(
+            [
+                (
+                    "0xAbC".to_string(),
+                    ThresholdOverride {
+                        confirmation_threshold: Some(2),
+                        revert_threshold: None,
+                    },
+                ),
+                (
+                    "0xdef".to_string(),
+                    ThresholdOverride {
+                        confirmation_threshold: None,
+                        revert_threshold: Some(3),
+                    },
+                ),
+            ]
+            .into(),
+        ));
+        let service = SlotLockServiceImpl::new(db, btc, 6)
+            .with_contract_thresholds(contract_thresholds.clone());
+
+        service
+            .batch_lock_slot(Request::new(BatchLockSlotRequest {
+                namespace: String::new(),
+                locked_at_block: 1000,
+                btc_block: 100,
+                slots: ["0xabc", "0xdef", "0x123"]
+                    .into_iter()
+                    .map(|contract_address| SlotData {
+                        contract_address: contract_address.to_string(),
+                        slot_index: vec![1],
+                        revert_value: vec![4],
+                        current_value: vec![7],
+                        btc_txid: TXID1.to_string(),
+                        metadata: Vec::new(),
+                        labels: Default::default(),
+                        alt_btc_txids: Vec::new(),
+                        required_confirmed_txids: 0,
+                    })
+                    .collect(),
+                contract_slots: Vec::new(),
+                atomic: false,
+            }))
+            .await?;
+
+        // Two confirmations settle locks of 0xabc only, and four Bitcoin blocks revert locks of
+        // 0xdef only
+        let response = service
+            .batch_get_slot_status(Request::new(BatchGetSlotStatusRequest {
+                namespace: String::new(),
+                current_block: 1001,
+                btc_block: 104,
+                slots: ["0xabc", "0xdef", "0x123"]
+                    .into_iter()
+                    .map(|contract_address| SlotIdentifier {
+                        contract_address: contract_address.to_string(),
+                        slot_index: vec![1],
+                    })
+                    .collect(),
+                max_staleness_blocks: None,
+            }))
+            .await?
+            .into_inner();
+        let statuses: Vec<_> = response
+            .slots
+            .iter()
+            .map(|slot| (slot.contract_address.as_str(), slot.status))
+            .collect();
+        assert!(statuses.contains(&("0xabc", get_slot_status_response::Status::Unlocked as i32)));
+        assert!(statuses.contains(&("0xdef", get_slot_status_response::Status::Reverted as i32)));
+        assert!(statuses.contains(&("0x123", get_slot_status_response::Status::Locked as i32)));
+
+        // Overrides adjusted at runtime apply to the next query
+        contract_thresholds.set(
+            "0x123",
+            ThresholdOverride {
+                confirmation_threshold: Some(1),
+                revert_threshold: None,
+            },
+        );
+        let response = service
+            .get_slot_status(Request::new(GetSlotStatusRequest {
+                namespace: String::new(),
+                contract_address: "0x123".to_string(),
+                current_block: 1002,
+                slot_index: vec![1],
+                btc_block: 104,
+                max_staleness_blocks: None,
+            }))
+            .await?
+            .into_inner();
+        assert_eq!(
+            response.status,
+            get_slot_status_response::Status::Unlocked as i32
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_namespaces_are_independent() -> Result<(), Box<dyn std::error::Error>> {
+        let db = crate::db::Database::new(rusqlite::Connection::open_in_memory()?)?;
+        let btc = MockBitcoinService::new();
+        btc.set_confirmations(TXID1, 1);
+        let service = SlotLockServiceImpl::new(db, btc, 6).with_namespace_thresholds(
+            [(
+                "testnet".to_string(),
+                ThresholdOverride {
+                    confirmation_threshold: Some(1),
+                    revert_threshold: Some(3),
+                },
+            )]
+            .into(),
+        );
+        let lock = |namespace: &str, btc_txid: &str| {
+            Request::new(LockSlotRequest {
+                namespace: namespace.to_string(),
+                locked_at_block: 1000,
+                btc_block: 100,
+                contract_address: "0x123".to_string(),
+                slot_index: vec![1],
+                revert_value: vec![4],
+                current_value: vec![7],
+                btc_txid: btc_txid.to_string(),
+                raw_tx_hex: String::new(),
+                metadata: Vec::new(),
+                labels: Default::default(),
+                alt_btc_txids: Vec::new(),
+                required_confirmed_txids: 0,
+            })
+        };
+
+        // The same slot is locked once per namespace
+        for namespace in ["", "testnet"] {
+            let response = service.lock_slot(lock(namespace, TXID1)).await?;
+            assert_eq!(
+                response.get_ref().status,
+                lock_slot_response::Status::Locked as i32
+            );
+        }
+        let response = service.lock_slot(lock("testnet", TXID2)).await?;
+        assert_eq!(
+            response.get_ref().status,
+            lock_slot_response::Status::AlreadyLocked as i32
+        );
+
+        // One confirmation settles the testnet lock only
+        let status = |namespace: &str| {
+            Request::new(GetSlotStatusRequest {
+                namespace: namespace.to_string(),
+                contract_address: "0x123".to_string(),
+                current_block: 1001,
+                slot_index: vec![1],
+                btc_block: 101,
+                max_staleness_blocks: None,
+            })
+        };
+        let response = service.get_slot_status(status("")).await?.into_inner();
+        assert_eq!(
+            response.status,
+            get_slot_status_response::Status::Locked as i32
+        );
+        let response = service
+            .get_slot_status(status("testnet"))
+            .await?
+            .into_inner();
+        assert_eq!(
+            response.status,
+            get_slot_status_response::Status::Unlocked as i32
+        );
+        let finalized = service
+            .finalize_block(Request::new(FinalizeBlockRequest {
+                namespace: "testnet".to_string(),
+                current_block: 1001,
+                btc_block: 101,
+            }))
+            .await?
+            .into_inner();
+        assert_eq!(finalized.ended, vec![response]);
+
+        let info = service
+            .get_sentinel_info(Request::new(GetSentinelInfoRequest {
+                namespace: "testnet".to_string(),
+            }))
+            .await?
+            .into_inner();
+        assert_eq!(info.confirmation_threshold, 1);
+        assert_eq!(info.revert_threshold, 3);
+        assert_eq!(info.active_locks, 0);
+
+        let status = service
+            .lock_slot(lock("test net", TXID1))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_slot_events() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::service::identity::CallerIdentity;
+        use futures::StreamExt;
+
+        let db = crate::db::Database::new(rusqlite::Connection::open_in_memory()?)?;
+        let btc = MockBitcoinService::new();
+        btc.set_confirmations(TXID1, MOCK_CONFIRMATION_THRESHOLD);
+        let service = SlotLockServiceImpl::new(db.clone(), btc, 6);
+        let mut events = service
+            .subscribe_slot_events(Request::new(SubscribeSlotEventsRequest {
+                namespace: Some(String::new()),
+                contract_address: String::new(),
+            }))
+            .await?
+            .into_inner();
+
+        let mut request = Request::new(BatchLockSlotRequest {
+            namespace: String::new(),
+            locked_at_block: 1000,
+            btc_block: 100,
+            slots: [(TXID1, 1), (TXID2, 2), (TXID2, 1)]
+                .into_iter()
+                .map(|(btc_txid, slot_index)| SlotData {
+                    contract_address: "0x123".to_string(),
+                    slot_index: vec![slot_index],
+                    btc_txid: btc_txid.to_string(),
+                    ..Default::default()
+                })
+                .collect(),
+            contract_slots: Vec::new(),
+            atomic: false,
+        });
+        request
+            .extensions_mut()
+            .insert(CallerIdentity("key:sequencer".to_string()));
+        service.batch_lock_slot(request).await?;
+        // The slot locked twice in the batch only emits one event
+        for slot_index in [1, 2] {
+            let event = events.next().await.unwrap()?;
+            assert_eq!(event.kind, slot_event::Kind::Locked as i32);
+            assert_eq!(event.sova_block, 1000);
+            assert_eq!(event.slot_index[31], slot_index);
+            assert_eq!(event.locked_by, "key:sequencer");
+        }
+        for row in db.lock_rows(Some(""), 0, 100)? {
+            assert_eq!(row.lock.locked_by.as_deref(), Some("key:sequencer"));
+        }
+
+        // A confirmed lock unlocks at a finalized block, and one past the revert threshold reverts
+        for btc_block in [101, 107] {
+            finalize(&service, 1001, btc_block).await?;
+        }
+        let event = events.next().await.unwrap()?;
+        assert_eq!(event.kind, slot_event::Kind::Unlocked as i32);
+        assert_eq!(
+            event.unlock_reason,
+            get_slot_status_response::UnlockReason::Confirmed as i32
+        );
+        let event = events.next().await.unwrap()?;
+        assert_eq!(event.kind, slot_event::Kind::Reverted as i32);
+        assert_eq!(event.sova_block, 1001);
+        assert_eq!(event.lock_start_block, 1000);
+        assert_eq!(event.locked_by, "key:sequencer");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_active_lock_quota() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::service::identity::CallerIdentity;
+
+        let db = crate::db::Database::new(rusqlite::Connection::open_in_memory()?)?;
+        let service = SlotLockServiceImpl::new(db, MockBitcoinService::new(), 6)
+            .with_active_lock_limits(IdentityConfig {
+                api_key_header: "x-api-key".to_string(),
+                api_keys: HashMap::new(),
+                client_cn_header: None,
+                trusted_proxies: Vec::new(),
+                max_active_locks: Some(2),
+                active_lock_limits: HashMap::from([("cn:sequencer".to_string(), 3)]),
+            });
+        fn identified<T>(message: T, caller: &str) -> Request<T> {
+            let mut request = Request::new(message);
+            request
+                .extensions_mut()
+                .insert(CallerIdentity(caller.to_string()));
+            request
+        }
+        let lock = |idx: u8| LockSlotRequest {
+            locked_at_block: 1000,
+            contract_address: "0x123".to_string(),
+            slot_index: vec![idx],
+            btc_txid: TXID1.to_string(),
+            btc_block: 100,
+            ..Default::default()
+        };
+        let batch = |slots: &[u8]| BatchLockSlotRequest {
+            locked_at_block: 1000,
+            btc_block: 100,
+            slots: slots
+                .iter()
+                .map(|&idx| SlotData {
+                    contract_address: "0x123".to_string(),
+                    slot_index: vec![idx],
+                    btc_txid: TXID1.to_string(),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        };
+        let is_quota = |status: Status| {
+            status.code() == tonic::Code::ResourceExhausted
+                && sova_sentinel_proto::error_info::error_info(&status)
+                    .unwrap()
+                    .reason
+                    == sova_sentinel_proto::error_info::ACTIVE_LOCK_QUOTA
+        };
+
+        // Reserved slots count against the quota
+        service
+            .lock_slot(identified(lock(1), "key:indexer"))
+            .await?;
+        let prepared = service
+            .prepare_lock(identified(
+                PrepareLockRequest {
+                    locked_at_block: 1000,
+                    btc_block: 100,
+                    slots: batch(&[2]).slots,
+                    ..Default::default()
+                },
+                "key:indexer",
+            ))
+            .await?;
+        assert_eq!(
+            prepared.get_ref().slots[0].status,
+            slot_lock_status::Status::Reserved as i32
+        );
+        let status = service
+            .lock_slot(identified(lock(3), "key:indexer"))
+            .await
+            .unwrap_err();
+        assert!(is_quota(status.clone()));
+        let info = sova_sentinel_proto::error_info::error_info(&status).unwrap();
+        assert_eq!(info.metadata["locked_by"], "key:indexer");
+        assert_eq!(info.metadata["active_locks"], "2");
+
+        // A batch over the quota locks none of its slots
+        let status = service
+            .batch_lock_slot(identified(batch(&[3, 4, 5, 6]), "cn:sequencer"))
+            .await
+            .unwrap_err();
+        assert!(is_quota(status));
+        let locked = service
+            .batch_lock_slot(identified(batch(&[3, 4, 5]), "cn:sequencer"))
+            .await?;
+        assert!(locked
+            .get_ref()
+            .slots
+            .iter()
+            .all(|slot| slot.status == slot_lock_status::Status::Locked as i32));
+
+        // Conflicting slots aren't counted
+        service
+            .batch_lock_slot(identified(batch(&[3, 4]), "key:backfill"))
+            .await?;
+
+        // Unidentified callers can't lock past limits set for everyone else
+        let status = service.lock_slot(Request::new(lock(6))).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::Unauthenticated);
+        assert_eq!(
+            sova_sentinel_proto::error_info::error_info(&status)
+                .unwrap()
+                .reason,
+            sova_sentinel_proto::error_info::UNIDENTIFIED_CALLER
+        );
+        let status = service
+            .batch_lock_slot(Request::new(batch(&[6, 7])))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::Unauthenticated);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_lock_slot_broadcasts_raw_tx() -> Result<(), Box<dyn std::error::Error>> {
+        let db = crate::db::Database::new(rusqlite::Connection::open_in_memory()?)?;
+        let btc = MockBitcoinService::new();
+        let service = SlotLockServiceImpl::new(db.clone(), btc.clone(), 6);
+
+        let tx = bitcoin::Transaction {
+            version: bitcoin::transaction::Version::TWO,
+            lock_time: bitcoin::absolute::LockTime::ZERO,
+            input: vec![bitcoin::TxIn::default()],
+            output: vec![bitcoin::TxOut {
+                value: bitcoin::Amount::from_sat(10_000),
+                script_pubkey: bitcoin::ScriptBuf::new(),
+            }],
+        };
+        let raw_tx = bitcoin::consensus::serialize(&tx);
+        let lock_request = |btc_txid: String| LockSlotRequest {
+            namespace: String::new(),
+            locked_at_block: 1000,
+            btc_block: 100,
+            contract_address: "0x123".to_string(),
+            slot_index: vec![1],
+            revert_value: vec![],
+            current_value: vec![],
+            btc_txid,
+            raw_tx_hex: hex::encode(&raw_tx),
+            metadata: Vec::new(),
+            labels: Default::default(),
+            alt_btc_txids: Vec::new(),
+            required_confirmed_txids: 0,
+        };
+
+        // A raw transaction that doesn't match btc_txid is rejected
+        let status = service
+            .lock_slot(Request::new(lock_request(TXID1.to_string())))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        assert!(btc.broadcasts().is_empty());
+
+        let btc_txid = tx.compute_txid().to_string();
+        let response = service
+            .lock_slot(Request::new(lock_request(btc_txid.clone())))
+            .await?;
+        assert_eq!(
+            response.get_ref().status,
+            lock_slot_response::Status::Locked as i32
+        );
+        assert_eq!(btc.broadcasts(), vec![raw_tx.clone()]);
+        assert_eq!(db.pending_lock_transactions()?, vec![(btc_txid, raw_tx)]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_replace_lock_tx() -> Result<(), Box<dyn std::error::Error>> {
+        let db = crate::db::Database::new(rusqlite::Connection::open_in_memory()?)?;
+        let btc = MockBitcoinService::new();
+        let service = SlotLockServiceImpl::new(db, btc.clone(), 6);
+
+        service
+            .lock_slot(Request::new(LockSlotRequest {
+                namespace: String::new(),
+                locked_at_block: 1000,
+                btc_block: 100,
+                contract_address: "0x123".to_string(),
+                slot_index: vec![1],
+                revert_value: vec![4, 5, 6],
+                current_value: vec![7, 8, 9],
+                btc_txid: TXID1.to_string(),
+                raw_tx_hex: String::new(),
+                metadata: Vec::new(),
+                labels: Default::default(),
+                alt_btc_txids: Vec::new(),
+                required_confirmed_txids: 0,
+            }))
+            .await?;
+
+        let response = service
+            .replace_lock_tx(Request::new(ReplaceLockTxRequest {
+                namespace: String::new(),
+                old_btc_txid: TXID1.to_string(),
+                new_btc_txid: TXID2.to_string(),
+            }))
+            .await?;
+        assert_eq!(response.get_ref().replaced_locks, 1);
+
+        // Nothing is waiting on the original anymore
+        let err = service
+            .replace_lock_tx(Request::new(ReplaceLockTxRequest {
+                namespace: String::new(),
+                old_btc_txid: TXID1.to_string(),
+                new_btc_txid: TXID3.to_string(),
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::NotFound);
+
+        // The lock now unlocks once the replacement confirms
+        btc.add_confirmed_tx(TXID2);
+        let response = service
+            .get_slot_status(Request::new(GetSlotStatusRequest {
+                namespace: String::new(),
+                current_block: 1001,
+                btc_block: 101,
+                contract_address: "0x123".to_string(),
+                slot_index: vec![1],
+                max_staleness_blocks: None,
+            }))
+            .await?;
+        assert_eq!(
+            response.get_ref().status,
+            get_slot_status_response::Status::Unlocked as i32
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_unlock_by_txid() -> Result<(), Box<dyn std::error::Error>> {
+        let db = crate::db::Database::new(rusqlite::Connection::open_in_memory()?)?;
+        let btc = MockBitcoinService::new();
+        let service = SlotLockServiceImpl::new(db, btc, 6);
+
+        for (slot_index, btc_txid) in [(1, TXID1), (2, TXID1), (3, TXID2)] {
+            service
+                .lock_slot(Request::new(LockSlotRequest {
+                    namespace: String::new(),
+                    locked_at_block: 1000,
+                    btc_block: 100,
+                    contract_address: "0x123".to_string(),
+                    slot_index: vec![slot_index],
+                    revert_value: vec![4, 5, 6],
+                    current_value: vec![7, 8, 9],
+                    btc_txid: btc_txid.to_string(),
+                    raw_tx_hex: String::new(),
+                    metadata: Vec::new(),
+                    labels: Default::default(),
+                    alt_btc_txids: Vec::new(),
+                    required_confirmed_txids: 0,
+                }))
+                .await?;
+        }
+
+        let response = service
+            .unlock_by_txid(Request::new(UnlockByTxidRequest {
+                namespace: String::new(),
+                btc_txid: TXID1.to_string(),
+                current_block: 1001,
+                revert: true,
+            }))
+            .await?;
+        assert_eq!(response.get_ref().slots.len(), 2);
+
+        // Every slot locked on the transaction was reverted, the others stay locked
+        let response = service
+            .batch_get_slot_status(Request::new(BatchGetSlotStatusRequest {
+                namespace: String::new(),
+                current_block: 1001,
+                btc_block: 101,
+                slots: [1, 2, 3]
+                    .into_iter()
+                    .map(|slot_index| SlotIdentifier {
+                        contract_address: "0x123".to_string(),
+                        slot_index: vec![slot_index],
+                    })
+                    .collect(),
+                max_staleness_blocks: None,
+            }))
+            .await?;
+        let statuses: Vec<_> = response
+            .get_ref()
+            .slots
+            .iter()
+            .map(|slot| (slot.status, slot.unlock_reason, slot.revert_value.clone()))
+            .collect();
+        let reverted = (
+            get_slot_status_response::Status::Reverted as i32,
+            get_slot_status_response::UnlockReason::ManualRevert as i32,
+            vec![4, 5, 6],
+        );
+        assert_eq!(
+            statuses,
+            vec![
+                reverted.clone(),
+                reverted,
+                (
+                    get_slot_status_response::Status::Locked as i32,
+                    get_slot_status_response::UnlockReason::Unspecified as i32,
+                    vec![]
+                ),
+            ]
+        );
+
+        // Nothing is waiting on the transaction anymore
+        let err = service
+            .unlock_by_txid(Request::new(UnlockByTxidRequest {
+                namespace: String::new(),
+                btc_txid: TXID1.to_string(),
+                current_block: 1002,
+                revert: false,
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::NotFound);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_atomic_lock_groups() -> Result<(), Box<dyn std::error::Error>> {
+        let db = crate::db::Database::new(rusqlite::Connection::open_in_memory()?)?;
+        let btc = MockBitcoinService::new();
+        let service = SlotLockServiceImpl::new(db, btc.clone(), 6);
+        let slot = |idx: u8, btc_txid: &str| SlotData {
+            contract_address: "0x123".to_string(),
+            slot_index: vec![idx],
+            revert_value: vec![4],
+            current_value: vec![7],
+            btc_txid: btc_txid.to_string(),
+            metadata: Vec::new(),
+            labels: Default::default(),
+            alt_btc_txids: Vec::new(),
+            required_confirmed_txids: 0,
+        };
+        let lock_atomic = |slots: Vec<SlotData>| {
+            service.batch_lock_slot(Request::new(BatchLockSlotRequest {
+                namespace: String::new(),
+                locked_at_block: 1000,
+                btc_block: 100,
+                slots,
+                contract_slots: Vec::new(),
+                atomic: true,
+            }))
+        };
+        let status = |idx: u8| {
+            service.get_slot_status(Request::new(GetSlotStatusRequest {
+                namespace: String::new(),
+                current_block: 1001,
+                btc_block: 101,
+                contract_address: "0x123".to_string(),
+                slot_index: vec![idx],
+                max_staleness_blocks: None,
+            }))
+        };
+
+        let response = lock_atomic(vec![slot(1, TXID1), slot(2, TXID2)])
+            .await?
+            .into_inner();
+        assert_ne!(response.lock_group, 0);
+        assert!(response
+            .slots
+            .iter()
+            .all(|slot| slot.status == slot_lock_status::Status::Locked as i32));
+
+        // An atomic batch with an already locked slot locks nothing
+        let response = lock_atomic(vec![slot(2, TXID3), slot(3, TXID3)])
+            .await?
+            .into_inner();
+        assert_eq!(response.lock_group, 0);
+        assert_eq!(
+            response
+                .slots
+                .iter()
+                .map(|slot| slot.status)
+                .collect::<Vec<_>>(),
+            vec![
+                slot_lock_status::Status::AlreadyLocked as i32,
+                slot_lock_status::Status::NotLocked as i32
+            ]
+        );
+        assert_eq!(status(3).await?.get_ref().btc_txid, "");
+
+        // A confirmed lock waits for the rest of its group
+        btc.add_confirmed_tx(TXID1);
+        let response = status(1).await?;
+        assert_eq!(
+            response.get_ref().status,
+            get_slot_status_response::Status::Locked as i32
+        );
+
+        // A double-spend of one lock reverts the whole group, including the confirmed lock
+        btc.set_double_spend(TXID2, DoubleSpendStatus::Conflicted);
+        let response = status(1).await?;
+        assert_eq!(
+            response.get_ref().status,
+            get_slot_status_response::Status::DoubleSpent as i32
+        );
+        assert_eq!(response.get_ref().revert_value, vec![4]);
+        assert_eq!(finalize(&service, 1001, 101).await?.ended.len(), 2);
+        btc.set_double_spend(TXID2, DoubleSpendStatus::None);
+        let response = status(2).await?;
+        assert_eq!(
+            response.get_ref().status,
+            get_slot_status_response::Status::DoubleSpent as i32
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_prepare_and_commit_lock() -> Result<(), Box<dyn std::error::Error>> {
+        let db = crate::db::Database::new(rusqlite::Connection::open_in_memory()?)?;
+        let service = SlotLockServiceImpl::new(db.clone(), MockBitcoinService::new(), 6);
+        let slot = |idx: u8| SlotData {
+            contract_address: "0x123".to_string(),
+            slot_index: vec![idx],
+            revert_value: vec![4],
+            current_value: vec![7],
+            btc_txid: TXID1.to_string(),
+            metadata: Vec::new(),
+            labels: Default::default(),
+            alt_btc_txids: Vec::new(),
+            required_confirmed_txids: 0,
+        };
+        async fn prepare(
+            service: &SlotLockServiceImpl<MockBitcoinService>,
+            slots: Vec<SlotData>,
+        ) -> Result<PrepareLockResponse, Status> {
+            let request = Request::new(PrepareLockRequest {
+                namespace: String::new(),
+                locked_at_block: 1000,
+                btc_block: 100,
+                slots,
+            });
+            Ok(service.prepare_lock(request).await?.into_inner())
+        }
+        let statuses = |slots: &[SlotLockStatus]| -> Vec<i32> {
+            slots.iter().map(|slot| slot.status).collect()
+        };
+
+        let prepared = prepare(&service, vec![slot(1), slot(2)]).await?;
+        assert_ne!(prepared.reservation_id, 0);
+        assert_eq!(
+            statuses(&prepared.slots),
+            vec![slot_lock_status::Status::Reserved as i32; 2]
+        );
+
+        // Reserved slots can't be locked or reserved by anyone else
+        let locked = service
+            .lock_slot(Request::new(LockSlotRequest {
+                locked_at_block: 1000,
+                contract_address: "0x123".to_string(),
+                slot_index: vec![1],
+                btc_txid: TXID2.to_string(),
+                btc_block: 100,
+                ..Default::default()
+            }))
+            .await?;
+        assert_eq!(
+            locked.get_ref().status,
+            lock_slot_response::Status::AlreadyLocked as i32
+        );
+        let other = prepare(&service, vec![slot(2), slot(3)]).await?;
+        assert_eq!(
+            statuses(&other.slots),
+            vec![
+                slot_lock_status::Status::AlreadyLocked as i32,
+                slot_lock_status::Status::Reserved as i32
+            ]
+        );
+
+        let committed = service
+            .commit_lock(Request::new(CommitLockRequest {
+                namespace: String::new(),
+                reservation_id: prepared.reservation_id,
+            }))
+            .await?
+            .into_inner();
+        assert_eq!(
+            statuses(&committed.slots),
+            vec![slot_lock_status::Status::Locked as i32; 2]
+        );
+        assert!(db.is_slot_locked("", "0x123", &slot_index(1))?);
+        assert!(!db.is_slot_locked("", "0x123", &slot_index(3))?);
+
+        // A reservation is committed once
+        let status = service
+            .commit_lock(Request::new(CommitLockRequest {
+                namespace: String::new(),
+                reservation_id: prepared.reservation_id,
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::NotFound);
+        assert_eq!(
+            sova_sentinel_proto::error_info::error_info(&status)
+                .unwrap()
+                .reason,
+            sova_sentinel_proto::error_info::RESERVATION_NOT_FOUND
+        );
+
+        // Expired reservations release their slots and can't be committed
+        let expiring = SlotLockServiceImpl::new(db.clone(), MockBitcoinService::new(), 6)
+            .with_reservation_timeout(Duration::ZERO);
+        let expired = prepare(&expiring, vec![slot(4)]).await?;
+        let reserved = prepare(&service, vec![slot(4)]).await?;
+        assert_eq!(
+            statuses(&reserved.slots),
+            vec![slot_lock_status::Status::Reserved as i32]
+        );
+        let status = service
+            .commit_lock(Request::new(CommitLockRequest {
+                namespace: String::new(),
+                reservation_id: expired.reservation_id,
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::NotFound);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_unlock_checks_expected_current_value() -> Result<(), Box<dyn std::error::Error>> {
+        let db = crate::db::Database::new(rusqlite::Connection::open_in_memory()?)?;
+        let service = SlotLockServiceImpl::new(db.clone(), MockBitcoinService::new(), 6);
+        for idx in [1, 2] {
+            service
+                .lock_slot(Request::new(LockSlotRequest {
+                    locked_at_block: 1000,
+                    contract_address: "0x123".to_string(),
+                    slot_index: vec![idx],
+                    current_value: vec![7],
+                    btc_txid: TXID1.to_string(),
+                    btc_block: 100,
+                    ..Default::default()
+                }))
+                .await?;
+        }
+        let slot = |idx: u8| SlotIdentifier {
+            contract_address: "0x123".to_string(),
+            slot_index: vec![idx],
+        };
+        let expected = |idx: u8, current_value: u8| ExpectedCurrentValue {
+            contract_address: "0x123".to_string(),
+            slot_index: vec![idx],
+            current_value: vec![current_value],
+        };
+        let unlock = |expected_current_values| {
+            service.batch_unlock_slot(Request::new(BatchUnlockSlotRequest {
+                namespace: String::new(),
+                current_block: 1001,
+                btc_block: 101,
+                slots: vec![slot(1), slot(2)],
+                expected_current_values,
+            }))
+        };
+
+        // A mismatch unlocks none of the slots
+        let status = unlock(vec![expected(1, 8)]).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+        let info = sova_sentinel_proto::error_info::error_info(&status).unwrap();
+        assert_eq!(
+            info.reason,
+            sova_sentinel_proto::error_info::CURRENT_VALUE_MISMATCH
+        );
+        assert_eq!(info.metadata["current_value"], "0x07");
+        assert_eq!(info.metadata["expected_current_value"], "0x08");
+        assert!(db.is_slot_locked("", "0x123", &slot_index(1))?);
+        assert!(db.is_slot_locked("", "0x123", &slot_index(2))?);
+
+        // Expected values must be for slots being unlocked
+        let status = unlock(vec![expected(3, 7)]).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+
+        unlock(vec![expected(1, 7)]).await?;
+        assert!(!db.is_slot_locked("", "0x123", &slot_index(1))?);
+        assert!(!db.is_slot_locked("", "0x123", &slot_index(2))?);
+
+        // A slot that is no longer locked holds no value
+        let status = unlock(vec![expected(1, 7)]).await.unwrap_err();
+        let info = sova_sentinel_proto::error_info::error_info(&status).unwrap();
+        assert!(!info.metadata.contains_key("current_value"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_alternative_txids_settle_lock() -> Result<(), Box<dyn std::error::Error>> {
+        let db = crate::db::Database::new(rusqlite::Connection::open_in_memory()?)?;
+        let btc = MockBitcoinService::new();
+        let service = SlotLockServiceImpl::new(db, btc.clone(), 6);
+        let lock = |slot_index: u8, btc_txid: &str, alt_btc_txids: &[&str]| {
+            service.lock_slot(Request::new(LockSlotRequest {
+                locked_at_block: 1000,
+                btc_block: 100,
+                contract_address: "0x123".to_string(),
+                slot_index: vec![slot_index],
+                btc_txid: btc_txid.to_string(),
+                alt_btc_txids: alt_btc_txids.iter().map(|txid| txid.to_string()).collect(),
+                ..Default::default()
+            }))
+        };
+        let get_status = |slot_index: u8| {
+            service.get_slot_status(Request::new(GetSlotStatusRequest {
+                namespace: String::new(),
+                current_block: 1001,
+                btc_block: 101,
+                contract_address: "0x123".to_string(),
+                slot_index: vec![slot_index],
+                max_staleness_blocks: None,
+            }))
+        };
+        let batch_status = || {
+            service.batch_get_slot_status(Request::new(BatchGetSlotStatusRequest {
+                namespace: String::new(),
+                current_block: 1001,
+                btc_block: 101,
+                slots: vec![SlotIdentifier {
+                    contract_address: "0x123".to_string(),
+                    slot_index: vec![2],
+                }],
+                max_staleness_blocks: None,
+            }))
+        };
+
+        // Alternatives must differ from the lock's transaction
+        let status = lock(1, TXID1, &[TXID1]).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+
+        lock(1, TXID1, &[TXID2]).await?;
+        lock(2, TXID3, &[TXID4]).await?;
+
+        // Either transaction confirming unlocks the slot
+        btc.add_confirmed_tx(TXID2);
+        let response = get_status(1).await?.into_inner();
+        assert_eq!(
+            response.status,
+            get_slot_status_response::Status::Unlocked as i32
+        );
+        assert_eq!(
+            response.unlock_reason,
+            get_slot_status_response::UnlockReason::Confirmed as i32
+        );
+
+        // A double-spent transaction leaves the lock at risk while an alternative can settle it
+        btc.set_double_spend(TXID3, DoubleSpendStatus::Conflicted);
+        let response = batch_status().await?.into_inner();
+        assert_eq!(
+            response.slots[0].status,
+            get_slot_status_response::Status::AtRisk as i32
+        );
+        btc.add_confirmed_tx(TXID4);
+        let response = batch_status().await?.into_inner();
+        assert_eq!(
+            response.slots[0].status,
+            get_slot_status_response::Status::Unlocked as i32
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_required_txids_settle_lock() -> Result<(), Box<dyn std::error::Error>> {
+        let db = crate::db::Database::new(rusqlite::Connection::open_in_memory()?)?;
+        let btc = MockBitcoinService::new();
+        let service = SlotLockServiceImpl::new(db, btc.clone(), 6);
+        let lock = |slot_index: u8, btc_txid: &str, alt_btc_txids: &[&str], required: u32| {
+            service.lock_slot(Request::new(LockSlotRequest {
+                locked_at_block: 1000,
+                btc_block: 100,
+                contract_address: "0x123".to_string(),
+                slot_index: vec![slot_index],
+                btc_txid: btc_txid.to_string(),
+                alt_btc_txids: alt_btc_txids.iter().map(|txid| txid.to_string()).collect(),
+                required_confirmed_txids: required,
+                ..Default::default()
+            }))
+        };
+        let get_status = |slot_index: u8| {
+            service.get_slot_status(Request::new(GetSlotStatusRequest {
+                namespace: String::new(),
+                current_block: 1001,
+                btc_block: 101,
+                contract_address: "0x123".to_string(),
+                slot_index: vec![slot_index],
+                max_staleness_blocks: None,
+            }))
+        };
+        let batch_status = |slot_index: u8| {
+            service.batch_get_slot_status(Request::new(BatchGetSlotStatusRequest {
+                namespace: String::new(),
+                current_block: 1001,
+                btc_block: 101,
+                slots: vec![SlotIdentifier {
+                    contract_address: "0x123".to_string(),
+                    slot_index: vec![slot_index],
+                }],
+                max_staleness_blocks: None,
+            }))
+        };
+
+        // A lock can't require more transactions than it lists
+        let status = lock(1, TXID1, &[TXID2], 3).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+
+        lock(1, TXID1, &[TXID2, TXID3], 2).await?;
+
+        // One confirmed transaction out of the two required keeps the slot locked
+        btc.add_confirmed_tx(TXID1);
+        let response = get_status(1).await?.into_inner();
+        assert_eq!(
+            response.status,
+            get_slot_status_response::Status::Locked as i32
+        );
+        assert_eq!(response.confirmations, 0);
+        btc.add_confirmed_tx(TXID3);
+        let response = batch_status(1).await?.into_inner();
+        assert_eq!(
+            response.slots[0].status,
+            get_slot_status_response::Status::Unlocked as i32
+        );
+
+        // A lock requiring every transaction can't settle once one is double-spent
+        lock(2, TXID4, &[TXID2], 2).await?;
+        btc.set_double_spend(TXID4, DoubleSpendStatus::Conflicted);
+        let response = batch_status(2).await?.into_inner();
+        assert_eq!(
+            response.slots[0].status,
+            get_slot_status_response::Status::DoubleSpent as i32
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_detected_replacement_keeps_lock() -> Result<(), Box<dyn std::error::Error>> {
+        use bitcoin::hashes::Hash;
+
+        let db = crate::db::Database::new(rusqlite::Connection::open_in_memory()?)?;
+        let btc = MockBitcoinService::new();
+        let service = SlotLockServiceImpl::new(db.clone(), btc.clone(), 6);
+
+        service
+            .lock_slot(Request::new(LockSlotRequest {
+                namespace: String::new(),
+                locked_at_block: 1000,
+                btc_block: 100,
+                contract_address: "0x123".to_string(),
+                slot_index: vec![1],
+                revert_value: vec![4, 5, 6],
+                current_value: vec![7, 8, 9],
+                btc_txid: TXID1.to_string(),
+                raw_tx_hex: String::new(),
+                metadata: Vec::new(),
+                labels: Default::default(),
+                alt_btc_txids: Vec::new(),
+                required_confirmed_txids: 0,
+            }))
+            .await?;
+
+        let replacement = bitcoin::Txid::from_byte_array([7; 32]);
+        btc.set_double_spend(TXID1, DoubleSpendStatus::Replaced(replacement));
+        let response = service
+            .get_slot_status(Request::new(GetSlotStatusRequest {
+                namespace: String::new(),
+                current_block: 1001,
+                btc_block: 101,
+                contract_address: "0x123".to_string(),
+                slot_index: vec![1],
+                max_staleness_blocks: None,
+            }))
+            .await?;
+        assert_eq!(
+            response.get_ref().status,
+            get_slot_status_response::Status::Locked as i32
+        );
+        // The replacement is recorded once the block is finalized
+        let finalized = finalize(&service, 1001, 101).await?;
+        assert!(finalized.ended.is_empty());
+        assert_eq!(finalized.active_locks, 1);
+        assert_eq!(
+            db.get_slot("", "0x123", &slot_index(1), 1001)?
+                .unwrap()
+                .btc_txid,
+            replacement.to_string()
+        );
+
+        Ok(())
+    }
+
+    /// Revert threshold of the lock state machine properties, low so reverts are common
+    const PROPERTY_REVERT_THRESHOLD: u64 = 3;
+    /// Slots and transactions the properties draw from, few so operations collide
+    const PROPERTY_SLOTS: u8 = 4;
+    const PROPERTY_TXIDS: [&str; 4] = [TXID1, TXID2, TXID3, TXID4];
+
+    /// Operation played in a Sova block after the block's locks
+    #[derive(Debug, Clone)]
+    enum BlockOp {
+        /// Queries the status of slots, in one batch call or one call each
+        Status {
+            slots: Vec<u8>,
+            batch: bool,
+        },
+        Unlock(Vec<u8>),
+        /// Confirms a transaction
+        Confirm(usize),
+    }
+
+    /// Sova block played by the properties: the Bitcoin blocks mined since the previous block,
+    /// the slots locked with the transaction of each, the other operations, then whether the
+    /// block is finalized. Locks come first, as a slot unlocked in a block can't be locked again
+    /// in the same block.
+    #[derive(Debug, Clone)]
+    struct PropertyBlock {
+        btc_blocks: u64,
+        locks: Vec<(u8, usize)>,
+        batch_lock: bool,
+        ops: Vec<BlockOp>,
+        finalize: bool,
+    }
+
+    fn property_slots() -> impl proptest::strategy::Strategy<Value = Vec<u8>> {
+        use proptest::prelude::*;
+
+        proptest::collection::btree_set(0..PROPERTY_SLOTS, 1..=PROPERTY_SLOTS as usize)
+            .prop_map(|slots| slots.into_iter().collect())
+    }
+
+    fn property_blocks() -> impl proptest::strategy::Strategy<Value = Vec<PropertyBlock>> {
+        use proptest::prelude::*;
+
+        let op = prop_oneof![
+            (property_slots(), any::<bool>())
+                .prop_map(|(slots, batch)| BlockOp::Status { slots, batch }),
+            property_slots().prop_map(BlockOp::Unlock),
+            (0..PROPERTY_TXIDS.len()).prop_map(BlockOp::Confirm),
+        ];
+        let block = (
+            0..=2u64,
+            proptest::collection::vec((0..PROPERTY_SLOTS, 0..PROPERTY_TXIDS.len()), 0..4),
+            any::<bool>(),
+            proptest::collection::vec(op, 0..5),
+            any::<bool>(),
+        )
+            .prop_map(
+                |(btc_blocks, locks, batch_lock, ops, finalize)| PropertyBlock {
+                    btc_blocks,
+                    locks,
+                    batch_lock,
+                    ops,
+                    finalize,
+                },
+            );
+        proptest::collection::vec(block, 1..12)
+    }
+
+    /// What the properties expect of a slot
+    #[derive(Debug, Default)]
+    struct ModelSlot {
+        /// Bitcoin block and transaction of the active lock
+        lock: Option<(u64, usize)>,
+        /// Sova block and reported status of the last unlock
+        unlocked: Option<(u64, i32)>,
+    }
+
+    impl ModelSlot {
+        /// Returns the status of the active lock at `btc_block`: reverted iff it is past the
+        /// revert threshold, whether or not its transaction confirmed
+        fn decide(&self, btc_block: u64, confirmed: &HashSet<usize>) -> Option<i32> {
+            let (lock_btc_block, tx) = self.lock?;
+            let status = if btc_block - lock_btc_block > PROPERTY_REVERT_THRESHOLD {
+                get_slot_status_response::Status::Reverted
+            } else if confirmed.contains(&tx) {
+                get_slot_status_response::Status::Unlocked
+            } else {
+                get_slot_status_response::Status::Locked
+            };
+            Some(status as i32)
+        }
+
+        /// Ends the active lock in `current_block` if `status` settles it
+        fn settle(&mut self, current_block: u64, status: i32) {
+            if status != get_slot_status_response::Status::Locked as i32 {
+                self.lock = None;
+                self.unlocked = Some((current_block, status));
+            }
+        }
+    }
+
+    fn property_failure(e: impl std::fmt::Display) -> proptest::test_runner::TestCaseError {
+        proptest::test_runner::TestCaseError::fail(e.to_string())
+    }
+
+    /// Plays `blocks` against the service, checking after every call that a slot never has two
+    /// active locks, that an ended lock is never reported locked, and that a lock is reported
+    /// reverted iff its Bitcoin block delta exceeds the revert threshold
+    async fn play_property_blocks<S: SlotStore + Clone>(
+        db: S,
+        blocks: Vec<PropertyBlock>,
+        implicit_unlocks: bool,
+    ) -> Result<(), proptest::test_runner::TestCaseError> {
+        use proptest::{prop_assert, prop_assert_eq};
+
+        let btc = MockBitcoinService::new();
+        let service =
+            SlotLockServiceImpl::new(db.clone(), btc.clone(), PROPERTY_REVERT_THRESHOLD as u32)
+                .with_implicit_unlocks(implicit_unlocks);
+        let mut model: Vec<ModelSlot> = (0..PROPERTY_SLOTS).map(|_| ModelSlot::default()).collect();
+        let mut confirmed = HashSet::new();
+        let mut btc_block = 100;
+        let identifier = |slot: u8| SlotIdentifier {
+            contract_address: "0x123".to_string(),
+            slot_index: slot_index(slot),
+        };
+
+        for (played, block) in blocks.into_iter().enumerate() {
+            let current_block = 1000 + played as u64;
+            btc_block += block.btc_blocks;
+
+            // Locks are accepted iff the slot has no active lock
+            let mut locks = block.locks;
+            if block.batch_lock {
+                let mut seen = HashSet::new();
+                locks.retain(|(slot, _)| seen.insert(*slot));
+            }
+            let slot_data = |&(slot, tx): &(u8, usize)| SlotData {
+                contract_address: "0x123".to_string(),
+                slot_index: slot_index(slot),
+                revert_value: vec![slot],
+                current_value: vec![slot, 1],
+                btc_txid: PROPERTY_TXIDS[tx].to_string(),
+                ..Default::default()
+            };
+            let accepted: Vec<bool> = if block.batch_lock && !locks.is_empty() {
+                service
+                    .batch_lock_slot(Request::new(BatchLockSlotRequest {
+                        namespace: String::new(),
+                        locked_at_block: current_block,
+                        btc_block,
+                        slots: locks.iter().map(slot_data).collect(),
+                        contract_slots: Vec::new(),
+                        atomic: false,
+                    }))
+                    .await
+                    .map_err(property_failure)?
+                    .into_inner()
+                    .slots
+                    .iter()
+                    .map(|slot| slot.status == slot_lock_status::Status::Locked as i32)
+                    .collect()
+            } else {
+                let mut accepted = Vec::new();
+                for lock in &locks {
+                    let data = slot_data(lock);
+                    let status = service
+                        .lock_slot(Request::new(LockSlotRequest {
+                            namespace: String::new(),
+                            locked_at_block: current_block,
+                            btc_block,
+                            contract_address: data.contract_address,
+                            slot_index: data.slot_index,
+                            revert_value: data.revert_value,
+                            current_value: data.current_value,
+                            btc_txid: data.btc_txid,
+                            raw_tx_hex: String::new(),
+                            metadata: Vec::new(),
+                            labels: Default::default(),
+                            alt_btc_txids: Vec::new(),
+                            required_confirmed_txids: 0,
+                        }))
+                        .await
+                        .map_err(property_failure)?
+                        .into_inner()
+                        .status;
+                    accepted.push(status == lock_slot_response::Status::Locked as i32);
+                }
+                accepted
+            };
+            prop_assert_eq!(accepted.len(), locks.len());
+            for (&(slot, tx), accepted) in locks.iter().zip(accepted) {
+                let model = &mut model[slot as usize];
+                prop_assert_eq!(accepted, model.lock.is_none(), "lock of slot {}", slot);
+                if accepted {
+                    model.lock = Some((btc_block, tx));
+                }
+            }
+
+            for op in block.ops {
+                match op {
+                    BlockOp::Confirm(tx) => {
+                        btc.add_confirmed_tx(PROPERTY_TXIDS[tx]);
+                        confirmed.insert(tx);
+                    }
+                    BlockOp::Unlock(slots) => {
+                        service
+                            .batch_unlock_slot(Request::new(BatchUnlockSlotRequest {
+                                namespace: String::new(),
+                                current_block,
+                                btc_block,
+                                slots: slots.iter().map(|&slot| identifier(slot)).collect(),
+                                expected_current_values: Vec::new(),
+                            }))
+                            .await
+                            .map_err(property_failure)?;
+                        for slot in slots {
+                            let model = &mut model[slot as usize];
+                            if model.lock.is_some() {
+                                model.settle(
+                                    current_block,
+                                    get_slot_status_response::Status::Unlocked as i32,
+                                );
+                            }
+                        }
+                    }
+                    BlockOp::Status { slots, batch } => {
+                        // Batch responses aren't in request order
+                        let statuses: HashMap<Vec<u8>, i32> = if batch {
+                            service
+                                .batch_get_slot_status(Request::new(BatchGetSlotStatusRequest {
+                                    namespace: String::new(),
+                                    current_block,
+                                    btc_block,
+                                    slots: slots.iter().map(|&slot| identifier(slot)).collect(),
+                                    max_staleness_blocks: None,
+                                }))
+                                .await
+                                .map_err(property_failure)?
+                                .into_inner()
+                                .slots
+                                .into_iter()
+                                .map(|slot| (slot.slot_index, slot.status))
+                                .collect()
+                        } else {
+                            let mut statuses = HashMap::new();
+                            for &slot in &slots {
+                                let response = service
+                                    .get_slot_status(Request::new(GetSlotStatusRequest {
+                                        namespace: String::new(),
+                                        current_block,
+                                        btc_block,
+                                        contract_address: "0x123".to_string(),
+                                        slot_index: slot_index(slot),
+                                        max_staleness_blocks: None,
+                                    }))
+                                    .await
+                                    .map_err(property_failure)?
+                                    .into_inner();
+                                statuses.insert(response.slot_index, response.status);
+                            }
+                            statuses
+                        };
+                        prop_assert_eq!(statuses.len(), slots.len());
+
+                        for slot in slots {
+                            let status = statuses[&slot_index(slot)];
+                            let model = &mut model[slot as usize];
+                            let expected = match model.decide(btc_block, &confirmed) {
+                                Some(expected) => {
+                                    if implicit_unlocks {
+                                        model.settle(current_block, expected);
+                                    }
+                                    expected
+                                }
+                                // An ended lock is reported as it ended in its own block, and
+                                // never as locked
+                                None => {
+                                    prop_assert!(
+                                        status != get_slot_status_response::Status::Locked as i32,
+                                        "slot {} reported locked after its unlock",
+                                        slot
+                                    );
+                                    match model.unlocked {
+                                        Some((unlocked_at, status))
+                                            if unlocked_at == current_block =>
+                                        {
+                                            status
+                                        }
+                                        _ => get_slot_status_response::Status::Unlocked as i32,
+                                    }
+                                }
+                            };
+                            prop_assert_eq!(status, expected, "status of slot {}", slot);
+                        }
+                    }
+                }
+            }
+
+            if block.finalize {
+                let response = service
+                    .finalize_block(Request::new(FinalizeBlockRequest {
+                        namespace: String::new(),
+                        current_block,
+                        btc_block,
+                    }))
+                    .await
+                    .map_err(property_failure)?
+                    .into_inner();
+                let ended: HashMap<Vec<u8>, i32> = response
+                    .ended
+                    .into_iter()
+                    .map(|slot| (slot.slot_index, slot.status))
+                    .collect();
+                for slot in 0..PROPERTY_SLOTS {
+                    let model = &mut model[slot as usize];
+                    let Some(expected) = model.decide(btc_block, &confirmed) else {
+                        continue;
+                    };
+                    model.settle(current_block, expected);
+                    let expected = (expected != get_slot_status_response::Status::Locked as i32)
+                        .then_some(expected);
+                    prop_assert_eq!(
+                        ended.get(&slot_index(slot)).copied(),
+                        expected,
+                        "finalized status of slot {}",
+                        slot
+                    );
+                }
+            }
+
+            let active = db
+                .active_lock_slots("", i64::MAX as u64)
+                .map_err(property_failure)?;
+            let mut active_slots = HashSet::new();
+            for (_, slot_index) in active {
+                prop_assert!(
+                    active_slots.insert(slot_index.clone()),
+                    "two active locks for slot {:?}",
+                    slot_index
+                );
+            }
+            prop_assert_eq!(
+                active_slots,
+                (0..PROPERTY_SLOTS)
+                    .filter(|&slot| model[slot as usize].lock.is_some())
+                    .map(slot_index)
+                    .collect::<HashSet<_>>()
+            );
+        }
+        Ok(())
+    }
+
+    proptest::proptest! {
+        #![proptest_config(proptest::test_runner::Config::with_cases(64))]
+
+        #[test]
+        fn test_lock_state_machine_properties(
+            blocks in property_blocks(),
+            implicit_unlocks in proptest::bool::ANY,
+        ) {
+            let db = crate::db::Database::new(
+                rusqlite::Connection::open_in_memory().map_err(property_failure)?,
+            )
+            .map_err(property_failure)?;
+            tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .map_err(property_failure)?
+                .block_on(play_property_blocks(db, blocks, implicit_unlocks))?;
+        }
+
+        #[test]
+        fn test_redb_lock_state_machine_properties(
+            blocks in property_blocks(),
+            implicit_unlocks in proptest::bool::ANY,
+        ) {
+            let db = crate::db::RedbStore::in_memory().map_err(property_failure)?;
+            tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .map_err(property_failure)?
+                .block_on(play_property_blocks(db, blocks, implicit_unlocks))?;
+        }
+    }
 }
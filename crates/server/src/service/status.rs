@@ -0,0 +1,713 @@
+use crate::db::is_fatal_error;
+use crate::service::bitcoin::{normalize_txid, BitcoinRpcError};
+use crate::service::slot_lock::StaleBtcBlock;
+use rusqlite::ErrorCode;
+use sova_sentinel_proto::bad_request::with_field_violations;
+use sova_sentinel_proto::details::StatusDetails;
+use sova_sentinel_proto::error_info::{
+    with_error_info, ACTIVE_LOCK_QUOTA, BATCH_TOO_LARGE, BITCOIN_NODE_UNAVAILABLE,
+    BITCOIN_RPC_FAILED, BITCOIN_RPC_TIMEOUT, CURRENT_VALUE_MISMATCH, DATABASE_BUSY,
+    DATABASE_FAILED, DATABASE_UNAVAILABLE, FENCED, JOURNAL_FAILED, LOCK_COMMITMENT_DISABLED,
+    LOCK_NOT_COMMITTED, LOCK_TX_NOT_FOUND, NOT_STANDBY, NO_SIGNING_KEY, RATE_LIMITED, READ_ONLY,
+    REPLICATION_DISABLED, RESERVATION_NOT_FOUND, STALE_BTC_BLOCK, STALE_REPLICA, STANDBY,
+    SUBSCRIBER_LAGGED, UNAUTHENTICATED, UNIDENTIFIED_CALLER,
+};
+use sova_sentinel_proto::google::rpc::bad_request::FieldViolation;
+use std::collections::HashMap;
+use std::time::Duration;
+use tonic::{Code, Status};
+
+/// Longest slot index accepted, the size of an EVM storage slot
+const MAX_SLOT_INDEX_LEN: usize = 32;
+/// Most hex digits accepted in a contract address, the size of an EVM address
+const MAX_CONTRACT_ADDRESS_DIGITS: usize = 40;
+/// Longest namespace accepted
+const MAX_NAMESPACE_LEN: usize = 64;
+/// Longest lock metadata accepted
+const MAX_METADATA_LEN: usize = 1024;
+/// Most labels accepted on one lock
+const MAX_LABELS: usize = 16;
+/// Longest label key accepted
+const MAX_LABEL_KEY_LEN: usize = 64;
+/// Longest label value accepted
+const MAX_LABEL_VALUE_LEN: usize = 256;
+/// Most alternative transactions accepted on one lock
+const MAX_ALT_BTC_TXIDS: usize = 8;
+/// Suggested retry delay when SQLite is busy or locked by another writer
+const DATABASE_BUSY_RETRY_DELAY: Duration = Duration::from_millis(100);
+/// Suggested retry delay while the database supervisor recovers the database
+const DATABASE_RECOVERY_RETRY_DELAY: Duration = Duration::from_secs(5);
+/// Suggested retry delay when the Bitcoin node could not be reached
+const BITCOIN_NODE_UNREACHABLE_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+fn is_database_busy(e: &anyhow::Error) -> bool {
+    e.chain().any(|cause| {
+        matches!(
+            cause.downcast_ref::<rusqlite::Error>(),
+            Some(rusqlite::Error::SqliteFailure(err, _))
+                if matches!(err.code, ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked)
+        )
+    })
+}
+
+fn is_database_read_only(e: &anyhow::Error) -> bool {
+    e.chain().any(|cause| {
+        matches!(
+            cause.downcast_ref::<rusqlite::Error>(),
+            Some(rusqlite::Error::SqliteFailure(err, _)) if err.code == ErrorCode::ReadOnly
+        )
+    })
+}
+
+// Maps a database failure to a gRPC status. A busy database is reported as UNAVAILABLE with a
+// RetryInfo detail, since the request will likely succeed once the other writer is done. So are
+// fatal errors and writes to a read-only fallback snapshot, which last until the database
+// supervisor reopens the database.
+pub(crate) fn database_status(e: anyhow::Error) -> Status {
+    let message = format!("Database error: {}", e);
+    if is_database_busy(&e) {
+        StatusDetails::new()
+            .retry_info(DATABASE_BUSY_RETRY_DELAY)
+            .error_info(DATABASE_BUSY, HashMap::new())
+            .into_status(Code::Unavailable, message)
+    } else if is_fatal_error(&e) || is_database_read_only(&e) {
+        StatusDetails::new()
+            .retry_info(DATABASE_RECOVERY_RETRY_DELAY)
+            .error_info(DATABASE_UNAVAILABLE, HashMap::new())
+            .into_status(Code::Unavailable, message)
+    } else {
+        with_error_info(Code::Internal, message, DATABASE_FAILED, HashMap::new())
+    }
+}
+
+// Rejects a state-changing request that couldn't be written to the journal ahead of handling it
+pub(crate) fn journal_failed_status(e: anyhow::Error) -> Status {
+    with_error_info(
+        Code::Internal,
+        format!("Journal error: {:#}", e),
+        JOURNAL_FAILED,
+        HashMap::new(),
+    )
+}
+
+// Maps a Bitcoin RPC failure to a gRPC status. Failures to reach the node are reported as
+// UNAVAILABLE with a RetryInfo detail; while the circuit breaker is open the suggested delay is
+// the remaining cooldown, which is also sent as a `grpc-retry-pushback-ms` hint.
+pub(crate) fn bitcoin_rpc_status(e: anyhow::Error) -> Status {
+    let message = format!("Bitcoin RPC error: {}", e);
+    match e.downcast_ref::<BitcoinRpcError>() {
+        Some(BitcoinRpcError::CircuitOpen { retry_after }) => {
+            let mut status = StatusDetails::new()
+                .retry_info(*retry_after)
+                .error_info(BITCOIN_NODE_UNAVAILABLE, HashMap::new())
+                .into_status(Code::Unavailable, message);
+            if let Ok(value) = retry_after.as_millis().to_string().parse() {
+                status
+                    .metadata_mut()
+                    .insert("grpc-retry-pushback-ms", value);
+            }
+            status
+        }
+        Some(BitcoinRpcError::BitcoinNodeUnreachable { .. }) => StatusDetails::new()
+            .retry_info(BITCOIN_NODE_UNREACHABLE_RETRY_DELAY)
+            .error_info(BITCOIN_NODE_UNAVAILABLE, HashMap::new())
+            .into_status(Code::Unavailable, message),
+        Some(BitcoinRpcError::BudgetExceeded { .. }) => with_error_info(
+            Code::DeadlineExceeded,
+            message,
+            BITCOIN_RPC_TIMEOUT,
+            HashMap::new(),
+        ),
+        _ => with_error_info(Code::Internal, message, BITCOIN_RPC_FAILED, HashMap::new()),
+    }
+}
+
+// Rejects writes on a sentinel that only serves status queries
+pub(crate) fn read_only_status() -> Status {
+    with_error_info(
+        Code::FailedPrecondition,
+        "This sentinel only serves slot status queries",
+        READ_ONLY,
+        HashMap::new(),
+    )
+}
+
+// Rejects writes on a standby, which only applies its primary's changes until it is promoted
+pub(crate) fn standby_status() -> Status {
+    with_error_info(
+        Code::FailedPrecondition,
+        "This sentinel is a standby and doesn't accept writes until it is promoted",
+        STANDBY,
+        HashMap::new(),
+    )
+}
+
+// Rejects status queries on a standby that don't say how stale an answer may be
+pub(crate) fn standby_read_status() -> Status {
+    with_error_info(
+        Code::FailedPrecondition,
+        "This sentinel is a standby and only answers status queries setting max_staleness_blocks",
+        STANDBY,
+        HashMap::new(),
+    )
+}
+
+// Rejects status queries on a standby further behind its primary than they allow
+pub(crate) fn stale_replica_status(lag_blocks: u64, max_staleness_blocks: u64) -> Status {
+    with_error_info(
+        Code::FailedPrecondition,
+        format!(
+            "This standby is {} blocks behind its primary, more than max_staleness_blocks={}",
+            lag_blocks, max_staleness_blocks
+        ),
+        STALE_REPLICA,
+        HashMap::from([
+            ("lag_blocks".to_string(), lag_blocks.to_string()),
+            (
+                "max_staleness_blocks".to_string(),
+                max_staleness_blocks.to_string(),
+            ),
+        ]),
+    )
+}
+
+// Rejects writes on a former primary superseded by a standby promoted to a higher epoch
+pub(crate) fn fenced_status(epoch: u64) -> Status {
+    with_error_info(
+        Code::FailedPrecondition,
+        format!(
+            "This sentinel was superseded by a primary at epoch {} and doesn't accept writes",
+            epoch
+        ),
+        FENCED,
+        HashMap::from([("epoch".to_string(), epoch.to_string())]),
+    )
+}
+
+// Reports that Promote was called on a sentinel that isn't a standby
+pub(crate) fn not_standby_status(role: &str) -> Status {
+    with_error_info(
+        Code::FailedPrecondition,
+        format!("Only a standby can be promoted, this sentinel is {}", role),
+        NOT_STANDBY,
+        HashMap::from([("role".to_string(), role.to_string())]),
+    )
+}
+
+// Reports that replication RPCs were called on a sentinel without a replication role
+pub(crate) fn replication_disabled_status() -> Status {
+    with_error_info(
+        Code::FailedPrecondition,
+        "This sentinel has no replication role",
+        REPLICATION_DISABLED,
+        HashMap::new(),
+    )
+}
+
+// Reports that GetPublicKey was called on a sentinel whose responses aren't signed
+pub(crate) fn no_signing_key_status() -> Status {
+    with_error_info(
+        Code::FailedPrecondition,
+        "This sentinel has no signing key",
+        NO_SIGNING_KEY,
+        HashMap::new(),
+    )
+}
+
+// Reports that lock commitments were requested from a sentinel that doesn't compute them
+pub(crate) fn lock_commitment_disabled_status() -> Status {
+    with_error_info(
+        Code::FailedPrecondition,
+        "This sentinel doesn't compute lock commitments",
+        LOCK_COMMITMENT_DISABLED,
+        HashMap::new(),
+    )
+}
+
+// Reports that GetLockInclusionProof found no active lock on the slot in the latest commitment
+pub(crate) fn lock_not_committed_status(contract_address: &str, slot_index: &[u8]) -> Status {
+    with_error_info(
+        Code::NotFound,
+        format!(
+            "Slot 0x{} of {} has no active lock in the latest lock commitment",
+            hex::encode(slot_index),
+            contract_address
+        ),
+        LOCK_NOT_COMMITTED,
+        HashMap::from([
+            ("contract_address".to_string(), contract_address.to_string()),
+            (
+                "slot_index".to_string(),
+                format!("0x{}", hex::encode(slot_index)),
+            ),
+        ]),
+    )
+}
+
+// Ends the event stream of a subscriber that missed events. It has to catch up through
+// ExportEvents before subscribing again.
+pub(crate) fn subscriber_lagged_status(missed: u64) -> Status {
+    with_error_info(
+        Code::Aborted,
+        format!("Subscriber fell behind and missed {} events", missed),
+        SUBSCRIBER_LAGGED,
+        HashMap::from([("missed_events".to_string(), missed.to_string())]),
+    )
+}
+
+// Rejects a request from a peer over its `quota`, e.g. `requests_per_sec`, suggesting when it
+// fits the quota again
+pub(crate) fn rate_limited_status(quota: &str, retry_after: Duration) -> Status {
+    StatusDetails::new()
+        .retry_info(retry_after)
+        .error_info(
+            RATE_LIMITED,
+            HashMap::from([("quota".to_string(), quota.to_string())]),
+        )
+        .into_status(
+            Code::ResourceExhausted,
+            format!("Request quota exceeded: {}", quota),
+        )
+}
+
+// Rejects locks that would take `caller` past the `limit` of active locks it can hold, with the
+// `active` locks it holds already
+pub(crate) fn active_lock_quota_status(caller: &str, active: u64, limit: u64) -> Status {
+    let metadata = HashMap::from([
+        ("locked_by".to_string(), caller.to_string()),
+        ("active_locks".to_string(), active.to_string()),
+        ("max_active_locks".to_string(), limit.to_string()),
+    ]);
+    with_error_info(
+        Code::ResourceExhausted,
+        format!("{} holds {} of its {} active locks", caller, active, limit),
+        ACTIVE_LOCK_QUOTA,
+        metadata,
+    )
+}
+
+// Rejects locks from a caller without an identity when active locks are limited
+pub(crate) fn unidentified_caller_status() -> Status {
+    with_error_info(
+        Code::Unauthenticated,
+        "Active locks are limited and the caller has no identity",
+        UNIDENTIFIED_CALLER,
+        HashMap::new(),
+    )
+}
+
+// Rejects a request to a listener that requires a bearer token it didn't carry
+pub(crate) fn unauthenticated_status() -> Status {
+    with_error_info(
+        Code::Unauthenticated,
+        "Missing or invalid bearer token",
+        UNAUTHENTICATED,
+        HashMap::new(),
+    )
+}
+
+// Reports that ReplaceLockTx or UnlockByTxid found no active locks on the transaction
+pub(crate) fn lock_tx_not_found_status(btc_txid: &str) -> Status {
+    with_error_info(
+        Code::NotFound,
+        format!("No active locks on btc_txid {}", btc_txid),
+        LOCK_TX_NOT_FOUND,
+        HashMap::from([("btc_txid".to_string(), btc_txid.to_string())]),
+    )
+}
+
+// Reports that CommitLock found no reservation to commit, because it never existed, was
+// already committed or timed out
+pub(crate) fn reservation_not_found_status(reservation_id: u64) -> Status {
+    with_error_info(
+        Code::NotFound,
+        format!("No pending reservation {}", reservation_id),
+        RESERVATION_NOT_FOUND,
+        HashMap::from([("reservation_id".to_string(), reservation_id.to_string())]),
+    )
+}
+
+/// Invalid fields of a request, reported together as one `INVALID_ARGUMENT` status with a
+/// `google.rpc.BadRequest` detail
+#[derive(Debug, Default)]
+pub(crate) struct FieldViolations(Vec<FieldViolation>);
+
+impl FieldViolations {
+    pub fn add(&mut self, field: impl Into<String>, description: impl Into<String>) {
+        self.0.push(FieldViolation {
+            field: field.into(),
+            description: description.into(),
+        });
+    }
+
+    /// Checks the contract address and slot index of the slot at `path`, and pads a valid slot
+    /// index in place to its canonical form, see [`normalize_slot_index`]
+    pub fn check_slot(&mut self, path: &str, contract_address: &str, slot_index: &mut Vec<u8>) {
+        self.check_contract_address(path, contract_address);
+        if slot_index.is_empty() || slot_index.len() > MAX_SLOT_INDEX_LEN {
+            self.add(
+                field(path, "slot_index"),
+                format!("must be 1 to {} bytes", MAX_SLOT_INDEX_LEN),
+            );
+        } else {
+            normalize_slot_index(slot_index);
+        }
+    }
+
+    /// Checks the contract address of the element at `path`, which is required
+    pub fn check_contract_address(&mut self, path: &str, contract_address: &str) {
+        if contract_address.is_empty() {
+            self.add(field(path, "contract_address"), "is required");
+        } else if !is_contract_address(contract_address) {
+            self.add(
+                field(path, "contract_address"),
+                format!(
+                    "must be 0x followed by 1 to {} hex digits",
+                    MAX_CONTRACT_ADDRESS_DIGITS
+                ),
+            );
+        }
+    }
+
+    /// Checks the namespace of a request, which may be empty for the default namespace
+    pub fn check_namespace(&mut self, namespace: &str) {
+        self.check_namespace_at("", namespace)
+    }
+
+    /// Checks the namespace of the element at `path`, see [`Self::check_namespace`]
+    pub fn check_namespace_at(&mut self, path: &str, namespace: &str) {
+        if namespace.len() > MAX_NAMESPACE_LEN || !is_name(namespace) {
+            self.add(
+                field(path, "namespace"),
+                format!(
+                    "must be at most {} letters, digits, '.', '_' or '-'",
+                    MAX_NAMESPACE_LEN
+                ),
+            );
+        }
+    }
+
+    /// Checks the metadata and labels of the lock at `path`
+    pub fn check_lock_data(
+        &mut self,
+        path: &str,
+        metadata: &[u8],
+        labels: &HashMap<String, String>,
+    ) {
+        if metadata.len() > MAX_METADATA_LEN {
+            self.add(
+                field(path, "metadata"),
+                format!("must be at most {} bytes", MAX_METADATA_LEN),
+            );
+        }
+        if labels.len() > MAX_LABELS {
+            self.add(
+                field(path, "labels"),
+                format!("must have at most {} entries", MAX_LABELS),
+            );
+        }
+        let mut keys: Vec<_> = labels.keys().collect();
+        keys.sort();
+        for key in keys {
+            let path = format!("{}[{:?}]", field(path, "labels"), key);
+            if key.is_empty() || key.len() > MAX_LABEL_KEY_LEN || !is_name(key) {
+                self.add(
+                    &path,
+                    format!(
+                        "key must be 1 to {} letters, digits, '.', '_' or '-'",
+                        MAX_LABEL_KEY_LEN
+                    ),
+                );
+            }
+            if labels[key].len() > MAX_LABEL_VALUE_LEN {
+                self.add(
+                    &path,
+                    format!("value must be at most {} bytes", MAX_LABEL_VALUE_LEN),
+                );
+            }
+        }
+    }
+
+    /// Normalizes the txid at `path` in place, see [`normalize_txid`]
+    pub fn normalize_txid(&mut self, path: &str, txid: &mut String) {
+        match normalize_txid(txid) {
+            Ok(normalized) => *txid = normalized,
+            Err(e) => self.add(path, e.to_string()),
+        }
+    }
+
+    /// Normalizes the alternative txids of the lock at `path` in place, which must differ from
+    /// its already normalized `btc_txid` and from each other
+    pub fn normalize_alt_txids(
+        &mut self,
+        path: &str,
+        btc_txid: &str,
+        alt_btc_txids: &mut [String],
+    ) {
+        let path = field(path, "alt_btc_txids");
+        if alt_btc_txids.len() > MAX_ALT_BTC_TXIDS {
+            self.add(
+                &path,
+                format!("must have at most {} entries", MAX_ALT_BTC_TXIDS),
+            );
+        }
+        for idx in 0..alt_btc_txids.len() {
+            let txid_path = format!("{}[{}]", path, idx);
+            self.normalize_txid(&txid_path, &mut alt_btc_txids[idx]);
+            let txid = &alt_btc_txids[idx];
+            if txid == btc_txid || alt_btc_txids[..idx].contains(txid) {
+                self.add(txid_path, "must not repeat btc_txid or another alternative");
+            }
+        }
+    }
+
+    /// Normalizes the number of transactions that must confirm to unlock the lock at `path`, 0
+    /// meaning 1, which can't exceed its `btc_txid` and alternatives
+    pub fn normalize_required_txids(
+        &mut self,
+        path: &str,
+        alt_btc_txids: &[String],
+        required_confirmed_txids: &mut u32,
+    ) {
+        *required_confirmed_txids = (*required_confirmed_txids).max(1);
+        if *required_confirmed_txids as usize > alt_btc_txids.len() + 1 {
+            self.add(
+                field(path, "required_confirmed_txids"),
+                "must not exceed the number of btc_txid and alt_btc_txids",
+            );
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns the status reporting the violations, if there are any
+    pub fn into_status(self) -> Option<Status> {
+        (!self.0.is_empty()).then(|| with_field_violations(self.0))
+    }
+}
+
+/// Left-pads a slot index with zeros to the 32 bytes of an EVM storage slot, so every encoding of
+/// the same big-endian slot number is stored and matched as one slot
+fn normalize_slot_index(slot_index: &mut Vec<u8>) {
+    if slot_index.len() < MAX_SLOT_INDEX_LEN {
+        let padding = MAX_SLOT_INDEX_LEN - slot_index.len();
+        slot_index.splice(0..0, std::iter::repeat_n(0, padding));
+    }
+}
+
+/// Returns whether `address` is `0x` followed by at most 40 hex digits. Case is kept as sent,
+/// since locks are stored under the address exactly as given.
+fn is_contract_address(address: &str) -> bool {
+    address.strip_prefix("0x").is_some_and(|digits| {
+        (1..=MAX_CONTRACT_ADDRESS_DIGITS).contains(&digits.len())
+            && digits.bytes().all(|b| b.is_ascii_hexdigit())
+    })
+}
+
+/// Returns whether `name` only has letters, digits, `.`, `_` and `-`, as namespaces and label keys
+/// must
+fn is_name(name: &str) -> bool {
+    name.bytes()
+        .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'.' | b'_' | b'-'))
+}
+
+/// Reports a single invalid field
+pub(crate) fn invalid_field(field: &str, description: impl Into<String>) -> Status {
+    let mut violations = FieldViolations::default();
+    violations.add(field, description);
+    with_field_violations(violations.0)
+}
+
+/// Joins the path of a repeated element with one of its fields, e.g. `slots[2]` and `btc_txid`
+pub(crate) fn field(path: &str, name: &str) -> String {
+    if path.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", path, name)
+    }
+}
+
+// Maps a status query with a stale btc_block to FAILED_PRECONDITION with a STALE_BTC_BLOCK
+// ErrorInfo detail carrying both heights, so the client can wait for its Bitcoin view to catch up
+pub(crate) fn stale_btc_block_status(e: StaleBtcBlock) -> Status {
+    let metadata = HashMap::from([
+        ("btc_block".to_string(), e.btc_block.to_string()),
+        ("lock_btc_block".to_string(), e.lock_btc_block.to_string()),
+    ]);
+    with_error_info(
+        Code::FailedPrecondition,
+        e.to_string(),
+        STALE_BTC_BLOCK,
+        metadata,
+    )
+}
+
+// Maps a conditional unlock whose slot changed to FAILED_PRECONDITION with a
+// CURRENT_VALUE_MISMATCH ErrorInfo detail carrying the slot and both values, `current_value` being
+// absent if the slot isn't locked
+pub(crate) fn current_value_mismatch_status(
+    contract_address: &str,
+    slot_index: &[u8],
+    expected: &[u8],
+    actual: Option<&[u8]>,
+) -> Status {
+    let mut metadata = HashMap::from([
+        ("contract_address".to_string(), contract_address.to_string()),
+        (
+            "slot_index".to_string(),
+            format!("0x{}", hex::encode(slot_index)),
+        ),
+        (
+            "expected_current_value".to_string(),
+            format!("0x{}", hex::encode(expected)),
+        ),
+    ]);
+    let message = match actual {
+        Some(actual) => {
+            metadata.insert(
+                "current_value".to_string(),
+                format!("0x{}", hex::encode(actual)),
+            );
+            format!(
+                "Slot 0x{} of {} holds current_value 0x{}, not the expected 0x{}",
+                hex::encode(slot_index),
+                contract_address,
+                hex::encode(actual),
+                hex::encode(expected)
+            )
+        }
+        None => format!(
+            "Slot 0x{} of {} isn't locked, expected current_value 0x{}",
+            hex::encode(slot_index),
+            contract_address,
+            hex::encode(expected)
+        ),
+    };
+    with_error_info(
+        Code::FailedPrecondition,
+        message,
+        CURRENT_VALUE_MISMATCH,
+        metadata,
+    )
+}
+
+// Maps a batch request over the maximum batch size to INVALID_ARGUMENT with a BATCH_TOO_LARGE
+// ErrorInfo detail carrying the maximum, so the client can split the batch and retry
+pub(crate) fn batch_too_large_status(batch_size: usize, max_batch_size: u32) -> Status {
+    let metadata = HashMap::from([
+        ("batch_size".to_string(), batch_size.to_string()),
+        ("max_batch_size".to_string(), max_batch_size.to_string()),
+    ]);
+    with_error_info(
+        Code::InvalidArgument,
+        format!(
+            "Batch of {} slots exceeds the maximum batch size of {}",
+            batch_size, max_batch_size
+        ),
+        BATCH_TOO_LARGE,
+        metadata,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sova_sentinel_proto::error_info::error_info;
+    use sova_sentinel_proto::retry_info::retry_delay;
+
+    fn reason(status: &Status) -> String {
+        error_info(status).unwrap().reason
+    }
+
+    #[test]
+    fn test_circuit_open_maps_to_unavailable() {
+        let status = bitcoin_rpc_status(
+            BitcoinRpcError::CircuitOpen {
+                retry_after: Duration::from_millis(1500),
+            }
+            .into(),
+        );
+        assert_eq!(status.code(), Code::Unavailable);
+        assert_eq!(
+            status.metadata().get("grpc-retry-pushback-ms").unwrap(),
+            "1500"
+        );
+        assert_eq!(retry_delay(&status), Some(Duration::from_millis(1500)));
+        assert_eq!(reason(&status), BITCOIN_NODE_UNAVAILABLE);
+
+        let status = bitcoin_rpc_status(anyhow::anyhow!("Operation failed"));
+        assert_eq!(status.code(), Code::Internal);
+        assert_eq!(retry_delay(&status), None);
+        assert_eq!(reason(&status), BITCOIN_RPC_FAILED);
+    }
+
+    #[test]
+    fn test_node_unreachable_has_retry_info() {
+        let status =
+            bitcoin_rpc_status(BitcoinRpcError::BitcoinNodeUnreachable { attempts: 5 }.into());
+        assert_eq!(status.code(), Code::Unavailable);
+        assert_eq!(
+            retry_delay(&status),
+            Some(BITCOIN_NODE_UNREACHABLE_RETRY_DELAY)
+        );
+        assert_eq!(reason(&status), BITCOIN_NODE_UNAVAILABLE);
+    }
+
+    #[test]
+    fn test_database_busy_has_retry_info() {
+        let busy = rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_BUSY),
+            None,
+        );
+        let status = database_status(busy.into());
+        assert_eq!(status.code(), Code::Unavailable);
+        assert_eq!(retry_delay(&status), Some(DATABASE_BUSY_RETRY_DELAY));
+        assert_eq!(reason(&status), DATABASE_BUSY);
+
+        let status = database_status(anyhow::anyhow!("no such table"));
+        assert_eq!(status.code(), Code::Internal);
+        assert_eq!(reason(&status), DATABASE_FAILED);
+    }
+
+    #[test]
+    fn test_field_violations() {
+        let mut violations = FieldViolations::default();
+        let mut txid = format!("0x{}", "AB".repeat(32));
+        violations.normalize_txid("slots[0].btc_txid", &mut txid);
+        assert_eq!(txid, "ab".repeat(32));
+        let mut slot_index = vec![1];
+        violations.check_slot("slots[0]", "0x123", &mut slot_index);
+        let mut expected = vec![0; 32];
+        expected[31] = 1;
+        assert_eq!(slot_index, expected);
+        violations.check_slot(
+            "slots[0]",
+            &format!("0x{}", "aB".repeat(20)),
+            &mut vec![0; 32],
+        );
+        assert!(violations.is_empty());
+
+        violations.check_slot("slots[1]", "", &mut vec![0; 33]);
+        violations.check_slot("slots[2]", "0xtoken", &mut vec![1]);
+        violations.check_slot("slots[3]", "123", &mut vec![1]);
+        violations.check_slot("slots[4]", &format!("0x{}", "a".repeat(41)), &mut vec![1]);
+        let status = violations.into_status().unwrap();
+        assert_eq!(status.code(), Code::InvalidArgument);
+        let fields: Vec<_> = sova_sentinel_proto::bad_request::field_violations(&status)
+            .into_iter()
+            .map(|violation| violation.field)
+            .collect();
+        assert_eq!(
+            fields,
+            vec![
+                "slots[1].contract_address",
+                "slots[1].slot_index",
+                "slots[2].contract_address",
+                "slots[3].contract_address",
+                "slots[4].contract_address",
+            ]
+        );
+    }
+}
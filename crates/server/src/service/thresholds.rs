@@ -0,0 +1,59 @@
+use crate::config::ThresholdOverride;
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+/// Threshold overrides by contract, shared between the SlotLock service that applies them and the
+/// admin service that adjusts them at runtime. Contract addresses are matched case-insensitively.
+#[derive(Debug, Default)]
+pub struct ContractThresholds {
+    overrides: RwLock<HashMap<String, ThresholdOverride>>,
+}
+
+impl ContractThresholds {
+    pub fn new(overrides: HashMap<String, ThresholdOverride>) -> Self {
+        Self {
+            overrides: RwLock::new(
+                overrides
+                    .into_iter()
+                    .filter(|(_, thresholds)| !thresholds.is_empty())
+                    .map(|(contract_address, thresholds)| {
+                        (contract_address.to_lowercase(), thresholds)
+                    })
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Returns the overrides of a contract, empty if it has none
+    pub fn get(&self, contract_address: &str) -> ThresholdOverride {
+        self.overrides
+            .read()
+            .unwrap()
+            .get(&contract_address.to_lowercase())
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Replaces the overrides of a contract, removing them if `thresholds` is empty
+    pub fn set(&self, contract_address: &str, thresholds: ThresholdOverride) {
+        let mut overrides = self.overrides.write().unwrap();
+        if thresholds.is_empty() {
+            overrides.remove(&contract_address.to_lowercase());
+        } else {
+            overrides.insert(contract_address.to_lowercase(), thresholds);
+        }
+    }
+
+    /// Returns all overrides, ordered by contract address
+    pub fn list(&self) -> Vec<(String, ThresholdOverride)> {
+        let mut overrides: Vec<_> = self
+            .overrides
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(contract_address, thresholds)| (contract_address.clone(), *thresholds))
+            .collect();
+        overrides.sort_by(|a, b| a.0.cmp(&b.0));
+        overrides
+    }
+}
@@ -0,0 +1,441 @@
+//! SPV verification of lock transactions. Block headers are synced from the Bitcoin node and
+//! checked for linkage and proof of work before they are stored, so the confirmations of a lock
+//! transaction can be proven with a Merkle proof against headers the sentinel validated itself,
+//! instead of resting on the `confirmations` count reported by the node.
+
+use crate::db::Database;
+use crate::service::{BitcoinRpcService, BitcoinRpcServiceAPI};
+use anyhow::{Context, Result};
+use bitcoin::bip158::FilterHeader;
+use bitcoin::block::Header;
+use bitcoin::consensus::Params;
+use bitcoin::merkle_tree::MerkleBlock;
+use bitcoin::{BlockHash, Network, Target, Txid, Work};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Headers validated and stored per transaction while syncing
+const HEADER_BATCH_SIZE: u64 = 500;
+
+/// Validated chain of block headers, starting at a checkpoint header
+pub struct HeaderStore {
+    db: Database,
+    params: Params,
+}
+
+impl HeaderStore {
+    pub fn new(db: Database, network: Network) -> Self {
+        Self {
+            db,
+            params: Params::new(network),
+        }
+    }
+
+    /// Returns the height and header of the highest stored block
+    pub fn tip(&self) -> Result<Option<(u64, Header)>> {
+        self.db.header_tip()
+    }
+
+    pub fn header(&self, height: u64) -> Result<Option<Header>> {
+        self.db.block_header(height)
+    }
+
+    /// Returns the height of the stored block with hash `block_hash`
+    pub fn height(&self, block_hash: &BlockHash) -> Result<Option<u64>> {
+        self.db.block_height(block_hash)
+    }
+
+    /// Replaces the stored chain with `header` at `height`. The checkpoint is trusted, only its
+    /// own proof of work is checked.
+    pub fn set_checkpoint(&self, height: u64, header: &Header) -> Result<()> {
+        self.check_pow(header)?;
+        self.db.with_transaction(|tx| {
+            self.db.truncate_block_headers(tx, 0)?;
+            self.db.insert_block_headers(tx, height, &[*header])
+        })
+    }
+
+    /// Validates `headers` as the next blocks after the stored tip and stores them
+    pub fn extend(&self, headers: &[Header]) -> Result<()> {
+        let (mut height, mut prev) = self
+            .tip()?
+            .context("Header store has no checkpoint to extend")?;
+        let start_height = height + 1;
+        for header in headers {
+            height += 1;
+            if header.prev_blockhash != prev.block_hash() {
+                anyhow::bail!(
+                    "Header {} at height {} doesn't extend block {}",
+                    header.block_hash(),
+                    height,
+                    prev.block_hash()
+                );
+            }
+            self.check_difficulty(height, &prev, header)?;
+            self.check_pow(header)?;
+            prev = *header;
+        }
+        self.db
+            .with_transaction(|tx| self.db.insert_block_headers(tx, start_height, headers))
+    }
+
+    /// Returns the cumulative proof of work of the stored headers above `height`
+    pub fn work_above(&self, height: u64) -> Result<Work> {
+        let tip_height = self.tip()?.map_or(height, |(tip_height, _)| tip_height);
+        let mut work = Work::from_be_bytes([0; 32]);
+        for height in height + 1..=tip_height {
+            if let Some(header) = self.header(height)? {
+                work = work + header.work();
+            }
+        }
+        Ok(work)
+    }
+
+    /// Returns the BIP157 filter header stored for the block at `height`, if its filter was
+    /// checked
+    pub fn filter_header(&self, height: u64) -> Result<Option<FilterHeader>> {
+        self.db.block_filter_header(height)
+    }
+
+    /// Stores the filter headers of the blocks from `start_height` on
+    pub fn set_filter_headers(
+        &self,
+        start_height: u64,
+        filter_headers: &[FilterHeader],
+    ) -> Result<()> {
+        self.db.with_transaction(|tx| {
+            self.db
+                .set_block_filter_headers(tx, start_height, filter_headers)
+        })
+    }
+
+    /// Deletes the headers above `height`, returning how many were deleted
+    pub fn rewind(&self, height: u64) -> Result<usize> {
+        self.db
+            .with_transaction(|tx| self.db.truncate_block_headers(tx, height + 1))
+    }
+
+    /// Returns the confirmations of `txid` proven by `proof`, a serialized Merkle block as
+    /// returned by `gettxoutproof`, or `None` if the proof's block isn't stored. Fails if the
+    /// proof is invalid or doesn't include `txid`.
+    pub fn confirmations(&self, txid: &Txid, proof: &[u8]) -> Result<Option<u32>> {
+        let merkle_block: MerkleBlock =
+            bitcoin::consensus::deserialize(proof).context("Malformed Merkle proof")?;
+        let (mut matches, mut indexes) = (Vec::new(), Vec::new());
+        merkle_block
+            .extract_matches(&mut matches, &mut indexes)
+            .map_err(|e| anyhow::anyhow!("Invalid Merkle proof: {:?}", e))?;
+        if !matches.contains(txid) {
+            anyhow::bail!("Merkle proof doesn't include {}", txid);
+        }
+
+        let Some(height) = self.db.block_height(&merkle_block.header.block_hash())? else {
+            return Ok(None);
+        };
+        let Some((tip_height, _)) = self.tip()? else {
+            return Ok(None);
+        };
+        Ok(Some(tip_height.saturating_sub(height) as u32 + 1))
+    }
+
+    fn check_pow(&self, header: &Header) -> Result<()> {
+        let target = header.target();
+        if target > self.params.max_attainable_target {
+            anyhow::bail!(
+                "Header {} has a target above the network's limit",
+                header.block_hash()
+            );
+        }
+        header.validate_pow(target).map_err(|_| {
+            anyhow::anyhow!("Header {} has invalid proof of work", header.block_hash())
+        })?;
+        Ok(())
+    }
+
+    /// Checks the difficulty only changes at retargets, and by at most a factor of 4. Not checked
+    /// on networks with minimum-difficulty blocks or without retargeting.
+    fn check_difficulty(&self, height: u64, prev: &Header, header: &Header) -> Result<()> {
+        if self.params.allow_min_difficulty_blocks || self.params.no_pow_retargeting {
+            return Ok(());
+        }
+        if !height.is_multiple_of(self.params.difficulty_adjustment_interval()) {
+            if header.bits != prev.bits {
+                anyhow::bail!(
+                    "Header {} at height {} changes the difficulty outside a retarget",
+                    header.block_hash(),
+                    height
+                );
+            }
+            return Ok(());
+        }
+
+        let (prev_target, target) = (prev.target(), header.target());
+        // The new target is rounded down by its compact encoding
+        let min_target =
+            Target::from_compact(prev_target.min_transition_threshold().to_compact_lossy());
+        if target < min_target || target > prev_target.max_transition_threshold(&self.params) {
+            anyhow::bail!(
+                "Header {} at height {} changes the difficulty by more than a factor of 4",
+                header.block_hash(),
+                height
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Keeps a header store in sync with the active chain of the Bitcoin node
+pub struct HeaderSync {
+    store: Arc<HeaderStore>,
+    bitcoin: BitcoinRpcService,
+    start_height: u64,
+    start_hash: Option<BlockHash>,
+    interval: Duration,
+}
+
+impl HeaderSync {
+    /// Syncs headers from `start_height` every interval. The header at `start_height` is the
+    /// checkpoint, taken from the node unless pinned with `with_start_hash`.
+    pub fn new(
+        store: Arc<HeaderStore>,
+        bitcoin: BitcoinRpcService,
+        start_height: u64,
+        interval: Duration,
+    ) -> Self {
+        Self {
+            store,
+            bitcoin,
+            start_height,
+            start_hash: None,
+            interval,
+        }
+    }
+
+    /// Requires the checkpoint to be the block with `hash`
+    pub fn with_start_hash(mut self, hash: BlockHash) -> Self {
+        self.start_hash = Some(hash);
+        self
+    }
+
+    /// Syncs the store up to the node's tip, first rewinding the headers the node reorged away.
+    /// Returns the height of the stored tip.
+    pub async fn sync(&self) -> Result<u64> {
+        let node_height = self.bitcoin.chain_info().await?.tip_height;
+        let mut height = self.rewind_to_node_chain(node_height).await?;
+        while height < node_height {
+            let end_height = node_height.min(height + HEADER_BATCH_SIZE);
+            let mut headers = Vec::new();
+            for height in height + 1..=end_height {
+                headers.push(self.bitcoin.block_header(height).await?);
+            }
+            self.store.extend(&headers)?;
+            height = end_height;
+        }
+        Ok(height)
+    }
+
+    /// Rewinds the store to its highest header on the node's chain, setting the checkpoint if the
+    /// store has none, and returns the height of that header
+    async fn rewind_to_node_chain(&self, node_height: u64) -> Result<u64> {
+        let checkpoint = self.store.header(self.start_height)?;
+        let checkpoint_valid = checkpoint.is_some_and(|header| {
+            self.start_hash
+                .is_none_or(|hash| header.block_hash() == hash)
+        });
+        let tip_height = match self.store.tip()? {
+            Some((tip_height, _)) if checkpoint_valid => tip_height,
+            _ => {
+                let header = self.bitcoin.block_header(self.start_height).await?;
+                if let Some(hash) = self.start_hash {
+                    if header.block_hash() != hash {
+                        anyhow::bail!(
+                            "Bitcoin node has block {} at height {}, expected {}",
+                            header.block_hash(),
+                            self.start_height,
+                            hash
+                        );
+                    }
+                }
+                self.store.set_checkpoint(self.start_height, &header)?;
+                tracing::info!(
+                    "Syncing block headers from block {} at height {}",
+                    header.block_hash(),
+                    self.start_height
+                );
+                return Ok(self.start_height);
+            }
+        };
+
+        let mut height = tip_height.min(node_height);
+        loop {
+            let stored = self
+                .store
+                .header(height)?
+                .with_context(|| format!("Header store has no header at height {}", height))?;
+            if self.bitcoin.block_header(height).await? == stored {
+                break;
+            }
+            if height <= self.start_height {
+                anyhow::bail!(
+                    "Checkpoint block {} is not on the Bitcoin node's chain",
+                    stored.block_hash()
+                );
+            }
+            height -= 1;
+        }
+        let rewound = self.store.rewind(height)?;
+        if rewound > 0 {
+            tracing::warn!(
+                "Rewound {} block headers reorged away by the Bitcoin node, back to height {}",
+                rewound,
+                height
+            );
+        }
+        Ok(height)
+    }
+
+    /// Syncs every interval, forever
+    pub async fn run(self) {
+        let mut ticker = tokio::time::interval(self.interval);
+        loop {
+            ticker.tick().await;
+            match self.sync().await {
+                Ok(height) => tracing::debug!("Block headers synced to height {}", height),
+                Err(e) => tracing::error!("Failed to sync block headers: {:#}", e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use bitcoin::block::Version;
+    use bitcoin::hashes::Hash;
+    use bitcoin::{CompactTarget, TxMerkleNode};
+
+    /// Mines a regtest header on `prev_blockhash`, distinguished from its siblings by `time`
+    pub(crate) fn mine_header(
+        prev_blockhash: BlockHash,
+        merkle_root: TxMerkleNode,
+        time: u32,
+    ) -> Header {
+        let mut header = Header {
+            version: Version::TWO,
+            prev_blockhash,
+            merkle_root,
+            time,
+            bits: CompactTarget::from_consensus(0x207fffff),
+            nonce: 0,
+        };
+        while header.validate_pow(header.target()).is_err() {
+            header.nonce += 1;
+        }
+        header
+    }
+
+    pub(crate) fn merkle_root(txids: &[Txid]) -> TxMerkleNode {
+        let root = bitcoin::merkle_tree::calculate_root(txids.iter().copied()).unwrap();
+        TxMerkleNode::from_raw_hash(root.to_raw_hash())
+    }
+
+    /// Returns a serialized Merkle block proving the block of `header` with `txids` includes
+    /// `txid`
+    pub(crate) fn merkle_proof(header: &Header, txids: &[Txid], txid: Txid) -> Vec<u8> {
+        bitcoin::consensus::serialize(&MerkleBlock::from_header_txids_with_predicate(
+            header,
+            txids,
+            |candidate| *candidate == txid,
+        ))
+    }
+
+    fn store() -> Result<HeaderStore> {
+        Ok(HeaderStore::new(
+            Database::new(rusqlite::Connection::open_in_memory()?)?,
+            Network::Regtest,
+        ))
+    }
+
+    fn chain(prev_blockhash: BlockHash, len: u32) -> Vec<Header> {
+        let mut headers: Vec<Header> = Vec::new();
+        for idx in 0..len {
+            let prev = headers.last().map_or(prev_blockhash, Header::block_hash);
+            headers.push(mine_header(prev, TxMerkleNode::all_zeros(), idx));
+        }
+        headers
+    }
+
+    #[test]
+    fn test_validates_headers() -> Result<()> {
+        let store = store()?;
+        let headers = chain(BlockHash::all_zeros(), 4);
+        assert!(store.extend(&headers[1..]).is_err());
+        store.set_checkpoint(10, &headers[0])?;
+
+        // Headers must link to the tip
+        assert!(store.extend(&headers[2..]).is_err());
+        let mut invalid = headers[1];
+        invalid.nonce += 1;
+        while invalid.validate_pow(invalid.target()).is_ok() {
+            invalid.nonce += 1;
+        }
+        assert!(store.extend(&[invalid]).is_err());
+        assert_eq!(store.tip()?.unwrap().0, 10);
+
+        store.extend(&headers[1..])?;
+        assert_eq!(store.tip()?, Some((13, headers[3])));
+
+        assert_eq!(store.rewind(11)?, 2);
+        assert_eq!(store.tip()?, Some((11, headers[1])));
+        Ok(())
+    }
+
+    #[test]
+    fn test_checks_difficulty_changes() -> Result<()> {
+        let mut store = store()?;
+        store.params.allow_min_difficulty_blocks = false;
+        store.params.no_pow_retargeting = false;
+        let mut headers = chain(BlockHash::all_zeros(), 2);
+        store.set_checkpoint(10, &headers[0])?;
+
+        // Blocks between retargets keep the difficulty of their parent
+        headers[1].bits = CompactTarget::from_consensus(0x203fffff);
+        while headers[1].validate_pow(headers[1].target()).is_err() {
+            headers[1].nonce += 1;
+        }
+        assert!(store.extend(&headers[1..]).is_err());
+        assert!(store
+            .check_difficulty(2016, &headers[0], &headers[1])
+            .is_ok());
+        headers[1].bits = CompactTarget::from_consensus(0x1f7fffff);
+        assert!(store
+            .check_difficulty(2016, &headers[0], &headers[1])
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_proves_confirmations() -> Result<()> {
+        let store = store()?;
+        let txids: Vec<Txid> = (0..3u8).map(|idx| Txid::hash(&[idx])).collect();
+        let checkpoint = mine_header(BlockHash::all_zeros(), TxMerkleNode::all_zeros(), 0);
+        let block = mine_header(checkpoint.block_hash(), merkle_root(&txids), 1);
+        let proof = merkle_proof(&block, &txids, txids[1]);
+
+        // The block isn't stored yet
+        assert_eq!(store.confirmations(&txids[1], &proof)?, None);
+
+        store.set_checkpoint(100, &checkpoint)?;
+        store.extend(&[block])?;
+        store.extend(&chain(block.block_hash(), 2))?;
+        assert_eq!(store.confirmations(&txids[1], &proof)?, Some(3));
+
+        // The proof only covers the transaction it was made for
+        assert!(store.confirmations(&txids[0], &proof).is_err());
+        // A hash of the proof, past the 80-byte header and transaction count
+        let mut forged = proof.clone();
+        forged[90] ^= 1;
+        assert!(store.confirmations(&txids[1], &forged).is_err());
+        Ok(())
+    }
+}
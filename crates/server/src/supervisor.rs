@@ -0,0 +1,200 @@
+//! Runtime supervision of the database. Fatal SQLite errors take the sentinel out of service while
+//! the database is reopened, instead of leaving every request failing until an external restart.
+
+use crate::db::{is_fatal_error, Database, DatabaseKey};
+use crate::service::HealthReporter;
+use anyhow::Result;
+use rusqlite::OpenFlags;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Database the sentinel is serving from, as of the supervisor's last check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DatabaseState {
+    /// The database file is readable
+    Healthy,
+    /// The database failed and couldn't be reopened, status queries are answered from a
+    /// read-only snapshot
+    Fallback,
+    /// The database failed and neither it nor a snapshot could be opened
+    Failed,
+}
+
+/// Periodically probes the database. On a fatal error it flips health to `NOT_SERVING` and reopens
+/// the database file, falling back to a read-only snapshot if one is configured, until the
+/// database is readable again.
+pub struct DatabaseSupervisor {
+    db: Database,
+    db_path: PathBuf,
+    fallback_path: Option<PathBuf>,
+    health: HealthReporter,
+    interval: Duration,
+    state: DatabaseState,
+}
+
+fn open(path: &Path, flags: OpenFlags, key: Option<&DatabaseKey>) -> Result<Database> {
+    let db = Database::open(
+        path,
+        flags | OpenFlags::SQLITE_OPEN_FULL_MUTEX,
+        key.cloned(),
+    )?;
+    db.probe()?;
+    Ok(db)
+}
+
+impl DatabaseSupervisor {
+    pub fn new(
+        db: Database,
+        db_path: impl Into<PathBuf>,
+        health: HealthReporter,
+        interval: Duration,
+    ) -> Self {
+        Self {
+            db,
+            db_path: db_path.into(),
+            fallback_path: None,
+            health,
+            interval,
+            state: DatabaseState::Healthy,
+        }
+    }
+
+    /// Serves status queries from the read-only snapshot at `path` while the database can't be
+    /// reopened
+    pub fn with_fallback(mut self, path: impl Into<PathBuf>) -> Self {
+        self.fallback_path = Some(path.into());
+        self
+    }
+
+    pub fn state(&self) -> DatabaseState {
+        self.state
+    }
+
+    /// Probes the database, or retries recovering it if an earlier check couldn't, and updates
+    /// the health status. Returns the resulting state.
+    pub fn check(&mut self) -> DatabaseState {
+        if self.state == DatabaseState::Healthy {
+            match self.db.probe() {
+                Ok(()) => return self.state,
+                Err(e) if !is_fatal_error(&e) => {
+                    tracing::warn!("Database probe failed: {:#}", e);
+                    return self.state;
+                }
+                Err(e) => {
+                    tracing::error!("Database failed, marking the sentinel NOT_SERVING: {:#}", e);
+                    self.health.set_serving(false);
+                }
+            }
+        }
+
+        self.state = self.recover();
+        self.health
+            .set_serving(self.state == DatabaseState::Healthy);
+        self.state
+    }
+
+    fn recover(&self) -> DatabaseState {
+        // Without SQLITE_OPEN_CREATE, so a deleted database file isn't silently replaced by an
+        // empty one
+        match open(
+            &self.db_path,
+            OpenFlags::SQLITE_OPEN_READ_WRITE,
+            self.db.key(),
+        )
+        .and_then(|db| self.db.replace_connection(db))
+        {
+            Ok(()) => {
+                tracing::info!("Reopened database {}", self.db_path.display());
+                return DatabaseState::Healthy;
+            }
+            Err(e) => tracing::error!(
+                "Failed to reopen database {}: {:#}",
+                self.db_path.display(),
+                e
+            ),
+        }
+
+        let Some(fallback_path) = &self.fallback_path else {
+            return DatabaseState::Failed;
+        };
+        if self.state == DatabaseState::Fallback {
+            return DatabaseState::Fallback;
+        }
+        // Snapshots are written by the backup scheduler, encrypted with the same key
+        match open(
+            fallback_path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY,
+            self.db.key(),
+        )
+        .and_then(|db| self.db.replace_connection(db))
+        {
+            Ok(()) => {
+                tracing::warn!(
+                    "Serving status queries from read-only snapshot {}",
+                    fallback_path.display()
+                );
+                DatabaseState::Fallback
+            }
+            Err(e) => {
+                tracing::error!(
+                    "Failed to open read-only snapshot {}: {:#}",
+                    fallback_path.display(),
+                    e
+                );
+                DatabaseState::Failed
+            }
+        }
+    }
+
+    /// Checks the database every interval, forever
+    pub async fn run(mut self) {
+        let mut ticker = tokio::time::interval(self.interval);
+        // The first tick completes immediately; the database was just checked at startup
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            self.check();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_recovers_from_corrupted_database() -> Result<()> {
+        let dir =
+            std::env::temp_dir().join(format!("sova-sentinel-supervisor-{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        let db_path = dir.join("slot_locks.db");
+        let snapshot_path = dir.join("snapshot.db");
+
+        let db = open(
+            &db_path,
+            OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE,
+            None,
+        )?;
+        fs::copy(&db_path, &snapshot_path)?;
+        let health = HealthReporter::default();
+        let mut supervisor =
+            DatabaseSupervisor::new(db.clone(), &db_path, health.clone(), Duration::from_secs(1))
+                .with_fallback(&snapshot_path);
+        assert_eq!(supervisor.check(), DatabaseState::Healthy);
+
+        fs::write(&db_path, vec![0xab; 4096])?;
+        assert!(is_fatal_error(&db.probe().unwrap_err()));
+        assert_eq!(supervisor.check(), DatabaseState::Fallback);
+        assert!(!health.is_serving());
+        db.probe()?;
+
+        fs::copy(&snapshot_path, &db_path)?;
+        assert_eq!(supervisor.check(), DatabaseState::Healthy);
+        assert!(health.is_serving());
+        db.probe()?;
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,1008 @@
+//! Programmable test doubles for code that talks to a sentinel, with the semantics the server
+//! uses, and [`TestServer`], which serves them over gRPC. Enabled with the `test-util` feature.
+
+use crate::config::IdentityConfig;
+use crate::db::{Database, LockedSlot, RevertDelivery, SlotInsertData, UnlockReason};
+use crate::service::{
+    slot_lock_server, AuthLayer, AuthToken, BitcoinRpcError, BitcoinRpcServiceAPI, ChainInfo,
+    DoubleSpendStatus, IdentityLayer, MempoolStatus, SlotLockServiceImpl, REQUEST_TIMEOUT,
+};
+use anyhow::Result;
+use bitcoin::Network;
+use sova_sentinel_client::SlotLockClient;
+use sova_sentinel_proto::request_id::REQUEST_ID_KEY;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tonic::transport::server::TcpIncoming;
+use tonic::transport::{ClientTlsConfig, Server, ServerTlsConfig};
+use tower::ServiceBuilder;
+use tower_http::compression::CompressionLayer;
+use tower_http::request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer};
+
+/// Confirmations after which [`MockBitcoinService`] counts a transaction as confirmed by default
+pub const DEFAULT_CONFIRMATION_THRESHOLD: u32 = 6;
+
+/// Bitcoin blocks after which a [`TestServer`] reverts a lock by default, the sentinel's default
+pub const DEFAULT_REVERT_THRESHOLD: u32 = 18;
+
+/// Failure returned by the next calls to a [`MockBitcoinService`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MockFailure {
+    /// The node could not be reached; reported as `UNAVAILABLE` with a retry hint
+    Unreachable,
+    /// The circuit breaker is open; reported as `UNAVAILABLE` with the remaining cooldown
+    CircuitOpen(Duration),
+    /// The call ran out of its time budget; reported as `DEADLINE_EXCEEDED`
+    BudgetExceeded(Duration),
+    /// Any other RPC error; reported as `INTERNAL`
+    Rpc(String),
+}
+
+impl MockFailure {
+    fn into_error(self) -> anyhow::Error {
+        match self {
+            Self::Unreachable => BitcoinRpcError::BitcoinNodeUnreachable { attempts: 1 }.into(),
+            Self::CircuitOpen(retry_after) => BitcoinRpcError::CircuitOpen { retry_after }.into(),
+            Self::BudgetExceeded(budget) => BitcoinRpcError::BudgetExceeded { budget }.into(),
+            Self::Rpc(message) => anyhow::anyhow!(message),
+        }
+    }
+}
+
+#[derive(Default)]
+struct MockBitcoinState {
+    confirmations: HashMap<String, u32>,
+    double_spends: HashMap<String, DoubleSpendStatus>,
+    mempool: HashMap<String, MempoolStatus>,
+    broadcasts: Vec<Vec<u8>>,
+    failures: Vec<MockFailure>,
+    latency: Duration,
+}
+
+/// Bitcoin node double whose confirmations, mempool statuses, double-spends and failures are set
+/// by the test. Clones share their state, so a test can keep a handle after passing one to the
+/// service.
+#[derive(Clone)]
+pub struct MockBitcoinService {
+    confirmation_threshold: u32,
+    chain_info: ChainInfo,
+    state: Arc<Mutex<MockBitcoinState>>,
+}
+
+impl Default for MockBitcoinService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockBitcoinService {
+    /// A regtest node at height 150 that knows no transactions
+    pub fn new() -> Self {
+        Self {
+            confirmation_threshold: DEFAULT_CONFIRMATION_THRESHOLD,
+            chain_info: ChainInfo {
+                network: Network::Regtest,
+                tip_height: 150,
+            },
+            state: Arc::new(Mutex::new(MockBitcoinState::default())),
+        }
+    }
+
+    pub fn with_confirmation_threshold(mut self, confirmation_threshold: u32) -> Self {
+        self.confirmation_threshold = confirmation_threshold;
+        self
+    }
+
+    pub fn with_chain_info(mut self, chain_info: ChainInfo) -> Self {
+        self.chain_info = chain_info;
+        self
+    }
+
+    /// Sets the number of confirmations reported for a transaction
+    pub fn set_confirmations(&self, txid: &str, confirmations: u32) {
+        let mut state = self.state.lock().unwrap();
+        state.confirmations.insert(txid.to_string(), confirmations);
+    }
+
+    /// Gives a transaction exactly enough confirmations to count as confirmed
+    pub fn add_confirmed_tx(&self, txid: &str) {
+        self.set_confirmations(txid, self.confirmation_threshold);
+    }
+
+    /// Sets the double-spend status reported for a transaction
+    pub fn set_double_spend(&self, txid: &str, status: DoubleSpendStatus) {
+        let mut state = self.state.lock().unwrap();
+        state.double_spends.insert(txid.to_string(), status);
+    }
+
+    /// Sets the mempool status reported for a transaction, [`MempoolStatus::Unseen`] by default
+    pub fn set_mempool_status(&self, txid: &str, status: MempoolStatus) {
+        let mut state = self.state.lock().unwrap();
+        state.mempool.insert(txid.to_string(), status);
+    }
+
+    /// Makes the next `count` calls fail with `failure`, after any failures already queued
+    pub fn fail_next_calls(&self, count: usize, failure: MockFailure) {
+        let mut state = self.state.lock().unwrap();
+        state.failures.extend(std::iter::repeat_n(failure, count));
+    }
+
+    /// Delays every call by `latency`, e.g. to test deadlines of calls that query the node
+    pub fn set_latency(&self, latency: Duration) {
+        self.state.lock().unwrap().latency = latency;
+    }
+
+    /// Returns the raw transactions broadcast so far, oldest first
+    pub fn broadcasts(&self) -> Vec<Vec<u8>> {
+        self.state.lock().unwrap().broadcasts.clone()
+    }
+
+    /// Waits out the latency, then fails with the next queued failure, if any
+    async fn respond(&self) -> Result<()> {
+        let latency = self.state.lock().unwrap().latency;
+        if !latency.is_zero() {
+            tokio::time::sleep(latency).await;
+        }
+        let mut state = self.state.lock().unwrap();
+        if state.failures.is_empty() {
+            return Ok(());
+        }
+        Err(state.failures.remove(0).into_error())
+    }
+}
+
+#[tonic::async_trait]
+impl BitcoinRpcServiceAPI for MockBitcoinService {
+    async fn get_confirmations(&self, txid: &str) -> Result<u32> {
+        self.respond().await?;
+        let state = self.state.lock().unwrap();
+        Ok(state.confirmations.get(txid).copied().unwrap_or(0))
+    }
+
+    fn confirmation_threshold(&self) -> u32 {
+        self.confirmation_threshold
+    }
+
+    async fn mempool_status(&self, txid: &str) -> Result<MempoolStatus> {
+        self.respond().await?;
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .mempool
+            .get(txid)
+            .copied()
+            .unwrap_or(MempoolStatus::Unseen))
+    }
+
+    async fn check_double_spend(&self, txid: &str) -> Result<DoubleSpendStatus> {
+        self.respond().await?;
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .double_spends
+            .get(txid)
+            .copied()
+            .unwrap_or(DoubleSpendStatus::None))
+    }
+
+    async fn broadcast_transaction(&self, raw_tx: &[u8]) -> Result<()> {
+        self.respond().await?;
+        self.state.lock().unwrap().broadcasts.push(raw_tx.to_vec());
+        Ok(())
+    }
+
+    async fn chain_info(&self) -> Result<ChainInfo> {
+        self.respond().await?;
+        Ok(self.chain_info)
+    }
+}
+
+/// In-memory slot store with the server's schema, for seeding locks and injecting database
+/// failures. Pass [`MockSlotStore::database`] to the service under test.
+#[derive(Clone)]
+pub struct MockSlotStore {
+    db: Database,
+}
+
+impl MockSlotStore {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            db: Database::new(rusqlite::Connection::open_in_memory()?)?,
+        })
+    }
+
+    /// Returns a handle to the store's database
+    pub fn database(&self) -> Database {
+        self.db.clone()
+    }
+
+    /// Inserts active locks as if they were locked through `BatchLockSlot`, returning whether each
+    /// slot was newly locked
+    pub fn lock(&self, slots: &[SlotInsertData]) -> Result<Vec<bool>> {
+        self.db
+            .with_transaction(|transaction| self.db.batch_insert_slot_locks(transaction, slots))
+    }
+
+    /// Unlocks a slot at `end_block` for the given reason
+    pub fn unlock(
+        &self,
+        contract_address: &str,
+        slot_index: &[u8],
+        end_block: u64,
+        reason: UnlockReason,
+    ) -> Result<()> {
+        self.db
+            .unlock_slot("", contract_address, slot_index, end_block, reason)
+    }
+
+    /// Returns the lock of a slot visible at `current_block`
+    pub fn slot(
+        &self,
+        contract_address: &str,
+        slot_index: &[u8],
+        current_block: u64,
+    ) -> Result<Option<LockedSlot>> {
+        self.db
+            .get_slot("", contract_address, slot_index, current_block)
+    }
+
+    /// Returns the reverts queued for delivery to a revert executor
+    pub fn pending_reverts(&self) -> Result<Vec<RevertDelivery>> {
+        self.db.pending_revert_deliveries(usize::MAX)
+    }
+
+    /// Makes every write to the slot locks fail with `message` until cleared, e.g. to test how a
+    /// client handles a lock or status query that can't be recorded
+    pub fn fail_writes(&self, message: &str) -> Result<()> {
+        let message = message.replace('\'', "''");
+        self.db.with_transaction(|transaction| {
+            for (name, event) in [
+                ("mock_fail_insert", "INSERT"),
+                ("mock_fail_update", "UPDATE"),
+            ] {
+                transaction.execute_batch(&format!(
+                    "CREATE TEMP TRIGGER IF NOT EXISTS {name} BEFORE {event} ON slot_locks
+                     BEGIN SELECT RAISE(ABORT, '{message}'); END;"
+                ))?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Lets writes succeed again after [`MockSlotStore::fail_writes`]
+    pub fn clear_failures(&self) -> Result<()> {
+        self.db.with_transaction(|transaction| {
+            transaction.execute_batch(
+                "DROP TRIGGER IF EXISTS temp.mock_fail_insert;
+                 DROP TRIGGER IF EXISTS temp.mock_fail_update;",
+            )?;
+            Ok(())
+        })
+    }
+}
+
+/// Slot lock service served by a [`TestServer`]
+pub type TestSlotLockService = SlotLockServiceImpl<MockBitcoinService>;
+
+type ConfigureFn = dyn FnOnce(TestSlotLockService) -> TestSlotLockService + Send;
+
+/// Options of a [`TestServer`], built by [`TestServer::builder`]
+pub struct TestServerBuilder {
+    bitcoin: MockBitcoinService,
+    revert_threshold: u32,
+    timeout: Duration,
+    auth_token: Option<String>,
+    tls: Option<(ServerTlsConfig, ClientTlsConfig)>,
+    identity: Option<IdentityConfig>,
+    configure: Option<Box<ConfigureFn>>,
+}
+
+impl TestServerBuilder {
+    /// Sets the Bitcoin node double, e.g. one with another confirmation threshold
+    pub fn with_bitcoin(mut self, bitcoin: MockBitcoinService) -> Self {
+        self.bitcoin = bitcoin;
+        self
+    }
+
+    pub fn with_revert_threshold(mut self, revert_threshold: u32) -> Self {
+        self.revert_threshold = revert_threshold;
+        self
+    }
+
+    /// Sets the time after which the server cancels a call, [`REQUEST_TIMEOUT`] by default
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Requires calls to carry `token` as a bearer token. The connected client sends it.
+    pub fn with_auth_token(mut self, token: &str) -> Self {
+        self.auth_token = Some(token.to_string());
+        self
+    }
+
+    /// Serves TLS with `server`. The connected client connects with `client`.
+    pub fn with_tls(mut self, server: ServerTlsConfig, client: ClientTlsConfig) -> Self {
+        self.tls = Some((server, client));
+        self
+    }
+
+    /// Identifies callers and limits their active locks with `identity`
+    pub fn with_identity(mut self, identity: IdentityConfig) -> Self {
+        self.identity = Some(identity);
+        self
+    }
+
+    /// Configures the slot lock service before it's served, e.g. to make it read-only
+    pub fn with_service(
+        mut self,
+        configure: impl FnOnce(TestSlotLockService) -> TestSlotLockService + Send + 'static,
+    ) -> Self {
+        self.configure = Some(Box::new(configure));
+        self
+    }
+
+    /// Starts serving on a free port of the loopback interface and connects a client
+    pub async fn start(self) -> Result<TestServer> {
+        let store = MockSlotStore::new()?;
+        let service = SlotLockServiceImpl::new(
+            store.database(),
+            self.bitcoin.clone(),
+            self.revert_threshold,
+        );
+        let service = match &self.identity {
+            Some(identity) => service.with_active_lock_limits(identity.clone()),
+            None => service,
+        };
+        let service = match self.configure {
+            Some(configure) => configure(service),
+            None => service,
+        };
+        let auth_token = self.auth_token.as_deref().map(AuthToken::new).transpose()?;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let incoming = TcpIncoming::from_listener(listener, true, None)
+            .map_err(|e| anyhow::anyhow!("Failed to listen on {}: {}", addr, e))?;
+        // The transport and middleware of the sentinel's listeners, without the layers that only
+        // log, count or rate limit calls
+        let middleware = ServiceBuilder::new()
+            .layer(CompressionLayer::new())
+            .layer(SetRequestIdLayer::new(
+                hyper::header::HeaderName::from_static(REQUEST_ID_KEY),
+                MakeRequestUuid,
+            ))
+            .layer(PropagateRequestIdLayer::new(
+                hyper::header::HeaderName::from_static(REQUEST_ID_KEY),
+            ))
+            .option_layer(auth_token.map(AuthLayer::new))
+            .option_layer(self.identity.map(IdentityLayer::new));
+        let (shutdown, stopped) = oneshot::channel::<()>();
+        let (server, client_tls) = match self.tls {
+            Some((server, client)) => (Server::builder().tls_config(server)?, Some(client)),
+            None => (Server::builder(), None),
+        };
+        let scheme = if client_tls.is_some() {
+            "https"
+        } else {
+            "http"
+        };
+        let server = tokio::spawn(
+            server
+                .timeout(self.timeout)
+                .layer(middleware.into_inner())
+                .add_service(slot_lock_server(service))
+                .serve_with_incoming_shutdown(incoming, async {
+                    stopped.await.ok();
+                }),
+        );
+
+        let client = SlotLockClient::builder(format!("{}://{}", scheme, addr));
+        let client = match client_tls {
+            Some(tls) => client.with_tls(tls),
+            None => client,
+        }
+        .connect()
+        .await?;
+        let client = match &self.auth_token {
+            Some(token) => client.with_auth_token(token)?,
+            None => client,
+        };
+        Ok(TestServer {
+            addr,
+            scheme,
+            bitcoin: self.bitcoin,
+            store,
+            client,
+            shutdown: Some(shutdown),
+            server,
+        })
+    }
+}
+
+/// Sentinel serving the doubles over gRPC on a local port until dropped, for end-to-end tests
+/// of the transport, middleware and client rather than of the service alone
+pub struct TestServer {
+    addr: SocketAddr,
+    scheme: &'static str,
+    bitcoin: MockBitcoinService,
+    store: MockSlotStore,
+    client: SlotLockClient,
+    shutdown: Option<oneshot::Sender<()>>,
+    server: JoinHandle<Result<(), tonic::transport::Error>>,
+}
+
+impl TestServer {
+    pub fn builder() -> TestServerBuilder {
+        TestServerBuilder {
+            bitcoin: MockBitcoinService::new(),
+            revert_threshold: DEFAULT_REVERT_THRESHOLD,
+            timeout: REQUEST_TIMEOUT,
+            auth_token: None,
+            tls: None,
+            identity: None,
+            configure: None,
+        }
+    }
+
+    /// Starts a server with the default options
+    pub async fn start() -> Result<Self> {
+        Self::builder().start().await
+    }
+
+    /// Returns the URL to connect other clients to, e.g. `http://127.0.0.1:41234`, or
+    /// `https://` with TLS
+    pub fn endpoint(&self) -> String {
+        format!("{}://{}", self.scheme, self.addr)
+    }
+
+    /// Returns the client connected when the server started
+    pub fn client(&self) -> &SlotLockClient {
+        &self.client
+    }
+
+    pub fn bitcoin(&self) -> &MockBitcoinService {
+        &self.bitcoin
+    }
+
+    pub fn store(&self) -> &MockSlotStore {
+        &self.store
+    }
+
+    /// Stops serving and waits for the server to shut down
+    pub async fn shutdown(mut self) -> Result<()> {
+        if let Some(shutdown) = self.shutdown.take() {
+            shutdown.send(()).ok();
+        }
+        (&mut self.server).await??;
+        Ok(())
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            shutdown.send(()).ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::{get_slot_status_response, slot_lock_status, SlotData};
+    use sova_sentinel_client::RetryPolicy;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tonic::codec::CompressionEncoding;
+
+    /// Client of `server` counting the attempts of its calls
+    async fn counting_client(
+        server: &TestServer,
+        retry_policy: RetryPolicy,
+    ) -> Result<(SlotLockClient, Arc<AtomicUsize>)> {
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let counter = attempts.clone();
+        let client = SlotLockClient::builder(server.endpoint())
+            .with_retry_policy(retry_policy)
+            .with_call_hook(move |_| {
+                counter.fetch_add(1, Ordering::Relaxed);
+            })
+            .connect()
+            .await?;
+        Ok((client, attempts))
+    }
+
+    fn slot(slot_index: u8) -> SlotInsertData {
+        SlotInsertData {
+            namespace: String::new(),
+            contract_address: "0x123".to_string(),
+            start_block: 100,
+            btc_block: 200,
+            slot_index: vec![slot_index],
+            btc_txid: "txid1".to_string(),
+            revert_value: vec![],
+            current_value: vec![],
+            metadata: Vec::new(),
+            labels: Default::default(),
+            alt_btc_txids: Vec::new(),
+            required_confirmed_txids: 1,
+            locked_by: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mock_bitcoin_failures() -> Result<()> {
+        let btc = MockBitcoinService::new();
+        btc.add_confirmed_tx("txid1");
+        btc.fail_next_calls(1, MockFailure::Unreachable);
+
+        let error = btc.get_confirmations("txid1").await.unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<BitcoinRpcError>(),
+            Some(BitcoinRpcError::BitcoinNodeUnreachable { .. })
+        ));
+        assert!(btc.is_tx_confirmed("txid1").await?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_mock_slot_store_write_failures() -> Result<()> {
+        let store = MockSlotStore::new()?;
+        store.lock(&[slot(1)])?;
+
+        store.fail_writes("disk full")?;
+        let error = store.lock(&[slot(2)]).unwrap_err();
+        assert!(error.to_string().contains("disk full"));
+        assert!(store
+            .unlock("0x123", &[1], 150, UnlockReason::Manual)
+            .is_err());
+
+        store.clear_failures()?;
+        store.unlock("0x123", &[1], 150, UnlockReason::Manual)?;
+        assert_eq!(
+            store.slot("0x123", &[1], 150)?.unwrap().unlock_reason,
+            Some(UnlockReason::Manual)
+        );
+        Ok(())
+    }
+
+    fn slot_data() -> SlotData {
+        SlotData {
+            contract_address: "0x123".to_string(),
+            slot_index: vec![1],
+            revert_value: vec![1],
+            current_value: vec![2],
+            btc_txid: "ab".repeat(32),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_server_serves_through_middleware() -> Result<(), Box<dyn std::error::Error>> {
+        let server = TestServer::builder()
+            .with_auth_token("secret")
+            .start()
+            .await?;
+
+        let response = server.client().lock_slot(100, 100, slot_data()).await?;
+        assert!(response.metadata().get(REQUEST_ID_KEY).is_some());
+
+        // Compressed calls are accepted, and calls without the token rejected
+        let compressed = SlotLockClient::builder(server.endpoint())
+            .with_compression(CompressionEncoding::Gzip)
+            .connect()
+            .await?
+            .with_auth_token("secret")?;
+        compressed
+            .get_slot_status(101, 100, "0x123".to_string(), vec![1])
+            .await?;
+        let anonymous = SlotLockClient::connect(server.endpoint()).await?;
+        let error = anonymous
+            .get_slot_status(101, 100, "0x123".to_string(), vec![1])
+            .await
+            .unwrap_err();
+        assert_eq!(error.code(), tonic::Code::Unauthenticated);
+
+        server.shutdown().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_server_serves_tls() -> Result<(), Box<dyn std::error::Error>> {
+        use sova_sentinel_client::{Certificate, Identity};
+
+        let testdata = concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/tls");
+        let listener = crate::config::ListenerConfig {
+            tls_cert: Some(format!("{}/server.pem", testdata)),
+            tls_key: Some(format!("{}/server.key", testdata)),
+            client_ca: Some(format!("{}/ca.pem", testdata)),
+            ..crate::config::ListenerConfig::new("127.0.0.1:0")
+        };
+        let ca = Certificate::from_pem(std::fs::read(format!("{}/ca.pem", testdata))?);
+        let identity = Identity::from_pem(
+            std::fs::read(format!("{}/client.pem", testdata))?,
+            std::fs::read(format!("{}/client.key", testdata))?,
+        );
+        let server = TestServer::builder()
+            .with_tls(
+                listener.tls_config()?.unwrap(),
+                ClientTlsConfig::new()
+                    .ca_certificate(ca.clone())
+                    .identity(identity),
+            )
+            .with_identity(IdentityConfig {
+                api_key_header: "x-api-key".to_string(),
+                api_keys: HashMap::new(),
+                client_cn_header: None,
+                trusted_proxies: Vec::new(),
+                max_active_locks: None,
+                active_lock_limits: HashMap::from([("cn:sequencer".to_string(), 1)]),
+            })
+            .start()
+            .await?;
+        assert!(server.endpoint().starts_with("https://"));
+        server.client().lock_slot(100, 100, slot_data()).await?;
+
+        // The caller is identified by its certificate's common name
+        let status = server
+            .client()
+            .lock_slot(
+                100,
+                100,
+                SlotData {
+                    slot_index: vec![2],
+                    ..slot_data()
+                },
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+
+        // Clients without a certificate signed by the client CA are refused, when connecting or
+        // at their first call
+        let anonymous = SlotLockClient::builder(server.endpoint())
+            .with_tls(ClientTlsConfig::new().ca_certificate(ca))
+            .connect()
+            .await;
+        if let Ok(anonymous) = anonymous {
+            assert!(anonymous
+                .get_slot_status(101, 100, "0x123".to_string(), vec![1])
+                .await
+                .is_err());
+        }
+
+        server.shutdown().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_server_cancels_slow_calls() -> Result<(), Box<dyn std::error::Error>> {
+        let server = TestServer::builder()
+            .with_timeout(Duration::from_millis(50))
+            .start()
+            .await?;
+        server.client().lock_slot(100, 100, slot_data()).await?;
+
+        server.bitcoin().set_latency(Duration::from_secs(1));
+        let error = server
+            .client()
+            .get_slot_status(101, 100, "0x123".to_string(), vec![1])
+            .await
+            .unwrap_err();
+        assert_eq!(error.code(), tonic::Code::Cancelled);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_client_retries_status_queries() -> Result<(), Box<dyn std::error::Error>> {
+        let server = TestServer::start().await?;
+        server.client().lock_slot(100, 100, slot_data()).await?;
+        let policy = RetryPolicy::exponential(3, Duration::from_millis(1));
+        let (client, attempts) = counting_client(&server, policy).await?;
+
+        // The query is retried while the Bitcoin node is unavailable
+        server
+            .bitcoin()
+            .fail_next_calls(2, MockFailure::CircuitOpen(Duration::from_millis(1)));
+        client
+            .get_slot_status(101, 100, "0x123".to_string(), vec![1])
+            .await?;
+        assert_eq!(attempts.swap(0, Ordering::Relaxed), 3);
+
+        // Until the policy's attempts run out
+        server
+            .bitcoin()
+            .fail_next_calls(3, MockFailure::CircuitOpen(Duration::from_millis(1)));
+        let status = client
+            .get_slot_status(101, 100, "0x123".to_string(), vec![1])
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::Unavailable);
+        assert_eq!(attempts.swap(0, Ordering::Relaxed), 3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_client_waits_for_unlock() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::proto::SlotIdentifier;
+        use sova_sentinel_client::SlotLockClientError;
+        use std::time::Instant;
+
+        let server = TestServer::start().await?;
+        let locked = |idx: u8| SlotData {
+            slot_index: vec![idx],
+            ..slot_data()
+        };
+        server
+            .client()
+            .batch_lock_slot(100, 100, vec![locked(1), locked(2)])
+            .await?;
+        let slot = |idx: u8| SlotIdentifier {
+            contract_address: "0x123".to_string(),
+            slot_index: vec![idx],
+        };
+        let poll_interval = Duration::from_millis(10);
+        let deadline = || Instant::now() + Duration::from_millis(100);
+
+        // Slots still locked at the deadline are counted
+        let error = server
+            .client()
+            .wait_for_unlock(101, 100, slot(1), poll_interval, deadline())
+            .await
+            .unwrap_err();
+        assert!(matches!(error, SlotLockClientError::StillLocked(1)));
+        let error = server
+            .client()
+            .wait_for_unlock_batch(
+                101,
+                100,
+                vec![slot(1), slot(2), slot(3)],
+                poll_interval,
+                deadline(),
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(error, SlotLockClientError::StillLocked(2)));
+
+        // A query that hasn't returned by the deadline gives up with it
+        server.bitcoin().set_latency(Duration::from_secs(10));
+        let started = Instant::now();
+        let error = server
+            .client()
+            .wait_for_unlock(101, 100, slot(1), poll_interval, deadline())
+            .await
+            .unwrap_err();
+        assert!(matches!(error, SlotLockClientError::StillLocked(1)));
+        assert!(started.elapsed() < Duration::from_secs(5));
+        server.bitcoin().set_latency(Duration::ZERO);
+
+        // Once the lock's transaction confirms, the slots are unlocked
+        server.bitcoin().add_confirmed_tx(&"ab".repeat(32));
+        let status = server
+            .client()
+            .wait_for_unlock(101, 100, slot(1), poll_interval, deadline())
+            .await?;
+        assert_eq!(status.status(), get_slot_status_response::Status::Unlocked);
+        let statuses = server
+            .client()
+            .wait_for_unlock_batch(101, 100, vec![slot(1), slot(2)], poll_interval, deadline())
+            .await?;
+        assert_eq!(statuses.len(), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_client_locks_in_chunks() -> Result<(), Box<dyn std::error::Error>> {
+        use sova_sentinel_proto::batch_size::BatchSizeHints;
+
+        let server = TestServer::builder()
+            .with_service(|service| {
+                service.with_batch_size_hints(BatchSizeHints {
+                    preferred: 0,
+                    max: 40,
+                })
+            })
+            .start()
+            .await?;
+        let policy = RetryPolicy::exponential(3, Duration::from_millis(1));
+        let (client, attempts) = counting_client(&server, policy).await?;
+        let slots = |indexes: std::ops::Range<u16>| {
+            indexes
+                .map(|idx| SlotData {
+                    slot_index: idx.to_be_bytes().to_vec(),
+                    ..slot_data()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // The first chunk of the default size is rejected, and the batch resent in chunks of the
+        // server's maximum
+        let statuses = client
+            .batch_lock_slot_chunked(100, 100, slots(0..250))
+            .await?;
+        assert_eq!(statuses.len(), 250);
+        assert!(statuses
+            .iter()
+            .all(|status| status.status() == slot_lock_status::Status::Locked));
+        assert_eq!(attempts.swap(0, Ordering::Relaxed), 8);
+        assert_eq!(client.batch_size(), 40);
+
+        // Later batches are chunked at the maximum from the start
+        let statuses = client
+            .batch_lock_slot_chunked(100, 100, slots(250..330))
+            .await?;
+        assert_eq!(statuses.len(), 80);
+        assert_eq!(attempts.swap(0, Ordering::Relaxed), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_blocking_client() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::proto::{lock_slot_response, SlotIdentifier};
+        use sova_sentinel_client::{SlotLockBlockingClient, SlotLockClientError};
+        use std::time::Instant;
+
+        // The server runs on worker threads while the test thread blocks on calls
+        let runtime = tokio::runtime::Runtime::new()?;
+        let server = runtime.block_on(TestServer::start())?;
+        let client = SlotLockBlockingClient::connect(server.endpoint())?;
+
+        let response = client.lock_slot(100, 100, slot_data())?.into_inner();
+        assert_eq!(response.status(), lock_slot_response::Status::Locked);
+        let response = client
+            .get_slot_status(101, 100, "0x123".to_string(), vec![1])?
+            .into_inner();
+        assert_eq!(response.status(), get_slot_status_response::Status::Locked);
+
+        let slot = SlotIdentifier {
+            contract_address: "0x123".to_string(),
+            slot_index: vec![1],
+        };
+        let poll_interval = Duration::from_millis(10);
+        let error = client
+            .wait_for_unlock(
+                101,
+                100,
+                slot.clone(),
+                poll_interval,
+                Instant::now() + Duration::from_millis(50),
+            )
+            .unwrap_err();
+        assert!(matches!(error, SlotLockClientError::StillLocked(1)));
+        server.bitcoin().add_confirmed_tx(&"ab".repeat(32));
+        let response = client.wait_for_unlock(
+            101,
+            100,
+            slot,
+            poll_interval,
+            Instant::now() + Duration::from_secs(5),
+        )?;
+        assert_eq!(
+            response.status(),
+            get_slot_status_response::Status::Unlocked
+        );
+
+        // The client's connection only makes progress while it's blocked on a call, so it's
+        // closed before the server waits for its connections to end
+        drop(client);
+        runtime.block_on(server.shutdown())?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_client_locks_evm_slots() -> Result<(), Box<dyn std::error::Error>> {
+        use alloy_primitives::{address, U256};
+        use sova_sentinel_client::evm::{EvmSlot, EvmSlotLock};
+
+        let server = TestServer::start().await?;
+        let contract = address!("00000000000000000000000000000000000000Ab");
+        let slot = |index: u64| EvmSlot::new(contract, U256::from(index));
+        let lock = |index: u64| EvmSlotLock {
+            slot: slot(index),
+            revert_value: U256::from(1),
+            current_value: U256::from(2),
+            btc_txid: "ab".repeat(32),
+        };
+
+        let statuses = server
+            .client()
+            .batch_lock_evm_slots(100, 100, vec![lock(1), lock(2)])
+            .await?;
+        assert!(statuses
+            .iter()
+            .all(|status| status.status() == slot_lock_status::Status::Locked));
+
+        // The sentinel reports the slots as they were sent, so they decode back to the same slots
+        let response = server
+            .client()
+            .get_evm_slot_status(101, 100, slot(1))
+            .await?
+            .into_inner();
+        assert_eq!(response.status(), get_slot_status_response::Status::Locked);
+        let responses = server
+            .client()
+            .batch_get_evm_slot_status(101, 100, vec![slot(2), slot(3)])
+            .await?;
+        assert_eq!(
+            responses
+                .iter()
+                .map(|response| EvmSlot::parse(&response.contract_address, &response.slot_index))
+                .collect::<Vec<_>>(),
+            vec![Some(slot(2)), Some(slot(3))]
+        );
+        assert_eq!(
+            responses[1].status(),
+            get_slot_status_response::Status::Unlocked
+        );
+
+        let unlocked = server
+            .client()
+            .batch_unlock_evm_slots(101, 100, vec![slot(2), slot(1)])
+            .await?;
+        assert_eq!(unlocked, vec![slot(2), slot(1)]);
+        let response = server
+            .client()
+            .get_evm_slot_status(101, 100, slot(1))
+            .await?
+            .into_inner();
+        assert_eq!(
+            response.status(),
+            get_slot_status_response::Status::Unlocked
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_client_decodes_statuses() -> Result<(), Box<dyn std::error::Error>> {
+        use sova_sentinel_client::{LockStatus, SlotLockOutcome, SlotStatus, SlotStatusReport};
+
+        let server = TestServer::start().await?;
+        let client = server.client();
+        let outcome =
+            SlotLockOutcome::from(client.lock_slot(100, 100, slot_data()).await?.into_inner());
+        assert_eq!(outcome.status, LockStatus::Locked);
+        assert_eq!(outcome.contract_address, "0x123");
+
+        // A batch reports the slot already locked and the other one locked
+        let other = SlotData {
+            slot_index: vec![2],
+            ..slot_data()
+        };
+        let outcomes = client
+            .batch_lock_slot(100, 100, vec![slot_data(), other])
+            .await?
+            .into_inner()
+            .slots
+            .into_iter()
+            .map(|status| SlotLockOutcome::from(status).status)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            outcomes,
+            vec![LockStatus::AlreadyLocked, LockStatus::Locked]
+        );
+
+        let report = SlotStatusReport::from(
+            client
+                .get_slot_status(101, 100, "0x123".to_string(), vec![1])
+                .await?
+                .into_inner(),
+        );
+        assert_eq!(report.status, SlotStatus::Locked);
+        assert!(report.status.is_locked());
+
+        // Once the lock's transaction confirms, the slot is unlocked rather than reverted
+        server.bitcoin().add_confirmed_tx(&"ab".repeat(32));
+        let report = SlotStatusReport::from(
+            client
+                .get_slot_status(101, 100, "0x123".to_string(), vec![1])
+                .await?
+                .into_inner(),
+        );
+        assert_eq!(report.status, SlotStatus::Unlocked);
+        assert!(!report.status.is_locked() && !report.status.is_reverted());
+        Ok(())
+    }
+}